@@ -0,0 +1,127 @@
+//! This module provides functions for the cofactors and adjugates of 2D and 3D matrices.
+//!
+//! The adjugate of a matrix is the transpose of its matrix of cofactors, and satisfies
+//! `A * adj(A) = det(A) * I`. This means that the inverse of an invertible matrix is
+//! `adj(A) / det(A)`.
+
+use glam::{DMat2, DMat3, DVec2, DVec3};
+
+/// Get the cofactor of the given entry of a 2D matrix. The indices are 0-based.
+///
+/// Returns `None` if the indices are out of bounds.
+pub fn cofactor_2d(matrix: DMat2, row: usize, column: usize) -> Option<f64> {
+    if row >= 2 || column >= 2 {
+        return None;
+    }
+
+    // The minor of a 2D matrix is just the single entry not in the given row or column
+    let minor = matrix.col(1 - column)[1 - row];
+    Some(sign(row, column) * minor)
+}
+
+/// Get the cofactor of the given entry of a 3D matrix. The indices are 0-based.
+///
+/// Returns `None` if the indices are out of bounds.
+pub fn cofactor_3d(matrix: DMat3, row: usize, column: usize) -> Option<f64> {
+    if row >= 3 || column >= 3 {
+        return None;
+    }
+
+    let rows: Vec<usize> = (0..3).filter(|&r| r != row).collect();
+    let columns: Vec<usize> = (0..3).filter(|&c| c != column).collect();
+    let entry = |r: usize, c: usize| matrix.col(columns[c])[rows[r]];
+
+    let minor = DMat2::from_cols(
+        DVec2::new(entry(0, 0), entry(1, 0)),
+        DVec2::new(entry(0, 1), entry(1, 1)),
+    )
+    .determinant();
+
+    Some(sign(row, column) * minor)
+}
+
+/// Get the adjugate of a 2D matrix.
+pub fn adjugate_2d(matrix: DMat2) -> DMat2 {
+    // The adjugate is the transpose of the cofactor matrix, so column j of the adjugate is row j
+    // of the cofactor matrix
+    let cofactor = |row, column| cofactor_2d(matrix, row, column).unwrap();
+    DMat2::from_cols(
+        DVec2::new(cofactor(0, 0), cofactor(0, 1)),
+        DVec2::new(cofactor(1, 0), cofactor(1, 1)),
+    )
+}
+
+/// Get the adjugate of a 3D matrix.
+pub fn adjugate_3d(matrix: DMat3) -> DMat3 {
+    // The adjugate is the transpose of the cofactor matrix, so column j of the adjugate is row j
+    // of the cofactor matrix
+    let cofactor = |row, column| cofactor_3d(matrix, row, column).unwrap();
+    DMat3::from_cols(
+        DVec3::new(cofactor(0, 0), cofactor(0, 1), cofactor(0, 2)),
+        DVec3::new(cofactor(1, 0), cofactor(1, 1), cofactor(1, 2)),
+        DVec3::new(cofactor(2, 0), cofactor(2, 1), cofactor(2, 2)),
+    )
+}
+
+/// The sign of the cofactor in the given position, which follows a checkerboard pattern.
+fn sign(row: usize, column: usize) -> f64 {
+    if (row + column).is_multiple_of(2) {
+        1.
+    } else {
+        -1.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn cofactors() {
+        // [1 2; 3 4]
+        let m = DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., 4.));
+        assert_eq!(cofactor_2d(m, 0, 0), Some(4.));
+        assert_eq!(cofactor_2d(m, 0, 1), Some(-3.));
+        assert_eq!(cofactor_2d(m, 1, 0), Some(-2.));
+        assert_eq!(cofactor_2d(m, 1, 1), Some(1.));
+        assert_eq!(cofactor_2d(m, 2, 0), None);
+
+        // [1 2 3; 0 4 5; 1 0 6]
+        let n = DMat3::from_cols(
+            DVec3::new(1., 0., 1.),
+            DVec3::new(2., 4., 0.),
+            DVec3::new(3., 5., 6.),
+        );
+        assert_eq!(cofactor_3d(n, 0, 0), Some(24.));
+        assert_eq!(cofactor_3d(n, 0, 1), Some(5.));
+        assert_eq!(cofactor_3d(n, 1, 2), Some(2.));
+        assert_eq!(cofactor_3d(n, 2, 2), Some(4.));
+        assert_eq!(cofactor_3d(n, 0, 3), None);
+    }
+
+    #[test]
+    fn adjugates() {
+        let m = DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., 4.));
+        assert_eq!(
+            adjugate_2d(m),
+            DMat2::from_cols(DVec2::new(4., -3.), DVec2::new(-2., 1.))
+        );
+
+        for _ in 0..100 {
+            let m = rand::random::<DMat2>();
+            assert_relative_eq!(
+                m * adjugate_2d(m),
+                m.determinant() * DMat2::IDENTITY,
+                epsilon = 0.0000001
+            );
+
+            let n = rand::random::<DMat3>();
+            assert_relative_eq!(
+                n * adjugate_3d(n),
+                n.determinant() * DMat3::IDENTITY,
+                epsilon = 0.0000001
+            );
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! This module provides helpers for treating a 3D matrix as a 2D affine transform, using
+//! homogeneous coordinates.
+//!
+//! A 2D point `(x, y)` is written as the 3D vector `(x, y, 1)`. A 3D matrix whose bottom row is
+//! `[0 0 1]` then maps points to points, and its top-left 2×2 block acts as a linear map while
+//! its top-right column translates. That lets matrix multiplication express translation, which no
+//! 2D matrix can.
+
+use super::EPSILON;
+use glam::{DMat2, DMat3, DVec2, DVec3};
+
+/// Build the affine transform which translates by the given offset.
+///
+/// ```
+/// # use trinity::math::translation_2d;
+/// # use glam::{DVec2, DVec3};
+/// let matrix = translation_2d(DVec2::new(3., -2.));
+/// assert_eq!(matrix * DVec3::new(1., 1., 1.), DVec3::new(4., -1., 1.));
+/// ```
+pub fn translation_2d(offset: DVec2) -> DMat3 {
+    DMat3::from_translation(offset)
+}
+
+/// Build the affine transform which applies the linear map and then translates by the given
+/// offset.
+pub fn affine_2d(linear: DMat2, translation: DVec2) -> DMat3 {
+    DMat3::from_cols(
+        linear.x_axis.extend(0.),
+        linear.y_axis.extend(0.),
+        translation.extend(1.),
+    )
+}
+
+/// Split an affine transform into its linear part and its translation, or return `None` if the
+/// matrix isn't affine. See [`is_affine_2d`].
+pub fn split_affine_2d(matrix: DMat3) -> Option<(DMat2, DVec2)> {
+    is_affine_2d(matrix).then(|| {
+        (
+            DMat2::from_cols(matrix.x_axis.truncate(), matrix.y_axis.truncate()),
+            matrix.z_axis.truncate(),
+        )
+    })
+}
+
+/// Is this matrix a 2D affine transform, meaning that its bottom row is `[0 0 1]`?
+pub fn is_affine_2d(matrix: DMat3) -> bool {
+    snap_affine_2d(matrix, |x| x.abs() <= EPSILON).is_some()
+}
+
+/// Make the bottom row of this matrix exactly `[0 0 1]`, as long as `is_zero` says that it's
+/// already close enough, or return `None` if it isn't.
+///
+/// Floating point error can make the bottom row drift away from `[0 0 1]` after a few
+/// operations, so this puts it back.
+pub fn snap_affine_2d(matrix: DMat3, is_zero: impl Fn(f64) -> bool) -> Option<DMat3> {
+    let bottom_row = matrix.row(2);
+    (is_zero(bottom_row.x) && is_zero(bottom_row.y) && is_zero(bottom_row.z - 1.)).then(|| {
+        DMat3::from_cols(
+            matrix.x_axis.truncate().extend(0.),
+            matrix.y_axis.truncate().extend(0.),
+            matrix.z_axis.truncate().extend(1.),
+        )
+    })
+}
+
+/// Apply an affine transform to a 2D point, by writing it in homogeneous coordinates.
+pub fn transform_point_2d(matrix: DMat3, point: DVec2) -> DVec2 {
+    let DVec3 { x, y, z } = matrix * point.extend(1.);
+    DVec2::new(x, y) / z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn affine_2d_transforms() {
+        let rotation = DMat2::from_angle(1.);
+        let transform = affine_2d(rotation, DVec2::new(1., 2.));
+        assert!(is_affine_2d(transform));
+        assert_eq!(
+            split_affine_2d(transform),
+            Some((rotation, DVec2::new(1., 2.)))
+        );
+        assert_relative_eq!(
+            transform_point_2d(transform, DVec2::X),
+            DVec2::new(1. + 1f64.cos(), 2. + 1f64.sin()),
+            epsilon = 0.000000001
+        );
+
+        // Translations compose by adding their offsets
+        assert_relative_eq!(
+            translation_2d(DVec2::new(1., 2.)) * translation_2d(DVec2::new(-3., 5.)),
+            translation_2d(DVec2::new(-2., 7.))
+        );
+
+        // The inverse of an affine transform is affine too
+        assert!(is_affine_2d(transform.inverse()));
+        assert_eq!(transform.inverse().row(2), DVec3::Z);
+
+        let drifted = DMat3::from_cols_array(&[1., 0., 1e-12, 0., 1., 0., 0., 0., 1. + 1e-12]);
+        assert!(is_affine_2d(drifted));
+        assert_eq!(
+            snap_affine_2d(drifted, |x| x.abs() <= EPSILON).map(|matrix| matrix.row(2)),
+            Some(DVec3::Z)
+        );
+
+        assert!(!is_affine_2d(DMat3::IDENTITY * 2.));
+        assert_eq!(split_affine_2d(DMat3::ZERO), None);
+    }
+}
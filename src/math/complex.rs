@@ -0,0 +1,272 @@
+//! This module provides [`Complex`], a simple complex number type.
+
+use super::Eigenvalue;
+use std::{
+    fmt,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+/// A complex number `re + im * i`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Complex {
+    /// The real part.
+    pub re: f64,
+
+    /// The imaginary part.
+    pub im: f64,
+}
+
+impl Complex {
+    /// The complex number 0.
+    pub const ZERO: Self = Self::new(0., 0.);
+
+    /// The complex number 1.
+    pub const ONE: Self = Self::new(1., 0.);
+
+    /// The imaginary unit `i`.
+    pub const I: Self = Self::new(0., 1.);
+
+    /// Create a complex number from its real and imaginary parts.
+    pub const fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// Create a complex number from its modulus and argument, in radians.
+    pub fn from_polar(modulus: f64, argument: f64) -> Self {
+        let (sin, cos) = argument.sin_cos();
+        Self::new(modulus * cos, modulus * sin)
+    }
+
+    /// The complex conjugate of this number.
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    /// The modulus (absolute value) of this number.
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The square of the modulus of this number, which avoids taking a square root.
+    pub fn abs_squared(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// The argument of this number in radians, in the range `(-π, π]`.
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    /// The multiplicative inverse of this number. The inverse of 0 is NaN.
+    pub fn recip(self) -> Self {
+        let denominator = self.abs_squared();
+        Self::new(self.re / denominator, -self.im / denominator)
+    }
+
+    /// The exponential of this number.
+    pub fn exp(self) -> Self {
+        Self::from_polar(self.re.exp(), self.im)
+    }
+
+    /// The principal natural logarithm of this number, whose imaginary part is in `(-π, π]`.
+    ///
+    /// The logarithm of 0 has a real part of negative infinity.
+    pub fn ln(self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+
+    /// Raise this number to a complex power, using the principal branch of the logarithm.
+    ///
+    /// 0 to the power of anything with a positive real part is 0.
+    ///
+    /// ```
+    /// # use trinity::math::Complex;
+    /// # use approx::assert_relative_eq;
+    /// let z = Complex::I.powc(Complex::new(2., 0.));
+    /// assert_relative_eq!(z.re, -1.);
+    /// assert_relative_eq!(z.im, 0., epsilon = 0.000000001);
+    /// ```
+    pub fn powc(self, power: Self) -> Self {
+        if self == Self::ZERO {
+            if power == Self::ZERO {
+                Self::ONE
+            } else if power.re > 0. {
+                Self::ZERO
+            } else {
+                Self::new(f64::NAN, f64::NAN)
+            }
+        } else {
+            (power * self.ln()).exp()
+        }
+    }
+
+    /// Is this number real, meaning that its imaginary part is exactly 0?
+    pub fn is_real(self) -> bool {
+        self.im == 0.
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::new(re, 0.)
+    }
+}
+
+impl From<Eigenvalue> for Complex {
+    fn from(eigenvalue: Eigenvalue) -> Self {
+        match eigenvalue {
+            Eigenvalue::Real(value) => value.into(),
+            Eigenvalue::Complex { re, im } => Self::new(re, im),
+        }
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Self::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+impl Mul<Complex> for f64 {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    #[allow(
+        clippy::suspicious_arithmetic_impl,
+        reason = "Division is multiplication by the reciprocal"
+    )]
+    fn div(self, rhs: Self) -> Self::Output {
+        self * rhs.recip()
+    }
+}
+
+impl Neg for Complex {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+/// Complex numbers are displayed like `3 + 2i`, `3 - 2i`, `2i`, or `3`.
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { re, im } = *self;
+        if im == 0. {
+            write!(f, "{re}")
+        } else if re == 0. {
+            write!(f, "{im}i")
+        } else if im < 0. {
+            write!(f, "{re} - {}i", -im)
+        } else {
+            write!(f, "{re} + {im}i")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    /// Assert that two complex numbers are close.
+    fn assert_close(a: Complex, b: Complex) {
+        assert_relative_eq!(a.re, b.re, epsilon = 0.000000001);
+        assert_relative_eq!(a.im, b.im, epsilon = 0.000000001);
+    }
+
+    #[test]
+    fn complex_arithmetic() {
+        let a = Complex::new(3., 2.);
+        let b = Complex::new(1., -4.);
+
+        assert_eq!(a + b, Complex::new(4., -2.));
+        assert_eq!(a - b, Complex::new(2., 6.));
+        assert_eq!(a * b, Complex::new(11., -10.));
+        assert_eq!(Complex::I * Complex::I, Complex::new(-1., 0.));
+        assert_eq!(2. * a, Complex::new(6., 4.));
+        assert_eq!(-a, Complex::new(-3., -2.));
+        assert_eq!(a.conj(), Complex::new(3., -2.));
+        assert_eq!(Complex::new(3., 4.).abs(), 5.);
+
+        for _ in 0..100 {
+            let a = Complex::new(rand::random(), rand::random());
+            let b = Complex::new(rand::random::<f64>() + 0.1, rand::random());
+            assert_close((a / b) * b, a);
+            assert_close(a * a.recip(), Complex::ONE);
+        }
+    }
+
+    #[test]
+    fn complex_functions() {
+        assert_close(Complex::new(0., PI).exp(), Complex::new(-1., 0.));
+        assert_close(Complex::new(-1., 0.).ln(), Complex::new(0., PI));
+        assert_close(Complex::I.ln(), Complex::new(0., PI / 2.));
+        assert_close(
+            Complex::new(-4., 0.).powc(Complex::new(0.5, 0.)),
+            Complex::new(0., 2.),
+        );
+        assert_close(
+            Complex::I.powc(Complex::I),
+            Complex::new((-PI / 2.).exp(), 0.),
+        );
+        assert_eq!(Complex::ZERO.powc(Complex::ZERO), Complex::ONE);
+        assert_eq!(Complex::ZERO.powc(Complex::I + Complex::ONE), Complex::ZERO);
+        assert_close(Complex::from_polar(2., PI / 2.), Complex::new(0., 2.));
+
+        for _ in 0..100 {
+            let z = Complex::new(rand::random::<f64>() - 0.5, rand::random::<f64>() - 0.5);
+            assert_close(z.ln().exp(), z);
+        }
+    }
+
+    #[test]
+    fn complex_display() {
+        assert_eq!(Complex::new(3., 2.).to_string(), "3 + 2i");
+        assert_eq!(Complex::new(3., -2.).to_string(), "3 - 2i");
+        assert_eq!(Complex::new(0., 2.5).to_string(), "2.5i");
+        assert_eq!(Complex::new(-3., 0.).to_string(), "-3");
+        assert_eq!(
+            Complex::from(Eigenvalue::Complex { re: 0., im: -1. }),
+            -Complex::I
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! This module provides functions to decompose 2D and 3D matrices into a rotation, a shear, and
+//! a scale.
+
+use super::{qr_2d, qr_3d, MatrixPredicates};
+use glam::{DMat2, DMat3, DQuat, DVec2, DVec3};
+
+/// A 2D matrix written as `rotation * shear * scale`, so a vector is scaled, then sheared, then
+/// rotated. See [`decompose_2d`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Decomposition2d {
+    /// The anticlockwise rotation in degrees, in the range `(-180, 180]`.
+    pub rotation_degrees: f64,
+
+    /// The scale factors along the x and y axes. The x scale is always positive, but the y scale
+    /// is negative if the matrix flips orientation.
+    pub scale: DVec2,
+
+    /// The shear factor, which moves each point along the x axis by this multiple of its y
+    /// coordinate.
+    pub shear: f64,
+}
+
+/// A 3D matrix written as `rotation * shear * scale`, so a vector is scaled, then sheared, then
+/// rotated. See [`decompose_3d`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Decomposition3d {
+    /// The rotation, as a unit quaternion.
+    pub rotation: DQuat,
+
+    /// The scale factors along the x, y, and z axes. The x and y scales are always positive, but
+    /// the z scale is negative if the matrix flips orientation.
+    pub scale: DVec3,
+
+    /// The shear factors `xy`, `xz`, and `yz`, where `ab` moves each point along the `a` axis by
+    /// this multiple of its `b` coordinate.
+    pub shear: DVec3,
+}
+
+impl Decomposition2d {
+    /// Recompose this decomposition into a single matrix.
+    pub fn to_matrix(&self) -> DMat2 {
+        DMat2::from_angle(self.rotation_degrees.to_radians())
+            * DMat2::from_cols(DVec2::X, DVec2::new(self.shear, 1.))
+            * DMat2::from_diagonal(self.scale)
+    }
+}
+
+impl Decomposition3d {
+    /// Recompose this decomposition into a single matrix.
+    pub fn to_matrix(&self) -> DMat3 {
+        let DVec3 {
+            x: xy,
+            y: xz,
+            z: yz,
+        } = self.shear;
+        DMat3::from_quat(self.rotation)
+            * DMat3::from_cols(DVec3::X, DVec3::new(xy, 1., 0.), DVec3::new(xz, yz, 1.))
+            * DMat3::from_diagonal(self.scale)
+    }
+}
+
+/// Decompose a 2D matrix into a rotation, a shear, and a scale, returning `None` if it's
+/// singular.
+///
+/// This comes from the [QR decomposition](qr_2d), since the rotation is the orthogonal part and
+/// the shear and scale together are the upper-triangular part.
+///
+/// ```
+/// # use trinity::math::decompose_2d;
+/// # use approx::assert_relative_eq;
+/// # use glam::{DMat2, DVec2};
+/// let matrix = DMat2::from_angle(30f64.to_radians()) * DMat2::from_diagonal(DVec2::new(2., 3.));
+/// let decomposition = decompose_2d(matrix).unwrap();
+///
+/// assert_relative_eq!(decomposition.rotation_degrees, 30., epsilon = 0.000000001);
+/// assert_relative_eq!(decomposition.scale.x, 2., epsilon = 0.000000001);
+/// assert_relative_eq!(decomposition.scale.y, 3., epsilon = 0.000000001);
+/// assert_relative_eq!(decomposition.shear, 0., epsilon = 0.000000001);
+/// ```
+pub fn decompose_2d(matrix: DMat2) -> Option<Decomposition2d> {
+    if matrix.is_singular() {
+        return None;
+    }
+
+    let mut qr = qr_2d(matrix);
+    if qr.q.determinant() < 0. {
+        // Move the reflection out of Q and into the y scale
+        let flip = DMat2::from_diagonal(DVec2::new(1., -1.));
+        qr.q *= flip;
+        qr.r = flip * qr.r;
+    }
+
+    Some(Decomposition2d {
+        rotation_degrees: qr.q.x_axis.y.atan2(qr.q.x_axis.x).to_degrees(),
+        scale: DVec2::new(qr.r.x_axis.x, qr.r.y_axis.y),
+        shear: qr.r.y_axis.x / qr.r.y_axis.y,
+    })
+}
+
+/// Decompose a 3D matrix into a rotation, a shear, and a scale, returning `None` if it's
+/// singular. See [`decompose_2d`].
+pub fn decompose_3d(matrix: DMat3) -> Option<Decomposition3d> {
+    if matrix.is_singular() {
+        return None;
+    }
+
+    let mut qr = qr_3d(matrix);
+    if qr.q.determinant() < 0. {
+        // Move the reflection out of Q and into the z scale
+        let flip = DMat3::from_diagonal(DVec3::new(1., 1., -1.));
+        qr.q *= flip;
+        qr.r = flip * qr.r;
+    }
+
+    let r = qr.r;
+    Some(Decomposition3d {
+        rotation: DQuat::from_mat3(&qr.q).normalize(),
+        scale: DVec3::new(r.x_axis.x, r.y_axis.y, r.z_axis.z),
+        shear: DVec3::new(
+            r.y_axis.x / r.y_axis.y,
+            r.z_axis.x / r.z_axis.z,
+            r.z_axis.y / r.z_axis.z,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn decompose_2d_matrices() {
+        let shear = DMat2::from_cols_array(&[1., 0., 0.5, 1.]);
+        let decomposition = decompose_2d(shear).unwrap();
+        assert_relative_eq!(decomposition.rotation_degrees, 0.);
+        assert_relative_eq!(decomposition.scale, DVec2::ONE);
+        assert_relative_eq!(decomposition.shear, 0.5);
+
+        let reflection = DMat2::from_cols_array(&[1., 0., 0., -1.]);
+        let decomposition = decompose_2d(reflection).unwrap();
+        assert_relative_eq!(decomposition.rotation_degrees, 0.);
+        assert_relative_eq!(decomposition.scale, DVec2::new(1., -1.));
+
+        assert_eq!(
+            decompose_2d(DMat2::from_cols_array(&[1., 2., 2., 4.])),
+            None
+        );
+        assert_eq!(decompose_2d(DMat2::ZERO), None);
+
+        for _ in 0..100 {
+            let matrix = DMat2::from_cols_array(&rand::random::<[f64; 4]>().map(|x| x * 4. - 2.));
+            let Some(decomposition) = decompose_2d(matrix) else {
+                continue;
+            };
+            assert!(decomposition.scale.x > 0.);
+            assert_eq!(decomposition.scale.y < 0., matrix.determinant() < 0.);
+            assert_relative_eq!(decomposition.to_matrix(), matrix, epsilon = 0.000001);
+        }
+    }
+
+    #[test]
+    fn decompose_3d_matrices() {
+        let rotation = DQuat::from_axis_angle(DVec3::new(1., 2., 3.).normalize(), 2.);
+        let matrix = DMat3::from_quat(rotation) * DMat3::from_diagonal(DVec3::new(1., 2., 3.));
+        let decomposition = decompose_3d(matrix).unwrap();
+        assert!(decomposition.rotation.abs_diff_eq(rotation, 0.000000001));
+        assert_relative_eq!(
+            decomposition.scale,
+            DVec3::new(1., 2., 3.),
+            epsilon = 0.000000001
+        );
+        assert_relative_eq!(decomposition.shear, DVec3::ZERO, epsilon = 0.000000001);
+
+        let reflection = DMat3::from_diagonal(DVec3::new(1., -1., 1.));
+        let decomposition = decompose_3d(reflection).unwrap();
+        assert_eq!(decomposition.scale.z, -1.);
+        assert_relative_eq!(decomposition.to_matrix(), reflection, epsilon = 0.000000001);
+
+        assert_eq!(
+            decompose_3d(DMat3::from_cols_array(&[
+                1., 2., 3., 4., 5., 6., 7., 8., 9.
+            ])),
+            None
+        );
+
+        for _ in 0..100 {
+            let matrix = DMat3::from_cols_array(&rand::random::<[f64; 9]>().map(|x| x * 4. - 2.));
+            let Some(decomposition) = decompose_3d(matrix) else {
+                continue;
+            };
+            assert!(decomposition.scale.x > 0. && decomposition.scale.y > 0.);
+            assert_eq!(decomposition.scale.z < 0., matrix.determinant() < 0.);
+            assert_relative_eq!(decomposition.to_matrix(), matrix, epsilon = 0.000001);
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! This module provides functions to find the eigenvalues and eigenvectors of 2D and 3D matrices.
+
+use glam::{DMat2, DMat3, DVec2, DVec3};
+use std::f64::consts::PI;
+
+/// The relative tolerance used to decide if two eigenvalues are the same, if an eigenvalue is
+/// real, and if a pivot is zero when finding eigenvectors.
+///
+/// Like in [`rank_2d`](super::rank_2d), this is scaled by the magnitude of the largest entry in
+/// the matrix, so scaling a matrix never changes the structure of its eigenvalues.
+const TOLERANCE: f64 = 0.000001;
+
+/// An eigenvalue of a real matrix, which may be complex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Eigenvalue {
+    /// A real eigenvalue.
+    Real(f64),
+
+    /// A complex eigenvalue. These always come in conjugate pairs.
+    Complex {
+        /// The real part.
+        re: f64,
+        /// The imaginary part, which is never zero.
+        im: f64,
+    },
+}
+
+impl Eigenvalue {
+    /// Get the value of this eigenvalue if it's real.
+    pub fn as_real(self) -> Option<f64> {
+        match self {
+            Self::Real(value) => Some(value),
+            Self::Complex { .. } => None,
+        }
+    }
+
+    /// Multiply this eigenvalue by a real number.
+    fn scale(self, factor: f64) -> Self {
+        match self {
+            Self::Real(value) => Self::Real(value * factor),
+            Self::Complex { re, im } => Self::Complex {
+                re: re * factor,
+                im: im * factor,
+            },
+        }
+    }
+}
+
+/// An eigenvalue of a matrix, along with its eigenvectors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Eigenpair<V> {
+    /// The eigenvalue.
+    pub value: Eigenvalue,
+
+    /// The algebraic multiplicity of the eigenvalue, which is the number of times it's a root of
+    /// the characteristic polynomial.
+    pub multiplicity: usize,
+
+    /// An orthonormal basis of the real eigenspace, so its length is the geometric multiplicity.
+    ///
+    /// This is never empty for a real eigenvalue, but it can be shorter than the multiplicity if
+    /// the matrix is defective, like a shear. It's always empty for a complex eigenvalue.
+    pub vectors: Vec<V>,
+}
+
+/// Find the eigenvalues and eigenvectors of a 2D matrix in closed form.
+///
+/// Real eigenvalues come first, from largest to smallest, followed by any complex conjugate
+/// pair, with the positive imaginary part first. The multiplicities always add up to 2, unless
+/// the matrix has non-finite entries, in which case the result is empty.
+pub fn eigen_2d(matrix: DMat2) -> Vec<Eigenpair<DVec2>> {
+    let Some(scale) = scale_of(&matrix.to_cols_array()) else {
+        return vec![];
+    };
+    let matrix = matrix / scale;
+
+    let half_trace = (matrix.x_axis.x + matrix.y_axis.y) / 2.;
+    let discriminant = half_trace * half_trace - matrix.determinant();
+    let roots = if discriminant < 0. {
+        let im = (-discriminant).sqrt();
+        [
+            Eigenvalue::Complex { re: half_trace, im },
+            Eigenvalue::Complex {
+                re: half_trace,
+                im: -im,
+            },
+        ]
+    } else {
+        let root = discriminant.sqrt();
+        [
+            Eigenvalue::Real(half_trace + root),
+            Eigenvalue::Real(half_trace - root),
+        ]
+    };
+
+    eigenpairs(
+        matrix.transpose().to_cols_array_2d(),
+        roots,
+        scale,
+        DVec2::from_array,
+    )
+}
+
+/// Find the eigenvalues and eigenvectors of a 3D matrix, by solving the characteristic cubic.
+///
+/// Real eigenvalues come first, from largest to smallest, followed by any complex conjugate
+/// pair, with the positive imaginary part first. The multiplicities always add up to 3, unless
+/// the matrix has non-finite entries, in which case the result is empty.
+pub fn eigen_3d(matrix: DMat3) -> Vec<Eigenpair<DVec3>> {
+    let Some(scale) = scale_of(&matrix.to_cols_array()) else {
+        return vec![];
+    };
+    let matrix = matrix / scale;
+    let [[a, b, c], [d, e, f], [g, h, i]] = matrix.transpose().to_cols_array_2d();
+
+    // The characteristic polynomial is x^3 - trace x^2 + minors x - det
+    let trace = a + e + i;
+    let minors = (a * e - b * d) + (a * i - c * g) + (e * i - f * h);
+    let determinant = matrix.determinant();
+
+    // Substituting x = t + trace / 3 gives the depressed cubic t^3 + pt + q
+    let shift = trace / 3.;
+    let p = minors - trace * trace / 3.;
+    let q = -2. * trace.powi(3) / 27. + trace * minors / 3. - determinant;
+
+    let roots = if p.abs() <= TOLERANCE.powi(2) && q.abs() <= TOLERANCE.powi(3) {
+        [Eigenvalue::Real(shift); 3]
+    } else if 4. * p.powi(3) + 27. * q * q <= 0. {
+        // Three real roots, which we find with the trigonometric method
+        let radius = 2. * (-p / 3.).sqrt();
+        let angle = (3. * q / (p * radius)).clamp(-1., 1.).acos() / 3.;
+        [0., 1., 2.].map(|k| Eigenvalue::Real(shift + radius * (angle - 2. * PI * k / 3.).cos()))
+    } else {
+        // One real root, which we find with Cardano's formula, and a complex conjugate pair,
+        // which we find by dividing it out of the depressed cubic
+        let root = (q * q / 4. + p.powi(3) / 27.).sqrt();
+        let real = (-q / 2. + root).cbrt() + (-q / 2. - root).cbrt();
+        let im = (p + 3. * real * real / 4.).max(0.).sqrt();
+        [
+            Eigenvalue::Real(shift + real),
+            Eigenvalue::Complex {
+                re: shift - real / 2.,
+                im,
+            },
+            Eigenvalue::Complex {
+                re: shift - real / 2.,
+                im: -im,
+            },
+        ]
+    };
+
+    eigenpairs(
+        matrix.transpose().to_cols_array_2d(),
+        roots,
+        scale,
+        DVec3::from_array,
+    )
+}
+
+/// Get the magnitude of the largest entry, or 1 if every entry is zero, so that we can divide
+/// by it. Returns `None` if any entry isn't finite.
+fn scale_of(entries: &[f64]) -> Option<f64> {
+    if entries.iter().any(|entry| !entry.is_finite()) {
+        return None;
+    }
+
+    let scale = entries.iter().fold(0f64, |max, entry| max.max(entry.abs()));
+    Some(if scale == 0. { 1. } else { scale })
+}
+
+/// Group these eigenvalues of the square matrix with the given rows, merge repeated ones, and
+/// find the eigenvectors of the real ones.
+///
+/// The matrix and eigenvalues should have been divided by `scale`, and the eigenvalues are
+/// multiplied by it again at the end.
+fn eigenpairs<const N: usize, V>(
+    rows: [[f64; N]; N],
+    roots: [Eigenvalue; N],
+    scale: f64,
+    to_vector: impl Fn([f64; N]) -> V,
+) -> Vec<Eigenpair<V>> {
+    let mut reals = vec![];
+    let mut complexes = vec![];
+    for root in roots {
+        match root {
+            Eigenvalue::Real(value) => reals.push(value),
+            Eigenvalue::Complex { re, im } if im.abs() <= TOLERANCE => reals.push(re),
+            Eigenvalue::Complex { im, .. } => complexes.push((root, im)),
+        }
+    }
+    reals.sort_by(|a, b| b.total_cmp(a));
+    complexes.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    let mut groups: Vec<Vec<f64>> = vec![];
+    for value in reals {
+        match groups.last_mut() {
+            Some(group) if group[0] - value <= TOLERANCE => group.push(value),
+            _ => groups.push(vec![value]),
+        }
+    }
+
+    let real_pairs = groups.into_iter().map(|group| {
+        let multiplicity = group.len();
+        let value = group.iter().sum::<f64>() / multiplicity as f64;
+
+        let mut shifted = rows;
+        for (index, row) in shifted.iter_mut().enumerate() {
+            row[index] -= value;
+        }
+
+        Eigenpair {
+            value: Eigenvalue::Real(value * scale),
+            multiplicity,
+            vectors: null_space(shifted)
+                .into_iter()
+                .take(multiplicity)
+                .map(&to_vector)
+                .collect(),
+        }
+    });
+
+    let complex_pairs = complexes.into_iter().map(|(value, _)| Eigenpair {
+        value: value.scale(scale),
+        multiplicity: 1,
+        vectors: vec![],
+    });
+
+    real_pairs.chain(complex_pairs).collect()
+}
+
+/// Find an orthonormal basis of the null space of the square matrix with the given rows, which
+/// should be singular.
+///
+/// This uses Gauss-Jordan elimination with full pivoting. Since the matrix is meant to be
+/// singular, the smallest pivot is always treated as zero, even if it's bigger than the
+/// tolerance, so the basis is never empty.
+fn null_space<const N: usize>(mut rows: [[f64; N]; N]) -> Vec<[f64; N]> {
+    let mut columns: [usize; N] = std::array::from_fn(|index| index);
+    let mut rank = 0;
+
+    while rank < N - 1 {
+        let (pivot_row, pivot_column) = (rank..N)
+            .flat_map(|row| (rank..N).map(move |column| (row, column)))
+            .max_by(|&(a, b), &(c, d)| rows[a][b].abs().total_cmp(&rows[c][d].abs()))
+            .expect("There should be at least one entry left");
+
+        if rows[pivot_row][pivot_column].abs() <= TOLERANCE {
+            break;
+        }
+
+        rows.swap(rank, pivot_row);
+        for row in rows.iter_mut() {
+            row.swap(rank, pivot_column);
+        }
+        columns.swap(rank, pivot_column);
+
+        let pivot = rows[rank];
+        for (index, row) in rows.iter_mut().enumerate() {
+            if index != rank {
+                let factor = row[rank] / pivot[rank];
+                for (entry, pivot_entry) in row.iter_mut().zip(pivot) {
+                    *entry -= factor * pivot_entry;
+                }
+            }
+        }
+
+        rank += 1;
+    }
+
+    let mut basis: Vec<[f64; N]> = vec![];
+    for free in rank..N {
+        let mut vector = [0.; N];
+        vector[columns[free]] = 1.;
+        for pivot in 0..rank {
+            vector[columns[pivot]] = -rows[pivot][free] / rows[pivot][pivot];
+        }
+
+        // Gram-Schmidt, to make the basis orthonormal
+        for other in &basis {
+            let dot: f64 = vector.iter().zip(other).map(|(a, b)| a * b).sum();
+            for (entry, other_entry) in vector.iter_mut().zip(other) {
+                *entry -= dot * other_entry;
+            }
+        }
+        let length = vector.iter().map(|entry| entry * entry).sum::<f64>().sqrt();
+
+        // Make the largest component positive, so that the vectors are predictable
+        let largest = vector
+            .iter()
+            .copied()
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap_or(1.);
+        let factor = length.recip().copysign(largest);
+
+        basis.push(vector.map(|entry| entry * factor));
+    }
+
+    basis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    /// Check that the multiplicities add up to `n`, and that every eigenvector really is a unit
+    /// eigenvector.
+    macro_rules! check_eigenpairs {
+        ($matrix:expr, $pairs:expr, $n:expr) => {{
+            let matrix = $matrix;
+            let pairs = $pairs;
+            assert_eq!(
+                pairs.iter().map(|pair| pair.multiplicity).sum::<usize>(),
+                $n,
+                "{matrix:?}"
+            );
+
+            for pair in &pairs {
+                assert!(pair.vectors.len() <= pair.multiplicity);
+                if let Eigenvalue::Real(value) = pair.value {
+                    assert!(!pair.vectors.is_empty());
+                    for &vector in &pair.vectors {
+                        assert_relative_eq!(vector.length(), 1., epsilon = 0.000001);
+                        assert_relative_eq!(matrix * vector, value * vector, epsilon = 0.00001);
+                    }
+                }
+            }
+        }};
+    }
+
+    #[test]
+    fn eigen_2d_matrices() {
+        let pairs = eigen_2d(DMat2::from_cols(DVec2::new(2., 1.), DVec2::new(1., 2.)));
+        assert_eq!(pairs.len(), 2);
+        assert_relative_eq!(pairs[0].value.as_real().unwrap(), 3.);
+        assert_relative_eq!(pairs[0].vectors[0], DVec2::ONE.normalize());
+        assert_relative_eq!(pairs[1].value.as_real().unwrap(), 1.);
+
+        // Rotations have complex eigenvalues
+        let pairs = eigen_2d(DMat2::from_angle(PI / 2.));
+        assert_eq!(pairs.len(), 2);
+        assert!(matches!(
+            pairs[0].value,
+            Eigenvalue::Complex { re, im } if re.abs() < 0.000001 && (im - 1.).abs() < 0.000001
+        ));
+        assert!(matches!(
+            pairs[1].value,
+            Eigenvalue::Complex { im, .. } if (im + 1.).abs() < 0.000001
+        ));
+        assert!(pairs[0].vectors.is_empty());
+
+        // A shear is defective
+        let pairs = eigen_2d(DMat2::from_cols(DVec2::new(1., 0.), DVec2::new(1., 1.)));
+        assert_eq!(
+            pairs,
+            vec![Eigenpair {
+                value: Eigenvalue::Real(1.),
+                multiplicity: 2,
+                vectors: vec![DVec2::X],
+            }]
+        );
+
+        assert_eq!(
+            eigen_2d(3. * DMat2::IDENTITY),
+            vec![Eigenpair {
+                value: Eigenvalue::Real(3.),
+                multiplicity: 2,
+                vectors: vec![DVec2::X, DVec2::Y],
+            }]
+        );
+        assert_eq!(eigen_2d(DMat2::ZERO)[0].vectors.len(), 2);
+        assert_eq!(
+            eigen_2d(DMat2::from_diagonal(DVec2::new(f64::NAN, 1.))),
+            vec![]
+        );
+
+        for _ in 0..100 {
+            let matrix = rand::random::<DMat2>() * 10.;
+            check_eigenpairs!(matrix, eigen_2d(matrix), 2);
+        }
+    }
+
+    #[test]
+    fn eigen_3d_matrices() {
+        let pairs = eigen_3d(DMat3::from_diagonal(DVec3::new(2., 5., 2.)));
+        assert_eq!(pairs.len(), 2);
+        assert_relative_eq!(pairs[0].value.as_real().unwrap(), 5., epsilon = 0.000001);
+        assert_relative_eq!(pairs[0].vectors[0], DVec3::Y, epsilon = 0.000001);
+        assert_relative_eq!(pairs[1].value.as_real().unwrap(), 2., epsilon = 0.000001);
+        assert_eq!(pairs[1].multiplicity, 2);
+        assert_eq!(pairs[1].vectors.len(), 2);
+
+        assert_eq!(
+            eigen_3d(DMat3::IDENTITY),
+            vec![Eigenpair {
+                value: Eigenvalue::Real(1.),
+                multiplicity: 3,
+                vectors: vec![DVec3::X, DVec3::Y, DVec3::Z],
+            }]
+        );
+
+        // A rotation about an axis keeps the axis and rotates everything else
+        let axis = DVec3::new(1., 2., 2.) / 3.;
+        let pairs = eigen_3d(DMat3::from_axis_angle(axis, PI / 3.));
+        assert_eq!(pairs.len(), 3);
+        assert_relative_eq!(pairs[0].value.as_real().unwrap(), 1., epsilon = 0.000001);
+        assert_relative_eq!(pairs[0].vectors[0], axis, epsilon = 0.000001);
+        assert!(matches!(
+            pairs[1].value,
+            Eigenvalue::Complex { re, im } if (re - 0.5).abs() < 0.000001 && (im - 0.75f64.sqrt()).abs() < 0.000001
+        ));
+
+        // A Jordan block with a repeated eigenvalue
+        let pairs = eigen_3d(DMat3::from_cols(
+            DVec3::new(2., 0., 0.),
+            DVec3::new(1., 2., 0.),
+            DVec3::new(0., 0., 3.),
+        ));
+        assert_eq!(pairs.len(), 2);
+        assert_relative_eq!(pairs[1].value.as_real().unwrap(), 2., epsilon = 0.000001);
+        assert_eq!(pairs[1].multiplicity, 2);
+        assert_eq!(pairs[1].vectors.len(), 1);
+        assert_relative_eq!(pairs[1].vectors[0], DVec3::X, epsilon = 0.000001);
+
+        for _ in 0..100 {
+            let matrix = rand::random::<DMat3>() * 10.;
+            let pairs = eigen_3d(matrix);
+            check_eigenpairs!(matrix, pairs.clone(), 3);
+
+            let trace: f64 = pairs
+                .iter()
+                .map(|pair| match pair.value {
+                    Eigenvalue::Real(value) => value * pair.multiplicity as f64,
+                    Eigenvalue::Complex { re, .. } => re,
+                })
+                .sum();
+            assert_relative_eq!(
+                trace,
+                matrix.x_axis.x + matrix.y_axis.y + matrix.z_axis.z,
+                epsilon = 0.00001
+            );
+        }
+    }
+}
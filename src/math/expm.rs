@@ -0,0 +1,162 @@
+//! This module provides the matrix exponential of 2D and 3D matrices.
+
+use super::Norm;
+use glam::{DMat2, DMat3};
+use std::ops::{Add, Mul};
+
+/// The number of terms of the Taylor series to use once the matrix has been scaled down. Since
+/// the scaled matrix has a norm of at most 1/2, the error from truncating the series is far
+/// smaller than rounding error.
+const TAYLOR_TERMS: u32 = 20;
+
+/// Find the matrix exponential `exp(M) = I + M + M²/2! + M³/3! + ...` of a 2D matrix.
+///
+/// The exponential of `t` times a generator like `[0 -1; 1 0]` is a rotation by `t` radians, so
+/// this can be used to animate continuous transformations.
+///
+/// Every entry is NaN if the matrix has any non-finite entries, and the result has infinite or NaN
+/// entries if it's too big to represent.
+pub fn expm_2d(matrix: DMat2) -> DMat2 {
+    scale_and_square(matrix, DMat2::IDENTITY)
+}
+
+/// Find the matrix exponential `exp(M) = I + M + M²/2! + M³/3! + ...` of a 3D matrix.
+///
+/// The exponential of `t` times a skew-symmetric generator is a rotation by `t` times the length
+/// of the generator's axis, so this can be used to animate continuous rotations.
+///
+/// Like [`expm_2d`], the result has non-finite entries if the matrix does, or if the result is too
+/// big to represent.
+pub fn expm_3d(matrix: DMat3) -> DMat3 {
+    scale_and_square(matrix, DMat3::IDENTITY)
+}
+
+/// Find the matrix exponential by scaling and squaring.
+///
+/// Since `exp(M) = exp(M / 2^s)^(2^s)`, we divide the matrix by a power of 2 until it's small
+/// enough for its Taylor series to converge quickly, and then square the result that many times.
+///
+/// The norm of a finite matrix is at most [`f64::MAX`], so there are never more than about a
+/// thousand squarings, and we stop early once the result overflows.
+fn scale_and_square<T>(matrix: T, identity: T) -> T
+where
+    T: Copy + Add<T, Output = T> + Mul<T, Output = T> + Mul<f64, Output = T> + Norm,
+{
+    let norm = matrix.norm();
+    if !norm.is_finite() {
+        return identity * f64::NAN;
+    }
+
+    let squarings = if norm > 0.5 {
+        (norm / 0.5).log2().ceil() as i32
+    } else {
+        0
+    };
+    let scaled = matrix * 0.5f64.powi(squarings);
+
+    // Horner's method on 1 + M(1 + M/2(1 + M/3(...)))
+    let mut result = identity;
+    for term in (1..=TAYLOR_TERMS).rev() {
+        result = identity + scaled * result * (term as f64).recip();
+    }
+
+    for _ in 0..squarings {
+        result = result * result;
+        if !result.norm().is_finite() {
+            break;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::{DVec2, DVec3};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn expm_2d_matrices() {
+        assert_eq!(expm_2d(DMat2::ZERO), DMat2::IDENTITY);
+        assert_relative_eq!(
+            expm_2d(DMat2::from_diagonal(DVec2::new(1., -2.))),
+            DMat2::from_diagonal(DVec2::new(1f64.exp(), (-2f64).exp())),
+            epsilon = 0.000000001
+        );
+
+        // Nilpotent matrices have a finite series
+        assert_relative_eq!(
+            expm_2d(DMat2::from_cols(DVec2::ZERO, DVec2::new(3., 0.))),
+            DMat2::from_cols(DVec2::X, DVec2::new(3., 1.)),
+            epsilon = 0.000000001
+        );
+
+        let generator = DMat2::from_cols(DVec2::Y, -DVec2::X);
+        for angle in [0.1, 1., PI, 10., -25.] {
+            assert_relative_eq!(
+                expm_2d(generator * angle),
+                DMat2::from_angle(angle),
+                epsilon = 0.000000001
+            );
+        }
+
+        // Huge entries overflow rather than running forever or giving the identity
+        let huge = expm_2d(DMat2::from_diagonal(DVec2::new(1e300, 1.)));
+        assert!(!huge.is_finite());
+        assert_ne!(huge, DMat2::IDENTITY);
+        assert!(!expm_2d(DMat2::IDENTITY * f64::MAX).is_finite());
+
+        assert!(expm_2d(DMat2::from_diagonal(DVec2::new(f64::INFINITY, 1.)))
+            .to_cols_array()
+            .iter()
+            .all(|x| x.is_nan()));
+        assert!(expm_2d(DMat2::from_cols_array(&[f64::NAN, 0., 0., 1.]))
+            .to_cols_array()
+            .iter()
+            .all(|x| x.is_nan()));
+    }
+
+    #[test]
+    fn expm_3d_matrices() {
+        assert_eq!(expm_3d(DMat3::ZERO), DMat3::IDENTITY);
+        assert_relative_eq!(
+            expm_3d(DMat3::from_diagonal(DVec3::new(1., 0., 3.))),
+            DMat3::from_diagonal(DVec3::new(1f64.exp(), 1., 3f64.exp())),
+            epsilon = 0.000000001
+        );
+
+        let axis = DVec3::new(2., -1., 2.) / 3.;
+        let generator = DMat3::from_cols(
+            DVec3::new(0., axis.z, -axis.y),
+            DVec3::new(-axis.z, 0., axis.x),
+            DVec3::new(axis.y, -axis.x, 0.),
+        );
+        for angle in [0.1, 1., PI, 10.] {
+            assert_relative_eq!(
+                expm_3d(generator * angle),
+                DMat3::from_axis_angle(axis, angle),
+                epsilon = 0.000000001
+            );
+        }
+
+        assert!(!expm_3d(DMat3::IDENTITY * 1e200).is_finite());
+        assert!(
+            expm_3d(DMat3::from_diagonal(DVec3::new(1., f64::NEG_INFINITY, 1.)))
+                .to_cols_array()
+                .iter()
+                .all(|x| x.is_nan())
+        );
+
+        // exp(A) exp(-A) = I
+        for _ in 0..100 {
+            let matrix = rand::random::<DMat3>() * 4. - DMat3::from_cols_array(&[2.; 9]);
+            assert_relative_eq!(
+                expm_3d(matrix) * expm_3d(-matrix),
+                DMat3::IDENTITY,
+                epsilon = 0.000001
+            );
+        }
+    }
+}
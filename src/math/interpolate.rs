@@ -0,0 +1,267 @@
+//! This module provides interpolation between 2D and 3D matrices, for animating smoothly from one
+//! transformation to another.
+//!
+//! Interpolating each entry separately can pass through singular matrices, like going from
+//! `rot(0)` to `rot(180)` through the zero matrix. Instead, we split each matrix with the polar
+//! decomposition `M = QS`, where `Q` is orthogonal and `S` is symmetric positive semi-definite,
+//! then interpolate the rotations along the shortest arc and the stretches entry by entry. A
+//! weighted average of positive semi-definite matrices is positive semi-definite, so the stretch
+//! never collapses unless both ends already had.
+
+use super::{svd_2d, svd_3d};
+use glam::{DMat2, DMat3, DQuat, DVec2, DVec3};
+
+/// The polar decomposition `M = QS` of a matrix `M`. See [`polar_2d`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Polar<M> {
+    /// The orthogonal matrix `Q`, which is the closest orthogonal matrix to `M`. This is a
+    /// rotation unless `M` flips orientation.
+    pub orthogonal: M,
+
+    /// The symmetric positive semi-definite matrix `S`, which stretches along its eigenvectors.
+    pub stretch: M,
+}
+
+/// Find the polar decomposition of a 2D matrix, or `None` if it has any non-finite entries.
+///
+/// This comes from the [singular value decomposition](svd_2d) `M = UΣVᵀ`, since `Q = UVᵀ` and
+/// `S = VΣVᵀ`.
+pub fn polar_2d(matrix: DMat2) -> Option<Polar<DMat2>> {
+    let svd = svd_2d(matrix)?;
+    Some(Polar {
+        orthogonal: svd.u * svd.v_transpose,
+        stretch: svd.v_transpose.transpose()
+            * DMat2::from_diagonal(svd.singular_values)
+            * svd.v_transpose,
+    })
+}
+
+/// Find the polar decomposition of a 3D matrix, or `None` if it has any non-finite entries. See
+/// [`polar_2d`].
+pub fn polar_3d(matrix: DMat3) -> Option<Polar<DMat3>> {
+    let svd = svd_3d(matrix)?;
+    Some(Polar {
+        orthogonal: svd.u * svd.v_transpose,
+        stretch: svd.v_transpose.transpose()
+            * DMat3::from_diagonal(svd.singular_values)
+            * svd.v_transpose,
+    })
+}
+
+/// How to interpolate between two matrices. See [`Interpolate`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interpolation {
+    /// Interpolate the rotation and stretch of the [polar decomposition](polar_2d) separately.
+    #[default]
+    Polar,
+
+    /// Interpolate each entry separately.
+    Linear,
+}
+
+impl Interpolation {
+    /// Interpolate from `a` (when `t` is 0) to `b` (when `t` is 1) in this way.
+    pub fn interpolate<M: Interpolate>(self, a: M, b: M, t: f64) -> M {
+        match self {
+            Self::Polar => a.interpolate_polar(b, t),
+            Self::Linear => a.interpolate_linear(b, t),
+        }
+    }
+}
+
+/// A matrix which can be interpolated. See [`interpolate`].
+pub trait Interpolate: Sized {
+    /// Interpolate each entry separately from `self` (when `t` is 0) to `other` (when `t` is 1).
+    fn interpolate_linear(self, other: Self, t: f64) -> Self;
+
+    /// Interpolate from `self` (when `t` is 0) to `other` (when `t` is 1) using the
+    /// [polar decomposition](polar_2d).
+    ///
+    /// This falls back to [`interpolate_linear`](Self::interpolate_linear) if exactly one of the
+    /// matrices flips orientation, since any continuous path between them has to pass through a
+    /// singular matrix anyway, or if either of them has any non-finite entries.
+    fn interpolate_polar(self, other: Self, t: f64) -> Self;
+}
+
+/// Interpolate from `a` (when `t` is 0) to `b` (when `t` is 1) using the polar decomposition, so
+/// that rotations stay rotations and the path doesn't collapse through singular matrices.
+///
+/// ```
+/// # use trinity::math::interpolate;
+/// # use approx::assert_relative_eq;
+/// # use glam::DMat2;
+/// let halfway = interpolate(DMat2::IDENTITY, DMat2::from_angle(3.), 0.5);
+/// assert_relative_eq!(halfway, DMat2::from_angle(1.5), epsilon = 0.000000001);
+///
+/// // Interpolating each entry would give the zero matrix
+/// let halfway = interpolate(DMat2::IDENTITY, -DMat2::IDENTITY, 0.5);
+/// assert_relative_eq!(halfway.determinant(), 1., epsilon = 0.000000001);
+/// ```
+pub fn interpolate<M: Interpolate>(a: M, b: M, t: f64) -> M {
+    a.interpolate_polar(b, t)
+}
+
+impl Interpolate for DMat2 {
+    fn interpolate_linear(self, other: Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+
+    fn interpolate_polar(self, other: Self, t: f64) -> Self {
+        let (Some(a), Some(b)) = (polar_2d(self), polar_2d(other)) else {
+            return self.interpolate_linear(other, t);
+        };
+
+        // Move a shared reflection out of both orthogonal parts so that they become rotations
+        let flip = match (
+            a.orthogonal.determinant() < 0.,
+            b.orthogonal.determinant() < 0.,
+        ) {
+            (false, false) => DMat2::IDENTITY,
+            (true, true) => DMat2::from_diagonal(DVec2::new(1., -1.)),
+            _ => return self.interpolate_linear(other, t),
+        };
+
+        let angle = |rotation: DMat2| rotation.x_axis.y.atan2(rotation.x_axis.x);
+        let start = angle(flip * a.orthogonal);
+        let mut difference = angle(flip * b.orthogonal) - start;
+        if difference > std::f64::consts::PI {
+            difference -= std::f64::consts::TAU;
+        } else if difference < -std::f64::consts::PI {
+            difference += std::f64::consts::TAU;
+        }
+
+        flip * DMat2::from_angle(start + difference * t)
+            * a.stretch.interpolate_linear(b.stretch, t)
+    }
+}
+
+impl Interpolate for DMat3 {
+    fn interpolate_linear(self, other: Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+
+    fn interpolate_polar(self, other: Self, t: f64) -> Self {
+        let (Some(a), Some(b)) = (polar_3d(self), polar_3d(other)) else {
+            return self.interpolate_linear(other, t);
+        };
+
+        // Move a shared reflection out of both orthogonal parts so that they become rotations
+        let flip = match (
+            a.orthogonal.determinant() < 0.,
+            b.orthogonal.determinant() < 0.,
+        ) {
+            (false, false) => DMat3::IDENTITY,
+            (true, true) => DMat3::from_diagonal(DVec3::new(1., 1., -1.)),
+            _ => return self.interpolate_linear(other, t),
+        };
+
+        let quat = |rotation: DMat3| DQuat::from_mat3(&rotation).normalize();
+        let rotation = quat(flip * a.orthogonal).slerp(quat(flip * b.orthogonal), t);
+
+        flip * DMat3::from_quat(rotation) * a.stretch.interpolate_linear(b.stretch, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn polar_decomposition() {
+        let matrix = DMat2::from_angle(0.7) * DMat2::from_cols_array(&[2., 1., 1., 3.]);
+        let polar = polar_2d(matrix).unwrap();
+        assert_relative_eq!(
+            polar.orthogonal,
+            DMat2::from_angle(0.7),
+            epsilon = 0.000000001
+        );
+        assert_relative_eq!(
+            polar.stretch,
+            DMat2::from_cols_array(&[2., 1., 1., 3.]),
+            epsilon = 0.000000001
+        );
+
+        for _ in 0..100 {
+            let matrix = DMat3::from_cols_array(&rand::random::<[f64; 9]>().map(|x| x * 4. - 2.));
+            let polar = polar_3d(matrix).unwrap();
+            assert_relative_eq!(polar.orthogonal * polar.stretch, matrix, epsilon = 0.000001);
+            assert_relative_eq!(
+                polar.orthogonal.transpose() * polar.orthogonal,
+                DMat3::IDENTITY,
+                epsilon = 0.000000001
+            );
+            assert_relative_eq!(polar.stretch, polar.stretch.transpose(), epsilon = 0.000001);
+        }
+
+        assert_eq!(polar_2d(DMat2::from_diagonal(DVec2::splat(f64::NAN))), None);
+    }
+
+    #[test]
+    fn interpolate_2d() {
+        let a = DMat2::from_angle(-3.) * 2.;
+        let b = DMat2::from_angle(3.) * DMat2::from_diagonal(DVec2::new(1., 4.));
+        assert_relative_eq!(interpolate(a, b, 0.), a, epsilon = 0.000000001);
+        assert_relative_eq!(interpolate(a, b, 1.), b, epsilon = 0.000000001);
+
+        // The shortest arc from -3 to 3 radians goes through π
+        let halfway = interpolate(DMat2::from_angle(-3.), DMat2::from_angle(3.), 0.5);
+        assert_relative_eq!(halfway, -DMat2::IDENTITY, epsilon = 0.000000001);
+
+        let reflection = DMat2::from_diagonal(DVec2::new(1., -1.));
+        let halfway = interpolate(reflection, reflection * DMat2::from_angle(1.), 0.5);
+        assert_relative_eq!(
+            halfway,
+            reflection * DMat2::from_angle(0.5),
+            epsilon = 0.000000001
+        );
+
+        // Flipping orientation can't avoid a singular matrix, so it falls back to a linear path
+        assert_eq!(
+            interpolate(DMat2::IDENTITY, reflection, 0.5),
+            Interpolation::Linear.interpolate(DMat2::IDENTITY, reflection, 0.5)
+        );
+
+        for _ in 0..100 {
+            let a = DMat2::from_angle(rand::random::<f64>() * 6.) * (rand::random::<f64>() + 0.5);
+            let b = DMat2::from_angle(rand::random::<f64>() * 6.) * (rand::random::<f64>() + 0.5);
+            for step in 0..=10 {
+                let t = step as f64 / 10.;
+                assert!(interpolate(a, b, t).determinant() > 0.2);
+            }
+        }
+    }
+
+    #[test]
+    fn interpolate_3d() {
+        let a = DMat3::from_rotation_x(1.) * DMat3::from_diagonal(DVec3::new(1., 2., 3.));
+        let b = DMat3::from_rotation_y(2.) * 3.;
+        assert_relative_eq!(interpolate(a, b, 0.), a, epsilon = 0.000000001);
+        assert_relative_eq!(interpolate(a, b, 1.), b, epsilon = 0.000000001);
+        assert_relative_eq!(
+            Interpolation::Polar.interpolate(DMat3::IDENTITY, DMat3::from_rotation_z(2.), 0.25),
+            DMat3::from_rotation_z(0.5),
+            epsilon = 0.000000001
+        );
+        assert_relative_eq!(
+            interpolate(DMat3::IDENTITY, DMat3::IDENTITY * 3., 0.5),
+            DMat3::IDENTITY * 2.,
+            epsilon = 0.000000001
+        );
+
+        for _ in 0..100 {
+            let axis = (DVec3::from_array(rand::random()) - 0.5).normalize();
+            let a = DMat3::from_axis_angle(axis, rand::random::<f64>() * 3.);
+            let b = DMat3::from_axis_angle(axis, -rand::random::<f64>() * 3.);
+            for step in 0..=10 {
+                let t = step as f64 / 10.;
+                assert_relative_eq!(
+                    interpolate(a, b, t).determinant(),
+                    1.,
+                    epsilon = 0.000000001
+                );
+            }
+        }
+    }
+}
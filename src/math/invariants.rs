@@ -0,0 +1,110 @@
+//! This module provides the numbers which summarise a 2D or 3D matrix at a glance, like its
+//! determinant, trace, and rank. See [`Invariants`].
+
+use super::{rank_2d, rank_3d};
+use glam::{DMat2, DMat3};
+
+/// The determinant, trace, and rank of a matrix, which are all unchanged by a change of basis.
+///
+/// These are cheap enough to find every frame, like while animating between two matrices to watch
+/// the determinant pass through zero.
+///
+/// ```
+/// # use trinity::math::{invariants_2d, Invariants};
+/// # use glam::DMat2;
+/// assert_eq!(
+///     invariants_2d(DMat2::from_cols_array(&[1., 2., 2., 4.])),
+///     Some(Invariants {
+///         determinant: 0.,
+///         trace: 5.,
+///         rank: 1,
+///     })
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Invariants {
+    /// The determinant, which is the factor that areas or volumes get scaled by, negative if the
+    /// orientation gets flipped.
+    pub determinant: f64,
+
+    /// The trace, which is the sum of the diagonal entries, and also the sum of the eigenvalues.
+    pub trace: f64,
+
+    /// The rank, which is the dimension of the space that everything gets mapped into. See
+    /// [`rank_2d`].
+    pub rank: usize,
+}
+
+/// Find the trace of a 2D matrix, which is the sum of its diagonal entries.
+pub fn trace_2d(matrix: DMat2) -> f64 {
+    matrix.x_axis.x + matrix.y_axis.y
+}
+
+/// Find the trace of a 3D matrix, which is the sum of its diagonal entries.
+pub fn trace_3d(matrix: DMat3) -> f64 {
+    matrix.x_axis.x + matrix.y_axis.y + matrix.z_axis.z
+}
+
+/// Find the [`Invariants`] of a 2D matrix, or `None` if it has any non-finite entries.
+pub fn invariants_2d(matrix: DMat2) -> Option<Invariants> {
+    Some(Invariants {
+        determinant: matrix.determinant(),
+        trace: trace_2d(matrix),
+        rank: rank_2d(matrix)?,
+    })
+}
+
+/// Find the [`Invariants`] of a 3D matrix, or `None` if it has any non-finite entries.
+pub fn invariants_3d(matrix: DMat3) -> Option<Invariants> {
+    Some(Invariants {
+        determinant: matrix.determinant(),
+        trace: trace_3d(matrix),
+        rank: rank_3d(matrix)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn invariants() {
+        assert_eq!(
+            invariants_3d(DMat3::IDENTITY),
+            Some(Invariants {
+                determinant: 1.,
+                trace: 3.,
+                rank: 3,
+            })
+        );
+        assert_eq!(
+            invariants_2d(DMat2::ZERO),
+            Some(Invariants {
+                determinant: 0.,
+                trace: 0.,
+                rank: 0,
+            })
+        );
+        assert_eq!(invariants_2d(DMat2::IDENTITY * f64::NAN), None);
+
+        // A change of basis doesn't change any of them
+        let matrix = DMat3::from_cols_array(&[2., 0., 1., 1., 3., 0., 0., 1., 4.]);
+        let basis = DMat3::from_cols_array(&[1., 1., 0., 0., 1., 2., 1., 0., 1.]);
+        let similar = basis.inverse() * matrix * basis;
+        let [a, b] = [matrix, similar].map(|matrix| invariants_3d(matrix).unwrap());
+        assert_relative_eq!(a.determinant, b.determinant, epsilon = 0.000000001);
+        assert_relative_eq!(a.trace, b.trace, epsilon = 0.000000001);
+        assert_eq!(a.rank, b.rank);
+
+        assert_eq!(trace_2d(DMat2::from_angle(1.)), 2. * 1f64.cos());
+        assert_eq!(
+            invariants_3d(DMat3::from_cols_array(&[
+                1., 2., 3., 2., 4., 6., 0., 0., 1.
+            ]))
+            .map(|invariants| invariants.rank),
+            Some(2)
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! This module provides the real Jordan normal form of 2D and 3D matrices, built on the
+//! eigenvalues and eigenvectors from [`eigen_2d`] and [`eigen_3d`].
+//!
+//! Every matrix can be written as `A = PJP⁻¹`, where `J` is block diagonal. A real eigenvalue `λ`
+//! gets blocks with `λ` on the diagonal and 1 just above it, and the number of blocks is the
+//! number of independent eigenvectors, so a defective matrix like a shear gets a bigger block. A
+//! complex conjugate pair `a ± bi` gets the 2×2 block `[a b; -b a]`, which is a rotation and
+//! scaling, so that everything stays real.
+//!
+//! This makes powers easy, since `Aⁿ = PJⁿP⁻¹` and each block of `Jⁿ` has a closed form.
+
+use super::{eigen_2d, eigen_3d, Complex, Eigenpair, Eigenvalue};
+use glam::{DMat2, DMat3, DVec2, DVec3};
+
+/// A single block on the diagonal of a Jordan form. See [`Jordan`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JordanBlock {
+    /// The eigenvalue of this block. A complex eigenvalue `a + bi` always has a positive
+    /// imaginary part, and stands for the 2×2 block `[a b; -b a]`.
+    pub value: Eigenvalue,
+
+    /// The size of this block, which is the length of its chain of generalised eigenvectors. A
+    /// complex block always has size 1, even though it takes up two rows and columns.
+    pub size: usize,
+}
+
+/// The real Jordan form `A = PJP⁻¹` of a matrix `A`. See [`jordan_form_2d`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Jordan<M> {
+    /// The invertible matrix `P`, whose columns are generalised eigenvectors, in the same order
+    /// as the blocks of `J`.
+    ///
+    /// For a real block, the first column of the block is an eigenvector, and every later column
+    /// `w` maps to the one before it under `A - λI`. For a complex block, the two columns are the
+    /// real and imaginary parts of a complex eigenvector.
+    pub p: M,
+
+    /// The block diagonal matrix `J`.
+    pub j: M,
+
+    /// The blocks on the diagonal of `J`, from top left to bottom right.
+    pub blocks: Vec<JordanBlock>,
+}
+
+impl Jordan<DMat2> {
+    /// Recompose this Jordan form into the original matrix `PJP⁻¹`.
+    pub fn to_matrix(&self) -> DMat2 {
+        self.p * self.j * self.p.inverse()
+    }
+
+    /// Compute the power `Aⁿ = PJⁿP⁻¹` of the original matrix, using the closed form of `Jⁿ`.
+    pub fn power(&self, n: u32) -> DMat2 {
+        self.p * DMat2::from_cols_array_2d(&block_power(&self.blocks, n)) * self.p.inverse()
+    }
+}
+
+impl Jordan<DMat3> {
+    /// Recompose this Jordan form into the original matrix `PJP⁻¹`.
+    pub fn to_matrix(&self) -> DMat3 {
+        self.p * self.j * self.p.inverse()
+    }
+
+    /// Compute the power `Aⁿ = PJⁿP⁻¹` of the original matrix, using the closed form of `Jⁿ`.
+    pub fn power(&self, n: u32) -> DMat3 {
+        self.p * DMat3::from_cols_array_2d(&block_power(&self.blocks, n)) * self.p.inverse()
+    }
+}
+
+/// Find the real Jordan form of a 2D matrix, or `None` if it has any non-finite entries.
+///
+/// Eigenvalues come in the same order as [`eigen_2d`].
+///
+/// ```
+/// # use trinity::math::{jordan_form_2d, Eigenvalue, JordanBlock};
+/// # use approx::assert_relative_eq;
+/// # use glam::DMat2;
+/// let shear = DMat2::from_cols_array(&[2., 0., 1., 2.]);
+/// let jordan = jordan_form_2d(shear).unwrap();
+///
+/// assert_eq!(
+///     jordan.blocks,
+///     vec![JordanBlock { value: Eigenvalue::Real(2.), size: 2 }]
+/// );
+/// assert_eq!(jordan.j, DMat2::from_cols_array(&[2., 0., 1., 2.]));
+/// assert_relative_eq!(jordan.power(10), DMat2::from_cols_array(&[1024., 0., 5120., 1024.]));
+/// ```
+pub fn jordan_form_2d(matrix: DMat2) -> Option<Jordan<DMat2>> {
+    let pairs = eigen_2d(matrix);
+    let mut columns = vec![];
+    let mut blocks = vec![];
+
+    for pair in &pairs {
+        match pair.value {
+            Eigenvalue::Real(value) => {
+                let shifted = matrix - DMat2::from_diagonal(DVec2::splat(value));
+                let others = pairs.iter().filter(|other| other.value != pair.value);
+                let generalised = others.fold(DMat2::IDENTITY, |product, other| {
+                    product * (matrix - DMat2::from_diagonal(DVec2::splat(real(other))))
+                });
+                real_chains(
+                    pair,
+                    |w| shifted * w,
+                    [generalised.x_axis, generalised.y_axis],
+                    |a, b| a.perp_dot(b).abs(),
+                    &mut columns,
+                    &mut blocks,
+                );
+            }
+            Eigenvalue::Complex { re, im } if im > 0. => {
+                let lambda = Complex::new(re, im);
+                let rows = matrix
+                    .transpose()
+                    .to_cols_array_2d()
+                    .map(|row| row.map(Complex::from));
+                let m = |row: usize, column: usize| {
+                    rows[row][column] - if row == column { lambda } else { Complex::ZERO }
+                };
+
+                let vector = [[m(0, 1), -m(0, 0)], [m(1, 1), -m(1, 0)]]
+                    .into_iter()
+                    .max_by(|a, b| norm_squared(a).total_cmp(&norm_squared(b)))?;
+                columns.push(DVec2::from_array(vector.map(|entry| entry.re)));
+                columns.push(DVec2::from_array(vector.map(|entry| entry.im)));
+                blocks.push(JordanBlock {
+                    value: pair.value,
+                    size: 1,
+                });
+            }
+            Eigenvalue::Complex { .. } => {}
+        }
+    }
+
+    let [x_axis, y_axis] = columns.try_into().ok()?;
+    let p = DMat2::from_cols(x_axis, y_axis);
+    (p.determinant() != 0.).then(|| Jordan {
+        p,
+        j: DMat2::from_cols_array_2d(&block_power(&blocks, 1)),
+        blocks,
+    })
+}
+
+/// Find the real Jordan form of a 3D matrix, or `None` if it has any non-finite entries. See
+/// [`jordan_form_2d`].
+///
+/// Eigenvalues come in the same order as [`eigen_3d`].
+pub fn jordan_form_3d(matrix: DMat3) -> Option<Jordan<DMat3>> {
+    let pairs = eigen_3d(matrix);
+    let mut columns = vec![];
+    let mut blocks = vec![];
+
+    for pair in &pairs {
+        match pair.value {
+            Eigenvalue::Real(value) => {
+                let shifted = matrix - DMat3::from_diagonal(DVec3::splat(value));
+                let others = pairs.iter().filter(|other| other.value != pair.value);
+                let generalised = others.fold(DMat3::IDENTITY, |product, other| {
+                    product * (matrix - DMat3::from_diagonal(DVec3::splat(real(other))))
+                });
+                real_chains(
+                    pair,
+                    |w| shifted * w,
+                    [generalised.x_axis, generalised.y_axis, generalised.z_axis],
+                    |a, b| a.cross(b).length(),
+                    &mut columns,
+                    &mut blocks,
+                );
+            }
+            Eigenvalue::Complex { re, im } if im > 0. => {
+                let lambda = Complex::new(re, im);
+                let rows = matrix
+                    .transpose()
+                    .to_cols_array_2d()
+                    .map(|row| row.map(Complex::from));
+                let m = |row: usize, column: usize| {
+                    rows[row][column] - if row == column { lambda } else { Complex::ZERO }
+                };
+
+                // A - λI has rank 2, so the cross product of two independent rows spans its
+                // null space
+                let cross = |a: usize, b: usize| {
+                    [
+                        m(a, 1) * m(b, 2) - m(a, 2) * m(b, 1),
+                        m(a, 2) * m(b, 0) - m(a, 0) * m(b, 2),
+                        m(a, 0) * m(b, 1) - m(a, 1) * m(b, 0),
+                    ]
+                };
+                let vector = [cross(0, 1), cross(0, 2), cross(1, 2)]
+                    .into_iter()
+                    .max_by(|a, b| norm_squared(a).total_cmp(&norm_squared(b)))?;
+                columns.push(DVec3::from_array(vector.map(|entry| entry.re)));
+                columns.push(DVec3::from_array(vector.map(|entry| entry.im)));
+                blocks.push(JordanBlock {
+                    value: pair.value,
+                    size: 1,
+                });
+            }
+            Eigenvalue::Complex { .. } => {}
+        }
+    }
+
+    let [x_axis, y_axis, z_axis] = columns.try_into().ok()?;
+    let p = DMat3::from_cols(x_axis, y_axis, z_axis);
+    (p.determinant() != 0.).then(|| Jordan {
+        p,
+        j: DMat3::from_cols_array_2d(&block_power(&blocks, 1)),
+        blocks,
+    })
+}
+
+/// Get the value of a real eigenvalue, or 0 for a complex one.
+fn real<V>(pair: &Eigenpair<V>) -> f64 {
+    pair.value.as_real().unwrap_or(0.)
+}
+
+/// The sum of the squared magnitudes of the entries of a complex vector.
+fn norm_squared<const N: usize>(vector: &[Complex; N]) -> f64 {
+    vector.iter().map(|entry| entry.abs_squared()).sum()
+}
+
+/// Push the columns of `P` and the blocks of `J` for a real eigenvalue.
+///
+/// Here, `shifted` applies `A - λI`, the columns of `generalised` span the generalised eigenspace
+/// of `λ`, and `independence` measures how far two unit vectors are from being parallel.
+fn real_chains<V>(
+    pair: &Eigenpair<V>,
+    shifted: impl Fn(V) -> V,
+    generalised: impl IntoIterator<Item = V>,
+    independence: impl Fn(V, V) -> f64,
+    columns: &mut Vec<V>,
+    blocks: &mut Vec<JordanBlock>,
+) where
+    V: Copy + VectorLength,
+{
+    let single = JordanBlock {
+        value: pair.value,
+        size: 1,
+    };
+    let geometric = pair.vectors.len();
+
+    if geometric >= pair.multiplicity {
+        columns.extend(pair.vectors.iter().take(pair.multiplicity));
+        blocks.extend(std::iter::repeat_n(single, pair.multiplicity));
+        return;
+    }
+
+    // The longest chain has length multiplicity - geometric + 1, so it starts from the vector in
+    // the generalised eigenspace which survives the most applications of A - λI
+    let chain_length = pair.multiplicity - geometric + 1;
+    let apply = |w: V, times: usize| (0..times).fold(w, |w, _| shifted(w));
+    let Some(start) = generalised
+        .into_iter()
+        .filter(|w| w.length() > 0.)
+        .map(|w| w.normalised())
+        .max_by(|&a, &b| {
+            apply(a, chain_length - 1)
+                .length()
+                .total_cmp(&apply(b, chain_length - 1).length())
+        })
+    else {
+        return;
+    };
+
+    let chain: Vec<V> = (0..chain_length)
+        .rev()
+        .map(|times| apply(start, times))
+        .collect();
+    columns.extend(&chain);
+    blocks.push(JordanBlock {
+        value: pair.value,
+        size: chain_length,
+    });
+
+    // Any remaining eigenvectors get their own blocks, choosing the ones furthest from the
+    // eigenvector at the start of the chain
+    let head = chain[0].normalised();
+    let mut others: Vec<V> = pair.vectors.clone();
+    others.sort_by(|&a, &b| independence(b, head).total_cmp(&independence(a, head)));
+    for &vector in others.iter().take(geometric - 1) {
+        columns.push(vector);
+        blocks.push(single);
+    }
+}
+
+/// A vector with a length, so that [`real_chains`] can work in 2D and 3D.
+trait VectorLength: Sized {
+    /// The Euclidean length of this vector.
+    fn length(self) -> f64;
+
+    /// This vector scaled to have length 1.
+    fn normalised(self) -> Self;
+}
+
+impl VectorLength for DVec2 {
+    fn length(self) -> f64 {
+        DVec2::length(self)
+    }
+
+    fn normalised(self) -> Self {
+        self.normalize()
+    }
+}
+
+impl VectorLength for DVec3 {
+    fn length(self) -> f64 {
+        DVec3::length(self)
+    }
+
+    fn normalised(self) -> Self {
+        self.normalize()
+    }
+}
+
+/// The binomial coefficient `n choose k`.
+fn binomial(n: u32, k: usize) -> f64 {
+    (0..k).fold(1., |product, i| {
+        product * (n as f64 - i as f64) / (i as f64 + 1.)
+    })
+}
+
+/// Find the columns of `Jⁿ` for the Jordan form with these blocks, in closed form.
+///
+/// A real block of size `k` has `(n choose d) λⁿ⁻ᵈ` on its `d`th superdiagonal, and a complex
+/// block `r [cos θ, sin θ; -sin θ, cos θ]` has the power `rⁿ [cos nθ, sin nθ; -sin nθ, cos nθ]`.
+fn block_power<const N: usize>(blocks: &[JordanBlock], n: u32) -> [[f64; N]; N] {
+    let mut columns = [[0.; N]; N];
+    let mut offset = 0;
+
+    for block in blocks {
+        match block.value {
+            Eigenvalue::Real(value) => {
+                for row in 0..block.size {
+                    for column in row..block.size {
+                        let d = column - row;
+                        columns[offset + column][offset + row] = match n.checked_sub(d as u32) {
+                            Some(rest) => binomial(n, d) * value.powf(f64::from(rest)),
+                            None => 0.,
+                        };
+                    }
+                }
+                offset += block.size;
+            }
+            Eigenvalue::Complex { re, im } => {
+                let power = Complex::new(re, im).powc(Complex::from(n as f64));
+                let (re, im) = if n == 0 {
+                    (1., 0.)
+                } else {
+                    (power.re, power.im)
+                };
+                columns[offset][offset] = re;
+                columns[offset][offset + 1] = -im;
+                columns[offset + 1][offset] = im;
+                columns[offset + 1][offset + 1] = re;
+                offset += 2;
+            }
+        }
+    }
+
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::integer_power;
+    use approx::assert_relative_eq;
+
+    /// Check that the Jordan form recomposes to the matrix and gives the right powers.
+    macro_rules! assert_jordan {
+        ($jordan:expr, $matrix:expr) => {{
+            let jordan = $jordan;
+            let matrix = $matrix;
+            assert_relative_eq!(jordan.to_matrix(), matrix, epsilon = 0.000001);
+            assert_relative_eq!(jordan.p * jordan.j, matrix * jordan.p, epsilon = 0.000001);
+            for n in [0, 1, 2, 5] {
+                let expected = integer_power(matrix, n);
+                let tolerance =
+                    0.000001 * (1. + expected.abs().to_cols_array().iter().sum::<f64>());
+                assert_relative_eq!(jordan.power(n), expected, epsilon = tolerance);
+            }
+        }};
+    }
+
+    #[test]
+    fn jordan_form_2d_matrices() {
+        let real = |value, size| JordanBlock {
+            value: Eigenvalue::Real(value),
+            size,
+        };
+
+        let diagonalisable = DMat2::from_cols_array(&[2., 1., 1., 2.]);
+        let jordan = jordan_form_2d(diagonalisable).unwrap();
+        assert_eq!(jordan.blocks, vec![real(3., 1), real(1., 1)]);
+        assert_relative_eq!(jordan.j, DMat2::from_diagonal(DVec2::new(3., 1.)));
+        assert_jordan!(jordan, diagonalisable);
+
+        let scalar = DMat2::IDENTITY * 3.;
+        let jordan = jordan_form_2d(scalar).unwrap();
+        assert_eq!(jordan.blocks, vec![real(3., 1), real(3., 1)]);
+        assert_jordan!(jordan, scalar);
+
+        let shear = DMat2::from_cols_array(&[1., 0., -3., 1.]);
+        let jordan = jordan_form_2d(shear).unwrap();
+        assert_eq!(jordan.blocks, vec![real(1., 2)]);
+        assert_eq!(jordan.j, DMat2::from_cols_array(&[1., 0., 1., 1.]));
+        assert_jordan!(jordan, shear);
+
+        let rotation = DMat2::from_angle(0.5) * 2.;
+        let jordan = jordan_form_2d(rotation).unwrap();
+        assert_eq!(jordan.blocks.len(), 1);
+        assert_relative_eq!(
+            jordan.j,
+            DMat2::from_cols_array(&[
+                2. * 0.5f64.cos(),
+                -2. * 0.5f64.sin(),
+                2. * 0.5f64.sin(),
+                2. * 0.5f64.cos()
+            ]),
+            epsilon = 0.000000001
+        );
+        assert_jordan!(jordan, rotation);
+
+        assert_eq!(
+            jordan_form_2d(DMat2::from_diagonal(DVec2::splat(f64::NAN))),
+            None
+        );
+
+        for _ in 0..100 {
+            let matrix = DMat2::from_cols_array(&rand::random::<[f64; 4]>().map(|x| x * 4. - 2.));
+            assert_jordan!(jordan_form_2d(matrix).unwrap(), matrix);
+        }
+    }
+
+    #[test]
+    fn jordan_form_3d_matrices() {
+        let real = |value, size| JordanBlock {
+            value: Eigenvalue::Real(value),
+            size,
+        };
+
+        let chain_3 = DMat3::from_cols_array(&[2., 0., 0., 1., 2., 0., 0., 1., 2.]);
+        let jordan = jordan_form_3d(chain_3).unwrap();
+        assert_eq!(jordan.blocks, vec![real(2., 3)]);
+        assert_eq!(jordan.j, chain_3);
+        assert_jordan!(jordan, chain_3);
+
+        let chain_2_1 = DMat3::from_cols_array(&[2., 0., 0., 1., 2., 0., 0., 0., 2.]);
+        let jordan = jordan_form_3d(chain_2_1).unwrap();
+        assert_eq!(jordan.blocks, vec![real(2., 2), real(2., 1)]);
+        assert_eq!(jordan.j, chain_2_1);
+        assert_jordan!(jordan, chain_2_1);
+
+        let defective = DMat3::from_cols_array(&[5., 1., 0., 0., 2., 0., 0., 1., 2.]);
+        let jordan = jordan_form_3d(defective).unwrap();
+        assert_eq!(
+            jordan
+                .blocks
+                .iter()
+                .map(|block| block.size)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_relative_eq!(
+            jordan.j,
+            DMat3::from_cols_array(&[5., 0., 0., 0., 2., 0., 0., 1., 2.]),
+            epsilon = 0.000000001
+        );
+        assert_jordan!(jordan, defective);
+
+        let rotation = DMat3::from_rotation_z(1.) * DMat3::from_diagonal(DVec3::new(2., 2., 3.));
+        let jordan = jordan_form_3d(rotation).unwrap();
+        assert_eq!(jordan.blocks[0], real(3., 1));
+        assert!(matches!(jordan.blocks[1].value, Eigenvalue::Complex { im, .. } if im > 0.));
+        assert_jordan!(jordan, rotation);
+
+        for _ in 0..100 {
+            let matrix = DMat3::from_cols_array(&rand::random::<[f64; 9]>().map(|x| x * 4. - 2.));
+            assert_jordan!(jordan_form_3d(matrix).unwrap(), matrix);
+        }
+    }
+}
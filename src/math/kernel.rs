@@ -0,0 +1,141 @@
+//! This module provides the kernel and column space of 2D and 3D matrices, which show how a
+//! singular matrix collapses space. See [`Subspaces`].
+
+use super::{rank_2d, rank_3d, svd_2d, svd_3d};
+use glam::{DMat2, DMat3, DVec2, DVec3};
+
+/// Orthonormal bases for the column space and kernel of a matrix.
+///
+/// The column space is the line or plane that everything gets mapped onto, and the kernel is the
+/// set of directions that get squashed to zero. Their dimensions always add up to the dimension of
+/// the matrix, and the dimension of the column space is the rank from [`rank_2d`] or [`rank_3d`].
+///
+/// ```
+/// # use trinity::math::subspaces_2d;
+/// # use approx::assert_relative_eq;
+/// # use glam::{DMat2, DVec2};
+/// let matrix = DMat2::from_cols_array(&[1., 2., 2., 4.]);
+/// let subspaces = subspaces_2d(matrix).unwrap();
+/// assert_eq!(subspaces.column_space.len(), 1);
+/// assert_eq!(subspaces.kernel.len(), 1);
+/// assert_relative_eq!(matrix * subspaces.kernel[0], DVec2::ZERO, epsilon = 0.000000001);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subspaces<V> {
+    /// An orthonormal basis of the column space, with the directions that get stretched the most
+    /// first.
+    pub column_space: Vec<V>,
+
+    /// An orthonormal basis of the kernel. Each vector is only defined up to sign, and a kernel
+    /// with more than one dimension could use any orthonormal basis.
+    pub kernel: Vec<V>,
+}
+
+/// Find the [`Subspaces`] of a 2D matrix, or `None` if it has any non-finite entries.
+pub fn subspaces_2d(matrix: DMat2) -> Option<Subspaces<DVec2>> {
+    let svd = svd_2d(matrix)?;
+    let rank = rank_2d(matrix)?;
+    let v = svd.v_transpose.transpose();
+
+    Some(Subspaces {
+        column_space: (0..rank).map(|i| svd.u.col(i)).collect(),
+        kernel: (rank..2).map(|i| v.col(i)).collect(),
+    })
+}
+
+/// Find the [`Subspaces`] of a 3D matrix, or `None` if it has any non-finite entries.
+pub fn subspaces_3d(matrix: DMat3) -> Option<Subspaces<DVec3>> {
+    let svd = svd_3d(matrix)?;
+    let rank = rank_3d(matrix)?;
+    let v = svd.v_transpose.transpose();
+
+    Some(Subspaces {
+        column_space: (0..rank).map(|i| svd.u.col(i)).collect(),
+        kernel: (rank..3).map(|i| v.col(i)).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn subspaces_2d_matrices() {
+        let subspaces = subspaces_2d(DMat2::IDENTITY * 3.).unwrap();
+        assert_eq!(subspaces.column_space.len(), 2);
+        assert!(subspaces.kernel.is_empty());
+
+        let subspaces = subspaces_2d(DMat2::ZERO).unwrap();
+        assert!(subspaces.column_space.is_empty());
+        assert_eq!(subspaces.kernel.len(), 2);
+
+        // Everything gets mapped onto the line through [1; 2], and [2; -1] gets squashed
+        let subspaces = subspaces_2d(DMat2::from_cols_array(&[1., 2., 2., 4.])).unwrap();
+        let [column] = subspaces.column_space[..] else {
+            panic!("The column space should be a line");
+        };
+        let [kernel] = subspaces.kernel[..] else {
+            panic!("The kernel should be a line");
+        };
+        let sign = column.x.signum();
+        assert_relative_eq!(
+            column * sign,
+            DVec2::new(1., 2.).normalize(),
+            epsilon = 0.000000001
+        );
+        let sign = kernel.x.signum();
+        assert_relative_eq!(
+            kernel * sign,
+            DVec2::new(2., -1.).normalize(),
+            epsilon = 0.000000001
+        );
+
+        assert_eq!(subspaces_2d(DMat2::IDENTITY * f64::NAN), None);
+        assert_eq!(subspaces_2d(DMat2::IDENTITY * f64::INFINITY), None);
+    }
+
+    #[test]
+    fn subspaces_3d_matrices() {
+        let matrices = [
+            DMat3::IDENTITY,
+            DMat3::ZERO,
+            DMat3::from_cols_array(&[1., 4., 7., 2., 5., 8., 3., 6., 9.]),
+            DMat3::from_cols_array(&[1., 2., 3., 2., 4., 6., -1., -2., -3.]),
+            DMat3::from_cols(DVec3::ZERO, DVec3::Z, DVec3::X),
+        ];
+
+        for matrix in matrices {
+            let Subspaces {
+                column_space,
+                kernel,
+            } = subspaces_3d(matrix).unwrap();
+            assert_eq!(Some(column_space.len()), rank_3d(matrix));
+            assert_eq!(column_space.len() + kernel.len(), 3);
+
+            // Together, the bases are orthonormal within each subspace
+            for basis in [&column_space, &kernel] {
+                for (i, a) in basis.iter().enumerate() {
+                    for (j, b) in basis.iter().enumerate() {
+                        let expected = if i == j { 1. } else { 0. };
+                        assert_relative_eq!(a.dot(*b), expected, epsilon = 0.000000001);
+                    }
+                }
+            }
+
+            for vector in &kernel {
+                assert_relative_eq!(matrix * *vector, DVec3::ZERO, epsilon = 0.000000001);
+            }
+
+            // Every column of the matrix lies in the column space
+            for i in 0..3 {
+                let column = matrix.col(i);
+                let projected: DVec3 = column_space
+                    .iter()
+                    .map(|basis| basis.dot(column) * *basis)
+                    .sum();
+                assert_relative_eq!(projected, column, epsilon = 0.000000001);
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! This module provides functions to solve linear systems of the form `Ax = b`, either exactly or
+//! in the least-squares sense.
+
+use super::{pinv_2d, pinv_3d, Invertible};
+use glam::{DMat2, DMat3, DVec2, DVec3};
+
+/// Solve the 2D linear system `Ax = b` for `x`, returning `None` if `A` is singular under the
+/// relative tolerance `epsilon`. See [`Invertible`].
+///
+/// ```
+/// # use trinity::math::solve_2d;
+/// # use glam::{DMat2, DVec2};
+/// let small = DMat2::IDENTITY * 0.00001;
+/// assert!(solve_2d(small, DVec2::ONE, 0.000000001).is_some());
+/// assert_eq!(solve_2d(DMat2::ZERO, DVec2::ONE, 0.000000001), None);
+/// ```
+pub fn solve_2d(a: DMat2, b: DVec2, epsilon: f64) -> Option<DVec2> {
+    a.checked_inverse(epsilon).map(|inverse| inverse * b)
+}
+
+/// Solve the 3D linear system `Ax = b` for `x`, returning `None` if `A` is singular under the
+/// relative tolerance `epsilon`. See [`Invertible`].
+pub fn solve_3d(a: DMat3, b: DVec3, epsilon: f64) -> Option<DVec3> {
+    a.checked_inverse(epsilon).map(|inverse| inverse * b)
+}
+
+/// The least-squares solution to a linear system `Ax = b`. See [`lstsq_2d`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LeastSquares<V> {
+    /// The shortest vector `x` which makes `Ax` as close as possible to `b`.
+    pub solution: V,
+
+    /// The norm of the residual `Ax - b`, which is zero exactly when the system is consistent.
+    pub residual: f64,
+}
+
+/// Solve the 2D linear system `Ax = b` for `x` in the least-squares sense, returning `None` if
+/// anything has non-finite entries.
+///
+/// If `A` is singular, then there might be no solution, or infinitely many. This finds the `x`
+/// that makes `Ax` as close as possible to `b`, and picks the shortest one if there are several.
+/// That's `A⁺ b`, using the [pseudoinverse](pinv_2d). If `A` is invertible, this is the same as
+/// [`solve_2d`] with a residual of zero.
+///
+/// ```
+/// # use trinity::math::lstsq_2d;
+/// # use approx::assert_relative_eq;
+/// # use glam::{DMat2, DVec2};
+/// // Both columns point along y = x, so the best we can do is get to (1.5, 1.5)
+/// let a = DMat2::from_cols_array(&[1., 1., 1., 1.]);
+/// let result = lstsq_2d(a, DVec2::new(1., 2.)).unwrap();
+/// assert_relative_eq!(result.solution, DVec2::new(0.75, 0.75), epsilon = 0.000000001);
+/// assert_relative_eq!(result.residual, 0.5f64.sqrt(), epsilon = 0.000000001);
+/// ```
+pub fn lstsq_2d(a: DMat2, b: DVec2) -> Option<LeastSquares<DVec2>> {
+    let solution = pinv_2d(a)? * b;
+    solution.is_finite().then(|| LeastSquares {
+        solution,
+        residual: (a * solution - b).length(),
+    })
+}
+
+/// Solve the 3D linear system `Ax = b` for `x` in the least-squares sense, returning `None` if
+/// anything has non-finite entries. See [`lstsq_2d`].
+pub fn lstsq_3d(a: DMat3, b: DVec3) -> Option<LeastSquares<DVec3>> {
+    let solution = pinv_3d(a)? * b;
+    solution.is_finite().then(|| LeastSquares {
+        solution,
+        residual: (a * solution - b).length(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::EPSILON;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn solve_2d_success() {
+        let a = DMat2::from_cols(DVec2::new(2., 1.), DVec2::new(1., 3.));
+        let x = solve_2d(a, DVec2::new(3., 5.), EPSILON).unwrap();
+        assert_relative_eq!(x, DVec2::new(0.8, 1.4));
+        assert_relative_eq!(a * x, DVec2::new(3., 5.));
+
+        for _ in 0..100 {
+            let a = rand::random::<DMat2>();
+            let b = rand::random::<DVec2>();
+            if let Some(x) = solve_2d(a, b, EPSILON) {
+                assert_relative_eq!(a * x, b, epsilon = 0.000001);
+            }
+        }
+    }
+
+    #[test]
+    fn solve_3d_success() {
+        let a = DMat3::from_cols(
+            DVec3::new(1., 4., 1.),
+            DVec3::new(2., 5., 2.),
+            DVec3::new(3., 6., 4.),
+        );
+        let b = DVec3::new(6., 15., 7.);
+        let x = solve_3d(a, b, EPSILON).unwrap();
+        assert_relative_eq!(x, DVec3::ONE, epsilon = 0.0000000001);
+
+        for _ in 0..100 {
+            let a = rand::random::<DMat3>();
+            let b = rand::random::<DVec3>();
+            if let Some(x) = solve_3d(a, b, EPSILON) {
+                assert_relative_eq!(a * x, b, epsilon = 0.000001);
+            }
+        }
+    }
+
+    #[test]
+    fn solve_singular() {
+        assert_eq!(solve_2d(DMat2::ZERO, DVec2::ONE, EPSILON), None);
+        assert_eq!(
+            solve_2d(
+                DMat2::from_cols(DVec2::new(1., 2.), DVec2::new(2., 4.)),
+                DVec2::ONE,
+                EPSILON
+            ),
+            None
+        );
+        assert_eq!(
+            solve_3d(
+                DMat3::from_cols(DVec3::X, DVec3::Y, DVec3::new(1., 1., 0.)),
+                DVec3::ONE,
+                EPSILON
+            ),
+            None
+        );
+
+        // Singularity is relative to the size of the entries
+        assert_relative_eq!(
+            solve_3d(DMat3::IDENTITY * 0.00001, DVec3::ONE, EPSILON).unwrap(),
+            DVec3::splat(100_000.),
+            max_relative = EPSILON
+        );
+        assert_eq!(
+            solve_2d(
+                DMat2::from_cols(DVec2::new(1., 2.), DVec2::new(2., 4.0001)),
+                DVec2::ONE,
+                0.001
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn lstsq_solutions() {
+        for _ in 0..100 {
+            let a = DMat3::from_cols_array(&rand::random::<[f64; 9]>().map(|x| x * 4. - 2.));
+            let b = DVec3::from_array(rand::random::<[f64; 3]>().map(|x| x * 4. - 2.));
+            let result = lstsq_3d(a, b).unwrap();
+            assert_relative_eq!(
+                result.solution,
+                solve_3d(a, b, EPSILON).unwrap(),
+                epsilon = 0.0001
+            );
+            assert_relative_eq!(result.residual, 0., epsilon = 0.000001);
+
+            // Fitting y = mx + c through three points, with the columns (x, 1, 0) and the
+            // unused third column making the matrix singular
+            let xs = DVec3::from_array(rand::random::<[f64; 3]>());
+            let a = DMat3::from_cols(xs, DVec3::ONE, DVec3::ZERO);
+            let result = lstsq_3d(a, b).unwrap();
+            assert_eq!(result.solution.z, 0.);
+
+            // The residual is orthogonal to the columns at the best fit
+            let residual = a * result.solution - b;
+            assert_relative_eq!(residual.dot(xs), 0., epsilon = 0.000001);
+            assert_relative_eq!(residual.dot(DVec3::ONE), 0., epsilon = 0.000001);
+            assert_relative_eq!(result.residual, residual.length(), epsilon = 0.000000001);
+        }
+
+        let result = lstsq_2d(DMat2::ZERO, DVec2::new(3., 4.)).unwrap();
+        assert_eq!(result.solution, DVec2::ZERO);
+        assert_eq!(result.residual, 5.);
+        assert_eq!(lstsq_2d(DMat2::IDENTITY, DVec2::new(f64::NAN, 0.)), None);
+    }
+}
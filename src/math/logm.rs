@@ -0,0 +1,181 @@
+//! This module provides the principal matrix logarithm of 2D and 3D matrices.
+
+use super::{eigen_2d, eigen_3d, Eigenpair, Eigenvalue, Norm, EPSILON};
+use glam::{DMat2, DMat3};
+use std::ops::{Add, Mul, Sub};
+
+/// The maximum number of square roots to take before using the series. Each square root halves
+/// the logarithm, so this is only reached for absurdly large or small eigenvalues.
+const MAX_SQUARE_ROOTS: i32 = 64;
+
+/// The maximum number of Denman-Beavers iterations when taking a square root. The iteration
+/// converges quadratically, so this is plenty.
+const MAX_ITERATIONS: usize = 100;
+
+/// The number of terms of the series for `log(I + X)` to use once `X` is small enough.
+const SERIES_TERMS: u32 = 40;
+
+/// Find the principal logarithm of a 2D matrix, which is the unique matrix `L` with
+/// `exp(L) = M` whose eigenvalues have imaginary parts strictly between `-π` and `π`.
+///
+/// This only exists as a real matrix if `M` has no real eigenvalues which are zero or negative,
+/// so we return `None` in that case. A rotation by 180° has a repeated eigenvalue of `-1`, so it
+/// has no principal logarithm, but any smaller rotation does.
+pub fn logm_2d(matrix: DMat2) -> Option<DMat2> {
+    let scale = matrix
+        .to_cols_array()
+        .iter()
+        .fold(0f64, |max, x| max.max(x.abs()));
+    has_principal_logarithm(&eigen_2d(matrix), scale)
+        .then(|| inverse_scale_and_square(matrix, DMat2::IDENTITY, DMat2::inverse))
+        .filter(|logarithm| logarithm.is_finite())
+}
+
+/// Find the principal logarithm of a 3D matrix, which is the unique matrix `L` with
+/// `exp(L) = M` whose eigenvalues have imaginary parts strictly between `-π` and `π`.
+///
+/// This only exists as a real matrix if `M` has no real eigenvalues which are zero or negative,
+/// so we return `None` in that case.
+pub fn logm_3d(matrix: DMat3) -> Option<DMat3> {
+    let scale = matrix
+        .to_cols_array()
+        .iter()
+        .fold(0f64, |max, x| max.max(x.abs()));
+    has_principal_logarithm(&eigen_3d(matrix), scale)
+        .then(|| inverse_scale_and_square(matrix, DMat3::IDENTITY, DMat3::inverse))
+        .filter(|logarithm| logarithm.is_finite())
+}
+
+/// Is every real eigenvalue positive, given the magnitude of the largest entry in the matrix?
+fn has_principal_logarithm<V>(eigenpairs: &[Eigenpair<V>], scale: f64) -> bool {
+    !eigenpairs.is_empty()
+        && eigenpairs.iter().all(|pair| match pair.value {
+            Eigenvalue::Real(value) => value > EPSILON * scale,
+            Eigenvalue::Complex { .. } => true,
+        })
+}
+
+/// Find the logarithm by inverse scaling and squaring.
+///
+/// Since `log(M) = 2^s log(M^(1/2^s))`, we take square roots until the matrix is close to the
+/// identity, and then use the series `log(I + X) = X - X²/2 + X³/3 - ...`.
+fn inverse_scale_and_square<T>(matrix: T, identity: T, inverse: impl Fn(&T) -> T) -> T
+where
+    T: Copy
+        + Add<T, Output = T>
+        + Sub<T, Output = T>
+        + Mul<T, Output = T>
+        + Mul<f64, Output = T>
+        + Norm,
+{
+    let mut root = matrix;
+    let mut square_roots = 0;
+    while (root - identity).norm() > 0.25 && square_roots < MAX_SQUARE_ROOTS {
+        root = square_root(root, identity, &inverse);
+        square_roots += 1;
+    }
+
+    let x = root - identity;
+    let mut power = identity;
+    let mut result = x * 0.;
+    for term in 1..=SERIES_TERMS {
+        power = power * x;
+        let sign = if term % 2 == 1 { 1. } else { -1. };
+        result = result + power * (sign / term as f64);
+    }
+
+    result * 2f64.powi(square_roots)
+}
+
+/// Find the principal square root of the matrix with the Denman-Beavers iteration.
+fn square_root<T>(matrix: T, identity: T, inverse: impl Fn(&T) -> T) -> T
+where
+    T: Copy + Add<T, Output = T> + Sub<T, Output = T> + Mul<f64, Output = T> + Norm,
+{
+    let mut y = matrix;
+    let mut z = identity;
+
+    for _ in 0..MAX_ITERATIONS {
+        let next_y = (y + inverse(&z)) * 0.5;
+        let next_z = (z + inverse(&y)) * 0.5;
+        let change = (next_y - y).norm();
+
+        y = next_y;
+        z = next_z;
+        if change <= f64::EPSILON * y.norm() {
+            break;
+        }
+    }
+
+    y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{expm_2d, expm_3d};
+    use approx::assert_relative_eq;
+    use glam::{DVec2, DVec3};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn logm_2d_matrices() {
+        assert_eq!(logm_2d(DMat2::IDENTITY), Some(DMat2::ZERO));
+        assert_relative_eq!(
+            logm_2d(DMat2::from_diagonal(DVec2::new(2., 0.5))).unwrap(),
+            DMat2::from_diagonal(DVec2::new(2f64.ln(), 0.5f64.ln())),
+            epsilon = 0.000000001
+        );
+
+        let generator = DMat2::from_cols(DVec2::Y, -DVec2::X);
+        for angle in [0.1, 1., 3., -3.] {
+            assert_relative_eq!(
+                logm_2d(DMat2::from_angle(angle)).unwrap(),
+                generator * angle,
+                epsilon = 0.000000001
+            );
+        }
+
+        assert_eq!(logm_2d(DMat2::ZERO), None);
+        assert_eq!(logm_2d(DMat2::from_diagonal(DVec2::new(-1., 2.))), None);
+        assert_eq!(logm_2d(DMat2::from_angle(PI)), None);
+        assert_eq!(
+            logm_2d(DMat2::from_cols(DVec2::new(1., 2.), DVec2::new(2., 4.))),
+            None
+        );
+
+        for _ in 0..100 {
+            let matrix = (rand::random::<DMat2>() - DMat2::from_cols_array(&[0.5; 4])) * 3.;
+            assert_relative_eq!(
+                logm_2d(expm_2d(matrix)).unwrap(),
+                matrix,
+                epsilon = 0.000001
+            );
+        }
+    }
+
+    #[test]
+    fn logm_3d_matrices() {
+        assert_eq!(logm_3d(DMat3::IDENTITY), Some(DMat3::ZERO));
+        assert_eq!(logm_3d(DMat3::from_diagonal(DVec3::new(1., 2., -3.))), None);
+
+        let axis = DVec3::new(2., -1., 2.) / 3.;
+        let rotation = DMat3::from_axis_angle(axis, 2.);
+        assert_relative_eq!(
+            expm_3d(logm_3d(rotation).unwrap()),
+            rotation,
+            epsilon = 0.000000001
+        );
+
+        for _ in 0..100 {
+            // Positive definite matrices always have logarithms
+            let random = rand::random::<DMat3>() * 4.;
+            let matrix = random.transpose() * random + DMat3::IDENTITY * 0.1;
+            assert_relative_eq!(
+                expm_3d(logm_3d(matrix).unwrap()),
+                matrix,
+                epsilon = 0.000001
+            );
+        }
+    }
+}
@@ -1,5 +1,68 @@
 //! This module provides some simple mathematical functions for general utility.
 
+mod adjugate;
+mod affine;
+mod complex;
+mod decompose;
+mod eigen;
+mod expm;
+mod interpolate;
+mod invariants;
+mod jordan;
+mod kernel;
+mod linear_system;
+mod logm;
+mod norms;
+mod pinv;
+mod predicates;
+mod projection;
+mod qr;
+mod random;
+mod rank;
+mod rotation;
+mod rref;
 mod square_multiply;
+mod svd;
+mod transition;
 
-pub use self::square_multiply::integer_power;
+/// The default tolerance used when comparing floats throughout the crate, and the default
+/// [`epsilon`](crate::matrix::expression::EvalOptions::epsilon) of the evaluator.
+///
+/// Comparisons between entries of a matrix usually scale this by the magnitude of the largest
+/// entry, so that scaling a matrix never changes the answer.
+pub(crate) const EPSILON: f64 = 0.000000001;
+
+pub use self::{
+    adjugate::{adjugate_2d, adjugate_3d, cofactor_2d, cofactor_3d},
+    affine::{
+        affine_2d, is_affine_2d, snap_affine_2d, split_affine_2d, transform_point_2d,
+        translation_2d,
+    },
+    complex::Complex,
+    decompose::{decompose_2d, decompose_3d, Decomposition2d, Decomposition3d},
+    eigen::{eigen_2d, eigen_3d, Eigenpair, Eigenvalue},
+    expm::{expm_2d, expm_3d},
+    interpolate::{interpolate, polar_2d, polar_3d, Interpolate, Interpolation, Polar},
+    invariants::{invariants_2d, invariants_3d, trace_2d, trace_3d, Invariants},
+    jordan::{jordan_form_2d, jordan_form_3d, Jordan, JordanBlock},
+    kernel::{subspaces_2d, subspaces_3d, Subspaces},
+    linear_system::{lstsq_2d, lstsq_3d, solve_2d, solve_3d, LeastSquares},
+    logm::{logm_2d, logm_3d},
+    norms::{MatrixNorm, MatrixNorms, Norm},
+    pinv::{pinv_2d, pinv_3d},
+    predicates::{MatrixPredicates, MatrixProperty},
+    projection::{line_projection_2d, line_projection_3d, plane_projection_3d},
+    qr::{qr_2d, qr_3d, Qr},
+    random::{RandomDistribution, RandomMatrix},
+    rank::{rank_2d, rank_2d_within, rank_3d, rank_3d_within},
+    rotation::{rotation_to_axis_angle, rotation_to_euler, rotation_to_quat},
+    rref::{rref, RowOp, Rref},
+    square_multiply::{
+        checked_integer_power, integer_power, signed_integer_power, CheckedProduct, Invertible,
+        PowerError, PowerZero,
+    },
+    svd::{svd_2d, svd_3d, Svd, SVD_TOLERANCE},
+    transition::{Easing, Keyframe, Timeline, Transition},
+};
+
+pub(crate) use self::norms::frobenius_norm;
@@ -0,0 +1,223 @@
+//! This module provides the [`Norm`] trait for vectors and matrices, and the [`MatrixNorms`]
+//! trait for the other common matrix norms.
+
+use super::{svd_2d, svd_3d};
+use glam::{DMat2, DMat3, DVec2, DVec3};
+use std::fmt;
+
+/// Something which has a norm (a notion of size).
+///
+/// For vectors, this is the Euclidean length. For matrices, this is the Frobenius norm, which is
+/// the square root of the sum of the squares of all the entries.
+pub trait Norm {
+    /// Compute the norm of this value.
+    fn norm(&self) -> f64;
+}
+
+impl Norm for DVec2 {
+    fn norm(&self) -> f64 {
+        self.length()
+    }
+}
+
+impl Norm for DVec3 {
+    fn norm(&self) -> f64 {
+        self.length()
+    }
+}
+
+impl Norm for DMat2 {
+    fn norm(&self) -> f64 {
+        frobenius_norm(&self.to_cols_array())
+    }
+}
+
+impl Norm for DMat3 {
+    fn norm(&self) -> f64 {
+        frobenius_norm(&self.to_cols_array())
+    }
+}
+
+/// Find the square root of the sum of the squares of these entries, without overflowing.
+///
+/// The entries are scaled down by the largest absolute entry before squaring, so entries of around
+/// `1e200` don't overflow to infinity, even though their squares would.
+pub(crate) fn frobenius_norm(entries: &[f64]) -> f64 {
+    let scale = entries.iter().fold(0f64, |max, x| max.max(x.abs()));
+
+    // This also covers entries which are all NaN, since `max` skips NaN
+    if scale == 0. || scale.is_infinite() {
+        return entries.iter().map(|x| x * x).sum::<f64>().sqrt();
+    }
+
+    scale
+        * entries
+            .iter()
+            .map(|x| (x / scale) * (x / scale))
+            .sum::<f64>()
+            .sqrt()
+}
+
+/// A way of measuring the size of a matrix. See [`MatrixNorms`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatrixNorm {
+    /// The Frobenius norm, which is the square root of the sum of the squares of all the entries.
+    /// This is what [`Norm`] gives for matrices.
+    Frobenius,
+
+    /// The spectral norm, which is the largest singular value. This is the most that the matrix
+    /// can stretch any vector by.
+    Spectral,
+
+    /// The largest absolute value of any entry.
+    MaxEntry,
+}
+
+impl MatrixNorm {
+    /// Every norm, in order.
+    pub const ALL: [Self; 3] = [Self::Frobenius, Self::Spectral, Self::MaxEntry];
+
+    /// The name of this norm in the expression language, like `fro`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Frobenius => "fro",
+            Self::Spectral => "spectral",
+            Self::MaxEntry => "max",
+        }
+    }
+}
+
+/// Norms are displayed as the quoted string used to choose them in the expression language, like
+/// `"fro"`.
+impl fmt::Display for MatrixNorm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.name())
+    }
+}
+
+/// The common norms of a matrix, on top of the Frobenius norm from [`Norm`].
+pub trait MatrixNorms: Norm {
+    /// The spectral norm, which is the largest singular value. This is NaN if any entries aren't
+    /// finite.
+    fn spectral_norm(&self) -> f64;
+
+    /// The largest absolute value of any entry.
+    fn max_entry_norm(&self) -> f64;
+
+    /// The given norm of this matrix.
+    fn matrix_norm(&self, norm: MatrixNorm) -> f64 {
+        match norm {
+            MatrixNorm::Frobenius => self.norm(),
+            MatrixNorm::Spectral => self.spectral_norm(),
+            MatrixNorm::MaxEntry => self.max_entry_norm(),
+        }
+    }
+}
+
+impl MatrixNorms for DMat2 {
+    fn spectral_norm(&self) -> f64 {
+        svd_2d(*self).map_or(f64::NAN, |svd| svd.singular_values.x)
+    }
+
+    fn max_entry_norm(&self) -> f64 {
+        self.abs().to_cols_array().into_iter().fold(0., f64::max)
+    }
+}
+
+impl MatrixNorms for DMat3 {
+    fn spectral_norm(&self) -> f64 {
+        svd_3d(*self).map_or(f64::NAN, |svd| svd.singular_values.x)
+    }
+
+    fn max_entry_norm(&self) -> f64 {
+        self.abs().to_cols_array().into_iter().fold(0., f64::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn norm_vectors() {
+        assert_relative_eq!(DVec2::new(3., 4.).norm(), 5.);
+        assert_relative_eq!(DVec2::ZERO.norm(), 0.);
+        assert_relative_eq!(DVec3::new(1., 2., 2.).norm(), 3.);
+        assert_relative_eq!(DVec3::new(-2., 3., -6.).norm(), 7.);
+    }
+
+    #[test]
+    fn norm_matrices() {
+        assert_relative_eq!(DMat2::IDENTITY.norm(), 2f64.sqrt());
+        assert_relative_eq!(
+            DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., 4.)).norm(),
+            30f64.sqrt()
+        );
+        assert_relative_eq!(DMat3::IDENTITY.norm(), 3f64.sqrt());
+        assert_relative_eq!(
+            DMat3::from_cols(
+                DVec3::new(1., 4., 7.),
+                DVec3::new(2., 5., 8.),
+                DVec3::new(3., 6., 9.),
+            )
+            .norm(),
+            285f64.sqrt()
+        );
+
+        // Squaring these entries would overflow, but the norm itself is fine
+        assert_relative_eq!(
+            (DMat2::IDENTITY * 1e200).norm(),
+            2f64.sqrt() * 1e200,
+            max_relative = 0.000000001
+        );
+        assert_relative_eq!(
+            DMat3::from_diagonal(DVec3::new(3e300, 4e300, 0.)).norm(),
+            5e300,
+            max_relative = 0.000000001
+        );
+        assert_relative_eq!(
+            (DMat2::IDENTITY * 1e-200).norm(),
+            2f64.sqrt() * 1e-200,
+            max_relative = 0.000000001
+        );
+
+        assert_eq!(
+            DMat2::from_diagonal(DVec2::new(f64::INFINITY, 1.)).norm(),
+            f64::INFINITY
+        );
+        assert!(DMat2::from_cols_array(&[f64::NAN, 0., 0., 1.])
+            .norm()
+            .is_nan());
+        assert!((DMat2::IDENTITY * f64::NAN).norm().is_nan());
+    }
+
+    #[test]
+    fn matrix_norms() {
+        let m = DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., -4.));
+        assert_relative_eq!(m.matrix_norm(MatrixNorm::Frobenius), 30f64.sqrt());
+        assert_relative_eq!(m.matrix_norm(MatrixNorm::MaxEntry), 4.);
+
+        // The singular values of a diagonal matrix are the absolute values of its diagonal
+        let diagonal = DMat3::from_diagonal(DVec3::new(2., -5., 3.));
+        assert_relative_eq!(diagonal.spectral_norm(), 5., epsilon = 0.000000001);
+        assert_relative_eq!(
+            DMat2::from_angle(1.2).spectral_norm(),
+            1.,
+            epsilon = 0.000000001
+        );
+        assert!(DMat2::from_cols_array(&[f64::NAN, 0., 0., 1.])
+            .spectral_norm()
+            .is_nan());
+
+        for _ in 0..100 {
+            let m = DMat3::from_cols_array(&rand::random::<[f64; 9]>().map(|x| x * 4. - 2.));
+            let spectral = m.spectral_norm();
+            assert!(m.max_entry_norm() <= spectral + 0.000000001);
+            assert!(spectral <= m.norm() + 0.000000001);
+        }
+
+        assert_eq!(MatrixNorm::Spectral.to_string(), "\"spectral\"");
+    }
+}
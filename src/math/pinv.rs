@@ -0,0 +1,119 @@
+//! This module provides the Moore-Penrose pseudoinverse of 2D and 3D matrices.
+//!
+//! The pseudoinverse `A⁺` of a matrix `A` is the closest thing it has to an inverse. If `A` is
+//! invertible, then `A⁺` is just `A⁻¹`. Otherwise, `A` squashes space down onto a line or plane,
+//! and `A⁺ b` is the shortest vector `x` that makes `Ax` as close as possible to `b`. That means
+//! `x` solves `Ax = b` in the least-squares sense, even if no exact solution exists.
+//!
+//! It comes from the [singular value decomposition](svd_2d) `A = U Σ Vᵀ` as `A⁺ = V Σ⁺ Uᵀ`,
+//! where `Σ⁺` inverts the non-zero singular values and leaves the zero ones as zero.
+
+use super::{svd_2d, svd_3d, SVD_TOLERANCE};
+use glam::{DMat2, DMat3, DVec2, DVec3};
+
+/// Find the pseudoinverse of a 2D matrix, or `None` if it has any non-finite entries.
+///
+/// Singular values at most [`SVD_TOLERANCE`] times the largest singular value count as zero.
+///
+/// ```
+/// # use trinity::math::pinv_2d;
+/// # use approx::assert_relative_eq;
+/// # use glam::DMat2;
+/// // This projects onto the line y = 2x, so its pseudoinverse is a scaled copy of it
+/// let singular = DMat2::from_cols_array(&[1., 2., 2., 4.]);
+/// assert_relative_eq!(pinv_2d(singular).unwrap(), singular / 25., epsilon = 0.000000001);
+///
+/// let invertible = DMat2::from_cols_array(&[2., 1., 1., 3.]);
+/// assert_relative_eq!(pinv_2d(invertible).unwrap(), invertible.inverse(), epsilon = 0.000000001);
+/// ```
+pub fn pinv_2d(matrix: DMat2) -> Option<DMat2> {
+    let svd = svd_2d(matrix)?;
+    let inverted = invert_singular_values(svd.singular_values.to_array());
+    Some(
+        svd.v_transpose.transpose()
+            * DMat2::from_diagonal(DVec2::from_array(inverted))
+            * svd.u.transpose(),
+    )
+}
+
+/// Find the pseudoinverse of a 3D matrix, or `None` if it has any non-finite entries. See
+/// [`pinv_2d`].
+pub fn pinv_3d(matrix: DMat3) -> Option<DMat3> {
+    let svd = svd_3d(matrix)?;
+    let inverted = invert_singular_values(svd.singular_values.to_array());
+    Some(
+        svd.v_transpose.transpose()
+            * DMat3::from_diagonal(DVec3::from_array(inverted))
+            * svd.u.transpose(),
+    )
+}
+
+/// Invert the given singular values, which are sorted from largest to smallest, leaving the ones
+/// which count as zero as zero.
+fn invert_singular_values<const N: usize>(singular_values: [f64; N]) -> [f64; N] {
+    let tolerance = SVD_TOLERANCE * singular_values[0];
+    singular_values.map(|value| if value > tolerance { value.recip() } else { 0. })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    /// Check the four Moore-Penrose conditions, which uniquely define the pseudoinverse.
+    macro_rules! assert_moore_penrose {
+        ($a:expr, $pinv:expr) => {
+            let (a, pinv) = ($a, $pinv);
+            assert_relative_eq!(a * pinv * a, a, epsilon = 0.000001);
+            assert_relative_eq!(pinv * a * pinv, pinv, epsilon = 0.000001);
+            assert_relative_eq!((a * pinv).transpose(), a * pinv, epsilon = 0.000001);
+            assert_relative_eq!((pinv * a).transpose(), pinv * a, epsilon = 0.000001);
+        };
+    }
+
+    #[test]
+    fn pinv_2d_matrices() {
+        assert_eq!(pinv_2d(DMat2::ZERO), Some(DMat2::ZERO));
+        assert_eq!(
+            pinv_2d(DMat2::from_cols_array(&[f64::NAN, 0., 0., 1.])),
+            None
+        );
+
+        let projection = DMat2::from_cols_array(&[1., 0., 0., 0.]);
+        assert_relative_eq!(pinv_2d(projection).unwrap(), projection);
+
+        for _ in 0..100 {
+            let matrix = DMat2::from_cols_array(&rand::random::<[f64; 4]>().map(|x| x * 4. - 2.));
+            assert_moore_penrose!(matrix, pinv_2d(matrix).unwrap());
+
+            // Make the second column a multiple of the first, so the matrix is singular
+            let singular = DMat2::from_cols(matrix.x_axis, matrix.x_axis * 3.);
+            assert_moore_penrose!(singular, pinv_2d(singular).unwrap());
+        }
+    }
+
+    #[test]
+    fn pinv_3d_matrices() {
+        assert_eq!(pinv_3d(DMat3::ZERO), Some(DMat3::ZERO));
+        assert_relative_eq!(
+            pinv_3d(DMat3::from_diagonal(DVec3::new(2., 0., -4.))).unwrap(),
+            DMat3::from_diagonal(DVec3::new(0.5, 0., -0.25)),
+            epsilon = 0.000000001
+        );
+
+        for _ in 0..100 {
+            let matrix = DMat3::from_cols_array(&rand::random::<[f64; 9]>().map(|x| x * 4. - 2.));
+            assert_moore_penrose!(matrix, pinv_3d(matrix).unwrap());
+
+            let rank_two = DMat3::from_cols(
+                matrix.x_axis,
+                matrix.y_axis,
+                matrix.x_axis - matrix.y_axis * 2.,
+            );
+            assert_moore_penrose!(rank_two, pinv_3d(rank_two).unwrap());
+
+            let rank_one = DMat3::from_cols(matrix.x_axis, -matrix.x_axis, matrix.x_axis * 0.5);
+            assert_moore_penrose!(rank_one, pinv_3d(rank_one).unwrap());
+        }
+    }
+}
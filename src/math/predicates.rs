@@ -0,0 +1,267 @@
+//! This module provides the [`MatrixPredicates`] trait, to check common properties of matrices.
+
+use super::{rank::rank_of_rows, EPSILON};
+use approx::RelativeEq;
+use glam::{DMat2, DMat3};
+use std::fmt;
+
+/// A property that a square matrix can have. See [`MatrixPredicates`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatrixProperty {
+    /// The matrix is orthogonal, meaning that its transpose is its inverse.
+    Orthogonal,
+
+    /// The matrix is symmetric, meaning that it's equal to its transpose.
+    Symmetric,
+
+    /// The matrix is singular, meaning that its determinant is zero.
+    Singular,
+
+    /// The matrix is a rotation, meaning that it's orthogonal with a determinant of 1.
+    Rotation,
+}
+
+impl MatrixProperty {
+    /// Every property, in order.
+    pub const ALL: [Self; 4] = [
+        Self::Orthogonal,
+        Self::Symmetric,
+        Self::Singular,
+        Self::Rotation,
+    ];
+
+    /// The name of this property, like `orthogonal`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Orthogonal => "orthogonal",
+            Self::Symmetric => "symmetric",
+            Self::Singular => "singular",
+            Self::Rotation => "rotation",
+        }
+    }
+}
+
+/// Properties are displayed as their predicate function in the expression language, like
+/// `is_orthogonal`.
+impl fmt::Display for MatrixProperty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "is_{}", self.name())
+    }
+}
+
+/// Tolerance-aware checks for common properties of a square matrix.
+///
+/// Every check allows for a small amount of floating point error, so something like
+/// `rot(30) * rot(60)` still counts as a rotation. The checks without a tolerance use a default
+/// of `1e-9`.
+pub trait MatrixPredicates {
+    /// Does this matrix have the given property, treating two numbers as equal if they're within
+    /// `epsilon` of each other, or within `max_relative` times the larger of their magnitudes? See
+    /// [`RelativeEq`].
+    ///
+    /// Symmetry and singularity scale `epsilon` by the magnitude of the largest entry in the
+    /// matrix, so that scaling a matrix never changes them. Orthogonality compares `MᵀM` to the
+    /// identity, so `epsilon` is an absolute tolerance there. Singularity only compares pivots to
+    /// zero, so it doesn't depend on `max_relative`, and a matrix with any infinite or NaN entries
+    /// always counts as singular, since it doesn't have a meaningful inverse.
+    fn has_property_within(
+        &self,
+        property: MatrixProperty,
+        epsilon: f64,
+        max_relative: f64,
+    ) -> bool;
+
+    /// Does this matrix have the given property?
+    fn has_property(&self, property: MatrixProperty) -> bool {
+        self.has_property_within(
+            property,
+            EPSILON,
+            <f64 as RelativeEq>::default_max_relative(),
+        )
+    }
+
+    /// Is this matrix orthogonal, meaning that `MᵀM = I`?
+    fn is_orthogonal(&self) -> bool {
+        self.has_property(MatrixProperty::Orthogonal)
+    }
+
+    /// Is this matrix symmetric, meaning that `Mᵀ = M`?
+    fn is_symmetric(&self) -> bool {
+        self.has_property(MatrixProperty::Symmetric)
+    }
+
+    /// Is this matrix singular, meaning that it has no inverse? This agrees with the rank, so a
+    /// matrix is singular exactly when its rank is less than its dimension.
+    fn is_singular(&self) -> bool {
+        self.has_property(MatrixProperty::Singular)
+    }
+
+    /// Is this matrix a rotation, meaning that it's orthogonal and doesn't flip orientation?
+    fn is_rotation(&self) -> bool {
+        self.has_property(MatrixProperty::Rotation)
+    }
+}
+
+/// Is the square matrix with these columns symmetric, with `epsilon` scaled by its largest
+/// entry? See [`MatrixPredicates::has_property_within`].
+fn columns_are_symmetric<const N: usize>(
+    columns: [[f64; N]; N],
+    epsilon: f64,
+    max_relative: f64,
+) -> bool {
+    let tolerance = epsilon
+        * columns
+            .iter()
+            .flatten()
+            .fold(0f64, |max, entry| max.max(entry.abs()));
+    (0..N).all(|row| {
+        (0..row).all(|column| {
+            columns[column][row].relative_eq(&columns[row][column], tolerance, max_relative)
+        })
+    })
+}
+
+/// Are the entries of this matrix all equal to the identity matrix, up to the tolerances? See
+/// [`MatrixPredicates::has_property_within`].
+fn columns_are_identity<const N: usize>(
+    columns: [[f64; N]; N],
+    epsilon: f64,
+    max_relative: f64,
+) -> bool {
+    columns.iter().enumerate().all(|(column, entries)| {
+        entries.iter().enumerate().all(|(row, &entry)| {
+            let expected = if row == column { 1. } else { 0. };
+            entry.relative_eq(&expected, epsilon, max_relative)
+        })
+    })
+}
+
+impl MatrixPredicates for DMat2 {
+    fn has_property_within(
+        &self,
+        property: MatrixProperty,
+        epsilon: f64,
+        max_relative: f64,
+    ) -> bool {
+        let is_orthogonal = || {
+            columns_are_identity(
+                (self.transpose() * *self).to_cols_array_2d(),
+                epsilon,
+                max_relative,
+            )
+        };
+        match property {
+            MatrixProperty::Orthogonal => is_orthogonal(),
+            MatrixProperty::Symmetric => {
+                columns_are_symmetric(self.to_cols_array_2d(), epsilon, max_relative)
+            }
+            MatrixProperty::Singular => {
+                rank_of_rows(self.transpose().to_cols_array_2d(), epsilon) != Some(2)
+            }
+            MatrixProperty::Rotation => is_orthogonal() && self.determinant() > 0.,
+        }
+    }
+}
+
+impl MatrixPredicates for DMat3 {
+    fn has_property_within(
+        &self,
+        property: MatrixProperty,
+        epsilon: f64,
+        max_relative: f64,
+    ) -> bool {
+        let is_orthogonal = || {
+            columns_are_identity(
+                (self.transpose() * *self).to_cols_array_2d(),
+                epsilon,
+                max_relative,
+            )
+        };
+        match property {
+            MatrixProperty::Orthogonal => is_orthogonal(),
+            MatrixProperty::Symmetric => {
+                columns_are_symmetric(self.to_cols_array_2d(), epsilon, max_relative)
+            }
+            MatrixProperty::Singular => {
+                rank_of_rows(self.transpose().to_cols_array_2d(), epsilon) != Some(3)
+            }
+            MatrixProperty::Rotation => is_orthogonal() && self.determinant() > 0.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{DQuat, DVec3};
+
+    #[test]
+    fn predicates_2d() {
+        let rotation = DMat2::from_angle(0.3) * DMat2::from_angle(1.2);
+        let reflection = DMat2::from_cols_array(&[1., 0., 0., -1.]);
+        let shear = DMat2::from_cols_array(&[1., 0., 1., 1.]);
+        let singular = DMat2::from_cols_array(&[1., 2., 2., 4.]);
+
+        assert!(rotation.is_orthogonal() && rotation.is_rotation());
+        assert!(!rotation.is_symmetric() && !rotation.is_singular());
+
+        assert!(reflection.is_orthogonal() && !reflection.is_rotation());
+        assert!(reflection.is_symmetric());
+
+        assert!(!shear.is_orthogonal() && !shear.is_symmetric() && !shear.is_singular());
+
+        assert!(singular.is_singular() && singular.is_symmetric());
+        assert!((singular * 1e12).is_singular() && (singular * 1e-12).is_singular());
+        assert!(DMat2::ZERO.is_singular() && !DMat2::IDENTITY.is_singular());
+        assert!(!(DMat2::IDENTITY * 1e-12).is_singular());
+        assert!((DMat2::IDENTITY * f64::NAN).is_singular());
+        assert!((DMat3::IDENTITY * f64::INFINITY).is_singular());
+
+        assert!(!(DMat2::IDENTITY * 2.).is_orthogonal());
+        assert!(!(DMat2::IDENTITY * -1.).has_property(MatrixProperty::Singular));
+        assert!((DMat2::IDENTITY * -1.).has_property(MatrixProperty::Rotation));
+
+        let nearly_singular = DMat2::from_cols_array(&[1., 2., 2., 4.0001]);
+        assert!(!nearly_singular.is_singular());
+        assert!(nearly_singular.has_property_within(MatrixProperty::Singular, 0.001, 0.));
+        let nearly_symmetric = DMat2::from_cols_array(&[1., 2., 2.0001, 4.]);
+        assert!(!nearly_symmetric.is_symmetric());
+        assert!(nearly_symmetric.has_property_within(MatrixProperty::Symmetric, 0.001, 0.));
+        assert!(nearly_symmetric.has_property_within(MatrixProperty::Symmetric, 0., 0.001));
+        assert!(!nearly_symmetric.has_property_within(MatrixProperty::Symmetric, 0., 0.));
+    }
+
+    #[test]
+    fn predicates_3d() {
+        let rotation = DMat3::from_quat(DQuat::from_axis_angle(
+            DVec3::new(1., 2., 3.).normalize(),
+            2.,
+        ));
+        let reflection = DMat3::from_diagonal(DVec3::new(1., -1., 1.));
+        let symmetric = DMat3::from_cols_array(&[1., 2., 3., 2., 4., 5., 3., 5., 6.]);
+        let singular = DMat3::from_cols_array(&[1., 2., 3., 4., 5., 6., 7., 8., 9.]);
+
+        assert!(rotation.is_rotation() && !rotation.is_symmetric());
+        assert!(!rotation.is_singular());
+        assert!(reflection.is_orthogonal() && !reflection.is_rotation());
+        assert!(symmetric.is_symmetric() && !symmetric.is_orthogonal());
+        assert!(!symmetric.is_singular());
+        assert!(singular.is_singular() && !singular.is_symmetric());
+        assert!((symmetric * 1e9).is_symmetric());
+
+        for property in MatrixProperty::ALL {
+            assert_eq!(
+                DMat3::IDENTITY.has_property(property),
+                property != MatrixProperty::Singular,
+                "{property}"
+            );
+        }
+    }
+
+    #[test]
+    fn matrix_property_display() {
+        assert_eq!(MatrixProperty::Orthogonal.to_string(), "is_orthogonal");
+        assert_eq!(MatrixProperty::Rotation.name(), "rotation");
+    }
+}
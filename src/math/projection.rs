@@ -0,0 +1,81 @@
+//! This module provides builders for orthographic projection matrices, which project onto a line
+//! through the origin in 2D or 3D, or onto a plane through the origin in 3D.
+//!
+//! Every projection `P` is symmetric and idempotent, meaning that `P² = P`.
+
+use glam::{DMat2, DMat3, DVec2, DVec3};
+
+/// Build the matrix which projects onto the line through the origin in the given direction,
+/// returning `None` if the direction is zero or not finite.
+///
+/// ```
+/// # use trinity::math::line_projection_2d;
+/// # use approx::assert_relative_eq;
+/// # use glam::{DMat2, DVec2};
+/// let projection = line_projection_2d(DVec2::new(1., 1.)).unwrap();
+/// assert_relative_eq!(projection, DMat2::from_cols_array(&[0.5, 0.5, 0.5, 0.5]));
+/// assert_eq!(line_projection_2d(DVec2::ZERO), None);
+/// ```
+pub fn line_projection_2d(direction: DVec2) -> Option<DMat2> {
+    let u = direction.try_normalize()?;
+    Some(DMat2::from_cols(u * u.x, u * u.y))
+}
+
+/// Build the matrix which projects onto the line through the origin in the given direction,
+/// returning `None` if the direction is zero or not finite. See [`line_projection_2d`].
+pub fn line_projection_3d(direction: DVec3) -> Option<DMat3> {
+    let u = direction.try_normalize()?;
+    Some(DMat3::from_cols(u * u.x, u * u.y, u * u.z))
+}
+
+/// Build the matrix which projects onto the plane through the origin with the given normal,
+/// returning `None` if the normal is zero or not finite.
+pub fn plane_projection_3d(normal: DVec3) -> Option<DMat3> {
+    line_projection_3d(normal).map(|onto_normal| DMat3::IDENTITY - onto_normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn projections() {
+        assert_eq!(
+            line_projection_2d(DVec2::X),
+            Some(DMat2::from_diagonal(DVec2::X))
+        );
+        assert_eq!(
+            plane_projection_3d(DVec3::new(0., 0., 2.)),
+            Some(DMat3::from_diagonal(DVec3::new(1., 1., 0.)))
+        );
+        assert_eq!(line_projection_3d(DVec3::ZERO), None);
+        assert_eq!(plane_projection_3d(DVec3::splat(f64::NAN)), None);
+
+        for _ in 0..100 {
+            let direction = DVec2::from_array(rand::random()) - 0.5;
+            let projection = line_projection_2d(direction).unwrap();
+            assert_relative_eq!(projection * projection, projection, epsilon = 0.000000001);
+            assert_relative_eq!(projection.transpose(), projection);
+            assert_relative_eq!(projection * direction, direction, epsilon = 0.000000001);
+            assert_relative_eq!(
+                projection * direction.perp(),
+                DVec2::ZERO,
+                epsilon = 0.000000001
+            );
+
+            let normal = DVec3::from_array(rand::random()) - 0.5;
+            let projection = plane_projection_3d(normal).unwrap();
+            assert_relative_eq!(projection * projection, projection, epsilon = 0.000000001);
+            assert_relative_eq!(projection.transpose(), projection);
+            assert_relative_eq!(projection * normal, DVec3::ZERO, epsilon = 0.000000001);
+            let in_plane = normal.normalize().any_orthonormal_vector();
+            assert_relative_eq!(projection * in_plane, in_plane, epsilon = 0.000000001);
+            assert_relative_eq!(
+                projection + line_projection_3d(normal).unwrap(),
+                DMat3::IDENTITY,
+                epsilon = 0.000000001
+            );
+        }
+    }
+}
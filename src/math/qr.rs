@@ -0,0 +1,161 @@
+//! This module provides the QR decomposition of 2D and 3D matrices.
+
+use glam::{DMat2, DMat3};
+
+/// The QR decomposition `A = QR` of a matrix `A`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Qr<M> {
+    /// The orthogonal matrix `Q`.
+    pub q: M,
+
+    /// The upper-triangular matrix `R`, whose diagonal entries are never negative.
+    pub r: M,
+}
+
+/// Find the QR decomposition of a 2D matrix.
+pub fn qr_2d(matrix: DMat2) -> Qr<DMat2> {
+    let (q, r) = householder_qr(matrix.transpose().to_cols_array_2d());
+    Qr {
+        q: DMat2::from_cols_array_2d(&q).transpose(),
+        r: DMat2::from_cols_array_2d(&r).transpose(),
+    }
+}
+
+/// Find the QR decomposition of a 3D matrix.
+pub fn qr_3d(matrix: DMat3) -> Qr<DMat3> {
+    let (q, r) = householder_qr(matrix.transpose().to_cols_array_2d());
+    Qr {
+        q: DMat3::from_cols_array_2d(&q).transpose(),
+        r: DMat3::from_cols_array_2d(&r).transpose(),
+    }
+}
+
+/// Find the QR decomposition of the square matrix with the given rows using Householder
+/// reflections, and return the rows of `Q` and `R`.
+///
+/// Unlike Gram-Schmidt, this still gives an orthogonal `Q` when the matrix is singular.
+fn householder_qr<const N: usize>(mut r: [[f64; N]; N]) -> ([[f64; N]; N], [[f64; N]; N]) {
+    let mut q: [[f64; N]; N] = std::array::from_fn(|row| {
+        std::array::from_fn(|column| if row == column { 1. } else { 0. })
+    });
+
+    for k in 0..N - 1 {
+        // Reflect the part of column k below the diagonal onto the diagonal
+        let length = (k..N).map(|row| r[row][k] * r[row][k]).sum::<f64>().sqrt();
+        let alpha = -length.copysign(r[k][k]);
+
+        let mut v = [0.; N];
+        for row in k..N {
+            v[row] = r[row][k];
+        }
+        v[k] -= alpha;
+
+        let v_squared: f64 = v.iter().map(|entry| entry * entry).sum();
+        if v_squared == 0. {
+            continue;
+        }
+
+        // R = HR and Q = QH, where H = I - 2vvᵀ / vᵀv
+        let projections: [f64; N] =
+            std::array::from_fn(|column| (k..N).map(|row| v[row] * r[row][column]).sum());
+        for (row, v_entry) in r.iter_mut().zip(v).skip(k) {
+            for (entry, projection) in row.iter_mut().zip(projections) {
+                *entry -= 2. * projection * v_entry / v_squared;
+            }
+        }
+        for row in q.iter_mut() {
+            let projection: f64 = (k..N).map(|column| row[column] * v[column]).sum();
+            for column in k..N {
+                row[column] -= 2. * projection * v[column] / v_squared;
+            }
+        }
+
+        // Clean up the rounding errors below the diagonal
+        for row in r.iter_mut().skip(k + 1) {
+            row[k] = 0.;
+        }
+    }
+
+    // Flip signs so that the diagonal of R is non-negative
+    for k in 0..N {
+        if r[k][k] < 0. {
+            for entry in r[k].iter_mut() {
+                *entry = -*entry;
+            }
+            for row in q.iter_mut() {
+                row[k] = -row[k];
+            }
+        }
+    }
+
+    (q, r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::{DVec2, DVec3};
+
+    #[test]
+    fn qr_2d_matrices() {
+        let qr = qr_2d(DMat2::from_cols(DVec2::new(3., 4.), DVec2::new(1., 2.)));
+        assert_relative_eq!(
+            qr.q,
+            DMat2::from_cols(DVec2::new(0.6, 0.8), DVec2::new(-0.8, 0.6)),
+            epsilon = 0.000000001
+        );
+        assert_relative_eq!(
+            qr.r,
+            DMat2::from_cols(DVec2::new(5., 0.), DVec2::new(2.2, 0.4)),
+            epsilon = 0.000000001
+        );
+
+        for matrix in (0..100).map(|_| rand::random::<DMat2>() * 10.).chain([
+            DMat2::ZERO,
+            DMat2::IDENTITY,
+            DMat2::from_cols(DVec2::new(1., 2.), DVec2::new(2., 4.)),
+            DMat2::from_cols(DVec2::ZERO, DVec2::new(1., 1.)),
+        ]) {
+            let qr = qr_2d(matrix);
+            assert_relative_eq!(qr.q * qr.r, matrix, epsilon = 0.000000001);
+            assert_relative_eq!(
+                qr.q * qr.q.transpose(),
+                DMat2::IDENTITY,
+                epsilon = 0.000000001
+            );
+            assert_eq!(qr.r.x_axis.y, 0.);
+            assert!(qr.r.x_axis.x >= 0. && qr.r.y_axis.y >= 0.);
+        }
+    }
+
+    #[test]
+    fn qr_3d_matrices() {
+        assert_eq!(
+            qr_3d(DMat3::IDENTITY),
+            Qr {
+                q: DMat3::IDENTITY,
+                r: DMat3::IDENTITY
+            }
+        );
+
+        for matrix in (0..100).map(|_| rand::random::<DMat3>() * 10.).chain([
+            DMat3::ZERO,
+            DMat3::from_cols(
+                DVec3::new(1., 2., 3.),
+                DVec3::new(2., 4., 6.),
+                DVec3::new(0., 1., 0.),
+            ),
+        ]) {
+            let qr = qr_3d(matrix);
+            assert_relative_eq!(qr.q * qr.r, matrix, epsilon = 0.000000001);
+            assert_relative_eq!(
+                qr.q * qr.q.transpose(),
+                DMat3::IDENTITY,
+                epsilon = 0.000000001
+            );
+            assert_eq!([qr.r.x_axis.y, qr.r.x_axis.z, qr.r.y_axis.z], [0.; 3]);
+            assert!(qr.r.x_axis.x >= 0. && qr.r.y_axis.y >= 0. && qr.r.z_axis.z >= 0.);
+        }
+    }
+}
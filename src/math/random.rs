@@ -0,0 +1,183 @@
+//! This module provides random matrices from a few useful distributions, for quickly filling a
+//! workspace in demos and tests. See [`RandomMatrix`].
+
+use glam::{DMat2, DMat3, DQuat, DVec2};
+use rand::Rng;
+use std::{f64::consts::TAU, fmt};
+
+/// A distribution of random matrices. See [`RandomMatrix`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RandomDistribution {
+    /// Every entry is uniformly distributed between -1 and 1.
+    #[default]
+    Uniform,
+
+    /// Every entry is a whole number from -5 to 5, all equally likely.
+    Integer,
+
+    /// A uniformly random orthogonal matrix, which is a rotation or a reflection with equal
+    /// probability.
+    Orthogonal,
+}
+
+impl RandomDistribution {
+    /// Every distribution, in order.
+    pub const ALL: [Self; 3] = [Self::Uniform, Self::Integer, Self::Orthogonal];
+
+    /// The name of this distribution in the expression language, like `uniform`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Uniform => "uniform",
+            Self::Integer => "integer",
+            Self::Orthogonal => "orthogonal",
+        }
+    }
+}
+
+/// Distributions are displayed as the quoted string used to choose them in the expression
+/// language, like `"orthogonal"`.
+impl fmt::Display for RandomDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.name())
+    }
+}
+
+/// A matrix which can be drawn at random from a [`RandomDistribution`].
+///
+/// Using a seeded random number generator gives the same matrices every time.
+///
+/// ```
+/// # use trinity::math::{RandomDistribution, RandomMatrix};
+/// # use approx::assert_relative_eq;
+/// # use glam::DMat3;
+/// # use rand::{rngs::StdRng, SeedableRng};
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let matrix = DMat3::random(RandomDistribution::Orthogonal, &mut rng);
+/// assert_relative_eq!(matrix * matrix.transpose(), DMat3::IDENTITY, epsilon = 0.000000001);
+///
+/// let mut rng = StdRng::seed_from_u64(42);
+/// assert_eq!(DMat3::random(RandomDistribution::Orthogonal, &mut rng), matrix);
+/// ```
+pub trait RandomMatrix: Sized {
+    /// Draw a random matrix from this distribution.
+    fn random(distribution: RandomDistribution, rng: &mut impl Rng) -> Self;
+}
+
+impl RandomMatrix for DMat2 {
+    fn random(distribution: RandomDistribution, rng: &mut impl Rng) -> Self {
+        match distribution {
+            RandomDistribution::Uniform | RandomDistribution::Integer => {
+                Self::from_cols_array(&random_entries(distribution, rng))
+            }
+            RandomDistribution::Orthogonal => {
+                let rotation = Self::from_angle(rng.gen_range(0. ..TAU));
+                if rng.gen() {
+                    rotation * Self::from_diagonal(DVec2::new(1., -1.))
+                } else {
+                    rotation
+                }
+            }
+        }
+    }
+}
+
+impl RandomMatrix for DMat3 {
+    fn random(distribution: RandomDistribution, rng: &mut impl Rng) -> Self {
+        match distribution {
+            RandomDistribution::Uniform | RandomDistribution::Integer => {
+                Self::from_cols_array(&random_entries(distribution, rng))
+            }
+            RandomDistribution::Orthogonal => {
+                // A point picked uniformly from the unit 4-ball and pushed out to the unit
+                // 3-sphere is a uniformly random unit quaternion, and so a uniformly random
+                // rotation
+                let quat = loop {
+                    let [x, y, z, w] = [(); 4].map(|()| rng.gen_range(-1. ..1.));
+                    let quat = DQuat::from_xyzw(x, y, z, w);
+                    let length_squared = quat.length_squared();
+                    if length_squared > 0.000001 && length_squared <= 1. {
+                        break quat.normalize();
+                    }
+                };
+
+                // Negating a 3D rotation gives a reflection, and -I commutes with everything, so
+                // this keeps the distribution uniform
+                let rotation = Self::from_quat(quat);
+                if rng.gen() {
+                    -rotation
+                } else {
+                    rotation
+                }
+            }
+        }
+    }
+}
+
+/// Draw `N` independent entries from a distribution which picks each entry on its own.
+fn random_entries<const N: usize>(
+    distribution: RandomDistribution,
+    rng: &mut impl Rng,
+) -> [f64; N] {
+    [(); N].map(|()| match distribution {
+        RandomDistribution::Integer => f64::from(rng.gen_range(-5..=5)),
+        _ => rng.gen_range(-1. ..1.),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn random_matrices() {
+        let mut rng = StdRng::seed_from_u64(1963);
+        let mut reflections = [0, 0];
+
+        for _ in 0..100 {
+            let matrix = DMat2::random(RandomDistribution::Uniform, &mut rng);
+            assert!(matrix
+                .to_cols_array()
+                .iter()
+                .all(|x| (-1. ..1.).contains(x)));
+
+            let matrix = DMat3::random(RandomDistribution::Integer, &mut rng);
+            assert!(matrix
+                .to_cols_array()
+                .iter()
+                .all(|&x| x.fract() == 0. && (-5. ..=5.).contains(&x)));
+
+            let matrix = DMat2::random(RandomDistribution::Orthogonal, &mut rng);
+            assert_relative_eq!(
+                matrix * matrix.transpose(),
+                DMat2::IDENTITY,
+                epsilon = 0.000000001
+            );
+            if matrix.determinant() < 0. {
+                reflections[0] += 1;
+            }
+
+            let matrix = DMat3::random(RandomDistribution::Orthogonal, &mut rng);
+            assert_relative_eq!(
+                matrix * matrix.transpose(),
+                DMat3::IDENTITY,
+                epsilon = 0.000000001
+            );
+            if matrix.determinant() < 0. {
+                reflections[1] += 1;
+            }
+        }
+
+        // Both rotations and reflections should turn up
+        assert!(reflections.iter().all(|count| (10..90).contains(count)));
+
+        let seeded = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            RandomDistribution::ALL.map(|distribution| DMat2::random(distribution, &mut rng))
+        };
+        assert_eq!(seeded(42), seeded(42));
+        assert_ne!(seeded(42), seeded(43));
+    }
+}
@@ -0,0 +1,164 @@
+//! This module provides functions to find the rank of 2D and 3D matrices.
+
+use super::EPSILON;
+use glam::{DMat2, DMat3};
+
+/// Find the rank of a 2D matrix, or `None` if it has any non-finite entries. This will always be
+/// 0, 1, or 2.
+pub fn rank_2d(matrix: DMat2) -> Option<usize> {
+    rank_2d_within(matrix, EPSILON)
+}
+
+/// Find the rank of a 3D matrix, or `None` if it has any non-finite entries. This will always be
+/// 0, 1, 2, or 3.
+pub fn rank_3d(matrix: DMat3) -> Option<usize> {
+    rank_3d_within(matrix, EPSILON)
+}
+
+/// Find the rank of a 2D matrix like [`rank_2d`], treating pivots within `epsilon` times the
+/// largest entry as zero, so that scaling a matrix never changes its rank.
+pub fn rank_2d_within(matrix: DMat2, epsilon: f64) -> Option<usize> {
+    rank_of_rows(matrix.transpose().to_cols_array_2d(), epsilon)
+}
+
+/// Find the rank of a 3D matrix like [`rank_3d`], treating pivots within `epsilon` times the
+/// largest entry as zero, so that scaling a matrix never changes its rank.
+pub fn rank_3d_within(matrix: DMat3, epsilon: f64) -> Option<usize> {
+    rank_of_rows(matrix.transpose().to_cols_array_2d(), epsilon)
+}
+
+/// Find the rank of the square matrix with the given rows, using Gaussian elimination with
+/// partial pivoting, or `None` if it has any non-finite entries, since infinite or NaN entries
+/// don't have a meaningful rank.
+///
+/// A pivot counts as zero if it's within `epsilon` times the largest entry of the matrix, so that
+/// scaling a matrix by a constant factor never changes its rank.
+pub(super) fn rank_of_rows<const N: usize>(mut rows: [[f64; N]; N], epsilon: f64) -> Option<usize> {
+    if rows.iter().flatten().any(|entry| !entry.is_finite()) {
+        return None;
+    }
+
+    let scale = rows
+        .iter()
+        .flatten()
+        .fold(0f64, |max, entry| max.max(entry.abs()));
+
+    if scale == 0. {
+        return Some(0);
+    }
+
+    let tolerance = epsilon * scale;
+    let mut rank = 0;
+
+    for column in 0..N {
+        let Some(pivot) =
+            (rank..N).max_by(|&a, &b| rows[a][column].abs().total_cmp(&rows[b][column].abs()))
+        else {
+            break;
+        };
+
+        if rows[pivot][column].abs() <= tolerance {
+            continue;
+        }
+
+        rows.swap(rank, pivot);
+
+        let pivot_row = rows[rank];
+        for row in rows.iter_mut().skip(rank + 1) {
+            let factor = row[column] / pivot_row[column];
+            for (entry, pivot_entry) in row.iter_mut().zip(pivot_row).skip(column) {
+                *entry -= factor * pivot_entry;
+            }
+        }
+
+        rank += 1;
+    }
+
+    Some(rank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{DVec2, DVec3};
+
+    #[test]
+    fn rank_2d_matrices() {
+        assert_eq!(rank_2d(DMat2::ZERO), Some(0));
+        assert_eq!(rank_2d(DMat2::IDENTITY), Some(2));
+        assert_eq!(
+            rank_2d(DMat2::from_cols(DVec2::new(1., 2.), DVec2::new(2., 4.))),
+            Some(1)
+        );
+        assert_eq!(
+            rank_2d(DMat2::from_cols(DVec2::new(0., 0.), DVec2::new(0., 3.))),
+            Some(1)
+        );
+        assert_eq!(
+            rank_2d(DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., 4.))),
+            Some(2)
+        );
+
+        // Scaling shouldn't change the rank
+        assert_eq!(
+            rank_2d(0.000000000001 * DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., 4.))),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn rank_3d_matrices() {
+        assert_eq!(rank_3d(DMat3::ZERO), Some(0));
+        assert_eq!(rank_3d(DMat3::IDENTITY), Some(3));
+        assert_eq!(
+            rank_3d(DMat3::from_cols(
+                DVec3::new(1., 4., 7.),
+                DVec3::new(2., 5., 8.),
+                DVec3::new(3., 6., 9.),
+            )),
+            Some(2)
+        );
+        assert_eq!(
+            rank_3d(DMat3::from_cols(
+                DVec3::new(1., 2., 3.),
+                DVec3::new(2., 4., 6.),
+                DVec3::new(-1., -2., -3.),
+            )),
+            Some(1)
+        );
+        assert_eq!(
+            rank_3d(DMat3::from_cols(DVec3::ZERO, DVec3::Z, DVec3::X)),
+            Some(2)
+        );
+        assert_eq!(
+            rank_3d(DMat3::from_cols(
+                DVec3::new(1., 0., 1.),
+                DVec3::new(2., 4., 0.),
+                DVec3::new(3., 5., 6.),
+            )),
+            Some(3)
+        );
+
+        let nearly_singular = DMat3::from_cols(DVec3::X, DVec3::Y, DVec3::new(1., 1., 0.0001));
+        assert_eq!(rank_3d(nearly_singular), Some(3));
+        assert_eq!(rank_3d_within(nearly_singular, 0.001), Some(2));
+    }
+
+    #[test]
+    fn rank_non_finite_matrices() {
+        assert_eq!(rank_2d(DMat2::IDENTITY * f64::NAN), None);
+        assert_eq!(rank_2d(DMat2::IDENTITY * f64::INFINITY), None);
+        assert_eq!(
+            rank_3d(DMat3::from_cols(
+                DVec3::X,
+                DVec3::Y,
+                DVec3::new(0., 0., f64::NAN)
+            )),
+            None
+        );
+        assert_eq!(
+            rank_3d(DMat3::from_cols(DVec3::X, DVec3::Y, DVec3::NEG_INFINITY)),
+            None
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! This module provides conversions from 3D rotation matrices to quaternions, Euler angles, and
+//! axis-angle pairs.
+//!
+//! The conversions the other way are already provided by glam, with [`DMat3::from_quat`],
+//! [`DMat3::from_euler`], and [`DMat3::from_axis_angle`].
+
+use super::MatrixPredicates;
+use glam::{DMat3, DQuat, DVec3, EulerRot};
+
+/// Convert a 3D rotation matrix into a unit quaternion, returning `None` if the matrix isn't a
+/// rotation (see [`MatrixPredicates::is_rotation`]).
+///
+/// The quaternion always has a non-negative `w` component, so the same rotation always gives
+/// the same quaternion.
+pub fn rotation_to_quat(matrix: DMat3) -> Option<DQuat> {
+    if !matrix.is_rotation() {
+        return None;
+    }
+
+    let quat = DQuat::from_mat3(&matrix).normalize();
+    Some(if quat.w < 0. { -quat } else { quat })
+}
+
+/// Convert a 3D rotation matrix into a unit axis and an anticlockwise angle about it in radians,
+/// returning `None` if the matrix isn't a rotation.
+///
+/// The angle is always in the range `[0, π]`. The identity has an angle of 0 about the x axis.
+///
+/// ```
+/// # use trinity::math::rotation_to_axis_angle;
+/// # use approx::assert_relative_eq;
+/// # use glam::{DMat3, DVec3};
+/// let (axis, angle) = rotation_to_axis_angle(DMat3::from_rotation_z(1.)).unwrap();
+/// assert_relative_eq!(axis, DVec3::Z);
+/// assert_relative_eq!(angle, 1.);
+///
+/// assert_eq!(rotation_to_axis_angle(DMat3::from_diagonal(DVec3::splat(2.))), None);
+/// ```
+pub fn rotation_to_axis_angle(matrix: DMat3) -> Option<(DVec3, f64)> {
+    rotation_to_quat(matrix).map(DQuat::to_axis_angle)
+}
+
+/// Convert a 3D rotation matrix into Euler angles in radians, applied in the given order,
+/// returning `None` if the matrix isn't a rotation.
+///
+/// See [`EulerRot`] for what each order means. Feeding the angles back into
+/// [`DMat3::from_euler`] with the same order gives the original matrix.
+pub fn rotation_to_euler(matrix: DMat3, order: EulerRot) -> Option<(f64, f64, f64)> {
+    rotation_to_quat(matrix).map(|quat| quat.to_euler(order))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    #[test]
+    fn rotation_conversions() {
+        assert_eq!(rotation_to_quat(DMat3::IDENTITY), Some(DQuat::IDENTITY));
+        assert_eq!(
+            rotation_to_axis_angle(DMat3::IDENTITY),
+            Some((DVec3::X, 0.))
+        );
+
+        let half_turn = rotation_to_axis_angle(DMat3::from_rotation_y(PI)).unwrap();
+        assert_relative_eq!(half_turn.0, DVec3::Y, epsilon = 0.000000001);
+        assert_relative_eq!(half_turn.1, PI, epsilon = 0.000000001);
+
+        let (x, y, z) =
+            rotation_to_euler(DMat3::from_rotation_x(FRAC_PI_2), EulerRot::XYZ).unwrap();
+        assert_relative_eq!(x, FRAC_PI_2, epsilon = 0.000000001);
+        assert_relative_eq!(y, 0., epsilon = 0.000000001);
+        assert_relative_eq!(z, 0., epsilon = 0.000000001);
+
+        for non_rotation in [
+            DMat3::ZERO,
+            DMat3::from_diagonal(DVec3::new(1., 1., -1.)),
+            DMat3::from_cols_array(&[1., 0., 0., 0.5, 1., 0., 0., 0., 1.]),
+            DMat3::from_rotation_z(1.) * 1.01,
+        ] {
+            assert_eq!(rotation_to_quat(non_rotation), None, "{non_rotation}");
+            assert_eq!(rotation_to_axis_angle(non_rotation), None);
+            assert_eq!(rotation_to_euler(non_rotation, EulerRot::ZYX), None);
+        }
+
+        for _ in 0..100 {
+            let axis = (DVec3::from_array(rand::random()) - 0.5).normalize();
+            let angle = rand::random::<f64>() * PI;
+            let matrix = DMat3::from_axis_angle(axis, angle);
+
+            let quat = rotation_to_quat(matrix).unwrap();
+            assert!(quat.w >= 0.);
+            assert_relative_eq!(DMat3::from_quat(quat), matrix, epsilon = 0.000000001);
+
+            let (found_axis, found_angle) = rotation_to_axis_angle(matrix).unwrap();
+            assert_relative_eq!(
+                DMat3::from_axis_angle(found_axis, found_angle),
+                matrix,
+                epsilon = 0.000000001
+            );
+
+            for order in [EulerRot::XYZ, EulerRot::ZYX, EulerRot::YXZ] {
+                let (a, b, c) = rotation_to_euler(matrix, order).unwrap();
+                assert_relative_eq!(
+                    DMat3::from_euler(order, a, b, c),
+                    matrix,
+                    epsilon = 0.000000001
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! This module provides row reduction to reduced row echelon form, recording every step.
+
+use super::EPSILON;
+use std::fmt;
+
+/// An elementary row operation. Rows are 0-indexed, but they're displayed 1-indexed, like
+/// `R1 ↔ R2`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RowOp {
+    /// Swap two rows.
+    Swap(usize, usize),
+
+    /// Multiply a row by a non-zero factor.
+    Scale {
+        /// The row to scale.
+        row: usize,
+        /// The factor to multiply it by.
+        factor: f64,
+    },
+
+    /// Add a multiple of one row to another.
+    AddMultiple {
+        /// The row to add to.
+        target: usize,
+        /// The row to add a multiple of.
+        source: usize,
+        /// The multiple of the source row to add.
+        factor: f64,
+    },
+}
+
+impl RowOp {
+    /// Apply this row operation to the rows of a matrix.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the rows are out of bounds.
+    pub fn apply<const C: usize>(&self, rows: &mut [[f64; C]]) {
+        match *self {
+            Self::Swap(a, b) => rows.swap(a, b),
+            Self::Scale { row, factor } => {
+                for entry in rows[row].iter_mut() {
+                    *entry *= factor;
+                }
+            }
+            Self::AddMultiple {
+                target,
+                source,
+                factor,
+            } => {
+                let source = rows[source];
+                for (entry, source_entry) in rows[target].iter_mut().zip(source) {
+                    *entry += factor * source_entry;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for RowOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Swap(a, b) => write!(f, "R{} ↔ R{}", a + 1, b + 1),
+            Self::Scale { row, factor } => write!(f, "R{0} → {factor}R{0}", row + 1),
+            Self::AddMultiple {
+                target,
+                source,
+                factor,
+            } if factor < 0. => write!(f, "R{0} → R{0} - {1}R{2}", target + 1, -factor, source + 1),
+            Self::AddMultiple {
+                target,
+                source,
+                factor,
+            } => write!(f, "R{0} → R{0} + {factor}R{1}", target + 1, source + 1),
+        }
+    }
+}
+
+/// The result of [`rref`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rref<const R: usize, const C: usize> {
+    /// The rows of the matrix in reduced row echelon form.
+    pub rows: [[f64; C]; R],
+
+    /// The row operations which turn the original matrix into the reduced one, in order.
+    pub steps: Vec<RowOp>,
+
+    /// The columns which contain a leading 1, in order. The length of this is the rank.
+    pub pivot_columns: Vec<usize>,
+}
+
+/// Row reduce the matrix with the given rows to reduced row echelon form, using Gauss-Jordan
+/// elimination with partial pivoting.
+///
+/// The matrix doesn't need to be square, so this can reduce augmented matrices like `[A | b]`,
+/// which are 2×3 or 3×4. Entries which are within a small tolerance of zero, relative to the
+/// largest entry, are treated as zero, and they're set to exactly zero in the result.
+///
+/// Replaying the [`steps`](Rref::steps) with [`RowOp::apply`] on the original matrix gives the
+/// reduced matrix, up to rounding.
+pub fn rref<const R: usize, const C: usize>(mut rows: [[f64; C]; R]) -> Rref<R, C> {
+    let scale = rows
+        .iter()
+        .flatten()
+        .fold(0f64, |max, entry| max.max(entry.abs()));
+    let tolerance = EPSILON * scale;
+
+    let mut steps = vec![];
+    let mut pivot_columns = vec![];
+
+    for column in 0..C {
+        let rank = pivot_columns.len();
+        if rank == R {
+            break;
+        }
+
+        let Some(pivot) =
+            (rank..R).max_by(|&a, &b| rows[a][column].abs().total_cmp(&rows[b][column].abs()))
+        else {
+            break;
+        };
+        if rows[pivot][column].abs() <= tolerance {
+            for row in rows.iter_mut().skip(rank) {
+                row[column] = 0.;
+            }
+            continue;
+        }
+
+        let mut record = |step: RowOp, rows: &mut [[f64; C]; R]| {
+            step.apply(rows);
+            steps.push(step);
+        };
+
+        if pivot != rank {
+            record(RowOp::Swap(rank, pivot), &mut rows);
+        }
+
+        let factor = rows[rank][column].recip();
+        if factor != 1. {
+            record(RowOp::Scale { row: rank, factor }, &mut rows);
+        }
+        rows[rank][column] = 1.;
+
+        for target in 0..R {
+            let factor = -rows[target][column];
+            if target != rank && factor != 0. {
+                record(
+                    RowOp::AddMultiple {
+                        target,
+                        source: rank,
+                        factor,
+                    },
+                    &mut rows,
+                );
+                rows[target][column] = 0.;
+            }
+        }
+
+        pivot_columns.push(column);
+    }
+
+    for entry in rows.iter_mut().flatten() {
+        if entry.abs() <= tolerance {
+            *entry = 0.;
+        }
+    }
+
+    Rref {
+        rows,
+        steps,
+        pivot_columns,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn rref_steps() {
+        let reduced = rref([[2., 4.], [1., 3.]]);
+        assert_eq!(reduced.rows, [[1., 0.], [0., 1.]]);
+        assert_eq!(reduced.pivot_columns, vec![0, 1]);
+        assert_eq!(
+            reduced.steps,
+            vec![
+                RowOp::Scale {
+                    row: 0,
+                    factor: 0.5
+                },
+                RowOp::AddMultiple {
+                    target: 1,
+                    source: 0,
+                    factor: -1.
+                },
+                RowOp::AddMultiple {
+                    target: 0,
+                    source: 1,
+                    factor: -2.
+                },
+            ]
+        );
+
+        let reduced = rref([[0., 1.], [3., 0.]]);
+        assert_eq!(reduced.rows, [[1., 0.], [0., 1.]]);
+        assert_eq!(reduced.steps[0], RowOp::Swap(0, 1));
+
+        assert_eq!(
+            reduced
+                .steps
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["R1 ↔ R2", "R1 → 0.3333333333333333R1"]
+        );
+        assert_eq!(
+            RowOp::AddMultiple {
+                target: 2,
+                source: 0,
+                factor: -2.
+            }
+            .to_string(),
+            "R3 → R3 - 2R1"
+        );
+    }
+
+    #[test]
+    fn rref_singular_and_augmented() {
+        let reduced = rref([[1., 2., 3.], [2., 4., 6.], [1., 0., 1.]]);
+        assert_eq!(reduced.pivot_columns, vec![0, 1]);
+        assert_relative_eq!(reduced.rows[0].as_slice(), [1., 0., 1.].as_slice());
+        assert_relative_eq!(reduced.rows[1].as_slice(), [0., 1., 1.].as_slice());
+        assert_eq!(reduced.rows[2], [0.; 3]);
+
+        assert_eq!(rref([[0.; 2]; 2]).rows, [[0.; 2]; 2]);
+        assert!(rref([[0.; 2]; 2]).steps.is_empty());
+
+        // x + y + z = 6, 2y + 5z = -4, 2x + 5y - z = 27 has the solution (5, 3, -2)
+        let reduced = rref([[1., 1., 1., 6.], [0., 2., 5., -4.], [2., 5., -1., 27.]]);
+        assert_eq!(reduced.pivot_columns, vec![0, 1, 2]);
+        for (row, solution) in reduced.rows.iter().zip([5., 3., -2.]) {
+            assert_relative_eq!(row[3], solution, epsilon = 0.000000001);
+        }
+
+        // An inconsistent system has a pivot in the augmented column
+        let reduced = rref([[1., 2., 3.], [2., 4., 5.]]);
+        assert_eq!(reduced.pivot_columns, vec![0, 2]);
+    }
+
+    #[test]
+    fn rref_steps_replay() {
+        for _ in 0..100 {
+            let original: [[f64; 4]; 3] = rand::random();
+            let reduced = rref(original);
+
+            let mut rows = original;
+            for step in &reduced.steps {
+                step.apply(&mut rows);
+            }
+            for (row, reduced_row) in rows.iter().zip(reduced.rows) {
+                assert_relative_eq!(
+                    row.as_slice(),
+                    reduced_row.as_slice(),
+                    epsilon = 0.000000001
+                );
+            }
+        }
+    }
+}
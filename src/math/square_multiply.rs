@@ -1,9 +1,10 @@
-//! This module just provides the [`integer_power`] function.
+//! This module provides the [`integer_power`] function, along with [`checked_integer_power`] and
+//! [`signed_integer_power`], which detect results that are too big to represent.
 
 use std::ops::Mul;
 
 /// The type used for the `power` parameter in [`integer_power`].
-type IntegerPowerType = u16;
+type IntegerPowerType = u32;
 
 /// Calculate `base` to the power of an integer, using the square and multiply algorithm.
 pub fn integer_power<T>(base: T, power: IntegerPowerType) -> T
@@ -11,7 +12,7 @@ where
     T: Mul<T, Output = T> + PowerZero + std::marker::Copy,
 {
     if power == 0 {
-        return <T as PowerZero>::POWER_ZERO;
+        return base.power_zero();
     }
 
     let mut num = base;
@@ -29,35 +30,177 @@ where
     num
 }
 
+/// Calculate `base` to the power of an integer like [`integer_power`], but return `None` if any
+/// step overflows. See [`CheckedProduct`].
+///
+/// ```
+/// # use trinity::math::checked_integer_power;
+/// # use glam::DMat2;
+/// assert_eq!(checked_integer_power(2u8, 7), Some(128));
+/// assert_eq!(checked_integer_power(2u8, 8), None);
+/// assert_eq!(checked_integer_power(DMat2::IDENTITY * 2., 100_000), None);
+/// ```
+pub fn checked_integer_power<T>(base: T, power: IntegerPowerType) -> Option<T>
+where
+    T: CheckedProduct + PowerZero + Clone,
+{
+    if power == 0 {
+        return Some(base.power_zero());
+    }
+
+    let mut num = base.clone();
+
+    for bit_idx in (0..power.ilog2()).rev() {
+        // Square
+        num = num.clone().checked_product(num)?;
+
+        if (1 << bit_idx) & power != 0 {
+            // Multiply
+            num = num.checked_product(base.clone())?;
+        }
+    }
+
+    Some(num)
+}
+
+/// The reasons that [`signed_integer_power`] can fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerError {
+    /// The result was too big to represent.
+    Overflow,
+
+    /// The power was negative, but the base couldn't be inverted.
+    NotInvertible,
+}
+
+/// Calculate `base` to the power of a signed integer like [`checked_integer_power`].
+///
+/// A negative power inverts the base first and then raises the inverse to the matching positive
+/// power, so something like `0.1⁻⁵` doesn't look singular just because `0.1⁵` is tiny. This fails
+/// if the power is negative and the base is singular under the relative tolerance `epsilon`. See
+/// [`Invertible`].
+///
+/// ```
+/// # use trinity::math::{signed_integer_power, PowerError};
+/// # use glam::DMat2;
+/// let matrix = DMat2::from_cols_array(&[2., 0., 0., 4.]);
+/// assert_eq!(
+///     signed_integer_power(matrix, -2, 0.),
+///     Ok(DMat2::from_cols_array(&[0.25, 0., 0., 0.0625]))
+/// );
+/// assert_eq!(
+///     signed_integer_power(DMat2::ZERO, -1, 0.),
+///     Err(PowerError::NotInvertible)
+/// );
+/// assert_eq!(
+///     signed_integer_power(matrix, 100_000, 0.),
+///     Err(PowerError::Overflow)
+/// );
+/// ```
+pub fn signed_integer_power<T>(base: T, power: i32, epsilon: f64) -> Result<T, PowerError>
+where
+    T: CheckedProduct + PowerZero + Invertible + Clone,
+{
+    let base = if power < 0 {
+        base.checked_inverse(epsilon)
+            .ok_or(PowerError::NotInvertible)?
+    } else {
+        base
+    };
+
+    checked_integer_power(base, power.unsigned_abs()).ok_or(PowerError::Overflow)
+}
+
+/// Multiplication which notices when the result is too big to represent. For integers, that's
+/// overflow, and for floats and matrices, that's anything which isn't finite.
+pub trait CheckedProduct: Sized {
+    /// Multiply `self` by `rhs`, returning `None` if the result is too big to represent.
+    fn checked_product(self, rhs: Self) -> Option<Self>;
+}
+
+/// Impl [`CheckedProduct`] for types with an `is_finite()` method.
+macro_rules! impl_checked_product_finite {
+    ($($t:ty),*) => {
+        $(impl CheckedProduct for $t {
+            fn checked_product(self, rhs: Self) -> Option<Self> {
+                let product = self * rhs;
+                product.is_finite().then_some(product)
+            }
+        })*
+    }
+}
+
+impl_checked_product_finite!(f32, f64, glam::DMat2, glam::DMat3);
+
+/// Impl [`CheckedProduct`] for all builtin signed and unsigned integer types.
+macro_rules! impl_checked_product_int {
+    ($($t:ty),*) => {
+        $(impl CheckedProduct for $t {
+            fn checked_product(self, rhs: Self) -> Option<Self> {
+                self.checked_mul(rhs)
+            }
+        })*
+    }
+}
+
+impl_checked_product_int!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+/// Something which might have a multiplicative inverse, like a float or a square matrix.
+pub trait Invertible: Sized {
+    /// Find the multiplicative inverse of this value, returning `None` if it's singular.
+    ///
+    /// A matrix is singular if a pivot in Gauss-Jordan elimination is within `epsilon` times its
+    /// largest entry, so that scaling a matrix never changes whether it can be inverted, whatever
+    /// its dimension. A number is its own largest entry, so only zero is singular.
+    fn checked_inverse(&self, epsilon: f64) -> Option<Self>;
+}
+
+impl Invertible for f64 {
+    fn checked_inverse(&self, _epsilon: f64) -> Option<Self> {
+        (*self != 0.).then(|| self.recip())
+    }
+}
+
 /// When you raise a number to the power of zero, you get one. When you raise a matrix to the power
 /// of zero, you get the identity matrix in that dimension. This trait exists to abstract over the
 /// type and allow [`integer_power`] to raise anything to the power of zero.
 pub trait PowerZero {
-    /// What is any value of this type raised to the power of 0?
-    const POWER_ZERO: Self;
+    /// What is this value raised to the power of 0? This takes `self` so that a matrix whose
+    /// dimension is only known at runtime can give the identity matrix of its own dimension.
+    fn power_zero(&self) -> Self;
 }
 
 impl PowerZero for f32 {
-    const POWER_ZERO: Self = 1.0;
+    fn power_zero(&self) -> Self {
+        1.0
+    }
 }
 
 impl PowerZero for f64 {
-    const POWER_ZERO: Self = 1.0;
+    fn power_zero(&self) -> Self {
+        1.0
+    }
 }
 
 impl PowerZero for glam::DMat2 {
-    const POWER_ZERO: Self = glam::DMat2::IDENTITY;
+    fn power_zero(&self) -> Self {
+        glam::DMat2::IDENTITY
+    }
 }
 
 impl PowerZero for glam::DMat3 {
-    const POWER_ZERO: Self = glam::DMat3::IDENTITY;
+    fn power_zero(&self) -> Self {
+        glam::DMat3::IDENTITY
+    }
 }
 
 /// Impl [`PowerZero`] for all builtin signed and unsigned integer types.
 macro_rules! impl_power_zero_int {
     ($($t:ty),*) => {
         $(impl PowerZero for $t {
-            const POWER_ZERO: $t = 1;
+            fn power_zero(&self) -> Self {
+                1
+            }
         })*
     }
 }
@@ -119,4 +262,75 @@ mod tests {
         assert_eq!(integer_power(m, 1), m);
         assert_eq!(integer_power(n, 0), DMat3::IDENTITY);
     }
+
+    #[test]
+    fn checked_integer_power_overflow() {
+        assert_eq!(checked_integer_power(3u64, 40), Some(3u64.pow(40)));
+        assert_eq!(checked_integer_power(3u64, 41), None);
+        assert_eq!(checked_integer_power(-2i8, 7), Some(-128));
+        assert_eq!(checked_integer_power(-2i8, 8), None);
+        assert_eq!(checked_integer_power(1u8, u32::MAX), Some(1));
+
+        assert!(checked_integer_power(10f64, 308).is_some());
+        assert_eq!(checked_integer_power(10f64, 309), None);
+        assert_eq!(checked_integer_power(0.5f64, 100_000), Some(0.));
+
+        let rotation = DMat2::from_angle(0.1);
+        assert_relative_eq!(
+            checked_integer_power(rotation, 100_000).unwrap(),
+            DMat2::from_angle(10_000.),
+            epsilon = 0.000001
+        );
+        assert_eq!(checked_integer_power(DMat3::IDENTITY * 1.01, 100_000), None);
+    }
+
+    #[test]
+    fn signed_integer_power_with_inverses() {
+        let epsilon = 0.000000001;
+
+        assert_eq!(signed_integer_power(2f64, -3, epsilon), Ok(0.125));
+        assert_eq!(signed_integer_power(2f64, 3, epsilon), Ok(8.));
+        assert_eq!(
+            signed_integer_power(0f64, -1, epsilon),
+            Err(PowerError::NotInvertible)
+        );
+        assert_eq!(signed_integer_power(0f64, 0, epsilon), Ok(1.));
+        assert_eq!(signed_integer_power(2f64, -2000, epsilon), Ok(0.));
+        assert_eq!(
+            signed_integer_power(0.5f64, -2000, epsilon),
+            Err(PowerError::Overflow)
+        );
+
+        // The base is far from singular, even though its fifth power would look singular
+        assert_relative_eq!(
+            signed_integer_power(DMat2::IDENTITY * 0.1, -5, epsilon).unwrap(),
+            DMat2::IDENTITY * 100_000.,
+            max_relative = 0.000000001
+        );
+
+        let m = DMat2::from_cols(DVec2::new(2.1, -3.2), DVec2::new(0.03, 1.92));
+        assert_relative_eq!(
+            signed_integer_power(m, -3, epsilon).unwrap(),
+            integer_power(m.inverse(), 3),
+            epsilon = 0.000000001
+        );
+
+        let n = DMat3::from_cols(
+            DVec3::new(2.1, -3.2, 4.5),
+            DVec3::new(0.03, 1.92, -1.16),
+            DVec3::new(-0.5, 1.34, 7.12),
+        );
+        assert_relative_eq!(
+            signed_integer_power(n, -2, epsilon).unwrap() * integer_power(n, 2),
+            DMat3::IDENTITY,
+            epsilon = 0.000000001
+        );
+
+        let singular = DMat3::from_cols(DVec3::X, DVec3::Y, DVec3::X + DVec3::Y);
+        assert_eq!(
+            signed_integer_power(singular, -1, epsilon),
+            Err(PowerError::NotInvertible)
+        );
+        assert_eq!(signed_integer_power(singular, 1, epsilon), Ok(singular));
+    }
 }
@@ -0,0 +1,253 @@
+//! This module provides the singular value decomposition of 2D and 3D matrices.
+
+use glam::{DMat2, DMat3, DVec2, DVec3};
+
+/// The relative tolerance of the singular value decomposition.
+///
+/// Columns count as orthogonal once the cosine of the angle between them is at most this, and
+/// singular values at most this times the magnitude of the largest entry in the matrix count as
+/// zero, so the corresponding columns of `U` are chosen arbitrarily to keep it orthogonal.
+pub const SVD_TOLERANCE: f64 = 0.000000000001;
+
+/// The maximum number of sweeps of Jacobi rotations. This is far more than ever needed in
+/// practice, since the rotations converge quadratically.
+const MAX_SWEEPS: usize = 100;
+
+/// The singular value decomposition `A = U Σ Vᵀ` of a matrix `A`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Svd<M, V> {
+    /// The orthogonal matrix `U`, whose columns are the left singular vectors.
+    pub u: M,
+
+    /// The diagonal of `Σ`, which are the singular values. These are never negative, and they're
+    /// sorted from largest to smallest.
+    ///
+    /// They're the lengths of the semi-axes of the ellipse or ellipsoid that the unit circle or
+    /// sphere gets mapped to, and the columns of `U` are the directions of those semi-axes.
+    pub singular_values: V,
+
+    /// The transpose of the orthogonal matrix `V`, whose columns are the right singular vectors.
+    pub v_transpose: M,
+}
+
+/// Find the singular value decomposition of a 2D matrix, or `None` if it has any non-finite
+/// entries. See [`SVD_TOLERANCE`].
+pub fn svd_2d(matrix: DMat2) -> Option<Svd<DMat2, DVec2>> {
+    let (u, singular_values, v) = jacobi_svd(matrix.to_cols_array_2d())?;
+    Some(Svd {
+        u: DMat2::from_cols_array_2d(&u),
+        singular_values: DVec2::from_array(singular_values),
+        v_transpose: DMat2::from_cols_array_2d(&v).transpose(),
+    })
+}
+
+/// Find the singular value decomposition of a 3D matrix, or `None` if it has any non-finite
+/// entries. See [`SVD_TOLERANCE`].
+pub fn svd_3d(matrix: DMat3) -> Option<Svd<DMat3, DVec3>> {
+    let (u, singular_values, v) = jacobi_svd(matrix.to_cols_array_2d())?;
+    Some(Svd {
+        u: DMat3::from_cols_array_2d(&u),
+        singular_values: DVec3::from_array(singular_values),
+        v_transpose: DMat3::from_cols_array_2d(&v).transpose(),
+    })
+}
+
+/// The dot product of two columns.
+fn dot<const N: usize>(a: &[f64; N], b: &[f64; N]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Find the singular value decomposition of the square matrix with the given columns, using
+/// one-sided Jacobi rotations, and return the columns of `U`, the singular values, and the
+/// columns of `V`.
+///
+/// We apply plane rotations to pairs of columns until every pair is orthogonal, accumulating
+/// the same rotations into `V`. The lengths of the final columns are then the singular values,
+/// and the normalised columns are the columns of `U`.
+#[allow(clippy::type_complexity)]
+fn jacobi_svd<const N: usize>(
+    mut columns: [[f64; N]; N],
+) -> Option<([[f64; N]; N], [f64; N], [[f64; N]; N])> {
+    if columns.iter().flatten().any(|entry| !entry.is_finite()) {
+        return None;
+    }
+
+    let scale = columns
+        .iter()
+        .flatten()
+        .fold(0f64, |max, entry| max.max(entry.abs()));
+    let mut v: [[f64; N]; N] = std::array::from_fn(|column| {
+        std::array::from_fn(|row| if row == column { 1. } else { 0. })
+    });
+
+    for _ in 0..MAX_SWEEPS {
+        let mut rotated = false;
+
+        for p in 0..N {
+            for q in p + 1..N {
+                let alpha = dot(&columns[p], &columns[p]);
+                let beta = dot(&columns[q], &columns[q]);
+                let gamma = dot(&columns[p], &columns[q]);
+                if gamma == 0. || gamma.abs() <= SVD_TOLERANCE * (alpha * beta).sqrt() {
+                    continue;
+                }
+                rotated = true;
+
+                // Choose the smaller of the two rotations that makes the columns orthogonal
+                let zeta = (beta - alpha) / (2. * gamma);
+                let tan = zeta.signum() / (zeta.abs() + (1. + zeta * zeta).sqrt());
+                let cos = (1. + tan * tan).sqrt().recip();
+                let sin = cos * tan;
+
+                for matrix in [&mut columns, &mut v] {
+                    let (left, right) = matrix.split_at_mut(q);
+                    for (a, b) in left[p].iter_mut().zip(right[0].iter_mut()) {
+                        (*a, *b) = (cos * *a - sin * *b, sin * *a + cos * *b);
+                    }
+                }
+            }
+        }
+
+        if !rotated {
+            break;
+        }
+    }
+
+    let mut order: [usize; N] = std::array::from_fn(|index| index);
+    let lengths = columns.map(|column| dot(&column, &column).sqrt());
+    order.sort_by(|&a, &b| lengths[b].total_cmp(&lengths[a]));
+
+    let singular_values = order.map(|index| lengths[index]);
+    let v = order.map(|index| v[index]);
+
+    let mut u: Vec<[f64; N]> = vec![];
+    for index in order {
+        let length = lengths[index];
+        if length > SVD_TOLERANCE * scale {
+            u.push(columns[index].map(|entry| entry / length));
+        }
+    }
+
+    // The columns for zero singular values can be anything that keeps U orthogonal, so we use
+    // whichever standard basis vector is furthest from the columns that we already have
+    while u.len() < N {
+        let column = (0..N)
+            .map(|axis| {
+                let mut vector = [0.; N];
+                vector[axis] = 1.;
+                for other in &u {
+                    let projection = dot(&vector, other);
+                    for (entry, other_entry) in vector.iter_mut().zip(other) {
+                        *entry -= projection * other_entry;
+                    }
+                }
+                vector
+            })
+            .max_by(|a, b| dot(a, a).total_cmp(&dot(b, b)))
+            .expect("There should be at least one axis");
+        let length = dot(&column, &column).sqrt();
+        u.push(column.map(|entry| entry / length));
+    }
+
+    let u = u
+        .try_into()
+        .unwrap_or_else(|_| unreachable!("U should have exactly N columns"));
+    Some((u, singular_values, v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn svd_2d_matrices() {
+        let svd = svd_2d(DMat2::from_cols(DVec2::new(3., 0.), DVec2::new(0., -5.))).unwrap();
+        assert_relative_eq!(svd.singular_values, DVec2::new(5., 3.));
+
+        // A shear maps the unit circle to an ellipse with these semi-axes
+        let svd = svd_2d(DMat2::from_cols(DVec2::new(1., 0.), DVec2::new(1., 1.))).unwrap();
+        let golden = (1. + 5f64.sqrt()) / 2.;
+        assert_relative_eq!(
+            svd.singular_values,
+            DVec2::new(golden, golden.recip()),
+            epsilon = 0.000000001
+        );
+
+        let svd = svd_2d(DMat2::ZERO).unwrap();
+        assert_eq!(svd.singular_values, DVec2::ZERO);
+        assert_relative_eq!(svd.u * svd.u.transpose(), DMat2::IDENTITY);
+
+        assert_eq!(svd_2d(DMat2::from_diagonal(DVec2::new(1., f64::NAN))), None);
+
+        for matrix in (0..100)
+            .map(|_| rand::random::<DMat2>() * 10.)
+            .chain([DMat2::from_cols(DVec2::new(1., 2.), DVec2::new(2., 4.))])
+        {
+            let svd = svd_2d(matrix).unwrap();
+            assert_relative_eq!(
+                svd.u * svd.u.transpose(),
+                DMat2::IDENTITY,
+                epsilon = 0.000000001
+            );
+            assert_relative_eq!(
+                svd.v_transpose * svd.v_transpose.transpose(),
+                DMat2::IDENTITY,
+                epsilon = 0.000000001
+            );
+            assert!(svd.singular_values.x >= svd.singular_values.y);
+            assert!(svd.singular_values.y >= 0.);
+            assert_relative_eq!(
+                svd.u * DMat2::from_diagonal(svd.singular_values) * svd.v_transpose,
+                matrix,
+                epsilon = 0.000000001
+            );
+        }
+    }
+
+    #[test]
+    fn svd_3d_matrices() {
+        let svd = svd_3d(DMat3::from_diagonal(DVec3::new(2., -7., 3.))).unwrap();
+        assert_relative_eq!(svd.singular_values, DVec3::new(7., 3., 2.));
+
+        // Rank 1, so two of the columns of U have to be made up
+        let svd = svd_3d(DMat3::from_cols(
+            DVec3::new(1., 2., 3.),
+            DVec3::new(2., 4., 6.),
+            DVec3::new(-1., -2., -3.),
+        ))
+        .unwrap();
+        assert_relative_eq!(
+            svd.singular_values,
+            DVec3::new(6f64.sqrt() * 14f64.sqrt(), 0., 0.),
+            epsilon = 0.000000001
+        );
+        assert_relative_eq!(
+            svd.u * svd.u.transpose(),
+            DMat3::IDENTITY,
+            epsilon = 0.000000001
+        );
+
+        for matrix in (0..100).map(|_| rand::random::<DMat3>() * 10.) {
+            let svd = svd_3d(matrix).unwrap();
+            assert_relative_eq!(
+                svd.u * svd.u.transpose(),
+                DMat3::IDENTITY,
+                epsilon = 0.000000001
+            );
+            assert_relative_eq!(
+                svd.v_transpose * svd.v_transpose.transpose(),
+                DMat3::IDENTITY,
+                epsilon = 0.000000001
+            );
+            assert!(svd.singular_values.x >= svd.singular_values.y);
+            assert!(svd.singular_values.y >= svd.singular_values.z);
+            assert!(svd.singular_values.z >= 0.);
+            assert_relative_eq!(
+                svd.u * DMat3::from_diagonal(svd.singular_values) * svd.v_transpose,
+                matrix,
+                epsilon = 0.000000001
+            );
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! This module provides animated transitions from one matrix to another, built on top of
+//! [`Interpolation`]. See [`Transition`], and [`Timeline`] for chaining them together.
+
+use super::{Interpolate, Interpolation};
+
+/// The pace of a [`Transition`], which turns the fraction of its duration that has passed into
+/// how far it is from the start matrix to the end matrix.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Easing {
+    /// Move at a constant speed.
+    Linear,
+
+    /// Speed up from the start and slow down into the end.
+    #[default]
+    EaseInOut,
+
+    /// Start slowly and speed up into the end.
+    EaseIn,
+
+    /// Start quickly and slow down into the end.
+    EaseOut,
+}
+
+impl Easing {
+    /// Every easing, in order.
+    pub const ALL: [Self; 4] = [Self::Linear, Self::EaseInOut, Self::EaseIn, Self::EaseOut];
+
+    /// Ease this fraction of the duration, which gets clamped between 0 and 1. The result is 0 at
+    /// the start and 1 at the end, for every easing.
+    pub fn ease(self, t: f64) -> f64 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Self::Linear => t,
+            Self::EaseInOut => t * t * (3. - 2. * t),
+            Self::EaseIn => t * t,
+            Self::EaseOut => t * (2. - t),
+        }
+    }
+}
+
+/// An animation from one matrix to another over a duration in seconds, like when a newly entered
+/// expression replaces the matrix being shown.
+///
+/// ```
+/// # use trinity::math::{Easing, Transition};
+/// # use approx::assert_relative_eq;
+/// # use glam::DMat2;
+/// let transition = Transition::new(DMat2::IDENTITY, DMat2::from_angle(3.), 2.);
+/// assert_eq!(transition.at(0.), DMat2::IDENTITY);
+/// assert_relative_eq!(transition.at(1.), DMat2::from_angle(1.5), epsilon = 0.000000001);
+/// assert_relative_eq!(transition.at(5.), DMat2::from_angle(3.), epsilon = 0.000000001);
+/// assert!(transition.is_finished(2.));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transition<M> {
+    /// The matrix at the start of the transition.
+    pub from: M,
+
+    /// The matrix at the end of the transition.
+    pub to: M,
+
+    /// How long the transition lasts, in seconds. A transition with no duration jumps straight
+    /// to the end.
+    pub duration: f64,
+
+    /// The pace of the transition.
+    pub easing: Easing,
+
+    /// How to get from one matrix to the other.
+    pub interpolation: Interpolation,
+}
+
+impl<M: Interpolate + Copy> Transition<M> {
+    /// Create a new transition with the default easing and interpolation.
+    pub fn new(from: M, to: M, duration: f64) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            easing: Easing::default(),
+            interpolation: Interpolation::default(),
+        }
+    }
+
+    /// Get how far through the transition we are after this many seconds, from 0 at the start
+    /// to 1 at the end, before easing.
+    pub fn progress(&self, elapsed: f64) -> f64 {
+        if self.duration > 0. {
+            (elapsed / self.duration).clamp(0., 1.)
+        } else {
+            1.
+        }
+    }
+
+    /// Has the transition finished after this many seconds?
+    pub fn is_finished(&self, elapsed: f64) -> bool {
+        self.progress(elapsed) >= 1.
+    }
+
+    /// Get the matrix to show after this many seconds.
+    pub fn at(&self, elapsed: f64) -> M {
+        match self.progress(elapsed) {
+            0. => self.from,
+            1. => self.to,
+            progress => {
+                self.interpolation
+                    .interpolate(self.from, self.to, self.easing.ease(progress))
+            }
+        }
+    }
+}
+
+/// One step of a [`Timeline`], which animates to this matrix from the one before.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keyframe<M> {
+    /// The matrix at the end of this step.
+    pub matrix: M,
+
+    /// How long this step lasts, in seconds. See [`Transition::duration`].
+    pub duration: f64,
+
+    /// The pace of this step.
+    pub easing: Easing,
+}
+
+impl<M> Keyframe<M> {
+    /// Create a new keyframe with the default easing.
+    pub fn new(matrix: M, duration: f64) -> Self {
+        Self {
+            matrix,
+            duration,
+            easing: Easing::default(),
+        }
+    }
+}
+
+/// A queue of [`Keyframe`]s which are animated through in order, starting from a fixed matrix.
+///
+/// The keyframes are a plain [`Vec`], so they can be added, removed and reordered freely.
+///
+/// ```
+/// # use trinity::math::{Keyframe, Timeline};
+/// # use glam::DMat2;
+/// let mut timeline = Timeline::new(DMat2::IDENTITY);
+/// timeline.keyframes.push(Keyframe::new(DMat2::IDENTITY * 2., 1.));
+/// timeline.keyframes.push(Keyframe::new(DMat2::from_angle(1.), 3.));
+///
+/// assert_eq!(timeline.duration(), 4.);
+/// assert_eq!(timeline.at(1.), DMat2::IDENTITY * 2.);
+/// assert_eq!(timeline.at(4.), DMat2::from_angle(1.));
+///
+/// timeline.move_keyframe(1, 0);
+/// assert_eq!(timeline.at(3.), DMat2::from_angle(1.));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timeline<M> {
+    /// The matrix before the first keyframe.
+    pub start: M,
+
+    /// The keyframes, in the order that they're animated through.
+    pub keyframes: Vec<Keyframe<M>>,
+
+    /// How to get from each matrix to the next.
+    pub interpolation: Interpolation,
+}
+
+impl<M: Interpolate + Copy> Timeline<M> {
+    /// Create a new timeline with no keyframes and the default interpolation.
+    pub fn new(start: M) -> Self {
+        Self {
+            start,
+            keyframes: Vec::new(),
+            interpolation: Interpolation::default(),
+        }
+    }
+
+    /// Get the total length of the timeline, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.keyframes
+            .iter()
+            .map(|keyframe| keyframe.duration.max(0.))
+            .sum()
+    }
+
+    /// Move the keyframe at index `from` so that it ends up at index `to`, shifting the keyframes
+    /// in between.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is out of bounds.
+    pub fn move_keyframe(&mut self, from: usize, to: usize) {
+        assert!(
+            to < self.keyframes.len(),
+            "Cannot move a keyframe to index {to} of {}",
+            self.keyframes.len()
+        );
+        let keyframe = self.keyframes.remove(from);
+        self.keyframes.insert(to, keyframe);
+    }
+
+    /// Get the [`Transition`] for each keyframe, from the matrix before it, in order.
+    pub fn transitions(&self) -> impl Iterator<Item = Transition<M>> + '_ {
+        self.keyframes.iter().scan(self.start, |from, keyframe| {
+            let transition = Transition {
+                from: *from,
+                to: keyframe.matrix,
+                duration: keyframe.duration,
+                easing: keyframe.easing,
+                interpolation: self.interpolation,
+            };
+            *from = keyframe.matrix;
+            Some(transition)
+        })
+    }
+
+    /// Has the whole timeline finished after this many seconds?
+    pub fn is_finished(&self, elapsed: f64) -> bool {
+        elapsed >= self.duration()
+    }
+
+    /// Get the matrix to show after this many seconds.
+    pub fn at(&self, mut elapsed: f64) -> M {
+        let mut matrix = self.start;
+        for transition in self.transitions() {
+            if elapsed < transition.duration {
+                return transition.at(elapsed);
+            }
+            elapsed -= transition.duration.max(0.);
+            matrix = transition.to;
+        }
+        matrix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use glam::{DMat3, DVec3};
+
+    #[test]
+    fn transitions() {
+        for easing in Easing::ALL {
+            assert_eq!(easing.ease(-1.), 0.);
+            assert_eq!(easing.ease(0.), 0.);
+            assert_eq!(easing.ease(1.), 1.);
+            assert_eq!(easing.ease(2.), 1.);
+        }
+        assert_eq!(Easing::Linear.ease(0.25), 0.25);
+        assert_eq!(Easing::EaseInOut.ease(0.5), 0.5);
+        assert!(Easing::EaseIn.ease(0.5) < 0.5);
+        assert!(Easing::EaseOut.ease(0.5) > 0.5);
+
+        let scale = DMat3::from_diagonal(DVec3::new(2., 3., 4.));
+        let mut transition = Transition::new(DMat3::IDENTITY, scale, 4.);
+        transition.easing = Easing::Linear;
+        transition.interpolation = Interpolation::Linear;
+        assert_eq!(transition.progress(1.), 0.25);
+        assert_relative_eq!(
+            transition.at(2.),
+            (DMat3::IDENTITY + scale) / 2.,
+            epsilon = 0.000000001
+        );
+        assert!(!transition.is_finished(3.9));
+        assert_eq!(transition.at(-1.), DMat3::IDENTITY);
+        assert_eq!(transition.at(10.), scale);
+
+        transition.duration = 0.;
+        assert!(transition.is_finished(0.));
+        assert_eq!(transition.at(0.), scale);
+    }
+
+    #[test]
+    fn timelines() {
+        let [a, b, c] = [2., 3., 4.].map(|x| DMat3::IDENTITY * x);
+        let mut timeline = Timeline::new(DMat3::IDENTITY);
+        assert_eq!(timeline.duration(), 0.);
+        assert!(timeline.is_finished(0.));
+        assert_eq!(timeline.at(1.), DMat3::IDENTITY);
+
+        timeline.interpolation = Interpolation::Linear;
+        timeline.keyframes = vec![
+            Keyframe {
+                easing: Easing::Linear,
+                ..Keyframe::new(a, 2.)
+            },
+            Keyframe::new(b, 0.),
+            Keyframe::new(c, 1.),
+        ];
+        assert_eq!(timeline.duration(), 3.);
+        assert_eq!(timeline.at(-1.), DMat3::IDENTITY);
+        assert_relative_eq!(
+            timeline.at(1.),
+            DMat3::IDENTITY * 1.5,
+            epsilon = 0.000000001
+        );
+        assert_eq!(timeline.at(2.), b, "Keyframes with no duration are skipped");
+        assert_relative_eq!(
+            timeline.at(2.5),
+            DMat3::IDENTITY * 3.5,
+            epsilon = 0.000000001
+        );
+        assert!(!timeline.is_finished(2.9));
+        assert_eq!(timeline.at(3.), c);
+
+        let transitions: Vec<_> = timeline.transitions().collect();
+        assert_eq!(
+            transitions
+                .iter()
+                .map(|t| (t.from, t.to))
+                .collect::<Vec<_>>(),
+            vec![(DMat3::IDENTITY, a), (a, b), (b, c)]
+        );
+
+        timeline.move_keyframe(0, 2);
+        assert_eq!(
+            timeline
+                .keyframes
+                .iter()
+                .map(|keyframe| keyframe.matrix)
+                .collect::<Vec<_>>(),
+            vec![b, c, a]
+        );
+        assert_eq!(timeline.at(1.), c);
+    }
+}
@@ -0,0 +1,304 @@
+//! This module provides [`CMatN`], a square matrix of complex numbers.
+
+use super::{
+    square::{self, SquareMatrix},
+    DMatN, MatrixValue,
+};
+use crate::math::{frobenius_norm, CheckedProduct, Complex, Invertible, Norm, PowerZero};
+use std::ops::{Mul, Neg};
+
+/// A square matrix of [`Complex`] numbers with any non-zero dimension, stored in column-major
+/// order like [`DMatN`].
+///
+/// A complex matrix is never turned back into a real matrix automatically, even if all of its
+/// entries happen to be real, so the type of an expression only depends on its structure.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CMatN {
+    /// The number of rows and columns.
+    dimension: usize,
+
+    /// The entries, column by column. There are always `dimension * dimension` of them.
+    entries: Vec<Complex>,
+}
+
+impl From<&DMatN> for CMatN {
+    fn from(matrix: &DMatN) -> Self {
+        Self::from_fn(matrix.dimension(), |row, column| {
+            matrix
+                .get(row, column)
+                .expect("Indices should be in bounds")
+                .into()
+        })
+    }
+}
+
+impl From<MatrixValue> for CMatN {
+    fn from(matrix: MatrixValue) -> Self {
+        match matrix {
+            MatrixValue::TwoD(matrix) => (&DMatN::from(matrix)).into(),
+            MatrixValue::ThreeD(matrix) => (&DMatN::from(matrix)).into(),
+            MatrixValue::Dynamic(matrix) => (&matrix).into(),
+        }
+    }
+}
+
+/// Matrices are serialized as a list of rows, and checked to be square when deserialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for CMatN {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_rows().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CMatN {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<Vec<Complex>>::deserialize(deserializer)?;
+        Self::from_rows(&rows)
+            .ok_or_else(|| serde::de::Error::custom("matrix rows must form a non-empty square"))
+    }
+}
+
+impl CMatN {
+    /// Create a matrix by calling `entry(row, column)` for every entry. The indices are 0-based.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dimension is 0.
+    pub fn from_fn(dimension: usize, mut entry: impl FnMut(usize, usize) -> Complex) -> Self {
+        assert!(dimension > 0, "CMatN must have a non-zero dimension");
+        Self {
+            dimension,
+            entries: (0..dimension)
+                .flat_map(|column| (0..dimension).map(move |row| (row, column)))
+                .map(|(row, column)| entry(row, column))
+                .collect(),
+        }
+    }
+
+    /// Create a matrix from its rows, returning `None` unless there are as many rows as each row
+    /// has entries.
+    pub fn from_rows(rows: &[Vec<Complex>]) -> Option<Self> {
+        let dimension = rows.len();
+        (dimension > 0 && rows.iter().all(|row| row.len() == dimension))
+            .then(|| Self::from_fn(dimension, |row, column| rows[row][column]))
+    }
+
+    /// The identity matrix of the given dimension.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dimension is 0.
+    pub fn identity(dimension: usize) -> Self {
+        Self::from_fn(dimension, |row, column| {
+            if row == column {
+                Complex::ONE
+            } else {
+                Complex::ZERO
+            }
+        })
+    }
+
+    /// The number of rows and columns of this matrix.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Get the entry in the given row and column of this matrix, if it exists. The indices are
+    /// 0-based.
+    pub fn get(&self, row: usize, column: usize) -> Option<Complex> {
+        (row < self.dimension && column < self.dimension)
+            .then(|| self.entries[column * self.dimension + row])
+    }
+
+    /// The rows of this matrix, from top to bottom.
+    pub fn to_rows(&self) -> Vec<Vec<Complex>> {
+        (0..self.dimension)
+            .map(|row| {
+                (0..self.dimension)
+                    .map(|column| self.entries[column * self.dimension + row])
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The transpose of this matrix. This doesn't conjugate the entries.
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.dimension, |row, column| {
+            self.entries[row * self.dimension + column]
+        })
+    }
+
+    /// Try to add two matrices together.
+    ///
+    /// This method will fail if the two matrices are of different dimensions.
+    pub fn try_add(left: &Self, right: &Self) -> Option<Self> {
+        (left.dimension == right.dimension).then(|| Self {
+            dimension: left.dimension,
+            entries: left
+                .entries
+                .iter()
+                .zip(&right.entries)
+                .map(|(&a, &b)| a + b)
+                .collect(),
+        })
+    }
+
+    /// Try to multiply two matrices together.
+    ///
+    /// This method will fail if the two matrices are of different dimensions.
+    pub fn try_mul(left: &Self, right: &Self) -> Option<Self> {
+        let n = left.dimension;
+        (n == right.dimension).then(|| {
+            Self::from_fn(n, |row, column| {
+                (0..n).fold(Complex::ZERO, |sum, k| {
+                    sum + left.entries[k * n + row] * right.entries[column * n + k]
+                })
+            })
+        })
+    }
+
+    /// Raise this matrix to a non-negative integer power, using the square and multiply
+    /// algorithm like [`DMatN::powu`].
+    pub fn powu(&self, power: u32) -> Self {
+        square::powu(self, power.into())
+    }
+
+    /// Are all the entries of this matrix finite?
+    pub fn is_finite(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|z| z.re.is_finite() && z.im.is_finite())
+    }
+
+    /// Try to invert this matrix with Gauss-Jordan elimination, returning `None` if it's
+    /// singular.
+    ///
+    /// A pivot counts as zero if it's within `epsilon` times the largest entry of the matrix.
+    pub fn try_inverse(&self, epsilon: f64) -> Option<Self> {
+        square::try_inverse(self, epsilon)
+    }
+}
+
+impl SquareMatrix for CMatN {
+    type Entry = Complex;
+
+    fn identity(dimension: usize) -> Self {
+        Self::identity(dimension)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn try_mul(left: &Self, right: &Self) -> Option<Self> {
+        Self::try_mul(left, right)
+    }
+
+    fn to_rows(&self) -> Vec<Vec<Complex>> {
+        self.to_rows()
+    }
+
+    fn from_square_rows(rows: Vec<Vec<Complex>>) -> Self {
+        Self::from_rows(&rows).expect("The rows should form a non-empty square")
+    }
+}
+
+impl Invertible for CMatN {
+    fn checked_inverse(&self, epsilon: f64) -> Option<Self> {
+        self.try_inverse(epsilon)
+    }
+}
+
+impl CheckedProduct for CMatN {
+    fn checked_product(self, rhs: Self) -> Option<Self> {
+        let product = Self::try_mul(&self, &rhs).expect("Dimensions should match");
+        product.is_finite().then_some(product)
+    }
+}
+
+impl PowerZero for CMatN {
+    fn power_zero(&self) -> Self {
+        Self::identity(self.dimension)
+    }
+}
+
+impl Mul<Complex> for CMatN {
+    type Output = CMatN;
+
+    fn mul(mut self, rhs: Complex) -> Self::Output {
+        self.entries
+            .iter_mut()
+            .for_each(|entry| *entry = *entry * rhs);
+        self
+    }
+}
+
+impl Mul<CMatN> for Complex {
+    type Output = CMatN;
+
+    fn mul(self, rhs: CMatN) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl Neg for CMatN {
+    type Output = CMatN;
+
+    fn neg(self) -> Self::Output {
+        self * -Complex::ONE
+    }
+}
+
+impl Norm for CMatN {
+    fn norm(&self) -> f64 {
+        let parts: Vec<f64> = self.entries.iter().flat_map(|z| [z.re, z.im]).collect();
+        frobenius_norm(&parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::EPSILON;
+    use approx::assert_relative_eq;
+    use glam::DMat2;
+
+    #[test]
+    fn cmatn_arithmetic() {
+        // A rotation by 90 degrees has eigenvalues i and -i
+        let rotation = CMatN::from(MatrixValue::TwoD(DMat2::from_cols_array(&[
+            0., 1., -1., 0.,
+        ])));
+        assert_eq!(rotation.get(0, 1), Some(-Complex::ONE));
+        assert_eq!(rotation.get(2, 0), None);
+
+        let shifted = CMatN::try_add(&rotation, &(-Complex::I * CMatN::identity(2))).unwrap();
+        assert_eq!(shifted.try_inverse(EPSILON), None);
+
+        assert_eq!(rotation.powu(4), CMatN::identity(2));
+        assert_eq!(
+            CMatN::try_mul(&rotation, &rotation.try_inverse(EPSILON).unwrap()).unwrap(),
+            CMatN::identity(2)
+        );
+        assert_eq!(CMatN::try_mul(&rotation, &CMatN::identity(3)), None);
+        assert_eq!(
+            (Complex::I * CMatN::identity(2)).transpose(),
+            Complex::I * CMatN::identity(2)
+        );
+        assert_relative_eq!((Complex::new(3., 4.) * CMatN::identity(4)).norm(), 10.);
+
+        for _ in 0..100 {
+            let matrix = CMatN::from_fn(3, |_, _| Complex::new(rand::random(), rand::random()));
+            let product = CMatN::try_mul(&matrix, &matrix.try_inverse(EPSILON).unwrap()).unwrap();
+            for row in 0..3 {
+                for column in 0..3 {
+                    let entry = product.get(row, column).unwrap();
+                    let expected = if row == column { 1. } else { 0. };
+                    assert_relative_eq!(entry.re, expected, epsilon = 0.000001);
+                    assert_relative_eq!(entry.im, 0., epsilon = 0.000001);
+                }
+            }
+        }
+    }
+}
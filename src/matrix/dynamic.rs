@@ -0,0 +1,472 @@
+//! This module provides [`DMatN`], a square matrix of any dimension.
+
+use super::square::{self, SquareMatrix};
+use crate::math::{
+    frobenius_norm, CheckedProduct, Invertible, MatrixNorms, MatrixPredicates, MatrixProperty,
+    Norm, PowerZero,
+};
+use approx::RelativeEq;
+use glam::{DMat2, DMat3};
+use std::ops::{Mul, Neg};
+
+/// The relative change in the estimate of the largest eigenvalue of `MᵀM` at which power
+/// iteration stops, when finding the spectral norm.
+const SPECTRAL_TOLERANCE: f64 = 0.00000000000001;
+
+/// The maximum number of steps of power iteration when finding the spectral norm.
+const MAX_POWER_ITERATIONS: usize = 1000;
+
+/// A square matrix of `f64`s with any non-zero dimension, stored in column-major order like the
+/// `glam` matrices.
+///
+/// This is slower than [`DMat2`] and [`DMat3`], and can't be visualised, so it's only used for
+/// matrices which are 4×4 or larger. See [`MatrixValue`](super::MatrixValue).
+#[derive(Clone, Debug, PartialEq)]
+pub struct DMatN {
+    /// The number of rows and columns.
+    dimension: usize,
+
+    /// The entries, column by column. There are always `dimension * dimension` of them.
+    entries: Vec<f64>,
+}
+
+impl From<DMat2> for DMatN {
+    fn from(matrix: DMat2) -> Self {
+        Self {
+            dimension: 2,
+            entries: matrix.to_cols_array().to_vec(),
+        }
+    }
+}
+
+impl From<DMat3> for DMatN {
+    fn from(matrix: DMat3) -> Self {
+        Self {
+            dimension: 3,
+            entries: matrix.to_cols_array().to_vec(),
+        }
+    }
+}
+
+/// Matrices are serialized as a list of rows, and checked to be square when deserialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DMatN {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_rows().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DMatN {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<Vec<f64>>::deserialize(deserializer)?;
+        Self::from_rows(&rows)
+            .ok_or_else(|| serde::de::Error::custom("matrix rows must form a non-empty square"))
+    }
+}
+
+impl DMatN {
+    /// Create a matrix by calling `entry(row, column)` for every entry. The indices are 0-based.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dimension is 0.
+    pub fn from_fn(dimension: usize, mut entry: impl FnMut(usize, usize) -> f64) -> Self {
+        assert!(dimension > 0, "DMatN must have a non-zero dimension");
+        Self {
+            dimension,
+            entries: (0..dimension)
+                .flat_map(|column| (0..dimension).map(move |row| (row, column)))
+                .map(|(row, column)| entry(row, column))
+                .collect(),
+        }
+    }
+
+    /// Create a matrix from its rows, returning `None` unless there are as many rows as each row
+    /// has entries.
+    ///
+    /// ```
+    /// # use trinity::matrix::DMatN;
+    /// let matrix = DMatN::from_rows(&[vec![1., 2.], vec![3., 4.]]).unwrap();
+    /// assert_eq!(matrix.get(0, 1), Some(2.));
+    ///
+    /// assert_eq!(DMatN::from_rows(&[vec![1., 2.]]), None);
+    /// assert_eq!(DMatN::from_rows(&[]), None);
+    /// ```
+    pub fn from_rows(rows: &[Vec<f64>]) -> Option<Self> {
+        let dimension = rows.len();
+        (dimension > 0 && rows.iter().all(|row| row.len() == dimension))
+            .then(|| Self::from_fn(dimension, |row, column| rows[row][column]))
+    }
+
+    /// The zero matrix of the given dimension.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dimension is 0.
+    pub fn zeros(dimension: usize) -> Self {
+        Self::from_fn(dimension, |_, _| 0.)
+    }
+
+    /// The identity matrix of the given dimension.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dimension is 0.
+    pub fn identity(dimension: usize) -> Self {
+        Self::from_fn(dimension, |row, column| if row == column { 1. } else { 0. })
+    }
+
+    /// The number of rows and columns of this matrix.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Get the entry in the given row and column of this matrix, if it exists. The indices are
+    /// 0-based.
+    pub fn get(&self, row: usize, column: usize) -> Option<f64> {
+        (row < self.dimension && column < self.dimension)
+            .then(|| self.entries[column * self.dimension + row])
+    }
+
+    /// The rows of this matrix, from top to bottom.
+    pub fn to_rows(&self) -> Vec<Vec<f64>> {
+        (0..self.dimension)
+            .map(|row| {
+                (0..self.dimension)
+                    .map(|column| self.entries[column * self.dimension + row])
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Convert this matrix to a [`DMat2`] if it's 2×2.
+    pub fn to_dmat2(&self) -> Option<DMat2> {
+        (self.dimension == 2).then(|| DMat2::from_cols_slice(&self.entries))
+    }
+
+    /// Convert this matrix to a [`DMat3`] if it's 3×3.
+    pub fn to_dmat3(&self) -> Option<DMat3> {
+        (self.dimension == 3).then(|| DMat3::from_cols_slice(&self.entries))
+    }
+
+    /// The transpose of this matrix.
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.dimension, |row, column| {
+            self.entries[row * self.dimension + column]
+        })
+    }
+
+    /// Try to add two matrices together.
+    ///
+    /// This method will fail if the two matrices are of different dimensions.
+    pub fn try_add(left: &Self, right: &Self) -> Option<Self> {
+        (left.dimension == right.dimension).then(|| Self {
+            dimension: left.dimension,
+            entries: left
+                .entries
+                .iter()
+                .zip(&right.entries)
+                .map(|(a, b)| a + b)
+                .collect(),
+        })
+    }
+
+    /// Try to multiply two matrices together.
+    ///
+    /// This method will fail if the two matrices are of different dimensions.
+    pub fn try_mul(left: &Self, right: &Self) -> Option<Self> {
+        let n = left.dimension;
+        (n == right.dimension).then(|| {
+            Self::from_fn(n, |row, column| {
+                (0..n)
+                    .map(|k| left.entries[k * n + row] * right.entries[column * n + k])
+                    .sum()
+            })
+        })
+    }
+
+    /// Raise this matrix to a non-negative integer power, using the square and multiply
+    /// algorithm like [`integer_power`](crate::math::integer_power).
+    pub fn powu(&self, power: u32) -> Self {
+        square::powu(self, power.into())
+    }
+
+    /// Are all the entries of this matrix finite?
+    pub fn is_finite(&self) -> bool {
+        self.entries.iter().all(|x| x.is_finite())
+    }
+
+    /// The determinant of this matrix, found by Gaussian elimination with partial pivoting.
+    pub fn determinant(&self) -> f64 {
+        square::determinant(self)
+    }
+
+    /// Try to invert this matrix with Gauss-Jordan elimination, returning `None` if it's
+    /// singular.
+    ///
+    /// A pivot counts as zero if it's within `epsilon` times the largest entry of the matrix.
+    pub fn try_inverse(&self, epsilon: f64) -> Option<Self> {
+        square::try_inverse(self, epsilon)
+    }
+}
+
+impl Mul<f64> for DMatN {
+    type Output = DMatN;
+
+    fn mul(mut self, rhs: f64) -> Self::Output {
+        self.entries.iter_mut().for_each(|entry| *entry *= rhs);
+        self
+    }
+}
+
+impl Mul<DMatN> for f64 {
+    type Output = DMatN;
+
+    fn mul(self, rhs: DMatN) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl Neg for DMatN {
+    type Output = DMatN;
+
+    fn neg(self) -> Self::Output {
+        self * -1.
+    }
+}
+
+impl Norm for DMatN {
+    fn norm(&self) -> f64 {
+        frobenius_norm(&self.entries)
+    }
+}
+
+/// The spectral norm is found by power iteration on `MᵀM`, since there's no singular value
+/// decomposition for matrices of any dimension.
+impl MatrixNorms for DMatN {
+    fn spectral_norm(&self) -> f64 {
+        if !self.is_finite() {
+            return f64::NAN;
+        }
+
+        let n = self.dimension;
+        let gram = Self::try_mul(&self.transpose(), self).expect("Dimensions should match");
+        let length = |vector: &[f64]| vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+        // Start from the longest column, which can only be zero if the whole matrix is zero
+        let mut vector = gram
+            .entries
+            .chunks(n)
+            .max_by(|a, b| length(a).total_cmp(&length(b)))
+            .expect("There should be at least one column")
+            .to_vec();
+        let mut eigenvalue = 0.;
+
+        for _ in 0..MAX_POWER_ITERATIONS {
+            let vector_length = length(&vector);
+            if vector_length == 0. {
+                return 0.;
+            }
+            vector.iter_mut().for_each(|x| *x /= vector_length);
+
+            let next: Vec<f64> = (0..n)
+                .map(|row| (0..n).map(|k| gram.entries[k * n + row] * vector[k]).sum())
+                .collect();
+            let next_eigenvalue: f64 = vector.iter().zip(&next).map(|(a, b)| a * b).sum();
+            vector = next;
+
+            let converged =
+                (next_eigenvalue - eigenvalue).abs() <= SPECTRAL_TOLERANCE * next_eigenvalue;
+            eigenvalue = next_eigenvalue;
+            if converged {
+                break;
+            }
+        }
+
+        eigenvalue.sqrt()
+    }
+
+    fn max_entry_norm(&self) -> f64 {
+        self.entries.iter().fold(0., |max, x| max.max(x.abs()))
+    }
+}
+
+impl SquareMatrix for DMatN {
+    type Entry = f64;
+
+    fn identity(dimension: usize) -> Self {
+        Self::identity(dimension)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn try_mul(left: &Self, right: &Self) -> Option<Self> {
+        Self::try_mul(left, right)
+    }
+
+    fn to_rows(&self) -> Vec<Vec<f64>> {
+        self.to_rows()
+    }
+
+    fn from_square_rows(rows: Vec<Vec<f64>>) -> Self {
+        Self::from_rows(&rows).expect("The rows should form a non-empty square")
+    }
+}
+
+impl Invertible for DMatN {
+    fn checked_inverse(&self, epsilon: f64) -> Option<Self> {
+        self.try_inverse(epsilon)
+    }
+}
+
+impl CheckedProduct for DMatN {
+    fn checked_product(self, rhs: Self) -> Option<Self> {
+        let product = Self::try_mul(&self, &rhs).expect("Dimensions should match");
+        product.is_finite().then_some(product)
+    }
+}
+
+impl PowerZero for DMatN {
+    fn power_zero(&self) -> Self {
+        Self::identity(self.dimension)
+    }
+}
+
+impl MatrixPredicates for DMatN {
+    fn has_property_within(
+        &self,
+        property: MatrixProperty,
+        epsilon: f64,
+        max_relative: f64,
+    ) -> bool {
+        let is_orthogonal = || {
+            let product = Self::try_mul(&self.transpose(), self).expect("Dimensions should match");
+            product.entries.iter().enumerate().all(|(index, &entry)| {
+                let expected = if index % (self.dimension + 1) == 0 {
+                    1.
+                } else {
+                    0.
+                };
+                entry.relative_eq(&expected, epsilon, max_relative)
+            })
+        };
+
+        match property {
+            MatrixProperty::Orthogonal => is_orthogonal(),
+            MatrixProperty::Symmetric => {
+                let tolerance = epsilon * self.max_entry_norm();
+                self.entries
+                    .iter()
+                    .zip(&self.transpose().entries)
+                    .all(|(a, b)| a.relative_eq(b, tolerance, max_relative))
+            }
+            MatrixProperty::Singular => self.try_inverse(epsilon).is_none(),
+            MatrixProperty::Rotation => is_orthogonal() && self.determinant() > 0.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{signed_integer_power, PowerError, EPSILON};
+    use approx::assert_relative_eq;
+    use glam::DVec3;
+
+    /// A 4×4 matrix with determinant 1.
+    fn matrix() -> DMatN {
+        DMatN::from_rows(&[
+            vec![1., 2., 0., 0.],
+            vec![0., 1., 3., 0.],
+            vec![0., 0., 1., 4.],
+            vec![0., 0., 0., 1.],
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn dmatn_matches_glam() {
+        for _ in 0..100 {
+            let a = rand::random::<DMat3>();
+            let b = rand::random::<DMat3>();
+            let (dyn_a, dyn_b) = (DMatN::from(a), DMatN::from(b));
+
+            assert_eq!(
+                DMatN::try_add(&dyn_a, &dyn_b).unwrap().to_dmat3(),
+                Some(a + b)
+            );
+            assert_relative_eq!(
+                DMatN::try_mul(&dyn_a, &dyn_b).unwrap().to_dmat3().unwrap(),
+                a * b,
+                epsilon = 0.000000001
+            );
+            assert_relative_eq!(dyn_a.determinant(), a.determinant(), epsilon = 0.000000001);
+            assert_eq!(dyn_a.transpose().to_dmat3(), Some(a.transpose()));
+            assert_relative_eq!(dyn_a.norm(), a.norm(), epsilon = 0.000000001);
+            assert_relative_eq!(
+                (dyn_a.clone() * 1e200).norm(),
+                a.norm() * 1e200,
+                max_relative = 0.000000001
+            );
+            assert_relative_eq!(dyn_a.spectral_norm(), a.spectral_norm(), epsilon = 0.000001);
+            assert_eq!(dyn_a.max_entry_norm(), a.max_entry_norm());
+            assert_relative_eq!(
+                dyn_a.try_inverse(EPSILON).unwrap().to_dmat3().unwrap(),
+                a.inverse(),
+                epsilon = 0.000001
+            );
+            assert_relative_eq!(
+                dyn_a.powu(5).to_dmat3().unwrap(),
+                a * a * a * a * a,
+                epsilon = 0.000000001
+            );
+        }
+
+        assert_eq!(DMatN::from(DMat2::IDENTITY).to_dmat3(), None);
+    }
+
+    #[test]
+    fn dmatn_four_by_four() {
+        let matrix = matrix();
+        assert_eq!(matrix.dimension(), 4);
+        assert_eq!(matrix.get(2, 3), Some(4.));
+        assert_eq!(matrix.get(3, 2), Some(0.));
+        assert_eq!(matrix.get(4, 0), None);
+        assert_eq!(matrix.determinant(), 1.);
+        assert_eq!(matrix.powu(0), DMatN::identity(4));
+        assert_eq!(DMatN::zeros(4).spectral_norm(), 0.);
+
+        let inverse = matrix.try_inverse(EPSILON).unwrap();
+        assert_eq!(
+            DMatN::try_mul(&matrix, &inverse).unwrap(),
+            DMatN::identity(4)
+        );
+        assert_eq!(DMatN::try_mul(&matrix, &DMatN::identity(3)), None);
+
+        let singular = DMatN::from_fn(4, |row, column| (row * column) as f64);
+        assert_eq!(singular.determinant(), 0.);
+        assert_eq!(singular.try_inverse(EPSILON), None);
+
+        assert_eq!(
+            signed_integer_power(matrix.clone(), 0, EPSILON),
+            Ok(DMatN::identity(4))
+        );
+        assert_eq!(signed_integer_power(matrix, -1, EPSILON), Ok(inverse));
+        assert_eq!(
+            signed_integer_power(singular.clone(), -1, EPSILON),
+            Err(PowerError::NotInvertible)
+        );
+        assert_eq!(
+            signed_integer_power(singular * 2., 100_000, EPSILON),
+            Err(PowerError::Overflow)
+        );
+
+        assert_eq!(
+            DMatN::from_fn(3, |row, column| DVec3::new(1., 2., 3.)[row] * column as f64).to_rows(),
+            vec![vec![0., 1., 2.], vec![0., 2., 4.], vec![0., 3., 6.]]
+        );
+    }
+}
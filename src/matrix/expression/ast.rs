@@ -1,18 +1,35 @@
 //! This module handles abstract syntax trees for parsed matrix expressions.
 
+use super::format::FormatOptions;
 use crate::{
-    math::integer_power,
-    matrix::{map::prelude::*, Matrix2dOr3d, MatrixName},
+    math::{
+        adjugate_2d, adjugate_3d, cofactor_2d, cofactor_3d, expm_2d, expm_3d, line_projection_2d,
+        logm_2d, logm_3d, lstsq_2d, lstsq_3d, pinv_2d, pinv_3d, plane_projection_3d,
+        rank_2d_within, rank_3d_within, signed_integer_power, snap_affine_2d, solve_2d, solve_3d,
+        translation_2d, Complex, Decomposition2d, MatrixNorm, MatrixNorms, MatrixPredicates,
+        MatrixProperty, Norm, PowerError, RandomDistribution, RandomMatrix, EPSILON,
+    },
+    matrix::{
+        map::{prelude::*, ScalarMapError},
+        CMatN, DMatN, MatrixName, MatrixValue, ScalarName, Vector2dOr3d,
+    },
 };
 use approx::RelativeEq;
-use glam::f64::{DMat2, DMat3};
+use glam::f64::{DMat2, DMat3, DVec2, DVec3};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{convert::Infallible, mem};
 use thiserror::Error;
 
-/// The epsilon value to use for relative comparisons.
-const EPSILON: f64 = 0.000000001;
-
 /// A node in the tree. Also represents the tree itself, since the root is just a node.
-#[derive(Clone, Debug, PartialEq)]
+///
+/// The tree owns all of its data (including the names of matrices, see [`MatrixName`]), so it
+/// doesn't borrow from the expression string or the tokens that it was parsed from, and can be
+/// stored for as long as needed.
+///
+/// Cloning, comparing, and dropping a tree all walk it without recursing, so arbitrarily deep
+/// trees can't overflow the stack.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AstNode {
     /// Multiply two things together.
     Multiply {
@@ -60,9 +77,19 @@ pub enum AstNode {
     /// A real number.
     Number(f64),
 
+    /// An imaginary number, written in the expression like `2i`, or just `i` for `1i`.
+    ///
+    /// Complex numbers like `3 + 2i` are written as the sum of a real number and an imaginary
+    /// one.
+    Imaginary(f64),
+
     /// A named matrix. See [`MatrixName`].
     NamedMatrix(MatrixName),
 
+    /// A scalar variable, like `x` or `angle`, which evaluates to a number. See [`ScalarName`]
+    /// and [`ScalarMap`].
+    Variable(ScalarName),
+
     /// A rotation matrix, written in the expression like `rot(45)` or `rot(90)`.
     RotationMatrix {
         /// The number of degrees of rotation.
@@ -74,49 +101,611 @@ pub enum AstNode {
 
     /// An unnamed 3D matrix, written inline in the expression like `[1 2 3; 4 5 6; 7 8 9]`.
     Anonymous3dMatrix(DMat3),
+
+    /// An unnamed matrix of any other dimension, written inline in the expression like
+    /// `[1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1]`.
+    ///
+    /// The parser only produces this for matrices which are 4×4 or larger.
+    AnonymousDynamicMatrix(DMatN),
+
+    /// An unnamed 2D column vector, written inline in the expression like `[1; 2]`.
+    Anonymous2dVector(DVec2),
+
+    /// An unnamed 3D column vector, written inline in the expression like `[1; 2; 3]`.
+    Anonymous3dVector(DVec3),
+
+    /// The dot product of two vectors, written in the expression like `dot(u, v)`.
+    DotProduct {
+        /// The vector on the left of the dot product.
+        left: Box<Self>,
+        /// The vector on the right of the dot product.
+        right: Box<Self>,
+    },
+
+    /// The cross product of two vectors, written in the expression like `cross(u, v)`.
+    ///
+    /// The cross product of two 3D vectors is another 3D vector. The cross product of two 2D
+    /// vectors is the z component of the cross product of those vectors embedded in 3D, which is
+    /// a number.
+    CrossProduct {
+        /// The vector on the left of the cross product.
+        left: Box<Self>,
+        /// The vector on the right of the cross product.
+        right: Box<Self>,
+    },
+
+    /// A single entry of a matrix, written in the expression like `A[1, 2]`. The indices are
+    /// 1-based, so `A[1, 1]` is the top left entry.
+    Index {
+        /// The matrix being indexed into.
+        matrix: Box<Self>,
+        /// The row of the entry.
+        row: usize,
+        /// The column of the entry.
+        column: usize,
+    },
+
+    /// A single row of a matrix as a vector, written in the expression like `row(A, 1)`. The index
+    /// is 1-based.
+    Row {
+        /// The matrix to take the row from.
+        matrix: Box<Self>,
+        /// The index of the row. Must evaluate to a positive integer.
+        index: Box<Self>,
+    },
+
+    /// A single column of a matrix as a vector, written in the expression like `col(A, 1)`. The
+    /// index is 1-based.
+    Column {
+        /// The matrix to take the column from.
+        matrix: Box<Self>,
+        /// The index of the column. Must evaluate to a positive integer.
+        index: Box<Self>,
+    },
+
+    /// A matrix built out of column vectors, written in the expression like `aug(u, v)` or
+    /// `aug(u, v, w)`.
+    ///
+    /// Since we only support square matrices, there must be as many columns as each column has
+    /// entries.
+    Augment {
+        /// The columns of the matrix, from left to right.
+        columns: Vec<Self>,
+    },
+
+    /// A 3D matrix built out of blocks, written in the expression like `block(A, u; v, s)`.
+    ///
+    /// The top left block must be a 2D matrix, the top right block must be a 2D (column) vector,
+    /// the bottom left block must be a 2D vector which gets used as a row, and the bottom right
+    /// block must be a number.
+    Block {
+        /// The 2D matrix in the top left.
+        top_left: Box<Self>,
+        /// The column vector in the top right.
+        top_right: Box<Self>,
+        /// The row vector in the bottom left.
+        bottom_left: Box<Self>,
+        /// The number in the bottom right.
+        bottom_right: Box<Self>,
+    },
+
+    /// The solution `x` to the linear system `Ax = b`, written in the expression like
+    /// `solve(A, b)`.
+    Solve {
+        /// The matrix `A`.
+        matrix: Box<Self>,
+        /// The vector `b`.
+        vector: Box<Self>,
+    },
+
+    /// The least-squares solution `x` to the linear system `Ax = b`, written in the expression
+    /// like `lstsq(A, b)`. This exists even if `A` is singular. See [`lstsq_2d`].
+    ///
+    /// The residual isn't part of the value, but it can be found with `norm(A * lstsq(A, b) - b)`.
+    LeastSquares {
+        /// The matrix `A`.
+        matrix: Box<Self>,
+        /// The vector `b`.
+        vector: Box<Self>,
+    },
+
+    /// The 2D affine transform which translates by `(x, y)`, written in the expression like
+    /// `translate(x, y)`. This is a 3D matrix in homogeneous coordinates. See [`translation_2d`].
+    Translation {
+        /// The offset along the x axis.
+        x: Box<Self>,
+        /// The offset along the y axis.
+        y: Box<Self>,
+    },
+
+    /// The norm of a vector or matrix, written in the expression like `norm(v)`.
+    ///
+    /// For vectors, this is the length. For matrices, this is the Frobenius norm.
+    Norm(Box<Self>),
+
+    /// The adjugate of a matrix, written in the expression like `adj(M)`.
+    Adjugate(Box<Self>),
+
+    /// The cofactor of a single entry of a matrix, written in the expression like
+    /// `cofactor(M, 1, 2)`. The indices are 1-based.
+    Cofactor {
+        /// The matrix to take the cofactor of.
+        matrix: Box<Self>,
+        /// The row of the entry. Must evaluate to a positive integer.
+        row: Box<Self>,
+        /// The column of the entry. Must evaluate to a positive integer.
+        column: Box<Self>,
+    },
+
+    /// The rank of a matrix, written in the expression like `rank(M)`.
+    Rank(Box<Self>),
+
+    /// The exponential of a number or matrix, written in the expression like `exp(M)`.
+    ///
+    /// For matrices, this is the matrix exponential, so `exp(t * [0 -1; 1 0])` is a rotation by
+    /// `t` radians.
+    Exponential(Box<Self>),
+
+    /// The natural logarithm of a number or matrix, written in the expression like `logm(M)`, or
+    /// `log(M)` for short.
+    ///
+    /// For matrices, this is the principal matrix logarithm, which only exists if the matrix
+    /// has no real eigenvalues which are zero or negative.
+    Logarithm(Box<Self>),
+
+    /// Whether a matrix has a property, written in the expression like `is_orthogonal(M)`. This
+    /// is 1 if it does and 0 if it doesn't. See [`MatrixPredicates`].
+    HasProperty {
+        /// The property to check for.
+        property: MatrixProperty,
+        /// The matrix to check.
+        matrix: Box<Self>,
+    },
+
+    /// A particular norm of a matrix, written in the expression like `norm(M, "fro")`. See
+    /// [`MatrixNorms`].
+    MatrixNorm {
+        /// The norm to take.
+        norm: MatrixNorm,
+        /// The matrix to take the norm of.
+        matrix: Box<Self>,
+    },
+
+    /// The Moore-Penrose pseudoinverse of a matrix, written in the expression like `pinv(M)`.
+    /// This is the inverse if there is one, and otherwise gives least-squares solutions. See
+    /// [`pinv_2d`].
+    PseudoInverse(Box<Self>),
+
+    /// The 2D matrix which projects onto the line through the origin at an anticlockwise angle in
+    /// degrees from the x axis, written in the expression like `proj_line(30)`. See
+    /// [`line_projection_2d`].
+    LineProjection(Box<Self>),
+
+    /// The 3D matrix which projects onto the plane through the origin with a normal vector,
+    /// written in the expression like `proj_plane([0; 0; 1])`. See [`plane_projection_3d`].
+    PlaneProjection(Box<Self>),
+
+    /// A random matrix with a dimension and a seed, written in the expression like `rand(2, 42)`
+    /// or `rand(3, 7, "orthogonal")`. The same seed always gives the same matrix. See
+    /// [`RandomMatrix`].
+    Random {
+        /// The distribution to draw the matrix from, which is uniform unless given.
+        distribution: RandomDistribution,
+        /// The dimension of the matrix, which must be 2 or 3.
+        dimension: Box<Self>,
+        /// The seed for the random number generator.
+        seed: Box<Self>,
+    },
+}
+
+impl Clone for AstNode {
+    fn clone(&self) -> Self {
+        self.fold_iteratively(|node, children| node.shallow_clone().with_children(children))
+    }
+}
+
+impl PartialEq for AstNode {
+    fn eq(&self, other: &Self) -> bool {
+        let mut pending = vec![(self, other)];
+
+        while let Some((left, right)) = pending.pop() {
+            if !left.shallow_eq(right) {
+                return false;
+            }
+            pending.extend(left.children().into_iter().zip(right.children()));
+        }
+
+        true
+    }
+}
+
+impl Drop for AstNode {
+    fn drop(&mut self) {
+        // Move the descendants onto the heap and drop them one at a time, so that each one has
+        // no children left by the time it gets dropped
+        let mut pending: Vec<Self> = self.children_mut().into_iter().map(Self::take).collect();
+
+        while let Some(mut node) = pending.pop() {
+            pending.extend(node.children_mut().into_iter().map(Self::take));
+        }
+    }
+}
+
+impl From<f64> for AstNode {
+    fn from(number: f64) -> Self {
+        Self::Number(number)
+    }
+}
+
+impl From<Complex> for AstNode {
+    fn from(number: Complex) -> Self {
+        if number.re == 0. {
+            Self::Imaginary(number.im)
+        } else {
+            Self::Add {
+                left: Box::new(Self::Number(number.re)),
+                right: Box::new(Self::Imaginary(number.im)),
+            }
+        }
+    }
+}
+
+impl From<MatrixName> for AstNode {
+    fn from(name: MatrixName) -> Self {
+        Self::NamedMatrix(name)
+    }
+}
+
+impl From<DMat2> for AstNode {
+    fn from(matrix: DMat2) -> Self {
+        Self::Anonymous2dMatrix(matrix)
+    }
+}
+
+impl From<DMat3> for AstNode {
+    fn from(matrix: DMat3) -> Self {
+        Self::Anonymous3dMatrix(matrix)
+    }
+}
+
+impl From<DMatN> for AstNode {
+    fn from(matrix: DMatN) -> Self {
+        MatrixValue::from(matrix).into()
+    }
+}
+
+impl From<MatrixValue> for AstNode {
+    fn from(matrix: MatrixValue) -> Self {
+        match matrix {
+            MatrixValue::TwoD(matrix) => matrix.into(),
+            MatrixValue::ThreeD(matrix) => matrix.into(),
+            MatrixValue::Dynamic(matrix) => Self::AnonymousDynamicMatrix(matrix),
+        }
+    }
+}
+
+/// Complex matrices don't have a literal syntax, so they're written as `A + i * B`, where `A` and
+/// `B` are the real and imaginary parts.
+impl From<CMatN> for AstNode {
+    fn from(matrix: CMatN) -> Self {
+        let part = |part: fn(Complex) -> f64| {
+            Box::new(Self::from(DMatN::from_fn(
+                matrix.dimension(),
+                |row, column| {
+                    part(
+                        matrix
+                            .get(row, column)
+                            .expect("Indices should be in bounds"),
+                    )
+                },
+            )))
+        };
+
+        Self::Add {
+            left: part(|z| z.re),
+            right: Box::new(Self::Multiply {
+                left: Box::new(Self::Imaginary(1.)),
+                right: part(|z| z.im),
+            }),
+        }
+    }
+}
+
+impl From<DVec2> for AstNode {
+    fn from(vector: DVec2) -> Self {
+        Self::Anonymous2dVector(vector)
+    }
+}
+
+impl From<DVec3> for AstNode {
+    fn from(vector: DVec3) -> Self {
+        Self::Anonymous3dVector(vector)
+    }
+}
+
+impl From<Vector2dOr3d> for AstNode {
+    fn from(vector: Vector2dOr3d) -> Self {
+        match vector {
+            Vector2dOr3d::TwoD(vector) => vector.into(),
+            Vector2dOr3d::ThreeD(vector) => vector.into(),
+        }
+    }
+}
+
+/// A decomposition is written as `rot(θ) * shear * scale`, leaving out any factors which do
+/// nothing, so `I` if they all do nothing. This is always a valid expression, so it can be shown
+/// to the user and copied, like when building a matrix from sliders.
+///
+/// ```
+/// # use trinity::{math::Decomposition2d, matrix::expression::{ast::AstNode, format::FormatOptions}};
+/// # use glam::DVec2;
+/// let decomposition = Decomposition2d {
+///     rotation_degrees: -90.,
+///     scale: DVec2::new(2., 1.),
+///     shear: -0.5,
+/// };
+/// assert_eq!(
+///     AstNode::from(decomposition).to_formatted_string(&FormatOptions::default()),
+///     "rot(270) * ([1 0; 0 1] - [0 0.5; 0 0]) * [2 0; 0 1]"
+/// );
+/// ```
+impl From<Decomposition2d> for AstNode {
+    fn from(decomposition: Decomposition2d) -> Self {
+        let degrees = decomposition.rotation_degrees.rem_euclid(360.);
+        let shear = DMat2::from_cols(DVec2::X, DVec2::new(decomposition.shear, 1.));
+        let scale = DMat2::from_diagonal(decomposition.scale);
+
+        [
+            (degrees != 0.).then_some(Self::RotationMatrix { degrees }),
+            (shear != DMat2::IDENTITY).then(|| signed_2d_literal(shear)),
+            (scale != DMat2::IDENTITY).then(|| signed_2d_literal(scale)),
+        ]
+        .into_iter()
+        .flatten()
+        .rev()
+        .reduce(|right, left| Self::Multiply {
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+        .unwrap_or_else(|| Self::NamedMatrix(MatrixName::new("I")))
+    }
+}
+
+/// Write a 2D matrix as a literal which can be parsed again, even though literals can't contain
+/// negative numbers, by subtracting a literal of the negative entries, like `[1 0; 0 0] - [0 0; 0
+/// 2]`.
+fn signed_2d_literal(matrix: DMat2) -> AstNode {
+    // Comparing rather than using f64::max avoids negative zeros, which would format as -0
+    let part = |sign: f64| {
+        DMat2::from_cols_array(&matrix.to_cols_array().map(|entry| {
+            if sign * entry > 0. {
+                sign * entry
+            } else {
+                0.
+            }
+        }))
+    };
+    let positive = part(1.);
+    let negative = part(-1.);
+
+    match (positive == DMat2::ZERO, negative == DMat2::ZERO) {
+        (_, true) => positive.into(),
+        (true, false) => AstNode::Negate(Box::new(negative.into())),
+        (false, false) => AstNode::Add {
+            left: Box::new(positive.into()),
+            right: Box::new(AstNode::Negate(Box::new(negative.into()))),
+        },
+    }
+}
+
+impl From<NumberOrMatrix> for AstNode {
+    fn from(value: NumberOrMatrix) -> Self {
+        match value {
+            NumberOrMatrix::Number(number) => number.into(),
+            NumberOrMatrix::Matrix(matrix) => matrix.into(),
+            NumberOrMatrix::Vector(vector) => vector.into(),
+            NumberOrMatrix::Complex(number) => number.into(),
+            NumberOrMatrix::ComplexMatrix(matrix) => matrix.into(),
+        }
+    }
+}
+
+/// Make a callback for [`AstNode::try_fold_iteratively`] which fails once the tree goes past these
+/// limits.
+pub(super) fn check_limits(
+    limits: EvaluationLimits,
+) -> impl FnMut(usize) -> Result<(), EvaluationError> {
+    let mut nodes = 0usize;
+    move |depth| {
+        nodes += 1;
+        if depth > limits.max_depth {
+            Err(EvaluationError::ExpressionTooDeep {
+                max_depth: limits.max_depth,
+            })
+        } else if nodes > limits.max_nodes {
+            Err(EvaluationError::ExpressionTooLarge {
+                max_nodes: limits.max_nodes,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Snap the final value of an evaluation to a 2D affine transform if the options ask for it. See
+/// [`EvalOptions::affine_2d`].
+pub(super) fn snap_affine(
+    value: NumberOrMatrix,
+    options: EvalOptions,
+) -> Result<NumberOrMatrix, EvaluationError> {
+    match value {
+        NumberOrMatrix::Matrix(MatrixValue::ThreeD(matrix)) if options.affine_2d => {
+            snap_affine_2d(matrix, |x| options.is_zero(x))
+                .map(|matrix| NumberOrMatrix::Matrix(MatrixValue::ThreeD(matrix)))
+                .ok_or(EvaluationError::NotAffine2d)
+        }
+        value => Ok(value),
+    }
+}
+
+/// Round an integer power for a matrix, failing if it's out of the range of an [`i32`].
+fn power_to_i32(power: f64) -> Result<i32, EvaluationError> {
+    let power = power.round();
+    if power.abs() <= i32::MAX as f64 {
+        Ok(power as i32)
+    } else {
+        Err(EvaluationError::PowerOverflow)
+    }
 }
 
-/// Either a number or a [`Matrix2dOr3d`].
+/// Either a number, a [`MatrixValue`], or a [`Vector2dOr3d`], or a complex number or matrix.
+///
+/// Once a value is complex, it stays complex, even if its imaginary part is zero, so that the
+/// type of a value only depends on the structure of the expression.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NumberOrMatrix {
     /// A number.
     Number(f64),
 
     /// Either a [`DMat2`] or [`DMat3`].
-    Matrix(Matrix2dOr3d),
+    Matrix(MatrixValue),
+
+    /// Either a [`DVec2`] or [`DVec3`].
+    Vector(Vector2dOr3d),
+
+    /// A complex number.
+    Complex(Complex),
+
+    /// A square matrix with complex entries. Vectors are always real.
+    ComplexMatrix(CMatN),
+}
+
+/// A complex number or matrix, used to implement arithmetic on complex values. See
+/// [`NumberOrMatrix::try_into_complex`].
+enum ComplexOperand {
+    /// A complex number, or a real number treated as one.
+    Scalar(Complex),
+
+    /// A complex matrix, or a real matrix treated as one.
+    Matrix(CMatN),
+}
+
+impl From<ComplexOperand> for NumberOrMatrix {
+    fn from(operand: ComplexOperand) -> Self {
+        match operand {
+            ComplexOperand::Scalar(number) => Self::Complex(number),
+            ComplexOperand::Matrix(matrix) => Self::ComplexMatrix(matrix),
+        }
+    }
 }
 
 impl NumberOrMatrix {
+    /// Is this a complex number or a complex matrix?
+    pub fn is_complex(&self) -> bool {
+        matches!(self, Self::Complex(_) | Self::ComplexMatrix(_))
+    }
+
+    /// Treat this value as a complex number or matrix, so that it can be combined with one.
+    ///
+    /// Vectors are always real, so they can't be combined with complex values.
+    fn try_into_complex(self) -> Result<ComplexOperand, EvaluationError> {
+        match self {
+            Self::Number(number) => Ok(ComplexOperand::Scalar(number.into())),
+            Self::Complex(number) => Ok(ComplexOperand::Scalar(number)),
+            Self::Matrix(matrix) => Ok(ComplexOperand::Matrix(matrix.into())),
+            Self::ComplexMatrix(matrix) => Ok(ComplexOperand::Matrix(matrix)),
+            Self::Vector(_) => Err(EvaluationError::CannotCombineVectorAndComplex),
+        }
+    }
+
+    /// Multiply two values, at least one of which is complex.
+    fn try_mul_complex(self, rhs: Self) -> Result<Self, EvaluationError> {
+        Ok(match (self.try_into_complex()?, rhs.try_into_complex()?) {
+            (ComplexOperand::Scalar(a), ComplexOperand::Scalar(b)) => Self::Complex(a * b),
+            (ComplexOperand::Scalar(a), ComplexOperand::Matrix(b))
+            | (ComplexOperand::Matrix(b), ComplexOperand::Scalar(a)) => Self::ComplexMatrix(a * b),
+            (ComplexOperand::Matrix(a), ComplexOperand::Matrix(b)) => Self::ComplexMatrix(
+                CMatN::try_mul(&a, &b).ok_or(EvaluationError::CannotMultiplyDifferentDimensions)?,
+            ),
+        })
+    }
+
     /// Try to multiply.
     pub fn try_mul(self, rhs: Self) -> Result<Self, EvaluationError> {
         Ok(match (self, rhs) {
+            (a @ (Self::Complex(_) | Self::ComplexMatrix(_)), b)
+            | (a, b @ (Self::Complex(_) | Self::ComplexMatrix(_))) => a.try_mul_complex(b)?,
             (Self::Number(a), Self::Number(b)) => Self::Number(a * b),
             (Self::Number(a), Self::Matrix(b)) => Self::Matrix(a * b),
             (Self::Matrix(a), Self::Number(b)) => Self::Matrix(a * b),
             (Self::Matrix(a), Self::Matrix(b)) => Self::Matrix(
-                Matrix2dOr3d::try_mul(a, b)
+                MatrixValue::try_mul(a, b)
+                    .ok_or(EvaluationError::CannotMultiplyDifferentDimensions)?,
+            ),
+            (Self::Number(a), Self::Vector(b)) => Self::Vector(a * b),
+            (Self::Vector(a), Self::Number(b)) => Self::Vector(a * b),
+            (Self::Matrix(a), Self::Vector(b)) => Self::Vector(
+                MatrixValue::try_mul_vector(a, b)
                     .ok_or(EvaluationError::CannotMultiplyDifferentDimensions)?,
             ),
+            (Self::Vector(_), Self::Matrix(_)) => {
+                Err(EvaluationError::CannotMultiplyVectorByMatrix)?
+            }
+            (Self::Vector(_), Self::Vector(_)) => Err(EvaluationError::CannotMultiplyTwoVectors)?,
         })
     }
 
     /// Try to divide.
     pub fn try_div(self, rhs: Self) -> Result<Self, EvaluationError> {
         match (self, rhs) {
+            (a @ (Self::Complex(_) | Self::ComplexMatrix(_)), b)
+            | (a, b @ (Self::Complex(_) | Self::ComplexMatrix(_))) => {
+                match (a.try_into_complex()?, b.try_into_complex()?) {
+                    (a, ComplexOperand::Scalar(b)) => {
+                        Self::from(a).try_mul_complex(Self::Complex(b.recip()))
+                    }
+                    (_, ComplexOperand::Matrix(_)) => Err(EvaluationError::CannotDivideByMatrix),
+                }
+            }
             (Self::Number(a), Self::Number(b)) => Ok(Self::Number(a / b)),
             (Self::Matrix(a), Self::Number(b)) => Ok(Self::Matrix(a * b.recip())),
+            (Self::Vector(a), Self::Number(b)) => Ok(Self::Vector(a * b.recip())),
             (_, Self::Matrix(_)) => Err(EvaluationError::CannotDivideByMatrix),
+            (_, Self::Vector(_)) => Err(EvaluationError::CannotDivideByVector),
         }
     }
 
     /// Try to add.
     pub fn try_add(self, rhs: Self) -> Result<Self, EvaluationError> {
         Ok(match (self, rhs) {
+            (a @ (Self::Complex(_) | Self::ComplexMatrix(_)), b)
+            | (a, b @ (Self::Complex(_) | Self::ComplexMatrix(_))) => {
+                match (a.try_into_complex()?, b.try_into_complex()?) {
+                    (ComplexOperand::Scalar(a), ComplexOperand::Scalar(b)) => Self::Complex(a + b),
+                    (ComplexOperand::Matrix(a), ComplexOperand::Matrix(b)) => Self::ComplexMatrix(
+                        CMatN::try_add(&a, &b)
+                            .ok_or(EvaluationError::CannotAddDifferentDimensions)?,
+                    ),
+                    _ => Err(EvaluationError::CannotAddNumberAndMatrix)?,
+                }
+            }
             (Self::Number(a), Self::Number(b)) => Self::Number(a + b),
             (Self::Matrix(a), Self::Matrix(b)) => Self::Matrix(
-                Matrix2dOr3d::try_add(a, b).ok_or(EvaluationError::CannotAddDifferentDimensions)?,
+                MatrixValue::try_add(a, b).ok_or(EvaluationError::CannotAddDifferentDimensions)?,
             ),
-            _ => Err(EvaluationError::CannotAddNumberAndMatrix)?,
+            (Self::Vector(a), Self::Vector(b)) => Self::Vector(
+                Vector2dOr3d::try_add(a, b).ok_or(EvaluationError::CannotAddDifferentDimensions)?,
+            ),
+            (Self::Number(_), Self::Matrix(_)) | (Self::Matrix(_), Self::Number(_)) => {
+                Err(EvaluationError::CannotAddNumberAndMatrix)?
+            }
+            (Self::Number(_), Self::Vector(_)) | (Self::Vector(_), Self::Number(_)) => {
+                Err(EvaluationError::CannotAddNumberAndVector)?
+            }
+            (Self::Matrix(_), Self::Vector(_)) | (Self::Vector(_), Self::Matrix(_)) => {
+                Err(EvaluationError::CannotAddMatrixAndVector)?
+            }
         })
     }
 
@@ -124,890 +713,4004 @@ impl NumberOrMatrix {
     pub fn negate(self) -> Self {
         match self {
             Self::Number(number) => Self::Number(-number),
-            Self::Matrix(Matrix2dOr3d::TwoD(matrix)) => Self::Matrix(Matrix2dOr3d::TwoD(-matrix)),
-            Self::Matrix(Matrix2dOr3d::ThreeD(matrix)) => {
-                Self::Matrix(Matrix2dOr3d::ThreeD(-matrix))
+            Self::Matrix(MatrixValue::TwoD(matrix)) => Self::Matrix(MatrixValue::TwoD(-matrix)),
+            Self::Matrix(MatrixValue::ThreeD(matrix)) => Self::Matrix(MatrixValue::ThreeD(-matrix)),
+            Self::Matrix(MatrixValue::Dynamic(matrix)) => {
+                Self::Matrix(MatrixValue::Dynamic(-matrix))
             }
+            Self::Vector(Vector2dOr3d::TwoD(vector)) => Self::Vector(Vector2dOr3d::TwoD(-vector)),
+            Self::Vector(Vector2dOr3d::ThreeD(vector)) => {
+                Self::Vector(Vector2dOr3d::ThreeD(-vector))
+            }
+            Self::Complex(number) => Self::Complex(-number),
+            Self::ComplexMatrix(matrix) => Self::ComplexMatrix(-matrix),
         }
     }
 
     /// Try to raise one thing to the power of another.
-    pub fn try_power(base: Self, power: Self) -> Result<Self, EvaluationError> {
+    ///
+    /// A matrix can only be raised to a power that counts as an integer under the given options,
+    /// and only to a negative power if it isn't singular under the relative tolerance
+    /// `options.epsilon`. See [`Invertible`](crate::math::Invertible).
+    pub fn try_power(
+        base: Self,
+        power: Self,
+        options: EvalOptions,
+    ) -> Result<Self, EvaluationError> {
         match (base, power) {
             (Self::Number(base), Self::Number(power)) => Ok(Self::Number(base.powf(power))),
-            (Self::Matrix(Matrix2dOr3d::TwoD(base)), Self::Number(power)) => {
-                if power.round().relative_eq(
-                    &power,
-                    EPSILON,
-                    <f64 as RelativeEq>::default_max_relative(),
-                ) {
-                    let needs_invert = power.round() < 0.;
-                    let power = power.round().abs() as u16;
-
-                    let result = integer_power(base, power);
-
-                    Ok(Self::Matrix(Matrix2dOr3d::TwoD(if needs_invert {
-                        if !result.determinant().relative_eq(
-                            &0.,
-                            EPSILON,
-                            <f64 as RelativeEq>::default_max_relative(),
-                        ) {
-                            result.inverse()
-                        } else {
-                            Err(EvaluationError::CannotInvertSingularMatrix)?
-                        }
-                    } else {
-                        result
-                    })))
-                } else {
-                    Err(EvaluationError::CannotRaiseMatrixToNonInteger)
+            (Self::Matrix(matrix), Self::Number(power)) => {
+                if !options.is_integer(power) {
+                    return Err(EvaluationError::CannotRaiseMatrixToNonInteger);
                 }
+
+                let power = power_to_i32(power)?;
+                match matrix {
+                    MatrixValue::TwoD(base) => {
+                        signed_integer_power(base, power, options.epsilon).map(MatrixValue::TwoD)
+                    }
+                    MatrixValue::ThreeD(base) => {
+                        signed_integer_power(base, power, options.epsilon).map(MatrixValue::ThreeD)
+                    }
+                    MatrixValue::Dynamic(base) => {
+                        signed_integer_power(base, power, options.epsilon).map(MatrixValue::Dynamic)
+                    }
+                }
+                .map(Self::Matrix)
+                .map_err(EvaluationError::from)
+            }
+            (Self::Vector(_), Self::Complex(_) | Self::ComplexMatrix(_))
+            | (Self::Complex(_) | Self::ComplexMatrix(_), Self::Vector(_)) => {
+                Err(EvaluationError::CannotCombineVectorAndComplex)
+            }
+            (_, Self::Matrix(_) | Self::ComplexMatrix(_)) => {
+                Err(EvaluationError::CannotRaiseToMatrix)
             }
-            (Self::Matrix(Matrix2dOr3d::ThreeD(base)), Self::Number(power)) => {
-                if power.round().relative_eq(
-                    &power,
-                    0.000000001,
-                    <f64 as RelativeEq>::default_max_relative(),
-                ) {
-                    let needs_invert = power.round() < 0.;
-                    let power = power.round().abs() as u16;
-
-                    let result = integer_power(base, power);
-
-                    Ok(Self::Matrix(Matrix2dOr3d::ThreeD(if needs_invert {
-                        if !result.determinant().relative_eq(
-                            &0.,
-                            EPSILON,
-                            <f64 as RelativeEq>::default_max_relative(),
-                        ) {
-                            result.inverse()
-                        } else {
-                            Err(EvaluationError::CannotInvertSingularMatrix)?
-                        }
-                    } else {
-                        result
-                    })))
+            (Self::Matrix(_) | Self::ComplexMatrix(_), Self::Complex(_)) => {
+                Err(EvaluationError::CannotRaiseMatrixToNonInteger)
+            }
+            (Self::ComplexMatrix(base), Self::Number(power)) => {
+                if options.is_integer(power) {
+                    signed_integer_power(base, power_to_i32(power)?, options.epsilon)
+                        .map(Self::ComplexMatrix)
+                        .map_err(EvaluationError::from)
                 } else {
                     Err(EvaluationError::CannotRaiseMatrixToNonInteger)
                 }
             }
-            (_, Self::Matrix(_)) => Err(EvaluationError::CannotRaiseToMatrix),
+            (Self::Number(base), Self::Complex(power)) => {
+                Ok(Self::Complex(Complex::from(base).powc(power)))
+            }
+            (Self::Complex(base), Self::Number(power)) => {
+                Ok(Self::Complex(base.powc(power.into())))
+            }
+            (Self::Complex(base), Self::Complex(power)) => Ok(Self::Complex(base.powc(power))),
+            (_, Self::Vector(_)) => Err(EvaluationError::CannotRaiseToVector),
+            (Self::Vector(_), Self::Number(_)) => Err(EvaluationError::CannotRaiseVector),
         }
     }
 
     /// Try to transpose this thing.
     pub fn try_transpose(self) -> Result<Self, EvaluationError> {
         match self {
-            Self::Number(_) => Err(EvaluationError::CannotTransposeNumber),
-            Self::Matrix(Matrix2dOr3d::TwoD(matrix)) => {
-                Ok(Self::Matrix(Matrix2dOr3d::TwoD(matrix.transpose())))
-            }
-            Self::Matrix(Matrix2dOr3d::ThreeD(matrix)) => {
-                Ok(Self::Matrix(Matrix2dOr3d::ThreeD(matrix.transpose())))
-            }
+            Self::Number(_) | Self::Complex(_) => Err(EvaluationError::CannotTransposeNumber),
+            Self::Matrix(matrix) => Ok(Self::Matrix(matrix.transpose())),
+            Self::ComplexMatrix(matrix) => Ok(Self::ComplexMatrix(matrix.transpose())),
+            Self::Vector(_) => Err(EvaluationError::CannotTransposeVector),
         }
     }
-}
-
-/// An error which can be returned by [`AstNode::evaluate`].
-#[allow(
-    missing_docs,
-    reason = "All variants impl Display and most are obvious from the name"
-)]
-#[derive(Clone, Debug, Error, PartialEq, Eq)]
-pub enum EvaluationError {
-    #[error("Cannot multiply two matrices of different dimensions")]
-    CannotMultiplyDifferentDimensions,
 
-    #[error("Cannot add two matrices of different dimensions")]
-    CannotAddDifferentDimensions,
-
-    #[error("Cannot add a number and a matrix")]
-    CannotAddNumberAndMatrix,
+    /// Try to get a single entry from a matrix. The indices are 1-based.
+    pub fn try_index(self, row: usize, column: usize) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Matrix(matrix) => row
+                .checked_sub(1)
+                .zip(column.checked_sub(1))
+                .and_then(|(r, c)| matrix.get(r, c))
+                .map(Self::Number)
+                .ok_or(EvaluationError::IndexOutOfBounds {
+                    row,
+                    column,
+                    dimension: matrix.dimension(),
+                }),
+            Self::ComplexMatrix(matrix) => row
+                .checked_sub(1)
+                .zip(column.checked_sub(1))
+                .and_then(|(r, c)| matrix.get(r, c))
+                .map(Self::Complex)
+                .ok_or(EvaluationError::IndexOutOfBounds {
+                    row,
+                    column,
+                    dimension: matrix.dimension(),
+                }),
+            _ => Err(EvaluationError::CannotIndexNonMatrix),
+        }
+    }
 
-    #[error("Cannot raise a matrix to a non-integer number")]
-    CannotRaiseMatrixToNonInteger,
+    /// Try to use this value as a 1-based index, returning it as a 0-based index.
+    fn try_into_index(self, options: EvalOptions) -> Result<usize, EvaluationError> {
+        match self {
+            Self::Number(number) if number >= 1. && options.is_integer(number) => {
+                Ok(number.round() as usize - 1)
+            }
+            _ => Err(EvaluationError::IndexMustBePositiveInteger),
+        }
+    }
 
-    #[error("Cannot raise anything to the power of a matrix")]
-    CannotRaiseToMatrix,
+    /// Try to get a single row from a matrix as a vector. The index is 1-based.
+    pub fn try_row(self, index: Self, options: EvalOptions) -> Result<Self, EvaluationError> {
+        if let Self::ComplexMatrix(_) = self {
+            return Err(EvaluationError::UnsupportedComplexMatrix);
+        }
+        let Self::Matrix(matrix) = self else {
+            return Err(EvaluationError::CannotExtractFromNonMatrix);
+        };
+        let index = index.try_into_index(options)?;
+        if let MatrixValue::Dynamic(matrix) = matrix {
+            return Err(EvaluationError::UnsupportedDimension {
+                dimension: matrix.dimension(),
+            });
+        }
 
-    #[error("Cannot divide by a matrix")]
-    CannotDivideByMatrix,
+        matrix
+            .row(index)
+            .map(Self::Vector)
+            .ok_or(EvaluationError::RowOutOfBounds {
+                row: index + 1,
+                dimension: matrix.dimension(),
+            })
+    }
 
-    #[error("Cannot invert a singular (determinant 0) matrix")]
-    CannotInvertSingularMatrix,
+    /// Try to get a single column from a matrix as a vector. The index is 1-based.
+    pub fn try_column(self, index: Self, options: EvalOptions) -> Result<Self, EvaluationError> {
+        if let Self::ComplexMatrix(_) = self {
+            return Err(EvaluationError::UnsupportedComplexMatrix);
+        }
+        let Self::Matrix(matrix) = self else {
+            return Err(EvaluationError::CannotExtractFromNonMatrix);
+        };
+        let index = index.try_into_index(options)?;
+        if let MatrixValue::Dynamic(matrix) = matrix {
+            return Err(EvaluationError::UnsupportedDimension {
+                dimension: matrix.dimension(),
+            });
+        }
 
-    #[error("Cannot transpose a scalar number")]
-    CannotTransposeNumber,
+        matrix
+            .column(index)
+            .map(Self::Vector)
+            .ok_or(EvaluationError::ColumnOutOfBounds {
+                column: index + 1,
+                dimension: matrix.dimension(),
+            })
+    }
 
-    /// An error occurred when getting a value from the matrix map.
-    #[error("{0}")]
-    MatrixMapError(#[from] MatrixMapError),
-}
+    /// Try to build a square matrix from a list of column vectors.
+    pub fn try_augment(columns: Vec<Self>) -> Result<Self, EvaluationError> {
+        let columns = columns
+            .into_iter()
+            .map(|column| match column {
+                Self::Vector(vector) => Ok(vector),
+                _ => Err(EvaluationError::AugmentRequiresVectors),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
-impl AstNode {
-    /// Evaluate this AST node by recursively evaulating whatever else needs to be evaluated.
-    pub fn evaluate(self, map: &impl MatrixMap) -> Result<NumberOrMatrix, EvaluationError> {
-        match self {
-            Self::Multiply { left, right } => {
-                NumberOrMatrix::try_mul(left.evaluate(map)?, right.evaluate(map)?)
-            }
-            Self::Divide { left, right } => {
-                NumberOrMatrix::try_div(left.evaluate(map)?, right.evaluate(map)?)
+        match columns.as_slice() {
+            [Vector2dOr3d::TwoD(a), Vector2dOr3d::TwoD(b)] => {
+                Ok(Self::Matrix(MatrixValue::TwoD(DMat2::from_cols(*a, *b))))
             }
-            Self::Add { left, right } => {
-                NumberOrMatrix::try_add(left.evaluate(map)?, right.evaluate(map)?)
-            }
-            Self::Negate(term) => Ok(NumberOrMatrix::negate(term.evaluate(map)?)),
-            Self::Exponent { base, power } => {
-                if *power == Self::NamedMatrix(MatrixName::new("T")) {
-                    NumberOrMatrix::try_transpose(base.evaluate(map)?)
+            [Vector2dOr3d::ThreeD(a), Vector2dOr3d::ThreeD(b), Vector2dOr3d::ThreeD(c)] => Ok(
+                Self::Matrix(MatrixValue::ThreeD(DMat3::from_cols(*a, *b, *c))),
+            ),
+            [first, rest @ ..] => {
+                let dimension = match first {
+                    Vector2dOr3d::TwoD(_) => 2,
+                    Vector2dOr3d::ThreeD(_) => 3,
+                };
+
+                if rest
+                    .iter()
+                    .any(|column| std::mem::discriminant(column) != std::mem::discriminant(first))
+                {
+                    Err(EvaluationError::CannotAugmentDifferentDimensions)
                 } else {
-                    NumberOrMatrix::try_power(base.evaluate(map)?, power.evaluate(map)?)
+                    Err(EvaluationError::AugmentWrongNumberOfColumns {
+                        columns: columns.len(),
+                        dimension,
+                    })
                 }
             }
-            Self::Number(number) => Ok(NumberOrMatrix::Number(number)),
-            Self::NamedMatrix(name) => Ok(NumberOrMatrix::Matrix(map.get(&name)?.into())),
-            Self::RotationMatrix { degrees } => Ok(NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(
-                DMat2::from_angle(degrees.to_radians()),
-            ))),
-            Self::Anonymous2dMatrix(matrix) => {
-                Ok(NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(matrix)))
-            }
-            Self::Anonymous3dMatrix(matrix) => {
-                Ok(NumberOrMatrix::Matrix(Matrix2dOr3d::ThreeD(matrix)))
-            }
+            [] => Err(EvaluationError::AugmentRequiresVectors),
         }
     }
 
-    /// Convert this AST node into an expression string.
-    pub fn to_expression_string(&self) -> String {
-        self.internal_to_expression_string(true)
+    /// Try to build a 3D matrix out of a 2D matrix, a column vector, a row vector, and a number.
+    ///
+    /// See [`AstNode::Block`].
+    pub fn try_block(
+        top_left: Self,
+        top_right: Self,
+        bottom_left: Self,
+        bottom_right: Self,
+    ) -> Result<Self, EvaluationError> {
+        match (top_left, top_right, bottom_left, bottom_right) {
+            (
+                Self::Matrix(MatrixValue::TwoD(matrix)),
+                Self::Vector(Vector2dOr3d::TwoD(column)),
+                Self::Vector(Vector2dOr3d::TwoD(row)),
+                Self::Number(number),
+            ) => Ok(Self::Matrix(MatrixValue::ThreeD(DMat3::from_cols(
+                matrix.x_axis.extend(row.x),
+                matrix.y_axis.extend(row.y),
+                column.extend(number),
+            )))),
+            (Self::Matrix(MatrixValue::Dynamic(matrix)), _, _, _) => {
+                Err(EvaluationError::UnsupportedDimension {
+                    dimension: matrix.dimension(),
+                })
+            }
+            _ => Err(EvaluationError::InvalidBlockMatrix),
+        }
     }
 
-    /// The internal implementation of [`AstNode::to_expression_string`]. The `top_level` parameter
-    /// is used to control parentheses.
-    fn internal_to_expression_string(&self, top_level: bool) -> String {
-        match self {
-            Self::Multiply { left, right } => {
-                let left = left.internal_to_expression_string(false);
-                let right = right.internal_to_expression_string(false);
-                let string = format!("{left} * {right}");
-                if !top_level {
-                    format!("({string})")
-                } else {
-                    string
-                }
+    /// Try to solve the linear system `Ax = b` for `x`, where `self` is `A` and `vector` is `b`.
+    ///
+    /// `A` counts as singular under the relative tolerance `options.epsilon`, just like when it's
+    /// raised to a negative power. See [`solve_2d`].
+    pub fn try_solve(self, vector: Self, options: EvalOptions) -> Result<Self, EvaluationError> {
+        match (self, vector) {
+            (Self::Matrix(MatrixValue::TwoD(a)), Self::Vector(Vector2dOr3d::TwoD(b))) => {
+                Ok(Self::Vector(Vector2dOr3d::TwoD(
+                    solve_2d(a, b, options.epsilon)
+                        .ok_or(EvaluationError::CannotSolveSingularSystem)?,
+                )))
             }
-            Self::Divide { left, right } => {
-                let left = left.internal_to_expression_string(false);
-                let right = right.internal_to_expression_string(false);
-                let string = format!("{left} / {right}");
-                if !top_level {
-                    format!("({string})")
-                } else {
-                    string
-                }
+            (Self::Matrix(MatrixValue::ThreeD(a)), Self::Vector(Vector2dOr3d::ThreeD(b))) => {
+                Ok(Self::Vector(Vector2dOr3d::ThreeD(
+                    solve_3d(a, b, options.epsilon)
+                        .ok_or(EvaluationError::CannotSolveSingularSystem)?,
+                )))
             }
-            Self::Add { left, right } => {
-                let left = left.internal_to_expression_string(false);
-                let right = right.internal_to_expression_string(false);
-                let string = format!("{left} + {right}");
-                if !top_level {
-                    format!("({string})")
-                } else {
-                    string
-                }
+            (Self::Matrix(MatrixValue::Dynamic(matrix)), _) => {
+                Err(EvaluationError::UnsupportedDimension {
+                    dimension: matrix.dimension(),
+                })
+            }
+            (Self::ComplexMatrix(_), _) => Err(EvaluationError::UnsupportedComplexMatrix),
+            (Self::Matrix(_), Self::Vector(_)) => {
+                Err(EvaluationError::CannotSolveDifferentDimensions)
+            }
+            _ => Err(EvaluationError::SolveRequiresMatrixAndVector),
+        }
+    }
+
+    /// Try to find the least-squares solution to the linear system `Ax = b`. See [`lstsq_2d`].
+    pub fn try_least_squares(self, vector: Self) -> Result<Self, EvaluationError> {
+        match (self, vector) {
+            (Self::Matrix(MatrixValue::TwoD(a)), Self::Vector(Vector2dOr3d::TwoD(b))) => {
+                Ok(Self::Vector(Vector2dOr3d::TwoD(
+                    lstsq_2d(a, b)
+                        .ok_or(EvaluationError::CannotSolveNonFiniteSystem)?
+                        .solution,
+                )))
+            }
+            (Self::Matrix(MatrixValue::ThreeD(a)), Self::Vector(Vector2dOr3d::ThreeD(b))) => {
+                Ok(Self::Vector(Vector2dOr3d::ThreeD(
+                    lstsq_3d(a, b)
+                        .ok_or(EvaluationError::CannotSolveNonFiniteSystem)?
+                        .solution,
+                )))
+            }
+            (Self::Matrix(MatrixValue::Dynamic(matrix)), _) => {
+                Err(EvaluationError::UnsupportedDimension {
+                    dimension: matrix.dimension(),
+                })
+            }
+            (Self::ComplexMatrix(_), _) => Err(EvaluationError::UnsupportedComplexMatrix),
+            (Self::Matrix(_), Self::Vector(_)) => {
+                Err(EvaluationError::CannotSolveDifferentDimensions)
+            }
+            _ => Err(EvaluationError::SolveRequiresMatrixAndVector),
+        }
+    }
+
+    /// Try to build the 2D affine transform which translates by `(self, y)`. See
+    /// [`translation_2d`].
+    pub fn try_translation(self, y: Self) -> Result<Self, EvaluationError> {
+        match (self, y) {
+            (Self::Number(x), Self::Number(y)) => Ok(Self::Matrix(MatrixValue::ThreeD(
+                translation_2d(DVec2::new(x, y)),
+            ))),
+            _ => Err(EvaluationError::TranslationRequiresNumbers),
+        }
+    }
+
+    /// Try to take the norm of a vector or matrix. See [`Norm`].
+    pub fn try_norm(self) -> Result<Self, EvaluationError> {
+        Ok(Self::Number(match self {
+            Self::Number(_) | Self::Complex(_) => Err(EvaluationError::NormRequiresVectorOrMatrix)?,
+            Self::Matrix(MatrixValue::TwoD(matrix)) => matrix.norm(),
+            Self::Matrix(MatrixValue::ThreeD(matrix)) => matrix.norm(),
+            Self::Matrix(MatrixValue::Dynamic(matrix)) => matrix.norm(),
+            Self::Vector(Vector2dOr3d::TwoD(vector)) => vector.norm(),
+            Self::Vector(Vector2dOr3d::ThreeD(vector)) => vector.norm(),
+            Self::ComplexMatrix(matrix) => matrix.norm(),
+        }))
+    }
+
+    /// Try to take the adjugate of a matrix.
+    pub fn try_adjugate(self) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Matrix(MatrixValue::TwoD(matrix)) => {
+                Ok(Self::Matrix(MatrixValue::TwoD(adjugate_2d(matrix))))
+            }
+            Self::Matrix(MatrixValue::ThreeD(matrix)) => {
+                Ok(Self::Matrix(MatrixValue::ThreeD(adjugate_3d(matrix))))
+            }
+            Self::Matrix(MatrixValue::Dynamic(matrix)) => {
+                Err(EvaluationError::UnsupportedDimension {
+                    dimension: matrix.dimension(),
+                })
+            }
+            Self::ComplexMatrix(_) => Err(EvaluationError::UnsupportedComplexMatrix),
+            _ => Err(EvaluationError::AdjugateRequiresMatrix),
+        }
+    }
+
+    /// Try to take the pseudoinverse of a matrix. See [`pinv_2d`].
+    pub fn try_pseudo_inverse(self) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Matrix(MatrixValue::TwoD(matrix)) => pinv_2d(matrix)
+                .map(|pinv| Self::Matrix(MatrixValue::TwoD(pinv)))
+                .ok_or(EvaluationError::PseudoInverseRequiresFiniteMatrix),
+            Self::Matrix(MatrixValue::ThreeD(matrix)) => pinv_3d(matrix)
+                .map(|pinv| Self::Matrix(MatrixValue::ThreeD(pinv)))
+                .ok_or(EvaluationError::PseudoInverseRequiresFiniteMatrix),
+            Self::Matrix(MatrixValue::Dynamic(matrix)) => {
+                Err(EvaluationError::UnsupportedDimension {
+                    dimension: matrix.dimension(),
+                })
+            }
+            Self::ComplexMatrix(_) => Err(EvaluationError::UnsupportedComplexMatrix),
+            _ => Err(EvaluationError::PseudoInverseRequiresMatrix),
+        }
+    }
+
+    /// Try to build the 2D projection onto the line at this angle in degrees. See
+    /// [`line_projection_2d`].
+    pub fn try_line_projection(self) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Number(degrees) => line_projection_2d(DVec2::from_angle(degrees.to_radians()))
+                .map(|projection| Self::Matrix(MatrixValue::TwoD(projection)))
+                .ok_or(EvaluationError::CannotProjectOntoNonFinite),
+            _ => Err(EvaluationError::LineProjectionRequiresNumber),
+        }
+    }
+
+    /// Try to build the 3D projection onto the plane with this normal vector. See
+    /// [`plane_projection_3d`].
+    pub fn try_plane_projection(self) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Vector(Vector2dOr3d::ThreeD(normal)) => plane_projection_3d(normal)
+                .map(|projection| Self::Matrix(MatrixValue::ThreeD(projection)))
+                .ok_or(EvaluationError::CannotProjectOntoNonFinite),
+            _ => Err(EvaluationError::PlaneProjectionRequiresVector),
+        }
+    }
+
+    /// Try to build a random matrix of dimension `self` from the distribution, using `seed` to seed
+    /// the random number generator. See [`RandomMatrix`].
+    pub fn try_random(
+        self,
+        seed: Self,
+        distribution: RandomDistribution,
+        options: EvalOptions,
+    ) -> Result<Self, EvaluationError> {
+        let (Self::Number(dimension), Self::Number(seed)) = (self, seed) else {
+            return Err(EvaluationError::RandomRequiresIntegers);
+        };
+        if dimension < 1.
+            || seed < 0.
+            || !options.is_integer(dimension)
+            || !options.is_integer(seed)
+        {
+            return Err(EvaluationError::RandomRequiresIntegers);
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed.round() as u64);
+        match dimension.round() as usize {
+            2 => Ok(Self::Matrix(MatrixValue::TwoD(DMat2::random(
+                distribution,
+                &mut rng,
+            )))),
+            3 => Ok(Self::Matrix(MatrixValue::ThreeD(DMat3::random(
+                distribution,
+                &mut rng,
+            )))),
+            dimension => Err(EvaluationError::UnsupportedDimension { dimension }),
+        }
+    }
+
+    /// Try to take the cofactor of a single entry of a matrix. The indices are 1-based.
+    pub fn try_cofactor(
+        self,
+        row: Self,
+        column: Self,
+        options: EvalOptions,
+    ) -> Result<Self, EvaluationError> {
+        if let Self::ComplexMatrix(_) = self {
+            return Err(EvaluationError::UnsupportedComplexMatrix);
+        }
+        let Self::Matrix(matrix) = self else {
+            return Err(EvaluationError::CofactorRequiresMatrix);
+        };
+        let row = row.try_into_index(options)?;
+        let column = column.try_into_index(options)?;
+
+        match matrix {
+            MatrixValue::TwoD(matrix) => cofactor_2d(matrix, row, column),
+            MatrixValue::ThreeD(matrix) => cofactor_3d(matrix, row, column),
+            MatrixValue::Dynamic(matrix) => {
+                return Err(EvaluationError::UnsupportedDimension {
+                    dimension: matrix.dimension(),
+                })
+            }
+        }
+        .map(Self::Number)
+        .ok_or(EvaluationError::IndexOutOfBounds {
+            row: row + 1,
+            column: column + 1,
+            dimension: matrix.dimension(),
+        })
+    }
+
+    /// Try to find the rank of a matrix, treating pivots within the `epsilon` of the options
+    /// times the largest entry as zero, like [`MatrixProperty::Singular`].
+    pub fn try_rank(self, options: EvalOptions) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Matrix(MatrixValue::TwoD(matrix)) => rank_2d_within(matrix, options.epsilon)
+                .map(|rank| Self::Number(rank as f64))
+                .ok_or(EvaluationError::RankRequiresFiniteMatrix),
+            Self::Matrix(MatrixValue::ThreeD(matrix)) => rank_3d_within(matrix, options.epsilon)
+                .map(|rank| Self::Number(rank as f64))
+                .ok_or(EvaluationError::RankRequiresFiniteMatrix),
+            Self::Matrix(MatrixValue::Dynamic(matrix)) => {
+                Err(EvaluationError::UnsupportedDimension {
+                    dimension: matrix.dimension(),
+                })
+            }
+            Self::ComplexMatrix(_) => Err(EvaluationError::UnsupportedComplexMatrix),
+            _ => Err(EvaluationError::RankRequiresMatrix),
+        }
+    }
+
+    /// Try to take the exponential of a number or matrix. See [`expm_2d`] and [`expm_3d`].
+    ///
+    /// The exponential of a matrix fails if any of its entries aren't finite, or if the result is
+    /// too big to represent.
+    pub fn try_exp(self) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Number(number) => Ok(Self::Number(number.exp())),
+            Self::Matrix(MatrixValue::TwoD(matrix)) => Some(expm_2d(matrix))
+                .filter(|result| result.is_finite())
+                .map(|result| Self::Matrix(MatrixValue::TwoD(result)))
+                .ok_or(EvaluationError::ExponentialNotFinite),
+            Self::Matrix(MatrixValue::ThreeD(matrix)) => Some(expm_3d(matrix))
+                .filter(|result| result.is_finite())
+                .map(|result| Self::Matrix(MatrixValue::ThreeD(result)))
+                .ok_or(EvaluationError::ExponentialNotFinite),
+            Self::Matrix(MatrixValue::Dynamic(matrix)) => {
+                Err(EvaluationError::UnsupportedDimension {
+                    dimension: matrix.dimension(),
+                })
+            }
+            Self::Complex(number) => Ok(Self::Complex(number.exp())),
+            Self::ComplexMatrix(_) => Err(EvaluationError::UnsupportedComplexMatrix),
+            Self::Vector(_) => Err(EvaluationError::ExponentialRequiresNumberOrMatrix),
+        }
+    }
+
+    /// Try to take the natural logarithm of a number or matrix. See [`logm_2d`] and
+    /// [`logm_3d`].
+    pub fn try_log(self) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Number(number) if number > 0. => Ok(Self::Number(number.ln())),
+            Self::Number(_) => Err(EvaluationError::LogarithmUndefined),
+            Self::Matrix(MatrixValue::TwoD(matrix)) => Ok(Self::Matrix(MatrixValue::TwoD(
+                logm_2d(matrix).ok_or(EvaluationError::LogarithmUndefined)?,
+            ))),
+            Self::Matrix(MatrixValue::ThreeD(matrix)) => Ok(Self::Matrix(MatrixValue::ThreeD(
+                logm_3d(matrix).ok_or(EvaluationError::LogarithmUndefined)?,
+            ))),
+            Self::Matrix(MatrixValue::Dynamic(matrix)) => {
+                Err(EvaluationError::UnsupportedDimension {
+                    dimension: matrix.dimension(),
+                })
             }
-            Self::Negate(term) => {
-                let term = term.internal_to_expression_string(false);
-                if !top_level {
-                    format!("(-{term})")
+            Self::Complex(number) if number != Complex::ZERO => Ok(Self::Complex(number.ln())),
+            Self::Complex(_) => Err(EvaluationError::LogarithmUndefined),
+            Self::ComplexMatrix(_) => Err(EvaluationError::UnsupportedComplexMatrix),
+            Self::Vector(_) => Err(EvaluationError::LogarithmRequiresNumberOrMatrix),
+        }
+    }
+
+    /// Try to check whether a matrix has a property, giving 1 if it does and 0 if it doesn't.
+    ///
+    /// Floating point error is ignored using the `epsilon` and `max_relative` of the options. See
+    /// [`MatrixPredicates::has_property_within`].
+    pub fn try_has_property(
+        self,
+        property: MatrixProperty,
+        options: EvalOptions,
+    ) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Matrix(matrix) => Ok(Self::Number(
+                if matrix.has_property_within(property, options.epsilon, options.max_relative) {
+                    1.
                 } else {
-                    format!("-{term}")
-                }
+                    0.
+                },
+            )),
+            Self::ComplexMatrix(_) => Err(EvaluationError::UnsupportedComplexMatrix),
+            _ => Err(EvaluationError::PropertyRequiresMatrix),
+        }
+    }
+
+    /// Try to take a particular norm of a matrix. See [`MatrixNorms`].
+    ///
+    /// Complex matrices only support the Frobenius norm.
+    pub fn try_matrix_norm(self, norm: MatrixNorm) -> Result<Self, EvaluationError> {
+        Ok(Self::Number(match self {
+            Self::Matrix(MatrixValue::TwoD(matrix)) => matrix.matrix_norm(norm),
+            Self::Matrix(MatrixValue::ThreeD(matrix)) => matrix.matrix_norm(norm),
+            Self::Matrix(MatrixValue::Dynamic(matrix)) => matrix.matrix_norm(norm),
+            Self::ComplexMatrix(matrix) if norm == MatrixNorm::Frobenius => matrix.norm(),
+            Self::ComplexMatrix(_) => Err(EvaluationError::UnsupportedComplexMatrix)?,
+            _ => Err(EvaluationError::MatrixNormRequiresMatrix)?,
+        }))
+    }
+
+    /// Try to take the dot product of two vectors.
+    pub fn try_dot(left: Self, right: Self) -> Result<Self, EvaluationError> {
+        match (left, right) {
+            (Self::Vector(a), Self::Vector(b)) => Ok(Self::Number(
+                Vector2dOr3d::try_dot(a, b).ok_or(EvaluationError::CannotDotDifferentDimensions)?,
+            )),
+            _ => Err(EvaluationError::DotProductRequiresVectors),
+        }
+    }
+
+    /// Try to take the cross product of two vectors.
+    ///
+    /// Two 3D vectors give a 3D vector, but two 2D vectors give the number which would be the z
+    /// component of their cross product if they were embedded in 3D.
+    pub fn try_cross(left: Self, right: Self) -> Result<Self, EvaluationError> {
+        match (left, right) {
+            (Self::Vector(Vector2dOr3d::TwoD(a)), Self::Vector(Vector2dOr3d::TwoD(b))) => {
+                Ok(Self::Number(a.perp_dot(b)))
             }
-            Self::Exponent { base, power } => {
-                let base = base.internal_to_expression_string(false);
-                // The braces also act as parens, so we can treat the power as if it were top-level
-                let power = power.internal_to_expression_string(true);
-                let string = format!("{base} ^ {{{power}}}");
-                if !top_level {
-                    format!("({string})")
+            (Self::Vector(Vector2dOr3d::ThreeD(a)), Self::Vector(Vector2dOr3d::ThreeD(b))) => {
+                Ok(Self::Vector(Vector2dOr3d::ThreeD(a.cross(b))))
+            }
+            (Self::Vector(_), Self::Vector(_)) => {
+                Err(EvaluationError::CannotCrossDifferentDimensions)
+            }
+            _ => Err(EvaluationError::CrossProductRequiresVectors),
+        }
+    }
+}
+
+/// Limits on the size of an expression that [`AstNode::evaluate_with_limits`] will evaluate.
+///
+/// Evaluation doesn't recurse, so these aren't needed to protect the stack. They stop a
+/// pathological expression (like one pasted in by a user) from taking a huge amount of time and
+/// memory to evaluate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvaluationLimits {
+    /// The maximum depth of the tree, where a single node has depth 1.
+    ///
+    /// This is separate from the parser's fixed
+    /// [`MAX_NESTING_DEPTH`](super::parser::MAX_NESTING_DEPTH), which only limits brackets,
+    /// function calls, negations, and powers, and which can't be changed here. A parsed
+    /// expression can still reach this limit through long chains of operators like
+    /// `1 + 1 + ... + 1`, which make deep trees without any nesting, and this is the only depth
+    /// limit on trees built directly as [`AstNode`]s.
+    pub max_depth: usize,
+
+    /// The maximum number of nodes in the tree.
+    pub max_nodes: usize,
+}
+
+impl Default for EvaluationLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 10_000,
+            max_nodes: 1_000_000,
+        }
+    }
+}
+
+/// Options that control how [`AstNode::evaluate_with_options`] evaluates an expression.
+///
+/// The tolerances decide when a float counts as an integer (like a matrix power or an index) and
+/// when a determinant counts as zero. Two numbers `a` and `b` count as equal if they're within
+/// `epsilon` of each other, or within `max_relative` times the larger of their magnitudes. See
+/// [`RelativeEq`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvalOptions {
+    /// The absolute tolerance for comparisons, mainly used for comparisons near zero.
+    pub epsilon: f64,
+
+    /// The relative tolerance for comparisons, used for comparisons between large numbers.
+    pub max_relative: f64,
+
+    /// The limits on the size of the expression.
+    pub limits: EvaluationLimits,
+
+    /// Whether to treat a 3D result as a 2D affine transform in homogeneous coordinates.
+    ///
+    /// If this is set, then the bottom row of a 3D matrix result must be `[0 0 1]` under these
+    /// tolerances, and it gets set to exactly that to clean up any floating point error.
+    /// Otherwise, evaluation fails with [`EvaluationError::NotAffine2d`].
+    pub affine_2d: bool,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        Self {
+            epsilon: EPSILON,
+            max_relative: <f64 as RelativeEq>::default_max_relative(),
+            limits: EvaluationLimits::default(),
+            affine_2d: false,
+        }
+    }
+}
+
+impl EvalOptions {
+    /// Does this number count as an integer under these tolerances?
+    pub fn is_integer(&self, number: f64) -> bool {
+        number
+            .round()
+            .relative_eq(&number, self.epsilon, self.max_relative)
+    }
+
+    /// Does this number count as zero under these tolerances?
+    pub fn is_zero(&self, number: f64) -> bool {
+        number.relative_eq(&0., self.epsilon, self.max_relative)
+    }
+}
+
+/// The value of one sub-expression, as returned by [`AstNode::evaluate_steps`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvaluationStep {
+    /// The sub-expression, formatted with the default [`FormatOptions`].
+    pub expression: String,
+
+    /// The value of the sub-expression.
+    pub value: NumberOrMatrix,
+}
+
+/// An error which can be returned by [`AstNode::evaluate`].
+#[allow(
+    missing_docs,
+    reason = "All variants impl Display and most are obvious from the name"
+)]
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum EvaluationError {
+    #[error("Cannot multiply two matrices of different dimensions")]
+    CannotMultiplyDifferentDimensions,
+
+    #[error("Cannot add two matrices of different dimensions")]
+    CannotAddDifferentDimensions,
+
+    #[error("Cannot add a number and a matrix")]
+    CannotAddNumberAndMatrix,
+
+    #[error("Cannot raise a matrix to a non-integer number")]
+    CannotRaiseMatrixToNonInteger,
+
+    #[error("Cannot raise anything to the power of a matrix")]
+    CannotRaiseToMatrix,
+
+    #[error("Cannot divide by a matrix")]
+    CannotDivideByMatrix,
+
+    #[error("Cannot invert a singular (determinant 0) matrix")]
+    CannotInvertSingularMatrix,
+
+    #[error("Raising to this power gives a result too big to represent")]
+    PowerOverflow,
+
+    #[error("Cannot transpose a scalar number")]
+    CannotTransposeNumber,
+
+    #[error("Cannot multiply a vector by a matrix (try multiplying the matrix by the vector)")]
+    CannotMultiplyVectorByMatrix,
+
+    #[error("Cannot multiply two vectors together (try using dot or cross)")]
+    CannotMultiplyTwoVectors,
+
+    #[error("Cannot divide by a vector")]
+    CannotDivideByVector,
+
+    #[error("Cannot add a number and a vector")]
+    CannotAddNumberAndVector,
+
+    #[error("Cannot add a matrix and a vector")]
+    CannotAddMatrixAndVector,
+
+    #[error("Cannot raise a vector to a power")]
+    CannotRaiseVector,
+
+    #[error("Cannot raise anything to the power of a vector")]
+    CannotRaiseToVector,
+
+    #[error("Cannot transpose a vector")]
+    CannotTransposeVector,
+
+    #[error("The dot product can only be taken of two vectors")]
+    DotProductRequiresVectors,
+
+    #[error("Cannot take the dot product of two vectors of different dimensions")]
+    CannotDotDifferentDimensions,
+
+    #[error("The cross product can only be taken of two vectors")]
+    CrossProductRequiresVectors,
+
+    #[error("Cannot take the cross product of two vectors of different dimensions")]
+    CannotCrossDifferentDimensions,
+
+    #[error("Can only index into a matrix")]
+    CannotIndexNonMatrix,
+
+    #[error("Index [{row}, {column}] is out of bounds for a {dimension}x{dimension} matrix")]
+    IndexOutOfBounds {
+        row: usize,
+        column: usize,
+        dimension: usize,
+    },
+
+    #[error("Can only extract rows and columns from a matrix")]
+    CannotExtractFromNonMatrix,
+
+    #[error("Row and column indices must be positive integers")]
+    IndexMustBePositiveInteger,
+
+    #[error("Row {row} is out of bounds for a {dimension}x{dimension} matrix")]
+    RowOutOfBounds { row: usize, dimension: usize },
+
+    #[error("Column {column} is out of bounds for a {dimension}x{dimension} matrix")]
+    ColumnOutOfBounds { column: usize, dimension: usize },
+
+    #[error("Can only augment column vectors together into a matrix")]
+    AugmentRequiresVectors,
+
+    #[error("Cannot augment vectors of different dimensions")]
+    CannotAugmentDifferentDimensions,
+
+    #[error("Cannot build a square matrix from {columns} columns of dimension {dimension}")]
+    AugmentWrongNumberOfColumns { columns: usize, dimension: usize },
+
+    #[error("A block matrix must look like block(2D matrix, 2D vector; 2D vector, number)")]
+    InvalidBlockMatrix,
+
+    #[error("Can only solve a linear system with a matrix and a vector")]
+    SolveRequiresMatrixAndVector,
+
+    #[error("Cannot solve a linear system with a matrix and vector of different dimensions")]
+    CannotSolveDifferentDimensions,
+
+    #[error("Cannot solve a linear system with a singular (determinant 0) matrix")]
+    CannotSolveSingularSystem,
+
+    #[error("Cannot solve a linear system with infinite or NaN entries")]
+    CannotSolveNonFiniteSystem,
+
+    #[error("Can only take the norm of a vector or matrix")]
+    NormRequiresVectorOrMatrix,
+
+    #[error("Can only take the adjugate of a matrix")]
+    AdjugateRequiresMatrix,
+
+    #[error("Can only take the cofactors of a matrix")]
+    CofactorRequiresMatrix,
+
+    #[error("Can only take the pseudoinverse of a matrix")]
+    PseudoInverseRequiresMatrix,
+
+    #[error("Cannot take the pseudoinverse of a matrix with infinite or NaN entries")]
+    PseudoInverseRequiresFiniteMatrix,
+
+    #[error("Can only build a translation from two numbers, like translate(1, 2)")]
+    TranslationRequiresNumbers,
+
+    #[error("The result is not a 2D affine transform, since its bottom row is not [0 0 1]")]
+    NotAffine2d,
+
+    #[error("Can only build a line projection from an angle in degrees, like proj_line(30)")]
+    LineProjectionRequiresNumber,
+
+    #[error(
+        "Can only build a plane projection from a 3D normal vector, like proj_plane([0; 0; 1])"
+    )]
+    PlaneProjectionRequiresVector,
+
+    #[error("Can only build a random matrix from a positive integer dimension and a non-negative integer seed, like rand(2, 42)")]
+    RandomRequiresIntegers,
+
+    #[error("Cannot project onto a line or plane with a zero, infinite, or NaN direction")]
+    CannotProjectOntoNonFinite,
+
+    #[error("Can only take the rank of a matrix")]
+    RankRequiresMatrix,
+
+    #[error("Cannot take the rank of a matrix with infinite or NaN entries")]
+    RankRequiresFiniteMatrix,
+
+    #[error("Can only check the properties of a matrix")]
+    PropertyRequiresMatrix,
+
+    #[error("Can only take a named norm like norm(M, \"fro\") of a matrix")]
+    MatrixNormRequiresMatrix,
+
+    #[error("Can only take the exponential of a number or matrix")]
+    ExponentialRequiresNumberOrMatrix,
+
+    #[error("The exponential of this matrix is too big, or it has infinite or NaN entries")]
+    ExponentialNotFinite,
+
+    #[error("Can only take the logarithm of a number or matrix")]
+    LogarithmRequiresNumberOrMatrix,
+
+    #[error("Can only take the logarithm of a positive number, or a matrix with no zero or negative real eigenvalues")]
+    LogarithmUndefined,
+
+    #[error("This operation only supports 2x2 and 3x3 matrices, not {dimension}x{dimension}")]
+    UnsupportedDimension { dimension: usize },
+
+    #[error("Cannot divide by zero")]
+    CannotDivideByZero,
+
+    #[error("This operation isn't supported by high-precision evaluation")]
+    UnsupportedHighPrecision,
+
+    #[error("High-precision evaluation needs at least one significant digit")]
+    ZeroPrecision,
+
+    #[error("This operation only supports real matrices, not complex ones")]
+    UnsupportedComplexMatrix,
+
+    #[error("Cannot combine a vector with a complex number or matrix, since vectors must be real")]
+    CannotCombineVectorAndComplex,
+
+    #[error("Expression is nested too deeply (the limit is {max_depth} levels)")]
+    ExpressionTooDeep { max_depth: usize },
+
+    #[error("Expression is too large (the limit is {max_nodes} terms)")]
+    ExpressionTooLarge { max_nodes: usize },
+
+    /// An error occurred when getting a value from the matrix map.
+    #[error("{0}")]
+    MatrixMapError(#[from] MatrixMapError),
+
+    /// An error occurred when getting a value from the scalar map.
+    #[error("{0}")]
+    ScalarMapError(#[from] ScalarMapError),
+}
+
+impl From<PowerError> for EvaluationError {
+    fn from(error: PowerError) -> Self {
+        match error {
+            PowerError::Overflow => Self::PowerOverflow,
+            PowerError::NotInvertible => Self::CannotInvertSingularMatrix,
+        }
+    }
+}
+
+impl AstNode {
+    /// Evaluate this AST node, using the [default options](EvalOptions::default).
+    ///
+    /// This doesn't recurse, so even very deeply nested expressions can't overflow the stack.
+    pub fn evaluate(self, map: &impl MatrixMap) -> Result<NumberOrMatrix, EvaluationError> {
+        self.evaluate_with_options(map, EvalOptions::default())
+    }
+
+    /// Evaluate this AST node, returning [`EvaluationError::ExpressionTooDeep`] or
+    /// [`EvaluationError::ExpressionTooLarge`] if the expression exceeds the given limits.
+    ///
+    /// The limits are checked as the tree is walked, so an oversized expression is rejected
+    /// without evaluating any more of it than necessary.
+    ///
+    /// ```
+    /// # use trinity::matrix::{expression::{ast::{EvaluationError, EvaluationLimits}, parse_expression_from_string}, map::prelude::*};
+    /// let ast = parse_expression_from_string("((1 + 2) * 3) + 4").unwrap();
+    /// let limits = EvaluationLimits {
+    ///     max_depth: 3,
+    ///     ..EvaluationLimits::default()
+    /// };
+    ///
+    /// assert_eq!(
+    ///     ast.evaluate_with_limits(&MatrixMap2::new(), limits),
+    ///     Err(EvaluationError::ExpressionTooDeep { max_depth: 3 })
+    /// );
+    /// ```
+    pub fn evaluate_with_limits(
+        self,
+        map: &impl MatrixMap,
+        limits: EvaluationLimits,
+    ) -> Result<NumberOrMatrix, EvaluationError> {
+        self.evaluate_with_options(
+            map,
+            EvalOptions {
+                limits,
+                ..EvalOptions::default()
+            },
+        )
+    }
+
+    /// Evaluate this AST node with the given tolerances and limits. See [`EvalOptions`].
+    ///
+    /// ```
+    /// # use trinity::matrix::{expression::{ast::{EvalOptions, EvaluationError}, parse_expression_from_string}, map::prelude::*};
+    /// let ast = parse_expression_from_string("[1 2; 2 4.0001]^-1").unwrap();
+    /// let loose = EvalOptions {
+    ///     epsilon: 0.001,
+    ///     ..EvalOptions::default()
+    /// };
+    ///
+    /// assert!(ast.clone().evaluate(&MatrixMap2::new()).is_ok());
+    /// assert_eq!(
+    ///     ast.evaluate_with_options(&MatrixMap2::new(), loose),
+    ///     Err(EvaluationError::CannotInvertSingularMatrix)
+    /// );
+    /// ```
+    pub fn evaluate_with_options(
+        self,
+        map: &impl MatrixMap,
+        options: EvalOptions,
+    ) -> Result<NumberOrMatrix, EvaluationError> {
+        let value = self.try_fold_iteratively(check_limits(options.limits), |node, children| {
+            node.evaluate_node(map, options, children)
+        })?;
+        snap_affine(value, options)
+    }
+
+    /// Evaluate this AST node like [`evaluate_with_options`](Self::evaluate_with_options), but
+    /// return the value of every sub-expression along the way, in the order that they were
+    /// evaluated. The last step is the whole expression.
+    ///
+    /// Plain numbers are left out unless they're the whole expression, since they aren't
+    /// interesting to show on their own. This is for stepping through an evaluation one
+    /// intermediate result at a time.
+    ///
+    /// ```
+    /// # use trinity::matrix::{expression::{ast::EvalOptions, parse_expression_from_string}, map::prelude::*, MatrixName};
+    /// # use glam::DMat2;
+    /// let mut map = MatrixMap2::new();
+    /// map.set(MatrixName::new("S"), DMat2::IDENTITY * 2.).unwrap();
+    /// map.set(MatrixName::new("T"), DMat2::IDENTITY).unwrap();
+    ///
+    /// let ast = parse_expression_from_string("rot(90) * S / 2 + T").unwrap();
+    /// let steps = ast.evaluate_steps(&map, EvalOptions::default()).unwrap();
+    /// let expressions: Vec<_> = steps.iter().map(|step| step.expression.as_str()).collect();
+    ///
+    /// assert_eq!(expressions, ["rot(90)", "S", "S / 2", "rot(90) * S / 2", "T", "rot(90) * S / 2 + T"]);
+    /// assert_eq!(steps.last().unwrap().value, ast.evaluate(&map).unwrap());
+    /// ```
+    pub fn evaluate_steps(
+        &self,
+        map: &impl MatrixMap,
+        options: EvalOptions,
+    ) -> Result<Vec<EvaluationStep>, EvaluationError> {
+        let format_options = FormatOptions::default();
+        let mut steps = Vec::new();
+
+        // Each node is formatted from the strings of its children, so that formatting every step
+        // doesn't format the same subexpressions over and over again
+        let (value, formatted) =
+            self.try_fold_iteratively(check_limits(options.limits), |node, children| {
+                let (values, formatted): (Vec<_>, Vec<_>) = children.into_iter().unzip();
+                let value = node.evaluate_node(map, options, values)?;
+                let formatted = node.format_node(&format_options, formatted);
+                if !matches!(node, Self::Number(_) | Self::Imaginary(_)) {
+                    steps.push(EvaluationStep {
+                        expression: formatted.string.clone(),
+                        value: value.clone(),
+                    });
+                }
+                Ok((value, formatted))
+            })?;
+
+        // The whole expression is always the last step, even if it's just a number
+        let value = snap_affine(value, options)?;
+        if matches!(self, Self::Number(_) | Self::Imaginary(_)) {
+            steps.push(EvaluationStep {
+                expression: formatted.string,
+                value,
+            });
+        } else if let Some(step) = steps.last_mut() {
+            step.value = value;
+        }
+        Ok(steps)
+    }
+
+    /// Evaluate just this node, given the values of its [`children`](Self::children).
+    pub(super) fn evaluate_node(
+        &self,
+        map: &impl MatrixMap,
+        options: EvalOptions,
+        children: Vec<NumberOrMatrix>,
+    ) -> Result<NumberOrMatrix, EvaluationError> {
+        let mut children = children.into_iter();
+        let mut next = || {
+            children
+                .next()
+                .expect("Every child should have been evaluated before its parent")
+        };
+
+        match self {
+            Self::Multiply { .. } => NumberOrMatrix::try_mul(next(), next()),
+            Self::Divide { .. } => NumberOrMatrix::try_div(next(), next()),
+            Self::Add { .. } => NumberOrMatrix::try_add(next(), next()),
+            Self::Negate(_) => Ok(NumberOrMatrix::negate(next())),
+            Self::Exponent { power, .. } => {
+                if power.is_transpose_marker() {
+                    NumberOrMatrix::try_transpose(next())
                 } else {
-                    string
+                    NumberOrMatrix::try_power(next(), next(), options)
                 }
             }
-            Self::Number(number) => number.to_string(),
-            Self::NamedMatrix(MatrixName { name }) => name.to_string(),
-            Self::RotationMatrix { degrees } => format!("rot({degrees})"),
-            Self::Anonymous2dMatrix(DMat2 { x_axis, y_axis }) => {
-                format!("[{} {}; {} {}]", x_axis.x, y_axis.x, x_axis.y, y_axis.y)
+            Self::Number(number) => Ok(NumberOrMatrix::Number(*number)),
+            Self::Imaginary(number) => Ok(NumberOrMatrix::Complex(Complex::new(0., *number))),
+            Self::NamedMatrix(name) => Ok(NumberOrMatrix::Matrix(map.get(name)?.into())),
+            Self::Variable(name) => Ok(NumberOrMatrix::Number(map.scalar(name)?)),
+            Self::RotationMatrix { degrees } => Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(
+                DMat2::from_angle(degrees.to_radians()),
+            ))),
+            Self::Anonymous2dMatrix(matrix) => {
+                Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(*matrix)))
             }
+            Self::Anonymous3dMatrix(matrix) => {
+                Ok(NumberOrMatrix::Matrix(MatrixValue::ThreeD(*matrix)))
+            }
+            Self::AnonymousDynamicMatrix(matrix) => {
+                Ok(NumberOrMatrix::Matrix(MatrixValue::from(matrix.clone())))
+            }
+            Self::Anonymous2dVector(vector) => {
+                Ok(NumberOrMatrix::Vector(Vector2dOr3d::TwoD(*vector)))
+            }
+            Self::Anonymous3dVector(vector) => {
+                Ok(NumberOrMatrix::Vector(Vector2dOr3d::ThreeD(*vector)))
+            }
+            Self::DotProduct { .. } => NumberOrMatrix::try_dot(next(), next()),
+            Self::CrossProduct { .. } => NumberOrMatrix::try_cross(next(), next()),
+            Self::Index { row, column, .. } => NumberOrMatrix::try_index(next(), *row, *column),
+            Self::Row { .. } => NumberOrMatrix::try_row(next(), next(), options),
+            Self::Column { .. } => NumberOrMatrix::try_column(next(), next(), options),
+            Self::Augment { .. } => NumberOrMatrix::try_augment(children.collect()),
+            Self::Block { .. } => NumberOrMatrix::try_block(next(), next(), next(), next()),
+            Self::Solve { .. } => NumberOrMatrix::try_solve(next(), next(), options),
+            Self::LeastSquares { .. } => NumberOrMatrix::try_least_squares(next(), next()),
+            Self::Translation { .. } => NumberOrMatrix::try_translation(next(), next()),
+            Self::Norm(_) => NumberOrMatrix::try_norm(next()),
+            Self::Adjugate(_) => NumberOrMatrix::try_adjugate(next()),
+            Self::Cofactor { .. } => NumberOrMatrix::try_cofactor(next(), next(), next(), options),
+            Self::Rank(_) => NumberOrMatrix::try_rank(next(), options),
+            Self::Exponential(_) => NumberOrMatrix::try_exp(next()),
+            Self::Logarithm(_) => NumberOrMatrix::try_log(next()),
+            Self::HasProperty { property, .. } => {
+                NumberOrMatrix::try_has_property(next(), *property, options)
+            }
+            Self::MatrixNorm { norm, .. } => NumberOrMatrix::try_matrix_norm(next(), *norm),
+            Self::PseudoInverse(_) => NumberOrMatrix::try_pseudo_inverse(next()),
+            Self::LineProjection(_) => NumberOrMatrix::try_line_projection(next()),
+            Self::PlaneProjection(_) => NumberOrMatrix::try_plane_projection(next()),
+            Self::Random { distribution, .. } => {
+                NumberOrMatrix::try_random(next(), next(), *distribution, options)
+            }
+        }
+    }
+
+    /// Convert this AST node into an expression string, with every compound operand in
+    /// parentheses. See [`to_formatted_string`](Self::to_formatted_string) for cleaner output.
+    ///
+    /// Like [`evaluate`](Self::evaluate), this doesn't recurse, so it can't overflow the stack.
+    pub fn to_expression_string(&self) -> String {
+        self.to_formatted_string(&FormatOptions::FULLY_PARENTHESISED)
+    }
+
+    /// Fold this tree into a single value, without recursing.
+    ///
+    /// The tree is walked depth-first using an explicit stack on the heap. `enter` is called with
+    /// the depth of each node (starting at 1) when it's first reached, and `combine` is called
+    /// with each node and the values of its [`children`](Self::children) once they've all been
+    /// combined. Either one can stop the fold by returning an error.
+    pub(super) fn try_fold_iteratively<T, E>(
+        &self,
+        mut enter: impl FnMut(usize) -> Result<(), E>,
+        mut combine: impl FnMut(&Self, Vec<T>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        /// A piece of work to do in the fold.
+        enum Task<'a> {
+            /// Reach this node at this depth, and queue up its children.
+            Enter(&'a AstNode, usize),
+
+            /// Combine this node with the values of this many children.
+            Combine(&'a AstNode, usize),
+        }
+
+        let mut tasks = vec![Task::Enter(self, 1)];
+        let mut values: Vec<T> = vec![];
+
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Enter(node, depth) => {
+                    enter(depth)?;
+                    let children = node.children();
+                    tasks.push(Task::Combine(node, children.len()));
+                    // The stack is last-in-first-out, so we push the children backwards to
+                    // handle them in order
+                    tasks.extend(
+                        children
+                            .into_iter()
+                            .rev()
+                            .map(|child| Task::Enter(child, depth + 1)),
+                    );
+                }
+                Task::Combine(node, child_count) => {
+                    let children = values.split_off(values.len() - child_count);
+                    values.push(combine(node, children)?);
+                }
+            }
+        }
+
+        Ok(values
+            .pop()
+            .expect("Folding a tree should always produce exactly one value"))
+    }
+
+    /// Get all the named matrices that are referenced in this AST, in the order that they appear.
+    pub fn named_matrices(&self) -> Vec<MatrixName> {
+        let mut names = vec![];
+        let mut pending = vec![self];
+
+        while let Some(node) = pending.pop() {
+            if let Self::NamedMatrix(name) = node {
+                names.push(name.clone());
+            }
+            // The stack is last-in-first-out, so we push the children backwards to find the
+            // names in order
+            pending.extend(node.children().into_iter().rev());
+        }
+
+        names
+    }
+
+    /// Is this node the `T` in a transposition like `A^T`?
+    pub fn is_transpose_marker(&self) -> bool {
+        *self == Self::NamedMatrix(MatrixName::new("T"))
+    }
+
+    /// Split a product like `C * B * A` into the transformations that it's made of, in the order
+    /// that they apply to a vector, so `[A, B, C]`. Anything that isn't a product is a single
+    /// stage.
+    ///
+    /// Only factors which are definitely matrices get their own stages. A scalar factor is
+    /// folded into the stage of the next matrix to its right (or the last one if there isn't
+    /// one), so `B * 2 * A` gives `[2 * A, B]`. If any factor might not be a matrix or a number,
+    /// like the vector in `A * [1; 2]`, then the product isn't a composition of transformations,
+    /// so the whole expression is a single stage.
+    ///
+    /// This is for animating a composition one stage at a time, to show that `B * A` means
+    /// applying `A` first. Like [`evaluate`](Self::evaluate), this doesn't recurse.
+    ///
+    /// ```
+    /// # use trinity::matrix::expression::{format::FormatOptions, parse_expression_from_string};
+    /// let ast = parse_expression_from_string("rot(90) * (S * R) * 2 * (A + B)").unwrap();
+    /// let stages: Vec<_> = ast
+    ///     .composition_stages()
+    ///     .into_iter()
+    ///     .map(|stage| stage.to_formatted_string(&FormatOptions::default()))
+    ///     .collect();
+    ///
+    /// assert_eq!(stages, ["2 * (A + B)", "R", "S", "rot(90)"]);
+    /// ```
+    pub fn composition_stages(&self) -> Vec<Self> {
+        let kinds = self.definite_kinds();
+        let kind = |node: &Self| kinds[&(node as *const Self)];
+
+        // The factors of the product from left to right, so the left factor is popped first
+        let mut factors = Vec::new();
+        let mut pending = vec![self];
+        while let Some(node) = pending.pop() {
+            match node {
+                Self::Multiply { left, right } => {
+                    pending.push(right);
+                    pending.push(left);
+                }
+                node => factors.push(node),
+            }
+        }
+
+        let is_composition = factors.iter().any(|&factor| kind(factor).matrix)
+            && factors
+                .iter()
+                .all(|&factor| kind(factor).matrix || kind(factor).number);
+        if !is_composition {
+            return vec![self.clone()];
+        }
+
+        let mut groups: Vec<Vec<&Self>> = Vec::new();
+        let mut scalars = Vec::new();
+        for factor in factors {
+            if kind(factor).matrix {
+                let mut group = mem::take(&mut scalars);
+                group.push(factor);
+                groups.push(group);
+            } else {
+                scalars.push(factor);
+            }
+        }
+        groups
+            .last_mut()
+            .expect("A composition should have at least one matrix")
+            .extend(scalars);
+
+        // The rightmost stage applies first, and products are right associative like in the parser
+        groups
+            .into_iter()
+            .rev()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .rev()
+                    .cloned()
+                    .reduce(|right, left| Self::Multiply {
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    })
+                    .expect("Every group should have a matrix")
+            })
+            .collect()
+    }
+
+    /// Get the direct children of this node, in the order that they appear in the expression.
+    ///
+    /// Like [`map_children`](Self::map_children), this doesn't include the `T` in a
+    /// transposition like `A^T`.
+    pub fn children(&self) -> Vec<&Self> {
+        match self {
+            Self::Multiply { left, right }
+            | Self::Divide { left, right }
+            | Self::Add { left, right }
+            | Self::DotProduct { left, right }
+            | Self::CrossProduct { left, right } => vec![left, right],
+            Self::Negate(term)
+            | Self::Norm(term)
+            | Self::Adjugate(term)
+            | Self::Rank(term)
+            | Self::Exponential(term)
+            | Self::Logarithm(term)
+            | Self::HasProperty { matrix: term, .. }
+            | Self::MatrixNorm { matrix: term, .. }
+            | Self::PseudoInverse(term)
+            | Self::LineProjection(term)
+            | Self::PlaneProjection(term) => vec![term],
+            Self::Exponent { base, power } => {
+                if power.is_transpose_marker() {
+                    vec![base]
+                } else {
+                    vec![base, power]
+                }
+            }
+            Self::Number(_)
+            | Self::Imaginary(_)
+            | Self::NamedMatrix(_)
+            | Self::Variable(_)
+            | Self::RotationMatrix { .. }
+            | Self::Anonymous2dMatrix(_)
+            | Self::Anonymous3dMatrix(_)
+            | Self::AnonymousDynamicMatrix(_)
+            | Self::Anonymous2dVector(_)
+            | Self::Anonymous3dVector(_) => vec![],
+            Self::Index { matrix, .. } => vec![matrix],
+            Self::Row { matrix, index } | Self::Column { matrix, index } => vec![matrix, index],
+            Self::Augment { columns } => columns.iter().collect(),
+            Self::Block {
+                top_left,
+                top_right,
+                bottom_left,
+                bottom_right,
+            } => vec![top_left, top_right, bottom_left, bottom_right],
+            Self::Solve { matrix, vector } | Self::LeastSquares { matrix, vector } => {
+                vec![matrix, vector]
+            }
+            Self::Translation { x, y } => vec![x, y],
+            Self::Random {
+                dimension, seed, ..
+            } => vec![dimension, seed],
+            Self::Cofactor {
+                matrix,
+                row,
+                column,
+            } => vec![matrix, row, column],
+        }
+    }
+
+    /// Get mutable references to the direct children of this node, in the same order as
+    /// [`children`](Self::children).
+    pub fn children_mut(&mut self) -> Vec<&mut Self> {
+        match self {
+            Self::Multiply { left, right }
+            | Self::Divide { left, right }
+            | Self::Add { left, right }
+            | Self::DotProduct { left, right }
+            | Self::CrossProduct { left, right } => vec![left, right],
+            Self::Negate(term)
+            | Self::Norm(term)
+            | Self::Adjugate(term)
+            | Self::Rank(term)
+            | Self::Exponential(term)
+            | Self::Logarithm(term)
+            | Self::HasProperty { matrix: term, .. }
+            | Self::MatrixNorm { matrix: term, .. }
+            | Self::PseudoInverse(term)
+            | Self::LineProjection(term)
+            | Self::PlaneProjection(term) => vec![term],
+            Self::Exponent { base, power } => {
+                if power.is_transpose_marker() {
+                    vec![base]
+                } else {
+                    vec![base, power]
+                }
+            }
+            Self::Number(_)
+            | Self::Imaginary(_)
+            | Self::NamedMatrix(_)
+            | Self::Variable(_)
+            | Self::RotationMatrix { .. }
+            | Self::Anonymous2dMatrix(_)
+            | Self::Anonymous3dMatrix(_)
+            | Self::AnonymousDynamicMatrix(_)
+            | Self::Anonymous2dVector(_)
+            | Self::Anonymous3dVector(_) => vec![],
+            Self::Index { matrix, .. } => vec![matrix],
+            Self::Row { matrix, index } | Self::Column { matrix, index } => vec![matrix, index],
+            Self::Augment { columns } => columns.iter_mut().collect(),
+            Self::Block {
+                top_left,
+                top_right,
+                bottom_left,
+                bottom_right,
+            } => vec![top_left, top_right, bottom_left, bottom_right],
+            Self::Solve { matrix, vector } | Self::LeastSquares { matrix, vector } => {
+                vec![matrix, vector]
+            }
+            Self::Translation { x, y } => vec![x, y],
+            Self::Random {
+                dimension, seed, ..
+            } => vec![dimension, seed],
+            Self::Cofactor {
+                matrix,
+                row,
+                column,
+            } => vec![matrix, row, column],
+        }
+    }
+
+    /// Move this node out, leaving a leaf in its place.
+    ///
+    /// Since [`AstNode`] implements [`Drop`], we can't move children out of a node with a pattern,
+    /// so we take them out of their boxes with this instead.
+    pub(super) fn take(node: &mut Self) -> Self {
+        mem::replace(node, Self::Number(0.))
+    }
+
+    /// Rebuild this node by applying `f` to each of its direct children, leaving leaves as they
+    /// are.
+    ///
+    /// The `T` in a transposition like `A^T` is part of the operator rather than a child, so `f`
+    /// is never called on it.
+    pub fn map_children(mut self, mut f: impl FnMut(Self) -> Self) -> Self {
+        for child in self.children_mut() {
+            *child = f(Self::take(child));
+        }
+        self
+    }
+
+    /// Rebuild this whole tree by applying `f` to every node, after its children have already
+    /// been rebuilt, without recursing.
+    ///
+    /// This stops at the first error, in the same order as [`evaluate`](Self::evaluate).
+    pub(super) fn try_map_bottom_up<E>(
+        self,
+        mut f: impl FnMut(Self) -> Result<Self, E>,
+    ) -> Result<Self, E> {
+        /// A piece of work to do in the rebuild.
+        enum Task {
+            /// Take the children out of this node and queue them up.
+            Enter(AstNode),
+
+            /// Put this many rebuilt children back into this node, and then rebuild it.
+            Combine(AstNode, usize),
+        }
+
+        let mut tasks = vec![Task::Enter(self)];
+        let mut values: Vec<Self> = vec![];
+
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Enter(mut node) => {
+                    let children: Vec<Self> =
+                        node.children_mut().into_iter().map(Self::take).collect();
+                    tasks.push(Task::Combine(node, children.len()));
+                    tasks.extend(children.into_iter().rev().map(Task::Enter));
+                }
+                Task::Combine(node, child_count) => {
+                    let children = values.split_off(values.len() - child_count);
+                    values.push(f(node.with_children(children))?);
+                }
+            }
+        }
+
+        Ok(values
+            .pop()
+            .expect("Rebuilding a tree should always produce exactly one node"))
+    }
+
+    /// Like [`try_map_bottom_up`](Self::try_map_bottom_up), but `f` can't fail.
+    pub(super) fn map_bottom_up(self, mut f: impl FnMut(Self) -> Self) -> Self {
+        match self.try_map_bottom_up(|node| Ok::<_, Infallible>(f(node))) {
+            Ok(node) => node,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Like [`try_fold_iteratively`](Self::try_fold_iteratively), but without a depth callback,
+    /// and `combine` can't fail.
+    pub(super) fn fold_iteratively<T>(&self, mut combine: impl FnMut(&Self, Vec<T>) -> T) -> T {
+        match self.try_fold_iteratively(
+            |_| Ok::<_, Infallible>(()),
+            |node, children| Ok(combine(node, children)),
+        ) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Replace the direct children of this node with these ones, in the same order as
+    /// [`children`](Self::children).
+    fn with_children(mut self, children: Vec<Self>) -> Self {
+        debug_assert_eq!(self.children().len(), children.len());
+        for (child, replacement) in self.children_mut().into_iter().zip(children) {
+            *child = replacement;
+        }
+        self
+    }
+
+    /// Clone this node without its children, which are replaced with leaves.
+    ///
+    /// The `T` in a transposition like `A^T` isn't a child, so it's kept.
+    fn shallow_clone(&self) -> Self {
+        let leaf = || Box::new(Self::Number(0.));
+
+        match self {
+            Self::Multiply { .. } => Self::Multiply {
+                left: leaf(),
+                right: leaf(),
+            },
+            Self::Divide { .. } => Self::Divide {
+                left: leaf(),
+                right: leaf(),
+            },
+            Self::Add { .. } => Self::Add {
+                left: leaf(),
+                right: leaf(),
+            },
+            Self::Negate(_) => Self::Negate(leaf()),
+            Self::Exponent { power, .. } => Self::Exponent {
+                base: leaf(),
+                power: if power.is_transpose_marker() {
+                    Box::new(Self::NamedMatrix(MatrixName::new("T")))
+                } else {
+                    leaf()
+                },
+            },
+            Self::Number(number) => Self::Number(*number),
+            Self::Imaginary(number) => Self::Imaginary(*number),
+            Self::NamedMatrix(name) => Self::NamedMatrix(name.clone()),
+            Self::Variable(name) => Self::Variable(name.clone()),
+            Self::RotationMatrix { degrees } => Self::RotationMatrix { degrees: *degrees },
+            Self::Anonymous2dMatrix(matrix) => Self::Anonymous2dMatrix(*matrix),
+            Self::Anonymous3dMatrix(matrix) => Self::Anonymous3dMatrix(*matrix),
+            Self::AnonymousDynamicMatrix(matrix) => Self::AnonymousDynamicMatrix(matrix.clone()),
+            Self::Anonymous2dVector(vector) => Self::Anonymous2dVector(*vector),
+            Self::Anonymous3dVector(vector) => Self::Anonymous3dVector(*vector),
+            Self::DotProduct { .. } => Self::DotProduct {
+                left: leaf(),
+                right: leaf(),
+            },
+            Self::CrossProduct { .. } => Self::CrossProduct {
+                left: leaf(),
+                right: leaf(),
+            },
+            Self::Index { row, column, .. } => Self::Index {
+                matrix: leaf(),
+                row: *row,
+                column: *column,
+            },
+            Self::Row { .. } => Self::Row {
+                matrix: leaf(),
+                index: leaf(),
+            },
+            Self::Column { .. } => Self::Column {
+                matrix: leaf(),
+                index: leaf(),
+            },
+            Self::Augment { columns } => Self::Augment {
+                columns: columns.iter().map(|_| Self::Number(0.)).collect(),
+            },
+            Self::Block { .. } => Self::Block {
+                top_left: leaf(),
+                top_right: leaf(),
+                bottom_left: leaf(),
+                bottom_right: leaf(),
+            },
+            Self::Solve { .. } => Self::Solve {
+                matrix: leaf(),
+                vector: leaf(),
+            },
+            Self::LeastSquares { .. } => Self::LeastSquares {
+                matrix: leaf(),
+                vector: leaf(),
+            },
+            Self::Translation { .. } => Self::Translation {
+                x: leaf(),
+                y: leaf(),
+            },
+            Self::Norm(_) => Self::Norm(leaf()),
+            Self::Adjugate(_) => Self::Adjugate(leaf()),
+            Self::Cofactor { .. } => Self::Cofactor {
+                matrix: leaf(),
+                row: leaf(),
+                column: leaf(),
+            },
+            Self::Rank(_) => Self::Rank(leaf()),
+            Self::Exponential(_) => Self::Exponential(leaf()),
+            Self::Logarithm(_) => Self::Logarithm(leaf()),
+            Self::HasProperty { property, .. } => Self::HasProperty {
+                property: *property,
+                matrix: leaf(),
+            },
+            Self::MatrixNorm { norm, .. } => Self::MatrixNorm {
+                norm: *norm,
+                matrix: leaf(),
+            },
+            Self::PseudoInverse(_) => Self::PseudoInverse(leaf()),
+            Self::LineProjection(_) => Self::LineProjection(leaf()),
+            Self::PlaneProjection(_) => Self::PlaneProjection(leaf()),
+            Self::Random { distribution, .. } => Self::Random {
+                distribution: *distribution,
+                dimension: leaf(),
+                seed: leaf(),
+            },
+        }
+    }
+
+    /// Are these two nodes the same, ignoring their children apart from how many there are?
+    fn shallow_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) | (Self::Imaginary(a), Self::Imaginary(b)) => a == b,
+            (Self::NamedMatrix(a), Self::NamedMatrix(b)) => a == b,
+            (Self::Variable(a), Self::Variable(b)) => a == b,
+            (Self::RotationMatrix { degrees: a }, Self::RotationMatrix { degrees: b }) => a == b,
+            (Self::Anonymous2dMatrix(a), Self::Anonymous2dMatrix(b)) => a == b,
+            (Self::Anonymous3dMatrix(a), Self::Anonymous3dMatrix(b)) => a == b,
+            (Self::AnonymousDynamicMatrix(a), Self::AnonymousDynamicMatrix(b)) => a == b,
+            (Self::Anonymous2dVector(a), Self::Anonymous2dVector(b)) => a == b,
+            (Self::Anonymous3dVector(a), Self::Anonymous3dVector(b)) => a == b,
+            (Self::Exponent { power: a, .. }, Self::Exponent { power: b, .. }) => {
+                a.is_transpose_marker() == b.is_transpose_marker()
+            }
+            (
+                Self::Index { row, column, .. },
+                Self::Index {
+                    row: other_row,
+                    column: other_column,
+                    ..
+                },
+            ) => row == other_row && column == other_column,
+            (Self::Augment { columns: a }, Self::Augment { columns: b }) => a.len() == b.len(),
+            (Self::HasProperty { property: a, .. }, Self::HasProperty { property: b, .. }) => {
+                a == b
+            }
+            (Self::MatrixNorm { norm: a, .. }, Self::MatrixNorm { norm: b, .. }) => a == b,
+            (
+                Self::Random {
+                    distribution: a, ..
+                },
+                Self::Random {
+                    distribution: b, ..
+                },
+            ) => a == b,
+            // Every other variant with the same discriminant only differs in its children
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::expression::parse_expression_from_string;
+    use approx::{assert_relative_eq, AbsDiffEq, RelativeEq};
+    use glam::{DVec2, DVec3};
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    impl AbsDiffEq for NumberOrMatrix {
+        type Epsilon = <f64 as AbsDiffEq>::Epsilon;
+
+        fn default_epsilon() -> Self::Epsilon {
+            <f64 as AbsDiffEq>::default_epsilon()
+        }
+
+        fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+            match (self, other) {
+                (Self::Number(a), Self::Number(b)) => a.abs_diff_eq(b, epsilon),
+                (Self::Matrix(a), Self::Matrix(b)) => a.abs_diff_eq(b, epsilon),
+                (Self::Vector(a), Self::Vector(b)) => a.abs_diff_eq(b, epsilon),
+                _ => false,
+            }
+        }
+    }
+
+    impl RelativeEq for NumberOrMatrix {
+        fn default_max_relative() -> Self::Epsilon {
+            <f64 as RelativeEq>::default_max_relative()
+        }
+
+        fn relative_eq(
+            &self,
+            other: &Self,
+            epsilon: Self::Epsilon,
+            max_relative: Self::Epsilon,
+        ) -> bool {
+            match (self, other) {
+                (Self::Number(a), Self::Number(b)) => a.relative_eq(b, epsilon, max_relative),
+                (Self::Matrix(a), Self::Matrix(b)) => a.relative_eq(b, epsilon, max_relative),
+                (Self::Vector(a), Self::Vector(b)) => a.relative_eq(b, epsilon, max_relative),
+                _ => false,
+            }
+        }
+    }
+
+    #[test]
+    fn ast_node_evaluation_success() {
+        let mut map2 = MatrixMap2::new();
+        let mut map3 = MatrixMap3::new();
+
+        // 10
+        assert_relative_eq!(
+            AstNode::evaluate(AstNode::Number(10.), &map2).unwrap(),
+            NumberOrMatrix::Number(10.)
+        );
+
+        // 3.2 * 5
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::Number(3.2)),
+                    right: Box::new(AstNode::Number(5.))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Number(16.)
+        );
+
+        // 1 + 2
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Add {
+                    left: Box::new(AstNode::Number(1.)),
+                    right: Box::new(AstNode::Number(2.))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Number(3.)
+        );
+
+        // 3 * [2 -2.2; 1.5 10]
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::Number(3.)),
+                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(2., 1.5),
+                        DVec2::new(-2.2, 10.)
+                    )))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(6., 4.5),
+                DVec2::new(-6.6, 30.)
+            )))
+        );
+
+        // [1 -2.34 2.3; 2.5 0 -0.5; 3.1 0.5 9.2] * ((1.2 + 2.3) * [2.3 -1.2 -3; 1.4 3 1; -3.2 -6.3 2.22])
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                        DVec3::new(1., 2.5, 3.1),
+                        DVec3::new(-2.34, 0., 0.5),
+                        DVec3::new(2.3, -0.5, 9.2)
+                    ))),
+                    right: Box::new(AstNode::Multiply {
+                        left: Box::new(AstNode::Add {
+                            left: Box::new(AstNode::Number(1.2)),
+                            right: Box::new(AstNode::Number(2.3))
+                        }),
+                        right: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                            DVec3::new(2.3, 1.4, -3.2),
+                            DVec3::new(-1.2, 3., -6.3),
+                            DVec3::new(-3., 1., 2.22)
+                        )))
+                    })
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::from_cols(
+                DVec3::new(-29.176, 25.725, -75.635),
+                DVec3::new(-79.485, 0.525, -210.63),
+                DVec3::new(-0.819, -30.135, 40.684)
+            ))),
+            epsilon = 0.00000000000001
+        );
+
+        // rot(45)
+        assert_relative_eq!(
+            AstNode::evaluate(AstNode::RotationMatrix { degrees: 45. }, &map2).unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+                DVec2::new(-FRAC_1_SQRT_2, FRAC_1_SQRT_2)
+            )))
+        );
+
+        // [1 2; 3 2] ^ (1 + 2)
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(1., 3.),
+                        DVec2::new(2., 2.)
+                    ))),
+                    power: Box::new(AstNode::Add {
+                        left: Box::new(AstNode::Number(1.)),
+                        right: Box::new(AstNode::Number(2.))
+                    })
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(25., 39.),
+                DVec2::new(26., 38.)
+            )))
+        );
+
+        // [1 2; 3 4] ^ -1
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(1., 3.),
+                        DVec2::new(2., 4.)
+                    ))),
+                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(-2., 1.5),
+                DVec2::new(1., -0.5)
+            )))
+        );
+
+        // [1 2 3; 4 5 6; 1 2 4] ^ -1
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                        DVec3::new(1., 4., 1.),
+                        DVec3::new(2., 5., 2.),
+                        DVec3::new(3., 6., 4.),
+                    ))),
+                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::from_cols(
+                DVec3::new(-(2. + 2. / 3.), 3. + 1. / 3., -1.),
+                DVec3::new(2. / 3., -1. / 3., 0.),
+                DVec3::new(1., -2., 1.),
+            )))
+        );
+
+        map2.set(
+            MatrixName::new("M"),
+            DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., 4.)),
+        )
+        .expect("Should be able to set 2D matrix M");
+
+        map3.set(
+            MatrixName::new("X"),
+            DMat3::from_cols(
+                DVec3::new(1., 4., 1.),
+                DVec3::new(2., 5., 2.),
+                DVec3::new(3., 6., 4.),
+            ),
+        )
+        .expect("Should be able to set 3D matrix X");
+
+        // M * 3
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("M"))),
+                    right: Box::new(AstNode::Number(3.))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(3., 9.),
+                DVec2::new(6., 12.)
+            )))
+        );
+
+        // X ^ (2 ^ {1 + 1})
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
+                    power: Box::new(AstNode::Exponent {
+                        base: Box::new(AstNode::Number(2.)),
+                        power: Box::new(AstNode::Add {
+                            left: Box::new(AstNode::Number(1.)),
+                            right: Box::new(AstNode::Number(1.))
+                        })
+                    })
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::from_cols(
+                DVec3::new(1035., 2568., 1159.),
+                DVec3::new(1566., 3885., 1754.),
+                DVec3::new(2349., 5826., 2632.),
+            )))
+        );
+
+        // X * (X + X)
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
+                    right: Box::new(AstNode::Add {
+                        left: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
+                        right: Box::new(AstNode::NamedMatrix(MatrixName::new("X")))
+                    })
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::from_cols(
+                DVec3::new(24., 60., 26.),
+                DVec3::new(36., 90., 40.),
+                DVec3::new(54., 132., 62.),
+            )))
+        );
+
+        // X * (1 + 2)
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
+                    right: Box::new(AstNode::Add {
+                        left: Box::new(AstNode::Number(1.)),
+                        right: Box::new(AstNode::Number(2.))
+                    })
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::from_cols(
+                DVec3::new(3., 12., 3.),
+                DVec3::new(6., 15., 6.),
+                DVec3::new(9., 18., 12.),
+            )))
+        );
+
+        // M * [1 0; 0 1] + [0 2; 3 0]
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Add {
+                    left: Box::new(AstNode::Multiply {
+                        left: Box::new(AstNode::NamedMatrix(MatrixName::new("M"))),
+                        right: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY))
+                    }),
+                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(0., 3.),
+                        DVec2::new(2., 0.)
+                    )))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(1., 6.),
+                DVec2::new(4., 4.),
+            )))
+        );
+
+        // 3 / 4
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Divide {
+                    left: Box::new(AstNode::Number(3.)),
+                    right: Box::new(AstNode::Number(4.))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Number(0.75)
+        );
+
+        // [1 2; 3 4] / 4
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Divide {
+                    left: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(1., 3.),
+                        DVec2::new(2., 4.)
+                    ))),
+                    right: Box::new(AstNode::Number(4.))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(0.25, 0.75),
+                DVec2::new(0.5, 1.)
+            )))
+        );
+
+        // 2 + (-1)
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Add {
+                    left: Box::new(AstNode::Number(2.)),
+                    right: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Number(1.)
+        );
+
+        // M + (2 * (-M))
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Add {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("M"))),
+                    right: Box::new(AstNode::Multiply {
+                        left: Box::new(AstNode::Number(2.)),
+                        right: Box::new(AstNode::Negate(Box::new(AstNode::NamedMatrix(
+                            MatrixName::new("M")
+                        ))))
+                    })
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(-1., -3.),
+                DVec2::new(-2., -4.)
+            )))
+        );
+
+        // X + (3.5 * (-X))
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Add {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
+                    right: Box::new(AstNode::Multiply {
+                        left: Box::new(AstNode::Number(3.5)),
+                        right: Box::new(AstNode::Negate(Box::new(AstNode::NamedMatrix(
+                            MatrixName::new("X")
+                        ))))
+                    })
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::from_cols(
+                DVec3::new(-2.5, -10., -2.5),
+                DVec3::new(-5., -12.5, -5.),
+                DVec3::new(-7.5, -15., -10.),
+            )))
+        );
+
+        // [1 2; 3 4] ^ T
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(1., 3.),
+                        DVec2::new(2., 4.)
+                    ))),
+                    power: Box::new(AstNode::NamedMatrix(MatrixName::new("T")))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(1., 2.),
+                DVec2::new(3., 4.)
+            )))
+        );
+
+        // [1 2 3; 4 5 6; 7 8 9] ^ T
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                        DVec3::new(1., 4., 7.),
+                        DVec3::new(2., 5., 8.),
+                        DVec3::new(3., 6., 9.),
+                    ))),
+                    power: Box::new(AstNode::NamedMatrix(MatrixName::new("T")))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::from_cols(
+                DVec3::new(1., 2., 3.),
+                DVec3::new(4., 5., 6.),
+                DVec3::new(7., 8., 9.),
+            )))
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_failure() {
+        let map2 = MatrixMap2::new();
+        let map3 = MatrixMap3::new();
+
+        // 3 + [4 -1; -1.5 3]
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Add {
+                    left: Box::new(AstNode::Number(3.)),
+                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(4., -1.5),
+                        DVec2::new(-1., 3.)
+                    )))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotAddNumberAndMatrix)
+        );
+
+        // [1 4 7; 2 5 8; 3 6 9] * [4 -1; 1.5 3]
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                        DVec3::new(1., 2., 3.),
+                        DVec3::new(4., 5., 6.),
+                        DVec3::new(7., 8., 9.)
+                    ))),
+                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(4., -1.5),
+                        DVec2::new(-1., 3.)
+                    )))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotMultiplyDifferentDimensions)
+        );
+
+        // [1 4 7; 2 5 8; 3 6 9] + [4 -1; 1.5 3]
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Add {
+                    left: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                        DVec3::new(1., 2., 3.),
+                        DVec3::new(4., 5., 6.),
+                        DVec3::new(7., 8., 9.)
+                    ))),
+                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(4., -1.5),
+                        DVec2::new(-1., 3.)
+                    )))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotAddDifferentDimensions)
+        );
+
+        // [1 0; 0 1] ^ 1.5
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
+                    power: Box::new(AstNode::Number(1.5))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotRaiseMatrixToNonInteger)
+        );
+
+        // [1 0 0; 0 1 0; 0 0 1] ^ -3.7
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::IDENTITY)),
+                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(3.7))))
+                },
+                &map3
+            ),
+            Err(EvaluationError::CannotRaiseMatrixToNonInteger)
+        );
+
+        // 2 / [1 2; 3 4]
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Divide {
+                    left: Box::new(AstNode::Number(2.)),
+                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(1., 3.),
+                        DVec2::new(2., 4.)
+                    )))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotDivideByMatrix)
+        );
+
+        // [1 0; 0 1] / [1 2; 3 4]
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Divide {
+                    left: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
+                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(1., 3.),
+                        DVec2::new(2., 4.)
+                    )))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotDivideByMatrix)
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::ZERO)),
+                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotInvertSingularMatrix)
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::ZERO)),
+                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                },
+                &map3
+            ),
+            Err(EvaluationError::CannotInvertSingularMatrix)
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Number(2.3)),
+                    power: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
+                },
+                &map2,
+            ),
+            Err(EvaluationError::CannotRaiseToMatrix)
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::IDENTITY)),
+                    power: Box::new(AstNode::Anonymous3dMatrix(DMat3::IDENTITY)),
+                },
+                &map3,
+            ),
+            Err(EvaluationError::CannotRaiseToMatrix)
+        );
+
+        // (1 + 2) ^ T
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Add {
+                        left: Box::new(AstNode::Number(1.)),
+                        right: Box::new(AstNode::Number(2.))
+                    }),
+                    power: Box::new(AstNode::NamedMatrix(MatrixName::new("T")))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotTransposeNumber)
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_vectors() {
+        let map2 = MatrixMap2::new();
+        let map3 = MatrixMap3::new();
+
+        // [1 2; 3 4] * [5; 6]
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(1., 3.),
+                        DVec2::new(2., 4.)
+                    ))),
+                    right: Box::new(AstNode::Anonymous2dVector(DVec2::new(5., 6.)))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Vector(Vector2dOr3d::TwoD(DVec2::new(17., 39.)))
+        );
 
-            // This is utterly bizarre, but cargo tarpaulin complains about this if it's formatted
-            // nicely (ie. across multiple lines). Either we have this one ugly line here, or code
-            // coverage takes a needless hit.
-            #[rustfmt::skip]
-            Self::Anonymous3dMatrix(DMat3 { x_axis, y_axis, z_axis }) => format!("[{} {} {}; {} {} {}; {} {} {}]", x_axis.x, y_axis.x, z_axis.x, x_axis.y, y_axis.y, z_axis.y, x_axis.z, y_axis.z, z_axis.z),
-        }
-    }
+        // dot([1; 2; 3], [4; -5; 6])
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::DotProduct {
+                    left: Box::new(AstNode::Anonymous3dVector(DVec3::new(1., 2., 3.))),
+                    right: Box::new(AstNode::Anonymous3dVector(DVec3::new(4., -5., 6.)))
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Number(12.)
+        );
 
-    /// Get all the named matrices that are referenced in this AST.
-    pub fn named_matrices(&self) -> Vec<MatrixName> {
-        match self {
-            Self::Multiply { left, right } => left
-                .named_matrices()
-                .into_iter()
-                .chain(right.named_matrices())
-                .collect(),
-            Self::Divide { left, right } => left
-                .named_matrices()
-                .into_iter()
-                .chain(right.named_matrices())
-                .collect(),
-            Self::Add { left, right } => left
-                .named_matrices()
-                .into_iter()
-                .chain(right.named_matrices())
-                .collect(),
-            Self::Negate(term) => term.named_matrices(),
-            Self::Exponent { base, power } => {
-                if **power == AstNode::NamedMatrix(MatrixName::new("T")) {
-                    base.named_matrices()
-                } else {
-                    base.named_matrices()
-                        .into_iter()
-                        .chain(power.named_matrices())
-                        .collect()
-                }
-            }
-            Self::Number(_) => vec![],
-            Self::NamedMatrix(matrix) => vec![matrix.clone()],
-            Self::RotationMatrix { degrees: _ } => vec![],
-            Self::Anonymous2dMatrix(_) => vec![],
-            Self::Anonymous3dMatrix(_) => vec![],
-        }
-    }
-}
+        // cross([1; 0; 0], [0; 1; 0])
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::CrossProduct {
+                    left: Box::new(AstNode::Anonymous3dVector(DVec3::X)),
+                    right: Box::new(AstNode::Anonymous3dVector(DVec3::Y))
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Vector(Vector2dOr3d::ThreeD(DVec3::Z))
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use approx::{assert_relative_eq, AbsDiffEq, RelativeEq};
-    use glam::{DVec2, DVec3};
-    use std::f64::consts::FRAC_1_SQRT_2;
+        // cross([1; 2], [3; 4])
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::CrossProduct {
+                    left: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.))),
+                    right: Box::new(AstNode::Anonymous2dVector(DVec2::new(3., 4.)))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Number(-2.)
+        );
 
-    impl AbsDiffEq for NumberOrMatrix {
-        type Epsilon = <f64 as AbsDiffEq>::Epsilon;
+        // [1; 2] / 2 + (-[1; 1])
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Add {
+                    left: Box::new(AstNode::Divide {
+                        left: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.))),
+                        right: Box::new(AstNode::Number(2.))
+                    }),
+                    right: Box::new(AstNode::Negate(Box::new(AstNode::Anonymous2dVector(
+                        DVec2::ONE
+                    ))))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Vector(Vector2dOr3d::TwoD(DVec2::new(-0.5, 0.)))
+        );
 
-        fn default_epsilon() -> Self::Epsilon {
-            <f64 as AbsDiffEq>::default_epsilon()
-        }
+        // dot([1; 2], [1; 2; 3])
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::DotProduct {
+                    left: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.))),
+                    right: Box::new(AstNode::Anonymous3dVector(DVec3::new(1., 2., 3.)))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotDotDifferentDimensions)
+        );
 
-        fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
-            match (self, other) {
-                (Self::Number(a), Self::Number(b)) => a.abs_diff_eq(b, epsilon),
-                (Self::Matrix(a), Self::Matrix(b)) => a.abs_diff_eq(b, epsilon),
-                _ => false,
-            }
-        }
-    }
+        // dot(2, [1; 2])
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::DotProduct {
+                    left: Box::new(AstNode::Number(2.)),
+                    right: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.)))
+                },
+                &map2
+            ),
+            Err(EvaluationError::DotProductRequiresVectors)
+        );
 
-    impl RelativeEq for NumberOrMatrix {
-        fn default_max_relative() -> Self::Epsilon {
-            <f64 as RelativeEq>::default_max_relative()
-        }
+        // cross([1; 2], [1; 2; 3])
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::CrossProduct {
+                    left: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.))),
+                    right: Box::new(AstNode::Anonymous3dVector(DVec3::new(1., 2., 3.)))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotCrossDifferentDimensions)
+        );
 
-        fn relative_eq(
-            &self,
-            other: &Self,
-            epsilon: Self::Epsilon,
-            max_relative: Self::Epsilon,
-        ) -> bool {
-            match (self, other) {
-                (Self::Number(a), Self::Number(b)) => a.relative_eq(b, epsilon, max_relative),
-                (Self::Matrix(a), Self::Matrix(b)) => a.relative_eq(b, epsilon, max_relative),
-                _ => false,
-            }
-        }
+        // cross([1 0; 0 1], [1; 2])
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::CrossProduct {
+                    left: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
+                    right: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.)))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CrossProductRequiresVectors)
+        );
+
+        // [1; 2] * [1 0; 0 1]
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.))),
+                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY))
+                },
+                &map2
+            ),
+            Err(EvaluationError::CannotMultiplyVectorByMatrix)
+        );
+
+        // [1 0 0; 0 1 0; 0 0 1] * [1; 2]
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::Anonymous3dMatrix(DMat3::IDENTITY)),
+                    right: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.)))
+                },
+                &map3
+            ),
+            Err(EvaluationError::CannotMultiplyDifferentDimensions)
+        );
     }
 
     #[test]
-    fn ast_node_evaluation_success() {
+    fn ast_node_evaluation_index() {
         let mut map2 = MatrixMap2::new();
-        let mut map3 = MatrixMap3::new();
+        let map3 = MatrixMap3::new();
 
-        // 10
+        map2.set(
+            MatrixName::new("A"),
+            DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., 4.)),
+        )
+        .expect("Should be able to set 2D matrix A");
+
+        // A[1, 2] * A
         assert_relative_eq!(
-            AstNode::evaluate(AstNode::Number(10.), &map2).unwrap(),
-            NumberOrMatrix::Number(10.)
+            AstNode::evaluate(
+                AstNode::Multiply {
+                    left: Box::new(AstNode::Index {
+                        matrix: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                        row: 1,
+                        column: 2
+                    }),
+                    right: Box::new(AstNode::NamedMatrix(MatrixName::new("A")))
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(2., 6.),
+                DVec2::new(4., 8.)
+            )))
         );
 
-        // 3.2 * 5
+        // [1 2 3; 4 5 6; 7 8 9][3, 1]
         assert_relative_eq!(
             AstNode::evaluate(
-                AstNode::Multiply {
-                    left: Box::new(AstNode::Number(3.2)),
-                    right: Box::new(AstNode::Number(5.))
+                AstNode::Index {
+                    matrix: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                        DVec3::new(1., 4., 7.),
+                        DVec3::new(2., 5., 8.),
+                        DVec3::new(3., 6., 9.),
+                    ))),
+                    row: 3,
+                    column: 1
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Number(7.)
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Index {
+                    matrix: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                    row: 3,
+                    column: 1
+                },
+                &map2
+            ),
+            Err(EvaluationError::IndexOutOfBounds {
+                row: 3,
+                column: 1,
+                dimension: 2
+            })
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Index {
+                    matrix: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                    row: 1,
+                    column: 0
+                },
+                &map2
+            ),
+            Err(EvaluationError::IndexOutOfBounds {
+                row: 1,
+                column: 0,
+                dimension: 2
+            })
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Index {
+                    matrix: Box::new(AstNode::Number(1.)),
+                    row: 1,
+                    column: 1
                 },
                 &map2
+            ),
+            Err(EvaluationError::CannotIndexNonMatrix)
+        );
+
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::Index {
+                matrix: Box::new(AstNode::Multiply {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                    right: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+                }),
+                row: 2,
+                column: 1
+            }),
+            "(A * B)[2, 1]"
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_row_column() {
+        let map3 = MatrixMap3::new();
+        let matrix = AstNode::Anonymous3dMatrix(DMat3::from_cols(
+            DVec3::new(1., 4., 7.),
+            DVec3::new(2., 5., 8.),
+            DVec3::new(3., 6., 9.),
+        ));
+
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Row {
+                    matrix: Box::new(matrix.clone()),
+                    index: Box::new(AstNode::Number(2.))
+                },
+                &map3
             )
             .unwrap(),
-            NumberOrMatrix::Number(16.)
+            NumberOrMatrix::Vector(Vector2dOr3d::ThreeD(DVec3::new(4., 5., 6.)))
         );
 
-        // 1 + 2
         assert_relative_eq!(
             AstNode::evaluate(
-                AstNode::Add {
-                    left: Box::new(AstNode::Number(1.)),
-                    right: Box::new(AstNode::Number(2.))
+                AstNode::Column {
+                    matrix: Box::new(matrix.clone()),
+                    index: Box::new(AstNode::Add {
+                        left: Box::new(AstNode::Number(1.)),
+                        right: Box::new(AstNode::Number(2.))
+                    })
+                },
+                &map3
+            )
+            .unwrap(),
+            NumberOrMatrix::Vector(Vector2dOr3d::ThreeD(DVec3::new(3., 6., 9.)))
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Row {
+                    matrix: Box::new(matrix.clone()),
+                    index: Box::new(AstNode::Number(4.))
+                },
+                &map3
+            ),
+            Err(EvaluationError::RowOutOfBounds {
+                row: 4,
+                dimension: 3
+            })
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Column {
+                    matrix: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
+                    index: Box::new(AstNode::Number(3.))
+                },
+                &map3
+            ),
+            Err(EvaluationError::ColumnOutOfBounds {
+                column: 3,
+                dimension: 2
+            })
+        );
+
+        for index in [0., -1., 1.5] {
+            assert_eq!(
+                AstNode::evaluate(
+                    AstNode::Column {
+                        matrix: Box::new(matrix.clone()),
+                        index: Box::new(AstNode::Number(index))
+                    },
+                    &map3
+                ),
+                Err(EvaluationError::IndexMustBePositiveInteger)
+            );
+        }
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Row {
+                    matrix: Box::new(AstNode::Anonymous2dVector(DVec2::X)),
+                    index: Box::new(AstNode::Number(1.))
+                },
+                &map3
+            ),
+            Err(EvaluationError::CannotExtractFromNonMatrix)
+        );
+
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::Column {
+                matrix: Box::new(matrix),
+                index: Box::new(AstNode::Number(1.))
+            }),
+            "col([1 2 3; 4 5 6; 7 8 9], 1)"
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_augment_block() {
+        let map2 = MatrixMap2::new();
+
+        // aug([1; 3], [2; 4])
+        assert_relative_eq!(
+            AstNode::evaluate(
+                AstNode::Augment {
+                    columns: vec![
+                        AstNode::Anonymous2dVector(DVec2::new(1., 3.)),
+                        AstNode::Anonymous2dVector(DVec2::new(2., 4.)),
+                    ]
                 },
                 &map2
             )
             .unwrap(),
-            NumberOrMatrix::Number(3.)
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols(
+                DVec2::new(1., 3.),
+                DVec2::new(2., 4.)
+            )))
         );
 
-        // 3 * [2 -2.2; 1.5 10]
+        // aug([1; 0; 0], [0; 1; 0], [0; 0; 1])
         assert_relative_eq!(
             AstNode::evaluate(
-                AstNode::Multiply {
-                    left: Box::new(AstNode::Number(3.)),
-                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(2., 1.5),
-                        DVec2::new(-2.2, 10.)
-                    )))
+                AstNode::Augment {
+                    columns: vec![
+                        AstNode::Anonymous3dVector(DVec3::X),
+                        AstNode::Anonymous3dVector(DVec3::Y),
+                        AstNode::Anonymous3dVector(DVec3::Z),
+                    ]
+                },
+                &map2
+            )
+            .unwrap(),
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::IDENTITY))
+        );
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Augment {
+                    columns: vec![
+                        AstNode::Anonymous3dVector(DVec3::X),
+                        AstNode::Anonymous3dVector(DVec3::Y),
+                    ]
                 },
                 &map2
-            )
-            .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(DMat2::from_cols(
-                DVec2::new(6., 4.5),
-                DVec2::new(-6.6, 30.)
-            )))
+            ),
+            Err(EvaluationError::AugmentWrongNumberOfColumns {
+                columns: 2,
+                dimension: 3
+            })
         );
 
-        // [1 -2.34 2.3; 2.5 0 -0.5; 3.1 0.5 9.2] * ((1.2 + 2.3) * [2.3 -1.2 -3; 1.4 3 1; -3.2 -6.3 2.22])
-        assert_relative_eq!(
+        assert_eq!(
             AstNode::evaluate(
-                AstNode::Multiply {
-                    left: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
-                        DVec3::new(1., 2.5, 3.1),
-                        DVec3::new(-2.34, 0., 0.5),
-                        DVec3::new(2.3, -0.5, 9.2)
-                    ))),
-                    right: Box::new(AstNode::Multiply {
-                        left: Box::new(AstNode::Add {
-                            left: Box::new(AstNode::Number(1.2)),
-                            right: Box::new(AstNode::Number(2.3))
-                        }),
-                        right: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
-                            DVec3::new(2.3, 1.4, -3.2),
-                            DVec3::new(-1.2, 3., -6.3),
-                            DVec3::new(-3., 1., 2.22)
-                        )))
-                    })
+                AstNode::Augment {
+                    columns: vec![
+                        AstNode::Anonymous2dVector(DVec2::X),
+                        AstNode::Anonymous3dVector(DVec3::Y),
+                    ]
                 },
-                &map3
-            )
-            .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::ThreeD(DMat3::from_cols(
-                DVec3::new(-29.176, 25.725, -75.635),
-                DVec3::new(-79.485, 0.525, -210.63),
-                DVec3::new(-0.819, -30.135, 40.684)
-            ))),
-            epsilon = 0.00000000000001
+                &map2
+            ),
+            Err(EvaluationError::CannotAugmentDifferentDimensions)
         );
 
-        // rot(45)
-        assert_relative_eq!(
-            AstNode::evaluate(AstNode::RotationMatrix { degrees: 45. }, &map2).unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(DMat2::from_cols(
-                DVec2::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2),
-                DVec2::new(-FRAC_1_SQRT_2, FRAC_1_SQRT_2)
-            )))
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Augment {
+                    columns: vec![
+                        AstNode::Anonymous2dMatrix(DMat2::IDENTITY),
+                        AstNode::Anonymous2dVector(DVec2::Y),
+                    ]
+                },
+                &map2
+            ),
+            Err(EvaluationError::AugmentRequiresVectors)
         );
 
-        // [1 2; 3 2] ^ (1 + 2)
+        // block([1 2; 3 4], [5; 6]; [7; 8], 9)
         assert_relative_eq!(
             AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                AstNode::Block {
+                    top_left: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
                         DVec2::new(1., 3.),
-                        DVec2::new(2., 2.)
+                        DVec2::new(2., 4.)
                     ))),
-                    power: Box::new(AstNode::Add {
-                        left: Box::new(AstNode::Number(1.)),
-                        right: Box::new(AstNode::Number(2.))
-                    })
+                    top_right: Box::new(AstNode::Anonymous2dVector(DVec2::new(5., 6.))),
+                    bottom_left: Box::new(AstNode::Anonymous2dVector(DVec2::new(7., 8.))),
+                    bottom_right: Box::new(AstNode::Number(9.)),
                 },
                 &map2
             )
             .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(DMat2::from_cols(
-                DVec2::new(25., 39.),
-                DVec2::new(26., 38.)
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::from_cols(
+                DVec3::new(1., 3., 7.),
+                DVec3::new(2., 4., 8.),
+                DVec3::new(5., 6., 9.),
             )))
         );
 
-        // [1 2; 3 4] ^ -1
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Block {
+                    top_left: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
+                    top_right: Box::new(AstNode::Anonymous2dVector(DVec2::X)),
+                    bottom_left: Box::new(AstNode::Number(0.)),
+                    bottom_right: Box::new(AstNode::Number(1.)),
+                },
+                &map2
+            ),
+            Err(EvaluationError::InvalidBlockMatrix)
+        );
+
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::Block {
+                top_left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                top_right: Box::new(AstNode::Augment {
+                    columns: vec![AstNode::NamedMatrix(MatrixName::new("U"))]
+                }),
+                bottom_left: Box::new(AstNode::NamedMatrix(MatrixName::new("V"))),
+                bottom_right: Box::new(AstNode::Number(1.)),
+            }),
+            "block(A, aug(U); V, 1)"
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_solve() {
+        let map2 = MatrixMap2::new();
+
+        // solve([2 1; 1 3], [3; 5])
         assert_relative_eq!(
             AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(1., 3.),
-                        DVec2::new(2., 4.)
+                AstNode::Solve {
+                    matrix: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                        DVec2::new(2., 1.),
+                        DVec2::new(1., 3.)
                     ))),
-                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                    vector: Box::new(AstNode::Anonymous2dVector(DVec2::new(3., 5.)))
                 },
                 &map2
             )
             .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(DMat2::from_cols(
-                DVec2::new(-2., 1.5),
-                DVec2::new(1., -0.5)
-            )))
+            NumberOrMatrix::Vector(Vector2dOr3d::TwoD(DVec2::new(0.8, 1.4)))
         );
 
-        // [1 2 3; 4 5 6; 1 2 4] ^ -1
+        // solve([1 2 3; 4 5 6; 1 2 4], [6; 15; 7])
         assert_relative_eq!(
             AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                AstNode::Solve {
+                    matrix: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
                         DVec3::new(1., 4., 1.),
                         DVec3::new(2., 5., 2.),
                         DVec3::new(3., 6., 4.),
                     ))),
-                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                    vector: Box::new(AstNode::Anonymous3dVector(DVec3::new(6., 15., 7.)))
                 },
-                &map3
+                &map2
             )
             .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::ThreeD(DMat3::from_cols(
-                DVec3::new(-(2. + 2. / 3.), 3. + 1. / 3., -1.),
-                DVec3::new(2. / 3., -1. / 3., 0.),
-                DVec3::new(1., -2., 1.),
-            )))
+            NumberOrMatrix::Vector(Vector2dOr3d::ThreeD(DVec3::ONE)),
+            epsilon = 0.0000000001
         );
 
-        map2.set(
-            MatrixName::new("M"),
-            DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., 4.)),
-        )
-        .expect("Should be able to set 2D matrix M");
-
-        map3.set(
-            MatrixName::new("X"),
-            DMat3::from_cols(
-                DVec3::new(1., 4., 1.),
-                DVec3::new(2., 5., 2.),
-                DVec3::new(3., 6., 4.),
-            ),
-        )
-        .expect("Should be able to set 3D matrix X");
-
-        // M * 3
-        assert_relative_eq!(
+        assert_eq!(
             AstNode::evaluate(
-                AstNode::Multiply {
-                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("M"))),
-                    right: Box::new(AstNode::Number(3.))
+                AstNode::Solve {
+                    matrix: Box::new(AstNode::Anonymous2dMatrix(DMat2::ZERO)),
+                    vector: Box::new(AstNode::Anonymous2dVector(DVec2::ONE))
                 },
                 &map2
-            )
-            .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(DMat2::from_cols(
-                DVec2::new(3., 9.),
-                DVec2::new(6., 12.)
-            )))
+            ),
+            Err(EvaluationError::CannotSolveSingularSystem)
         );
 
-        // X ^ (2 ^ {1 + 1})
-        assert_relative_eq!(
+        assert_eq!(
             AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
-                    power: Box::new(AstNode::Exponent {
-                        base: Box::new(AstNode::Number(2.)),
-                        power: Box::new(AstNode::Add {
-                            left: Box::new(AstNode::Number(1.)),
-                            right: Box::new(AstNode::Number(1.))
-                        })
-                    })
+                AstNode::Solve {
+                    matrix: Box::new(AstNode::Anonymous3dMatrix(DMat3::IDENTITY)),
+                    vector: Box::new(AstNode::Anonymous2dVector(DVec2::ONE))
                 },
-                &map3
-            )
-            .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::ThreeD(DMat3::from_cols(
-                DVec3::new(1035., 2568., 1159.),
-                DVec3::new(1566., 3885., 1754.),
-                DVec3::new(2349., 5826., 2632.),
-            )))
+                &map2
+            ),
+            Err(EvaluationError::CannotSolveDifferentDimensions)
         );
 
-        // X * (X + X)
-        assert_relative_eq!(
+        assert_eq!(
             AstNode::evaluate(
-                AstNode::Multiply {
-                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
-                    right: Box::new(AstNode::Add {
-                        left: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
-                        right: Box::new(AstNode::NamedMatrix(MatrixName::new("X")))
-                    })
+                AstNode::Solve {
+                    matrix: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
+                    vector: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY))
                 },
-                &map3
-            )
-            .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::ThreeD(DMat3::from_cols(
-                DVec3::new(24., 60., 26.),
-                DVec3::new(36., 90., 40.),
-                DVec3::new(54., 132., 62.),
-            )))
+                &map2
+            ),
+            Err(EvaluationError::SolveRequiresMatrixAndVector)
         );
+    }
 
-        // X * (1 + 2)
+    #[test]
+    fn ast_node_evaluation_norm() {
+        let mut map2 = MatrixMap2::new();
+
+        // norm([3; 4])
         assert_relative_eq!(
             AstNode::evaluate(
-                AstNode::Multiply {
-                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
-                    right: Box::new(AstNode::Add {
-                        left: Box::new(AstNode::Number(1.)),
-                        right: Box::new(AstNode::Number(2.))
-                    })
-                },
-                &map3
+                AstNode::Norm(Box::new(AstNode::Anonymous2dVector(DVec2::new(3., 4.)))),
+                &map2
             )
             .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::ThreeD(DMat3::from_cols(
-                DVec3::new(3., 12., 3.),
-                DVec3::new(6., 15., 6.),
-                DVec3::new(9., 18., 12.),
-            )))
+            NumberOrMatrix::Number(5.)
         );
 
-        // M * [1 0; 0 1] + [0 2; 3 0]
+        // norm([1 2; 3 4])
         assert_relative_eq!(
             AstNode::evaluate(
-                AstNode::Add {
-                    left: Box::new(AstNode::Multiply {
-                        left: Box::new(AstNode::NamedMatrix(MatrixName::new("M"))),
-                        right: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY))
-                    }),
-                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(0., 3.),
-                        DVec2::new(2., 0.)
-                    )))
-                },
+                AstNode::Norm(Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                    DVec2::new(1., 3.),
+                    DVec2::new(2., 4.)
+                )))),
                 &map2
             )
             .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(DMat2::from_cols(
-                DVec2::new(1., 6.),
-                DVec2::new(4., 4.),
+            NumberOrMatrix::Number(30f64.sqrt())
+        );
+
+        assert_eq!(
+            AstNode::evaluate(AstNode::Norm(Box::new(AstNode::Number(2.))), &map2),
+            Err(EvaluationError::NormRequiresVectorOrMatrix)
+        );
+
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::Norm(Box::new(AstNode::Add {
+                left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                right: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+            }))),
+            "norm(A + B)"
+        );
+
+        map2.set(
+            MatrixName::new("M"),
+            DMat2::from_cols_array(&[3., 0., 0., -4.]),
+        )
+        .unwrap();
+        let evaluate = |expression: &str| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map2)
+        };
+        for (expression, expected) in [
+            ("norm(M, \"fro\")", 5.),
+            ("norm(M, \"spectral\")", 4.),
+            ("norm(M, \"max\")", 4.),
+            ("norm(i * M, \"fro\")", 5.),
+            (
+                "norm([2 0 0 0; 0 1 0 0; 0 0 3 0; 0 0 0 1], \"spectral\")",
+                3.,
+            ),
+        ] {
+            let Ok(NumberOrMatrix::Number(norm)) = evaluate(expression) else {
+                panic!("{expression} should evaluate to a number");
+            };
+            assert!(
+                norm.relative_eq(&expected, EPSILON, EPSILON),
+                "{expression}"
+            );
+        }
+        assert_eq!(
+            evaluate("norm([3; 4], \"max\")"),
+            Err(EvaluationError::MatrixNormRequiresMatrix)
+        );
+        assert_eq!(
+            evaluate("norm(i * M, \"max\")"),
+            Err(EvaluationError::UnsupportedComplexMatrix)
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_pseudo_inverse() {
+        let map = MatrixMap2::new();
+        let evaluate = |expression: &str| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map)
+        };
+
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(pinv))) = evaluate("pinv([1 2; 2 4])")
+        else {
+            panic!("The pseudoinverse of a 2D matrix should be a 2D matrix");
+        };
+        assert_relative_eq!(
+            pinv,
+            DMat2::from_cols_array(&[1., 2., 2., 4.]) / 25.,
+            epsilon = EPSILON
+        );
+
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::ThreeD(product))) =
+            evaluate("pinv([1 2 3; 0 1 4; 5 6 0]) * [1 2 3; 0 1 4; 5 6 0]")
+        else {
+            panic!("The pseudoinverse of a 3D matrix should be a 3D matrix");
+        };
+        assert_relative_eq!(product, DMat3::IDENTITY, epsilon = 0.000001);
+
+        assert_eq!(
+            evaluate("pinv([1; 2])"),
+            Err(EvaluationError::PseudoInverseRequiresMatrix)
+        );
+        assert_eq!(
+            evaluate("pinv(i * [1 2; 3 4])"),
+            Err(EvaluationError::UnsupportedComplexMatrix)
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_projections() {
+        let map = MatrixMap2::new();
+        let evaluate = |expression: &str| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map)
+        };
+
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(projection))) =
+            evaluate("proj_line(90) * [3 1; 4 1]")
+        else {
+            panic!("A line projection should be a 2D matrix");
+        };
+        assert_relative_eq!(
+            projection,
+            DMat2::from_cols_array(&[0., 4., 0., 1.]),
+            epsilon = EPSILON
+        );
+
+        let Ok(NumberOrMatrix::Vector(Vector2dOr3d::ThreeD(projected))) =
+            evaluate("proj_plane([0; 0; 2]) * [1; 2; 3]")
+        else {
+            panic!("A projected 3D vector should be a 3D vector");
+        };
+        assert_relative_eq!(projected, DVec3::new(1., 2., 0.), epsilon = EPSILON);
+
+        assert_eq!(
+            evaluate("proj_line([1; 2])"),
+            Err(EvaluationError::LineProjectionRequiresNumber)
+        );
+        assert_eq!(
+            evaluate("proj_plane([1; 2])"),
+            Err(EvaluationError::PlaneProjectionRequiresVector)
+        );
+        assert_eq!(
+            evaluate("proj_plane([0; 0; 0])"),
+            Err(EvaluationError::CannotProjectOntoNonFinite)
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_least_squares() {
+        let map = MatrixMap2::new();
+        let evaluate = |expression: &str| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map)
+        };
+
+        let Ok(NumberOrMatrix::Vector(Vector2dOr3d::TwoD(solution))) =
+            evaluate("lstsq([1 1; 1 1], [1; 2])")
+        else {
+            panic!("The least-squares solution of a 2D system should be a 2D vector");
+        };
+        assert_relative_eq!(solution, DVec2::new(0.75, 0.75), epsilon = EPSILON);
+
+        let Ok(NumberOrMatrix::Number(residual)) =
+            evaluate("norm([1 1; 1 1] * lstsq([1 1; 1 1], [1; 2]) - [1; 2])")
+        else {
+            panic!("The residual should be a number");
+        };
+        assert_relative_eq!(residual, 0.5f64.sqrt(), epsilon = EPSILON);
+
+        let Ok(NumberOrMatrix::Number(difference)) = evaluate(
+            "norm(lstsq([1 2 3; 4 5 6; 7 8 10], [1; 2; 3]) - solve([1 2 3; 4 5 6; 7 8 10], [1; 2; 3]))",
+        ) else {
+            panic!("The difference should have a norm");
+        };
+        assert_relative_eq!(difference, 0., epsilon = 0.000000001);
+        assert_eq!(
+            evaluate("lstsq([1 2; 3 4], [1; 2; 3])"),
+            Err(EvaluationError::CannotSolveDifferentDimensions)
+        );
+        assert_eq!(
+            evaluate("lstsq([1; 2], [1; 2])"),
+            Err(EvaluationError::SolveRequiresMatrixAndVector)
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_affine_2d() {
+        let map = MatrixMap2::new();
+        let affine = EvalOptions {
+            affine_2d: true,
+            ..EvalOptions::default()
+        };
+        let evaluate = |expression: &str, options: EvalOptions| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate_with_options(&map, options)
+        };
+
+        assert_eq!(
+            evaluate("translate(1, 2) * translate(3, 4)", affine),
+            Ok(NumberOrMatrix::Matrix(MatrixValue::ThreeD(
+                DMat3::from_translation(DVec2::new(4., 6.))
             )))
         );
 
-        // 3 / 4
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::ThreeD(matrix))) = evaluate(
+            "(translate(2, 0) * [0.1 0.2 0; 0.3 0.4 0; 0 0 1])^-1 * translate(2, 0)",
+            affine,
+        ) else {
+            panic!("The result should be a 3D matrix");
+        };
+        assert_eq!(matrix.row(2), DVec3::Z);
+
+        assert_eq!(
+            evaluate("[1 0 0; 0 1 0; 1 0 1]", affine),
+            Err(EvaluationError::NotAffine2d)
+        );
+        assert!(evaluate("[1 0 0; 0 1 0; 1 0 1]", EvalOptions::default()).is_ok());
+        assert_eq!(
+            evaluate("2 * translate(1, 2)", affine),
+            Err(EvaluationError::NotAffine2d)
+        );
+        assert_eq!(
+            evaluate("[1 2; 3 4]", affine),
+            evaluate("[1 2; 3 4]", EvalOptions::default())
+        );
+        assert_eq!(
+            evaluate("translate(1, [1; 2])", affine),
+            Err(EvaluationError::TranslationRequiresNumbers)
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_adjugate_cofactor() {
+        let mut map2 = MatrixMap2::new();
+        map2.set(
+            MatrixName::new("A"),
+            DMat2::from_cols(DVec2::new(1., 3.), DVec2::new(2., 4.)),
+        )
+        .expect("Should be able to set 2D matrix A");
+
+        // adj(A) / (-2) == A ^ -1
         assert_relative_eq!(
             AstNode::evaluate(
                 AstNode::Divide {
-                    left: Box::new(AstNode::Number(3.)),
-                    right: Box::new(AstNode::Number(4.))
+                    left: Box::new(AstNode::Adjugate(Box::new(AstNode::NamedMatrix(
+                        MatrixName::new("A")
+                    )))),
+                    right: Box::new(AstNode::Negate(Box::new(AstNode::Number(2.))))
                 },
                 &map2
             )
             .unwrap(),
-            NumberOrMatrix::Number(0.75)
+            AstNode::evaluate(
+                AstNode::Exponent {
+                    base: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                },
+                &map2
+            )
+            .unwrap()
         );
 
-        // [1 2; 3 4] / 4
+        // cofactor([1 2 3; 0 4 5; 1 0 6], 1, 2)
         assert_relative_eq!(
             AstNode::evaluate(
-                AstNode::Divide {
-                    left: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(1., 3.),
-                        DVec2::new(2., 4.)
+                AstNode::Cofactor {
+                    matrix: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                        DVec3::new(1., 0., 1.),
+                        DVec3::new(2., 4., 0.),
+                        DVec3::new(3., 5., 6.),
                     ))),
-                    right: Box::new(AstNode::Number(4.))
+                    row: Box::new(AstNode::Number(1.)),
+                    column: Box::new(AstNode::Number(2.))
                 },
                 &map2
             )
             .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(DMat2::from_cols(
-                DVec2::new(0.25, 0.75),
-                DVec2::new(0.5, 1.)
-            )))
+            NumberOrMatrix::Number(5.)
         );
 
-        // 2 + (-1)
-        assert_relative_eq!(
+        assert_eq!(
             AstNode::evaluate(
-                AstNode::Add {
-                    left: Box::new(AstNode::Number(2.)),
-                    right: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                AstNode::Cofactor {
+                    matrix: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                    row: Box::new(AstNode::Number(1.)),
+                    column: Box::new(AstNode::Number(3.))
                 },
                 &map2
-            )
-            .unwrap(),
-            NumberOrMatrix::Number(1.)
+            ),
+            Err(EvaluationError::IndexOutOfBounds {
+                row: 1,
+                column: 3,
+                dimension: 2
+            })
         );
 
-        // M + (2 * (-M))
-        assert_relative_eq!(
+        assert_eq!(
             AstNode::evaluate(
-                AstNode::Add {
-                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("M"))),
-                    right: Box::new(AstNode::Multiply {
-                        left: Box::new(AstNode::Number(2.)),
-                        right: Box::new(AstNode::Negate(Box::new(AstNode::NamedMatrix(
-                            MatrixName::new("M")
-                        ))))
-                    })
+                AstNode::Adjugate(Box::new(AstNode::Anonymous2dVector(DVec2::ONE))),
+                &map2
+            ),
+            Err(EvaluationError::AdjugateRequiresMatrix)
+        );
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Cofactor {
+                    matrix: Box::new(AstNode::Anonymous2dVector(DVec2::ONE)),
+                    row: Box::new(AstNode::Number(1.)),
+                    column: Box::new(AstNode::Number(1.))
                 },
                 &map2
-            )
-            .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(DMat2::from_cols(
-                DVec2::new(-1., -3.),
-                DVec2::new(-2., -4.)
-            )))
+            ),
+            Err(EvaluationError::CofactorRequiresMatrix)
         );
 
-        // X + (3.5 * (-X))
-        assert_relative_eq!(
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::Cofactor {
+                matrix: Box::new(AstNode::Adjugate(Box::new(AstNode::NamedMatrix(
+                    MatrixName::new("A")
+                )))),
+                row: Box::new(AstNode::Number(1.)),
+                column: Box::new(AstNode::Number(2.))
+            }),
+            "cofactor(adj(A), 1, 2)"
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_rank() {
+        let map3 = MatrixMap3::new();
+
+        // rank([1 2 3; 4 5 6; 7 8 9])
+        assert_eq!(
             AstNode::evaluate(
-                AstNode::Add {
-                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("X"))),
-                    right: Box::new(AstNode::Multiply {
-                        left: Box::new(AstNode::Number(3.5)),
-                        right: Box::new(AstNode::Negate(Box::new(AstNode::NamedMatrix(
-                            MatrixName::new("X")
-                        ))))
-                    })
-                },
+                AstNode::Rank(Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                    DVec3::new(1., 4., 7.),
+                    DVec3::new(2., 5., 8.),
+                    DVec3::new(3., 6., 9.),
+                )))),
                 &map3
-            )
-            .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::ThreeD(DMat3::from_cols(
-                DVec3::new(-2.5, -10., -2.5),
-                DVec3::new(-5., -12.5, -5.),
-                DVec3::new(-7.5, -15., -10.),
-            )))
+            ),
+            Ok(NumberOrMatrix::Number(2.))
         );
 
-        // [1 2; 3 4] ^ T
-        assert_relative_eq!(
+        // rank(rot(30))
+        assert_eq!(
             AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(1., 3.),
-                        DVec2::new(2., 4.)
-                    ))),
-                    power: Box::new(AstNode::NamedMatrix(MatrixName::new("T")))
-                },
-                &map2
-            )
-            .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::TwoD(DMat2::from_cols(
-                DVec2::new(1., 2.),
-                DVec2::new(3., 4.)
-            )))
+                AstNode::Rank(Box::new(AstNode::RotationMatrix { degrees: 30. })),
+                &map3
+            ),
+            Ok(NumberOrMatrix::Number(2.))
         );
 
-        // [1 2 3; 4 5 6; 7 8 9] ^ T
-        assert_relative_eq!(
+        assert_eq!(
             AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
-                        DVec3::new(1., 4., 7.),
-                        DVec3::new(2., 5., 8.),
-                        DVec3::new(3., 6., 9.),
-                    ))),
-                    power: Box::new(AstNode::NamedMatrix(MatrixName::new("T")))
-                },
+                AstNode::Rank(Box::new(AstNode::Anonymous3dVector(DVec3::ONE))),
+                &map3
+            ),
+            Err(EvaluationError::RankRequiresMatrix)
+        );
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Rank(Box::new(AstNode::Anonymous2dMatrix(
+                    DMat2::IDENTITY * f64::NAN
+                ))),
+                &map3
+            ),
+            Err(EvaluationError::RankRequiresFiniteMatrix)
+        );
+
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::Rank(Box::new(AstNode::NamedMatrix(
+                MatrixName::new("M")
+            )))),
+            "rank(M)"
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_exponential() {
+        let map2 = MatrixMap2::new();
+
+        // exp(1)
+        assert_eq!(
+            AstNode::evaluate(AstNode::Exponential(Box::new(AstNode::Number(1.))), &map2),
+            Ok(NumberOrMatrix::Number(std::f64::consts::E))
+        );
+
+        // exp(2 * [0 -1; 1 0]) is a rotation by 2 radians
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(matrix))) = AstNode::evaluate(
+            AstNode::Exponential(Box::new(AstNode::Multiply {
+                left: Box::new(AstNode::Number(2.)),
+                right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                    DVec2::new(0., 1.),
+                    DVec2::new(-1., 0.),
+                ))),
+            })),
+            &map2,
+        ) else {
+            panic!("The exponential of a 2D matrix should be a 2D matrix");
+        };
+        assert!(matrix.abs_diff_eq(DMat2::from_angle(2.), 0.000000001));
+
+        assert_eq!(
+            AstNode::evaluate(
+                AstNode::Exponential(Box::new(AstNode::Anonymous2dVector(DVec2::ONE))),
                 &map2
-            )
-            .unwrap(),
-            NumberOrMatrix::Matrix(Matrix2dOr3d::ThreeD(DMat3::from_cols(
-                DVec3::new(1., 2., 3.),
-                DVec3::new(4., 5., 6.),
-                DVec3::new(7., 8., 9.),
+            ),
+            Err(EvaluationError::ExponentialRequiresNumberOrMatrix)
+        );
+        assert_eq!(
+            parse_expression_from_string("exp([1e300 0; 0 1])")
+                .unwrap()
+                .evaluate(&map2),
+            Err(EvaluationError::ExponentialNotFinite)
+        );
+
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::Exponential(Box::new(AstNode::NamedMatrix(
+                MatrixName::new("M")
+            )))),
+            "exp(M)"
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_logarithm() {
+        let map2 = MatrixMap2::new();
+        let log = |node| AstNode::evaluate(AstNode::Logarithm(Box::new(node)), &map2);
+
+        assert_eq!(log(AstNode::Number(1.)), Ok(NumberOrMatrix::Number(0.)));
+        assert_eq!(
+            log(AstNode::Negate(Box::new(AstNode::Number(1.)))),
+            Err(EvaluationError::LogarithmUndefined)
+        );
+
+        // log(rot(90)) is the generator of rotations, scaled by a quarter turn
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(matrix))) =
+            log(AstNode::RotationMatrix { degrees: 90. })
+        else {
+            panic!("The logarithm of a 2D matrix should be a 2D matrix");
+        };
+        assert!(matrix.abs_diff_eq(
+            DMat2::from_cols(DVec2::new(0., 1.), DVec2::new(-1., 0.)) * std::f64::consts::FRAC_PI_2,
+            0.000000001
+        ));
+
+        assert_eq!(
+            log(AstNode::RotationMatrix { degrees: 180. }),
+            Err(EvaluationError::LogarithmUndefined)
+        );
+        assert_eq!(
+            log(AstNode::Anonymous2dVector(DVec2::ONE)),
+            Err(EvaluationError::LogarithmRequiresNumberOrMatrix)
+        );
+
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::Logarithm(Box::new(AstNode::Exponential(
+                Box::new(AstNode::NamedMatrix(MatrixName::new("M")))
+            )))),
+            "logm(exp(M))"
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_mixed_dimensions() {
+        let mut map = MatrixMapN::new();
+        map.set(MatrixName::new("A"), DMat2::IDENTITY.into())
+            .unwrap();
+        map.set(MatrixName::new("B"), DMat3::IDENTITY.into())
+            .unwrap();
+
+        let evaluate = |expression: &str| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map)
+        };
+
+        assert_eq!(
+            evaluate("A * 2 + A"),
+            Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(
+                DMat2::IDENTITY * 3.
+            )))
+        );
+        assert_eq!(
+            evaluate("B ^ T"),
+            Ok(NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::IDENTITY)))
+        );
+        assert_eq!(
+            evaluate("A + B"),
+            Err(EvaluationError::CannotAddDifferentDimensions)
+        );
+        assert_eq!(
+            evaluate("B * A"),
+            Err(EvaluationError::CannotMultiplyDifferentDimensions)
+        );
+        assert_eq!(
+            evaluate("A * [1; 2; 3]"),
+            Err(EvaluationError::CannotMultiplyDifferentDimensions)
+        );
+        assert_eq!(
+            parse_expression_from_string("A + B")
+                .unwrap()
+                .infer_shape(&map),
+            Err(EvaluationError::CannotAddDifferentDimensions)
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_dynamic() {
+        use crate::matrix::expression::parse_expression_from_string;
+
+        let matrix = DMatN::from_rows(&[
+            vec![1., 2., 0., 0.],
+            vec![0., 1., 3., 0.],
+            vec![0., 0., 1., 4.],
+            vec![0., 0., 0., 1.],
+        ])
+        .unwrap();
+        let mut map = MatrixMapN::new();
+        map.set(MatrixName::new("M"), matrix.clone().into())
+            .unwrap();
+        map.set(MatrixName::new("A"), DMat2::IDENTITY.into())
+            .unwrap();
+
+        let evaluate = |expression| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map)
+        };
+        let dynamic = |matrix| Ok(NumberOrMatrix::Matrix(MatrixValue::Dynamic(matrix)));
+
+        assert_eq!(evaluate("M"), dynamic(matrix.clone()));
+        assert_eq!(evaluate("M ^ {-1} * M"), dynamic(DMatN::identity(4)));
+        assert_eq!(
+            evaluate("M ^ 0 + [1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1]"),
+            dynamic(DMatN::identity(4) * 2.)
+        );
+        assert_eq!(evaluate("-M / 2"), dynamic(matrix.clone() * -0.5));
+        assert_eq!(evaluate("M ^ T"), dynamic(matrix.transpose()));
+        assert_eq!(
+            evaluate("M[3, 4] + (M ^ 2)[1, 3]"),
+            Ok(NumberOrMatrix::Number(10.))
+        );
+        assert_eq!(
+            evaluate("norm(M)"),
+            Ok(NumberOrMatrix::Number(33f64.sqrt()))
+        );
+        assert_eq!(
+            evaluate("A * 2"),
+            Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(
+                DMat2::IDENTITY * 2.
             )))
         );
+
+        assert_eq!(
+            evaluate("M[5, 1]"),
+            Err(EvaluationError::IndexOutOfBounds {
+                row: 5,
+                column: 1,
+                dimension: 4
+            })
+        );
+        assert_eq!(
+            evaluate("M + A"),
+            Err(EvaluationError::CannotAddDifferentDimensions)
+        );
+        assert_eq!(
+            evaluate("M * [1; 2]"),
+            Err(EvaluationError::CannotMultiplyDifferentDimensions)
+        );
+        assert_eq!(
+            evaluate("(M - M) ^ {-1}"),
+            Err(EvaluationError::CannotInvertSingularMatrix)
+        );
+        for expression in [
+            "rank(M)",
+            "adj(M)",
+            "exp(M)",
+            "logm(M)",
+            "log(M)",
+            "row(M, 1)",
+            "solve(M, [1; 2])",
+        ] {
+            assert_eq!(
+                evaluate(expression),
+                Err(EvaluationError::UnsupportedDimension { dimension: 4 }),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn ast_node_evaluation_complex() {
+        use crate::matrix::expression::parse_expression_from_string;
+
+        let mut map = MatrixMap2::new();
+        map.set(
+            MatrixName::new("A"),
+            DMat2::from_cols(DVec2::new(0., 1.), DVec2::new(-1., 0.)),
+        )
+        .unwrap();
+
+        let evaluate = |expression| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map)
+        };
+        let complex = |re, im| Ok(NumberOrMatrix::Complex(Complex::new(re, im)));
+
+        assert_eq!(evaluate("3 + 2i"), complex(3., 2.));
+        assert_eq!(evaluate("(1 + i) * (1 - i)"), complex(2., 0.));
+        assert_eq!(evaluate("i * i"), complex(-1., 0.));
+        assert_eq!(evaluate("2 / i"), complex(0., -2.));
+        assert_eq!(evaluate("(A + i * A)[2, 1]"), complex(1., 1.));
+        assert_eq!(evaluate("((2 * i * A) ^ {-1})[1, 2]"), complex(0., -0.5));
+        assert_eq!(
+            evaluate("(i * A) ^ 2"),
+            Ok(NumberOrMatrix::ComplexMatrix(CMatN::identity(2)))
+        );
+        assert_eq!(
+            evaluate("norm(i * A)"),
+            Ok(NumberOrMatrix::Number(2f64.sqrt()))
+        );
+
+        let Ok(NumberOrMatrix::Complex(number)) = evaluate("exp(i * 2) * 2 ^ i") else {
+            panic!("Complex functions should give complex numbers");
+        };
+        let expected = Complex::from_polar(1., 2.) * Complex::from_polar(1., 2f64.ln());
+        assert!(number.re.relative_eq(&expected.re, EPSILON, EPSILON));
+        assert!(number.im.relative_eq(&expected.im, EPSILON, EPSILON));
+
+        for (expression, error) in [
+            ("i * [1; 2]", EvaluationError::CannotCombineVectorAndComplex),
+            ("i + A", EvaluationError::CannotAddNumberAndMatrix),
+            ("A ^ i", EvaluationError::CannotRaiseMatrixToNonInteger),
+            ("2 / (i * A)", EvaluationError::CannotDivideByMatrix),
+            ("rank(i * A)", EvaluationError::UnsupportedComplexMatrix),
+            ("row(i * A, 1)", EvaluationError::UnsupportedComplexMatrix),
+            ("log(0i)", EvaluationError::LogarithmUndefined),
+            (
+                "(i * A) ^ 1.5",
+                EvaluationError::CannotRaiseMatrixToNonInteger,
+            ),
+        ] {
+            assert_eq!(evaluate(expression), Err(error), "{expression}");
+        }
+
+        // Complex values can be turned back into ASTs which evaluate to the same thing
+        for expression in ["3 - 2i", "i", "A * (1 + i) - 2i * A ^ T"] {
+            let value = evaluate(expression).unwrap();
+            assert_eq!(
+                AstNode::from(value.clone()).evaluate(&map),
+                Ok(value),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn ast_node_from_decomposition() {
+        use crate::matrix::expression::format::FormatOptions;
+
+        let map = MatrixMap2::new();
+        let round_trip = |decomposition: Decomposition2d| {
+            let expression =
+                AstNode::from(decomposition).to_formatted_string(&FormatOptions::default());
+            match parse_expression_from_string(&expression)
+                .unwrap_or_else(|error| panic!("'{expression}' should parse: {error}"))
+                .evaluate(&map)
+            {
+                Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(matrix))) => (expression, matrix),
+                result => panic!("'{expression}' should be a 2D matrix, not {result:?}"),
+            }
+        };
+
+        let identity = Decomposition2d {
+            rotation_degrees: 0.,
+            scale: DVec2::ONE,
+            shear: 0.,
+        };
+        assert_eq!(round_trip(identity), ("I".to_string(), DMat2::IDENTITY));
+        assert_eq!(
+            round_trip(Decomposition2d {
+                scale: DVec2::new(-2., -0.),
+                ..identity
+            }),
+            (
+                "-[2 0; 0 0]".to_string(),
+                DMat2::from_cols_array(&[-2., 0., 0., 0.])
+            )
+        );
+
+        for _ in 0..100 {
+            let [degrees, x, y, shear] = rand::random::<[f64; 4]>().map(|x| x * 8. - 4.);
+            let decomposition = Decomposition2d {
+                rotation_degrees: degrees * 90.,
+                scale: DVec2::new(x, y),
+                shear,
+            };
+            let (expression, matrix) = round_trip(decomposition);
+            assert_relative_eq!(matrix, decomposition.to_matrix(), epsilon = EPSILON);
+            assert!(!expression.contains("-0"), "{expression}");
+        }
+    }
+
+    #[test]
+    fn ast_node_owns_its_data() {
+        /// Only compiles if the AST doesn't borrow anything.
+        fn assert_owned<T: 'static + Send + Sync>(_: &T) {}
+
+        let ast = {
+            let expression = String::from("2 * Abc + [1 2; 3 4] - [5; 6]");
+            crate::matrix::expression::parse_expression_from_string(&expression).unwrap()
+        };
+        assert_owned(&ast);
+
+        assert_eq!(
+            ast,
+            AstNode::Add {
+                left: Box::new(AstNode::Add {
+                    left: Box::new(AstNode::Multiply {
+                        left: Box::new(2.0.into()),
+                        right: Box::new(MatrixName::new("Abc").into())
+                    }),
+                    right: Box::new(
+                        MatrixValue::TwoD(DMat2::from_cols_array(&[1., 3., 2., 4.])).into()
+                    )
+                }),
+                right: Box::new(AstNode::Negate(Box::new(
+                    Vector2dOr3d::TwoD(DVec2::new(5., 6.)).into()
+                )))
+            }
+        );
     }
 
+    #[cfg(feature = "serde")]
     #[test]
-    fn ast_node_evaluation_failure() {
-        let map2 = MatrixMap2::new();
-        let map3 = MatrixMap3::new();
+    fn ast_node_serde_round_trip() {
+        use crate::matrix::expression::{
+            parse_expression_from_string, tokenise::tokenise_expression,
+        };
 
-        // 3 + [4 -1; -1.5 3]
+        for expression in [
+            "[1 2; 3 4]",
+            "[1.5 2 3; 4 5 6; 7 8 9.25]",
+            "[1; 2] + [3; 4; 5]",
+            "2 * A ^ {-1} - rot(45) / (B + Ct) * [1 0; 0 1]",
+            "dot(aug([1; 0], [0; 1])[1, 2] * U, cross(V, W)) + norm(block(A, U; V, 2))",
+            "cofactor(adj(M), 1, 2) + rank(solve(M, [1; 2])) + row(M, 2) + col(M, 1)",
+            "[1 2 3 4; 5 6 7 8; 9 10 11 12; 13 14 15 16.5]",
+            "(3 + 2i) * A - i",
+        ] {
+            let ast = parse_expression_from_string(expression).unwrap();
+            let json = serde_json::to_string(&ast).unwrap();
+            assert_eq!(
+                serde_json::from_str::<AstNode>(&json).unwrap(),
+                ast,
+                "{expression}"
+            );
+
+            let tokens = tokenise_expression(expression).unwrap();
+            let json = serde_json::to_string(&tokens).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Vec<crate::matrix::expression::tokenise::Token>>(&json)
+                    .unwrap(),
+                tokens,
+                "{expression}"
+            );
+        }
+
+        for value in [
+            NumberOrMatrix::Number(-3.5),
+            NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols_array(&[1., 2., 3., 4.]))),
+            NumberOrMatrix::Matrix(MatrixValue::ThreeD(DMat3::from_cols_array(&[
+                1., 2., 3., 4., 5., 6., 7., 8., 9.,
+            ]))),
+            NumberOrMatrix::Vector(Vector2dOr3d::ThreeD(DVec3::new(1., 2., 3.))),
+            NumberOrMatrix::Matrix(MatrixValue::Dynamic(DMatN::identity(4))),
+            NumberOrMatrix::Complex(Complex::new(1., -2.)),
+            NumberOrMatrix::ComplexMatrix(Complex::I * CMatN::identity(3)),
+        ] {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(
+                serde_json::from_str::<NumberOrMatrix>(&json).unwrap(),
+                value
+            );
+        }
+
+        assert!(serde_json::from_str::<AstNode>(r#"{"NamedMatrix":"abc"}"#).is_err());
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Add {
-                    left: Box::new(AstNode::Number(3.)),
-                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(4., -1.5),
-                        DVec2::new(-1., 3.)
-                    )))
-                },
-                &map2
-            ),
-            Err(EvaluationError::CannotAddNumberAndMatrix)
+            serde_json::from_str::<AstNode>(r#"{"NamedMatrix":"Abc"}"#).unwrap(),
+            AstNode::NamedMatrix(MatrixName::new("Abc"))
         );
+    }
 
-        // [1 4 7; 2 5 8; 3 6 9] * [4 -1; 1.5 3]
+    #[test]
+    fn ast_node_deeply_nested() {
+        let mut nested = AstNode::Number(1.);
+        for _ in 0..5000 {
+            nested = AstNode::Add {
+                left: Box::new(AstNode::Number(1.)),
+                right: Box::new(nested),
+            };
+        }
+
+        let string = nested.to_expression_string();
+        assert!(string.starts_with("1 + (1 + (1 + "));
+        assert!(string.ends_with(&format!("1 + 1{}", ")".repeat(4999))));
+        assert_eq!(string.matches('(').count(), 4999);
+
+        let map = MatrixMap2::new();
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Multiply {
-                    left: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
-                        DVec3::new(1., 2., 3.),
-                        DVec3::new(4., 5., 6.),
-                        DVec3::new(7., 8., 9.)
-                    ))),
-                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(4., -1.5),
-                        DVec2::new(-1., 3.)
-                    )))
-                },
-                &map2
+            nested.clone().evaluate(&map),
+            Ok(NumberOrMatrix::Number(5001.))
+        );
+        assert_eq!(
+            nested.clone().evaluate_with_limits(
+                &map,
+                EvaluationLimits {
+                    max_depth: 5000,
+                    ..EvaluationLimits::default()
+                }
             ),
-            Err(EvaluationError::CannotMultiplyDifferentDimensions)
+            Err(EvaluationError::ExpressionTooDeep { max_depth: 5000 })
         );
-
-        // [1 4 7; 2 5 8; 3 6 9] + [4 -1; 1.5 3]
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Add {
-                    left: Box::new(AstNode::Anonymous3dMatrix(DMat3::from_cols(
-                        DVec3::new(1., 2., 3.),
-                        DVec3::new(4., 5., 6.),
-                        DVec3::new(7., 8., 9.)
-                    ))),
-                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(4., -1.5),
-                        DVec2::new(-1., 3.)
-                    )))
-                },
-                &map2
+            nested.evaluate_with_limits(
+                &map,
+                EvaluationLimits {
+                    max_nodes: 10_000,
+                    ..EvaluationLimits::default()
+                }
             ),
-            Err(EvaluationError::CannotAddDifferentDimensions)
+            Err(EvaluationError::ExpressionTooLarge { max_nodes: 10_000 })
         );
+    }
 
-        // [1 0; 0 1] ^ 1.5
+    #[test]
+    fn deeply_nested_strings() {
+        use crate::matrix::expression::{
+            check::check,
+            parser::{ParseError, MAX_NESTING_DEPTH},
+            shape::Shape,
+            TokeniseOrParseError,
+        };
+
+        let mut map = MatrixMap2::new();
+        map.set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+        let limits = EvaluationLimits {
+            max_depth: usize::MAX,
+            max_nodes: usize::MAX,
+        };
+
+        // Long chains of operators parse into very deep trees, which we can walk, compare, and
+        // drop without overflowing the stack
+        let sum = parse_expression_from_string(&vec!["1"; 12_000].join(" + ")).unwrap();
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
-                    power: Box::new(AstNode::Number(1.5))
-                },
-                &map2
-            ),
-            Err(EvaluationError::CannotRaiseMatrixToNonInteger)
+            sum.clone().evaluate(&map),
+            Err(EvaluationError::ExpressionTooDeep { max_depth: 10_000 })
+        );
+        assert_eq!(
+            sum.clone().evaluate_with_limits(&map, limits),
+            Ok(NumberOrMatrix::Number(12_000.))
         );
+        assert_eq!(sum.clone(), sum);
+        assert_eq!(sum.clone().simplify(), AstNode::Number(12_000.));
+        assert_eq!(sum.infer_shape(&map), Ok(Shape::Number));
 
-        // [1 0 0; 0 1 0; 0 0 1] ^ -3.7
+        let difference = parse_expression_from_string(&vec!["A"; 12_000].join(" - ")).unwrap();
+        assert_eq!(difference.named_matrices().len(), 12_000);
+        assert_eq!(check(&difference, &map), []);
+        assert_ne!(difference, sum);
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::IDENTITY)),
-                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(3.7))))
-                },
-                &map3
-            ),
-            Err(EvaluationError::CannotRaiseMatrixToNonInteger)
+            difference.clone().evaluate_with_limits(&map, limits),
+            Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(
+                DMat2::IDENTITY * -11_998.
+            )))
         );
+        assert!(difference
+            .clone()
+            .evaluate_partially(&map)
+            .unwrap()
+            .equivalent(&AstNode::from(DMat2::IDENTITY * -11_998.)));
 
-        // 2 / [1 2; 3 4]
+        let product = parse_expression_from_string(&vec!["A"; 1000].join(" * ")).unwrap();
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Divide {
-                    left: Box::new(AstNode::Number(2.)),
-                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(1., 3.),
-                        DVec2::new(2., 4.)
-                    )))
-                },
-                &map2
-            ),
-            Err(EvaluationError::CannotDivideByMatrix)
+            product
+                .substitute(
+                    &MatrixName::new("A"),
+                    &AstNode::RotationMatrix { degrees: 0. }
+                )
+                .simplify_symbolic(),
+            AstNode::RotationMatrix { degrees: 0. }
         );
+        assert_eq!(product.clone().canonicalise(), product);
+
+        // Nesting is limited, since the parser recurses for each level, and the error points at
+        // the first token that's too deep
+        for (nested, tokens_per_level) in [
+            (format!("{}A{}", "(".repeat(1000), ")".repeat(1000)), 1),
+            (format!("{}A{}", "exp(".repeat(1000), ")".repeat(1000)), 2),
+            (format!("{}A", "-".repeat(1000)), 1),
+            (format!("A{}", "^2".repeat(1000)), 2),
+        ] {
+            match parse_expression_from_string(&nested) {
+                Err(TokeniseOrParseError::ParseError(ParseError::TooDeeplyNested(diagnostic))) => {
+                    assert_eq!(
+                        diagnostic.token_index,
+                        MAX_NESTING_DEPTH * tokens_per_level,
+                        "{nested}"
+                    );
+                }
+                result => panic!("Expected {nested} to be nested too deeply, but got {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn ast_node_evaluation_properties() {
+        let mut map = MatrixMap3::new();
+        map.set(
+            MatrixName::new("M"),
+            DMat3::from_cols_array(&[1., 2., 3., 4., 5., 6., 7., 8., 9.]),
+        )
+        .unwrap();
+        let evaluate = |expression: &str| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map)
+        };
+
+        for (expression, expected) in [
+            ("is_orthogonal(rot(30) * rot(60))", 1.),
+            ("is_rotation(rot(30) * rot(60))", 1.),
+            ("is_rotation([0 1; 1 0])", 0.),
+            ("is_orthogonal([0 1; 1 0])", 1.),
+            ("is_symmetric(M)", 0.),
+            ("is_symmetric(M + M^T)", 1.),
+            ("is_singular(M)", 1.),
+            ("is_singular(M + [1 0 0; 0 0 0; 0 0 0])", 0.),
+            ("is_singular([1 2 0 0; 2 4 0 0; 0 0 1 0; 0 0 0 1])", 1.),
+            ("is_rotation([0 0 1 0; 1 0 0 0; 0 1 0 0; 0 0 0 1])", 1.),
+        ] {
+            assert_eq!(
+                evaluate(expression),
+                Ok(NumberOrMatrix::Number(expected)),
+                "{expression}"
+            );
+        }
 
-        // [1 0; 0 1] / [1 2; 3 4]
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Divide {
-                    left: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
-                    right: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols(
-                        DVec2::new(1., 3.),
-                        DVec2::new(2., 4.)
-                    )))
-                },
-                &map2
-            ),
-            Err(EvaluationError::CannotDivideByMatrix)
+            evaluate("is_singular(2)"),
+            Err(EvaluationError::PropertyRequiresMatrix)
         );
+        assert_eq!(
+            evaluate("is_orthogonal(i * M)"),
+            Err(EvaluationError::UnsupportedComplexMatrix)
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluation_random() {
+        let mut map = MatrixMap2::new();
+        map.populate_random(1, RandomDistribution::Integer, 42)
+            .unwrap();
+        let evaluate = |expression: &str| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map)
+        };
 
+        // The seed alone decides the matrix, just like the first matrix from populate_random
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous2dMatrix(DMat2::ZERO)),
-                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
-                },
-                &map2
-            ),
-            Err(EvaluationError::CannotInvertSingularMatrix)
+            evaluate("rand(2, 42, \"integer\")"),
+            Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(
+                map.get(&MatrixName::new("R1")).unwrap()
+            )))
+        );
+        assert_eq!(
+            evaluate("rand(2, 6 * 7)"),
+            evaluate("rand(2, 42, \"uniform\")")
         );
+        assert_ne!(evaluate("rand(2, 42)"), evaluate("rand(2, 43)"));
 
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::ThreeD(matrix))) =
+            evaluate("rand(3, 7, \"orthogonal\")")
+        else {
+            panic!("rand(3, ...) should be a 3D matrix");
+        };
+        assert_relative_eq!(
+            matrix * matrix.transpose(),
+            DMat3::IDENTITY,
+            epsilon = EPSILON
+        );
+
+        for expression in [
+            "rand(2.5, 1)",
+            "rand(2, -1)",
+            "rand(0, 1)",
+            "rand(R1, 1)",
+            "rand(2, i)",
+        ] {
+            assert_eq!(
+                evaluate(expression),
+                Err(EvaluationError::RandomRequiresIntegers),
+                "{expression}"
+            );
+        }
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::ZERO)),
-                    power: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
-                },
-                &map3
-            ),
-            Err(EvaluationError::CannotInvertSingularMatrix)
+            evaluate("rand(4, 1)"),
+            Err(EvaluationError::UnsupportedDimension { dimension: 4 })
         );
+    }
+
+    #[test]
+    fn ast_node_evaluation_options() {
+        let evaluate = |expression: &str, options: EvalOptions| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate_with_options(&MatrixMap2::new(), options)
+        };
+        let loose = EvalOptions {
+            epsilon: 0.001,
+            max_relative: 0.001,
+            ..EvalOptions::default()
+        };
+        let strict = EvalOptions {
+            epsilon: 0.,
+            max_relative: 0.,
+            ..EvalOptions::default()
+        };
 
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Number(2.3)),
-                    power: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY)),
-                },
-                &map2,
-            ),
-            Err(EvaluationError::CannotRaiseToMatrix)
+            evaluate("[1 2; 3 4]^2.0001", EvalOptions::default()),
+            Err(EvaluationError::CannotRaiseMatrixToNonInteger)
+        );
+        assert_eq!(
+            evaluate("[1 2; 3 4]^2.0001", loose),
+            evaluate("[1 2; 3 4]^2", loose)
+        );
+        assert_eq!(
+            evaluate("[1 2; 3 4]^(3 * (1 / 3))", strict),
+            Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(
+                DMat2::from_cols_array(&[1., 3., 2., 4.])
+            )))
+        );
+        assert_eq!(
+            evaluate("row([1 2; 3 4], 1.0001)", loose),
+            Ok(NumberOrMatrix::Vector(Vector2dOr3d::TwoD(DVec2::new(
+                1., 2.
+            ))))
+        );
+        assert_eq!(
+            evaluate("row([1 2; 3 4], 1.0001)", EvalOptions::default()),
+            Err(EvaluationError::IndexMustBePositiveInteger)
         );
 
+        for expression in [
+            "[1 2; 3 4] ^ 100000",
+            "[1 2 3; 4 5 6; 7 8 10] ^ -100000",
+            "[1 2 3 4; 5 6 7 8; 9 10 11 12; 13 14 15 16] ^ 100000",
+            "(i * [1 2; 3 4]) ^ 100000",
+            "[1 0; 0 1] ^ 10000000000",
+        ] {
+            assert_eq!(
+                evaluate(expression, EvalOptions::default()),
+                Err(EvaluationError::PowerOverflow),
+                "{expression}"
+            );
+        }
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Anonymous3dMatrix(DMat3::IDENTITY)),
-                    power: Box::new(AstNode::Anonymous3dMatrix(DMat3::IDENTITY)),
-                },
-                &map3,
-            ),
-            Err(EvaluationError::CannotRaiseToMatrix)
+            evaluate("[0 1; 1 0] ^ 100001", EvalOptions::default()),
+            Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(
+                DMat2::from_cols_array(&[0., 1., 1., 0.])
+            )))
+        );
+        for expression in [
+            "[1 2; 2 4.0001]^-1",
+            "(i * [1 2; 2 4.0001])^-1",
+            "[1 2 0 0; 2 4.0001 0 0; 0 0 1 0; 0 0 0 1]^-1",
+        ] {
+            assert!(
+                evaluate(expression, EvalOptions::default()).is_ok(),
+                "{expression}"
+            );
+            assert_eq!(
+                evaluate(expression, loose),
+                Err(EvaluationError::CannotInvertSingularMatrix),
+                "{expression}"
+            );
+        }
+        for expression in [
+            "is_singular([1 2; 2 4.0001])",
+            "is_singular([1 2 0 0; 2 4.0001 0 0; 0 0 1 0; 0 0 0 1])",
+            "is_symmetric([1 2 0 0; 2.0001 4 0 0; 0 0 1 0; 0 0 0 1])",
+            "is_orthogonal([1.0001 0; 0 1])",
+        ] {
+            assert_eq!(
+                evaluate(expression, EvalOptions::default()),
+                Ok(NumberOrMatrix::Number(0.)),
+                "{expression}"
+            );
+            assert_eq!(
+                evaluate(expression, loose),
+                Ok(NumberOrMatrix::Number(1.)),
+                "{expression}"
+            );
+        }
+        assert_eq!(
+            evaluate("rank([1 2; 2 4.0001])", EvalOptions::default()),
+            Ok(NumberOrMatrix::Number(2.))
+        );
+        assert_eq!(
+            evaluate("rank([1 2; 2 4.0001])", loose),
+            Ok(NumberOrMatrix::Number(1.))
         );
+        let relative = EvalOptions {
+            epsilon: 0.,
+            max_relative: 0.001,
+            ..EvalOptions::default()
+        };
+        for expression in [
+            "is_symmetric([1000 2; 2.001 4])",
+            "is_orthogonal([1.0001 0; 0 1])",
+        ] {
+            assert_eq!(
+                evaluate(expression, strict),
+                Ok(NumberOrMatrix::Number(0.)),
+                "{expression}"
+            );
+            assert_eq!(
+                evaluate(expression, relative),
+                Ok(NumberOrMatrix::Number(1.)),
+                "{expression}"
+            );
+        }
 
-        // (1 + 2) ^ T
+        // Negative powers invert the base first, so a small base doesn't look singular
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(matrix))) =
+            evaluate("[0.1 0; 0 0.1] ^ -5", EvalOptions::default())
+        else {
+            panic!("A small 2D matrix should be invertible");
+        };
+        assert_relative_eq!(
+            matrix,
+            DMat2::IDENTITY * 100_000.,
+            max_relative = 0.000000001
+        );
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::Dynamic(matrix))) = evaluate(
+            "[0.1 0 0 0; 0 0.1 0 0; 0 0 0.1 0; 0 0 0 0.1] ^ -5",
+            EvalOptions::default(),
+        ) else {
+            panic!("A small 4D matrix should be invertible");
+        };
+        let difference = DMatN::try_add(&matrix, &(DMatN::identity(4) * -100_000.)).unwrap();
+        assert!(difference.norm() < 0.000001);
+
+        // Whether a matrix is singular doesn't depend on its scale or its dimension
+        for expression in [
+            "[0.00001 0; 0 0.00001] ^ -1",
+            "[0.00001 0 0; 0 0.00001 0; 0 0 0.00001] ^ -1",
+            "[0.00001 0 0 0; 0 0.00001 0 0; 0 0 0.00001 0; 0 0 0 0.00001] ^ -1",
+            "(i * [0.00001 0; 0 0.00001]) ^ -1",
+        ] {
+            assert!(
+                evaluate(expression, EvalOptions::default()).is_ok(),
+                "{expression}"
+            );
+        }
+        let Ok(NumberOrMatrix::Vector(Vector2dOr3d::TwoD(solution))) = evaluate(
+            "solve([0.00001 0; 0 0.00001], [1; 1])",
+            EvalOptions::default(),
+        ) else {
+            panic!("A small 2D system should be solvable");
+        };
+        assert_relative_eq!(solution, DVec2::splat(100_000.), max_relative = 0.000000001);
         assert_eq!(
-            AstNode::evaluate(
-                AstNode::Exponent {
-                    base: Box::new(AstNode::Add {
-                        left: Box::new(AstNode::Number(1.)),
-                        right: Box::new(AstNode::Number(2.))
-                    }),
-                    power: Box::new(AstNode::NamedMatrix(MatrixName::new("T")))
-                },
-                &map2
-            ),
-            Err(EvaluationError::CannotTransposeNumber)
+            evaluate("solve([1 2; 2 4.0001], [1; 1])", loose),
+            Err(EvaluationError::CannotSolveSingularSystem)
         );
+
+        assert!(EvalOptions::default().is_integer(3. + 1e-12));
+        assert!(!strict.is_integer(3. + 1e-12));
+        assert!(loose.is_zero(0.0001));
+        assert!(!EvalOptions::default().is_zero(0.0001));
     }
 
     #[test]
@@ -1107,6 +4810,171 @@ mod tests {
             }),
             "2 + (-3)"
         );
+
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::CrossProduct {
+                left: Box::new(AstNode::Anonymous3dVector(DVec3::new(1., 2., 3.))),
+                right: Box::new(AstNode::Add {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("V"))),
+                    right: Box::new(AstNode::Anonymous3dVector(DVec3::ONE))
+                })
+            }),
+            "cross([1; 2; 3], V + [1; 1; 1])"
+        );
+
+        assert_eq!(
+            AstNode::to_expression_string(&AstNode::DotProduct {
+                left: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.))),
+                right: Box::new(AstNode::NamedMatrix(MatrixName::new("V")))
+            }),
+            "dot([1; 2], V)"
+        );
+    }
+
+    #[test]
+    fn ast_node_evaluate_steps() {
+        let parse = |expression: &str| parse_expression_from_string(expression).unwrap();
+        let mut map = MatrixMap2::new();
+        map.set(MatrixName::new("A"), DMat2::IDENTITY * 3.).unwrap();
+        let steps = |expression: &str| {
+            parse(expression)
+                .evaluate_steps(&map, EvalOptions::default())
+                .map(|steps| {
+                    steps
+                        .into_iter()
+                        .map(|step| (step.expression, step.value))
+                        .collect::<Vec<_>>()
+                })
+        };
+        let matrix = |matrix: DMat2| NumberOrMatrix::Matrix(MatrixValue::TwoD(matrix));
+
+        assert_eq!(
+            steps("2 * A + I"),
+            Ok(vec![
+                ("A".to_string(), matrix(DMat2::IDENTITY * 3.)),
+                ("2 * A".to_string(), matrix(DMat2::IDENTITY * 6.)),
+                ("I".to_string(), matrix(DMat2::IDENTITY)),
+                ("2 * A + I".to_string(), matrix(DMat2::IDENTITY * 7.)),
+            ])
+        );
+        assert_eq!(
+            steps("2"),
+            Ok(vec![("2".to_string(), NumberOrMatrix::Number(2.))])
+        );
+        assert_eq!(
+            steps("2 + 3"),
+            Ok(vec![("2 + 3".to_string(), NumberOrMatrix::Number(5.))])
+        );
+        assert_eq!(
+            steps("A + B"),
+            Err(EvaluationError::MatrixMapError(
+                MatrixMapError::NameNotDefined(MatrixName::new("B"))
+            ))
+        );
+
+        // Each caption is the subexpression formatted on its own, even though it's built from the
+        // captions of its children
+        let expressions: Vec<_> = steps("A - 2 * A ^ T + -(A + A) / 2")
+            .unwrap()
+            .into_iter()
+            .map(|(expression, _)| expression)
+            .collect();
+        assert_eq!(
+            expressions,
+            [
+                "A",
+                "A",
+                "A ^ T",
+                "2 * A ^ T",
+                "-(2 * A ^ T)",
+                "A - 2 * A ^ T",
+                "A",
+                "A",
+                "A + A",
+                "-(A + A)",
+                "-(A + A) / 2",
+                "A - 2 * A ^ T + -(A + A) / 2",
+            ]
+        );
+
+        // The last step is snapped just like the whole evaluation
+        let options = EvalOptions {
+            affine_2d: true,
+            ..EvalOptions::default()
+        };
+        let ast = parse("translate(1, 2)^-1 * translate(3, 4)");
+        assert_eq!(
+            ast.evaluate_steps(&map, options)
+                .unwrap()
+                .last()
+                .unwrap()
+                .value,
+            ast.evaluate_with_options(&map, options).unwrap()
+        );
+    }
+
+    #[test]
+    fn ast_node_composition_stages() {
+        let [a, b, c] = ["A", "B", "C"].map(|name| AstNode::NamedMatrix(MatrixName::new(name)));
+        let parse = |expression: &str| parse_expression_from_string(expression).unwrap();
+
+        assert_eq!(
+            parse("B * A").composition_stages(),
+            vec![a.clone(), b.clone()]
+        );
+        assert_eq!(
+            parse("C * (B * A)").composition_stages(),
+            vec![a.clone(), b.clone(), c.clone()]
+        );
+
+        // Scalars are folded into the next matrix, rather than being stages of their own
+        let stages = |expression: &str| -> Vec<String> {
+            parse(expression)
+                .composition_stages()
+                .iter()
+                .map(|stage| stage.to_formatted_string(&FormatOptions::default()))
+                .collect()
+        };
+        assert_eq!(stages("2 * A"), ["2 * A"]);
+        assert_eq!(stages("2 * A * B"), ["B", "2 * A"]);
+        assert_eq!(stages("C * B * x * 3 * A"), ["x * 3 * A", "B", "C"]);
+        assert_eq!(stages("C * (B * A) * 2"), ["A * 2", "B", "C"]);
+
+        // A matrix times a scalar variable is a single stage, and products with vectors or only
+        // numbers aren't compositions at all
+        for expression in ["A * v", "A + B * C", "B * A * [1; 2]", "2 * 3"] {
+            let ast = parse(expression);
+            assert_eq!(ast.composition_stages(), vec![ast.clone()], "{expression}");
+        }
+
+        // Multiplying the stages back together in reverse gives the same result
+        let mut map = MatrixMap2::new();
+        map.set(
+            MatrixName::new("A"),
+            DMat2::from_cols_array(&[1., 2., 3., 4.]),
+        )
+        .unwrap();
+        map.set(MatrixName::new("B"), DMat2::from_angle(0.3))
+            .unwrap();
+        let product = parse("B * 3 * A * (B + A) * 2");
+        let mut composed = DMat2::IDENTITY;
+        for stage in product.composition_stages() {
+            let Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(stage))) = stage.evaluate(&map) else {
+                panic!("Every stage should be a 2D matrix");
+            };
+            composed = stage * composed;
+        }
+        let Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(product))) = product.evaluate(&map) else {
+            panic!("The product should be a 2D matrix");
+        };
+        assert_relative_eq!(product, composed, epsilon = EPSILON);
+
+        // Long chains don't overflow the stack
+        let chain = (0..5000).fold(a.clone(), |chain, _| AstNode::Multiply {
+            left: Box::new(b.clone()),
+            right: Box::new(chain),
+        });
+        assert_eq!(chain.composition_stages().len(), 5001);
     }
 
     #[test]
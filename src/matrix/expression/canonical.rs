@@ -0,0 +1,256 @@
+//! This module handles putting ASTs into a canonical form, so that expressions which only differ
+//! in the order of their terms can be compared.
+
+use super::ast::AstNode;
+use std::mem;
+
+impl AstNode {
+    /// Put this AST into a canonical form, where commutative and associative operations are
+    /// written in a fixed order.
+    ///
+    /// Chains of additions are flattened and their terms are sorted, and then rebuilt from the
+    /// left like the parser does. Chains of multiplications are flattened too, but since matrix
+    /// multiplication isn't commutative, only the factors which are definitely numbers are moved
+    /// to the front and sorted, and the number literals among them are multiplied together.
+    /// The operands of dot products are sorted as well.
+    ///
+    /// The canonical AST evaluates to the same thing as the original, up to rounding, and if the
+    /// original fails to evaluate, then so does the canonical version.
+    ///
+    /// ```
+    /// # use trinity::matrix::expression::parse_expression_from_string;
+    /// let canonicalise = |expression| {
+    ///     parse_expression_from_string(expression)
+    ///         .unwrap()
+    ///         .canonicalise()
+    ///         .to_expression_string()
+    /// };
+    ///
+    /// assert_eq!(canonicalise("M * 2"), "2 * M");
+    /// assert_eq!(canonicalise("B * (3 * A) * 2"), "6 * (B * A)");
+    /// assert_eq!(canonicalise("C + (B + A)"), "(A + B) + C");
+    /// ```
+    pub fn canonicalise(self) -> Self {
+        self.map_bottom_up(Self::canonicalise_node)
+    }
+
+    /// Canonicalise this node like [`canonicalise`](Self::canonicalise), assuming that its
+    /// children have already been canonicalised.
+    fn canonicalise_node(self) -> Self {
+        match self {
+            node @ Self::Add { .. } => {
+                let mut terms = vec![];
+                node.into_terms(&mut terms);
+                sort_by_expression(&mut terms);
+
+                terms
+                    .into_iter()
+                    .reduce(|left, right| Self::Add {
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    })
+                    .expect("An addition should have at least two terms")
+            }
+            node @ Self::Multiply { .. } => {
+                let mut factors = vec![];
+                node.into_factors(&mut factors);
+
+                let mut product = None;
+                let mut scalars = vec![];
+                let mut others = vec![];
+                for factor in factors {
+                    if let Some(number) = factor.literal_number() {
+                        product = Some(product.unwrap_or(1.) * number);
+                    } else if factor.is_definitely_number() {
+                        scalars.push(factor);
+                    } else {
+                        others.push(factor);
+                    }
+                }
+                sort_by_expression(&mut scalars);
+
+                // Multiplying by 1 does nothing, unless it's the only factor
+                let product = product
+                    .filter(|&number| number != 1. || (scalars.is_empty() && others.is_empty()));
+                let product = product.map(|number| {
+                    if number < 0. {
+                        Self::Negate(Box::new(Self::Number(-number)))
+                    } else {
+                        Self::Number(number)
+                    }
+                });
+
+                product
+                    .into_iter()
+                    .chain(scalars)
+                    .chain(others)
+                    .rev()
+                    .reduce(|right, left| Self::Multiply {
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    })
+                    .expect("A multiplication should have at least one factor left")
+            }
+            mut node @ Self::DotProduct { .. } => {
+                if let Self::DotProduct { left, right } = &mut node {
+                    if left.to_expression_string() > right.to_expression_string() {
+                        mem::swap(left, right);
+                    }
+                }
+                node
+            }
+            node => node,
+        }
+    }
+
+    /// Are these two ASTs the same, once they've both been [canonicalised](Self::canonicalise)?
+    ///
+    /// This means that `2 * M` and `M * 2` are equivalent, as are `A + (B + C)` and `(C + B) + A`,
+    /// but `A * B` and `B * A` are not.
+    ///
+    /// ```
+    /// # use trinity::matrix::expression::parse_expression_from_string;
+    /// let parse = |expression| parse_expression_from_string(expression).unwrap();
+    ///
+    /// assert!(parse("2 * M").equivalent(&parse("M * 2")));
+    /// assert!(!parse("A * B").equivalent(&parse("B * A")));
+    /// ```
+    pub fn equivalent(&self, other: &Self) -> bool {
+        self.clone().canonicalise() == other.clone().canonicalise()
+    }
+
+    /// Split a chain of additions into its terms, in order.
+    fn into_terms(self, terms: &mut Vec<Self>) {
+        let mut pending = vec![self];
+
+        while let Some(mut node) = pending.pop() {
+            match &mut node {
+                // The right term is pushed first so that the left one is popped first
+                Self::Add { left, right } => {
+                    pending.push(Self::take(right));
+                    pending.push(Self::take(left));
+                }
+                _ => terms.push(node),
+            }
+        }
+    }
+
+    /// Split a chain of multiplications into its factors, in order.
+    fn into_factors(self, factors: &mut Vec<Self>) {
+        let mut pending = vec![self];
+
+        while let Some(mut node) = pending.pop() {
+            match &mut node {
+                // The right factor is pushed first so that the left one is popped first
+                Self::Multiply { left, right } => {
+                    pending.push(Self::take(right));
+                    pending.push(Self::take(left));
+                }
+                _ => factors.push(node),
+            }
+        }
+    }
+
+    /// If this node is a number literal, or the negation of one, then return its value.
+    fn literal_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(number) => Some(*number),
+            Self::Negate(term) => match **term {
+                Self::Number(number) => Some(-number),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Sort these nodes by their expression strings, which gives an arbitrary but fixed order.
+fn sort_by_expression(nodes: &mut [AstNode]) {
+    nodes.sort_by_cached_key(AstNode::to_expression_string);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::{
+        expression::{ast::AstNode, parse_expression_from_string},
+        map::prelude::*,
+        MatrixName,
+    };
+    use glam::DMat2;
+
+    /// Parse this expression, panicking if it's invalid.
+    fn parse(expression: &str) -> AstNode {
+        parse_expression_from_string(expression).unwrap()
+    }
+
+    /// Parse and canonicalise this expression.
+    fn canonicalise(expression: &str) -> AstNode {
+        parse(expression).canonicalise()
+    }
+
+    #[test]
+    fn canonicalise_success() {
+        assert_eq!(canonicalise("M * 2"), parse("2 * M"));
+        assert_eq!(canonicalise("M * 2 * 3"), parse("6 * M"));
+        assert_eq!(canonicalise("M * -2 * 3"), parse("-6 * M"));
+        assert_eq!(canonicalise("2 * 0.5 * M"), parse("M"));
+        assert_eq!(canonicalise("2 * 0.5"), parse("1"));
+        assert_eq!(
+            canonicalise("B * norm(V) * A * 2 * dot(U, V)"),
+            parse("2 * (dot(U, V) * (norm(V) * (B * A)))")
+        );
+        assert_eq!(canonicalise("(A + B) + C"), parse("(A + B) + C"));
+        assert_eq!(canonicalise("C + (B + A)"), parse("(A + B) + C"));
+        assert_eq!(canonicalise("B - A"), parse("-A + B"));
+        assert_eq!(canonicalise("dot(V, U)"), parse("dot(U, V)"));
+        assert_eq!(canonicalise("cross(V, U)"), parse("cross(V, U)"));
+
+        // Children are canonicalised too
+        assert_eq!(
+            canonicalise("norm((B + A) * 2) + (C * 3) ^ T"),
+            parse("(3 * C) ^ T + norm(2 * (A + B))")
+        );
+    }
+
+    #[test]
+    fn canonicalise_keeps_value() {
+        let mut map = MatrixMap2::new();
+        map.set(
+            MatrixName::new("A"),
+            DMat2::from_cols_array(&[1., 2., 3., 4.]),
+        )
+        .unwrap();
+        map.set(
+            MatrixName::new("B"),
+            DMat2::from_cols_array(&[0., -1., 2., 5.]),
+        )
+        .unwrap();
+
+        for expression in [
+            "B * 2 * A * 4 + A * 3 * B",
+            "dot(row(A, 1), col(B, 2)) * A + (B - A) * rank(A)",
+            "A * [1; 2] * 2 + B ^ T * [3; 4]",
+            "A + [1; 2]",
+            "2 * C",
+        ] {
+            let ast = parse(expression);
+            assert_eq!(
+                ast.clone().canonicalise().evaluate(&map),
+                ast.evaluate(&map),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn equivalent() {
+        assert!(parse("2 * M").equivalent(&parse("M * 2")));
+        assert!(parse("A + (B + C)").equivalent(&parse("(C + B) + A")));
+        assert!(parse("3 * (A * 2) * B").equivalent(&parse("6 * A * B")));
+        assert!(parse("dot(U, V) * A").equivalent(&parse("A * dot(V, U)")));
+
+        assert!(!parse("A * B").equivalent(&parse("B * A")));
+        assert!(!parse("A - B").equivalent(&parse("B - A")));
+        assert!(!parse("cross(U, V)").equivalent(&parse("cross(V, U)")));
+    }
+}
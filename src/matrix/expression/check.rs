@@ -0,0 +1,199 @@
+//! This module handles checking an expression for mistakes without evaluating it. See [`check`].
+
+use super::{
+    ast::{AstNode, EvaluationError},
+    shape::Shape,
+};
+use crate::matrix::map::MatrixMap;
+use std::{fmt, ptr};
+
+/// A mistake in an expression that would stop it from being evaluated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckIssue {
+    /// The smallest subexpression that contains the mistake.
+    pub node: AstNode,
+
+    /// The error that evaluating the subexpression would cause.
+    pub error: EvaluationError,
+}
+
+impl fmt::Display for CheckIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} in '{}'",
+            self.error,
+            self.node.to_expression_string()
+        )
+    }
+}
+
+/// Check the expression for mistakes like undefined matrix names, transposing a number, or
+/// dividing by a matrix, without evaluating it.
+///
+/// Unlike [`AstNode::evaluate`] and [`AstNode::infer_shape`], this doesn't stop at the first
+/// mistake. Every independent mistake is reported, in the order that they appear in the
+/// expression. If a subexpression has a mistake, then the expressions containing it aren't
+/// checked, since any errors there would probably just be caused by the first mistake.
+///
+/// Like [`AstNode::infer_shape`], this can't find mistakes that depend on the actual values of
+/// the matrices, so an expression with no issues can still fail to evaluate.
+///
+/// ```
+/// # use trinity::matrix::{MatrixName, expression::{check::check, parse_expression_from_string}, map::prelude::*};
+/// # use glam::DMat2;
+/// let mut map = MatrixMap2::new();
+/// map.set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+///
+/// let ast = parse_expression_from_string("2 ^ T + A / B - A / A").unwrap();
+/// let issues: Vec<String> = check(&ast, &map)
+///     .into_iter()
+///     .map(|issue| issue.to_string())
+///     .collect();
+///
+/// assert_eq!(
+///     issues,
+///     [
+///         "Cannot transpose a scalar number in '2 ^ {T}'",
+///         "Matrix named \"B\" is not defined in 'B'",
+///         "Cannot divide by a matrix in 'A / A'",
+///     ]
+/// );
+/// ```
+pub fn check(ast: &AstNode, map: &impl MatrixMap) -> Vec<CheckIssue> {
+    let mut issues = vec![];
+    // Every child is checked before its parent, so we find every independent mistake
+    ast.fold_iteratively(|node, children| check_node(node, children, map, &mut issues));
+    issues
+}
+
+/// Check this node, given the shapes of its children (or `None` for children with issues), adding
+/// any issue to the list, and return the shape of the node if it and all its children have no
+/// issues.
+fn check_node(
+    node: &AstNode,
+    children: Vec<Option<Shape>>,
+    map: &impl MatrixMap,
+    issues: &mut Vec<CheckIssue>,
+) -> Option<Shape> {
+    let children: Vec<(&AstNode, Shape)> = node
+        .children()
+        .into_iter()
+        .zip(children)
+        .map(|(child, shape)| shape.map(|shape| (child, shape)))
+        .collect::<Option<_>>()?;
+
+    let shape = node.shape_with(map, |child| {
+        Ok(children
+            .iter()
+            .find(|(node, _)| ptr::eq(*node, child))
+            .expect("shape_with should only ask for the shapes of direct children")
+            .1)
+    });
+
+    match shape {
+        Ok(shape) => Some(shape),
+        Err(error) => {
+            issues.push(CheckIssue {
+                node: node.clone(),
+                error,
+            });
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{
+        expression::parse_expression_from_string,
+        map::{prelude::*, ScalarMapError},
+        MatrixName, ScalarName,
+    };
+    use glam::DMat2;
+
+    /// Parse this expression, panicking if it's invalid.
+    fn parse(expression: &str) -> AstNode {
+        parse_expression_from_string(expression).unwrap()
+    }
+
+    /// Check this expression against a map with a 2D matrix `A` and a scalar `t`, and return the
+    /// offending subexpression and error of each issue.
+    fn issues(expression: &str) -> Vec<(AstNode, EvaluationError)> {
+        let mut map = Environment::<MatrixMap2>::new();
+        map.set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+        map.scalars_mut().set(ScalarName::new("t"), 3.).unwrap();
+
+        check(&parse(expression), &map)
+            .into_iter()
+            .map(|issue| (issue.node, issue.error))
+            .collect()
+    }
+
+    #[test]
+    fn check_no_issues() {
+        for expression in [
+            "2 * A ^ T - A / 3",
+            "[1 2 3; 4 5 6; 7 8 10] * [1; 2; 3] + cross([1; 0; 0], [0; 1; 0])",
+            "norm(A) * rank(A) + dot(row(A, 1), col(A, 2))",
+            "block(A, [1; 2]; [3; 4], 5) * [1; 2; 3]",
+            "2t * A - t * A",
+            // Value-dependent errors aren't found
+            "(A - A) ^ {-1}",
+        ] {
+            assert_eq!(issues(expression), vec![], "{expression}");
+        }
+    }
+
+    #[test]
+    fn check_multiple_issues() {
+        let undefined = |name| {
+            EvaluationError::MatrixMapError(MatrixMapError::NameNotDefined(MatrixName::new(name)))
+        };
+
+        assert_eq!(issues("C"), vec![(parse("C"), undefined("C"))]);
+        assert_eq!(
+            issues("x * A + C"),
+            vec![
+                (
+                    parse("x"),
+                    EvaluationError::ScalarMapError(ScalarMapError::NameNotDefined(
+                        ScalarName::new("x")
+                    ))
+                ),
+                (parse("C"), undefined("C")),
+            ]
+        );
+        assert_eq!(
+            issues("C + D * A"),
+            vec![(parse("C"), undefined("C")), (parse("D"), undefined("D"))]
+        );
+        assert_eq!(
+            issues("3 ^ T + A / A"),
+            vec![
+                (parse("3 ^ T"), EvaluationError::CannotTransposeNumber),
+                (parse("A / A"), EvaluationError::CannotDivideByMatrix),
+            ]
+        );
+
+        // Issues in a subexpression stop the expressions containing it from being checked
+        assert_eq!(
+            issues("(A + [1; 2; 3]) ^ T * C + 1"),
+            vec![
+                (
+                    parse("A + [1; 2; 3]"),
+                    EvaluationError::CannotAddMatrixAndVector
+                ),
+                (parse("C"), undefined("C")),
+            ]
+        );
+        assert_eq!(
+            issues("A * [1 2 3; 4 5 6; 7 8 9] + A + 1"),
+            vec![(
+                parse("A * [1 2 3; 4 5 6; 7 8 9]"),
+                EvaluationError::CannotMultiplyDifferentDimensions
+            )]
+        );
+    }
+}
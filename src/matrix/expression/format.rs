@@ -0,0 +1,551 @@
+//! This module handles formatting ASTs as expression strings. See
+//! [`AstNode::to_formatted_string`].
+
+use super::ast::AstNode;
+use crate::{math::RandomDistribution, matrix::MatrixName};
+use glam::f64::{DMat2, DMat3, DVec2, DVec3};
+use std::convert::Infallible;
+
+/// Which parentheses to put in a formatted expression.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Parentheses {
+    /// Only use parentheses where they're needed to keep the structure of the expression, based
+    /// on the precedence and associativity of the operators.
+    #[default]
+    Minimal,
+
+    /// Put every compound operand in parentheses, like
+    /// [`to_expression_string`](AstNode::to_expression_string).
+    All,
+}
+
+/// How much space to put around operators and separators in a formatted expression.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Spacing {
+    /// Spaces around binary operators and after commas and semicolons, like `dot(A, B) + 2 * C`.
+    #[default]
+    Spaced,
+
+    /// No spaces, except where they're needed between the entries of matrix literals, like
+    /// `dot(A,B)+2*C`.
+    Compact,
+}
+
+/// Options for [`AstNode::to_formatted_string`].
+///
+/// The default options give the cleanest output, with minimal parentheses, full numeric
+/// precision, and spaces around operators.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatOptions {
+    /// Which parentheses to use.
+    pub parentheses: Parentheses,
+
+    /// The maximum number of decimal places to show for each number, or `None` to show every
+    /// number exactly. Trailing zeroes are always removed.
+    pub precision: Option<usize>,
+
+    /// How much space to use.
+    pub spacing: Spacing,
+}
+
+impl FormatOptions {
+    /// The options used by [`AstNode::to_expression_string`].
+    pub const FULLY_PARENTHESISED: Self = Self {
+        parentheses: Parentheses::All,
+        precision: None,
+        spacing: Spacing::Spaced,
+    };
+
+    /// Format this number with the given precision.
+    fn number(&self, number: f64) -> String {
+        let Some(precision) = self.precision else {
+            return number.to_string();
+        };
+
+        let string = format!("{number:.precision$}");
+        let string = if string.contains('.') {
+            string.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            &string
+        };
+
+        // Rounding a small negative number shouldn't give a negative zero
+        if string == "-0" {
+            "0".to_string()
+        } else {
+            string.to_string()
+        }
+    }
+
+    /// Format a binary operator with the right spacing.
+    fn operator(&self, symbol: &str) -> String {
+        match self.spacing {
+            Spacing::Spaced => format!(" {symbol} "),
+            Spacing::Compact => symbol.to_string(),
+        }
+    }
+
+    /// Format a separator, like a comma or semicolon, with the right spacing.
+    fn separator(&self, symbol: char) -> String {
+        match self.spacing {
+            Spacing::Spaced => format!("{symbol} "),
+            Spacing::Compact => symbol.to_string(),
+        }
+    }
+
+    /// Format the rows of a matrix or vector literal.
+    fn literal(&self, rows: &[&[f64]]) -> String {
+        let rows: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&number| self.number(number))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        format!("[{}]", rows.join(&self.separator(';')))
+    }
+}
+
+/// How tightly a node binds to its operands, from loosest to tightest, following the grammar in
+/// the [`parser`](super::parser).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    /// Addition and subtraction.
+    Add,
+
+    /// Multiplication.
+    Multiply,
+
+    /// Division.
+    Divide,
+
+    /// Negation, including negative numbers.
+    Negate,
+
+    /// Exponentiation.
+    Exponent,
+
+    /// Indexing.
+    Index,
+
+    /// Names, numbers, literals, and function calls, which never need parentheses.
+    Atom,
+}
+
+/// A formatted subexpression, before any parentheses have been put around it.
+pub(super) struct Formatted {
+    /// The formatted string.
+    pub(super) string: String,
+
+    /// The string of the negated operand, if this node is a negation, so that adding it can be
+    /// written as a subtraction.
+    negated: Option<String>,
+}
+
+impl AstNode {
+    /// Convert this AST node into an expression string, formatted with the given options.
+    ///
+    /// With the default options, this only uses parentheses where they're needed, which makes it
+    /// good for echoing an expression back to the user. With [minimal](Parentheses::Minimal)
+    /// parentheses, parsing the formatted string always gives back the same AST.
+    ///
+    /// Like [`evaluate`](Self::evaluate), this doesn't recurse, so it can't overflow the stack.
+    ///
+    /// ```
+    /// # use trinity::matrix::expression::{format::{FormatOptions, Spacing}, parse_expression_from_string};
+    /// let ast = parse_expression_from_string("(2 / 3) * M + X / 4 - (Y ^ {-1})").unwrap();
+    ///
+    /// assert_eq!(ast.to_expression_string(), "(((2 / 3) * M) + (X / 4)) + (-(Y ^ {-1}))");
+    /// assert_eq!(ast.to_formatted_string(&FormatOptions::default()), "2 / 3 * M + X / 4 - Y ^ {-1}");
+    /// assert_eq!(
+    ///     ast.to_formatted_string(&FormatOptions {
+    ///         precision: Some(3),
+    ///         spacing: Spacing::Compact,
+    ///         ..Default::default()
+    ///     }),
+    ///     "2/3*M+X/4-Y^{-1}"
+    /// );
+    /// ```
+    pub fn to_formatted_string(&self, options: &FormatOptions) -> String {
+        let result: Result<Formatted, Infallible> = self.try_fold_iteratively(
+            |_| Ok(()),
+            |node, children| Ok(node.format_node(options, children)),
+        );
+        result.unwrap_or_else(|never| match never {}).string
+    }
+
+    /// Format just this node as an expression string, given the formatted strings of its
+    /// [`children`](Self::children).
+    pub(super) fn format_node(
+        &self,
+        options: &FormatOptions,
+        children: Vec<Formatted>,
+    ) -> Formatted {
+        let nodes = self.children();
+
+        // Adding a negation is really a subtraction, whose right operand only needs parentheses
+        // if it's another addition
+        if let (Parentheses::Minimal, Self::Add { right, .. }) = (options.parentheses, self) {
+            if let Self::Negate(operand) = &**right {
+                let [left, right]: [Formatted; 2] = children
+                    .try_into()
+                    .unwrap_or_else(|_| unreachable!("Addition should have two children"));
+                let left = self.parenthesise(options, 0, nodes[0], left.string);
+                let operand_string = right
+                    .negated
+                    .expect("The negated operand should have been kept");
+                let operand_string = if operand.precedence() < Precedence::Multiply {
+                    format!("({operand_string})")
+                } else {
+                    operand_string
+                };
+
+                return Formatted {
+                    string: format!("{left}{}{operand_string}", options.operator("-")),
+                    negated: None,
+                };
+            }
+        }
+
+        let negated = match self {
+            Self::Negate(_) => children.first().map(|operand| operand.string.clone()),
+            _ => None,
+        };
+        let mut children =
+            nodes
+                .iter()
+                .zip(children)
+                .enumerate()
+                .map(|(index, (child, formatted))| {
+                    self.parenthesise(options, index, child, formatted.string)
+                });
+        let mut next = || {
+            children
+                .next()
+                .expect("Every child should have been formatted before its parent")
+        };
+
+        let comma = options.separator(',');
+        let string = match self {
+            Self::Multiply { .. } => format!("{}{}{}", next(), options.operator("*"), next()),
+            Self::Divide { .. } => format!("{}{}{}", next(), options.operator("/"), next()),
+            Self::Add { .. } => format!("{}{}{}", next(), options.operator("+"), next()),
+            Self::Negate(_) => format!("-{}", next()),
+            Self::Exponent { power, .. } => {
+                let base = next();
+
+                // The braces also act as parens, so the power never needs both. With minimal
+                // parentheses, single terms don't need either
+                let braces = match options.parentheses {
+                    Parentheses::All => true,
+                    Parentheses::Minimal => {
+                        !power.is_transpose_marker()
+                            && !self.needs_parens(options, 1, power)
+                            && power.precedence() < Precedence::Atom
+                    }
+                };
+                let power = if power.is_transpose_marker() {
+                    "T".to_string()
+                } else {
+                    next()
+                };
+
+                if braces {
+                    format!("{base}{}{{{power}}}", options.operator("^"))
+                } else {
+                    format!("{base}{}{power}", options.operator("^"))
+                }
+            }
+            Self::Number(number) => options.number(*number),
+            Self::Imaginary(number) if *number == 1. => "i".to_string(),
+            Self::Imaginary(number) => format!("{}i", options.number(*number)),
+            Self::NamedMatrix(MatrixName { name }) => name.to_string(),
+            Self::Variable(name) => name.to_string(),
+            Self::RotationMatrix { degrees } => format!("rot({})", options.number(*degrees)),
+            Self::Anonymous2dMatrix(DMat2 { x_axis, y_axis }) => {
+                options.literal(&[&[x_axis.x, y_axis.x], &[x_axis.y, y_axis.y]])
+            }
+            Self::Anonymous3dMatrix(DMat3 {
+                x_axis,
+                y_axis,
+                z_axis,
+            }) => options.literal(&[
+                &[x_axis.x, y_axis.x, z_axis.x],
+                &[x_axis.y, y_axis.y, z_axis.y],
+                &[x_axis.z, y_axis.z, z_axis.z],
+            ]),
+            Self::AnonymousDynamicMatrix(matrix) => {
+                let rows = matrix.to_rows();
+                options.literal(&rows.iter().map(Vec::as_slice).collect::<Vec<_>>())
+            }
+            Self::Anonymous2dVector(DVec2 { x, y }) => options.literal(&[&[*x], &[*y]]),
+            Self::Anonymous3dVector(DVec3 { x, y, z }) => options.literal(&[&[*x], &[*y], &[*z]]),
+            Self::DotProduct { .. } => format!("dot({}{comma}{})", next(), next()),
+            Self::CrossProduct { .. } => format!("cross({}{comma}{})", next(), next()),
+            Self::Index { row, column, .. } => format!("{}[{row}{comma}{column}]", next()),
+            Self::Row { .. } => format!("row({}{comma}{})", next(), next()),
+            Self::Column { .. } => format!("col({}{comma}{})", next(), next()),
+            Self::Augment { .. } => format!("aug({})", children.collect::<Vec<_>>().join(&comma)),
+            Self::Block { .. } => format!(
+                "block({}{comma}{}{}{}{comma}{})",
+                next(),
+                next(),
+                options.separator(';'),
+                next(),
+                next()
+            ),
+            Self::Solve { .. } => format!("solve({}{comma}{})", next(), next()),
+            Self::LeastSquares { .. } => format!("lstsq({}{comma}{})", next(), next()),
+            Self::Translation { .. } => format!("translate({}{comma}{})", next(), next()),
+            Self::Random {
+                distribution: RandomDistribution::Uniform,
+                ..
+            } => format!("rand({}{comma}{})", next(), next()),
+            Self::Random { distribution, .. } => {
+                format!("rand({}{comma}{}{comma}{distribution})", next(), next())
+            }
+            Self::Norm(_) => format!("norm({})", next()),
+            Self::Adjugate(_) => format!("adj({})", next()),
+            Self::Cofactor { .. } => {
+                format!("cofactor({}{comma}{}{comma}{})", next(), next(), next())
+            }
+            Self::Rank(_) => format!("rank({})", next()),
+            Self::Exponential(_) => format!("exp({})", next()),
+            Self::Logarithm(_) => format!("logm({})", next()),
+            Self::HasProperty { property, .. } => format!("{property}({})", next()),
+            Self::MatrixNorm { norm, .. } => format!("norm({}{comma}{norm})", next()),
+            Self::PseudoInverse(_) => format!("pinv({})", next()),
+            Self::LineProjection(_) => format!("proj_line({})", next()),
+            Self::PlaneProjection(_) => format!("proj_plane({})", next()),
+        };
+
+        Formatted { string, negated }
+    }
+
+    /// Put this formatted child in parentheses if it [needs them](Self::needs_parens).
+    fn parenthesise(
+        &self,
+        options: &FormatOptions,
+        index: usize,
+        child: &Self,
+        string: String,
+    ) -> String {
+        if self.needs_parens(options, index, child) {
+            format!("({string})")
+        } else {
+            string
+        }
+    }
+
+    /// Does this child at this index in [`children`](Self::children) need parentheses around it
+    /// to keep the structure of the expression when it's parsed again?
+    fn needs_parens(&self, options: &FormatOptions, index: usize, child: &Self) -> bool {
+        match options.parentheses {
+            Parentheses::All => child.is_compound() && self.child_needs_parens(index),
+            Parentheses::Minimal => {
+                let precedence = child.precedence();
+                match self {
+                    // Addition and division are left associative, and multiplication is right
+                    // associative
+                    Self::Add { .. } if index == 0 => precedence < Precedence::Add,
+                    Self::Add { .. } => precedence < Precedence::Multiply,
+                    Self::Multiply { .. } if index == 0 => precedence < Precedence::Divide,
+                    Self::Multiply { .. } => precedence < Precedence::Multiply,
+                    Self::Divide { .. } if index == 0 => precedence < Precedence::Divide,
+                    Self::Divide { .. } => precedence < Precedence::Negate,
+
+                    // A negation applies to a single term, so anything after it would apply
+                    // to the negation instead, like `-A ^ 2` meaning `(-A) ^ 2`
+                    Self::Negate(_) => {
+                        precedence != Precedence::Negate && precedence < Precedence::Atom
+                    }
+
+                    // The parser would accept a negated base, but it's confusing to read, so
+                    // we only leave out the parens for indices and single terms
+                    Self::Exponent { .. } if index == 0 => precedence < Precedence::Index,
+                    Self::Exponent { .. } => {
+                        precedence != Precedence::Negate && precedence < Precedence::Exponent
+                    }
+                    Self::Index { .. } => precedence < Precedence::Atom,
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    /// How tightly this node binds to its operands.
+    fn precedence(&self) -> Precedence {
+        match self {
+            Self::Add { .. } => Precedence::Add,
+            Self::Multiply { .. } => Precedence::Multiply,
+            Self::Divide { .. } => Precedence::Divide,
+            Self::Negate(_) => Precedence::Negate,
+            Self::Number(number) | Self::Imaginary(number) if number.is_sign_negative() => {
+                Precedence::Negate
+            }
+            Self::Exponent { .. } => Precedence::Exponent,
+            Self::Index { .. } => Precedence::Index,
+            _ => Precedence::Atom,
+        }
+    }
+
+    /// Is this node made of several terms joined by an operator, so that it needs parentheses
+    /// when it's used inside another operator?
+    fn is_compound(&self) -> bool {
+        matches!(
+            self,
+            Self::Multiply { .. }
+                | Self::Divide { .. }
+                | Self::Add { .. }
+                | Self::Negate(_)
+                | Self::Exponent { .. }
+        )
+    }
+
+    /// Does the child at this index in [`children`](Self::children) need parentheses around it
+    /// if it's [compound](Self::is_compound) and we're putting in every parenthesis?
+    ///
+    /// Operators need their operands in parentheses, but function arguments and powers in braces
+    /// are already delimited.
+    fn child_needs_parens(&self, index: usize) -> bool {
+        match self {
+            Self::Multiply { .. }
+            | Self::Divide { .. }
+            | Self::Add { .. }
+            | Self::Negate(_)
+            | Self::Index { .. } => true,
+            Self::Exponent { .. } => index == 0,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::expression::parse_expression_from_string;
+
+    /// Parse this expression, panicking if it's invalid.
+    fn parse(expression: &str) -> AstNode {
+        parse_expression_from_string(expression).unwrap()
+    }
+
+    /// Parse this expression and format it with the default options.
+    fn minimal(expression: &str) -> String {
+        parse(expression).to_formatted_string(&FormatOptions::default())
+    }
+
+    #[test]
+    fn to_formatted_string_minimal_parens() {
+        assert_eq!(minimal("((2 / 3) * M) + (X / 4)"), "2 / 3 * M + X / 4");
+        assert_eq!(minimal("(A + B) + C"), "A + B + C");
+        assert_eq!(minimal("A + (B + C)"), "A + (B + C)");
+        assert_eq!(minimal("A - B - C"), "A - B - C");
+        assert_eq!(minimal("A - (B - C)"), "A - (B - C)");
+        assert_eq!(minimal("A - (B * C)"), "A - B * C");
+        assert_eq!(minimal("A - -B"), "A - -B");
+        assert_eq!(minimal("(A * B) * C"), "(A * B) * C");
+        assert_eq!(minimal("A * (B * C)"), "A * B * C");
+        assert_eq!(minimal("(A / B) / C"), "A / B / C");
+        assert_eq!(minimal("A / (B / C)"), "A / (B / C)");
+        assert_eq!(minimal("A / (B * C)"), "A / (B * C)");
+        assert_eq!(minimal("(A + B) * -C"), "(A + B) * -C");
+        assert_eq!(minimal("-(A * B)"), "-(A * B)");
+        assert_eq!(minimal("--A"), "--A");
+        assert_eq!(minimal("(-A) ^ 2"), "(-A) ^ 2");
+        assert_eq!(minimal("-(A ^ 2)"), "-(A ^ 2)");
+        assert_eq!(minimal("(A ^ 2) ^ T"), "(A ^ 2) ^ T");
+        assert_eq!(minimal("A ^ (2 ^ 3)"), "A ^ {2 ^ 3}");
+        assert_eq!(minimal("A ^ {-1}"), "A ^ {-1}");
+        assert_eq!(minimal("A ^ (1 + 1)"), "A ^ (1 + 1)");
+        assert_eq!(minimal("A[1, 2] ^ 2"), "A[1, 2] ^ 2");
+        assert_eq!(minimal("(A * B)[1, 2]"), "(A * B)[1, 2]");
+        assert_eq!(minimal("-(A[1, 2])"), "-(A[1, 2])");
+        assert_eq!(
+            minimal("dot((A + B), (C * D)) + block(A, U; V, 1)"),
+            "dot(A + B, C * D) + block(A, U; V, 1)"
+        );
+    }
+
+    #[test]
+    fn to_formatted_string_round_trips() {
+        for expression in [
+            "((2 / 3) * M) + (X / 4)",
+            "A + (B + C) - (D - E) * F",
+            "((A * B) * C) / (D / E) / F",
+            "-(A * B) + (-A) ^ 2 - -(A ^ 2) + --A",
+            "(A ^ 2) ^ T + A ^ (2 ^ 3) + A ^ {-1} + A ^ (1 + 1)",
+            "-(A[1, 2]) + (A * B)[1, 2] + (-A)[2, 1] + A[1, 2] ^ -2",
+            "(A + B) * -C / -(D / E)",
+            "rot(45) * [1 2; 3 4.5] + [1; 2] * [1 2 3; 4 5 6; 7 8 9]",
+            "aug(A + B, [1; 2; 3], C) + cofactor(A * B, 1, 2) * rank(adj(A))",
+            "solve(A, V) + norm(cross(U, V)) * row(A, 1) - col(A, 2)",
+            "exp(2 * A) * exp(-1) + exp(A ^ T) - logm(exp(A))",
+            "(3 + 2i) * A - i * B ^ {2i} + -2.5i",
+            "is_orthogonal(A) * is_symmetric(A ^ T * A) + is_singular(A - B) - is_rotation(rot(30))",
+            "norm(A, \"fro\") + norm(A * B, \"spectral\") / norm(A ^ T, \"max\") - norm(A)",
+            "pinv(A) * B - pinv(pinv(A - B) ^ T)",
+            "proj_line(30) * A + proj_line(-45 / 2) - proj_plane(cross(U, V)) * proj_plane([1; 2; 3])",
+            "lstsq(A, V) + 2 * lstsq(A * B, solve(B, V))",
+            "2 * x * A - angle ^ 2 + rot(45) * -scale_factor / t",
+            "translate(1, -2.5) * [1 0 0; 0 1 0; 0 0 1] - translate(norm(V), 3 * 4)",
+            "rand(2, 42) + rand(1 + 1, seed, \"integer\") * rand(2, 7, \"orthogonal\")",
+            "[1, 0, 0, 0; 0, 1, 0, 0; 0, 0, 1, 0; 0, 0, 0, 1] * [1 2 3 4 5; 1 2 3 4 5; 1 2 3 4 5; 1 2 3 4 5; 1 2 3 4 5.5]",
+        ] {
+            let ast = parse(expression);
+            for spacing in [Spacing::Spaced, Spacing::Compact] {
+                let formatted = ast.to_formatted_string(&FormatOptions {
+                    spacing,
+                    ..Default::default()
+                });
+                assert_eq!(parse(&formatted), ast, "{expression} -> {formatted}");
+            }
+        }
+    }
+
+    #[test]
+    fn to_formatted_string_precision_and_spacing() {
+        let ast = parse("0.125 * [1.5 2; 3 4.25] + dot(A, rot(33.375)) - 2.0625");
+
+        assert_eq!(
+            ast.to_formatted_string(&FormatOptions::default()),
+            "0.125 * [1.5 2; 3 4.25] + dot(A, rot(33.375)) - 2.0625"
+        );
+        assert_eq!(
+            ast.to_formatted_string(&FormatOptions {
+                precision: Some(2),
+                ..Default::default()
+            }),
+            "0.12 * [1.5 2; 3 4.25] + dot(A, rot(33.38)) - 2.06"
+        );
+        assert_eq!(
+            ast.to_formatted_string(&FormatOptions {
+                precision: Some(0),
+                spacing: Spacing::Compact,
+                ..Default::default()
+            }),
+            "0*[2 2;3 4]+dot(A,rot(33))-2"
+        );
+        assert_eq!(
+            AstNode::Number(-0.0001).to_formatted_string(&FormatOptions {
+                precision: Some(2),
+                ..Default::default()
+            }),
+            "0"
+        );
+
+        assert_eq!(
+            ast.to_formatted_string(&FormatOptions::FULLY_PARENTHESISED),
+            ast.to_expression_string()
+        );
+        assert_eq!(
+            ast.to_expression_string(),
+            "((0.125 * [1.5 2; 3 4.25]) + dot(A, rot(33.375))) + (-2.0625)"
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! This module handles rendering ASTs as LaTeX. See [`AstNode::to_latex`].
+
+use super::ast::AstNode;
+use crate::math::{MatrixNorm, RandomDistribution};
+use std::{convert::Infallible, fmt};
+
+impl AstNode {
+    /// Render this AST as a LaTeX math expression, suitable for putting inside `$...$`.
+    ///
+    /// Matrix and vector literals become `pmatrix` environments, divisions become fractions, and
+    /// transposes and inverses are written as `^{T}` and `^{-1}`. Products are written by putting
+    /// the terms next to each other, unless the right term starts with a digit. The
+    /// `amsmath` package is needed for the matrices and operator names.
+    ///
+    /// ```
+    /// # use trinity::matrix::expression::parse_expression_from_string;
+    /// let latex = |expression| {
+    ///     parse_expression_from_string(expression)
+    ///         .unwrap()
+    ///         .to_latex()
+    /// };
+    ///
+    /// assert_eq!(latex("2 * A ^ T - B / 3"), r"2 A^{T} - \frac{B}{3}");
+    /// assert_eq!(latex("M_one ^ {-1} * [1; 2]"), r"M_{\mathrm{one}}^{-1} \begin{pmatrix} 1 \\ 2 \end{pmatrix}");
+    /// assert_eq!(latex("norm((A + B) * 2)"), r"\left\lVert \left(A + B\right) \cdot 2 \right\rVert");
+    /// ```
+    pub fn to_latex(&self) -> String {
+        let result: Result<String, Infallible> =
+            self.try_fold_iteratively(|_| Ok(()), |node, children| Ok(node.latex_node(children)));
+        result.unwrap_or_else(|never| match never {})
+    }
+
+    /// Render just this node as LaTeX, given the rendered LaTeX of its
+    /// [`children`](Self::children).
+    fn latex_node(&self, children: Vec<String>) -> String {
+        let nodes = self.children();
+        let mut children = nodes
+            .iter()
+            .zip(children)
+            .enumerate()
+            .map(|(index, (child, latex))| {
+                if self.notation_child_needs_parens(index, child) {
+                    format!(r"\left({latex}\right)")
+                } else {
+                    latex
+                }
+            });
+        let mut next = || {
+            children
+                .next()
+                .expect("Every child should have been rendered before its parent")
+        };
+
+        match self {
+            Self::Multiply { .. } => {
+                let left = next();
+                let right = next();
+                if right.starts_with(|c: char| c.is_ascii_digit()) {
+                    format!(r"{left} \cdot {right}")
+                } else {
+                    format!("{left} {right}")
+                }
+            }
+            Self::Divide { .. } => format!(r"\frac{{{}}}{{{}}}", next(), next()),
+            Self::Add { right, .. } => {
+                let left = next();
+                let right_latex = next();
+                match right_latex.strip_prefix('-') {
+                    // Adding a negation is really a subtraction
+                    Some(rest) if matches!(**right, Self::Negate(_)) => format!("{left} - {rest}"),
+                    _ => format!("{left} + {right_latex}"),
+                }
+            }
+            Self::Negate(_) => format!("-{}", next()),
+            Self::Exponent { power, .. } => {
+                let base = next();
+                let power = if power.is_transpose_marker() {
+                    "T".to_string()
+                } else {
+                    next()
+                };
+                format!("{base}^{{{power}}}")
+            }
+            Self::Number(number) => number.to_string(),
+            Self::Imaginary(number) if *number == 1. => "i".to_string(),
+            Self::Imaginary(number) => format!("{number}i"),
+            Self::NamedMatrix(name) => latex_name(name),
+            Self::Variable(name) => latex_name(name),
+            Self::RotationMatrix { degrees } => format!(r"\operatorname{{rot}}({degrees}^\circ)"),
+            Self::Anonymous2dMatrix(matrix) => pmatrix(&[
+                &[matrix.x_axis.x, matrix.y_axis.x],
+                &[matrix.x_axis.y, matrix.y_axis.y],
+            ]),
+            Self::Anonymous3dMatrix(matrix) => pmatrix(&[
+                &[matrix.x_axis.x, matrix.y_axis.x, matrix.z_axis.x],
+                &[matrix.x_axis.y, matrix.y_axis.y, matrix.z_axis.y],
+                &[matrix.x_axis.z, matrix.y_axis.z, matrix.z_axis.z],
+            ]),
+            Self::AnonymousDynamicMatrix(matrix) => {
+                let rows = matrix.to_rows();
+                pmatrix(&rows.iter().map(Vec::as_slice).collect::<Vec<_>>())
+            }
+            Self::Anonymous2dVector(vector) => pmatrix(&[&[vector.x], &[vector.y]]),
+            Self::Anonymous3dVector(vector) => pmatrix(&[&[vector.x], &[vector.y], &[vector.z]]),
+            Self::DotProduct { .. } => format!(r"{} \cdot {}", next(), next()),
+            Self::CrossProduct { .. } => format!(r"{} \times {}", next(), next()),
+            Self::Index { row, column, .. } => format!("{}_{{{row},{column}}}", next()),
+            Self::Row { .. } => format!(r"\operatorname{{row}}({}, {})", next(), next()),
+            Self::Column { .. } => format!(r"\operatorname{{col}}({}, {})", next(), next()),
+            Self::Augment { .. } => format!(
+                r"\begin{{pmatrix}} {} \end{{pmatrix}}",
+                children.collect::<Vec<_>>().join(" & ")
+            ),
+            Self::Block { .. } => format!(
+                r"\begin{{pmatrix}} {} & {} \\ {}^{{T}} & {} \end{{pmatrix}}",
+                next(),
+                next(),
+                next(),
+                next()
+            ),
+            Self::Solve { .. } => format!(r"\operatorname{{solve}}({}, {})", next(), next()),
+            Self::LeastSquares { .. } => {
+                format!(r"\operatorname{{lstsq}}({}, {})", next(), next())
+            }
+            Self::Translation { .. } => {
+                format!(r"\operatorname{{translate}}({}, {})", next(), next())
+            }
+            Self::Random {
+                distribution: RandomDistribution::Uniform,
+                ..
+            } => format!(r"\operatorname{{rand}}({}, {})", next(), next()),
+            Self::Random { distribution, .. } => format!(
+                r"\operatorname{{rand}}({}, {}, \text{{{}}})",
+                next(),
+                next(),
+                distribution.name()
+            ),
+            Self::Norm(_) => format!(r"\left\lVert {} \right\rVert", next()),
+            Self::Adjugate(_) => format!(r"\operatorname{{adj}}({})", next()),
+            Self::Cofactor { .. } => format!(
+                r"\operatorname{{cofactor}}({}, {}, {})",
+                next(),
+                next(),
+                next()
+            ),
+            Self::Rank(_) => format!(r"\operatorname{{rank}}({})", next()),
+            Self::Exponential(_) => format!(r"\exp\left({}\right)", next()),
+            Self::Logarithm(_) => format!(r"\log\left({}\right)", next()),
+            Self::HasProperty { property, .. } => {
+                format!(r"\operatorname{{is\_{}}}({})", property.name(), next())
+            }
+            Self::MatrixNorm { norm, .. } => {
+                let subscript = match norm {
+                    MatrixNorm::Frobenius => "F",
+                    MatrixNorm::Spectral => "2",
+                    MatrixNorm::MaxEntry => r"\max",
+                };
+                format!(r"\left\lVert {} \right\rVert_{{{subscript}}}", next())
+            }
+            Self::PseudoInverse(_) => format!(r"\operatorname{{pinv}}({})", next()),
+            Self::LineProjection(_) => format!(r"\operatorname{{proj\_line}}({})", next()),
+            Self::PlaneProjection(_) => format!(r"\operatorname{{proj\_plane}}({})", next()),
+        }
+    }
+
+    /// Does the child at this index in [`children`](Self::children) need parentheses around it
+    /// when this node is rendered in mathematical notation, like LaTeX or MathML?
+    pub(super) fn notation_child_needs_parens(&self, index: usize, child: &Self) -> bool {
+        let is_sum = matches!(child, Self::Add { .. });
+        let is_negation = matches!(child, Self::Negate(_));
+        let is_product = matches!(child, Self::Multiply { .. } | Self::Divide { .. });
+
+        match self {
+            // Fractions already group their parts, and addition is associative
+            Self::Divide { .. } | Self::Add { .. } => false,
+            Self::Multiply { .. } => is_sum || (index == 1 && is_negation),
+            Self::Negate(_) => is_sum || is_negation,
+            Self::Exponent { .. } => {
+                index == 0
+                    && (is_sum
+                        || is_negation
+                        || is_product
+                        || matches!(child, Self::Exponent { .. } | Self::Index { .. }))
+            }
+            Self::Index { .. } | Self::DotProduct { .. } | Self::CrossProduct { .. } => {
+                is_sum || is_negation || is_product || matches!(child, Self::Exponent { .. })
+            }
+            Self::Block { .. } => index == 2 && (is_sum || is_negation || is_product),
+            _ => false,
+        }
+    }
+}
+
+/// Render a matrix or variable name as LaTeX.
+///
+/// Single letters are left alone, since they're already italic in maths mode. Anything after
+/// the first underscore becomes an upright subscript, and longer names are made upright.
+fn latex_name(name: &impl fmt::Display) -> String {
+    /// Make a name upright, escaping any underscores.
+    fn upright(name: &str) -> String {
+        format!(r"\mathrm{{{}}}", name.replace('_', r"\_"))
+    }
+
+    let name = name.to_string();
+    let (head, subscript) = match name.split_once('_') {
+        Some((head, subscript)) if !subscript.is_empty() => (head, Some(subscript)),
+        _ => (name.as_str(), None),
+    };
+
+    let head = if head.chars().count() == 1 {
+        head.to_string()
+    } else {
+        upright(head)
+    };
+
+    match subscript {
+        Some(subscript) => format!("{head}_{{{}}}", upright(subscript)),
+        None => head,
+    }
+}
+
+/// Render these rows of numbers as a LaTeX `pmatrix`.
+fn pmatrix(rows: &[&[f64]]) -> String {
+    let rows: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(f64::to_string)
+                .collect::<Vec<_>>()
+                .join(" & ")
+        })
+        .collect();
+    format!(r"\begin{{pmatrix}} {} \end{{pmatrix}}", rows.join(r" \\ "))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::expression::parse_expression_from_string;
+
+    /// Parse this expression and render it as LaTeX.
+    fn latex(expression: &str) -> String {
+        parse_expression_from_string(expression).unwrap().to_latex()
+    }
+
+    #[test]
+    fn to_latex_operators() {
+        assert_eq!(latex("A * B"), "A B");
+        assert_eq!(latex("2 * 3"), r"2 \cdot 3");
+        assert_eq!(latex("A + B - C"), "A + B - C");
+        assert_eq!(latex("A - (B + C)"), r"A - \left(B + C\right)");
+        assert_eq!(latex("A - -B"), r"A - \left(-B\right)");
+        assert_eq!(latex("A * (2 * B)"), r"A \cdot 2 B");
+        assert_eq!(latex("(A + B) * C"), r"\left(A + B\right) C");
+        assert_eq!(latex("A * -B"), r"A \left(-B\right)");
+        assert_eq!(latex("--A"), r"-\left(-A\right)");
+        assert_eq!(latex("(A + B) / (2 * C)"), r"\frac{A + B}{2 C}");
+        assert_eq!(latex("A ^ T"), "A^{T}");
+        assert_eq!(latex("A ^ {-1}"), "A^{-1}");
+        assert_eq!(latex("(A * B) ^ 2"), r"\left(A B\right)^{2}");
+        assert_eq!(latex("(A ^ 2) ^ T"), r"\left(A^{2}\right)^{T}");
+        assert_eq!(latex("A ^ (1 + 1)"), "A^{1 + 1}");
+    }
+
+    #[test]
+    fn to_latex_terms() {
+        assert_eq!(latex("A"), "A");
+        assert_eq!(latex("Rot"), r"\mathrm{Rot}");
+        assert_eq!(latex("M_one_two"), r"M_{\mathrm{one\_two}}");
+        assert_eq!(latex("Big_matrix"), r"\mathrm{Big}_{\mathrm{matrix}}");
+        assert_eq!(latex("M_"), r"\mathrm{M\_}");
+        assert_eq!(latex("rot(90)"), r"\operatorname{rot}(90^\circ)");
+        assert_eq!(
+            latex("[1 2; 3 4]"),
+            r"\begin{pmatrix} 1 & 2 \\ 3 & 4 \end{pmatrix}"
+        );
+        assert_eq!(
+            latex("[1 2 3; 4 5 6; 7 8 9.5]"),
+            r"\begin{pmatrix} 1 & 2 & 3 \\ 4 & 5 & 6 \\ 7 & 8 & 9.5 \end{pmatrix}"
+        );
+        assert_eq!(
+            latex("[1; 2; 3]"),
+            r"\begin{pmatrix} 1 \\ 2 \\ 3 \end{pmatrix}"
+        );
+    }
+
+    #[test]
+    fn to_latex_functions() {
+        assert_eq!(
+            latex("dot(U_one, A * V)"),
+            r"U_{\mathrm{one}} \cdot \left(A V\right)"
+        );
+        assert_eq!(latex("cross(U, V)"), r"U \times V");
+        assert_eq!(latex("(A + B)[1, 2]"), r"\left(A + B\right)_{1,2}");
+        assert_eq!(
+            latex("row(A, 1) + col(A, 2)"),
+            r"\operatorname{row}(A, 1) + \operatorname{col}(A, 2)"
+        );
+        assert_eq!(latex("aug(U, V)"), r"\begin{pmatrix} U & V \end{pmatrix}");
+        assert_eq!(
+            latex("block(A, U; V, 1)"),
+            r"\begin{pmatrix} A & U \\ V^{T} & 1 \end{pmatrix}"
+        );
+        assert_eq!(latex("solve(A, V)"), r"\operatorname{solve}(A, V)");
+        assert_eq!(latex("exp(2 * A)"), r"\exp\left(2 A\right)");
+        assert_eq!(latex("norm(V)"), r"\left\lVert V \right\rVert");
+        assert_eq!(
+            latex("adj(A) + cofactor(A, 1, 2) * rank(A)"),
+            r"\operatorname{adj}(A) + \operatorname{cofactor}(A, 1, 2) \operatorname{rank}(A)"
+        );
+    }
+}
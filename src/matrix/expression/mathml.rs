@@ -0,0 +1,349 @@
+//! This module handles rendering ASTs as MathML. See [`AstNode::to_mathml`].
+
+use super::ast::AstNode;
+use crate::math::RandomDistribution;
+use std::{convert::Infallible, fmt};
+
+/// The invisible operator for multiplication, so that screen readers can read `AB` as "A times
+/// B".
+const INVISIBLE_TIMES: &str = "<mo>&#x2062;</mo>";
+
+impl AstNode {
+    /// Render this AST as a presentation MathML `<math>` element, which can be embedded
+    /// directly in a web page.
+    ///
+    /// The notation matches [`to_latex`](Self::to_latex), so matrix literals are written in
+    /// brackets, divisions are written as fractions, and so on.
+    ///
+    /// ```
+    /// # use trinity::matrix::expression::parse_expression_from_string;
+    /// let mathml = parse_expression_from_string("A ^ T / 2")
+    ///     .unwrap()
+    ///     .to_mathml();
+    ///
+    /// assert_eq!(
+    ///     mathml,
+    ///     concat!(
+    ///         r#"<math xmlns="http://www.w3.org/1998/Math/MathML">"#,
+    ///         r#"<mfrac><msup><mi>A</mi><mi mathvariant="normal">T</mi></msup><mn>2</mn></mfrac>"#,
+    ///         "</math>"
+    ///     )
+    /// );
+    /// ```
+    pub fn to_mathml(&self) -> String {
+        let result: Result<String, Infallible> =
+            self.try_fold_iteratively(|_| Ok(()), |node, children| Ok(node.mathml_node(children)));
+        let body = result.unwrap_or_else(|never| match never {});
+        format!(r#"<math xmlns="http://www.w3.org/1998/Math/MathML">{body}</math>"#)
+    }
+
+    /// Render just this node as MathML, given the rendered MathML of its
+    /// [`children`](Self::children).
+    fn mathml_node(&self, children: Vec<String>) -> String {
+        let nodes = self.children();
+        let mut children =
+            nodes
+                .iter()
+                .zip(children)
+                .enumerate()
+                .map(|(index, (child, mathml))| {
+                    if self.notation_child_needs_parens(index, child) {
+                        parens(&mathml)
+                    } else {
+                        mathml
+                    }
+                });
+        let mut next = || {
+            children
+                .next()
+                .expect("Every child should have been rendered before its parent")
+        };
+
+        match self {
+            Self::Multiply { .. } => {
+                let left = next();
+                let right = next();
+                if right.trim_start_matches("<mrow>").starts_with("<mn>") {
+                    format!("<mrow>{left}<mo>&#x22C5;</mo>{right}</mrow>")
+                } else {
+                    format!("<mrow>{left}{INVISIBLE_TIMES}{right}</mrow>")
+                }
+            }
+            Self::Divide { .. } => format!("<mfrac>{}{}</mfrac>", next(), next()),
+            Self::Add { right, .. } => {
+                let left = next();
+                let right_mathml = next();
+                let subtracted = right_mathml
+                    .strip_prefix("<mrow><mo>-</mo>")
+                    .and_then(|rest| rest.strip_suffix("</mrow>"));
+                match subtracted {
+                    // Adding a negation is really a subtraction
+                    Some(rest) if matches!(**right, Self::Negate(_)) => {
+                        format!("<mrow>{left}<mo>-</mo>{rest}</mrow>")
+                    }
+                    _ => format!("<mrow>{left}<mo>+</mo>{right_mathml}</mrow>"),
+                }
+            }
+            Self::Negate(_) => format!("<mrow><mo>-</mo>{}</mrow>", next()),
+            Self::Exponent { power, .. } => {
+                let base = next();
+                let power = if power.is_transpose_marker() {
+                    r#"<mi mathvariant="normal">T</mi>"#.to_string()
+                } else {
+                    next()
+                };
+                format!("<msup>{base}{power}</msup>")
+            }
+            Self::Number(number) => number_mathml(*number),
+            Self::Imaginary(number) if *number == 1. => "<mi>i</mi>".to_string(),
+            Self::Imaginary(number) => format!("<mrow>{}<mi>i</mi></mrow>", number_mathml(*number)),
+            Self::NamedMatrix(name) => name_mathml(name),
+            Self::Variable(name) => name_mathml(name),
+            Self::RotationMatrix { degrees } => function(
+                "rot",
+                &[format!(
+                    "<mrow>{}<mo>&#xB0;</mo></mrow>",
+                    number_mathml(*degrees)
+                )],
+            ),
+            Self::Anonymous2dMatrix(matrix) => table(&[
+                vec![
+                    number_mathml(matrix.x_axis.x),
+                    number_mathml(matrix.y_axis.x),
+                ],
+                vec![
+                    number_mathml(matrix.x_axis.y),
+                    number_mathml(matrix.y_axis.y),
+                ],
+            ]),
+            Self::Anonymous3dMatrix(matrix) => table(&[
+                vec![
+                    number_mathml(matrix.x_axis.x),
+                    number_mathml(matrix.y_axis.x),
+                    number_mathml(matrix.z_axis.x),
+                ],
+                vec![
+                    number_mathml(matrix.x_axis.y),
+                    number_mathml(matrix.y_axis.y),
+                    number_mathml(matrix.z_axis.y),
+                ],
+                vec![
+                    number_mathml(matrix.x_axis.z),
+                    number_mathml(matrix.y_axis.z),
+                    number_mathml(matrix.z_axis.z),
+                ],
+            ]),
+            Self::AnonymousDynamicMatrix(matrix) => table(
+                &matrix
+                    .to_rows()
+                    .into_iter()
+                    .map(|row| row.into_iter().map(number_mathml).collect())
+                    .collect::<Vec<_>>(),
+            ),
+            Self::Anonymous2dVector(vector) => {
+                table(&[vec![number_mathml(vector.x)], vec![number_mathml(vector.y)]])
+            }
+            Self::Anonymous3dVector(vector) => table(&[
+                vec![number_mathml(vector.x)],
+                vec![number_mathml(vector.y)],
+                vec![number_mathml(vector.z)],
+            ]),
+            Self::DotProduct { .. } => {
+                format!("<mrow>{}<mo>&#x22C5;</mo>{}</mrow>", next(), next())
+            }
+            Self::CrossProduct { .. } => {
+                format!("<mrow>{}<mo>&#xD7;</mo>{}</mrow>", next(), next())
+            }
+            Self::Index { row, column, .. } => format!(
+                "<msub>{}<mrow><mn>{row}</mn><mo>,</mo><mn>{column}</mn></mrow></msub>",
+                next()
+            ),
+            Self::Row { .. } => function("row", &[next(), next()]),
+            Self::Column { .. } => function("col", &[next(), next()]),
+            Self::Augment { .. } => table(&[children.collect()]),
+            Self::Block { .. } => {
+                let top = vec![next(), next()];
+                let bottom_left =
+                    format!(r#"<msup>{}<mi mathvariant="normal">T</mi></msup>"#, next());
+                table(&[top, vec![bottom_left, next()]])
+            }
+            Self::Solve { .. } => function("solve", &[next(), next()]),
+            Self::LeastSquares { .. } => function("lstsq", &[next(), next()]),
+            Self::Translation { .. } => function("translate", &[next(), next()]),
+            Self::Random {
+                distribution: RandomDistribution::Uniform,
+                ..
+            } => function("rand", &[next(), next()]),
+            Self::Random { distribution, .. } => function(
+                "rand",
+                &[
+                    next(),
+                    next(),
+                    format!("<mtext>{}</mtext>", distribution.name()),
+                ],
+            ),
+            Self::Norm(_) => format!("<mrow><mo>&#x2016;</mo>{}<mo>&#x2016;</mo></mrow>", next()),
+            Self::Adjugate(_) => function("adj", &[next()]),
+            Self::Cofactor { .. } => function("cofactor", &[next(), next(), next()]),
+            Self::Rank(_) => function("rank", &[next()]),
+            Self::Exponential(_) => function("exp", &[next()]),
+            Self::Logarithm(_) => function("log", &[next()]),
+            Self::HasProperty { property, .. } => function(&property.to_string(), &[next()]),
+            Self::MatrixNorm { norm, .. } => format!(
+                "<msub><mrow><mo>&#x2016;</mo>{}<mo>&#x2016;</mo></mrow><mi>{}</mi></msub>",
+                next(),
+                norm.name()
+            ),
+            Self::PseudoInverse(_) => function("pinv", &[next()]),
+            Self::LineProjection(_) => function("proj_line", &[next()]),
+            Self::PlaneProjection(_) => function("proj_plane", &[next()]),
+        }
+    }
+}
+
+/// Render a number as MathML, with the sign as a separate operator.
+fn number_mathml(number: f64) -> String {
+    if number < 0. {
+        format!("<mrow><mo>-</mo><mn>{}</mn></mrow>", -number)
+    } else {
+        format!("<mn>{number}</mn>")
+    }
+}
+
+/// Render a matrix or variable name as MathML, with the same conventions as LaTeX rendering.
+///
+/// Single letters are italic identifiers, and longer names are upright. Anything after the
+/// first underscore becomes an upright subscript.
+fn name_mathml(name: &impl fmt::Display) -> String {
+    /// Render a part of the name as an identifier.
+    fn identifier(name: &str) -> String {
+        if name.chars().count() == 1 {
+            format!("<mi>{name}</mi>")
+        } else {
+            format!(r#"<mi mathvariant="normal">{name}</mi>"#)
+        }
+    }
+
+    let name = name.to_string();
+    match name.split_once('_') {
+        Some((head, subscript)) if !subscript.is_empty() => format!(
+            r#"<msub>{}<mi mathvariant="normal">{subscript}</mi></msub>"#,
+            identifier(head)
+        ),
+        _ => identifier(&name),
+    }
+}
+
+/// Wrap this MathML in parentheses.
+fn parens(mathml: &str) -> String {
+    format!("<mrow><mo>(</mo>{mathml}<mo>)</mo></mrow>")
+}
+
+/// Render a function application, like `adj(A)`.
+fn function(name: &str, arguments: &[String]) -> String {
+    format!(
+        "<mrow><mi>{name}</mi><mo>&#x2061;</mo>{}</mrow>",
+        parens(&arguments.join("<mo>,</mo>"))
+    )
+}
+
+/// Render these rows of MathML as a table in parentheses.
+fn table(rows: &[Vec<String>]) -> String {
+    let rows: String = rows
+        .iter()
+        .map(|row| {
+            let cells: String = row
+                .iter()
+                .map(|cell| format!("<mtd>{cell}</mtd>"))
+                .collect();
+            format!("<mtr>{cells}</mtr>")
+        })
+        .collect();
+    parens(&format!("<mtable>{rows}</mtable>"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::expression::parse_expression_from_string;
+
+    /// Parse this expression and render it as MathML, without the surrounding `<math>` element.
+    fn mathml(expression: &str) -> String {
+        let mathml = parse_expression_from_string(expression)
+            .unwrap()
+            .to_mathml();
+        mathml
+            .strip_prefix(r#"<math xmlns="http://www.w3.org/1998/Math/MathML">"#)
+            .and_then(|mathml| mathml.strip_suffix("</math>"))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn to_mathml_operators() {
+        assert_eq!(
+            mathml("A * B"),
+            "<mrow><mi>A</mi><mo>&#x2062;</mo><mi>B</mi></mrow>"
+        );
+        assert_eq!(
+            mathml("A * 2"),
+            "<mrow><mi>A</mi><mo>&#x22C5;</mo><mn>2</mn></mrow>"
+        );
+        assert_eq!(
+            mathml("A - B"),
+            "<mrow><mi>A</mi><mo>-</mo><mi>B</mi></mrow>"
+        );
+        assert_eq!(
+            mathml("-(A + B)"),
+            "<mrow><mo>-</mo><mrow><mo>(</mo><mrow><mi>A</mi><mo>+</mo><mi>B</mi></mrow><mo>)</mo></mrow></mrow>"
+        );
+        assert_eq!(
+            mathml("A ^ {-1}"),
+            "<msup><mi>A</mi><mrow><mo>-</mo><mn>1</mn></mrow></msup>"
+        );
+        assert_eq!(
+            mathml("(A + B) / 2"),
+            "<mfrac><mrow><mi>A</mi><mo>+</mo><mi>B</mi></mrow><mn>2</mn></mfrac>"
+        );
+    }
+
+    #[test]
+    fn to_mathml_terms() {
+        assert_eq!(mathml("Rot"), r#"<mi mathvariant="normal">Rot</mi>"#);
+        assert_eq!(
+            mathml("M_one"),
+            r#"<msub><mi>M</mi><mi mathvariant="normal">one</mi></msub>"#
+        );
+        assert_eq!(
+            mathml("[1 2; 3 4]"),
+            "<mrow><mo>(</mo><mtable><mtr><mtd><mn>1</mn></mtd><mtd><mn>2</mn></mtd></mtr><mtr><mtd><mn>3</mn></mtd><mtd><mn>4</mn></mtd></mtr></mtable><mo>)</mo></mrow>"
+        );
+        assert_eq!(
+            mathml("rot(90)"),
+            "<mrow><mi>rot</mi><mo>&#x2061;</mo><mrow><mo>(</mo><mrow><mn>90</mn><mo>&#xB0;</mo></mrow><mo>)</mo></mrow></mrow>"
+        );
+    }
+
+    #[test]
+    fn to_mathml_functions() {
+        assert_eq!(
+            mathml("A[1, 2]"),
+            "<msub><mi>A</mi><mrow><mn>1</mn><mo>,</mo><mn>2</mn></mrow></msub>"
+        );
+        assert_eq!(
+            mathml("norm(V)"),
+            "<mrow><mo>&#x2016;</mo><mi>V</mi><mo>&#x2016;</mo></mrow>"
+        );
+        assert_eq!(
+            mathml("cofactor(A, 1, 2)"),
+            "<mrow><mi>cofactor</mi><mo>&#x2061;</mo><mrow><mo>(</mo><mi>A</mi><mo>,</mo><mn>1</mn><mo>,</mo><mn>2</mn><mo>)</mo></mrow></mrow>"
+        );
+        assert_eq!(
+            mathml("block(A, U; V, 1)"),
+            r#"<mrow><mo>(</mo><mtable><mtr><mtd><mi>A</mi></mtd><mtd><mi>U</mi></mtd></mtr><mtr><mtd><msup><mi>V</mi><mi mathvariant="normal">T</mi></msup></mtd><mtd><mn>1</mn></mtd></mtr></mtable><mo>)</mo></mrow>"#
+        );
+        assert_eq!(
+            mathml("aug(U, V)"),
+            "<mrow><mo>(</mo><mtable><mtr><mtd><mi>U</mi></mtd><mtd><mi>V</mi></mtd></mtr></mtable><mo>)</mo></mrow>"
+        );
+    }
+}
@@ -0,0 +1,422 @@
+//! This module handles evaluating expressions while only evaluating each distinct subexpression
+//! once. See [`AstNode::evaluate_memoised`].
+
+use super::ast::{
+    check_limits, snap_affine, AstNode, EvalOptions, EvaluationError, EvaluationLimits,
+    NumberOrMatrix,
+};
+use crate::matrix::map::MatrixMap;
+use glam::f64::{DMat2, DMat3, DVec2, DVec3};
+use smol_str::SmolStr;
+use std::{collections::HashMap, mem::Discriminant};
+
+/// Everything that identifies the structure of a single node, with its children given by the
+/// IDs of their own structures.
+///
+/// Two subtrees are structurally equal if and only if they have equal keys, so comparing keys is
+/// enough to find repeated subexpressions, without comparing whole subtrees.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct NodeKey {
+    /// The kind of node.
+    kind: Discriminant<AstNode>,
+
+    /// The name of the matrix or variable, if this is a named matrix or a variable.
+    name: Option<SmolStr>,
+
+    /// The numbers stored in this node itself, as bits so that they can be hashed.
+    data: Vec<u64>,
+
+    /// The structure IDs of the children of this node.
+    children: Vec<usize>,
+}
+
+impl NodeKey {
+    /// Build the key for this node, given the structure IDs of its
+    /// [`children`](AstNode::children).
+    fn new(node: &AstNode, children: Vec<usize>) -> Self {
+        let mut name = None;
+        let data = match node {
+            AstNode::Number(number)
+            | AstNode::Imaginary(number)
+            | AstNode::RotationMatrix { degrees: number } => {
+                vec![number.to_bits()]
+            }
+            AstNode::NamedMatrix(matrix) => {
+                name = Some(matrix.name.clone());
+                vec![]
+            }
+            AstNode::Variable(variable) => {
+                name = Some(variable.name.clone());
+                vec![]
+            }
+            AstNode::Anonymous2dMatrix(matrix) => bits(&DMat2::to_cols_array(matrix)),
+            AstNode::Anonymous3dMatrix(matrix) => bits(&DMat3::to_cols_array(matrix)),
+            AstNode::AnonymousDynamicMatrix(matrix) => bits(&matrix.to_rows().concat()),
+            AstNode::Anonymous2dVector(vector) => bits(&DVec2::to_array(vector)),
+            AstNode::Anonymous3dVector(vector) => bits(&DVec3::to_array(vector)),
+            AstNode::Index { row, column, .. } => vec![*row as u64, *column as u64],
+            AstNode::HasProperty { property, .. } => vec![*property as u64],
+            AstNode::MatrixNorm { norm, .. } => vec![*norm as u64],
+            AstNode::Random { distribution, .. } => vec![*distribution as u64],
+            _ => vec![],
+        };
+
+        Self {
+            kind: std::mem::discriminant(node),
+            name,
+            data,
+            children,
+        }
+    }
+}
+
+/// Get the bits of each of these numbers.
+fn bits(numbers: &[f64]) -> Vec<u64> {
+    numbers.iter().map(|number| number.to_bits()).collect()
+}
+
+/// The most distinct subexpression structures that an [`EvaluationCache`] remembers before it
+/// starts again from empty.
+const MAX_CACHED_STRUCTURES: usize = 1 << 16;
+
+/// A cache of the values of subexpressions, used by [`AstNode::evaluate_memoised`].
+///
+/// The cache can be reused across evaluations, even of different expressions, and it's only
+/// valid for the map and options that it was last used with. When it gets used with a map with a
+/// different [generation](MatrixMap::generation) or with different [`EvalOptions`], everything in
+/// it is thrown away, both the cached values and the structures of the subexpressions that it's
+/// seen. It's also emptied before an evaluation once it has seen too many distinct
+/// subexpressions, so evaluating an endless stream of new expressions against the same map
+/// doesn't grow it without bound.
+#[derive(Clone, Debug, Default)]
+pub struct EvaluationCache {
+    /// The ID of every distinct structure that we've seen.
+    structures: HashMap<NodeKey, usize>,
+
+    /// The value of every structure that we've evaluated, by ID.
+    values: HashMap<usize, NumberOrMatrix>,
+
+    /// The generation of the map that the values were computed with.
+    generation: Option<u64>,
+
+    /// The options that the values were computed with.
+    options: Option<EvalOptions>,
+}
+
+impl EvaluationCache {
+    /// Create a new, empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of subexpressions with cached values.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Are there no cached values?
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Forget everything in the cache.
+    pub fn clear(&mut self) {
+        self.structures.clear();
+        self.values.clear();
+        self.generation = None;
+        self.options = None;
+    }
+
+    /// Get the structure ID of every node in the tree, keyed by the address of the node, failing
+    /// if the tree goes past the given limits.
+    fn structure_ids(
+        &mut self,
+        ast: &AstNode,
+        limits: EvaluationLimits,
+    ) -> Result<HashMap<*const AstNode, usize>, EvaluationError> {
+        let mut ids = HashMap::new();
+
+        ast.try_fold_iteratively(check_limits(limits), |node, children| {
+            let next_id = self.structures.len();
+            let id = *self
+                .structures
+                .entry(NodeKey::new(node, children))
+                .or_insert(next_id);
+            ids.insert(node as *const AstNode, id);
+            Ok(id)
+        })?;
+
+        Ok(ids)
+    }
+}
+
+impl AstNode {
+    /// Evaluate this AST node like [`evaluate`](Self::evaluate), but only evaluate each distinct
+    /// subexpression once.
+    ///
+    /// In an expression like `(A * B) + (A * B)^T`, the product `A * B` is only computed once.
+    /// The values are kept in the cache, so evaluating this expression again (or another one
+    /// which shares subexpressions with it) reuses them until the map changes.
+    ///
+    /// ```
+    /// # use trinity::matrix::{MatrixName, expression::{memo::EvaluationCache, parse_expression_from_string}, map::prelude::*};
+    /// # use glam::DMat2;
+    /// let mut map = MatrixMap2::new();
+    /// map.set(MatrixName::new("A"), DMat2::from_cols_array(&[1., 2., 3., 4.])).unwrap();
+    /// map.set(MatrixName::new("B"), DMat2::from_cols_array(&[0., 1., -1., 0.])).unwrap();
+    ///
+    /// let ast = parse_expression_from_string("(A * B) + (A * B) ^ T").unwrap();
+    /// let mut cache = EvaluationCache::new();
+    ///
+    /// assert_eq!(ast.evaluate_memoised(&map, &mut cache), ast.clone().evaluate(&map));
+    ///
+    /// // A * B, (A * B) ^ T, and the addition
+    /// assert_eq!(cache.len(), 3);
+    /// ```
+    pub fn evaluate_memoised(
+        &self,
+        map: &impl MatrixMap,
+        cache: &mut EvaluationCache,
+    ) -> Result<NumberOrMatrix, EvaluationError> {
+        self.evaluate_memoised_with_options(map, EvalOptions::default(), cache)
+    }
+
+    /// Evaluate this AST node like [`evaluate_with_options`](Self::evaluate_with_options), but
+    /// only evaluate each distinct subexpression once. See
+    /// [`evaluate_memoised`](Self::evaluate_memoised).
+    pub fn evaluate_memoised_with_options(
+        &self,
+        map: &impl MatrixMap,
+        options: EvalOptions,
+        cache: &mut EvaluationCache,
+    ) -> Result<NumberOrMatrix, EvaluationError> {
+        /// A piece of work to do in the evaluation.
+        enum Task<'a> {
+            /// Evaluate this node, unless its value is already cached.
+            Enter(&'a AstNode),
+
+            /// Evaluate this node from the values of this many children.
+            Combine(&'a AstNode, usize),
+        }
+
+        if cache.generation != Some(map.generation())
+            || cache.options != Some(options)
+            || cache.structures.len() > MAX_CACHED_STRUCTURES
+        {
+            cache.clear();
+            cache.generation = Some(map.generation());
+            cache.options = Some(options);
+        }
+
+        let ids = cache.structure_ids(self, options.limits)?;
+        let id_of = |node: &AstNode| ids[&(node as *const AstNode)];
+
+        let mut tasks = vec![Task::Enter(self)];
+        let mut values: Vec<NumberOrMatrix> = vec![];
+
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Enter(node) => {
+                    if let Some(value) = cache.values.get(&id_of(node)) {
+                        values.push(value.clone());
+                    } else {
+                        let children = node.children();
+                        tasks.push(Task::Combine(node, children.len()));
+                        tasks.extend(children.into_iter().rev().map(Task::Enter));
+                    }
+                }
+                Task::Combine(node, child_count) => {
+                    let children = values.split_off(values.len() - child_count);
+                    let value = node.evaluate_node(map, options, children)?;
+
+                    // Leaves are cheap to evaluate, so there's no point caching them
+                    if child_count > 0 {
+                        cache.values.insert(id_of(node), value.clone());
+                    }
+                    values.push(value);
+                }
+            }
+        }
+
+        let value = values
+            .pop()
+            .expect("Evaluating a tree should always produce exactly one value");
+        snap_affine(value, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{expression::parse_expression_from_string, map::prelude::*, MatrixName};
+
+    /// Parse this expression, panicking if it's invalid.
+    fn parse(expression: &str) -> AstNode {
+        parse_expression_from_string(expression).unwrap()
+    }
+
+    #[test]
+    fn evaluate_memoised_matches_evaluate() {
+        let mut map = MatrixMap2::new();
+        map.set(
+            MatrixName::new("A"),
+            DMat2::from_cols_array(&[1., 2., 3., 4.]),
+        )
+        .unwrap();
+        map.set(
+            MatrixName::new("B"),
+            DMat2::from_cols_array(&[0., -1., 2., 5.]),
+        )
+        .unwrap();
+
+        let mut cache = EvaluationCache::new();
+        for expression in [
+            "(A * B) + (A * B) ^ T + norm(A * B) * A",
+            "2 * A ^ T - B / 3",
+            "A ^ {-1} * [1; 2] + row(A * B, 1)",
+            "aug([1; 0; 0], [0; 1; 0], [0; 0; 1]) * block(A, [1; 2]; [3; 4], 5)",
+            "A[1, 2] + B[1, 2] + rank(A) + cofactor(A, 1, 2)",
+            "A + [1; 2]",
+            "is_symmetric(A) + is_singular(A) * 2 + is_orthogonal(rot(30)) * 4",
+            "norm(A, \"fro\") + norm(A, \"spectral\") * 10 + norm(A, \"max\") * 100",
+            "C * A",
+        ] {
+            let ast = parse(expression);
+            assert_eq!(
+                ast.evaluate_memoised(&map, &mut cache),
+                ast.clone().evaluate(&map),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_memoised_shares_subexpressions() {
+        let mut map = MatrixMap2::new();
+        map.set(MatrixName::new("A"), DMat2::from_angle(1.))
+            .unwrap();
+
+        let mut cache = EvaluationCache::new();
+        assert!(cache.is_empty());
+
+        // A * A, A ^ {2}, the products with 2, and the two additions
+        parse("2 * (A * A) + A ^ 2 + 2 * (A * A)")
+            .evaluate_memoised(&map, &mut cache)
+            .unwrap();
+        assert_eq!(cache.len(), 5);
+
+        // Only the new addition is evaluated
+        parse("(2 * (A * A) + A ^ 2) + A ^ 2")
+            .evaluate_memoised(&map, &mut cache)
+            .unwrap();
+        assert_eq!(cache.len(), 6);
+
+        // Changing the map throws away the values
+        map.set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+        assert_eq!(
+            parse("A * A").evaluate_memoised(&map, &mut cache),
+            Ok(NumberOrMatrix::Matrix(DMat2::IDENTITY.into()))
+        );
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.structures.len(), 2);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn evaluate_memoised_bounds_structures() {
+        let map = MatrixMap2::new();
+        let mut cache = EvaluationCache::new();
+        let ast = parse("1 + 2");
+        ast.evaluate_memoised(&map, &mut cache).unwrap();
+
+        // Even without any changes to the map, too many structures empties the cache
+        for number in 0..=MAX_CACHED_STRUCTURES {
+            let key = NodeKey::new(&AstNode::Number(number as f64), vec![]);
+            cache.structures.insert(key, number);
+        }
+
+        assert_eq!(
+            ast.evaluate_memoised(&map, &mut cache),
+            Ok(NumberOrMatrix::Number(3.))
+        );
+        assert_eq!(cache.structures.len(), 3);
+    }
+
+    #[test]
+    fn evaluate_memoised_layered_map() {
+        let [a, b] = ["A", "B"].map(MatrixName::new);
+        let mut lesson = MatrixMap2::new();
+        lesson.set(a.clone(), DMat2::IDENTITY * 2.).unwrap();
+
+        let mut map = LayeredMatrixMap::new();
+        map.insert_layer(0, lesson);
+        map.set(b, DMat2::IDENTITY).unwrap();
+
+        // The top layer was changed last, but it doesn't have the lesson's matrices, so its
+        // values can't come from the cache of the whole map
+        let ast = parse("A * A");
+        let mut cache = EvaluationCache::new();
+        assert_eq!(
+            ast.evaluate_memoised(&map, &mut cache),
+            Ok(NumberOrMatrix::Matrix((DMat2::IDENTITY * 4.).into()))
+        );
+        assert_eq!(
+            ast.evaluate_memoised(map.top(), &mut cache),
+            ast.clone().evaluate(map.top())
+        );
+        assert!(ast.evaluate_memoised(map.top(), &mut cache).is_err());
+    }
+
+    #[test]
+    fn evaluate_memoised_with_options() {
+        let map = MatrixMap2::new();
+        let mut cache = EvaluationCache::new();
+        let loose = EvalOptions {
+            epsilon: 0.001,
+            ..EvalOptions::default()
+        };
+
+        // Changing the options throws away the values
+        let ast = parse("is_singular([1 2; 2 4.0001] * [1 0; 0 1])");
+        for (options, expected) in [(EvalOptions::default(), 0.), (loose, 1.)] {
+            assert_eq!(
+                ast.evaluate_memoised_with_options(&map, options, &mut cache),
+                Ok(NumberOrMatrix::Number(expected))
+            );
+        }
+
+        let ast = parse("(i * [1 2; 2 4.0001]) ^ {-1}");
+        assert!(ast.evaluate_memoised(&map, &mut cache).is_ok());
+        assert_eq!(
+            ast.evaluate_memoised_with_options(&map, loose, &mut cache),
+            Err(EvaluationError::CannotInvertSingularMatrix)
+        );
+
+        let shallow = EvalOptions {
+            limits: EvaluationLimits {
+                max_depth: 2,
+                ..EvaluationLimits::default()
+            },
+            ..EvalOptions::default()
+        };
+        assert_eq!(
+            parse("1 + 2 * 3").evaluate_memoised_with_options(&map, shallow, &mut cache),
+            Err(EvaluationError::ExpressionTooDeep { max_depth: 2 })
+        );
+
+        let affine = EvalOptions {
+            affine_2d: true,
+            ..EvalOptions::default()
+        };
+        for expression in ["[1 0 2; 0 1 3; 0 0 1] * 2 / 2", "[1 0 0; 0 1 0; 1 0 1]"] {
+            let ast = parse(expression);
+            assert_eq!(
+                ast.evaluate_memoised_with_options(&map, affine, &mut cache),
+                ast.clone().evaluate_with_options(&map, affine),
+                "{expression}"
+            );
+        }
+    }
+}
@@ -5,10 +5,24 @@
 //! [`parse_tokens_into_ast`](self::parser::parse_tokens_into_ast) (see
 //! [`AstNode`](self::ast::AstNode)), and then [`evaulate`](self::ast::AstNode::evaluate) it.
 
+use nom::Offset;
+use std::ops::Range;
 use thiserror::Error;
 
 pub mod ast;
+mod canonical;
+pub mod check;
+pub mod format;
+mod latex;
+mod mathml;
+pub mod memo;
 pub mod parser;
+mod partial;
+#[cfg(feature = "high-precision")]
+pub mod precise;
+pub mod shape;
+mod simplify;
+mod substitute;
 pub mod tokenise;
 
 /// An error that occurred during tokenisation or during parsing.
@@ -30,20 +44,79 @@ impl<'i> From<self::tokenise::TokeniseError<'i>> for TokeniseOrParseError<'i> {
     }
 }
 
+impl<'i> TokeniseOrParseError<'i> {
+    /// Get the span of the offending part of the expression, as byte offsets, so that it can be
+    /// underlined for the user. The expression must be the one that produced this error.
+    ///
+    /// This is `None` if the parse error hasn't been [located](self::parser::ParseError::locate).
+    ///
+    /// ```
+    /// # use trinity::matrix::expression::parse_expression_from_string;
+    /// let expression = "2 * (A + @)";
+    /// let error = parse_expression_from_string(expression).unwrap_err();
+    /// assert_eq!(error.span(expression), Some(9..10));
+    /// ```
+    pub fn span(&self, expression: &'i str) -> Option<Range<usize>> {
+        let unconsumed = |input: &str| {
+            let start = expression.offset(input);
+            let length = input.chars().next().map_or(0, char::len_utf8);
+            start..start + length
+        };
+
+        match self {
+            Self::TokeniseError(self::tokenise::TokeniseError::Empty) => Some(0..0),
+            Self::TokeniseError(self::tokenise::TokeniseError::UnexpectedCharacter {
+                rest,
+                ..
+            }) => Some(unconsumed(rest)),
+            Self::ParseError(
+                self::parser::ParseError::Unexpected(diagnostic)
+                | self::parser::ParseError::MixedMatrixSeparators(diagnostic)
+                | self::parser::ParseError::TooDeeplyNested(diagnostic),
+            ) => diagnostic.span.clone(),
+        }
+    }
+}
+
 /// Parse the expression directly from a string into an AST.
 pub fn parse_expression_from_string(
     expression: &str,
-) -> Result<self::ast::AstNode, TokeniseOrParseError> {
-    let tokens = self::tokenise::tokenise_expression(expression)?;
-    let ast = self::parser::parse_tokens_into_ast(&tokens)?;
+) -> Result<self::ast::AstNode, TokeniseOrParseError<'_>> {
+    let (tokens, spans): (Vec<_>, Vec<_>) =
+        self::tokenise::tokenise_expression_with_spans(expression)?
+            .into_iter()
+            .unzip();
+    let ast = self::parser::parse_tokens_into_ast(&tokens)
+        .map_err(|error| error.locate(expression, &spans))?;
     Ok(ast)
 }
 
+/// Parse the expression directly from a string into an AST, reporting every parse error rather
+/// than just the first one. See [`parse_tokens_with_recovery`](self::parser::parse_tokens_with_recovery).
+///
+/// Tokenisation errors can't be recovered from, so if tokenisation fails, the only error will be
+/// the tokenisation error.
+pub fn parse_expression_from_string_with_recovery(
+    expression: &str,
+) -> Result<self::ast::AstNode, Vec<TokeniseOrParseError<'_>>> {
+    let (tokens, spans): (Vec<_>, Vec<_>) =
+        self::tokenise::tokenise_expression_with_spans(expression)
+            .map_err(|error| vec![error.into()])?
+            .into_iter()
+            .unzip();
+    self::parser::parse_tokens_with_recovery(&tokens).map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|error| error.locate(expression, &spans).into())
+            .collect()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::ast::AstNode;
     use super::*;
-    use crate::matrix::MatrixName;
+    use crate::matrix::{MatrixName, ScalarName};
 
     #[test]
     fn parse_expression_from_string_success() {
@@ -116,11 +189,11 @@ mod tests {
                     })
                 }),
                 right: Box::new(AstNode::Divide {
-                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("D"))),
-                    right: Box::new(AstNode::Divide {
-                        left: Box::new(AstNode::Number(3.)),
-                        right: Box::new(AstNode::Number(2.))
-                    })
+                    left: Box::new(AstNode::Divide {
+                        left: Box::new(AstNode::NamedMatrix(MatrixName::new("D"))),
+                        right: Box::new(AstNode::Number(3.))
+                    }),
+                    right: Box::new(AstNode::Number(2.))
                 })
             })
         );
@@ -190,6 +263,235 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_expression_from_string_vectors() {
+        use glam::{DVec2, DVec3};
+
+        assert_eq!(
+            parse_expression_from_string("M[1; 2]"),
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::NamedMatrix(MatrixName::new("M"))),
+                right: Box::new(AstNode::Anonymous2dVector(DVec2::new(1., 2.)))
+            })
+        );
+
+        assert_eq!(
+            parse_expression_from_string("2 dot(U, V) - cross([1; 0; 0], W)"),
+            Ok(AstNode::Add {
+                left: Box::new(AstNode::Multiply {
+                    left: Box::new(AstNode::Number(2.)),
+                    right: Box::new(AstNode::DotProduct {
+                        left: Box::new(AstNode::NamedMatrix(MatrixName::new("U"))),
+                        right: Box::new(AstNode::NamedMatrix(MatrixName::new("V")))
+                    })
+                }),
+                right: Box::new(AstNode::Negate(Box::new(AstNode::CrossProduct {
+                    left: Box::new(AstNode::Anonymous3dVector(DVec3::X)),
+                    right: Box::new(AstNode::NamedMatrix(MatrixName::new("W")))
+                })))
+            })
+        );
+    }
+
+    #[test]
+    fn parse_expression_from_string_index() {
+        assert_eq!(
+            parse_expression_from_string("A[1,2] * B"),
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::Index {
+                    matrix: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                    row: 1,
+                    column: 2
+                }),
+                right: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+            })
+        );
+    }
+
+    #[test]
+    fn parse_expression_from_string_augment_block() {
+        assert_eq!(
+            parse_expression_from_string("aug(U, V, W)"),
+            Ok(AstNode::Augment {
+                columns: vec![
+                    AstNode::NamedMatrix(MatrixName::new("U")),
+                    AstNode::NamedMatrix(MatrixName::new("V")),
+                    AstNode::NamedMatrix(MatrixName::new("W")),
+                ]
+            })
+        );
+
+        assert_eq!(
+            parse_expression_from_string("block(A, U; V, 2 - 1)"),
+            Ok(AstNode::Block {
+                top_left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                top_right: Box::new(AstNode::NamedMatrix(MatrixName::new("U"))),
+                bottom_left: Box::new(AstNode::NamedMatrix(MatrixName::new("V"))),
+                bottom_right: Box::new(AstNode::Add {
+                    left: Box::new(AstNode::Number(2.)),
+                    right: Box::new(AstNode::Negate(Box::new(AstNode::Number(1.))))
+                }),
+            })
+        );
+
+        assert!(parse_expression_from_string("aug()").is_err());
+        assert!(parse_expression_from_string("block(A, U, V, 1)").is_err());
+    }
+
+    #[test]
+    fn parse_expression_from_string_solve() {
+        assert_eq!(
+            parse_expression_from_string("solve(A, B)"),
+            Ok(AstNode::Solve {
+                matrix: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                vector: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+            })
+        );
+    }
+
+    #[test]
+    fn parse_expression_from_string_norm() {
+        assert_eq!(
+            parse_expression_from_string("norm(A)/2"),
+            Ok(AstNode::Divide {
+                left: Box::new(AstNode::Norm(Box::new(AstNode::NamedMatrix(
+                    MatrixName::new("A")
+                )))),
+                right: Box::new(AstNode::Number(2.))
+            })
+        );
+
+        assert!(parse_expression_from_string("norm(A, B)").is_err());
+    }
+
+    #[test]
+    fn parse_expression_from_string_adjugate_cofactor() {
+        assert_eq!(
+            parse_expression_from_string("adj(A) cofactor(A, 1, 2)"),
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::Adjugate(Box::new(AstNode::NamedMatrix(
+                    MatrixName::new("A")
+                )))),
+                right: Box::new(AstNode::Cofactor {
+                    matrix: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                    row: Box::new(AstNode::Number(1.)),
+                    column: Box::new(AstNode::Number(2.))
+                })
+            })
+        );
+    }
+
+    #[test]
+    fn parse_expression_from_string_comments() {
+        let ast = parse_expression_from_string("A + B # add them together");
+        assert_eq!(
+            ast,
+            Ok(AstNode::Add {
+                left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                right: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+            })
+        );
+
+        // Comments are tolerated but not preserved when round-tripping
+        assert_eq!(ast.unwrap().to_expression_string(), "A + B");
+    }
+
+    #[test]
+    fn parse_expression_from_string_unicode() {
+        assert_eq!(
+            parse_expression_from_string("A⁻¹ × Bᵀ"),
+            parse_expression_from_string("A^{-1} * B^T")
+        );
+
+        assert_eq!(
+            parse_expression_from_string("2 · A ÷ 3 − B"),
+            parse_expression_from_string("2 * A / 3 - B")
+        );
+    }
+
+    #[test]
+    fn parse_expression_from_string_comma_separated_matrices() {
+        use glam::{DMat2, DMat3};
+
+        assert_eq!(
+            parse_expression_from_string("[1, 2; 3, 4]"),
+            parse_expression_from_string("[1 2; 3 4]")
+        );
+        assert_eq!(
+            parse_expression_from_string("[1,0,0;0,1,0;0,0,1] * [1, 0; 0, 1]"),
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::Anonymous3dMatrix(DMat3::IDENTITY)),
+                right: Box::new(AstNode::Anonymous2dMatrix(DMat2::IDENTITY))
+            })
+        );
+
+        let error = parse_expression_from_string("2 [1, 2; 3 4]").unwrap_err();
+        assert_eq!(
+            error,
+            TokeniseOrParseError::ParseError(parser::ParseError::MixedMatrixSeparators(Box::new(
+                parser::Diagnostic {
+                    token_index: 1,
+                    span: Some(2..3),
+                    column: Some(3),
+                    found: Some(tokenise::Token::OpenSquareBracket),
+                    after: Some(tokenise::Token::Number(2.)),
+                    expected: vec![],
+                }
+            )))
+        );
+        assert_eq!(
+            error.to_string(),
+            "The matrix starting at column 3 mixes commas and spaces, but its entries must be \
+            separated by either all commas or all spaces"
+        );
+        assert!(matches!(
+            parse_expression_from_string("[1 2 3; 4, 5, 6; 7 8 9]"),
+            Err(TokeniseOrParseError::ParseError(
+                parser::ParseError::MixedMatrixSeparators(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn parse_expression_from_string_left_associative() {
+        use super::ast::NumberOrMatrix;
+        use crate::matrix::map::prelude::*;
+
+        assert_eq!(
+            parse_expression_from_string("8/4/2"),
+            Ok(AstNode::Divide {
+                left: Box::new(AstNode::Divide {
+                    left: Box::new(AstNode::Number(8.)),
+                    right: Box::new(AstNode::Number(4.))
+                }),
+                right: Box::new(AstNode::Number(2.))
+            })
+        );
+
+        assert_eq!(
+            parse_expression_from_string("10-3-2"),
+            Ok(AstNode::Add {
+                left: Box::new(AstNode::Add {
+                    left: Box::new(AstNode::Number(10.)),
+                    right: Box::new(AstNode::Negate(Box::new(AstNode::Number(3.))))
+                }),
+                right: Box::new(AstNode::Negate(Box::new(AstNode::Number(2.))))
+            })
+        );
+
+        let map = MatrixMap2::new();
+        let evaluate = |expression| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(&map)
+        };
+        assert_eq!(evaluate("8/4/2"), Ok(NumberOrMatrix::Number(1.)));
+        assert_eq!(evaluate("10-3-2"), Ok(NumberOrMatrix::Number(5.)));
+        assert_eq!(evaluate("10-3+2"), Ok(NumberOrMatrix::Number(9.)));
+        assert_eq!(evaluate("1+2-3-4"), Ok(NumberOrMatrix::Number(-4.)));
+        assert_eq!(evaluate("64/8/4/2"), Ok(NumberOrMatrix::Number(1.)));
+    }
+
     #[test]
     fn parse_expression_from_string_abc() {
         assert_eq!(
@@ -219,6 +521,25 @@ mod tests {
             })
         );
 
+        assert_eq!(
+            parse_expression_from_string("A2B"),
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::NamedMatrix(MatrixName::new("A2"))),
+                right: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+            })
+        );
+
+        assert_eq!(
+            parse_expression_from_string("A 2B"),
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                right: Box::new(AstNode::Multiply {
+                    left: Box::new(AstNode::Number(2.)),
+                    right: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+                })
+            })
+        );
+
         assert_eq!(
             parse_expression_from_string("Abc"),
             Ok(AstNode::NamedMatrix(MatrixName::new("Abc")))
@@ -226,114 +547,204 @@ mod tests {
 
         assert_eq!(
             parse_expression_from_string("aBC"),
-            Err(TokeniseOrParseError::TokeniseError(
-                tokenise::TokeniseError::NomError {
-                    nom_error: nom::Err::Error(nom::error::Error::new(
-                        "aBC",
-                        nom::error::ErrorKind::MultiSpace
-                    ))
-                }
-            ))
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::Variable(ScalarName::new("a"))),
+                right: Box::new(AstNode::Multiply {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("B"))),
+                    right: Box::new(AstNode::NamedMatrix(MatrixName::new("C")))
+                })
+            })
         );
 
         assert_eq!(
             parse_expression_from_string("aBc"),
-            Err(TokeniseOrParseError::TokeniseError(
-                tokenise::TokeniseError::NomError {
-                    nom_error: nom::Err::Error(nom::error::Error::new(
-                        "aBc",
-                        nom::error::ErrorKind::MultiSpace
-                    ))
-                }
-            ))
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::Variable(ScalarName::new("a"))),
+                right: Box::new(AstNode::NamedMatrix(MatrixName::new("Bc")))
+            })
         );
 
         assert_eq!(
             parse_expression_from_string("abC"),
-            Err(TokeniseOrParseError::TokeniseError(
-                tokenise::TokeniseError::NomError {
-                    nom_error: nom::Err::Error(nom::error::Error::new(
-                        "abC",
-                        nom::error::ErrorKind::MultiSpace
-                    ))
-                }
-            ))
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::Variable(ScalarName::new("ab"))),
+                right: Box::new(AstNode::NamedMatrix(MatrixName::new("C")))
+            })
         );
 
         assert_eq!(
             parse_expression_from_string("abc"),
-            Err(TokeniseOrParseError::TokeniseError(
-                tokenise::TokeniseError::NomError {
-                    nom_error: nom::Err::Error(nom::error::Error::new(
-                        "abc",
-                        nom::error::ErrorKind::MultiSpace
-                    ))
-                }
-            ))
+            Ok(AstNode::Variable(ScalarName::new("abc")))
         );
     }
 
     #[test]
     fn parse_expression_from_string_failure() {
         use super::{
-            parser::ParseError,
+            parser::{Diagnostic, Expected, ParseError},
             tokenise::{Token, TokeniseError},
         };
 
         assert_eq!(
             parse_expression_from_string(""),
-            Err(TokeniseOrParseError::TokeniseError(
-                TokeniseError::NomError {
-                    nom_error: nom::Err::Error(nom::error::Error::new(
-                        "",
-                        nom::error::ErrorKind::MultiSpace
-                    ))
-                }
-            ))
+            Err(TokeniseOrParseError::TokeniseError(TokeniseError::Empty))
         );
 
         assert_eq!(
             parse_expression_from_string("2 @ M"),
             Err(TokeniseOrParseError::TokeniseError(
-                TokeniseError::UnconsumedInput("@ M")
+                TokeniseError::UnexpectedCharacter {
+                    character: '@',
+                    column: 3,
+                    rest: "@ M"
+                }
             ))
         );
 
         assert_eq!(
             parse_expression_from_string("C++"),
-            Err(TokeniseOrParseError::ParseError(ParseError::NomError(
-                nom::Err::Error(nom::error::Error::new(
-                    vec![Token::Plus],
-                    nom::error::ErrorKind::Tag
-                ))
+            Err(TokeniseOrParseError::ParseError(ParseError::Unexpected(
+                Box::new(Diagnostic {
+                    token_index: 2,
+                    span: Some(2..3),
+                    column: Some(3),
+                    found: Some(Token::Plus),
+                    after: Some(Token::Plus),
+                    expected: vec![Expected::Term],
+                })
             )))
         );
 
         assert_eq!(
             parse_expression_from_string("[1 2 3 4]"),
-            Err(TokeniseOrParseError::ParseError(ParseError::NomError(
-                nom::Err::Error(nom::error::Error::new(
-                    vec![
-                        Token::OpenSquareBracket,
-                        Token::Number(1.0),
-                        Token::Number(2.0),
-                        Token::Number(3.0),
-                        Token::Number(4.0),
-                        Token::CloseSquareBracket
-                    ],
-                    nom::error::ErrorKind::Tag
-                ))
+            Err(TokeniseOrParseError::ParseError(ParseError::Unexpected(
+                Box::new(Diagnostic {
+                    token_index: 5,
+                    span: Some(8..9),
+                    column: Some(9),
+                    found: Some(Token::CloseSquareBracket),
+                    after: Some(Token::Number(4.0)),
+                    expected: vec![Expected::Token(Token::Semicolon)],
+                })
             )))
         );
 
         assert_eq!(
             parse_expression_from_string("[1"),
-            Err(TokeniseOrParseError::ParseError(ParseError::NomError(
-                nom::Err::Error(nom::error::Error::new(
-                    vec![Token::OpenSquareBracket, Token::Number(1.0),],
-                    nom::error::ErrorKind::Tag
-                ))
+            Err(TokeniseOrParseError::ParseError(ParseError::Unexpected(
+                Box::new(Diagnostic {
+                    token_index: 2,
+                    span: Some(2..2),
+                    column: Some(3),
+                    found: None,
+                    after: Some(Token::Number(1.0)),
+                    expected: vec![Expected::Number, Expected::Token(Token::Semicolon)],
+                })
             )))
         );
+
+        assert_eq!(
+            parse_expression_from_string("A B )"),
+            Err(TokeniseOrParseError::ParseError(ParseError::Unexpected(
+                Box::new(Diagnostic {
+                    token_index: 2,
+                    span: Some(4..5),
+                    column: Some(5),
+                    found: Some(Token::CloseParen),
+                    after: Some(Token::NamedMatrix(MatrixName::new("B"))),
+                    expected: vec![Expected::Operator, Expected::EndOfExpression],
+                })
+            )))
+        );
+    }
+
+    #[test]
+    fn parse_expression_from_string_error_spans() {
+        let span = |expression| {
+            parse_expression_from_string(expression)
+                .unwrap_err()
+                .span(expression)
+        };
+
+        assert_eq!(span(""), Some(0..0));
+        assert_eq!(span("2 @ M"), Some(2..3));
+        assert_eq!(span("A + £"), Some(4..6));
+        assert_eq!(span("C++"), Some(2..3));
+        assert_eq!(span("[1"), Some(2..2));
+        assert_eq!(span("[1, 2; 3 4]"), Some(0..1));
+
+        let error = super::parser::parse_tokens_into_ast(&[tokenise::Token::Plus]).unwrap_err();
+        assert_eq!(TokeniseOrParseError::from(error).span("+"), None);
+    }
+
+    #[test]
+    fn parse_expression_from_string_with_recovery_success() {
+        assert_eq!(
+            parse_expression_from_string_with_recovery("A + 2 * B"),
+            parse_expression_from_string("A + 2 * B").map_err(|error| vec![error])
+        );
+
+        assert_eq!(
+            parse_expression_from_string_with_recovery("2 @ M"),
+            Err(vec![TokeniseOrParseError::TokeniseError(
+                tokenise::TokeniseError::UnexpectedCharacter {
+                    character: '@',
+                    column: 3,
+                    rest: "@ M"
+                }
+            )])
+        );
+
+        let messages: Vec<String> =
+            parse_expression_from_string_with_recovery("(2 * ) + [1 2 3 4] B )")
+                .unwrap_err()
+                .into_iter()
+                .map(|error| error.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "expected a number, matrix, function or '(' after '*' at column 6, but found ')'",
+                "expected ';' after '4' at column 18, but found ']'",
+                "expected an operator or the end of the expression after 'B' at column 22, but found ')'",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_expression_from_string_failure_messages() {
+        let message = |expression| {
+            parse_expression_from_string(expression)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            message("2 * 3 * )"),
+            "expected a number, matrix, function or '(' after '*' at column 9, but found ')'"
+        );
+        assert_eq!(
+            message("(1 + 2"),
+            "expected ')' after '2' at column 7, but found the end of the expression"
+        );
+        assert_eq!(
+            message("dot(A; B)"),
+            "expected ',' after 'A' at column 6, but found ';'"
+        );
+        assert_eq!(
+            message("2 rot(90"),
+            "expected ')' after '90' at column 9, but found the end of the expression"
+        );
+        assert_eq!(
+            message("M⁻¹ )"),
+            "expected an operator or the end of the expression after '}' at column 5, but found ')'"
+        );
+
+        assert_eq!(
+            parser::parse_tokens_into_ast(&[tokenise::Token::Star])
+                .unwrap_err()
+                .to_string(),
+            "expected a number, matrix, function or '(' at token 1, but found '*'"
+        );
     }
 }
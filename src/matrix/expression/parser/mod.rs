@@ -4,45 +4,304 @@
 //! ```text
 //! expression        -> addition ;
 //! addition          -> multiply ( ("+" | "-") multiply )* ;
-//! multiply          -> divide ( "*" divide )* ;
+//! multiply          -> divide ( "*"? divide )* ;
 //! divide            -> exponent ( "/" exponent )* ;
-//! exponent          -> term ( "^" term )? ;
-//! term              -> "-"? term | matrixName | anonymousMatrix | rotationMatrix | NUMBER | "(" expression ")" ;
+//! exponent          -> index ( "^" index )? ;
+//! index             -> term INDEX? ;
+//! term              -> "-"? term | matrixName | variable | anonymousMatrix | anonymousVector | rotationMatrix | function | imaginary | NUMBER | "(" expression ")" ;
+//! imaginary         -> NUMBER? "i" ;
 //! matrixName        -> See [`MatrixName`] struct
-//! anonymousMatrix   -> anonymous2dMatrix | anonymous3dMatrix ;
-//! anonymous2dMatrix -> "[" NUMBER NUMBER ";" NUMBER NUMBER "]" ;
-//! anonymous3dMatrix -> "[" NUMBER NUMBER NUMBER ";" NUMBER NUMBER NUMBER ";" NUMBER NUMBER NUMBER "]" ;
+//! variable          -> See [`ScalarName`] struct
+//! anonymousMatrix   -> anonymous2dMatrix | anonymous3dMatrix | anonymousNdMatrix ;
+//! anonymous2dMatrix -> "[" NUMBER ","? NUMBER ";" NUMBER ","? NUMBER "]" ;
+//! anonymous3dMatrix -> "[" NUMBER ","? NUMBER ","? NUMBER ";" NUMBER ","? NUMBER ","? NUMBER ";" NUMBER ","? NUMBER ","? NUMBER "]" ;
+//! anonymousNdMatrix -> "[" NUMBER ( ","? NUMBER )* ( ";" NUMBER ( ","? NUMBER )* )* "]" ;
+//! anonymousVector   -> anonymous2dVector | anonymous3dVector ;
+//! anonymous2dVector -> "[" NUMBER ";" NUMBER "]" ;
+//! anonymous3dVector -> "[" NUMBER ";" NUMBER ";" NUMBER "]" ;
 //! rotationMatrix    -> "rot" "(" NUMBER ")" ;
+//! function          -> ( "norm" | "adj" | "rank" | "exp" | "logm" | "log" | "pinv" | "proj_line" | "proj_plane" ) "(" expression ")"
+//!                    | "norm" "(" expression "," normName ")"
+//!                    | ( "dot" | "cross" | "row" | "col" | "solve" | "lstsq" | "translate" ) "(" expression "," expression ")"
+//!                    | "cofactor" "(" expression "," expression "," expression ")"
+//!                    | "rand" "(" expression "," expression ( "," distName )? ")"
+//!                    | property "(" expression ")"
+//!                    | augment | block ;
+//! property          -> "is_orthogonal" | "is_symmetric" | "is_singular" | "is_rotation" ;
+//! normName          -> "\"fro\"" | "\"spectral\"" | "\"max\"" ;
+//! distName          -> "\"uniform\"" | "\"integer\"" | "\"orthogonal\"" ;
+//! augment           -> "aug" "(" expression ( "," expression )* ")" ;
+//! block             -> "block" "(" expression "," expression ";" expression "," expression ")" ;
+//! INDEX             -> "[" INTEGER "," INTEGER "]" ;
 //! ```
+//!
+//! Chains of addition, subtraction, and division are left-associative, so `8 / 4 / 2` means
+//! `(8 / 4) / 2` and `10 - 3 - 2` means `(10 - 3) - 2`. Chains of multiplication are
+//! right-associative, so `A * B * C` means `A * (B * C)`, and the `*` may be left out, like `2A`.
+//!
+//! The matrix logarithm is written `logm`, and `log` is accepted as a shorter alias for it.
+//!
+//! An `anonymousNdMatrix` must be square and at least 4×4, since smaller matrices are parsed as
+//! `anonymous2dMatrix` or `anonymous3dMatrix`.
+//!
+//! The entries in each row of an anonymous matrix must either all be separated by commas or all
+//! be separated by whitespace. Mixing the two is a [`ParseError::MixedMatrixSeparators`].
+//!
+//! Brackets, function calls, negations, and powers can only be nested [`MAX_NESTING_DEPTH`]
+//! levels deep, since the parser recurses for each level. Anything deeper is a
+//! [`ParseError::TooDeeplyNested`]. Long chains of operators like `1 + 1 + ... + 1` don't count
+//! as nesting, so they can be as long as needed.
 
 mod nom_impl;
+mod recovery;
 mod tokens;
 
+pub use self::recovery::parse_tokens_with_recovery;
+
+use self::nom_impl::TokenParseError;
 use super::{ast::AstNode, tokenise::Token};
+use std::{fmt, ops::Range};
 use thiserror::Error;
 
-/// The default error used by [`nom::IResult`].
-type NomError = ::nom::Err<::nom::error::Error<Vec<Token>>>;
+/// The most levels of brackets, function calls, negations, and powers that an expression can be
+/// nested inside. The whole expression counts as the first level, so `(A)` is nested two levels
+/// deep.
+///
+/// This protects the parser's stack, so it's fixed and doesn't depend on
+/// [`EvaluationLimits`](super::ast::EvaluationLimits), which only apply when evaluating. Those
+/// limits still apply to parsed expressions, whose trees can be much deeper than their nesting.
+///
+/// ```
+/// # use trinity::matrix::expression::{parse_expression_from_string, parser::MAX_NESTING_DEPTH};
+/// let nested = |depth| "(".repeat(depth) + "A" + &")".repeat(depth);
+/// assert!(parse_expression_from_string(&nested(MAX_NESTING_DEPTH - 1)).is_ok());
+/// assert!(parse_expression_from_string(&nested(MAX_NESTING_DEPTH)).is_err());
+/// ```
+pub const MAX_NESTING_DEPTH: usize = 32;
+
+/// Something that the parser expected to find. See [`Diagnostic`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expected {
+    /// A specific token.
+    Token(Token),
+
+    /// A numeric literal.
+    Number,
+
+    /// A named matrix.
+    MatrixName,
+
+    /// A scalar variable.
+    VariableName,
+
+    /// A matrix index like `[1, 2]`.
+    Index,
+
+    /// The name of a matrix norm, like `"fro"`.
+    NormName,
+
+    /// The name of a distribution of random matrices, like `"orthogonal"`.
+    DistributionName,
+
+    /// The start of a term, like a number, a matrix, a function call, or a bracketed expression.
+    Term,
+
+    /// An operator between two terms.
+    Operator,
+
+    /// The end of the expression.
+    EndOfExpression,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Token(token) => write!(f, "'{token}'"),
+            Self::Number => write!(f, "a number"),
+            Self::MatrixName => write!(f, "a matrix name"),
+            Self::VariableName => write!(f, "a variable name"),
+            Self::Index => write!(f, "an index like '[1, 2]'"),
+            Self::NormName => write!(f, "a norm like '\"fro\"', '\"spectral\"' or '\"max\"'"),
+            Self::DistributionName => write!(
+                f,
+                "a distribution like '\"uniform\"', '\"integer\"' or '\"orthogonal\"'"
+            ),
+            Self::Term => write!(f, "a number, matrix, function or '('"),
+            Self::Operator => write!(f, "an operator"),
+            Self::EndOfExpression => write!(f, "the end of the expression"),
+        }
+    }
+}
+
+/// A human-readable description of where and why parsing failed.
+///
+/// The span and column are only known once the diagnostic has been [located](Self::locate) in
+/// the original expression string, which
+/// [`parse_expression_from_string`](super::parse_expression_from_string) does automatically.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// The index of the offending token in the token list. This is the length of the token list
+    /// if the parser ran out of tokens.
+    pub token_index: usize,
+
+    /// The span of the offending token in the original expression, as byte offsets.
+    pub span: Option<Range<usize>>,
+
+    /// The 1-based column of the start of the offending token in the original expression,
+    /// counted in characters.
+    pub column: Option<usize>,
+
+    /// The token that was found, or `None` if the parser reached the end of the tokens.
+    pub found: Option<Token>,
+
+    /// The token just before the offending one, if there is one.
+    pub after: Option<Token>,
+
+    /// Everything that the parser would have accepted instead.
+    pub expected: Vec<Expected>,
+}
+
+impl Diagnostic {
+    /// Create a new diagnostic for the token at this index in the list.
+    fn new(tokens: &[Token], token_index: usize, expected: Vec<Expected>) -> Self {
+        Self {
+            token_index,
+            span: None,
+            column: None,
+            found: tokens.get(token_index).cloned(),
+            after: token_index
+                .checked_sub(1)
+                .and_then(|index| tokens.get(index))
+                .cloned(),
+            expected,
+        }
+    }
+
+    /// Fill in the span and column of this diagnostic, given the expression that was tokenised
+    /// and the span of each token, as returned by
+    /// [`tokenise_expression_with_spans`](super::tokenise::tokenise_expression_with_spans).
+    pub fn locate(&mut self, expression: &str, spans: &[Range<usize>]) {
+        let span = spans
+            .get(self.token_index)
+            .cloned()
+            .unwrap_or(expression.len()..expression.len());
+        self.column = Some(expression[..span.start].chars().count() + 1);
+        self.span = Some(span);
+    }
+
+    /// Describe where the offending token is, like "at column 5", or by its position in the
+    /// token list if the diagnostic hasn't been [located](Self::locate).
+    pub fn location(&self) -> String {
+        match self.column {
+            Some(column) => format!("at column {column}"),
+            None => format!("at token {}", self.token_index + 1),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected ")?;
+        for (index, expected) in self.expected.iter().enumerate() {
+            match index {
+                0 => write!(f, "{expected}")?,
+                _ if index == self.expected.len() - 1 => write!(f, " or {expected}")?,
+                _ => write!(f, ", {expected}")?,
+            }
+        }
+
+        if let Some(after) = &self.after {
+            write!(f, " after '{after}'")?;
+        }
+
+        write!(f, " {}", self.location())?;
+
+        match &self.found {
+            Some(found) => write!(f, ", but found '{found}'"),
+            None => write!(f, ", but found the end of the expression"),
+        }
+    }
+}
 
 /// An error that occurred during parsing.
 #[derive(Debug, Error, PartialEq)]
 pub enum ParseError {
-    /// An error created by [`nom`].
-    #[error("Internal nom error: {0:?}")]
-    NomError(#[from] NomError),
+    /// The parser found something that it didn't expect. The diagnostic is boxed to keep this
+    /// error small.
+    #[error("{0}")]
+    Unexpected(Box<Diagnostic>),
+
+    /// An anonymous matrix mixed commas and whitespace to separate its entries, like
+    /// `[1, 2; 3 4]`. The diagnostic points at the opening bracket of the offending matrix, and
+    /// doesn't expect anything.
+    #[error(
+        "The matrix starting {} mixes commas and spaces, but its entries must be separated by \
+        either all commas or all spaces",
+        .0.location()
+    )]
+    MixedMatrixSeparators(Box<Diagnostic>),
+
+    /// The expression was nested more than [`MAX_NESTING_DEPTH`] levels deep. The diagnostic
+    /// points at the first token that was too deep, and doesn't expect anything.
+    #[error(
+        "Expression is nested more than {} levels deep {}",
+        MAX_NESTING_DEPTH,
+        .0.location()
+    )]
+    TooDeeplyNested(Box<Diagnostic>),
+}
+
+impl ParseError {
+    /// Create a new error from an error from [`nom_impl`] that happened at this token.
+    fn new(tokens: &[Token], token_index: usize, error: TokenParseError) -> Self {
+        match error {
+            TokenParseError::Expected { expected, .. } => {
+                Self::Unexpected(Box::new(Diagnostic::new(tokens, token_index, expected)))
+            }
+            TokenParseError::MixedMatrixSeparators { .. } => {
+                Self::MixedMatrixSeparators(Box::new(Diagnostic::new(tokens, token_index, vec![])))
+            }
+            TokenParseError::TooDeeplyNested { .. } => {
+                Self::TooDeeplyNested(Box::new(Diagnostic::new(tokens, token_index, vec![])))
+            }
+        }
+    }
 
-    /// Some of the input was left unparsed.
-    #[error("Unconsumed input after tokenising expression: '{0:?}'")]
-    UnconsumedInput(Vec<Token>),
+    /// Locate this error in the original expression. See [`Diagnostic::locate`].
+    pub fn locate(mut self, expression: &str, spans: &[Range<usize>]) -> Self {
+        let (Self::Unexpected(diagnostic)
+        | Self::MixedMatrixSeparators(diagnostic)
+        | Self::TooDeeplyNested(diagnostic)) = &mut self;
+        diagnostic.locate(expression, spans);
+        self
+    }
 }
 
 /// Parse a list of tokens into an AST.
+///
+/// This function stops at the first error. See [`parse_tokens_with_recovery`] to get every error.
 pub fn parse_tokens_into_ast(tokens: &[Token]) -> Result<AstNode, ParseError> {
+    parse_tokens_raw(tokens).map_err(|error| {
+        let token_index = tokens.len() - error.remaining();
+        ParseError::new(tokens, token_index, error)
+    })
+}
+
+/// Parse a list of tokens into an AST, returning the error from [`nom_impl`] if parsing fails.
+fn parse_tokens_raw(tokens: &[Token]) -> Result<AstNode, TokenParseError> {
     let (token_list, ast) = self::nom_impl::parse_expression(self::tokens::TokenList::new(tokens))
-        .map_err(|err| err.map_input(|token_list| token_list.tokens.to_vec()))?;
+        .map_err(|err| match err {
+            ::nom::Err::Error(error) | ::nom::Err::Failure(error) => error,
+            ::nom::Err::Incomplete(_) => {
+                unreachable!("None of our parsers are streaming parsers")
+            }
+        })?;
 
     if !token_list.tokens.is_empty() {
-        return Err(ParseError::UnconsumedInput(token_list.tokens.to_vec()));
+        return Err(TokenParseError::Expected {
+            remaining: token_list.tokens.len(),
+            expected: vec![Expected::Operator, Expected::EndOfExpression],
+        });
     }
 
     Ok(ast)
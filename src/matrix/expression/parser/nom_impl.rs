@@ -1,120 +1,255 @@
 //! This module implements functions for parsing [`TokenList`]s with [`nom`].
 
-use super::tokens::TokenList;
-use crate::matrix::expression::{ast::AstNode, tokenise::Token};
+use super::{tokens::TokenList, Expected, MAX_NESTING_DEPTH};
+use crate::{
+    math::{MatrixNorm, RandomDistribution},
+    matrix::{
+        expression::{ast::AstNode, tokenise::Token},
+        DMatN,
+    },
+};
 use glam::{DMat2, DMat3, DVec2, DVec3};
-use nom::{branch::alt, bytes::complete::take, sequence::tuple, IResult, Parser};
+use nom::{
+    branch::alt,
+    combinator::opt,
+    error::ErrorKind,
+    multi::separated_list1,
+    sequence::{preceded, tuple},
+    IResult, InputLength, Parser,
+};
 
-/// Parse a matrix expression from a list of tokens.
-pub fn parse_expression(tokens: TokenList) -> IResult<TokenList, AstNode> {
-    parse_addition(tokens)
+/// The error used by all the parsers in this module.
+///
+/// Errors only remember how many tokens were remaining when they happened, rather than borrowing
+/// the [`TokenList`], so that they can outlive the tokens and be turned into a
+/// [`Diagnostic`](super::Diagnostic).
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenParseError {
+    /// The parser expected one of these things.
+    Expected {
+        /// The number of tokens remaining when the error happened.
+        remaining: usize,
+
+        /// Everything that the parser would have accepted.
+        expected: Vec<Expected>,
+    },
+
+    /// An anonymous matrix mixed commas and whitespace to separate its entries. See
+    /// [`ParseError::MixedMatrixSeparators`](super::ParseError::MixedMatrixSeparators).
+    MixedMatrixSeparators {
+        /// The number of tokens remaining from the opening bracket of the matrix.
+        remaining: usize,
+    },
+
+    /// The expression was nested more than [`MAX_NESTING_DEPTH`] levels deep. See
+    /// [`ParseError::TooDeeplyNested`](super::ParseError::TooDeeplyNested).
+    TooDeeplyNested {
+        /// The number of tokens remaining from the first token that was too deep.
+        remaining: usize,
+    },
 }
 
-/// Parse an addition.
-fn parse_addition(tokens: TokenList) -> IResult<TokenList, AstNode> {
-    let (tokens, left) = parse_multiply(tokens)?;
+impl TokenParseError {
+    /// Create a recoverable error at the start of these tokens, saying that we expected something
+    /// else.
+    fn expected(tokens: TokenList, expected: Expected) -> nom::Err<Self> {
+        nom::Err::Error(Self::Expected {
+            remaining: tokens.input_len(),
+            expected: vec![expected],
+        })
+    }
 
-    match consume_basic_token(Token::Plus)(tokens) {
-        Ok((tokens, ())) => {
-            let (tokens, right) = parse_addition(tokens)?;
+    /// The number of tokens remaining when the error happened.
+    pub fn remaining(&self) -> usize {
+        match self {
+            Self::Expected { remaining, .. }
+            | Self::MixedMatrixSeparators { remaining }
+            | Self::TooDeeplyNested { remaining } => *remaining,
+        }
+    }
+}
 
-            Ok((
-                tokens,
-                AstNode::Add {
-                    left: Box::new(left),
-                    right: Box::new(right),
-                },
-            ))
+impl nom::error::ParseError<TokenList<'_>> for TokenParseError {
+    fn from_error_kind(input: TokenList<'_>, _kind: ErrorKind) -> Self {
+        Self::Expected {
+            remaining: input.input_len(),
+            expected: vec![],
         }
-        Err(_) => match consume_basic_token(Token::Minus)(tokens) {
-            Ok((tokens, ())) => {
-                let (tokens, right) = parse_addition(tokens)?;
-
-                Ok((
-                    tokens,
-                    AstNode::Add {
-                        left: Box::new(left),
-                        right: Box::new(AstNode::Negate(Box::new(right))),
+    }
+
+    fn append(_input: TokenList<'_>, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Keep whichever error got furthest through the tokens, since that's the one that the user
+    /// most likely cares about. If they got equally far, then we merge what they expected.
+    fn or(self, other: Self) -> Self {
+        match self.remaining().cmp(&other.remaining()) {
+            std::cmp::Ordering::Less => self,
+            std::cmp::Ordering::Greater => other,
+            std::cmp::Ordering::Equal => match (self, other) {
+                (
+                    Self::Expected {
+                        remaining,
+                        mut expected,
                     },
-                ))
-            }
-            Err(_) => Ok((tokens, left)),
-        },
+                    Self::Expected {
+                        expected: other_expected,
+                        ..
+                    },
+                ) => {
+                    for thing in other_expected {
+                        if !expected.contains(&thing) {
+                            expected.push(thing);
+                        }
+                    }
+                    Self::Expected {
+                        remaining,
+                        expected,
+                    }
+                }
+                (
+                    error @ (Self::MixedMatrixSeparators { .. } | Self::TooDeeplyNested { .. }),
+                    _,
+                )
+                | (_, error) => error,
+            },
+        }
     }
 }
 
-/// Parse a multiplication.
-fn parse_multiply(tokens: TokenList) -> IResult<TokenList, AstNode> {
-    let (tokens, left) = parse_divide(tokens)?;
+/// The result type used by all the parsers in this module.
+type ParseResult<'l, O> = IResult<TokenList<'l>, O, TokenParseError>;
 
-    match consume_basic_token(Token::Star)(tokens) {
-        Ok((tokens, ())) => {
-            let (tokens, right) = parse_multiply(tokens)?;
+/// Parse a matrix expression from a list of tokens.
+pub fn parse_expression(tokens: TokenList) -> ParseResult<AstNode> {
+    nested(parse_addition)(tokens)
+}
 
-            Ok((
-                tokens,
-                AstNode::Multiply {
-                    left: Box::new(left),
-                    right: Box::new(right),
-                },
-            ))
+/// Run this parser one level of nesting deeper than the tokens it's given.
+///
+/// The parser recurses for every level of nesting, so we fail without backtracking once we're
+/// more than [`MAX_NESTING_DEPTH`] levels deep, rather than overflowing the stack.
+fn nested<'l, O>(
+    mut parser: impl FnMut(TokenList<'l>) -> ParseResult<'l, O>,
+) -> impl FnMut(TokenList<'l>) -> ParseResult<'l, O> {
+    move |tokens: TokenList<'l>| {
+        if tokens.depth >= MAX_NESTING_DEPTH {
+            return Err(nom::Err::Failure(TokenParseError::TooDeeplyNested {
+                remaining: tokens.input_len(),
+            }));
         }
-        Err(_) => {
-            // No star token means that this is the implicit multiplication branch
-            // We want to check if the next token is a minus because `2 - 1` should be a
-            // subtraction, but would otherwise parse as `2 * (-1)`
-            if consume_basic_token(Token::Minus)(tokens).is_ok() {
-                Ok((tokens, left))
-            } else {
-                match parse_multiply(tokens) {
-                    Ok((tokens, right)) => Ok((
-                        tokens,
-                        AstNode::Multiply {
-                            left: Box::new(left),
-                            right: Box::new(right),
-                        },
-                    )),
-                    Err(_) => Ok((tokens, left)),
-                }
-            }
+
+        let (mut rest, output) = parser(TokenList {
+            depth: tokens.depth + 1,
+            ..tokens
+        })?;
+        rest.depth = tokens.depth;
+        Ok((rest, output))
+    }
+}
+
+/// Parse an addition or subtraction. Chains like `a - b - c` are left-associative, so they
+/// parse as `(a - b) - c`.
+fn parse_addition(tokens: TokenList) -> ParseResult<AstNode> {
+    let (mut tokens, mut left) = parse_multiply(tokens)?;
+
+    loop {
+        if let Ok((rest, ())) = consume_basic_token(Token::Plus)(tokens) {
+            let (rest, right) = parse_multiply(rest)?;
+            left = AstNode::Add {
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+            tokens = rest;
+        } else if let Ok((rest, ())) = consume_basic_token(Token::Minus)(tokens) {
+            let (rest, right) = parse_multiply(rest)?;
+            left = AstNode::Add {
+                left: Box::new(left),
+                right: Box::new(AstNode::Negate(Box::new(right))),
+            };
+            tokens = rest;
+        } else {
+            return Ok((tokens, left));
         }
     }
 }
 
-/// Parse a division.
-fn parse_divide(tokens: TokenList) -> IResult<TokenList, AstNode> {
-    let (tokens, left) = parse_exponent(tokens)?;
+/// Parse a multiplication, which may be implicit like `2A`. Chains like `a * b * c` are
+/// right-associative, so they parse as `a * (b * c)`, since matrices apply from right to left.
+fn parse_multiply(tokens: TokenList) -> ParseResult<AstNode> {
+    let (mut tokens, first) = parse_divide(tokens)?;
+    let mut factors = vec![first];
 
-    match consume_basic_token(Token::Slash)(tokens) {
-        Ok((tokens, ())) => {
-            let (tokens, right) = parse_divide(tokens)?;
+    loop {
+        if let Ok((rest, ())) = consume_basic_token(Token::Star)(tokens) {
+            let (rest, factor) = parse_divide(rest)?;
+            factors.push(factor);
+            tokens = rest;
+            continue;
+        }
 
-            Ok((
-                tokens,
-                AstNode::Divide {
-                    left: Box::new(left),
-                    right: Box::new(right),
-                },
-            ))
+        // No star token means that this is the implicit multiplication branch
+        // We want to check if the next token is a minus because `2 - 1` should be a
+        // subtraction, but would otherwise parse as `2 * (-1)`
+        if consume_basic_token(Token::Minus)(tokens).is_ok() {
+            break;
+        }
+
+        match parse_divide(tokens) {
+            Ok((rest, factor)) => {
+                factors.push(factor);
+                tokens = rest;
+            }
+            Err(nom::Err::Error(error)) if error.remaining() == tokens.input_len() => break,
+            // If the right hand side got part of the way through before failing, then the user
+            // clearly meant to write something there, so we report the error
+            Err(error) => return Err(error),
         }
-        Err(_) => Ok((tokens, left)),
     }
+
+    let product = factors
+        .into_iter()
+        .rev()
+        .reduce(|right, left| AstNode::Multiply {
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+        .expect("There should always be at least one factor");
+    Ok((tokens, product))
+}
+
+/// Parse a division. Chains like `a / b / c` are left-associative, so they parse as
+/// `(a / b) / c`.
+fn parse_divide(tokens: TokenList) -> ParseResult<AstNode> {
+    let (mut tokens, mut left) = parse_exponent(tokens)?;
+
+    while let Ok((rest, ())) = consume_basic_token(Token::Slash)(tokens) {
+        let (rest, right) = parse_exponent(rest)?;
+        left = AstNode::Divide {
+            left: Box::new(left),
+            right: Box::new(right),
+        };
+        tokens = rest;
+    }
+
+    Ok((tokens, left))
 }
 
 /// Parse an exponentiation.
-fn parse_exponent(tokens: TokenList) -> IResult<TokenList, AstNode> {
-    let (tokens, base) = parse_term(tokens)?;
+fn parse_exponent(tokens: TokenList) -> ParseResult<AstNode> {
+    let (tokens, base) = parse_index(tokens)?;
 
     match consume_basic_token(Token::Caret)(tokens) {
         Ok((tokens, ())) => {
             let (tokens, power) = {
                 match consume_basic_token(Token::OpenBrace)(tokens) {
                     Ok((tokens, ())) => {
-                        let (tokens, power) = parse_exponent(tokens)?;
+                        let (tokens, power) = nested(parse_exponent)(tokens)?;
                         let (tokens, ()) = consume_basic_token(Token::CloseBrace)(tokens)?;
                         (tokens, power)
                     }
-                    Err(_) => parse_exponent(tokens)?,
+                    Err(_) => nested(parse_exponent)(tokens)?,
                 }
             };
 
@@ -130,17 +265,40 @@ fn parse_exponent(tokens: TokenList) -> IResult<TokenList, AstNode> {
     }
 }
 
+/// Parse a term which may be followed by an index, like `A[1, 2]`.
+fn parse_index(tokens: TokenList) -> ParseResult<AstNode> {
+    let (tokens, term) = parse_term(tokens)?;
+
+    match parse_index_token(tokens) {
+        Ok((tokens, (row, column))) => Ok((
+            tokens,
+            AstNode::Index {
+                matrix: Box::new(term),
+                row,
+                column,
+            },
+        )),
+        Err(_) => Ok((tokens, term)),
+    }
+}
+
 /// Parse a single term of the AST. See [`crate::matrix::expression::parser`] for details on the
 /// grammar.
-fn parse_term(tokens: TokenList) -> IResult<TokenList, AstNode> {
+fn parse_term(tokens: TokenList) -> ParseResult<AstNode> {
     alt((
-        tuple((consume_basic_token(Token::Minus), parse_term))
+        tuple((consume_basic_token(Token::Minus), nested(parse_term)))
             .map(|((), term)| AstNode::Negate(Box::new(term))),
         parse_named_matrix,
+        parse_variable,
         parse_rotation_matrix,
+        parse_function,
+        parse_imaginary,
         parse_number,
         parse_anonymous_2d_matrix,
         parse_anonymous_3d_matrix,
+        parse_anonymous_dynamic_matrix,
+        parse_anonymous_2d_vector,
+        parse_anonymous_3d_vector,
         tuple((
             consume_basic_token(Token::OpenParen),
             parse_expression,
@@ -149,10 +307,23 @@ fn parse_term(tokens: TokenList) -> IResult<TokenList, AstNode> {
         .map(|((), expression, ())| expression),
     ))
     .parse(tokens)
+    .map_err(|error| {
+        // If none of the alternatives got anywhere, then listing all of their first tokens
+        // isn't very helpful, so we just say that we expected a term
+        error.map(|error| match error {
+            TokenParseError::Expected { remaining, .. } if remaining == tokens.input_len() => {
+                TokenParseError::Expected {
+                    remaining,
+                    expected: vec![Expected::Term],
+                }
+            }
+            error => error,
+        })
+    })
 }
 
 /// Parse an [`AstNode::RotationMatrix`].
-fn parse_rotation_matrix(tokens: TokenList) -> IResult<TokenList, AstNode> {
+fn parse_rotation_matrix(tokens: TokenList) -> ParseResult<AstNode> {
     tuple((
         consume_basic_token(Token::Rot),
         consume_basic_token(Token::OpenParen),
@@ -166,16 +337,318 @@ fn parse_rotation_matrix(tokens: TokenList) -> IResult<TokenList, AstNode> {
     .parse(tokens)
 }
 
-/// Parse an anonymous 2D matrix, like `[1 2; 3 4]`.
-fn parse_anonymous_2d_matrix(tokens: TokenList) -> IResult<TokenList, AstNode> {
-    let (tokens, ()) = consume_basic_token(Token::OpenSquareBracket)(tokens)?;
+/// Parse an [`AstNode::DotProduct`], like `dot(u, v)`.
+fn parse_dot_product(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_two_argument_function(Token::Dot)
+        .map(|(left, right)| AstNode::DotProduct {
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::CrossProduct`], like `cross(u, v)`.
+fn parse_cross_product(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_two_argument_function(Token::Cross)
+        .map(|(left, right)| AstNode::CrossProduct {
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::Row`], like `row(A, 1)`.
+fn parse_row(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_two_argument_function(Token::Row)
+        .map(|(matrix, index)| AstNode::Row {
+            matrix: Box::new(matrix),
+            index: Box::new(index),
+        })
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::Column`], like `col(A, 1)`.
+fn parse_column(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_two_argument_function(Token::Col)
+        .map(|(matrix, index)| AstNode::Column {
+            matrix: Box::new(matrix),
+            index: Box::new(index),
+        })
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::Solve`], like `solve(A, b)`.
+fn parse_solve(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_two_argument_function(Token::Solve)
+        .map(|(matrix, vector)| AstNode::Solve {
+            matrix: Box::new(matrix),
+            vector: Box::new(vector),
+        })
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::LeastSquares`], like `lstsq(A, b)`.
+fn parse_least_squares(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_two_argument_function(Token::Lstsq)
+        .map(|(matrix, vector)| AstNode::LeastSquares {
+            matrix: Box::new(matrix),
+            vector: Box::new(vector),
+        })
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::Translation`], like `translate(1, 2)`.
+fn parse_translation(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_two_argument_function(Token::Translate)
+        .map(|(x, y)| AstNode::Translation {
+            x: Box::new(x),
+            y: Box::new(y),
+        })
+        .parse(tokens)
+}
+
+/// Parse a call to any of the builtin functions, like `dot(u, v)` or `rank(M)`.
+fn parse_function(tokens: TokenList) -> ParseResult<AstNode> {
+    alt((
+        parse_dot_product,
+        parse_cross_product,
+        parse_row,
+        parse_column,
+        parse_augment,
+        parse_block,
+        parse_solve,
+        parse_least_squares,
+        parse_translation,
+        parse_norm,
+        parse_adjugate,
+        parse_cofactor,
+        parse_rank,
+        parse_exponential,
+        parse_logarithm,
+        parse_pseudo_inverse,
+        parse_line_projection,
+        parse_plane_projection,
+        parse_random,
+        parse_has_property,
+    ))
+    .parse(tokens)
+}
+
+/// Parse an [`AstNode::Random`], like `rand(2, 42)` or `rand(3, 7, "orthogonal")`.
+fn parse_random(tokens: TokenList) -> ParseResult<AstNode> {
+    tuple((
+        consume_basic_token(Token::Rand),
+        consume_basic_token(Token::OpenParen),
+        parse_expression,
+        consume_basic_token(Token::Comma),
+        parse_expression,
+        opt(preceded(
+            consume_basic_token(Token::Comma),
+            parse_distribution_name,
+        )),
+        consume_basic_token(Token::CloseParen),
+    ))
+    .map(
+        |((), (), dimension, (), seed, distribution, ())| AstNode::Random {
+            distribution: distribution.unwrap_or_default(),
+            dimension: Box::new(dimension),
+            seed: Box::new(seed),
+        },
+    )
+    .parse(tokens)
+}
+
+/// Parse an [`AstNode::Norm`], like `norm(v)`, or an [`AstNode::MatrixNorm`], like
+/// `norm(M, "fro")`.
+fn parse_norm(tokens: TokenList) -> ParseResult<AstNode> {
+    tuple((
+        consume_basic_token(Token::Norm),
+        consume_basic_token(Token::OpenParen),
+        parse_expression,
+        opt(preceded(consume_basic_token(Token::Comma), parse_norm_name)),
+        consume_basic_token(Token::CloseParen),
+    ))
+    .map(|((), (), term, norm, ())| match norm {
+        Some(norm) => AstNode::MatrixNorm {
+            norm,
+            matrix: Box::new(term),
+        },
+        None => AstNode::Norm(Box::new(term)),
+    })
+    .parse(tokens)
+}
+
+/// Parse an [`AstNode::Adjugate`], like `adj(M)`.
+fn parse_adjugate(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_one_argument_function(Token::Adj)
+        .map(|term| AstNode::Adjugate(Box::new(term)))
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::Rank`], like `rank(M)`.
+fn parse_rank(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_one_argument_function(Token::Rank)
+        .map(|term| AstNode::Rank(Box::new(term)))
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::Exponential`], like `exp(M)`.
+fn parse_exponential(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_one_argument_function(Token::Exp)
+        .map(|term| AstNode::Exponential(Box::new(term)))
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::Logarithm`], like `logm(M)` or `log(M)`.
+fn parse_logarithm(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_one_argument_function(Token::Log)
+        .map(|term| AstNode::Logarithm(Box::new(term)))
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::PseudoInverse`], like `pinv(M)`.
+fn parse_pseudo_inverse(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_one_argument_function(Token::Pinv)
+        .map(|term| AstNode::PseudoInverse(Box::new(term)))
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::LineProjection`], like `proj_line(30)`.
+fn parse_line_projection(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_one_argument_function(Token::ProjLine)
+        .map(|term| AstNode::LineProjection(Box::new(term)))
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::PlaneProjection`], like `proj_plane([0; 0; 1])`.
+fn parse_plane_projection(tokens: TokenList) -> ParseResult<AstNode> {
+    parse_one_argument_function(Token::ProjPlane)
+        .map(|term| AstNode::PlaneProjection(Box::new(term)))
+        .parse(tokens)
+}
+
+/// Parse an [`AstNode::HasProperty`], like `is_orthogonal(M)`.
+fn parse_has_property(tokens: TokenList) -> ParseResult<AstNode> {
+    match tokens.tokens.first() {
+        Some(&Token::Property(property)) => parse_one_argument_function(Token::Property(property))
+            .map(|matrix| AstNode::HasProperty {
+                property,
+                matrix: Box::new(matrix),
+            })
+            .parse(tokens),
+        _ => Err(TokenParseError::expected(tokens, Expected::Term)),
+    }
+}
+
+/// Parse an [`AstNode::Cofactor`], like `cofactor(M, 1, 2)`.
+fn parse_cofactor(tokens: TokenList) -> ParseResult<AstNode> {
+    tuple((
+        consume_basic_token(Token::Cofactor),
+        consume_basic_token(Token::OpenParen),
+        parse_expression,
+        consume_basic_token(Token::Comma),
+        parse_expression,
+        consume_basic_token(Token::Comma),
+        parse_expression,
+        consume_basic_token(Token::CloseParen),
+    ))
+    .map(
+        |((), (), matrix, (), row, (), column, ())| AstNode::Cofactor {
+            matrix: Box::new(matrix),
+            row: Box::new(row),
+            column: Box::new(column),
+        },
+    )
+    .parse(tokens)
+}
+
+/// Parse an [`AstNode::Augment`], like `aug(u, v, w)`.
+fn parse_augment(tokens: TokenList) -> ParseResult<AstNode> {
+    tuple((
+        consume_basic_token(Token::Aug),
+        consume_basic_token(Token::OpenParen),
+        separated_list1(consume_basic_token(Token::Comma), parse_expression),
+        consume_basic_token(Token::CloseParen),
+    ))
+    .map(|((), (), columns, ())| AstNode::Augment { columns })
+    .parse(tokens)
+}
+
+/// Parse an [`AstNode::Block`], like `block(A, u; v, s)`.
+fn parse_block(tokens: TokenList) -> ParseResult<AstNode> {
+    tuple((
+        consume_basic_token(Token::Block),
+        consume_basic_token(Token::OpenParen),
+        parse_expression,
+        consume_basic_token(Token::Comma),
+        parse_expression,
+        consume_basic_token(Token::Semicolon),
+        parse_expression,
+        consume_basic_token(Token::Comma),
+        parse_expression,
+        consume_basic_token(Token::CloseParen),
+    ))
+    .map(
+        |((), (), top_left, (), top_right, (), bottom_left, (), bottom_right, ())| AstNode::Block {
+            top_left: Box::new(top_left),
+            top_right: Box::new(top_right),
+            bottom_left: Box::new(bottom_left),
+            bottom_right: Box::new(bottom_right),
+        },
+    )
+    .parse(tokens)
+}
+
+/// Parse a call to a builtin function which takes one argument, like `f(x)`, and return the
+/// argument.
+fn parse_one_argument_function<'l>(
+    function_token: Token,
+) -> impl Fn(TokenList<'l>) -> ParseResult<'l, AstNode> {
+    move |tokens: TokenList<'l>| {
+        tuple((
+            consume_basic_token(function_token.clone()),
+            consume_basic_token(Token::OpenParen),
+            parse_expression,
+            consume_basic_token(Token::CloseParen),
+        ))
+        .map(|((), (), argument, ())| argument)
+        .parse(tokens)
+    }
+}
+
+/// Parse a call to a builtin function which takes two arguments, like `f(x, y)`, and return the
+/// two arguments.
+fn parse_two_argument_function<'l>(
+    function_token: Token,
+) -> impl Fn(TokenList<'l>) -> ParseResult<'l, (AstNode, AstNode)> {
+    move |tokens: TokenList<'l>| {
+        tuple((
+            consume_basic_token(function_token.clone()),
+            consume_basic_token(Token::OpenParen),
+            parse_expression,
+            consume_basic_token(Token::Comma),
+            parse_expression,
+            consume_basic_token(Token::CloseParen),
+        ))
+        .map(|((), (), first, (), second, ())| (first, second))
+        .parse(tokens)
+    }
+}
+
+/// Parse an anonymous 2D matrix, like `[1 2; 3 4]` or `[1, 2; 3, 4]`.
+fn parse_anonymous_2d_matrix(start: TokenList) -> ParseResult<AstNode> {
+    let (tokens, ()) = consume_basic_token(Token::OpenSquareBracket)(start)?;
     let (tokens, ix) = parse_number(tokens)?;
+    let (tokens, comma_1) = parse_entry_separator(tokens)?;
     let (tokens, jx) = parse_number(tokens)?;
     let (tokens, ()) = consume_basic_token(Token::Semicolon)(tokens)?;
     let (tokens, iy) = parse_number(tokens)?;
+    let (tokens, comma_2) = parse_entry_separator(tokens)?;
     let (tokens, jy) = parse_number(tokens)?;
     let (tokens, ()) = consume_basic_token(Token::CloseSquareBracket)(tokens)?;
 
+    check_consistent_separators(start, &[comma_1, comma_2])?;
+
     let matrix = match (ix, jx, iy, jy) {
         (AstNode::Number(ix), AstNode::Number(jx), AstNode::Number(iy), AstNode::Number(jy)) => {
             AstNode::Anonymous2dMatrix(DMat2::from_cols(DVec2::new(ix, iy), DVec2::new(jx, jy)))
@@ -186,22 +659,33 @@ fn parse_anonymous_2d_matrix(tokens: TokenList) -> IResult<TokenList, AstNode> {
     Ok((tokens, matrix))
 }
 
-/// Parse an anonymous 3D matrix, like `[1 2 3; 4 5 6; 7 8 9]`.
-fn parse_anonymous_3d_matrix(tokens: TokenList) -> IResult<TokenList, AstNode> {
-    let (tokens, ()) = consume_basic_token(Token::OpenSquareBracket)(tokens)?;
+/// Parse an anonymous 3D matrix, like `[1 2 3; 4 5 6; 7 8 9]` or `[1, 2, 3; 4, 5, 6; 7, 8, 9]`.
+fn parse_anonymous_3d_matrix(start: TokenList) -> ParseResult<AstNode> {
+    let (tokens, ()) = consume_basic_token(Token::OpenSquareBracket)(start)?;
     let (tokens, ix) = parse_number(tokens)?;
+    let (tokens, comma_1) = parse_entry_separator(tokens)?;
     let (tokens, jx) = parse_number(tokens)?;
+    let (tokens, comma_2) = parse_entry_separator(tokens)?;
     let (tokens, kx) = parse_number(tokens)?;
     let (tokens, ()) = consume_basic_token(Token::Semicolon)(tokens)?;
     let (tokens, iy) = parse_number(tokens)?;
+    let (tokens, comma_3) = parse_entry_separator(tokens)?;
     let (tokens, jy) = parse_number(tokens)?;
+    let (tokens, comma_4) = parse_entry_separator(tokens)?;
     let (tokens, ky) = parse_number(tokens)?;
     let (tokens, ()) = consume_basic_token(Token::Semicolon)(tokens)?;
     let (tokens, iz) = parse_number(tokens)?;
+    let (tokens, comma_5) = parse_entry_separator(tokens)?;
     let (tokens, jz) = parse_number(tokens)?;
+    let (tokens, comma_6) = parse_entry_separator(tokens)?;
     let (tokens, kz) = parse_number(tokens)?;
     let (tokens, ()) = consume_basic_token(Token::CloseSquareBracket)(tokens)?;
 
+    check_consistent_separators(
+        start,
+        &[comma_1, comma_2, comma_3, comma_4, comma_5, comma_6],
+    )?;
+
     let matrix = match (ix, jx, kx, iy, jy, ky, iz, jz, kz) {
         (
             AstNode::Number(ix),
@@ -224,67 +708,204 @@ fn parse_anonymous_3d_matrix(tokens: TokenList) -> IResult<TokenList, AstNode> {
     Ok((tokens, matrix))
 }
 
+/// Parse an anonymous matrix which is 4×4 or larger, like `[1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1]`.
+///
+/// Smaller matrices are handled by [`parse_anonymous_2d_matrix`] and
+/// [`parse_anonymous_3d_matrix`], so this fails on them.
+fn parse_anonymous_dynamic_matrix(start: TokenList) -> ParseResult<AstNode> {
+    let number = |node| match node {
+        AstNode::Number(number) => number,
+        _ => panic!("parse_number should only ever return AstNode::Number"),
+    };
+
+    let (mut tokens, ()) = consume_basic_token(Token::OpenSquareBracket)(start)?;
+    let mut rows: Vec<Vec<f64>> = vec![];
+    let mut separators = vec![];
+
+    loop {
+        let (rest, first) = parse_number(tokens)?;
+        let mut row = vec![number(first)];
+        tokens = rest;
+
+        while let Ok((rest, (comma, entry))) =
+            tuple((parse_entry_separator, parse_number)).parse(tokens)
+        {
+            separators.push(comma);
+            row.push(number(entry));
+            tokens = rest;
+        }
+        rows.push(row);
+
+        // Smaller matrices can't end yet, so don't suggest closing them
+        match consume_basic_token(Token::Semicolon)(tokens) {
+            Ok((rest, ())) => tokens = rest,
+            Err(error) if rows.len() < 4 => return Err(error),
+            Err(_) => break,
+        }
+    }
+
+    let (tokens, ()) = consume_basic_token(Token::CloseSquareBracket)(tokens)?;
+
+    let matrix = match DMatN::from_rows(&rows) {
+        Some(matrix) if matrix.dimension() >= 4 => matrix,
+        _ => return Err(TokenParseError::expected(start, Expected::Term)),
+    };
+    check_consistent_separators(start, &separators)?;
+
+    Ok((tokens, AstNode::AnonymousDynamicMatrix(matrix)))
+}
+
+/// Parse the separator between two entries in the same row of an anonymous matrix, returning
+/// `true` if it was a comma and `false` if it was just whitespace.
+fn parse_entry_separator(tokens: TokenList) -> ParseResult<bool> {
+    match consume_basic_token(Token::Comma)(tokens) {
+        Ok((tokens, ())) => Ok((tokens, true)),
+        Err(_) => Ok((tokens, false)),
+    }
+}
+
+/// Check that an anonymous matrix starting at `start` uses either all commas or all whitespace to
+/// separate its entries, returning a [`nom::Err::Failure`] if not.
+///
+/// See [`ParseError::MixedMatrixSeparators`](super::ParseError::MixedMatrixSeparators).
+fn check_consistent_separators(
+    start: TokenList,
+    separators: &[bool],
+) -> Result<(), nom::Err<TokenParseError>> {
+    if separators.iter().all(|&comma| comma) || separators.iter().all(|&comma| !comma) {
+        Ok(())
+    } else {
+        Err(nom::Err::Failure(TokenParseError::MixedMatrixSeparators {
+            remaining: start.input_len(),
+        }))
+    }
+}
+
+/// Parse an anonymous 2D column vector, like `[1; 2]`.
+fn parse_anonymous_2d_vector(tokens: TokenList) -> ParseResult<AstNode> {
+    let (tokens, ()) = consume_basic_token(Token::OpenSquareBracket)(tokens)?;
+    let (tokens, x) = parse_number(tokens)?;
+    let (tokens, ()) = consume_basic_token(Token::Semicolon)(tokens)?;
+    let (tokens, y) = parse_number(tokens)?;
+    let (tokens, ()) = consume_basic_token(Token::CloseSquareBracket)(tokens)?;
+
+    let vector = match (x, y) {
+        (AstNode::Number(x), AstNode::Number(y)) => AstNode::Anonymous2dVector(DVec2::new(x, y)),
+        _ => panic!("parse_number should only ever return AstNode::Number"),
+    };
+
+    Ok((tokens, vector))
+}
+
+/// Parse an anonymous 3D column vector, like `[1; 2; 3]`.
+fn parse_anonymous_3d_vector(tokens: TokenList) -> ParseResult<AstNode> {
+    let (tokens, ()) = consume_basic_token(Token::OpenSquareBracket)(tokens)?;
+    let (tokens, x) = parse_number(tokens)?;
+    let (tokens, ()) = consume_basic_token(Token::Semicolon)(tokens)?;
+    let (tokens, y) = parse_number(tokens)?;
+    let (tokens, ()) = consume_basic_token(Token::Semicolon)(tokens)?;
+    let (tokens, z) = parse_number(tokens)?;
+    let (tokens, ()) = consume_basic_token(Token::CloseSquareBracket)(tokens)?;
+
+    let vector = match (x, y, z) {
+        (AstNode::Number(x), AstNode::Number(y), AstNode::Number(z)) => {
+            AstNode::Anonymous3dVector(DVec3::new(x, y, z))
+        }
+        _ => panic!("parse_number should only ever return AstNode::Number"),
+    };
+
+    Ok((tokens, vector))
+}
+
 /// Consume a basic token that has no corresponding [`AstNode`].
-fn consume_basic_token<'l>(
-    expected_token: Token,
-) -> impl Fn(TokenList<'l>) -> IResult<TokenList<'l>, ()> {
-    move |tokens: TokenList<'l>| {
-        let (rest, tok) = take(1usize)(tokens)?;
-        if !tok.tokens.is_empty() && tok.tokens[0] == expected_token {
-            Ok((rest, ()))
-        } else {
-            Err(nom::Err::Error(nom::error::Error::new(
-                tokens,
-                nom::error::ErrorKind::Tag,
-            )))
+fn consume_basic_token<'l>(expected_token: Token) -> impl Fn(TokenList<'l>) -> ParseResult<'l, ()> {
+    move |tokens: TokenList<'l>| match tokens.tokens.split_first() {
+        Some((token, rest)) if *token == expected_token => Ok((tokens.with_tokens(rest), ())),
+        _ => Err(TokenParseError::expected(
+            tokens,
+            Expected::Token(expected_token.clone()),
+        )),
+    }
+}
+
+/// Parse a [`Token::DistributionName`] into its distribution.
+fn parse_distribution_name(tokens: TokenList) -> ParseResult<RandomDistribution> {
+    match tokens.tokens.split_first() {
+        Some((&Token::DistributionName(distribution), rest)) => {
+            Ok((tokens.with_tokens(rest), distribution))
         }
+        _ => Err(TokenParseError::expected(
+            tokens,
+            Expected::DistributionName,
+        )),
+    }
+}
+
+/// Parse a [`Token::Index`] into its row and column.
+fn parse_index_token(tokens: TokenList) -> ParseResult<(usize, usize)> {
+    match tokens.tokens.split_first() {
+        Some((&Token::Index { row, column }, rest)) => {
+            Ok((tokens.with_tokens(rest), (row, column)))
+        }
+        _ => Err(TokenParseError::expected(tokens, Expected::Index)),
+    }
+}
+
+/// Parse a [`Token::NormName`] into its norm.
+fn parse_norm_name(tokens: TokenList) -> ParseResult<MatrixNorm> {
+    match tokens.tokens.split_first() {
+        Some((&Token::NormName(norm), rest)) => Ok((tokens.with_tokens(rest), norm)),
+        _ => Err(TokenParseError::expected(tokens, Expected::NormName)),
     }
 }
 
 /// Parse an [`AstNode::Number`].
-fn parse_number(tokens: TokenList) -> IResult<TokenList, AstNode> {
-    let (rest, tok) = take(1usize)(tokens)?;
-    if tok.tokens.is_empty() {
-        Err(nom::Err::Error(nom::error::Error::new(
-            tokens,
-            nom::error::ErrorKind::Tag,
-        )))
-    } else {
-        match tok.tokens[0] {
-            Token::Number(num) => Ok((rest, AstNode::Number(num))),
-            _ => Err(nom::Err::Error(nom::error::Error::new(
-                tokens,
-                nom::error::ErrorKind::Tag,
-            ))),
+fn parse_number(tokens: TokenList) -> ParseResult<AstNode> {
+    match tokens.tokens.split_first() {
+        Some((&Token::Number(num), rest)) => Ok((tokens.with_tokens(rest), AstNode::Number(num))),
+        _ => Err(TokenParseError::expected(tokens, Expected::Number)),
+    }
+}
+
+/// Parse an [`AstNode::Imaginary`], like `2i`, or just `i`.
+fn parse_imaginary(tokens: TokenList) -> ParseResult<AstNode> {
+    match tokens.tokens {
+        [Token::Number(num), Token::ImaginaryUnit, rest @ ..] => {
+            Ok((tokens.with_tokens(rest), AstNode::Imaginary(*num)))
         }
+        [Token::ImaginaryUnit, rest @ ..] => Ok((tokens.with_tokens(rest), AstNode::Imaginary(1.))),
+        _ => Err(TokenParseError::expected(
+            tokens,
+            Expected::Token(Token::ImaginaryUnit),
+        )),
     }
 }
 
 /// Parse an [`AstNode::NamedMatrix`].
-fn parse_named_matrix(tokens: TokenList) -> IResult<TokenList, AstNode> {
-    let (rest, tok) = take(1usize)(tokens)?;
-    if tok.tokens.is_empty() {
-        Err(nom::Err::Error(nom::error::Error::new(
-            tokens,
-            nom::error::ErrorKind::Tag,
-        )))
-    } else {
-        match &tok.tokens[0] {
-            Token::NamedMatrix(matrix_name) => {
-                Ok((rest, AstNode::NamedMatrix(matrix_name.clone())))
-            }
-            _ => Err(nom::Err::Error(nom::error::Error::new(
-                tokens,
-                nom::error::ErrorKind::Tag,
-            ))),
+fn parse_named_matrix(tokens: TokenList) -> ParseResult<AstNode> {
+    match tokens.tokens.split_first() {
+        Some((Token::NamedMatrix(matrix_name), rest)) => Ok((
+            tokens.with_tokens(rest),
+            AstNode::NamedMatrix(matrix_name.clone()),
+        )),
+        _ => Err(TokenParseError::expected(tokens, Expected::MatrixName)),
+    }
+}
+
+/// Parse an [`AstNode::Variable`].
+fn parse_variable(tokens: TokenList) -> ParseResult<AstNode> {
+    match tokens.tokens.split_first() {
+        Some((Token::Variable(name), rest)) => {
+            Ok((tokens.with_tokens(rest), AstNode::Variable(name.clone())))
         }
+        _ => Err(TokenParseError::expected(tokens, Expected::VariableName)),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::matrix::MatrixName;
+    use crate::matrix::{MatrixName, ScalarName};
     use Token as T;
     use TokenList as TL;
 
@@ -295,6 +916,11 @@ mod tests {
             Ok((TL::EMPTY, AstNode::NamedMatrix(MatrixName::new("M"))))
         );
 
+        assert_eq!(
+            parse_variable(TL::new(&[T::Variable(ScalarName::new("theta"))])),
+            Ok((TL::EMPTY, AstNode::Variable(ScalarName::new("theta"))))
+        );
+
         assert_eq!(
             parse_number(TL::new(&[T::Number(12.5)])),
             Ok((TL::EMPTY, AstNode::Number(12.5)))
@@ -480,6 +1106,332 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_vectors_success() {
+        assert_eq!(
+            parse_anonymous_2d_vector(TL::new(&[
+                T::OpenSquareBracket,
+                T::Number(1.),
+                T::Semicolon,
+                T::Number(2.),
+                T::CloseSquareBracket,
+            ])),
+            Ok((TL::EMPTY, AstNode::Anonymous2dVector(DVec2::new(1., 2.))))
+        );
+
+        assert_eq!(
+            parse_anonymous_3d_vector(TL::new(&[
+                T::OpenSquareBracket,
+                T::Number(1.),
+                T::Semicolon,
+                T::Number(2.),
+                T::Semicolon,
+                T::Number(3.),
+                T::CloseSquareBracket,
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::Anonymous3dVector(DVec3::new(1., 2., 3.))
+            ))
+        );
+
+        assert_eq!(
+            parse_dot_product(TL::new(&[
+                T::Dot,
+                T::OpenParen,
+                T::NamedMatrix(MatrixName::new("U")),
+                T::Comma,
+                T::Number(2.),
+                T::NamedMatrix(MatrixName::new("V")),
+                T::CloseParen,
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::DotProduct {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("U"))),
+                    right: Box::new(AstNode::Multiply {
+                        left: Box::new(AstNode::Number(2.)),
+                        right: Box::new(AstNode::NamedMatrix(MatrixName::new("V")))
+                    })
+                }
+            ))
+        );
+
+        assert_eq!(
+            parse_cross_product(TL::new(&[
+                T::Cross,
+                T::OpenParen,
+                T::NamedMatrix(MatrixName::new("U")),
+                T::Comma,
+                T::NamedMatrix(MatrixName::new("V")),
+                T::CloseParen,
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::CrossProduct {
+                    left: Box::new(AstNode::NamedMatrix(MatrixName::new("U"))),
+                    right: Box::new(AstNode::NamedMatrix(MatrixName::new("V")))
+                }
+            ))
+        );
+
+        assert!(parse_dot_product(TL::new(&[
+            T::Dot,
+            T::OpenParen,
+            T::NamedMatrix(MatrixName::new("U")),
+            T::CloseParen,
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn parse_index_success() {
+        assert_eq!(
+            parse_index(TL::new(&[
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Index { row: 1, column: 2 },
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::Index {
+                    matrix: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                    row: 1,
+                    column: 2
+                }
+            ))
+        );
+
+        // (A B)[2, 1] ^ 2
+        assert_eq!(
+            parse_exponent(TL::new(&[
+                T::OpenParen,
+                T::NamedMatrix(MatrixName::new("A")),
+                T::NamedMatrix(MatrixName::new("B")),
+                T::CloseParen,
+                T::Index { row: 2, column: 1 },
+                T::Caret,
+                T::Number(2.),
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::Exponent {
+                    base: Box::new(AstNode::Index {
+                        matrix: Box::new(AstNode::Multiply {
+                            left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                            right: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+                        }),
+                        row: 2,
+                        column: 1
+                    }),
+                    power: Box::new(AstNode::Number(2.))
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_row_column_success() {
+        assert_eq!(
+            parse_row(TL::new(&[
+                T::Row,
+                T::OpenParen,
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Comma,
+                T::Number(2.),
+                T::CloseParen,
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::Row {
+                    matrix: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                    index: Box::new(AstNode::Number(2.))
+                }
+            ))
+        );
+
+        assert_eq!(
+            parse_column(TL::new(&[
+                T::Col,
+                T::OpenParen,
+                T::NamedMatrix(MatrixName::new("A")),
+                T::NamedMatrix(MatrixName::new("B")),
+                T::Comma,
+                T::Number(1.),
+                T::Plus,
+                T::Number(1.),
+                T::CloseParen,
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::Column {
+                    matrix: Box::new(AstNode::Multiply {
+                        left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                        right: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+                    }),
+                    index: Box::new(AstNode::Add {
+                        left: Box::new(AstNode::Number(1.)),
+                        right: Box::new(AstNode::Number(1.))
+                    })
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_comma_separated_matrices() {
+        assert_eq!(
+            parse_anonymous_2d_matrix(TL::new(&[
+                T::OpenSquareBracket,
+                T::Number(1.),
+                T::Comma,
+                T::Number(2.),
+                T::Semicolon,
+                T::Number(3.),
+                T::Comma,
+                T::Number(4.),
+                T::CloseSquareBracket,
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::Anonymous2dMatrix(DMat2::from_cols(
+                    DVec2::new(1., 3.),
+                    DVec2::new(2., 4.)
+                ))
+            ))
+        );
+
+        assert_eq!(
+            parse_anonymous_3d_matrix(TL::new(&[
+                T::OpenSquareBracket,
+                T::Number(1.),
+                T::Comma,
+                T::Number(2.),
+                T::Comma,
+                T::Number(3.),
+                T::Semicolon,
+                T::Number(4.),
+                T::Comma,
+                T::Number(5.),
+                T::Comma,
+                T::Number(6.),
+                T::Semicolon,
+                T::Number(7.),
+                T::Comma,
+                T::Number(8.),
+                T::Comma,
+                T::Number(9.),
+                T::CloseSquareBracket,
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::Anonymous3dMatrix(DMat3::from_cols(
+                    DVec3::new(1., 4., 7.),
+                    DVec3::new(2., 5., 8.),
+                    DVec3::new(3., 6., 9.),
+                ))
+            ))
+        );
+
+        let mixed = [
+            T::OpenSquareBracket,
+            T::Number(1.),
+            T::Comma,
+            T::Number(2.),
+            T::Semicolon,
+            T::Number(3.),
+            T::Number(4.),
+            T::CloseSquareBracket,
+        ];
+        assert_eq!(
+            parse_anonymous_2d_matrix(TL::new(&mixed)),
+            Err(nom::Err::Failure(TokenParseError::MixedMatrixSeparators {
+                remaining: 8
+            }))
+        );
+        assert_eq!(
+            parse_expression(TL::new(&mixed)),
+            Err(nom::Err::Failure(TokenParseError::MixedMatrixSeparators {
+                remaining: 8
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_dynamic_matrices() {
+        use crate::matrix::expression::tokenise::tokenise_expression;
+
+        let parse = |expression| {
+            let tokens = tokenise_expression(expression).unwrap();
+            parse_anonymous_dynamic_matrix(TL::new(&tokens)).map(|(rest, ast)| {
+                assert_eq!(rest, TL::EMPTY);
+                ast
+            })
+        };
+
+        assert_eq!(
+            parse("[1 2 3 4; 5 6 7 8; 9 10 11 12; 13 14 15 16]"),
+            Ok(AstNode::AnonymousDynamicMatrix(DMatN::from_fn(
+                4,
+                |row, column| (4 * row + column + 1) as f64
+            )))
+        );
+        assert_eq!(
+            parse("[1, 0, 0, 0, 0; 0, 1, 0, 0, 0; 0, 0, 1, 0, 0; 0, 0, 0, 1, 0; 0, 0, 0, 0, 1]"),
+            Ok(AstNode::AnonymousDynamicMatrix(DMatN::identity(5)))
+        );
+
+        // Too small, not square, or not a matrix at all
+        for expression in [
+            "[1 2 3; 4 5 6; 7 8 9]",
+            "[1 2 3 4; 5 6 7 8; 9 10 11 12]",
+            "[1 2 3 4; 5 6 7; 9 10 11 12; 13 14 15 16]",
+            "[1; 2; 3; 4]",
+            "[1 2 3 4 5; 6 7 8 9 10; 11 12 13 14 15; 16 17 18 19 20;]",
+        ] {
+            assert!(
+                matches!(parse(expression), Err(nom::Err::Error(_))),
+                "{expression}"
+            );
+        }
+
+        assert!(matches!(
+            parse("[1 0 0 0; 0 1 0 0; 0 0 1 0; 0, 0, 0, 1]"),
+            Err(nom::Err::Failure(
+                TokenParseError::MixedMatrixSeparators { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn parse_imaginary_success() {
+        use crate::matrix::expression::tokenise::tokenise_expression;
+
+        let parse = |expression| {
+            let tokens = tokenise_expression(expression).unwrap();
+            parse_expression(TL::new(&tokens)).map(|(rest, ast)| {
+                assert_eq!(rest, TL::EMPTY);
+                ast
+            })
+        };
+
+        assert_eq!(
+            parse("3 + 2.5i"),
+            Ok(AstNode::Add {
+                left: Box::new(AstNode::Number(3.)),
+                right: Box::new(AstNode::Imaginary(2.5))
+            })
+        );
+        assert_eq!(
+            parse("-i * A"),
+            Ok(AstNode::Multiply {
+                left: Box::new(AstNode::Negate(Box::new(AstNode::Imaginary(1.)))),
+                right: Box::new(AstNode::NamedMatrix(MatrixName::new("A")))
+            })
+        );
+        assert_eq!(parse("2 i"), Ok(AstNode::Imaginary(2.)));
+        assert!(parse_imaginary(TL::new(&[T::Number(2.), T::Plus])).is_err());
+    }
+
     #[test]
     fn parse_compound_success() {
         // A + B * C
@@ -586,5 +1538,41 @@ mod tests {
                 }
             ))
         );
+
+        // 2 A * B is 2 * (A * B), whether the star is implicit or not
+        assert_eq!(
+            parse_expression(TL::new(&[
+                T::Number(2.),
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Star,
+                T::NamedMatrix(MatrixName::new("B")),
+            ])),
+            Ok((
+                TL::EMPTY,
+                AstNode::Multiply {
+                    left: Box::new(AstNode::Number(2.)),
+                    right: Box::new(AstNode::Multiply {
+                        left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                        right: Box::new(AstNode::NamedMatrix(MatrixName::new("B")))
+                    })
+                }
+            ))
+        );
+
+        // Long products are parsed in a loop, so they don't overflow the stack
+        let tokens: Vec<Token> = (0..5000)
+            .flat_map(|_| [T::NamedMatrix(MatrixName::new("A")), T::Star])
+            .chain([T::NamedMatrix(MatrixName::new("B"))])
+            .collect();
+        let (rest, mut ast) = parse_expression(TL::new(&tokens)).unwrap();
+        assert_eq!(rest, TL::EMPTY);
+        let mut factors = 1;
+        while let AstNode::Multiply { left, right } = &mut ast {
+            assert_eq!(**left, AstNode::NamedMatrix(MatrixName::new("A")));
+            ast = AstNode::take(right);
+            factors += 1;
+        }
+        assert_eq!(ast, AstNode::NamedMatrix(MatrixName::new("B")));
+        assert_eq!(factors, 5001);
     }
 }
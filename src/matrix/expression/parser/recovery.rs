@@ -0,0 +1,311 @@
+//! This module implements error recovery for the parser, so that we can report every mistake in
+//! an expression after a single parse, rather than just the first one.
+//!
+//! We don't recover inside the [`nom`] parsers themselves. Instead, whenever parsing fails, we
+//! record the error, patch the token list around the error so that the parser can get past it,
+//! and then parse again. Every token in the patched list remembers its index in the original
+//! list, so that the errors can point at the tokens that the user actually wrote.
+
+use super::{nom_impl::TokenParseError, parse_tokens_raw, AstNode, Expected, ParseError};
+use crate::matrix::expression::tokenise::Token;
+
+/// The term that we substitute for broken parts of the expression so that we can keep parsing.
+const PLACEHOLDER: Token = Token::Number(1.);
+
+/// Parse a list of tokens into an AST, recovering from errors to report as many of them as
+/// possible.
+///
+/// When the parser fails, we skip the broken part of the expression and carry on. A missing term
+/// (like in `2 * )`) gets replaced with a placeholder, a stray token at the end of an expression
+/// (like the `)` in `A B )`) gets skipped, and anything else replaces the innermost bracketed
+/// group containing the error with a placeholder. Errors that are right next to the previous
+/// error are assumed to be caused by it, so they're not reported.
+///
+/// If parsing succeeds first time, then this returns the same AST as [`parse_tokens_into_ast`],
+/// and if it fails, then the first error is the same one that [`parse_tokens_into_ast`] would
+/// return.
+///
+/// [`parse_tokens_into_ast`]: super::parse_tokens_into_ast
+pub fn parse_tokens_with_recovery(tokens: &[Token]) -> Result<AstNode, Vec<ParseError>> {
+    let mut working: Vec<(Token, Option<usize>)> = tokens
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(index, token)| (token, Some(index)))
+        .collect();
+    let mut errors = vec![];
+    let mut last_error_index: Option<usize> = None;
+
+    // Every patch either removes tokens or lets the parser get further, but we cap the number of
+    // attempts anyway so that a bug in the recovery can't hang the caller
+    for _ in 0..=4 * (tokens.len() + 1) {
+        let working_tokens: Vec<Token> = working.iter().map(|(token, _)| token.clone()).collect();
+
+        let error = match parse_tokens_raw(&working_tokens) {
+            Ok(ast) if errors.is_empty() => return Ok(ast),
+            Ok(_) => return Err(errors),
+            Err(error) => error,
+        };
+
+        let working_index = working.len() - error.remaining();
+        let original_index = original_index(&working, working_index, tokens.len());
+
+        if last_error_index.is_none_or(|last| original_index > last + 1) {
+            errors.push(ParseError::new(tokens, original_index, error.clone()));
+            last_error_index = Some(original_index);
+        }
+
+        // No patch can get the parser out of an expression that's nested too deeply, and any
+        // errors after it would just be caused by how we gave up on it
+        if let TokenParseError::TooDeeplyNested { .. } = error {
+            return Err(errors);
+        }
+
+        patch(&mut working, working_index, &error);
+    }
+
+    Err(errors)
+}
+
+/// Get the index in the original token list of the token at this index in the working list.
+///
+/// Tokens that we inserted don't have an original index, so we use the next original token, or
+/// the end of the original list.
+fn original_index(
+    working: &[(Token, Option<usize>)],
+    working_index: usize,
+    original_len: usize,
+) -> usize {
+    working[working_index..]
+        .iter()
+        .find_map(|&(_, index)| index)
+        .unwrap_or(original_len)
+}
+
+/// Patch the working token list so that the parser can get past this error at this index.
+fn patch(working: &mut Vec<(Token, Option<usize>)>, index: usize, error: &TokenParseError) {
+    let expected = match error {
+        TokenParseError::Expected { expected, .. } => expected,
+        TokenParseError::MixedMatrixSeparators { .. } => {
+            // The error points at the opening bracket of the matrix
+            let end = matching_close_bracket(working, index + 1);
+            working.splice(index..end, [(PLACEHOLDER, None)]);
+            return;
+        }
+        TokenParseError::TooDeeplyNested { .. } => {
+            unreachable!("Recovery should stop at an expression that's nested too deeply")
+        }
+    };
+
+    let found = working.get(index).map(|(token, _)| token);
+    let in_square_brackets = innermost_open_bracket(working, index)
+        .is_some_and(|start| working[start].0 == Token::OpenSquareBracket);
+
+    if expected.contains(&Expected::Operator) {
+        // There's a stray token after a complete expression, so we skip it
+        working.remove(index);
+    } else if expected.contains(&Expected::Term) && !in_square_brackets {
+        if found.is_none_or(is_synchronising) {
+            working.insert(index, (PLACEHOLDER, None));
+        } else {
+            working.remove(index);
+        }
+    } else if let Some(start) = innermost_open_bracket(working, index) {
+        let start = match start.checked_sub(1).map(|before| &working[before].0) {
+            Some(token) if working[start].0 == Token::OpenParen && is_function(token) => start - 1,
+            _ => start,
+        };
+        let end = matching_close_bracket(working, index);
+        working.splice(start..end, [(PLACEHOLDER, None)]);
+    } else if let Some(Expected::Token(token)) = expected
+        .iter()
+        .find(|expected| matches!(expected, Expected::Token(_)))
+    {
+        working.insert(index, (token.clone(), None));
+    } else {
+        working.insert(index, (PLACEHOLDER, None));
+    }
+}
+
+/// Find the index of the innermost bracket that was opened before this index and is still open.
+fn innermost_open_bracket(working: &[(Token, Option<usize>)], index: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (position, (token, _)) in working[..index].iter().enumerate().rev() {
+        if is_close_bracket(token) {
+            depth += 1;
+        } else if is_open_bracket(token) {
+            match depth.checked_sub(1) {
+                Some(new_depth) => depth = new_depth,
+                None => return Some(position),
+            }
+        }
+    }
+    None
+}
+
+/// Find the index just after the bracket that closes the group containing this index, or the end
+/// of the list if the group never gets closed.
+fn matching_close_bracket(working: &[(Token, Option<usize>)], index: usize) -> usize {
+    let mut depth = 0usize;
+    for (position, (token, _)) in working.iter().enumerate().skip(index) {
+        if is_open_bracket(token) {
+            depth += 1;
+        } else if is_close_bracket(token) {
+            match depth.checked_sub(1) {
+                Some(new_depth) => depth = new_depth,
+                None => return position + 1,
+            }
+        }
+    }
+    working.len()
+}
+
+/// Is this token an opening bracket of any kind?
+fn is_open_bracket(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::OpenParen | Token::OpenSquareBracket | Token::OpenBrace
+    )
+}
+
+/// Is this token a closing bracket of any kind?
+fn is_close_bracket(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::CloseParen | Token::CloseSquareBracket | Token::CloseBrace
+    )
+}
+
+/// Is this token one that can come straight after a term, like an operator or closing bracket?
+///
+/// If the parser wanted a term but found one of these, then the term is missing, rather than
+/// being replaced by a stray token.
+fn is_synchronising(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Plus
+            | Token::Minus
+            | Token::Star
+            | Token::Slash
+            | Token::Caret
+            | Token::Comma
+            | Token::Semicolon
+    ) || is_close_bracket(token)
+}
+
+/// Is this token the name of a builtin function, which must be followed by brackets?
+fn is_function(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Rot
+            | Token::Dot
+            | Token::Cross
+            | Token::Row
+            | Token::Col
+            | Token::Aug
+            | Token::Block
+            | Token::Solve
+            | Token::Lstsq
+            | Token::ProjLine
+            | Token::ProjPlane
+            | Token::Translate
+            | Token::Norm
+            | Token::Adj
+            | Token::Cofactor
+            | Token::Rank
+            | Token::Rand
+            | Token::Exp
+            | Token::Log
+            | Token::Pinv
+            | Token::Property(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{expression::tokenise::tokenise_expression, MatrixName};
+
+    /// Tokenise and parse this expression with recovery, and return the index of the offending
+    /// token for each error.
+    fn error_indices(expression: &str) -> Vec<usize> {
+        let tokens = tokenise_expression(expression).unwrap();
+        parse_tokens_with_recovery(&tokens)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| match error {
+                ParseError::Unexpected(diagnostic)
+                | ParseError::MixedMatrixSeparators(diagnostic)
+                | ParseError::TooDeeplyNested(diagnostic) => diagnostic.token_index,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recovery_success() {
+        let tokens = tokenise_expression("2 * A + rot(90)").unwrap();
+        assert_eq!(
+            parse_tokens_with_recovery(&tokens),
+            Ok(super::super::parse_tokens_into_ast(&tokens).unwrap())
+        );
+    }
+
+    #[test]
+    fn recovery_single_error() {
+        for expression in [
+            "2 * )",
+            "C++",
+            "(1 + 2",
+            "dot(A; B)",
+            "[1 2 3 4]",
+            "[1",
+            "rot 90",
+            "A^{2",
+            "[1, 2; 3 4]",
+        ] {
+            let tokens = tokenise_expression(expression).unwrap();
+            let errors = parse_tokens_with_recovery(&tokens).unwrap_err();
+            assert_eq!(
+                errors,
+                vec![super::super::parse_tokens_into_ast(&tokens).unwrap_err()],
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn recovery_multiple_errors() {
+        assert_eq!(error_indices("2 * ) + (3 * )"), vec![2, 7]);
+        assert_eq!(error_indices("(1 + ) * [1 2 3 4] - A B )"), vec![3, 10, 14]);
+        assert_eq!(error_indices("dot(A; B) + norm(,) / C++"), vec![3, 9, 14]);
+        assert_eq!(error_indices("[1, 2; 3 4] + [1 2; 3, 4]"), vec![0, 9]);
+
+        // Recovery stops at the first expression that's nested too deeply
+        let depth = super::super::MAX_NESTING_DEPTH;
+        let expression = format!("2 * ) + {}A{} + )", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(error_indices(&expression), vec![2, 4 + depth]);
+
+        let tokens = tokenise_expression("A + * B )").unwrap();
+        assert_eq!(
+            parse_tokens_with_recovery(&tokens),
+            Err(vec![
+                ParseError::Unexpected(Box::new(super::super::Diagnostic {
+                    token_index: 2,
+                    span: None,
+                    column: None,
+                    found: Some(Token::Star),
+                    after: Some(Token::Plus),
+                    expected: vec![Expected::Term],
+                })),
+                ParseError::Unexpected(Box::new(super::super::Diagnostic {
+                    token_index: 4,
+                    span: None,
+                    column: None,
+                    found: Some(Token::CloseParen),
+                    after: Some(Token::NamedMatrix(MatrixName::new("B"))),
+                    expected: vec![Expected::Operator, Expected::EndOfExpression],
+                })),
+            ])
+        );
+    }
+}
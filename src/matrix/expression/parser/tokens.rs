@@ -2,7 +2,7 @@
 //! [`nom`] parsers.
 
 use crate::matrix::expression::tokenise::Token;
-use nom::{InputIter, InputTake};
+use nom::{InputIter, InputLength, InputTake};
 use std::iter::Enumerate;
 
 /// A list of tokens.
@@ -10,17 +10,31 @@ use std::iter::Enumerate;
 pub struct TokenList<'l> {
     /// The list of tokens themselves.
     pub tokens: &'l [Token],
+
+    /// How many levels of nesting the parser is inside at the start of these tokens. See
+    /// [`MAX_NESTING_DEPTH`](super::MAX_NESTING_DEPTH).
+    pub depth: usize,
 }
 
 impl<'l> TokenList<'l> {
     /// The empty [`TokenList`], primarily used for asserting parser behaviour.
     #[cfg(test)]
-    pub const EMPTY: Self = Self { tokens: &[] };
+    pub const EMPTY: Self = Self {
+        tokens: &[],
+        depth: 0,
+    };
 
     /// Create a new [`TokenList`] from this list of tokens.
     #[inline]
     pub fn new<'t: 'l>(tokens: &'t [Token]) -> Self {
-        Self { tokens }
+        Self { tokens, depth: 0 }
+    }
+
+    /// Create a [`TokenList`] of these tokens at the same depth as this one, like the tokens
+    /// left over after parsing the start of this list.
+    #[inline]
+    pub fn with_tokens(self, tokens: &'l [Token]) -> Self {
+        Self { tokens, ..self }
     }
 }
 
@@ -28,12 +42,23 @@ impl InputTake for TokenList<'_> {
     fn take(&self, count: usize) -> Self {
         Self {
             tokens: &self.tokens[0..count],
+            depth: self.depth,
         }
     }
 
     fn take_split(&self, count: usize) -> (Self, Self) {
         let (first, second) = self.tokens.split_at(count);
-        (TokenList { tokens: second }, TokenList { tokens: first })
+        let split = |tokens| Self {
+            tokens,
+            depth: self.depth,
+        };
+        (split(second), split(first))
+    }
+}
+
+impl InputLength for TokenList<'_> {
+    fn input_len(&self) -> usize {
+        self.tokens.len()
     }
 }
 
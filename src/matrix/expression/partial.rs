@@ -0,0 +1,191 @@
+//! This module handles evaluating as much of an AST as possible when some of the named matrices
+//! or variables aren't defined yet.
+
+use super::ast::{AstNode, EvaluationError};
+use crate::matrix::{
+    map::{prelude::*, ScalarMapError},
+    MatrixValue,
+};
+
+impl AstNode {
+    /// Evaluate as much of this AST as possible, leaving the parts that depend on undefined
+    /// matrices as they are.
+    ///
+    /// Where [`evaluate`](Self::evaluate) would fail with [`MatrixMapError::NameNotDefined`],
+    /// this instead returns a reduced AST. Every subtree that only depends on defined matrices is
+    /// replaced with its value as a literal, and the rest of the tree is kept. If every matrix is
+    /// defined, then the result is just the value of the whole expression as a literal.
+    ///
+    /// Any other error in a part of the expression that can be evaluated is still returned, but
+    /// errors that involve an undefined matrix can't be found until it's defined.
+    ///
+    /// ```
+    /// # use trinity::matrix::{MatrixName, expression::parse_expression_from_string, map::prelude::*};
+    /// # use glam::DMat2;
+    /// let mut map = MatrixMap2::new();
+    /// map.set(MatrixName::new("A"), DMat2::from_cols_array(&[1., 2., 3., 4.])).unwrap();
+    ///
+    /// let partial = |expression| {
+    ///     parse_expression_from_string(expression)
+    ///         .unwrap()
+    ///         .evaluate_partially(&map)
+    ///         .map(|ast| ast.to_expression_string())
+    /// };
+    ///
+    /// assert_eq!(partial("(2 + 3) * B + A"), Ok("(5 * B) + [1 3; 2 4]".to_string()));
+    /// assert_eq!(partial("norm([3; 4]) * A"), Ok("[5 15; 10 20]".to_string()));
+    /// assert!(partial("B + A * [1 2 3; 4 5 6; 7 8 9]").is_err());
+    /// ```
+    pub fn evaluate_partially(self, map: &impl MatrixMap) -> Result<Self, EvaluationError> {
+        self.try_map_bottom_up(|node| node.evaluate_node_partially(map))
+    }
+
+    /// Evaluate this node like [`evaluate_partially`](Self::evaluate_partially), assuming that
+    /// its children have already been evaluated as far as they can be.
+    fn evaluate_node_partially(self, map: &impl MatrixMap) -> Result<Self, EvaluationError> {
+        match &self {
+            Self::NamedMatrix(name) => match map.get(name) {
+                Ok(matrix) => Ok(Into::<MatrixValue>::into(matrix).into()),
+                Err(MatrixMapError::NameNotDefined(_)) => Ok(self),
+                Err(error) => Err(error.into()),
+            },
+            Self::Variable(name) => match map.scalar(name) {
+                Ok(number) => Ok(Self::Number(number)),
+                Err(ScalarMapError::NameNotDefined(_)) => Ok(self),
+                Err(error) => Err(error.into()),
+            },
+            node if node.children().into_iter().all(Self::is_value_literal) => {
+                Ok(self.evaluate(map)?.into())
+            }
+            _ => Ok(self),
+        }
+    }
+
+    /// Is this node a literal number, matrix, or vector, like [`evaluate_partially`] produces for
+    /// values that it knows?
+    ///
+    /// [`evaluate_partially`]: Self::evaluate_partially
+    fn is_value_literal(&self) -> bool {
+        matches!(
+            self,
+            Self::Number(_)
+                | Self::Imaginary(_)
+                | Self::Anonymous2dMatrix(_)
+                | Self::Anonymous3dMatrix(_)
+                | Self::AnonymousDynamicMatrix(_)
+                | Self::Anonymous2dVector(_)
+                | Self::Anonymous3dVector(_)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::{
+        expression::{
+            ast::{AstNode, EvaluationError},
+            parse_expression_from_string,
+        },
+        map::prelude::*,
+        MatrixName,
+    };
+    use glam::DMat2;
+
+    /// Parse this expression, panicking if it's invalid.
+    fn parse(expression: &str) -> AstNode {
+        parse_expression_from_string(expression).unwrap()
+    }
+
+    /// A map with `A` defined.
+    fn map() -> MatrixMap2 {
+        let mut map = MatrixMap2::new();
+        map.set(
+            MatrixName::new("A"),
+            DMat2::from_cols_array(&[1., 2., 3., 4.]),
+        )
+        .unwrap();
+        map
+    }
+
+    #[test]
+    fn evaluate_partially_reduces_known_parts() {
+        let map = map();
+        let a = AstNode::from(DMat2::from_cols_array(&[1., 2., 3., 4.]));
+
+        assert_eq!(parse("B").evaluate_partially(&map), Ok(parse("B")));
+        assert_eq!(
+            parse("(2 * 3) * B ^ T").evaluate_partially(&map),
+            Ok(parse("6 * B ^ T"))
+        );
+        assert_eq!(
+            parse("B * A + C").evaluate_partially(&map),
+            Ok(AstNode::Add {
+                left: Box::new(AstNode::Multiply {
+                    left: Box::new(parse("B")),
+                    right: Box::new(a.clone()),
+                }),
+                right: Box::new(parse("C")),
+            })
+        );
+        assert_eq!(
+            parse("dot(B * [1; 0], A * [1; 0]) - 1").evaluate_partially(&map),
+            Ok(AstNode::Add {
+                left: Box::new(AstNode::DotProduct {
+                    left: Box::new(parse("B * [1; 0]")),
+                    right: Box::new(parse("[1; 2]")),
+                }),
+                right: Box::new(AstNode::Number(-1.)),
+            })
+        );
+    }
+
+    #[test]
+    fn evaluate_partially_matches_evaluate() {
+        let map = map();
+
+        for expression in [
+            "2 * A ^ T - A / 3",
+            "rot(90) * A[1, 2] + adj(A)",
+            "A ^ {-1} * [1; 2]",
+            "A + [1; 2]",
+            "(A - A) ^ {-1}",
+        ] {
+            let ast = parse(expression);
+            assert_eq!(
+                ast.clone().evaluate_partially(&map),
+                ast.evaluate(&map).map(AstNode::from),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_partially_errors() {
+        let map = map();
+
+        assert_eq!(
+            parse("B * (A + [1; 2])").evaluate_partially(&map),
+            Err(EvaluationError::CannotAddMatrixAndVector)
+        );
+        assert_eq!(
+            AstNode::Add {
+                left: Box::new(parse("B")),
+                right: Box::new(AstNode::NamedMatrix(MatrixName { name: "a".into() })),
+            }
+            .evaluate_partially(&map),
+            Err(EvaluationError::MatrixMapError(
+                MatrixMapError::InvalidName("a".into())
+            ))
+        );
+
+        // Errors involving undefined matrices can't be found yet
+        assert_eq!(
+            parse("B + [1; 2] ^ T").evaluate_partially(&map),
+            Err(EvaluationError::CannotTransposeVector)
+        );
+        assert_eq!(
+            parse("B ^ T + 2").evaluate_partially(&map),
+            Ok(parse("B ^ T + 2"))
+        );
+    }
+}
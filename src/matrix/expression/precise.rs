@@ -0,0 +1,568 @@
+//! This module provides an arbitrary-precision evaluation path for [`AstNode`], for
+//! numerically nasty expressions like high powers or inverses of nearly singular matrices.
+//!
+//! It's only available with the `high-precision` feature, and it only supports numbers and
+//! matrix arithmetic. See [`AstNode::evaluate_precise`].
+
+use super::ast::{check_limits, AstNode, EvaluationError, EvaluationLimits, NumberOrMatrix};
+use crate::matrix::{
+    map::MatrixMap,
+    square::{self, Entry, SquareMatrix},
+    DMatN, MatrixValue,
+};
+use dashu_float::DBig;
+use glam::DMat2;
+
+/// The result of evaluating an expression with [`AstNode::evaluate_precise`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum PreciseValue {
+    /// A scalar number.
+    Number(DBig),
+
+    /// A square matrix.
+    Matrix(PreciseMatrix),
+}
+
+/// A square matrix of arbitrary-precision decimal numbers, with any non-zero dimension.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreciseMatrix {
+    /// The rows of the matrix, from top to bottom. There are always as many rows as each row has
+    /// entries.
+    rows: Vec<Vec<DBig>>,
+}
+
+/// Convert a float into a decimal with the given number of significant digits, or `None` if it
+/// isn't finite.
+///
+/// This goes through the shortest decimal representation of the float, so `0.1` becomes exactly
+/// `0.1` rather than the nearest binary fraction.
+fn to_precise(number: f64, digits: usize) -> Option<DBig> {
+    number.is_finite().then(|| {
+        number
+            .to_string()
+            .parse::<DBig>()
+            .expect("A finite float should always be a valid decimal")
+            .with_precision(digits)
+            .value()
+    })
+}
+
+/// The absolute value of a decimal.
+fn abs(number: &DBig) -> DBig {
+    if *number < DBig::ZERO {
+        -number
+    } else {
+        number.clone()
+    }
+}
+
+/// Check that the number is an integer that fits in an `i64`, and return it.
+fn to_integer(number: &DBig) -> Option<i64> {
+    (number.fract() == DBig::ZERO)
+        .then(|| i64::try_from(number.to_int().value()).ok())
+        .flatten()
+}
+
+impl PreciseMatrix {
+    /// Convert a float matrix into a decimal matrix, with every entry rounded to the given
+    /// number of significant digits.
+    ///
+    /// Returns `None` if any entry isn't finite.
+    pub fn from_matrix_value(matrix: &MatrixValue, digits: usize) -> Option<Self> {
+        let dimension = matrix.dimension();
+        (0..dimension)
+            .map(|row| {
+                (0..dimension)
+                    .map(|column| to_precise(matrix.get(row, column)?, digits))
+                    .collect()
+            })
+            .collect::<Option<_>>()
+            .map(|rows| Self { rows })
+    }
+
+    /// The identity matrix of the given dimension.
+    pub fn identity(dimension: usize) -> Self {
+        Self {
+            rows: (0..dimension)
+                .map(|row| {
+                    (0..dimension)
+                        .map(|column| if row == column { DBig::ONE } else { DBig::ZERO })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// The number of rows and columns of this matrix.
+    pub fn dimension(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Get the entry in the given row and column of this matrix, if it exists. The indices are
+    /// 0-based.
+    pub fn get(&self, row: usize, column: usize) -> Option<&DBig> {
+        self.rows.get(row)?.get(column)
+    }
+
+    /// Round this matrix to the nearest float matrix.
+    pub fn to_matrix_value(&self) -> MatrixValue {
+        DMatN::from_fn(self.dimension(), |row, column| {
+            self.rows[row][column].to_f64().value()
+        })
+        .into()
+    }
+
+    /// The transpose of this matrix.
+    pub fn transpose(&self) -> Self {
+        let n = self.dimension();
+        Self {
+            rows: (0..n)
+                .map(|row| {
+                    (0..n)
+                        .map(|column| self.rows[column][row].clone())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Multiply every entry of this matrix by a number.
+    pub fn scale(&self, factor: &DBig) -> Self {
+        Self {
+            rows: self
+                .rows
+                .iter()
+                .map(|row| row.iter().map(|entry| entry * factor).collect())
+                .collect(),
+        }
+    }
+
+    /// Try to add two matrices together.
+    ///
+    /// This method will fail if the two matrices are of different dimensions.
+    pub fn try_add(left: &Self, right: &Self) -> Option<Self> {
+        (left.dimension() == right.dimension()).then(|| Self {
+            rows: left
+                .rows
+                .iter()
+                .zip(&right.rows)
+                .map(|(a, b)| a.iter().zip(b).map(|(a, b)| a + b).collect())
+                .collect(),
+        })
+    }
+
+    /// Try to multiply two matrices together.
+    ///
+    /// This method will fail if the two matrices are of different dimensions.
+    pub fn try_mul(left: &Self, right: &Self) -> Option<Self> {
+        let n = left.dimension();
+        (n == right.dimension()).then(|| Self {
+            rows: (0..n)
+                .map(|row| {
+                    (0..n)
+                        .map(|column| {
+                            (0..n).fold(DBig::ZERO, |sum, k| {
+                                sum + &left.rows[row][k] * &right.rows[k][column]
+                            })
+                        })
+                        .collect()
+                })
+                .collect(),
+        })
+    }
+
+    /// Raise this matrix to a non-negative integer power, using the square and multiply
+    /// algorithm.
+    pub fn powu(&self, power: u64) -> Self {
+        square::powu(self, power)
+    }
+
+    /// The determinant of this matrix, found with Gaussian elimination.
+    pub fn determinant(&self) -> DBig {
+        square::determinant(self)
+    }
+
+    /// Try to invert this matrix with Gauss-Jordan elimination, returning `None` if it's
+    /// singular.
+    ///
+    /// Unlike the float matrices, a pivot only counts as zero if it's exactly zero at this
+    /// precision.
+    pub fn try_inverse(&self) -> Option<Self> {
+        square::try_inverse(self, 0.)
+    }
+}
+
+impl Entry for DBig {
+    type Magnitude = DBig;
+
+    const ZERO: Self = DBig::ZERO;
+    const ONE: Self = DBig::ONE;
+
+    fn magnitude(&self) -> DBig {
+        abs(self)
+    }
+
+    fn scale_magnitude(magnitude: DBig, epsilon: f64) -> DBig {
+        let epsilon: DBig = epsilon
+            .to_string()
+            .parse()
+            .expect("The tolerance should be finite");
+        magnitude * epsilon
+    }
+}
+
+impl SquareMatrix for PreciseMatrix {
+    type Entry = DBig;
+
+    fn identity(dimension: usize) -> Self {
+        Self::identity(dimension)
+    }
+
+    fn dimension(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn try_mul(left: &Self, right: &Self) -> Option<Self> {
+        Self::try_mul(left, right)
+    }
+
+    fn to_rows(&self) -> Vec<Vec<DBig>> {
+        self.rows.clone()
+    }
+
+    fn from_square_rows(rows: Vec<Vec<DBig>>) -> Self {
+        Self { rows }
+    }
+}
+
+impl PreciseValue {
+    /// Round this value to the nearest float value.
+    pub fn to_number_or_matrix(&self) -> NumberOrMatrix {
+        match self {
+            Self::Number(number) => NumberOrMatrix::Number(number.to_f64().value()),
+            Self::Matrix(matrix) => NumberOrMatrix::Matrix(matrix.to_matrix_value()),
+        }
+    }
+
+    /// Try to multiply two values together.
+    fn try_mul(left: Self, right: Self) -> Result<Self, EvaluationError> {
+        match (left, right) {
+            (Self::Number(a), Self::Number(b)) => Ok(Self::Number(a * b)),
+            (Self::Number(a), Self::Matrix(b)) | (Self::Matrix(b), Self::Number(a)) => {
+                Ok(Self::Matrix(b.scale(&a)))
+            }
+            (Self::Matrix(a), Self::Matrix(b)) => PreciseMatrix::try_mul(&a, &b)
+                .map(Self::Matrix)
+                .ok_or(EvaluationError::CannotMultiplyDifferentDimensions),
+        }
+    }
+
+    /// Try to divide one value by another.
+    fn try_div(left: Self, right: Self) -> Result<Self, EvaluationError> {
+        match right {
+            Self::Number(divisor) if divisor == DBig::ZERO => {
+                Err(EvaluationError::CannotDivideByZero)
+            }
+            Self::Number(divisor) => match left {
+                Self::Number(a) => Ok(Self::Number(a / divisor)),
+                Self::Matrix(a) => Ok(Self::Matrix(a.scale(&(DBig::ONE / divisor)))),
+            },
+            Self::Matrix(_) => Err(EvaluationError::CannotDivideByMatrix),
+        }
+    }
+
+    /// Try to add two values together.
+    fn try_add(left: Self, right: Self) -> Result<Self, EvaluationError> {
+        match (left, right) {
+            (Self::Number(a), Self::Number(b)) => Ok(Self::Number(a + b)),
+            (Self::Matrix(a), Self::Matrix(b)) => PreciseMatrix::try_add(&a, &b)
+                .map(Self::Matrix)
+                .ok_or(EvaluationError::CannotAddDifferentDimensions),
+            _ => Err(EvaluationError::CannotAddNumberAndMatrix),
+        }
+    }
+
+    /// Negate this value.
+    fn negate(self) -> Self {
+        match self {
+            Self::Number(number) => Self::Number(-number),
+            Self::Matrix(matrix) => Self::Matrix(matrix.scale(&-DBig::ONE)),
+        }
+    }
+
+    /// Try to raise a value to a power.
+    ///
+    /// Matrices can only be raised to integer powers. Numbers can be raised to any power, except
+    /// that a negative number can only be raised to an integer power.
+    fn try_power(base: Self, power: Self) -> Result<Self, EvaluationError> {
+        let Self::Number(power) = power else {
+            return Err(EvaluationError::CannotRaiseToMatrix);
+        };
+
+        match base {
+            Self::Number(base) => match to_integer(&power) {
+                Some(power) if base == DBig::ZERO && power < 0 => {
+                    Err(EvaluationError::CannotDivideByZero)
+                }
+                Some(power) => Ok(Self::Number(base.powi(power.into()))),
+                None if base < DBig::ZERO => Err(EvaluationError::UnsupportedHighPrecision),
+                None => Ok(Self::Number(base.powf(&power))),
+            },
+            Self::Matrix(base) => {
+                let power =
+                    to_integer(&power).ok_or(EvaluationError::CannotRaiseMatrixToNonInteger)?;
+                let base = if power < 0 {
+                    base.try_inverse()
+                        .ok_or(EvaluationError::CannotInvertSingularMatrix)?
+                } else {
+                    base
+                };
+                Ok(Self::Matrix(base.powu(power.unsigned_abs())))
+            }
+        }
+    }
+}
+
+impl AstNode {
+    /// Evaluate this AST node with arbitrary-precision decimal arithmetic, keeping the given
+    /// number of significant digits throughout.
+    ///
+    /// Every number and matrix entry in the expression starts from the shortest decimal that
+    /// rounds to its float value, so literals like `0.1` are exact. Only numbers and matrices are
+    /// supported, along with adding, multiplying, dividing, negating, transposing, indexing, and
+    /// raising them to powers. Anything else returns
+    /// [`EvaluationError::UnsupportedHighPrecision`], and asking for no significant digits at all
+    /// returns [`EvaluationError::ZeroPrecision`].
+    ///
+    /// ```
+    /// # use trinity::matrix::{expression::{ast::NumberOrMatrix, parse_expression_from_string}, map::prelude::*};
+    /// let ast = parse_expression_from_string("(1 + 10^-20) - 1").unwrap();
+    /// let value = ast.evaluate_precise(&MatrixMap2::new(), 50).unwrap();
+    /// assert_eq!(value.to_number_or_matrix(), NumberOrMatrix::Number(1e-20));
+    /// assert_eq!(ast.evaluate(&MatrixMap2::new()), Ok(NumberOrMatrix::Number(0.)));
+    /// ```
+    pub fn evaluate_precise(
+        &self,
+        map: &impl MatrixMap,
+        digits: usize,
+    ) -> Result<PreciseValue, EvaluationError> {
+        if digits == 0 {
+            return Err(EvaluationError::ZeroPrecision);
+        }
+
+        self.try_fold_iteratively(
+            check_limits(EvaluationLimits::default()),
+            |node, children| node.evaluate_node_precise(map, digits, children),
+        )
+    }
+
+    /// Evaluate just this node with arbitrary precision, given the values of its
+    /// [`children`](Self::children).
+    fn evaluate_node_precise(
+        &self,
+        map: &impl MatrixMap,
+        digits: usize,
+        children: Vec<PreciseValue>,
+    ) -> Result<PreciseValue, EvaluationError> {
+        let mut children = children.into_iter();
+        let mut next = || {
+            children
+                .next()
+                .expect("Every child should have been evaluated before its parent")
+        };
+        let matrix = |matrix: MatrixValue| {
+            PreciseMatrix::from_matrix_value(&matrix, digits)
+                .map(PreciseValue::Matrix)
+                .ok_or(EvaluationError::UnsupportedHighPrecision)
+        };
+
+        match self {
+            Self::Multiply { .. } => PreciseValue::try_mul(next(), next()),
+            Self::Divide { .. } => PreciseValue::try_div(next(), next()),
+            Self::Add { .. } => PreciseValue::try_add(next(), next()),
+            Self::Negate(_) => Ok(next().negate()),
+            Self::Exponent { power, .. } => {
+                if power.is_transpose_marker() {
+                    match next() {
+                        PreciseValue::Number(_) => Err(EvaluationError::CannotTransposeNumber),
+                        PreciseValue::Matrix(matrix) => {
+                            Ok(PreciseValue::Matrix(matrix.transpose()))
+                        }
+                    }
+                } else {
+                    PreciseValue::try_power(next(), next())
+                }
+            }
+            Self::Number(number) => to_precise(*number, digits)
+                .map(PreciseValue::Number)
+                .ok_or(EvaluationError::UnsupportedHighPrecision),
+            Self::Variable(name) => to_precise(map.scalar(name)?, digits)
+                .map(PreciseValue::Number)
+                .ok_or(EvaluationError::UnsupportedHighPrecision),
+            Self::NamedMatrix(name) => matrix(map.get(name)?.into()),
+            Self::RotationMatrix { degrees } => {
+                matrix(MatrixValue::TwoD(DMat2::from_angle(degrees.to_radians())))
+            }
+            Self::Anonymous2dMatrix(value) => matrix(MatrixValue::TwoD(*value)),
+            Self::Anonymous3dMatrix(value) => matrix(MatrixValue::ThreeD(*value)),
+            Self::AnonymousDynamicMatrix(value) => matrix(MatrixValue::from(value.clone())),
+            Self::Index { row, column, .. } => match next() {
+                PreciseValue::Matrix(matrix) => row
+                    .checked_sub(1)
+                    .zip(column.checked_sub(1))
+                    .and_then(|(r, c)| matrix.get(r, c))
+                    .map(|entry| PreciseValue::Number(entry.clone()))
+                    .ok_or(EvaluationError::IndexOutOfBounds {
+                        row: *row,
+                        column: *column,
+                        dimension: matrix.dimension(),
+                    }),
+                PreciseValue::Number(_) => Err(EvaluationError::CannotIndexNonMatrix),
+            },
+            _ => Err(EvaluationError::UnsupportedHighPrecision),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{expression::parse_expression_from_string, map::prelude::*, MatrixName};
+    use glam::DMat3;
+
+    /// Parse and evaluate an expression with 50 significant digits.
+    fn evaluate(expression: &str, map: &impl MatrixMap) -> Result<PreciseValue, EvaluationError> {
+        parse_expression_from_string(expression)
+            .unwrap()
+            .evaluate_precise(map, 50)
+    }
+
+    /// Parse a decimal string.
+    fn decimal(number: &str) -> DBig {
+        number.parse().unwrap()
+    }
+
+    #[test]
+    fn evaluate_precise_numbers() {
+        let map = MatrixMap2::new();
+
+        assert_eq!(
+            evaluate("0.1 + 0.2", &map),
+            Ok(PreciseValue::Number(decimal("0.3")))
+        );
+        assert_eq!(
+            evaluate("(1 + 10^-30) - 1", &map),
+            Ok(PreciseValue::Number(decimal("1e-30")))
+        );
+        assert_eq!(
+            evaluate("2^100", &map),
+            Ok(PreciseValue::Number(decimal(
+                "1267650600228229401496703205376"
+            )))
+        );
+        assert_eq!(
+            evaluate("1 / 3", &map),
+            Ok(PreciseValue::Number(decimal(
+                "0.33333333333333333333333333333333333333333333333333"
+            )))
+        );
+        assert_eq!(
+            evaluate("1 / 0", &map),
+            Err(EvaluationError::CannotDivideByZero)
+        );
+        assert_eq!(
+            evaluate("0 ^ -1", &map),
+            Err(EvaluationError::CannotDivideByZero)
+        );
+        assert_eq!(
+            evaluate("(-2) ^ 0.5", &map),
+            Err(EvaluationError::UnsupportedHighPrecision)
+        );
+        assert_eq!(
+            evaluate("exp(1)", &map),
+            Err(EvaluationError::UnsupportedHighPrecision)
+        );
+
+        for expression in ["1 / 3", "2", "[1 2; 3 4] ^ -1"] {
+            assert_eq!(
+                parse_expression_from_string(expression)
+                    .unwrap()
+                    .evaluate_precise(&map, 0),
+                Err(EvaluationError::ZeroPrecision),
+                "{expression}"
+            );
+        }
+
+        let deep = vec!["1"; 12_000].join(" + ");
+        assert_eq!(
+            evaluate(&deep, &map),
+            Err(EvaluationError::ExpressionTooDeep { max_depth: 10_000 })
+        );
+    }
+
+    #[test]
+    fn evaluate_precise_matrices() {
+        let mut map = MatrixMap3::new();
+        // A nearly singular matrix, whose inverse has huge entries
+        map.set(
+            MatrixName::new("M"),
+            DMat3::from_cols_array(&[1., 1., 1., 1., 1.000000001, 1., 1., 1., 1.0000000001]),
+        )
+        .unwrap();
+        map.set(
+            MatrixName::new("A"),
+            DMat3::from_cols_array(&[2., 0., 0., 1., 3., 0., 0., 0., 1.]),
+        )
+        .unwrap();
+
+        let Ok(PreciseValue::Matrix(identity)) = evaluate("M * M^-1", &map) else {
+            panic!("M should be invertible");
+        };
+        let tolerance = decimal("1e-30");
+        for row in 0..3 {
+            for column in 0..3 {
+                let expected = if row == column { DBig::ONE } else { DBig::ZERO };
+                let entry = identity.get(row, column).unwrap();
+                assert!(
+                    abs(&(entry - &expected)) < tolerance,
+                    "{entry} != {expected}"
+                );
+            }
+        }
+
+        let Ok(PreciseValue::Matrix(cube)) = evaluate("A^3", &map) else {
+            panic!("A^3 should be a matrix");
+        };
+        assert_eq!(cube.determinant(), decimal("216"));
+        assert_eq!(cube.get(0, 1), Some(&decimal("19")));
+        assert_eq!(
+            evaluate("(A^T)[2, 1] + A[1, 2]", &map),
+            Ok(PreciseValue::Number(decimal("2")))
+        );
+
+        let Ok(PreciseValue::Matrix(inverse)) = evaluate("A^-2", &map) else {
+            panic!("A should be invertible");
+        };
+        assert_eq!(inverse.get(0, 0), Some(&decimal("0.25")));
+        assert_eq!(inverse.to_matrix_value().get(0, 1), Some(-5. / 36.),);
+
+        assert_eq!(
+            evaluate("A^0.5", &map),
+            Err(EvaluationError::CannotRaiseMatrixToNonInteger)
+        );
+        assert_eq!(
+            evaluate("(A - A)^-1", &map),
+            Err(EvaluationError::CannotInvertSingularMatrix)
+        );
+        assert_eq!(
+            evaluate("A + 1", &map),
+            Err(EvaluationError::CannotAddNumberAndMatrix)
+        );
+        assert_eq!(
+            evaluate("B", &map),
+            Err(EvaluationError::MatrixMapError(
+                MatrixMapError::NameNotDefined(MatrixName::new("B"))
+            ))
+        );
+    }
+}
@@ -0,0 +1,741 @@
+//! This module handles working out what shape of value an expression will evaluate to, without
+//! actually evaluating it. See [`AstNode::infer_shape`].
+
+use super::ast::{AstNode, EvalOptions, EvaluationError, NumberOrMatrix};
+use crate::{
+    math::{MatrixNorm, RandomDistribution},
+    matrix::{map::prelude::*, MatrixValue, Vector2dOr3d},
+};
+use std::{fmt, ptr};
+
+/// The shape of the value that an expression evaluates to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Shape {
+    /// A single number.
+    Number,
+
+    /// A 2D matrix.
+    Matrix2d,
+
+    /// A 3D matrix.
+    Matrix3d,
+
+    /// A matrix of any other dimension. See [`MatrixValue::Dynamic`].
+    MatrixNd(usize),
+
+    /// A 2D column vector.
+    Vector2d,
+
+    /// A 3D column vector.
+    Vector3d,
+
+    /// A complex number. See [`NumberOrMatrix::Complex`].
+    Complex,
+
+    /// A complex matrix of the given dimension. See [`NumberOrMatrix::ComplexMatrix`].
+    ComplexMatrix(usize),
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number => write!(f, "a number"),
+            Self::Matrix2d => write!(f, "a 2D matrix"),
+            Self::Matrix3d => write!(f, "a 3D matrix"),
+            Self::MatrixNd(dimension) => write!(f, "a {dimension}x{dimension} matrix"),
+            Self::Vector2d => write!(f, "a 2D vector"),
+            Self::Vector3d => write!(f, "a 3D vector"),
+            Self::Complex => write!(f, "a complex number"),
+            Self::ComplexMatrix(dimension) => {
+                write!(f, "a complex {dimension}x{dimension} matrix")
+            }
+        }
+    }
+}
+
+impl From<&NumberOrMatrix> for Shape {
+    fn from(value: &NumberOrMatrix) -> Self {
+        match value {
+            NumberOrMatrix::Number(_) => Self::Number,
+            NumberOrMatrix::Matrix(matrix) => matrix.into(),
+            NumberOrMatrix::Vector(Vector2dOr3d::TwoD(_)) => Self::Vector2d,
+            NumberOrMatrix::Vector(Vector2dOr3d::ThreeD(_)) => Self::Vector3d,
+            NumberOrMatrix::Complex(_) => Self::Complex,
+            NumberOrMatrix::ComplexMatrix(matrix) => Self::ComplexMatrix(matrix.dimension()),
+        }
+    }
+}
+
+impl From<&MatrixValue> for Shape {
+    fn from(value: &MatrixValue) -> Self {
+        match value {
+            MatrixValue::TwoD(_) => Self::Matrix2d,
+            MatrixValue::ThreeD(_) => Self::Matrix3d,
+            MatrixValue::Dynamic(matrix) => Self::MatrixNd(matrix.dimension()),
+        }
+    }
+}
+
+impl Shape {
+    /// Is this shape a matrix?
+    fn is_matrix(self) -> bool {
+        matches!(self, Self::Matrix2d | Self::Matrix3d | Self::MatrixNd(_))
+    }
+
+    /// Is this shape a vector?
+    fn is_vector(self) -> bool {
+        matches!(self, Self::Vector2d | Self::Vector3d)
+    }
+
+    /// The dimension of this matrix or vector, or `None` for a number.
+    fn dimension(self) -> Option<usize> {
+        match self {
+            Self::Number | Self::Complex => None,
+            Self::Matrix2d | Self::Vector2d => Some(2),
+            Self::Matrix3d | Self::Vector3d => Some(3),
+            Self::MatrixNd(dimension) | Self::ComplexMatrix(dimension) => Some(dimension),
+        }
+    }
+
+    /// Is this shape a complex number or matrix?
+    fn is_complex(self) -> bool {
+        matches!(self, Self::Complex | Self::ComplexMatrix(_))
+    }
+
+    /// The real shape with the same dimension as this one.
+    fn to_real(self) -> Self {
+        match self {
+            Self::Complex => Self::Number,
+            Self::ComplexMatrix(2) => Self::Matrix2d,
+            Self::ComplexMatrix(3) => Self::Matrix3d,
+            Self::ComplexMatrix(dimension) => Self::MatrixNd(dimension),
+            shape => shape,
+        }
+    }
+
+    /// The complex shape with the same dimension as this one. Vectors are always real, so they
+    /// stay the same.
+    fn to_complex(self) -> Self {
+        match self {
+            Self::Number => Self::Complex,
+            shape if shape.is_matrix() => {
+                Self::ComplexMatrix(shape.dimension().expect("Matrices have a dimension"))
+            }
+            shape => shape,
+        }
+    }
+
+    /// Apply a rule for real shapes to this shape. If this shape is complex, then the rule is
+    /// applied to the matching real shape, and the result is made complex again.
+    fn map_real(
+        self,
+        rule: impl FnOnce(Self) -> Result<Self, EvaluationError>,
+    ) -> Result<Self, EvaluationError> {
+        if self.is_complex() {
+            rule(self.to_real()).map(Self::to_complex)
+        } else {
+            rule(self)
+        }
+    }
+
+    /// Apply a rule for two real shapes to these shapes, like [`map_real`](Self::map_real). If
+    /// either shape is complex, then neither can be a vector, since vectors are always real.
+    fn combine_real(
+        self,
+        rhs: Self,
+        rule: impl FnOnce(Self, Self) -> Result<Self, EvaluationError>,
+    ) -> Result<Self, EvaluationError> {
+        if !self.is_complex() && !rhs.is_complex() {
+            rule(self, rhs)
+        } else if self.is_vector() || rhs.is_vector() {
+            Err(EvaluationError::CannotCombineVectorAndComplex)
+        } else {
+            rule(self.to_real(), rhs.to_real()).map(Self::to_complex)
+        }
+    }
+
+    /// Return [`EvaluationError::UnsupportedComplexMatrix`] if this is a
+    /// [`Self::ComplexMatrix`], since most matrix operations only support real matrices.
+    fn check_real_matrix(self) -> Result<Self, EvaluationError> {
+        match self {
+            Self::ComplexMatrix(_) => Err(EvaluationError::UnsupportedComplexMatrix),
+            shape => Ok(shape),
+        }
+    }
+
+    /// Return [`EvaluationError::UnsupportedDimension`] if this is a [`Self::MatrixNd`], since
+    /// most operations only support 2D and 3D matrices.
+    fn check_supported(self) -> Result<Self, EvaluationError> {
+        match self {
+            Self::MatrixNd(dimension) => Err(EvaluationError::UnsupportedDimension { dimension }),
+            shape => Ok(shape),
+        }
+    }
+
+    /// The shape of multiplying these shapes. See [`NumberOrMatrix::try_mul`].
+    fn try_mul(self, rhs: Self) -> Result<Self, EvaluationError> {
+        match (self, rhs) {
+            (Self::Number, shape) | (shape, Self::Number) => Ok(shape),
+            (Self::Matrix2d, Self::Matrix2d) => Ok(Self::Matrix2d),
+            (Self::Matrix3d, Self::Matrix3d) => Ok(Self::Matrix3d),
+            (Self::Matrix2d, Self::Vector2d) => Ok(Self::Vector2d),
+            (Self::Matrix3d, Self::Vector3d) => Ok(Self::Vector3d),
+            (Self::MatrixNd(a), Self::MatrixNd(b)) if a == b => Ok(Self::MatrixNd(a)),
+            (a, _) if a.is_matrix() => Err(EvaluationError::CannotMultiplyDifferentDimensions),
+            (_, b) if b.is_matrix() => Err(EvaluationError::CannotMultiplyVectorByMatrix),
+            _ => Err(EvaluationError::CannotMultiplyTwoVectors),
+        }
+    }
+
+    /// The shape of dividing these shapes. See [`NumberOrMatrix::try_div`].
+    fn try_div(self, rhs: Self) -> Result<Self, EvaluationError> {
+        match rhs {
+            Self::Number => Ok(self),
+            Self::Vector2d | Self::Vector3d => Err(EvaluationError::CannotDivideByVector),
+            _ => Err(EvaluationError::CannotDivideByMatrix),
+        }
+    }
+
+    /// The shape of adding these shapes. See [`NumberOrMatrix::try_add`].
+    fn try_add(self, rhs: Self) -> Result<Self, EvaluationError> {
+        match (self, rhs) {
+            (a, b) if a == b => Ok(a),
+            (Self::Number, b) | (b, Self::Number) if b.is_matrix() => {
+                Err(EvaluationError::CannotAddNumberAndMatrix)
+            }
+            (Self::Number, _) | (_, Self::Number) => Err(EvaluationError::CannotAddNumberAndVector),
+            (a, b) if a.is_matrix() == b.is_matrix() => {
+                Err(EvaluationError::CannotAddDifferentDimensions)
+            }
+            _ => Err(EvaluationError::CannotAddMatrixAndVector),
+        }
+    }
+
+    /// The shape of raising one shape to the power of another. See [`NumberOrMatrix::try_power`].
+    fn try_power(base: Self, power: Self) -> Result<Self, EvaluationError> {
+        match (base, power) {
+            (Self::Vector2d | Self::Vector3d, Self::Complex | Self::ComplexMatrix(_))
+            | (Self::Complex | Self::ComplexMatrix(_), Self::Vector2d | Self::Vector3d) => {
+                Err(EvaluationError::CannotCombineVectorAndComplex)
+            }
+            (_, Self::Matrix2d | Self::Matrix3d | Self::MatrixNd(_) | Self::ComplexMatrix(_)) => {
+                Err(EvaluationError::CannotRaiseToMatrix)
+            }
+            (_, Self::Vector2d | Self::Vector3d) => Err(EvaluationError::CannotRaiseToVector),
+            (Self::Vector2d | Self::Vector3d, Self::Number) => {
+                Err(EvaluationError::CannotRaiseVector)
+            }
+            (base, Self::Complex) if base.to_real().is_matrix() => {
+                Err(EvaluationError::CannotRaiseMatrixToNonInteger)
+            }
+            (_, Self::Complex) => Ok(Self::Complex),
+            (base, Self::Number) => Ok(base),
+        }
+    }
+
+    /// The shape of transposing this shape. See [`NumberOrMatrix::try_transpose`].
+    fn try_transpose(self) -> Result<Self, EvaluationError> {
+        match self {
+            Self::Number => Err(EvaluationError::CannotTransposeNumber),
+            Self::Vector2d | Self::Vector3d => Err(EvaluationError::CannotTransposeVector),
+            matrix => Ok(matrix),
+        }
+    }
+
+    /// The vector shape with the same dimension as this matrix shape.
+    fn column_shape(self) -> Self {
+        match self {
+            Self::Matrix3d | Self::Vector3d => Self::Vector3d,
+            _ => Self::Vector2d,
+        }
+    }
+}
+
+impl AstNode {
+    /// Work out the shape of the value that this expression would evaluate to, without
+    /// evaluating it.
+    ///
+    /// The map is only used to find the dimensions of the named matrices and to check that the
+    /// scalar variables are defined. If the expression is
+    /// not dimensionally consistent, then this returns the same error that
+    /// [`evaluate`](Self::evaluate) would. Errors that depend on the actual values, like trying
+    /// to invert a singular matrix, are not detected, so evaluation can still fail even if this
+    /// succeeds.
+    ///
+    /// ```
+    /// # use trinity::matrix::{MatrixName, expression::{parse_expression_from_string, shape::Shape}, map::prelude::*};
+    /// # use glam::DMat2;
+    /// let mut map = MatrixMap2::new();
+    /// map.set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+    ///
+    /// let shape = |expression| {
+    ///     parse_expression_from_string(expression)
+    ///         .unwrap()
+    ///         .infer_shape(&map)
+    /// };
+    ///
+    /// assert_eq!(shape("2 * A ^ T"), Ok(Shape::Matrix2d));
+    /// assert_eq!(shape("A * [1; 2]"), Ok(Shape::Vector2d));
+    /// assert_eq!(shape("norm(A)"), Ok(Shape::Number));
+    /// assert!(shape("A + [1 2 3; 4 5 6; 7 8 9]").is_err());
+    /// ```
+    pub fn infer_shape(&self, map: &impl MatrixMap) -> Result<Shape, EvaluationError> {
+        self.try_fold_iteratively(
+            |_| Ok(()),
+            |node, children: Vec<Shape>| {
+                node.shape_with(map, |child| {
+                    let index = node
+                        .children()
+                        .into_iter()
+                        .position(|node| ptr::eq(node, child))
+                        .expect("shape_with should only ask for the shapes of direct children");
+                    Ok(children[index])
+                })
+            },
+        )
+    }
+
+    /// Work out the shape of this node, using `child_shape` to get the shapes of its children.
+    ///
+    /// The map is only used to look up named matrices and scalar variables at this node, not in its
+    /// children.
+    pub(super) fn shape_with(
+        &self,
+        map: &impl MatrixMap,
+        mut child_shape: impl FnMut(&AstNode) -> Result<Shape, EvaluationError>,
+    ) -> Result<Shape, EvaluationError> {
+        match self {
+            Self::Multiply { left, right } => {
+                child_shape(left)?.combine_real(child_shape(right)?, Shape::try_mul)
+            }
+            Self::Divide { left, right } => {
+                child_shape(left)?.combine_real(child_shape(right)?, Shape::try_div)
+            }
+            Self::Add { left, right } => {
+                child_shape(left)?.combine_real(child_shape(right)?, Shape::try_add)
+            }
+            Self::Negate(term) => child_shape(term),
+            Self::Exponent { base, power } => {
+                if power.is_transpose_marker() {
+                    child_shape(base)?.map_real(Shape::try_transpose)
+                } else {
+                    Shape::try_power(child_shape(base)?, child_shape(power)?)
+                }
+            }
+            Self::Number(_) => Ok(Shape::Number),
+            Self::Variable(name) => map.scalar(name).map(|_| Shape::Number).map_err(Into::into),
+            Self::Imaginary(_) => Ok(Shape::Complex),
+            Self::NamedMatrix(name) => Ok((&map.get(name)?.into()).into()),
+            Self::RotationMatrix { .. } | Self::Anonymous2dMatrix(_) => Ok(Shape::Matrix2d),
+            Self::Anonymous3dMatrix(_) => Ok(Shape::Matrix3d),
+            Self::AnonymousDynamicMatrix(matrix) => Ok((&MatrixValue::from(matrix.clone())).into()),
+            Self::Anonymous2dVector(_) => Ok(Shape::Vector2d),
+            Self::Anonymous3dVector(_) => Ok(Shape::Vector3d),
+            Self::DotProduct { left, right } => match (child_shape(left)?, child_shape(right)?) {
+                (a, b) if a.is_vector() && a == b => Ok(Shape::Number),
+                (a, b) if a.is_vector() && b.is_vector() => {
+                    Err(EvaluationError::CannotDotDifferentDimensions)
+                }
+                _ => Err(EvaluationError::DotProductRequiresVectors),
+            },
+            Self::CrossProduct { left, right } => match (child_shape(left)?, child_shape(right)?) {
+                (Shape::Vector2d, Shape::Vector2d) => Ok(Shape::Number),
+                (Shape::Vector3d, Shape::Vector3d) => Ok(Shape::Vector3d),
+                (a, b) if a.is_vector() && b.is_vector() => {
+                    Err(EvaluationError::CannotCrossDifferentDimensions)
+                }
+                _ => Err(EvaluationError::CrossProductRequiresVectors),
+            },
+            Self::Index {
+                matrix,
+                row,
+                column,
+            } => child_shape(matrix)?.map_real(|shape| match shape.dimension() {
+                Some(dimension) if shape.is_matrix() => {
+                    if (1..=dimension).contains(row) && (1..=dimension).contains(column) {
+                        Ok(Shape::Number)
+                    } else {
+                        Err(EvaluationError::IndexOutOfBounds {
+                            row: *row,
+                            column: *column,
+                            dimension,
+                        })
+                    }
+                }
+                _ => Err(EvaluationError::CannotIndexNonMatrix),
+            }),
+            Self::Row { matrix, index } | Self::Column { matrix, index } => {
+                let shape = child_shape(matrix)?.check_real_matrix()?;
+                if !shape.is_matrix() {
+                    return Err(EvaluationError::CannotExtractFromNonMatrix);
+                }
+                if child_shape(index)? != Shape::Number {
+                    return Err(EvaluationError::IndexMustBePositiveInteger);
+                }
+                Ok(shape.check_supported()?.column_shape())
+            }
+            Self::Augment { columns } => {
+                let shapes = columns
+                    .iter()
+                    .map(&mut child_shape)
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                match shapes.as_slice() {
+                    [] => Err(EvaluationError::AugmentRequiresVectors),
+                    shapes if shapes.iter().any(|shape| !shape.is_vector()) => {
+                        Err(EvaluationError::AugmentRequiresVectors)
+                    }
+                    [first, rest @ ..] if rest.iter().any(|shape| shape != first) => {
+                        Err(EvaluationError::CannotAugmentDifferentDimensions)
+                    }
+                    [Shape::Vector2d, Shape::Vector2d] => Ok(Shape::Matrix2d),
+                    [Shape::Vector3d, Shape::Vector3d, Shape::Vector3d] => Ok(Shape::Matrix3d),
+                    [first, ..] => Err(EvaluationError::AugmentWrongNumberOfColumns {
+                        columns: shapes.len(),
+                        dimension: first.dimension().unwrap_or_default(),
+                    }),
+                }
+            }
+            Self::Block {
+                top_left,
+                top_right,
+                bottom_left,
+                bottom_right,
+            } => match (
+                child_shape(top_left)?,
+                child_shape(top_right)?,
+                child_shape(bottom_left)?,
+                child_shape(bottom_right)?,
+            ) {
+                (Shape::Matrix2d, Shape::Vector2d, Shape::Vector2d, Shape::Number) => {
+                    Ok(Shape::Matrix3d)
+                }
+                (Shape::MatrixNd(dimension), ..) => {
+                    Err(EvaluationError::UnsupportedDimension { dimension })
+                }
+                _ => Err(EvaluationError::InvalidBlockMatrix),
+            },
+            Self::Solve { matrix, vector } | Self::LeastSquares { matrix, vector } => {
+                match (child_shape(matrix)?, child_shape(vector)?) {
+                    (Shape::ComplexMatrix(_), _) => Err(EvaluationError::UnsupportedComplexMatrix),
+                    (Shape::MatrixNd(dimension), _) => {
+                        Err(EvaluationError::UnsupportedDimension { dimension })
+                    }
+                    (Shape::Matrix2d, Shape::Vector2d) => Ok(Shape::Vector2d),
+                    (Shape::Matrix3d, Shape::Vector3d) => Ok(Shape::Vector3d),
+                    (a, b) if a.is_matrix() && b.is_vector() => {
+                        Err(EvaluationError::CannotSolveDifferentDimensions)
+                    }
+                    _ => Err(EvaluationError::SolveRequiresMatrixAndVector),
+                }
+            }
+            Self::Translation { x, y } => match (child_shape(x)?, child_shape(y)?) {
+                (Shape::Number, Shape::Number) => Ok(Shape::Matrix3d),
+                _ => Err(EvaluationError::TranslationRequiresNumbers),
+            },
+            Self::Norm(term) => match child_shape(term)? {
+                Shape::Number | Shape::Complex => Err(EvaluationError::NormRequiresVectorOrMatrix),
+                _ => Ok(Shape::Number),
+            },
+            Self::Adjugate(term) => {
+                match child_shape(term)?.check_real_matrix()?.check_supported()? {
+                    shape if shape.is_matrix() => Ok(shape),
+                    _ => Err(EvaluationError::AdjugateRequiresMatrix),
+                }
+            }
+            Self::Cofactor {
+                matrix,
+                row,
+                column,
+            } => {
+                let shape = child_shape(matrix)?.check_real_matrix()?;
+                if !shape.is_matrix() {
+                    return Err(EvaluationError::CofactorRequiresMatrix);
+                }
+                if child_shape(row)? != Shape::Number || child_shape(column)? != Shape::Number {
+                    return Err(EvaluationError::IndexMustBePositiveInteger);
+                }
+                shape.check_supported()?;
+                Ok(Shape::Number)
+            }
+            Self::Rank(term) => match child_shape(term)?.check_real_matrix()?.check_supported()? {
+                shape if shape.is_matrix() => Ok(Shape::Number),
+                _ => Err(EvaluationError::RankRequiresMatrix),
+            },
+            Self::Exponential(term) => {
+                match child_shape(term)?.check_real_matrix()?.check_supported()? {
+                    shape if shape.is_vector() => {
+                        Err(EvaluationError::ExponentialRequiresNumberOrMatrix)
+                    }
+                    shape => Ok(shape),
+                }
+            }
+            Self::Logarithm(term) => match child_shape(term)?
+                .check_real_matrix()?
+                .check_supported()?
+            {
+                shape if shape.is_vector() => Err(EvaluationError::LogarithmRequiresNumberOrMatrix),
+                shape => Ok(shape),
+            },
+            Self::HasProperty { matrix, .. } => match child_shape(matrix)?.check_real_matrix()? {
+                shape if shape.is_matrix() => Ok(Shape::Number),
+                _ => Err(EvaluationError::PropertyRequiresMatrix),
+            },
+            Self::LineProjection(term) => match child_shape(term)? {
+                Shape::Number => Ok(Shape::Matrix2d),
+                _ => Err(EvaluationError::LineProjectionRequiresNumber),
+            },
+            Self::PlaneProjection(term) => match child_shape(term)? {
+                Shape::Vector3d => Ok(Shape::Matrix3d),
+                _ => Err(EvaluationError::PlaneProjectionRequiresVector),
+            },
+            Self::PseudoInverse(term) => {
+                match child_shape(term)?.check_real_matrix()?.check_supported()? {
+                    shape if shape.is_matrix() => Ok(shape),
+                    _ => Err(EvaluationError::PseudoInverseRequiresMatrix),
+                }
+            }
+            Self::MatrixNorm { norm, matrix } => match child_shape(matrix)? {
+                Shape::ComplexMatrix(_) if *norm == MatrixNorm::Frobenius => Ok(Shape::Number),
+                shape if shape.check_real_matrix()?.is_matrix() => Ok(Shape::Number),
+                _ => Err(EvaluationError::MatrixNormRequiresMatrix),
+            },
+            Self::Random {
+                dimension, seed, ..
+            } => match (child_shape(dimension)?, child_shape(seed)?) {
+                (Shape::Number, Shape::Number) => {
+                    // The shape depends on the value of the dimension, so that has to be evaluated,
+                    // but any seed gives the same shape
+                    let value = NumberOrMatrix::try_random(
+                        dimension.as_ref().clone().evaluate(map)?,
+                        NumberOrMatrix::Number(0.),
+                        RandomDistribution::Uniform,
+                        EvalOptions::default(),
+                    )?;
+                    Ok((&value).into())
+                }
+                _ => Err(EvaluationError::RandomRequiresIntegers),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{
+        expression::parse_expression_from_string, map::ScalarMapError, MatrixName, ScalarName,
+    };
+    use glam::DMat2;
+
+    /// A map with some 2D matrices defined.
+    fn map() -> MatrixMap2 {
+        let mut map = MatrixMap2::new();
+        map.set(
+            MatrixName::new("A"),
+            DMat2::from_cols_array(&[1., 2., 3., 4.]),
+        )
+        .unwrap();
+        map.set(
+            MatrixName::new("B"),
+            DMat2::from_cols_array(&[0., -1., 2., 5.]),
+        )
+        .unwrap();
+        map
+    }
+
+    #[test]
+    fn infer_shape_matches_evaluation() {
+        let map = map();
+
+        for expression in [
+            "2 * 3 + 1",
+            "A",
+            "2 * A ^ T - B / 3",
+            "A ^ {-1} * B ^ 2",
+            "rot(45) * [1; 2]",
+            "[1 2 3; 4 5 6; 7 8 10] * [1; 2; 3]",
+            "dot(A * [1; 0], [0; 1])",
+            "cross([1; 2], [3; 4])",
+            "cross([1; 0; 0], [0; 1; 0])",
+            "A[1, 2] + norm(B)",
+            "row(A, 1) + col(B, 2)",
+            "aug([1; 2], [3; 4]) * A",
+            "aug([1; 0; 0], [0; 1; 0], [0; 0; 1])",
+            "block(A, [1; 2]; [3; 4], 5)",
+            "solve(A, [1; 2])",
+            "proj_line(30) * A",
+            "proj_plane([1; 2; 3]) * [1; 1; 1]",
+            "translate(1, norm([3; 4])) * [1 2 3; 4 5 6; 7 8 9]",
+            "adj(A) + cofactor(A, 1, 2) * A",
+            "rank(A) * [1; 1]",
+            "[1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1] ^ {-2} * 3",
+            "(3 + 2i) * (1 - i) ^ 2",
+            "i * A + B / i",
+            "((i * A) ^ {-1}) ^ T",
+            "(2i * A)[1, 2] + norm(i * A) + exp(i) * log(i)",
+            "i * [1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1] * 2",
+            "is_orthogonal(A) * is_rotation(rot(30)) * [1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1]",
+            "norm(i * A, \"fro\") + norm([1 2 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1], \"spectral\")",
+            "rand(2, 42) * A + rand(1 + 2, 7, \"orthogonal\")[1, 1] * A",
+        ] {
+            let ast = parse_expression_from_string(expression).unwrap();
+            let value = ast.clone().evaluate(&map).unwrap();
+            assert_eq!(
+                ast.infer_shape(&map),
+                Ok(Shape::from(&value)),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn infer_shape_errors() {
+        let map = map();
+
+        for (expression, error) in [
+            (
+                "A + [1 2 3; 4 5 6; 7 8 9]",
+                EvaluationError::CannotAddDifferentDimensions,
+            ),
+            ("A + 1", EvaluationError::CannotAddNumberAndMatrix),
+            ("[1; 2] + 1", EvaluationError::CannotAddNumberAndVector),
+            ("A + [1; 2]", EvaluationError::CannotAddMatrixAndVector),
+            ("2 ^ T", EvaluationError::CannotTransposeNumber),
+            ("[1; 2] ^ T", EvaluationError::CannotTransposeVector),
+            ("2 / A", EvaluationError::CannotDivideByMatrix),
+            ("A / [1; 2]", EvaluationError::CannotDivideByVector),
+            (
+                "A * [1; 2; 3]",
+                EvaluationError::CannotMultiplyDifferentDimensions,
+            ),
+            ("[1; 2] * A", EvaluationError::CannotMultiplyVectorByMatrix),
+            ("[1; 2] * [1; 2]", EvaluationError::CannotMultiplyTwoVectors),
+            ("2 ^ A", EvaluationError::CannotRaiseToMatrix),
+            ("[1; 2] ^ 2", EvaluationError::CannotRaiseVector),
+            ("dot(A, B)", EvaluationError::DotProductRequiresVectors),
+            (
+                "cross([1; 2], [1; 2; 3])",
+                EvaluationError::CannotCrossDifferentDimensions,
+            ),
+            (
+                "A[3, 1]",
+                EvaluationError::IndexOutOfBounds {
+                    row: 3,
+                    column: 1,
+                    dimension: 2,
+                },
+            ),
+            (
+                "row([1; 2], 1)",
+                EvaluationError::CannotExtractFromNonMatrix,
+            ),
+            (
+                "aug([1; 2], [1; 2; 3])",
+                EvaluationError::CannotAugmentDifferentDimensions,
+            ),
+            (
+                "aug([1; 2; 3], [1; 2; 3])",
+                EvaluationError::AugmentWrongNumberOfColumns {
+                    columns: 2,
+                    dimension: 3,
+                },
+            ),
+            (
+                "block(A, 1; [1; 2], 1)",
+                EvaluationError::InvalidBlockMatrix,
+            ),
+            (
+                "solve(A, [1; 2; 3])",
+                EvaluationError::CannotSolveDifferentDimensions,
+            ),
+            ("norm(2)", EvaluationError::NormRequiresVectorOrMatrix),
+            ("adj([1; 2])", EvaluationError::AdjugateRequiresMatrix),
+            ("cofactor(2, 1, 1)", EvaluationError::CofactorRequiresMatrix),
+            ("rank(2)", EvaluationError::RankRequiresMatrix),
+            (
+                "is_singular([1; 2])",
+                EvaluationError::PropertyRequiresMatrix,
+            ),
+            (
+                "is_symmetric(i * A)",
+                EvaluationError::UnsupportedComplexMatrix,
+            ),
+            ("pinv(2)", EvaluationError::PseudoInverseRequiresMatrix),
+            (
+                "proj_line(A)",
+                EvaluationError::LineProjectionRequiresNumber,
+            ),
+            (
+                "proj_plane([1; 2])",
+                EvaluationError::PlaneProjectionRequiresVector,
+            ),
+            (
+                "translate(1, [1; 2])",
+                EvaluationError::TranslationRequiresNumbers,
+            ),
+            ("rand(A, 1)", EvaluationError::RandomRequiresIntegers),
+            (
+                "rand(4, 1)",
+                EvaluationError::UnsupportedDimension { dimension: 4 },
+            ),
+            (
+                "pinv([1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1])",
+                EvaluationError::UnsupportedDimension { dimension: 4 },
+            ),
+            (
+                "norm([1; 2], \"max\")",
+                EvaluationError::MatrixNormRequiresMatrix,
+            ),
+            (
+                "norm(i * A, \"spectral\")",
+                EvaluationError::UnsupportedComplexMatrix,
+            ),
+            (
+                "rank([1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1])",
+                EvaluationError::UnsupportedDimension { dimension: 4 },
+            ),
+            (
+                "A * [1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1]",
+                EvaluationError::CannotMultiplyDifferentDimensions,
+            ),
+            ("i * [1; 2]", EvaluationError::CannotCombineVectorAndComplex),
+            ("[1; 2] ^ i", EvaluationError::CannotCombineVectorAndComplex),
+            ("i + A", EvaluationError::CannotAddNumberAndMatrix),
+            ("A ^ i", EvaluationError::CannotRaiseMatrixToNonInteger),
+            ("i ^ (i * A)", EvaluationError::CannotRaiseToMatrix),
+            ("i / (i * A)", EvaluationError::CannotDivideByMatrix),
+            ("i ^ T", EvaluationError::CannotTransposeNumber),
+            ("norm(2i)", EvaluationError::NormRequiresVectorOrMatrix),
+            ("adj(i * A)", EvaluationError::UnsupportedComplexMatrix),
+            (
+                "exp(i * [1 0 0 0; 0 1 0 0; 0 0 1 0; 0 0 0 1])",
+                EvaluationError::UnsupportedComplexMatrix,
+            ),
+            (
+                "C",
+                EvaluationError::MatrixMapError(MatrixMapError::NameNotDefined(MatrixName::new(
+                    "C",
+                ))),
+            ),
+            (
+                "2x * A",
+                EvaluationError::ScalarMapError(ScalarMapError::NameNotDefined(ScalarName::new(
+                    "x",
+                ))),
+            ),
+        ] {
+            let ast = parse_expression_from_string(expression).unwrap();
+            assert_eq!(ast.infer_shape(&map), Err(error.clone()), "{expression}");
+            assert_eq!(ast.evaluate(&map), Err(error), "{expression}");
+        }
+
+        // Errors that depend on values aren't detected
+        let ast = parse_expression_from_string("(A - A) ^ {-1}").unwrap();
+        assert_eq!(ast.infer_shape(&map), Ok(Shape::Matrix2d));
+        assert_eq!(
+            ast.evaluate(&map),
+            Err(EvaluationError::CannotInvertSingularMatrix)
+        );
+    }
+}
@@ -0,0 +1,566 @@
+//! This module handles simplifying ASTs without knowing the values of any named matrices.
+
+use super::ast::{AstNode, NumberOrMatrix};
+use crate::matrix::{map::prelude::*, DMatN};
+use glam::f64::{DMat2, DMat3};
+use std::collections::HashMap;
+
+impl AstNode {
+    /// Simplify this AST by folding constants and removing operations that do nothing.
+    ///
+    /// Subtrees that don't reference any named matrices and evaluate to a number are replaced
+    /// with that number, so `2 * 3 + 1` becomes `7`. Subtrees that evaluate to a matrix or vector
+    /// are left alone, so that things like `rot(90)` stay readable. Double negations are removed,
+    /// as are multiplications and divisions by `1`, and additions of `0` to something that is
+    /// definitely a number.
+    ///
+    /// The simplified AST always evaluates to the same thing as the original, and if the
+    /// original fails to evaluate, then so does the simplified version. The simplification is
+    /// done bottom-up, so it's not clever enough to notice that `(A + 1) + 2` could be
+    /// `A + 3`.
+    ///
+    /// ```
+    /// # use trinity::matrix::expression::parse_expression_from_string;
+    /// let simplify = |expression| {
+    ///     parse_expression_from_string(expression)
+    ///         .unwrap()
+    ///         .simplify()
+    ///         .to_expression_string()
+    /// };
+    ///
+    /// assert_eq!(simplify("2 * 3 + 1"), "7");
+    /// assert_eq!(simplify("--A * (3 - 2)"), "A");
+    /// assert_eq!(simplify("A ^ (2 * 2) + norm(A) * (0 + 1)"), "(A ^ {4}) + norm(A)");
+    /// ```
+    pub fn simplify(self) -> Self {
+        self.map_bottom_up(Self::simplify_node)
+    }
+
+    /// Simplify this node like [`simplify`](Self::simplify), assuming that its children have
+    /// already been simplified.
+    fn simplify_node(mut self) -> Self {
+        if let Some(number) = self.fold_constant() {
+            return number;
+        }
+
+        match &mut self {
+            Self::Negate(term) => match &mut **term {
+                Self::Negate(inner) => Self::take(inner),
+                _ => self,
+            },
+            Self::Multiply { left, right } => {
+                if left.is_number(1.) {
+                    Self::take(right)
+                } else if right.is_number(1.) {
+                    Self::take(left)
+                } else {
+                    self
+                }
+            }
+            Self::Divide { left, right } => {
+                if right.is_number(1.) {
+                    Self::take(left)
+                } else {
+                    self
+                }
+            }
+            Self::Add { left, right } => {
+                if left.is_number(0.) && right.is_definitely_number() {
+                    Self::take(right)
+                } else if right.is_number(0.) && left.is_definitely_number() {
+                    Self::take(left)
+                } else {
+                    self
+                }
+            }
+            _ => self,
+        }
+    }
+
+    /// If this node doesn't reference any named matrices and evaluates to a finite number, then
+    /// return that number as a node.
+    ///
+    /// Negative numbers are returned as the negation of a positive number, since that's how the
+    /// parser represents them.
+    fn fold_constant(&self) -> Option<Self> {
+        if self.is_literal_number() || !self.named_matrices().is_empty() {
+            return None;
+        }
+
+        match self.clone().evaluate(&MatrixMap2::new()) {
+            Ok(NumberOrMatrix::Number(number)) if number.is_finite() => Some(if number < 0. {
+                Self::Negate(Box::new(Self::Number(-number)))
+            } else {
+                Self::Number(number)
+            }),
+            _ => None,
+        }
+    }
+
+    /// Is this node a number literal, or the negation of one?
+    fn is_literal_number(&self) -> bool {
+        match self {
+            Self::Number(_) => true,
+            Self::Negate(term) => matches!(**term, Self::Number(_)),
+            _ => false,
+        }
+    }
+
+    /// Is this node exactly this number?
+    fn is_number(&self, number: f64) -> bool {
+        match self {
+            Self::Number(n) => *n == number,
+            Self::Negate(term) => matches!(**term, Self::Number(n) if -n == number),
+            _ => false,
+        }
+    }
+
+    /// Is this node guaranteed to evaluate to a (possibly complex) number (if it evaluates
+    /// successfully at all), regardless of the values of any named matrices?
+    ///
+    /// This is conservative, so it returns `false` for things like the cross product, which is
+    /// only a number for 2D vectors.
+    pub(super) fn is_definitely_number(&self) -> bool {
+        self.definite_kind().number
+    }
+
+    /// Simplify this AST like [`simplify`](Self::simplify), and then rewrite it using algebraic
+    /// identities of matrices.
+    ///
+    /// The identities are:
+    /// - `A * I = I * A = A`, where `I` is an identity matrix literal or `rot(0)`
+    /// - `(A^T)^T = A`
+    /// - `(A^{-1})^{-1} = A`
+    /// - `rot(a) * rot(b) = rot(a + b)`
+    /// - `A * A^{-1} = A^{-1} * A = I`
+    ///
+    /// Unlike [`simplify`](Self::simplify), this assumes that the expression is well-formed. That
+    /// means that the dimensions of the matrices match and that every inverse exists, so it may
+    /// turn an expression that fails to evaluate into one that doesn't. The only exception is
+    /// for inverses of matrix literals, which are only cancelled if the matrix really is
+    /// invertible.
+    ///
+    /// Since we don't know the dimensions of named matrices, `A * A^{-1}` becomes `A^{0}`, which
+    /// evaluates to the identity matrix of the right dimension.
+    ///
+    /// ```
+    /// # use trinity::matrix::expression::parse_expression_from_string;
+    /// let simplify = |expression| {
+    ///     parse_expression_from_string(expression)
+    ///         .unwrap()
+    ///         .simplify_symbolic()
+    ///         .to_expression_string()
+    /// };
+    ///
+    /// assert_eq!(simplify("(A ^ T) ^ T * [1 0; 0 1]"), "A");
+    /// assert_eq!(simplify("rot(30) * rot(60)"), "rot(90)");
+    /// assert_eq!(simplify("M * M ^ {-1}"), "M ^ {0}");
+    /// ```
+    pub fn simplify_symbolic(self) -> Self {
+        self.simplify().rewrite_identities()
+    }
+
+    /// Rewrite this AST using the identities listed in [`simplify_symbolic`](Self::simplify_symbolic).
+    fn rewrite_identities(self) -> Self {
+        self.map_bottom_up(Self::rewrite_identity)
+    }
+
+    /// Rewrite this node like [`rewrite_identities`](Self::rewrite_identities), assuming that its
+    /// children have already been rewritten.
+    fn rewrite_identity(mut self) -> Self {
+        match &mut self {
+            Self::Multiply { left, right } => {
+                if left.is_identity() && right.is_definitely_matrix() {
+                    Self::take(right)
+                } else if right.is_identity() && left.is_definitely_matrix() {
+                    Self::take(left)
+                } else if let (
+                    Self::RotationMatrix { degrees: a },
+                    Self::RotationMatrix { degrees: b },
+                ) = (&**left, &**right)
+                {
+                    Self::RotationMatrix { degrees: a + b }
+                } else if left.inverse_of() == Some(&**right) {
+                    right.identity_like().unwrap_or(self)
+                } else if right.inverse_of() == Some(&**left) {
+                    left.identity_like().unwrap_or(self)
+                } else {
+                    self
+                }
+            }
+            Self::Exponent { base, power } => match &mut **base {
+                Self::Exponent {
+                    base: inner_base,
+                    power: inner_power,
+                } if (power.is_transpose_marker() && inner_power.is_transpose_marker())
+                    || (power.is_number(-1.) && inner_power.is_number(-1.)) =>
+                {
+                    Self::take(inner_base)
+                }
+                _ => self,
+            },
+            _ => self,
+        }
+    }
+
+    /// If this node is `A^{-1}`, then return `A`.
+    fn inverse_of(&self) -> Option<&Self> {
+        match self {
+            Self::Exponent { base, power } if power.is_number(-1.) => Some(base),
+            _ => None,
+        }
+    }
+
+    /// Return the identity matrix with the same dimension as this matrix, as long as it's
+    /// invertible.
+    ///
+    /// We can only check invertibility for matrix literals, so any other matrix is assumed to
+    /// be invertible, and its identity is `A^{0}`. If this node is definitely not a matrix, then
+    /// we return `None`.
+    fn identity_like(&self) -> Option<Self> {
+        match self {
+            Self::Anonymous2dMatrix(matrix) => {
+                (matrix.determinant() != 0.).then_some(Self::Anonymous2dMatrix(DMat2::IDENTITY))
+            }
+            Self::Anonymous3dMatrix(matrix) => {
+                (matrix.determinant() != 0.).then_some(Self::Anonymous3dMatrix(DMat3::IDENTITY))
+            }
+            node if node.is_definitely_matrix() => Some(Self::Exponent {
+                base: Box::new(node.clone()),
+                power: Box::new(Self::Number(0.)),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Is this node an identity matrix literal, or `rot(0)`?
+    fn is_identity(&self) -> bool {
+        match self {
+            Self::Anonymous2dMatrix(matrix) => *matrix == DMat2::IDENTITY,
+            Self::Anonymous3dMatrix(matrix) => *matrix == DMat3::IDENTITY,
+            Self::AnonymousDynamicMatrix(matrix) => *matrix == DMatN::identity(matrix.dimension()),
+            Self::RotationMatrix { degrees } => *degrees == 0.,
+            _ => false,
+        }
+    }
+
+    /// Is this node guaranteed to evaluate to a matrix (if it evaluates successfully at all),
+    /// regardless of the values of any named matrices?
+    ///
+    /// Like [`is_definitely_number`](Self::is_definitely_number), this is conservative.
+    fn is_definitely_matrix(&self) -> bool {
+        self.definite_kind().matrix
+    }
+
+    /// Work out whether this node is definitely a number and whether it's definitely a matrix,
+    /// for [`is_definitely_number`](Self::is_definitely_number) and
+    /// [`is_definitely_matrix`](Self::is_definitely_matrix).
+    ///
+    /// This folds over the whole tree rather than recursing, so it works on any depth of tree.
+    fn definite_kind(&self) -> DefiniteKind {
+        self.fold_iteratively(DefiniteKind::of)
+    }
+
+    /// Work out the [`DefiniteKind`] of every node in this tree, keyed by the address of the
+    /// node.
+    ///
+    /// This takes a single pass over the tree, rather than one pass for every node.
+    pub(super) fn definite_kinds(&self) -> HashMap<*const Self, DefiniteKind> {
+        let mut kinds = HashMap::new();
+        self.fold_iteratively(|node, children| {
+            let kind = DefiniteKind::of(node, children);
+            kinds.insert(node as *const Self, kind);
+            kind
+        });
+        kinds
+    }
+}
+
+/// Whether a node is definitely a number and whether it's definitely a matrix. See
+/// [`AstNode::definite_kind`].
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct DefiniteKind {
+    /// Is the node definitely a number?
+    pub(super) number: bool,
+
+    /// Is the node definitely a matrix?
+    pub(super) matrix: bool,
+}
+
+impl DefiniteKind {
+    /// The kind of a node which is definitely a number.
+    const NUMBER: Self = Self {
+        number: true,
+        matrix: false,
+    };
+
+    /// Work out the kind of this node, given the kinds of its [`children`](AstNode::children).
+    fn of(node: &AstNode, children: Vec<DefiniteKind>) -> Self {
+        let child = |index: usize| children[index];
+
+        match node {
+            AstNode::Number(_)
+            | AstNode::Imaginary(_)
+            | AstNode::Variable(_)
+            | AstNode::DotProduct { .. }
+            | AstNode::Index { .. }
+            | AstNode::Norm(_)
+            | AstNode::Cofactor { .. }
+            | AstNode::Rank(_)
+            | AstNode::HasProperty { .. }
+            | AstNode::MatrixNorm { .. } => Self::NUMBER,
+            AstNode::NamedMatrix(_)
+            | AstNode::RotationMatrix { .. }
+            | AstNode::Anonymous2dMatrix(_)
+            | AstNode::Anonymous3dMatrix(_)
+            | AstNode::AnonymousDynamicMatrix(_)
+            | AstNode::Augment { .. }
+            | AstNode::Block { .. }
+            | AstNode::Adjugate(_)
+            | AstNode::PseudoInverse(_)
+            | AstNode::Translation { .. }
+            | AstNode::Random { .. }
+            | AstNode::LineProjection(_)
+            | AstNode::PlaneProjection(_) => Self {
+                number: false,
+                matrix: !node.is_transpose_marker(),
+            },
+            AstNode::Negate(_) => child(0),
+            AstNode::Exponent { power, .. } => Self {
+                number: !power.is_transpose_marker() && child(0).number && child(1).number,
+                matrix: child(0).matrix,
+            },
+            AstNode::Multiply { .. } => {
+                let (left, right) = (child(0), child(1));
+                Self {
+                    number: left.number && right.number,
+                    matrix: (left.matrix && (right.matrix || right.number))
+                        || (left.number && right.matrix),
+                }
+            }
+            AstNode::Divide { .. } => {
+                let (left, right) = (child(0), child(1));
+                Self {
+                    number: left.number && right.number,
+                    matrix: left.matrix && right.number,
+                }
+            }
+            AstNode::Add { .. } => {
+                let (left, right) = (child(0), child(1));
+                Self {
+                    number: left.number && right.number,
+                    matrix: left.matrix && right.matrix,
+                }
+            }
+            _ => Self::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::{
+        expression::{
+            ast::{AstNode, NumberOrMatrix},
+            parse_expression_from_string,
+        },
+        map::prelude::*,
+        MatrixName, MatrixValue,
+    };
+    use glam::DMat2;
+
+    /// Parse and simplify this expression and return the simplified AST.
+    fn simplify(expression: &str) -> AstNode {
+        parse_expression_from_string(expression).unwrap().simplify()
+    }
+
+    #[test]
+    fn simplify_constant_folding() {
+        assert_eq!(simplify("2*3+1"), AstNode::Number(7.));
+        assert_eq!(simplify("8/4/2"), AstNode::Number(1.));
+        assert_eq!(
+            simplify("1 - 3"),
+            AstNode::Negate(Box::new(AstNode::Number(2.)))
+        );
+        assert_eq!(simplify("2 ^ (3 + 1)"), AstNode::Number(16.));
+        assert_eq!(simplify("dot([1; 2], [3; 4]) - 1"), AstNode::Number(10.));
+        assert_eq!(
+            simplify("[1 2; 3 4][2, 1] * rank([1 2; 2 4])"),
+            AstNode::Number(3.)
+        );
+
+        assert_eq!(
+            simplify("A * (2 * 3)"),
+            AstNode::Multiply {
+                left: Box::new(AstNode::NamedMatrix(MatrixName::new("A"))),
+                right: Box::new(AstNode::Number(6.))
+            }
+        );
+
+        // Matrices and vectors aren't folded
+        assert_eq!(
+            simplify("rot(90) * 2"),
+            parse_expression_from_string("rot(90) * 2").unwrap()
+        );
+        assert_eq!(
+            simplify("[1 2; 3 4] ^ (1 + 1)"),
+            AstNode::Exponent {
+                base: Box::new(AstNode::Anonymous2dMatrix(DMat2::from_cols_array(&[
+                    1., 3., 2., 4.
+                ]))),
+                power: Box::new(AstNode::Number(2.))
+            }
+        );
+
+        // Neither are things that fail to evaluate or aren't finite
+        assert_eq!(
+            simplify("1 / 0"),
+            parse_expression_from_string("1 / 0").unwrap()
+        );
+        assert_eq!(
+            simplify("2 ^ T"),
+            parse_expression_from_string("2 ^ T").unwrap()
+        );
+    }
+
+    #[test]
+    fn simplify_identities() {
+        assert_eq!(simplify("--A"), AstNode::NamedMatrix(MatrixName::new("A")));
+        assert_eq!(
+            simplify("---A"),
+            AstNode::Negate(Box::new(AstNode::NamedMatrix(MatrixName::new("A"))))
+        );
+        assert_eq!(
+            simplify("1 * A * 1"),
+            AstNode::NamedMatrix(MatrixName::new("A"))
+        );
+        assert_eq!(
+            simplify("A / (3 - 2)"),
+            AstNode::NamedMatrix(MatrixName::new("A"))
+        );
+        assert_eq!(simplify("A ^ T * 1"), simplify("A ^ T"));
+        assert_eq!(
+            simplify("norm(A) + 0"),
+            AstNode::Norm(Box::new(AstNode::NamedMatrix(MatrixName::new("A"))))
+        );
+        assert_eq!(simplify("0 + A[1, 2]"), simplify("A[1, 2]"));
+
+        // Adding 0 to a matrix is an error, so we can't remove it
+        assert_eq!(
+            simplify("A + 0"),
+            parse_expression_from_string("A + 0").unwrap()
+        );
+        assert_eq!(
+            simplify("A + 2 - 2"),
+            parse_expression_from_string("A + 2 - 2").unwrap()
+        );
+    }
+
+    #[test]
+    fn simplify_symbolic_identities() {
+        let simplify = |expression| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .simplify_symbolic()
+        };
+        let parse = |expression| parse_expression_from_string(expression).unwrap();
+
+        assert_eq!(simplify("A * [1 0; 0 1]"), parse("A"));
+        assert_eq!(simplify("[1 0 0; 0 1 0; 0 0 1] * (A + B)"), parse("A + B"));
+        assert_eq!(simplify("rot(0) * A ^ T"), parse("A ^ T"));
+        assert_eq!(simplify("(A ^ T) ^ T"), parse("A"));
+        assert_eq!(simplify("((A * B) ^ {-1}) ^ {-1}"), parse("A * B"));
+        assert_eq!(simplify("rot(30) * rot(60) * rot(270)"), parse("rot(360)"));
+        assert_eq!(simplify("rot(30) * rot(0) * A"), parse("rot(30) * A"));
+        assert_eq!(simplify("M * M ^ {-1}"), parse("M ^ {0}"));
+        assert_eq!(simplify("(A + B) ^ {-1} * (A + B)"), parse("(A + B) ^ {0}"));
+        assert_eq!(
+            simplify("[1 2; 3 4] ^ {-1} * [1 2; 3 4]"),
+            parse("[1 0; 0 1]")
+        );
+
+        // Numbers times the identity are scaled identities, not numbers
+        assert_eq!(simplify("2 * [1 0; 0 1]"), parse("2 * [1 0; 0 1]"));
+        assert_eq!(
+            simplify("norm(A) * [1 0; 0 1]"),
+            parse("norm(A) * [1 0; 0 1]")
+        );
+
+        // Singular literals can't be cancelled with their inverse
+        assert_eq!(
+            simplify("[1 2; 2 4] * [1 2; 2 4] ^ {-1}"),
+            parse("[1 2; 2 4] * [1 2; 2 4] ^ {-1}")
+        );
+
+        // A transpose and an inverse don't cancel
+        assert_eq!(simplify("(A ^ T) ^ {-1}"), parse("(A ^ T) ^ {-1}"));
+    }
+
+    #[test]
+    fn simplify_symbolic_preserves_evaluation() {
+        let mut map = MatrixMap2::new();
+        map.set(
+            MatrixName::new("A"),
+            DMat2::from_cols_array(&[1., 2., 3., 4.]),
+        )
+        .unwrap();
+        map.set(
+            MatrixName::new("B"),
+            DMat2::from_cols_array(&[0., -1., 2., 5.]),
+        )
+        .unwrap();
+
+        for expression in [
+            "A * [1 0; 0 1] * (B ^ T) ^ T",
+            "rot(30) * rot(45) * A",
+            "A * A ^ {-1} + B",
+            "(B ^ {-1}) ^ {-1} * B ^ {-1} * B",
+        ] {
+            let ast = parse_expression_from_string(expression).unwrap();
+            let simplified = ast.clone().simplify_symbolic().evaluate(&map).unwrap();
+            let original = ast.evaluate(&map).unwrap();
+            match (simplified, original) {
+                (
+                    NumberOrMatrix::Matrix(MatrixValue::TwoD(simplified)),
+                    NumberOrMatrix::Matrix(MatrixValue::TwoD(original)),
+                ) => assert!(
+                    simplified.abs_diff_eq(original, 0.000001),
+                    "{expression}: {simplified} != {original}"
+                ),
+                other => panic!("{expression} should evaluate to 2D matrices, not {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn simplify_preserves_evaluation() {
+        let mut map = MatrixMap2::new();
+        map.set(
+            MatrixName::new("A"),
+            DMat2::from_cols_array(&[1., 2., 3., 4.]),
+        )
+        .unwrap();
+        map.set(
+            MatrixName::new("B"),
+            DMat2::from_cols_array(&[0., -1., 2., 5.]),
+        )
+        .unwrap();
+
+        for expression in [
+            "2 * A + 3 * B ^ (1 + 1)",
+            "--A * 1 - (2 - 2) * B",
+            "A / (4 / 2) * rot(90)",
+            "norm(A) * (1 + 0) + 0 + A[1, 2]",
+            "(A ^ T) ^ (3 - 1) + dot([1; 2], [3; 4]) * B",
+            "A + 0",
+            "2 ^ T",
+        ] {
+            let ast = parse_expression_from_string(expression).unwrap();
+            assert_eq!(
+                ast.clone().simplify().evaluate(&map),
+                ast.evaluate(&map),
+                "{expression}"
+            );
+        }
+    }
+}
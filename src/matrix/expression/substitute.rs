@@ -0,0 +1,110 @@
+//! This module handles substituting sub-expressions for named matrices in an AST.
+
+use super::ast::AstNode;
+use crate::matrix::MatrixName;
+
+impl AstNode {
+    /// Replace every reference to the named matrix with the replacement expression.
+    ///
+    /// The `T` in a transposition like `A^T` is not a reference to a matrix called `T`, so it is
+    /// never replaced, even when `name` is `T`. The replacement is inserted as-is, so if it
+    /// references `name` itself, then those references are left alone rather than being
+    /// substituted again.
+    ///
+    /// ```
+    /// # use trinity::matrix::{MatrixName, expression::parse_expression_from_string};
+    /// let expression = parse_expression_from_string("2 * C ^ T").unwrap();
+    /// let definition = parse_expression_from_string("A * B").unwrap();
+    /// assert_eq!(
+    ///     expression
+    ///         .substitute(&MatrixName::new("C"), &definition)
+    ///         .to_expression_string(),
+    ///     "2 * ((A * B) ^ {T})"
+    /// );
+    /// ```
+    pub fn substitute(&self, name: &MatrixName, replacement: &AstNode) -> AstNode {
+        // Since the replacement goes in after the children have been visited, it's never
+        // searched for references itself
+        self.clone().map_bottom_up(|node| match &node {
+            Self::NamedMatrix(matrix) if matrix == name => replacement.clone(),
+            _ => node,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::{
+        expression::{ast::AstNode, parse_expression_from_string},
+        map::prelude::*,
+        MatrixName,
+    };
+    use glam::DMat2;
+
+    /// Parse this expression, panicking if it's invalid.
+    fn parse(expression: &str) -> AstNode {
+        parse_expression_from_string(expression).unwrap()
+    }
+
+    #[test]
+    fn substitute_success() {
+        let c = MatrixName::new("C");
+
+        assert_eq!(parse("C").substitute(&c, &parse("A * B")), parse("A * B"));
+        assert_eq!(
+            parse("2 * C + norm(C)[1, 1] - dot(C, [1; 2])").substitute(&c, &parse("A + B")),
+            parse("2 * (A + B) + norm(A + B)[1, 1] - dot(A + B, [1; 2])")
+        );
+        assert_eq!(
+            parse("aug(C, D) + block(C, U; V, 2)").substitute(&c, &parse("[1; 2]")),
+            parse("aug([1; 2], D) + block([1; 2], U; V, 2)")
+        );
+        assert_eq!(parse("A * B").substitute(&c, &parse("3")), parse("A * B"));
+
+        // The replacement isn't substituted into itself
+        assert_eq!(
+            parse("C * A").substitute(&c, &parse("C ^ 2")),
+            parse("C ^ 2 * A")
+        );
+    }
+
+    #[test]
+    fn substitute_transpose() {
+        let t = MatrixName::new("T");
+
+        assert_eq!(
+            parse("T ^ T + A ^ T").substitute(&t, &parse("rot(90)")),
+            parse("rot(90) ^ T + A ^ T")
+        );
+        assert_eq!(
+            parse("A ^ T").substitute(&MatrixName::new("A"), &parse("T")),
+            parse("T ^ T")
+        );
+        assert_eq!(
+            parse("A ^ {T}").substitute(&MatrixName::new("A"), &parse("B * C")),
+            parse("(B * C) ^ T")
+        );
+    }
+
+    #[test]
+    fn substitute_preserves_evaluation() {
+        let a = DMat2::from_cols_array(&[1., 2., 3., 4.]);
+        let b = DMat2::from_cols_array(&[0., -1., 2., 5.]);
+
+        let mut map = MatrixMap2::new();
+        map.set(MatrixName::new("A"), a).unwrap();
+        map.set(MatrixName::new("B"), b).unwrap();
+        map.set(MatrixName::new("C"), a * b).unwrap();
+
+        let definition = parse("A * B");
+        for expression in ["C", "2 * C ^ T - A", "C ^ {-1} * B + rot(45) * C"] {
+            assert_eq!(
+                parse(expression)
+                    .substitute(&MatrixName::new("C"), &definition)
+                    .evaluate(&map),
+                parse(expression).evaluate(&map),
+                "{expression}"
+            );
+        }
+    }
+}
@@ -1,15 +1,27 @@
 //! This module handles tokenising a matrix expression string into a list of [`Token`]s.
 
-use crate::matrix::{MatrixName, LEADING_MATRIX_NAME_REGEX};
+use crate::{
+    math::{MatrixNorm, MatrixProperty, RandomDistribution},
+    matrix::{MatrixName, ScalarName, LEADING_MATRIX_NAME_REGEX, LEADING_SCALAR_NAME_REGEX},
+};
 use nom::{
-    branch::alt, bytes::complete::tag, character::complete::multispace1, multi::many1,
-    number::complete::float, IResult, Parser,
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{
+        char, digit0, digit1, multispace0, multispace1, not_line_ending, one_of, satisfy,
+    },
+    combinator::{consumed, map_res, not, opt, recognize, verify},
+    multi::many1,
+    sequence::{delimited, terminated, tuple},
+    IResult, Offset, Parser,
 };
 use nom_regex::str::re_find;
+use std::{fmt, ops::Range};
 use thiserror::Error;
 
 /// A single token in the token list that results from tokenisation.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token {
     /// A named matrix. See [`MatrixName`].
     NamedMatrix(MatrixName),
@@ -17,9 +29,82 @@ pub enum Token {
     /// A numeric literal.
     Number(f64),
 
+    /// A scalar variable. See [`ScalarName`].
+    Variable(ScalarName),
+
+    /// The imaginary unit `i`.
+    ImaginaryUnit,
+
     /// The rotation command `rot`.
     Rot,
 
+    /// The dot product function `dot`.
+    Dot,
+
+    /// The cross product function `cross`.
+    Cross,
+
+    /// The row extraction function `row`.
+    Row,
+
+    /// The column extraction function `col`.
+    Col,
+
+    /// The augmented matrix function `aug`.
+    Aug,
+
+    /// The block matrix function `block`.
+    Block,
+
+    /// The linear system solver function `solve`.
+    Solve,
+
+    /// The least-squares solver function `lstsq`.
+    Lstsq,
+
+    /// The 2D affine translation function `translate`.
+    Translate,
+
+    /// The norm function `norm`.
+    Norm,
+
+    /// The adjugate function `adj`.
+    Adj,
+
+    /// The cofactor function `cofactor`.
+    Cofactor,
+
+    /// The rank function `rank`.
+    Rank,
+
+    /// The random matrix function `rand`.
+    Rand,
+
+    /// The exponential function `exp`.
+    Exp,
+
+    /// The matrix logarithm function `logm`, which can also be written `log`.
+    Log,
+
+    /// The pseudoinverse function `pinv`.
+    Pinv,
+
+    /// The 2D line projection function `proj_line`.
+    ProjLine,
+
+    /// The 3D plane projection function `proj_plane`.
+    ProjPlane,
+
+    /// A matrix property predicate function, like `is_orthogonal`.
+    Property(MatrixProperty),
+
+    /// The name of a matrix norm as a quoted string, like `"fro"`. See [`MatrixNorm`].
+    NormName(MatrixNorm),
+
+    /// The name of a distribution of random matrices as a quoted string, like `"orthogonal"`. See
+    /// [`RandomDistribution`].
+    DistributionName(RandomDistribution),
+
     /// The `+` symbol.
     Plus,
 
@@ -38,6 +123,20 @@ pub enum Token {
     /// The `;` symbol.
     Semicolon,
 
+    /// The `,` symbol.
+    Comma,
+
+    /// An index into a matrix, like `[1, 2]`. The indices are 1-based.
+    ///
+    /// This is tokenised as a single token so that it can't be confused with the square brackets
+    /// of an anonymous matrix or vector.
+    Index {
+        /// The row of the entry.
+        row: usize,
+        /// The column of the entry.
+        column: usize,
+    },
+
     /// The `(` symbol.
     OpenParen,
 
@@ -57,27 +156,87 @@ pub enum Token {
     CloseBrace,
 }
 
-/// The default error used by [`nom::IResult`].
-type NomError<'i> = ::nom::Err<::nom::error::Error<&'i str>>;
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NamedMatrix(name) => write!(f, "{name}"),
+            Self::Number(number) => write!(f, "{number}"),
+            Self::Variable(name) => write!(f, "{name}"),
+            Self::ImaginaryUnit => write!(f, "i"),
+            Self::Rot => write!(f, "rot"),
+            Self::Dot => write!(f, "dot"),
+            Self::Cross => write!(f, "cross"),
+            Self::Row => write!(f, "row"),
+            Self::Col => write!(f, "col"),
+            Self::Aug => write!(f, "aug"),
+            Self::Block => write!(f, "block"),
+            Self::Solve => write!(f, "solve"),
+            Self::Lstsq => write!(f, "lstsq"),
+            Self::Translate => write!(f, "translate"),
+            Self::Norm => write!(f, "norm"),
+            Self::Adj => write!(f, "adj"),
+            Self::Cofactor => write!(f, "cofactor"),
+            Self::Rank => write!(f, "rank"),
+            Self::Rand => write!(f, "rand"),
+            Self::Exp => write!(f, "exp"),
+            Self::Log => write!(f, "logm"),
+            Self::Pinv => write!(f, "pinv"),
+            Self::ProjLine => write!(f, "proj_line"),
+            Self::ProjPlane => write!(f, "proj_plane"),
+            Self::Property(property) => write!(f, "{property}"),
+            Self::NormName(norm) => write!(f, "{norm}"),
+            Self::DistributionName(distribution) => write!(f, "{distribution}"),
+            Self::Plus => write!(f, "+"),
+            Self::Minus => write!(f, "-"),
+            Self::Star => write!(f, "*"),
+            Self::Slash => write!(f, "/"),
+            Self::Caret => write!(f, "^"),
+            Self::Semicolon => write!(f, ";"),
+            Self::Comma => write!(f, ","),
+            Self::Index { row, column } => write!(f, "[{row}, {column}]"),
+            Self::OpenParen => write!(f, "("),
+            Self::CloseParen => write!(f, ")"),
+            Self::OpenSquareBracket => write!(f, "["),
+            Self::CloseSquareBracket => write!(f, "]"),
+            Self::OpenBrace => write!(f, "{{"),
+            Self::CloseBrace => write!(f, "}}"),
+        }
+    }
+}
 
 /// An error that occurred during tokenisation.
 #[derive(Debug, Error, PartialEq)]
 pub enum TokeniseError<'i> {
-    /// An error created by [`nom`].
-    #[error("Internal nom error: {nom_error:?}")]
-    NomError {
-        /// The internal error from [`nom`].
-        nom_error: NomError<'i>,
-    },
+    /// The expression was completely empty.
+    #[error("The expression is empty")]
+    Empty,
+
+    /// The tokeniser found a character which can't start any token.
+    #[error("Unexpected character {character:?} at column {column}")]
+    UnexpectedCharacter {
+        /// The offending character.
+        character: char,
 
-    /// Some of the input was left un-tokenised.
-    #[error("Unconsumed input after tokenising expression: '{0}'")]
-    UnconsumedInput(&'i str),
+        /// The 1-based column of the character in the expression, counted in characters.
+        column: usize,
+
+        /// The rest of the expression, starting at the offending character.
+        rest: &'i str,
+    },
 }
 
-impl<'i> From<NomError<'i>> for TokeniseError<'i> {
-    fn from(nom_error: NomError<'i>) -> Self {
-        TokeniseError::NomError { nom_error }
+impl<'i> TokeniseError<'i> {
+    /// Create an error for the tokeniser stopping at `rest`, which must be a suffix of
+    /// `expression`.
+    fn stopped_at(expression: &'i str, rest: &'i str) -> Self {
+        match rest.chars().next() {
+            Some(character) => Self::UnexpectedCharacter {
+                character,
+                column: expression[..expression.offset(rest)].chars().count() + 1,
+                rest,
+            },
+            None => Self::Empty,
+        }
     }
 }
 
@@ -86,6 +245,13 @@ impl<'i> From<NomError<'i>> for TokeniseError<'i> {
 /// Note that the tokeniser cannot tokenise negative numbers. It will instead tokenise the minus
 /// sign and then tokenise the positive number.
 ///
+/// Comments starting with `#` or `//` run until the end of the line and are stripped, just like
+/// whitespace.
+///
+/// Some unicode symbols are accepted as aliases for ASCII operators, so that expressions can be
+/// pasted from textbooks. `×` and `·` mean `*`, `÷` means `/`, `−` means `-`, `⁻¹` means `^{-1}`,
+/// and `ᵀ` means `^T`.
+///
 /// ```
 /// # use trinity::matrix::expression::tokenise::{Token, tokenise_expression};
 /// assert_eq!(
@@ -110,19 +276,73 @@ impl<'i> From<NomError<'i>> for TokeniseError<'i> {
 /// ```
 #[allow(clippy::needless_lifetimes)]
 pub fn tokenise_expression<'i>(expression: &'i str) -> Result<Vec<Token>, TokeniseError<'i>> {
-    let (input, opt_tokens) = many1(alt((
-        tokenise_named_matrix.map(Some),
-        tokenise_rot.map(Some),
-        tokenise_punctuation.map(Some),
-        tokenise_number.map(Some),
-        multispace1.map(|_| None),
-    )))(expression)?;
+    Ok(tokenise_expression_with_spans(expression)?
+        .into_iter()
+        .map(|(token, _span)| token)
+        .collect())
+}
+
+/// Tokenise the whole expression like [`tokenise_expression`], but also return the span of each
+/// token as a range of byte offsets into the expression.
+///
+/// Tokens that come from the same piece of the expression (like the unicode alias `⁻¹`) all have
+/// the same span.
+///
+/// ```
+/// # use trinity::matrix::{MatrixName, expression::tokenise::{Token, tokenise_expression_with_spans}};
+/// assert_eq!(
+///     tokenise_expression_with_spans("2 * A"),
+///     Ok(vec![
+///         (Token::Number(2.0), 0..1),
+///         (Token::Star, 2..3),
+///         (Token::NamedMatrix(MatrixName::new("A")), 4..5)
+///     ])
+/// );
+/// ```
+#[allow(clippy::needless_lifetimes)]
+pub fn tokenise_expression_with_spans<'i>(
+    expression: &'i str,
+) -> Result<Vec<(Token, Range<usize>)>, TokeniseError<'i>> {
+    let (input, token_groups) = many1(consumed(alt((
+        tokenise_comment.map(|_| vec![]),
+        tokenise_named_matrix.map(|token| vec![token]),
+        tokenise_builtin_function.map(|token| vec![token]),
+        tokenise_index.map(|token| vec![token]),
+        tokenise_norm_name.map(|token| vec![token]),
+        tokenise_distribution_name.map(|token| vec![token]),
+        tokenise_punctuation.map(|token| vec![token]),
+        tokenise_unicode_alias,
+        tokenise_number.map(|token| vec![token]),
+        tokenise_variable.map(|token| vec![token]),
+        tokenise_imaginary_unit.map(|token| vec![token]),
+        multispace1.map(|_| vec![]),
+    ))))(expression)
+    .map_err(|error| match error {
+        ::nom::Err::Error(error) | ::nom::Err::Failure(error) => {
+            TokeniseError::stopped_at(expression, error.input)
+        }
+        ::nom::Err::Incomplete(_) => unreachable!("None of our tokenisers are streaming parsers"),
+    })?;
 
     if !input.is_empty() {
-        return Err(TokeniseError::UnconsumedInput(input));
+        return Err(TokeniseError::stopped_at(expression, input));
     }
 
-    Ok(opt_tokens.into_iter().flatten().collect())
+    Ok(token_groups
+        .into_iter()
+        .flat_map(|(source, tokens)| {
+            let start = expression.offset(source);
+            let span = start..start + source.len();
+            tokens.into_iter().map(move |token| (token, span.clone()))
+        })
+        .collect())
+}
+
+/// Tokenise a comment like `# comment` or `// comment`, which runs until the end of the line.
+fn tokenise_comment(input: &str) -> IResult<&str, &str> {
+    tuple((alt((tag("#"), tag("//"))), not_line_ending))
+        .map(|(_, comment)| comment)
+        .parse(input)
 }
 
 /// Tokenise a single named matrix from the expression.
@@ -132,14 +352,152 @@ fn tokenise_named_matrix(input: &str) -> IResult<&str, Token> {
         .parse(input)
 }
 
-/// Tokenise a single number from the expression.
+/// Tokenise a single number from the expression, like `2`, `1.5`, `.5`, or `1e-3`.
+///
+/// Only digits make a number, so `nan` and `inf` are variable names, and an exponent is only
+/// taken if a digit follows it, so that `2e` and `2exp(A)` multiply by `e` and `exp(A)`.
 fn tokenise_number(input: &str) -> IResult<&str, Token> {
-    float.map(|num| Token::Number(num as f64)).parse(input)
+    let mantissa = alt((
+        recognize(tuple((digit1, opt(tuple((char('.'), digit0)))))),
+        recognize(tuple((char('.'), digit1))),
+    ));
+    let exponent = tuple((one_of("eE"), opt(one_of("+-")), digit1));
+
+    map_res(recognize(tuple((mantissa, opt(exponent)))), str::parse)
+        .map(Token::Number)
+        .parse(input)
 }
 
-/// Tokenise a rotation command from the expression.
-fn tokenise_rot(input: &str) -> IResult<&str, Token> {
-    tag("rot").map(|_| Token::Rot).parse(input)
+/// Tokenise a scalar variable from the expression.
+///
+/// This is tried after builtin functions, so that `rot` keeps its meaning, and it never matches a
+/// lone `i`, which is the imaginary unit.
+fn tokenise_variable(input: &str) -> IResult<&str, Token> {
+    verify(re_find(LEADING_SCALAR_NAME_REGEX.clone()), |name: &str| {
+        name != "i"
+    })
+    .map(|name| Token::Variable(ScalarName { name: name.into() }))
+    .parse(input)
+}
+
+/// Tokenise the imaginary unit `i` from the expression.
+///
+/// This is tried after variables, so that names like `index` aren't split up.
+fn tokenise_imaginary_unit(input: &str) -> IResult<&str, Token> {
+    tag("i").map(|_| Token::ImaginaryUnit).parse(input)
+}
+
+/// Tokenise the name of a builtin function (like `rot`) from the expression.
+///
+/// The name can't be followed by anything that could continue a scalar name, so that variables
+/// like `rows` and `logx` aren't split into a function and another variable.
+fn tokenise_builtin_function(input: &str) -> IResult<&str, Token> {
+    let builtin = alt((
+        tag("rot").map(|_| Token::Rot),
+        tag("dot").map(|_| Token::Dot),
+        tag("cross").map(|_| Token::Cross),
+        tag("row").map(|_| Token::Row),
+        tag("col").map(|_| Token::Col),
+        tag("aug").map(|_| Token::Aug),
+        tag("block").map(|_| Token::Block),
+        tag("solve").map(|_| Token::Solve),
+        tag("lstsq").map(|_| Token::Lstsq),
+        tag("translate").map(|_| Token::Translate),
+        tag("norm").map(|_| Token::Norm),
+        tag("adj").map(|_| Token::Adj),
+        tag("cofactor").map(|_| Token::Cofactor),
+        tag("rank").map(|_| Token::Rank),
+        tag("rand").map(|_| Token::Rand),
+        tag("exp").map(|_| Token::Exp),
+        alt((tag("logm"), tag("log"))).map(|_| Token::Log),
+        tag("pinv").map(|_| Token::Pinv),
+        tag("proj_line").map(|_| Token::ProjLine),
+        tag("proj_plane").map(|_| Token::ProjPlane),
+        alt((
+            tag("is_orthogonal").map(|_| Token::Property(MatrixProperty::Orthogonal)),
+            tag("is_symmetric").map(|_| Token::Property(MatrixProperty::Symmetric)),
+            tag("is_singular").map(|_| Token::Property(MatrixProperty::Singular)),
+            tag("is_rotation").map(|_| Token::Property(MatrixProperty::Rotation)),
+        )),
+    ));
+
+    terminated(
+        builtin,
+        not(satisfy(|c| c.is_ascii_lowercase() || c == '_')),
+    )(input)
+}
+
+/// Tokenise the name of a matrix norm as a quoted string, like `"fro"`.
+fn tokenise_norm_name(input: &str) -> IResult<&str, Token> {
+    delimited(
+        char('"'),
+        alt((
+            tag("fro").map(|_| MatrixNorm::Frobenius),
+            tag("spectral").map(|_| MatrixNorm::Spectral),
+            tag("max").map(|_| MatrixNorm::MaxEntry),
+        )),
+        char('"'),
+    )
+    .map(Token::NormName)
+    .parse(input)
+}
+
+/// Tokenise the name of a distribution of random matrices as a quoted string, like
+/// `"orthogonal"`.
+fn tokenise_distribution_name(input: &str) -> IResult<&str, Token> {
+    delimited(
+        char('"'),
+        alt((
+            tag("uniform").map(|_| RandomDistribution::Uniform),
+            tag("integer").map(|_| RandomDistribution::Integer),
+            tag("orthogonal").map(|_| RandomDistribution::Orthogonal),
+        )),
+        char('"'),
+    )
+    .map(Token::DistributionName)
+    .parse(input)
+}
+
+/// Tokenise a matrix index like `[1, 2]` from the expression.
+fn tokenise_index(input: &str) -> IResult<&str, Token> {
+    /// Parse a single index from the input.
+    fn index(input: &str) -> IResult<&str, usize> {
+        map_res(digit1, str::parse::<usize>)(input)
+    }
+
+    tuple((
+        tag("["),
+        multispace0,
+        index,
+        multispace0,
+        tag(","),
+        multispace0,
+        index,
+        multispace0,
+        tag("]"),
+    ))
+    .map(|(_, _, row, _, _, _, column, _, _)| Token::Index { row, column })
+    .parse(input)
+}
+
+/// Tokenise a unicode alias for one or more ASCII tokens. See [`tokenise_expression`].
+fn tokenise_unicode_alias(input: &str) -> IResult<&str, Vec<Token>> {
+    alt((
+        tag("×").map(|_| vec![Token::Star]),
+        tag("·").map(|_| vec![Token::Star]),
+        tag("÷").map(|_| vec![Token::Slash]),
+        tag("−").map(|_| vec![Token::Minus]),
+        tag("⁻¹").map(|_| {
+            vec![
+                Token::Caret,
+                Token::OpenBrace,
+                Token::Minus,
+                Token::Number(1.),
+                Token::CloseBrace,
+            ]
+        }),
+        tag("ᵀ").map(|_| vec![Token::Caret, Token::NamedMatrix(MatrixName::new("T"))]),
+    ))(input)
 }
 
 /// Tokenise a piece of punctuation from the expression.
@@ -151,6 +509,7 @@ fn tokenise_punctuation(input: &str) -> IResult<&str, Token> {
         tag("/").map(|_| Token::Slash),
         tag("^").map(|_| Token::Caret),
         tag(";").map(|_| Token::Semicolon),
+        tag(",").map(|_| Token::Comma),
         tag("(").map(|_| Token::OpenParen),
         tag(")").map(|_| Token::CloseParen),
         tag("[").map(|_| Token::OpenSquareBracket),
@@ -176,6 +535,10 @@ mod tests {
             "T",
             "Some_really_long_matrix_name_but_its_okay_because_it_fits_the_rules",
             "Abc",
+            "A1",
+            "B_12",
+            "Ω",
+            "Φx",
         ];
         for name in valid_names {
             assert_eq!(
@@ -218,12 +581,22 @@ mod tests {
             ))
         );
 
+        assert_eq!(
+            tokenise_named_matrix("A2B"),
+            Ok(("B", Token::NamedMatrix(MatrixName::new("A2"))))
+        );
+
+        assert_eq!(
+            tokenise_named_matrix("ΣΔ"),
+            Ok(("Δ", Token::NamedMatrix(MatrixName::new("Σ"))))
+        );
+
         assert_eq!(
             tokenise_named_matrix("It's"),
             Ok(("'s", Token::NamedMatrix(MatrixName::new("It"))))
         );
 
-        let invalid_names = ["", "m", " M", "x", "my_matrix", "::"];
+        let invalid_names = ["", "m", " M", "x", "my_matrix", "::", "1A", "σ"];
         for name in invalid_names {
             assert!(
                 tokenise_named_matrix(name).is_err(),
@@ -322,19 +695,339 @@ mod tests {
         );
 
         assert_eq!(
-            tokenise_expression("ABC + A2B"),
+            tokenise_expression("ABC + A2B - A 2B + 2ΣA"),
             Ok(vec![
                 T::NamedMatrix(MatrixName::new("A")),
                 T::NamedMatrix(MatrixName::new("B")),
                 T::NamedMatrix(MatrixName::new("C")),
                 T::Plus,
+                T::NamedMatrix(MatrixName::new("A2")),
+                T::NamedMatrix(MatrixName::new("B")),
+                T::Minus,
                 T::NamedMatrix(MatrixName::new("A")),
                 T::Number(2.),
                 T::NamedMatrix(MatrixName::new("B")),
+                T::Plus,
+                T::Number(2.),
+                T::NamedMatrix(MatrixName::new("Σ")),
+                T::NamedMatrix(MatrixName::new("A")),
+            ])
+        );
+    }
+
+    #[test]
+    fn tokenise_expression_builtin_functions() {
+        use super::Token as T;
+
+        assert_eq!(
+            tokenise_expression(
+                "dot([1; 2], V) * cross(A,[3;4;5]) row col aug block solve lstsq translate norm adj cofactor rank rand exp logm log pinv proj_line proj_plane is_orthogonal is_symmetric is_singular is_rotation \"fro\" \"spectral\" \"max\" \"uniform\" \"integer\" \"orthogonal\""
+            ),
+            Ok(vec![
+                T::Dot,
+                T::OpenParen,
+                T::OpenSquareBracket,
+                T::Number(1.),
+                T::Semicolon,
+                T::Number(2.),
+                T::CloseSquareBracket,
+                T::Comma,
+                T::NamedMatrix(MatrixName::new("V")),
+                T::CloseParen,
+                T::Star,
+                T::Cross,
+                T::OpenParen,
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Comma,
+                T::OpenSquareBracket,
+                T::Number(3.),
+                T::Semicolon,
+                T::Number(4.),
+                T::Semicolon,
+                T::Number(5.),
+                T::CloseSquareBracket,
+                T::CloseParen,
+                T::Row,
+                T::Col,
+                T::Aug,
+                T::Block,
+                T::Solve,
+                T::Lstsq,
+                T::Translate,
+                T::Norm,
+                T::Adj,
+                T::Cofactor,
+                T::Rank,
+                T::Rand,
+                T::Exp,
+                T::Log,
+                T::Log,
+                T::Pinv,
+                T::ProjLine,
+                T::ProjPlane,
+                T::Property(MatrixProperty::Orthogonal),
+                T::Property(MatrixProperty::Symmetric),
+                T::Property(MatrixProperty::Singular),
+                T::Property(MatrixProperty::Rotation),
+                T::NormName(MatrixNorm::Frobenius),
+                T::NormName(MatrixNorm::Spectral),
+                T::NormName(MatrixNorm::MaxEntry),
+                T::DistributionName(RandomDistribution::Uniform),
+                T::DistributionName(RandomDistribution::Integer),
+                T::DistributionName(RandomDistribution::Orthogonal),
             ])
         );
     }
 
+    #[test]
+    fn tokenise_expression_imaginary() {
+        use super::Token as T;
+
+        assert_eq!(
+            tokenise_expression("3 + 2i - i*A"),
+            Ok(vec![
+                T::Number(3.),
+                T::Plus,
+                T::Number(2.),
+                T::ImaginaryUnit,
+                T::Minus,
+                T::ImaginaryUnit,
+                T::Star,
+                T::NamedMatrix(MatrixName::new("A")),
+            ])
+        );
+    }
+
+    #[test]
+    fn tokenise_expression_numbers() {
+        use super::Token as T;
+
+        assert_eq!(
+            tokenise_expression("2 1.5 .5 3. 1e3 2.5E-2 1e+2"),
+            Ok(vec![
+                T::Number(2.),
+                T::Number(1.5),
+                T::Number(0.5),
+                T::Number(3.),
+                T::Number(1000.),
+                T::Number(0.025),
+                T::Number(100.),
+            ])
+        );
+
+        // An exponent needs digits, so otherwise the letter starts a variable, function, or matrix
+        assert_eq!(
+            tokenise_expression("2e"),
+            Ok(vec![T::Number(2.), T::Variable(ScalarName::new("e"))])
+        );
+        assert_eq!(
+            tokenise_expression("2ex - 3E"),
+            Ok(vec![
+                T::Number(2.),
+                T::Variable(ScalarName::new("ex")),
+                T::Minus,
+                T::Number(3.),
+                T::NamedMatrix(MatrixName::new("E")),
+            ])
+        );
+        assert_eq!(
+            tokenise_expression("2exp(M)"),
+            Ok(vec![
+                T::Number(2.),
+                T::Exp,
+                T::OpenParen,
+                T::NamedMatrix(MatrixName::new("M")),
+                T::CloseParen,
+            ])
+        );
+
+        // Names that start like special floats are just variables
+        for name in ["nan", "nancy", "inf", "inform", "infinity"] {
+            assert_eq!(
+                tokenise_expression(name),
+                Ok(vec![T::Variable(ScalarName::new(name))]),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn tokenise_expression_variables() {
+        use super::Token as T;
+
+        assert_eq!(
+            tokenise_expression("2x + angle_one * A - i index"),
+            Ok(vec![
+                T::Number(2.),
+                T::Variable(ScalarName::new("x")),
+                T::Plus,
+                T::Variable(ScalarName::new("angle_one")),
+                T::Star,
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Minus,
+                T::ImaginaryUnit,
+                T::Variable(ScalarName::new("index")),
+            ])
+        );
+        assert_eq!(
+            tokenise_expression("rows * exponent + logx - rot2"),
+            Ok(vec![
+                T::Variable(ScalarName::new("rows")),
+                T::Star,
+                T::Variable(ScalarName::new("exponent")),
+                T::Plus,
+                T::Variable(ScalarName::new("logx")),
+                T::Minus,
+                T::Rot,
+                T::Number(2.),
+            ])
+        );
+        assert_eq!(
+            tokenise_expression("rot(t)"),
+            Ok(vec![
+                T::Rot,
+                T::OpenParen,
+                T::Variable(ScalarName::new("t")),
+                T::CloseParen,
+            ])
+        );
+    }
+
+    #[test]
+    fn tokenise_expression_index() {
+        use super::Token as T;
+
+        assert_eq!(
+            tokenise_index("[1,2]"),
+            Ok(("", T::Index { row: 1, column: 2 }))
+        );
+        assert_eq!(
+            tokenise_index("[ 3 , 10 ] * B"),
+            Ok((" * B", T::Index { row: 3, column: 10 }))
+        );
+        assert!(tokenise_index("[1 2; 3 4]").is_err());
+        assert!(tokenise_index("[1.5, 2]").is_err());
+
+        assert_eq!(
+            tokenise_expression("A[1, 2] * [1; 2]"),
+            Ok(vec![
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Index { row: 1, column: 2 },
+                T::Star,
+                T::OpenSquareBracket,
+                T::Number(1.),
+                T::Semicolon,
+                T::Number(2.),
+                T::CloseSquareBracket,
+            ])
+        );
+    }
+
+    #[test]
+    fn tokenise_expression_comments() {
+        use super::Token as T;
+
+        assert_eq!(tokenise_comment("# hello"), Ok(("", " hello")));
+        assert_eq!(tokenise_comment("// a * b\nC"), Ok(("\nC", " a * b")));
+        assert!(tokenise_comment("/ 2").is_err());
+
+        assert_eq!(
+            tokenise_expression("A / 2 // halve A"),
+            Ok(vec![
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Slash,
+                T::Number(2.),
+            ])
+        );
+
+        assert_eq!(
+            tokenise_expression("# The first matrix\nA * # the second one\nB"),
+            Ok(vec![
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Star,
+                T::NamedMatrix(MatrixName::new("B")),
+            ])
+        );
+    }
+
+    #[test]
+    fn tokenise_expression_unicode() {
+        use super::Token as T;
+
+        assert_eq!(
+            tokenise_expression("2 × A · B ÷ 3 − C"),
+            Ok(vec![
+                T::Number(2.),
+                T::Star,
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Star,
+                T::NamedMatrix(MatrixName::new("B")),
+                T::Slash,
+                T::Number(3.),
+                T::Minus,
+                T::NamedMatrix(MatrixName::new("C")),
+            ])
+        );
+
+        assert_eq!(
+            tokenise_expression("A⁻¹Bᵀ"),
+            Ok(vec![
+                T::NamedMatrix(MatrixName::new("A")),
+                T::Caret,
+                T::OpenBrace,
+                T::Minus,
+                T::Number(1.),
+                T::CloseBrace,
+                T::NamedMatrix(MatrixName::new("B")),
+                T::Caret,
+                T::NamedMatrix(MatrixName::new("T")),
+            ])
+        );
+
+        assert_eq!(
+            tokenise_expression("A ⁻ 1"),
+            Err(TokeniseError::UnexpectedCharacter {
+                character: '⁻',
+                column: 3,
+                rest: "⁻ 1"
+            })
+        );
+    }
+
+    #[test]
+    fn tokenise_expression_spans() {
+        assert_eq!(
+            tokenise_expression_with_spans("rot(90) # comment\n  × A[1, 2]⁻¹"),
+            Ok(vec![
+                (Token::Rot, 0..3),
+                (Token::OpenParen, 3..4),
+                (Token::Number(90.), 4..6),
+                (Token::CloseParen, 6..7),
+                (Token::Star, 20..22),
+                (Token::NamedMatrix(MatrixName::new("A")), 23..24),
+                (Token::Index { row: 1, column: 2 }, 24..30),
+                (Token::Caret, 30..35),
+                (Token::OpenBrace, 30..35),
+                (Token::Minus, 30..35),
+                (Token::Number(1.), 30..35),
+                (Token::CloseBrace, 30..35),
+            ])
+        );
+    }
+
+    #[test]
+    fn token_display() {
+        let tokens = tokenise_expression("A * rot(90) / [1, 2; 3, 4]^{-1} + B[2, 1]").unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" "),
+            "A * rot ( 90 ) / [ 1 , 2 ; 3 , 4 ] ^ { - 1 } + B [2, 1]"
+        );
+    }
+
     #[test]
     fn tokenise_expression_abc() {
         assert_eq!(
@@ -369,77 +1062,88 @@ mod tests {
 
         assert_eq!(
             tokenise_expression("aBC"),
-            Err(TokeniseError::NomError {
-                nom_error: nom::Err::Error(nom::error::Error::new(
-                    "aBC",
-                    nom::error::ErrorKind::MultiSpace
-                ))
-            })
+            Ok(vec![
+                Token::Variable(ScalarName::new("a")),
+                Token::NamedMatrix(MatrixName::new("B")),
+                Token::NamedMatrix(MatrixName::new("C"))
+            ])
         );
 
         assert_eq!(
             tokenise_expression("aBc"),
-            Err(TokeniseError::NomError {
-                nom_error: nom::Err::Error(nom::error::Error::new(
-                    "aBc",
-                    nom::error::ErrorKind::MultiSpace
-                ))
-            })
+            Ok(vec![
+                Token::Variable(ScalarName::new("a")),
+                Token::NamedMatrix(MatrixName::new("Bc"))
+            ])
         );
 
         assert_eq!(
             tokenise_expression("abC"),
-            Err(TokeniseError::NomError {
-                nom_error: nom::Err::Error(nom::error::Error::new(
-                    "abC",
-                    nom::error::ErrorKind::MultiSpace
-                ))
-            })
+            Ok(vec![
+                Token::Variable(ScalarName::new("ab")),
+                Token::NamedMatrix(MatrixName::new("C"))
+            ])
         );
 
         assert_eq!(
             tokenise_expression("abc"),
-            Err(TokeniseError::NomError {
-                nom_error: nom::Err::Error(nom::error::Error::new(
-                    "abc",
-                    nom::error::ErrorKind::MultiSpace
-                ))
-            })
+            Ok(vec![Token::Variable(ScalarName::new("abc"))])
         );
     }
 
     #[test]
     fn tokenise_expression_failure() {
+        assert_eq!(tokenise_expression(""), Err(TokeniseError::Empty));
+
         assert_eq!(
             tokenise_expression("@"),
-            Err(TokeniseError::NomError {
-                nom_error: ::nom::Err::Error(::nom::error::Error {
-                    input: "@",
-                    code: ::nom::error::ErrorKind::MultiSpace
-                })
+            Err(TokeniseError::UnexpectedCharacter {
+                character: '@',
+                column: 1,
+                rest: "@"
             })
         );
 
         assert_eq!(
             tokenise_expression(" []@"),
-            Err(TokeniseError::UnconsumedInput("@"))
+            Err(TokeniseError::UnexpectedCharacter {
+                character: '@',
+                column: 4,
+                rest: "@"
+            })
+        );
+
+        assert_eq!(
+            tokenise_expression("norm(A, \"nuclear\")"),
+            Err(TokeniseError::UnexpectedCharacter {
+                character: '"',
+                column: 9,
+                rest: "\"nuclear\")"
+            })
         );
 
         assert_eq!(
             tokenise_expression(std::str::from_utf8(&[10, 5, 91]).unwrap()),
-            Err(TokeniseError::UnconsumedInput(
-                std::str::from_utf8(&[5, 91]).unwrap()
-            ))
+            Err(TokeniseError::UnexpectedCharacter {
+                character: '\u{5}',
+                column: 2,
+                rest: std::str::from_utf8(&[5, 91]).unwrap()
+            })
         );
 
         assert_eq!(
-            tokenise_expression("word"),
-            Err(TokeniseError::NomError {
-                nom_error: ::nom::Err::Error(::nom::error::Error {
-                    input: "word",
-                    code: ::nom::error::ErrorKind::MultiSpace
-                })
+            tokenise_expression("_word"),
+            Err(TokeniseError::UnexpectedCharacter {
+                character: '_',
+                column: 1,
+                rest: "_word"
             })
         );
+
+        // Columns count characters rather than bytes
+        let error = tokenise_expression("é + é").unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected character 'é' at column 1");
+        let error = tokenise_expression("2 × 3 £").unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected character '£' at column 7");
     }
 }
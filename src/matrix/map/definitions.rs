@@ -0,0 +1,506 @@
+//! This module provides [`DefinedMatrixMap`], a [`MatrixMap`] where matrices can also be defined
+//! by expressions of other matrices.
+//!
+//! A definition like `C := A * B` is stored as an AST rather than a value, and it's evaluated
+//! again whenever the map changes, so reading `C` after changing `A` gives the new product.
+//! Definitions are evaluated in dependency order, so they can refer to other definitions, but a
+//! definition can never depend on itself, so something like `A := B + C` and `B := 2 A` is
+//! rejected with [`MatrixMapError::CyclicDefinition`].
+
+use super::{is_builtin, next_generation, MatrixMap, MatrixMapError, ScalarMapError};
+use crate::matrix::{
+    expression::ast::{AstNode, EvaluationError, NumberOrMatrix},
+    MatrixName, MatrixValue, ScalarName,
+};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+/// An error explaining why a definition in a [`DefinedMatrixMap`] can't be evaluated.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum DefinitionError {
+    /// Evaluating the expression failed.
+    #[error("{0}")]
+    Evaluation(#[from] EvaluationError),
+
+    /// The expression evaluated to something which can't be stored in the map, like a number,
+    /// or a 3D matrix in a map of 2D matrices.
+    #[error("The definition of \"{0}\" doesn't evaluate to a matrix that fits in this map")]
+    WrongType(MatrixName),
+}
+
+/// A [`MatrixMap`] wrapping another map `M`, where each matrix is either a plain value or defined
+/// by an expression of other matrices.
+///
+/// Every definition is evaluated again whenever the map changes, so the values read from the map
+/// are always up to date. A definition which can't be evaluated, maybe because it refers to a
+/// matrix which doesn't exist yet, is kept anyway and [`get`](MatrixMap::get) returns
+/// [`MatrixMapError::DefinitionFailed`] until it can be. The reason is available from
+/// [`error`](Self::error).
+///
+/// Only matrices with a value are included in [`iter`](MatrixMap::iter) and
+/// [`len`](MatrixMap::len), so failed definitions are left out.
+///
+/// ```
+/// # use trinity::matrix::{expression::parse_expression_from_string, map::prelude::*, MatrixName};
+/// # use glam::DMat2;
+/// let mut map = DefinedMatrixMap::<MatrixMap2>::new();
+/// let a = MatrixName::new("A");
+/// let c = MatrixName::new("C");
+///
+/// map.set(a.clone(), DMat2::IDENTITY).unwrap();
+/// map.define(c.clone(), parse_expression_from_string("2A").unwrap()).unwrap();
+/// assert_eq!(map.get(&c), Ok(DMat2::IDENTITY * 2.));
+///
+/// map.set(a, DMat2::from_cols_array(&[1., 2., 3., 4.])).unwrap();
+/// assert_eq!(map.get(&c), Ok(DMat2::from_cols_array(&[2., 4., 6., 8.])));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DefinedMatrixMap<M: MatrixMap> {
+    /// The value of every matrix, including the current values of the definitions.
+    values: M,
+
+    /// The expressions defining some of the matrices.
+    definitions: HashMap<MatrixName, AstNode>,
+
+    /// The errors from the definitions which failed the last time they were evaluated.
+    errors: HashMap<MatrixName, DefinitionError>,
+
+    /// The generation of this map. See [`MatrixMap::generation`].
+    generation: u64,
+}
+
+impl<M: MatrixMap> DefinedMatrixMap<M>
+where
+    M::MatrixType: TryFrom<MatrixValue>,
+{
+    /// Define the matrix with the given name by an expression, replacing any old value or
+    /// definition.
+    ///
+    /// This fails if the name is invalid, or if the definition would depend on itself, in which
+    /// case the map is left unchanged. If the expression can't be evaluated for any other reason,
+    /// then the definition is still stored, and the reason is available from
+    /// [`error`](Self::error).
+    pub fn define(&mut self, name: MatrixName, expression: AstNode) -> Result<(), MatrixMapError> {
+        if !name.self_is_valid() {
+            return Err(MatrixMapError::InvalidName(name.name));
+        }
+        if is_builtin(&name) {
+            return Err(MatrixMapError::ReadOnlyName(name));
+        }
+        let lookup = |other: &MatrixName| {
+            if *other == name {
+                Some(&expression)
+            } else {
+                self.definitions.get(other)
+            }
+        };
+        if let Some(cycle) = find_cycle(&name, &lookup) {
+            return Err(MatrixMapError::CyclicDefinition(cycle));
+        }
+
+        let _ = self.values.remove(&name);
+        self.definitions.insert(name, expression);
+        self.reevaluate();
+        Ok(())
+    }
+
+    /// Get the expression defining the named matrix, if it's defined by one.
+    pub fn definition(&self, name: &MatrixName) -> Option<&AstNode> {
+        self.definitions.get(name)
+    }
+
+    /// Get the reason why the named matrix's definition can't currently be evaluated, if it can't.
+    pub fn error(&self, name: &MatrixName) -> Option<&DefinitionError> {
+        self.errors.get(name)
+    }
+
+    /// Does the map have a value or a definition for this name?
+    fn contains(&self, name: &MatrixName) -> bool {
+        self.definitions.contains_key(name) || self.values.get(name).is_ok()
+    }
+
+    /// Evaluate every definition again, in dependency order, and store the results.
+    fn reevaluate(&mut self) {
+        for name in self.definitions.keys() {
+            let _ = self.values.remove(name);
+        }
+        self.errors.clear();
+        self.generation = next_generation();
+
+        for name in self.evaluation_order() {
+            let result = match self.definitions[&name].clone().evaluate(&*self) {
+                Ok(NumberOrMatrix::Matrix(matrix)) => M::MatrixType::try_from(matrix)
+                    .map_err(|_| DefinitionError::WrongType(name.clone())),
+                Ok(_) => Err(DefinitionError::WrongType(name.clone())),
+                Err(error) => Err(error.into()),
+            };
+
+            match result {
+                Ok(value) => {
+                    // The name was validated when it was defined
+                    let _ = self.values.set(name, value);
+                }
+                Err(error) => {
+                    self.errors.insert(name, error);
+                }
+            }
+        }
+    }
+
+    /// Sort the defined names so that every definition comes after the definitions it depends
+    /// on.
+    fn evaluation_order(&self) -> Vec<MatrixName> {
+        let mut order = Vec::with_capacity(self.definitions.len());
+        let mut visited = HashSet::new();
+
+        // Sort the names so that the order is deterministic
+        let mut names: Vec<_> = self.definitions.keys().collect();
+        names.sort_unstable();
+        for name in names {
+            self.visit(name, &mut visited, &mut order);
+        }
+
+        order
+    }
+
+    /// Visit this name in a depth-first search of the dependencies for
+    /// [`evaluation_order`](Self::evaluation_order), adding it to the order after everything it
+    /// depends on.
+    fn visit<'a>(
+        &'a self,
+        name: &'a MatrixName,
+        visited: &mut HashSet<&'a MatrixName>,
+        order: &mut Vec<MatrixName>,
+    ) {
+        let Some(expression) = self.definitions.get(name) else {
+            return;
+        };
+        if !visited.insert(name) {
+            return;
+        }
+
+        for dependency in expression.named_matrices() {
+            if let Some((dependency, _)) = self.definitions.get_key_value(&dependency) {
+                self.visit(dependency, visited, order);
+            }
+        }
+        order.push(name.clone());
+    }
+}
+
+/// Search the definitions depth-first from `start`, returning the first cycle found, starting and
+/// ending with the same name. The definitions are given by `lookup`, so that the search can
+/// include a change to them before it's made.
+fn find_cycle<'a>(
+    start: &MatrixName,
+    lookup: &dyn Fn(&MatrixName) -> Option<&'a AstNode>,
+) -> Option<Vec<MatrixName>> {
+    /// Visit this name, where the stack holds the names currently being visited, so finding a
+    /// name which is already on the stack means there's a cycle.
+    fn visit<'a>(
+        name: &MatrixName,
+        lookup: &dyn Fn(&MatrixName) -> Option<&'a AstNode>,
+        stack: &mut Vec<MatrixName>,
+        finished: &mut HashSet<MatrixName>,
+    ) -> Option<Vec<MatrixName>> {
+        let expression = lookup(name)?;
+        if finished.contains(name) {
+            return None;
+        }
+        if let Some(start) = stack.iter().position(|other| other == name) {
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(name.clone());
+            return Some(cycle);
+        }
+
+        stack.push(name.clone());
+        for dependency in expression.named_matrices() {
+            if let Some(cycle) = visit(&dependency, lookup, stack, finished) {
+                return Some(cycle);
+            }
+        }
+        stack.pop();
+
+        finished.insert(name.clone());
+        None
+    }
+
+    visit(start, lookup, &mut Vec::new(), &mut HashSet::new())
+}
+
+impl<M: MatrixMap> MatrixMap for DefinedMatrixMap<M>
+where
+    M::MatrixType: TryFrom<MatrixValue>,
+{
+    type MatrixType = M::MatrixType;
+
+    fn new() -> Self {
+        Self {
+            values: M::new(),
+            definitions: HashMap::new(),
+            errors: HashMap::new(),
+            generation: next_generation(),
+        }
+    }
+
+    /// Set the value of the matrix with the given name, replacing any definition of it.
+    fn set(&mut self, name: MatrixName, value: Self::MatrixType) -> Result<(), MatrixMapError> {
+        self.values.set(name.clone(), value)?;
+        self.definitions.remove(&name);
+        self.reevaluate();
+        Ok(())
+    }
+
+    fn scalar(&self, name: &ScalarName) -> Result<f64, ScalarMapError> {
+        self.values.scalar(name)
+    }
+
+    fn get(&self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        if self.errors.contains_key(name) {
+            Err(MatrixMapError::DefinitionFailed(name.clone()))
+        } else {
+            self.values.get(name)
+        }
+    }
+
+    /// Remove the named matrix from the map, returning its old value.
+    ///
+    /// This also removes a definition which can't currently be evaluated, but then returns
+    /// [`MatrixMapError::DefinitionFailed`], since there's no value to return.
+    fn remove(&mut self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        let result = if self.definitions.remove(name).is_some() && self.errors.contains_key(name) {
+            Err(MatrixMapError::DefinitionFailed(name.clone()))
+        } else {
+            self.values.remove(name)
+        };
+
+        if result.is_ok() || self.errors.contains_key(name) {
+            self.reevaluate();
+        }
+        result
+    }
+
+    fn clear(&mut self) {
+        if !self.values.is_empty() || !self.definitions.is_empty() {
+            self.values.clear();
+            self.definitions.clear();
+            self.errors.clear();
+            self.generation = next_generation();
+        }
+    }
+
+    /// Move the matrix named `from` to the name `to`, whether it's a value or a definition.
+    ///
+    /// Other definitions which refer to `from` aren't changed, so they'll fail unless something
+    /// else is given that name. This fails with [`MatrixMapError::CyclicDefinition`] if the moved
+    /// definition would depend on itself under its new name.
+    fn rename(
+        &mut self,
+        from: &MatrixName,
+        to: MatrixName,
+        overwrite: bool,
+    ) -> Result<(), MatrixMapError> {
+        if !MatrixName::is_valid(from.name.as_str()) {
+            return Err(MatrixMapError::InvalidName(from.name.clone()));
+        }
+        if !to.self_is_valid() {
+            return Err(MatrixMapError::InvalidName(to.name));
+        }
+        if is_builtin(from) {
+            return Err(MatrixMapError::ReadOnlyName(from.to_owned()));
+        }
+        if is_builtin(&to) {
+            return Err(MatrixMapError::ReadOnlyName(to));
+        }
+        if !self.contains(from) {
+            return Err(MatrixMapError::NameNotDefined(from.to_owned()));
+        }
+        if *from == to {
+            return Ok(());
+        }
+        if !overwrite && self.contains(&to) {
+            return Err(MatrixMapError::NameAlreadyDefined(to));
+        }
+        if let Some(expression) = self.definitions.get(from) {
+            let lookup = |other: &MatrixName| {
+                if *other == to {
+                    Some(expression)
+                } else if other == from {
+                    None
+                } else {
+                    self.definitions.get(other)
+                }
+            };
+            if let Some(cycle) = find_cycle(&to, &lookup) {
+                return Err(MatrixMapError::CyclicDefinition(cycle));
+            }
+        }
+
+        self.definitions.remove(&to);
+        let _ = self.values.remove(&to);
+        if let Some(expression) = self.definitions.remove(from) {
+            self.definitions.insert(to, expression);
+        } else {
+            self.values.rename(from, to, true)?;
+        }
+        self.reevaluate();
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&MatrixName, &Self::MatrixType)> {
+        self.values.iter()
+    }
+
+    fn is_defined(&self, name: &MatrixName) -> bool {
+        self.values.is_defined(name)
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{
+        expression::parse_expression_from_string,
+        map::{MatrixMap2, MatrixMap3},
+    };
+    use glam::{DMat2, DMat3};
+
+    /// Parse an expression which is known to be valid.
+    fn parse(expression: &str) -> AstNode {
+        parse_expression_from_string(expression).unwrap()
+    }
+
+    #[test]
+    fn defined_matrices_update() {
+        let [a, b, c, d, e, x] = ["A", "B", "C", "D", "E", "X"].map(MatrixName::new);
+        let m1 = DMat2::from_cols_array(&[1., 2., 3., 4.]);
+        let m2 = DMat2::from_cols_array(&[0., 1., -1., 0.]);
+
+        let mut map = DefinedMatrixMap::<MatrixMap2>::new();
+        map.set(a.clone(), m1).unwrap();
+        map.set(b.clone(), m2).unwrap();
+        map.define(c.clone(), parse("A B")).unwrap();
+        map.define(d.clone(), parse("C + A")).unwrap();
+        assert_eq!(map.get(&c), Ok(m1 * m2));
+        assert_eq!(map.get(&d), Ok(m1 * m2 + m1));
+        assert_eq!(map.definition(&c), Some(&parse("A B")));
+        assert_eq!(map.definition(&a), None);
+        assert_eq!(
+            map.names(),
+            vec![a.clone(), b.clone(), c.clone(), d.clone()]
+        );
+
+        let generation = map.generation();
+        map.set(a.clone(), m2).unwrap();
+        assert_ne!(map.generation(), generation);
+        assert_eq!(map.get(&c), Ok(m2 * m2));
+        assert_eq!(map.get(&d), Ok(m2 * m2 + m2));
+        assert_eq!(
+            parse("D - A").evaluate(&map),
+            Ok(NumberOrMatrix::Matrix((m2 * m2).into()))
+        );
+
+        // Definitions which can't be evaluated yet are kept until they can be
+        map.define(e.clone(), parse("X + C")).unwrap();
+        assert_eq!(
+            map.get(&e),
+            Err(MatrixMapError::DefinitionFailed(e.clone()))
+        );
+        assert_eq!(
+            map.error(&e),
+            Some(&DefinitionError::Evaluation(
+                MatrixMapError::NameNotDefined(x.clone()).into()
+            ))
+        );
+        assert_eq!(map.len(), 4);
+        map.set(x, m1).unwrap();
+        assert_eq!(map.get(&e), Ok(m1 + m2 * m2));
+        assert_eq!(map.error(&e), None);
+
+        map.define(d.clone(), parse("2")).unwrap();
+        assert_eq!(map.error(&d), Some(&DefinitionError::WrongType(d.clone())));
+        map.define(d.clone(), parse("[1 0 0; 0 1 0; 0 0 1]"))
+            .unwrap();
+        assert_eq!(map.error(&d), Some(&DefinitionError::WrongType(d.clone())));
+
+        // Cyclic definitions are rejected and leave the map unchanged
+        assert_eq!(
+            map.define(c.clone(), parse("D + C")),
+            Err(MatrixMapError::CyclicDefinition(vec![c.clone(), c.clone()]))
+        );
+        let cycle = map.define(a.clone(), parse("2 E")).unwrap_err();
+        assert_eq!(
+            cycle,
+            MatrixMapError::CyclicDefinition(vec![a.clone(), e.clone(), c.clone(), a.clone()])
+        );
+        assert_eq!(cycle.to_string(), "Cyclic definition: A -> E -> C -> A");
+        assert_eq!(map.definition(&c), Some(&parse("A B")));
+        assert_eq!(map.get(&a), Ok(m2));
+        assert_eq!(map.get(&e), Ok(m1 + m2 * m2));
+    }
+
+    #[test]
+    fn defined_matrices_replace_rename_remove() {
+        let [a, b, c] = ["A", "B", "C"].map(MatrixName::new);
+        let mut map = DefinedMatrixMap::<MatrixMap3>::new();
+        map.set(a.clone(), DMat3::IDENTITY).unwrap();
+        map.define(b.clone(), parse("3A")).unwrap();
+        map.define(c.clone(), parse("B^2")).unwrap();
+        assert_eq!(map.get(&c), Ok(DMat3::IDENTITY * 9.));
+
+        // Setting a value replaces a definition, and defining replaces a value
+        map.set(b.clone(), DMat3::IDENTITY * 2.).unwrap();
+        assert_eq!(map.definition(&b), None);
+        assert_eq!(map.get(&c), Ok(DMat3::IDENTITY * 4.));
+        map.define(a.clone(), parse("B")).unwrap();
+        assert_eq!(map.get(&a), Ok(DMat3::IDENTITY * 2.));
+
+        assert_eq!(
+            map.rename(&c, b.clone(), false),
+            Err(MatrixMapError::NameAlreadyDefined(b.clone()))
+        );
+        assert_eq!(
+            map.define(MatrixName { name: "x".into() }, parse("A")),
+            Err(MatrixMapError::InvalidName("x".into()))
+        );
+        assert_eq!(
+            map.define(MatrixName::new("I"), parse("A")),
+            Err(MatrixMapError::ReadOnlyName(MatrixName::new("I")))
+        );
+
+        // Renaming a value breaks the definitions which refer to it
+        let b_two = MatrixName::new("B_two");
+        map.rename(&b, b_two.clone(), false).unwrap();
+        assert_eq!(map.get(&b_two), Ok(DMat3::IDENTITY * 2.));
+        assert_eq!(
+            map.get(&a),
+            Err(MatrixMapError::DefinitionFailed(a.clone()))
+        );
+        assert_eq!(
+            map.rename(&a, b.clone(), false),
+            Err(MatrixMapError::CyclicDefinition(vec![b.clone(), b.clone()]))
+        );
+        assert_eq!(map.definition(&a), Some(&parse("B")));
+
+        assert_eq!(
+            map.remove(&a),
+            Err(MatrixMapError::DefinitionFailed(a.clone()))
+        );
+        assert_eq!(map.definition(&a), None);
+        assert_eq!(map.get(&a), Err(MatrixMapError::NameNotDefined(a)));
+        map.set(b, DMat3::IDENTITY).unwrap();
+        assert_eq!(map.remove(&c), Ok(DMat3::IDENTITY));
+        assert_eq!(map.names(), vec![MatrixName::new("B"), b_two]);
+
+        map.clear();
+        assert!(map.is_empty());
+    }
+}
@@ -0,0 +1,44 @@
+//! This module provides the types used to compare and combine maps with
+//! [`MatrixMap::diff`](super::MatrixMap::diff) and [`MatrixMap::merge`](super::MatrixMap::merge).
+
+use crate::matrix::MatrixName;
+use std::collections::BTreeSet;
+
+/// The differences between two maps, as returned by [`MatrixMap::diff`](super::MatrixMap::diff).
+/// Each set is sorted by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatrixMapDiff {
+    /// The names which are only defined in the new map.
+    pub added: BTreeSet<MatrixName>,
+
+    /// The names which are only defined in the old map.
+    pub removed: BTreeSet<MatrixName>,
+
+    /// The names which are defined in both maps, but with different values.
+    pub changed: BTreeSet<MatrixName>,
+}
+
+impl MatrixMapDiff {
+    /// Are the two maps the same?
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// What [`MatrixMap::merge`](super::MatrixMap::merge) should do when both maps define the same
+/// name with different values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MergePolicy {
+    /// Keep the value in the map being merged into.
+    KeepOurs,
+
+    /// Overwrite the value with the one from the other map.
+    TakeTheirs,
+
+    /// Don't merge anything, and fail with
+    /// [`MatrixMapError::MergeConflict`](super::MatrixMapError::MergeConflict).
+    #[default]
+    Fail,
+}
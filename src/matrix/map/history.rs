@@ -0,0 +1,249 @@
+//! This module provides [`MatrixMapHistory`], a [`MatrixMap`] which records every change made to
+//! it so that they can be undone and redone.
+
+use super::{MatrixMap, MatrixMapError, ScalarMapError};
+use crate::matrix::{MatrixName, ScalarName};
+
+/// A change to a map, recorded as the old value of every name it touched, where `None` means that
+/// the name wasn't defined. Applying an edit puts those values back.
+type Edit<T> = Vec<(MatrixName, Option<T>)>;
+
+/// A [`MatrixMap`] wrapping another map `M`, which records every successful call to
+/// [`set`](MatrixMap::set), [`remove`](MatrixMap::remove), [`rename`](MatrixMap::rename), and
+/// [`clear`](MatrixMap::clear) so that it can be undone with [`undo`](Self::undo) and redone with
+/// [`redo`](Self::redo).
+///
+/// Making a new change after undoing some changes throws away the changes that could have been
+/// redone, like in a text editor. Only values are recorded, so wrapping a
+/// [`DefinedMatrixMap`](super::DefinedMatrixMap) would turn its definitions back into plain
+/// values on undo.
+///
+/// ```
+/// # use trinity::matrix::{map::prelude::*, MatrixName};
+/// # use glam::DMat2;
+/// let mut map = MatrixMapHistory::<MatrixMap2>::new();
+/// let a = MatrixName::new("A");
+///
+/// map.set(a.clone(), DMat2::IDENTITY).unwrap();
+/// map.set(a.clone(), DMat2::ZERO).unwrap();
+/// assert!(map.undo());
+/// assert_eq!(map.get(&a), Ok(DMat2::IDENTITY));
+/// assert!(map.redo());
+/// assert_eq!(map.get(&a), Ok(DMat2::ZERO));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MatrixMapHistory<M: MatrixMap> {
+    /// The current state of the map.
+    map: M,
+
+    /// The edits which would undo each change, with the most recent change last.
+    undo_stack: Vec<Edit<M::MatrixType>>,
+
+    /// The edits which would redo each undone change, with the most recently undone change last.
+    redo_stack: Vec<Edit<M::MatrixType>>,
+}
+
+impl<M: MatrixMap> MatrixMapHistory<M> {
+    /// Undo the most recent change, returning false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop() {
+            Some(edit) => {
+                let redo = self.apply(edit);
+                self.redo_stack.push(redo);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Redo the most recently undone change, returning false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(edit) => {
+                let undo = self.apply(edit);
+                self.undo_stack.push(undo);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Is there a change to undo?
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Is there an undone change to redo?
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Forget every recorded change, without changing the map.
+    pub fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// Get the map without its history.
+    pub fn into_inner(self) -> M {
+        self.map
+    }
+
+    /// Record the current values of these names before they're changed.
+    fn snapshot<'a>(&self, names: impl IntoIterator<Item = &'a MatrixName>) -> Edit<M::MatrixType> {
+        names
+            .into_iter()
+            .map(|name| (name.clone(), self.map.get(name).ok()))
+            .collect()
+    }
+
+    /// Record a successful change, which can be undone by the given edit.
+    fn record(&mut self, undo: Edit<M::MatrixType>) {
+        self.undo_stack.push(undo);
+        self.redo_stack.clear();
+    }
+
+    /// Put back the values in the edit, returning the edit which would reverse this.
+    fn apply(&mut self, edit: Edit<M::MatrixType>) -> Edit<M::MatrixType> {
+        let reverse = self.snapshot(edit.iter().map(|(name, _)| name));
+        for (name, value) in edit {
+            // These names were all valid when the edit was recorded
+            let _ = match value {
+                Some(value) => self.map.set(name, value),
+                None => self.map.remove(&name).map(|_| ()),
+            };
+        }
+        reverse
+    }
+}
+
+impl<M: MatrixMap> MatrixMap for MatrixMapHistory<M> {
+    type MatrixType = M::MatrixType;
+
+    fn new() -> Self {
+        Self {
+            map: M::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    fn set(&mut self, name: MatrixName, value: Self::MatrixType) -> Result<(), MatrixMapError> {
+        let undo = self.snapshot([&name]);
+        self.map.set(name, value)?;
+        self.record(undo);
+        Ok(())
+    }
+
+    fn scalar(&self, name: &ScalarName) -> Result<f64, ScalarMapError> {
+        self.map.scalar(name)
+    }
+
+    fn get(&self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        self.map.get(name)
+    }
+
+    fn remove(&mut self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        let undo = self.snapshot([name]);
+        let value = self.map.remove(name)?;
+        self.record(undo);
+        Ok(value)
+    }
+
+    fn clear(&mut self) {
+        if !self.map.is_empty() {
+            let names = self.map.names();
+            let undo = self.snapshot(&names);
+            self.map.clear();
+            self.record(undo);
+        }
+    }
+
+    fn rename(
+        &mut self,
+        from: &MatrixName,
+        to: MatrixName,
+        overwrite: bool,
+    ) -> Result<(), MatrixMapError> {
+        let undo = self.snapshot([from, &to]);
+        let same = *from == to;
+        self.map.rename(from, to, overwrite)?;
+        if !same {
+            self.record(undo);
+        }
+        Ok(())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&MatrixName, &Self::MatrixType)> {
+        self.map.iter()
+    }
+
+    fn is_defined(&self, name: &MatrixName) -> bool {
+        self.map.is_defined(name)
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn generation(&self) -> u64 {
+        self.map.generation()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::map::MatrixMap2;
+    use glam::DMat2;
+
+    #[test]
+    fn matrix_map_undo_redo() {
+        let [a, b] = ["A", "B"].map(MatrixName::new);
+        let m1 = DMat2::from_cols_array(&[1., 2., 3., 4.]);
+        let m2 = DMat2::from_cols_array(&[0., 1., -1., 0.]);
+
+        let mut map = MatrixMapHistory::<MatrixMap2>::new();
+        assert!(!map.can_undo());
+        assert!(!map.undo());
+
+        map.set(a.clone(), m1).unwrap();
+        map.set(b.clone(), m2).unwrap();
+        map.rename(&a, b.clone(), true).unwrap();
+        assert_eq!(map.names(), vec![b.clone()]);
+
+        assert!(map.undo());
+        assert_eq!(map.get(&a), Ok(m1));
+        assert_eq!(map.get(&b), Ok(m2));
+        assert!(map.undo());
+        assert_eq!(map.get(&b), Err(MatrixMapError::NameNotDefined(b.clone())));
+        assert!(map.can_redo());
+        assert!(map.redo());
+        assert!(map.redo());
+        assert!(!map.redo());
+        assert_eq!(map.get(&b), Ok(m1));
+        assert_eq!(map.len(), 1);
+
+        map.clear();
+        assert_eq!(
+            map.remove(&a),
+            Err(MatrixMapError::NameNotDefined(a.clone()))
+        );
+        assert!(map.undo());
+        assert_eq!(map.get(&b), Ok(m1));
+
+        // Failed changes aren't recorded, and a new change throws away the undone ones
+        assert!(map.undo());
+        assert!(map.can_redo());
+        assert!(map.set(MatrixName { name: "x".into() }, m1).is_err());
+        assert!(map.can_redo());
+        assert_eq!(map.remove(&b), Ok(m2));
+        assert!(!map.can_redo());
+        assert!(map.undo());
+        assert_eq!(map.get(&b), Ok(m2));
+
+        map.clear_history();
+        assert!(!map.can_undo());
+        assert_eq!(map.into_inner().get(&a), Ok(m1));
+    }
+}
@@ -0,0 +1,298 @@
+//! This module provides [`LayeredMatrixMap`], a [`MatrixMap`] made of a stack of maps, where
+//! lookups fall through the layers and changes only ever go to the top layer.
+
+use super::{next_generation, MatrixMap, MatrixMapError, ScalarMapError};
+use crate::matrix::{MatrixName, ScalarName};
+use std::collections::BTreeMap;
+
+/// A [`MatrixMap`] made of a stack of maps `M`, like the matrices provided by a lesson underneath
+/// the user's own workspace.
+///
+/// Getting a matrix looks through the layers from the top down, so a name in a higher layer
+/// shadows the same name in any lower layer. Every change goes to the top layer, so the lower
+/// layers are never changed through this map. Setting a name which is defined in a lower layer
+/// shadows it in the top layer, and removing or renaming that shadowing matrix reveals the lower
+/// one again. A matrix which is only defined in a lower layer can't be removed or renamed, and
+/// doing so gives [`MatrixMapError::ReadOnlyName`].
+///
+/// There is always at least one layer.
+///
+/// ```
+/// # use trinity::matrix::{map::prelude::*, MatrixName};
+/// # use glam::DMat2;
+/// let [a, b] = ["A", "B"].map(MatrixName::new);
+/// let mut lesson = MatrixMap2::new();
+/// lesson.set(a.clone(), DMat2::IDENTITY).unwrap();
+/// lesson.set(b.clone(), DMat2::IDENTITY * 2.).unwrap();
+///
+/// let mut map = LayeredMatrixMap::new();
+/// map.insert_layer(0, lesson);
+/// map.set(a.clone(), DMat2::ZERO).unwrap();
+///
+/// assert_eq!(map.get(&a), Ok(DMat2::ZERO));
+/// assert_eq!(map.get(&b), Ok(DMat2::IDENTITY * 2.));
+/// assert_eq!(map.remove(&b), Err(MatrixMapError::ReadOnlyName(b)));
+///
+/// // The lesson's matrix was only shadowed, not overwritten
+/// map.remove(&a).unwrap();
+/// assert_eq!(map.get(&a), Ok(DMat2::IDENTITY));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LayeredMatrixMap<M> {
+    /// The layers, from the bottom up. This is never empty, and the last layer is the top.
+    layers: Vec<M>,
+
+    /// The generation of this map, which changes when layers are added, removed, or changed. See
+    /// [`MatrixMap::generation`].
+    ///
+    /// This is never the generation of any layer, so a layer is never mistaken for the whole map.
+    generation: u64,
+}
+
+impl<M: PartialEq> PartialEq for LayeredMatrixMap<M> {
+    fn eq(&self, other: &Self) -> bool {
+        // Maps with the same layers are equal, even if they got there in different ways
+        self.layers == other.layers
+    }
+}
+
+impl<M: MatrixMap> LayeredMatrixMap<M> {
+    /// Get the layers, from the bottom up. The last layer is the top, which gets every change.
+    pub fn layers(&self) -> &[M] {
+        &self.layers
+    }
+
+    /// Get the layer at this index, counting from the bottom, to change it directly, like when a
+    /// lesson reloads its own matrices.
+    ///
+    /// The layer can't be watched once it's borrowed, so this always gives the map a new
+    /// generation, even if the layer isn't actually changed.
+    pub fn layer_mut(&mut self, index: usize) -> Option<&mut M> {
+        let layer = self.layers.get_mut(index)?;
+        self.generation = next_generation();
+        Some(layer)
+    }
+
+    /// Get the top layer, which gets every change.
+    pub fn top(&self) -> &M {
+        self.layers
+            .last()
+            .expect("A layered map should always have at least one layer")
+    }
+
+    /// Push a new layer on top of all the others, so that it shadows them and gets every change
+    /// from now on.
+    pub fn push_layer(&mut self, layer: M) {
+        self.layers.push(layer);
+        self.generation = next_generation();
+    }
+
+    /// Insert a new layer at this index, counting from the bottom, so 0 puts it underneath every
+    /// other layer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is more than the number of layers.
+    pub fn insert_layer(&mut self, index: usize, layer: M) {
+        self.layers.insert(index, layer);
+        self.generation = next_generation();
+    }
+
+    /// Remove and return the layer at this index, counting from the bottom. This returns `None`
+    /// if there's no such layer, or if it's the only layer.
+    pub fn remove_layer(&mut self, index: usize) -> Option<M> {
+        if index < self.layers.len() && self.layers.len() > 1 {
+            self.generation = next_generation();
+            Some(self.layers.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Get the index of the highest layer which defines this name, counting from the bottom, or
+    /// `None` if no layer defines it.
+    pub fn layer_of(&self, name: &MatrixName) -> Option<usize> {
+        self.layers.iter().rposition(|layer| layer.is_defined(name))
+    }
+
+    /// Apply `change` to the top layer, and give this map a new generation if that changed the
+    /// top layer.
+    fn change_top<T>(&mut self, change: impl FnOnce(&mut M) -> T) -> T {
+        let top = self
+            .layers
+            .last_mut()
+            .expect("A layered map should always have at least one layer");
+        let generation = top.generation();
+        let result = change(top);
+        if top.generation() != generation {
+            self.generation = next_generation();
+        }
+        result
+    }
+
+    /// Turn a [`MatrixMapError::NameNotDefined`] from the top layer into a
+    /// [`MatrixMapError::ReadOnlyName`] if a lower layer defines the name.
+    fn lower_layers_read_only(&self, error: MatrixMapError) -> MatrixMapError {
+        match error {
+            MatrixMapError::NameNotDefined(name) if self.is_defined(&name) => {
+                MatrixMapError::ReadOnlyName(name)
+            }
+            error => error,
+        }
+    }
+}
+
+impl<M: MatrixMap> MatrixMap for LayeredMatrixMap<M> {
+    type MatrixType = M::MatrixType;
+
+    /// Create a new map with a single, empty layer.
+    fn new() -> Self {
+        Self {
+            layers: vec![M::new()],
+            generation: next_generation(),
+        }
+    }
+
+    fn set(&mut self, name: MatrixName, value: Self::MatrixType) -> Result<(), MatrixMapError> {
+        self.change_top(|top| top.set(name, value))
+    }
+
+    fn get(&self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        for layer in self.layers.iter().rev() {
+            match layer.get(name) {
+                Err(MatrixMapError::NameNotDefined(_)) => {}
+                result => return result,
+            }
+        }
+        Err(MatrixMapError::NameNotDefined(name.to_owned()))
+    }
+
+    fn scalar(&self, name: &ScalarName) -> Result<f64, ScalarMapError> {
+        for layer in self.layers.iter().rev() {
+            match layer.scalar(name) {
+                Err(ScalarMapError::NameNotDefined(_)) => {}
+                result => return result,
+            }
+        }
+        Err(ScalarMapError::NameNotDefined(name.to_owned()))
+    }
+
+    fn remove(&mut self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        self.change_top(|top| top.remove(name))
+            .map_err(|error| self.lower_layers_read_only(error))
+    }
+
+    /// Remove every matrix from the top layer, leaving the lower layers alone.
+    fn clear(&mut self) {
+        self.change_top(M::clear);
+    }
+
+    fn rename(
+        &mut self,
+        from: &MatrixName,
+        to: MatrixName,
+        overwrite: bool,
+    ) -> Result<(), MatrixMapError> {
+        let from_top = self.layer_of(from) == Some(self.layers.len() - 1);
+        if from_top && !overwrite && *from != to && self.is_defined(&to) {
+            return Err(MatrixMapError::NameAlreadyDefined(to));
+        }
+
+        self.change_top(|top| top.rename(from, to, true))
+            .map_err(|error| self.lower_layers_read_only(error))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&MatrixName, &Self::MatrixType)> {
+        // Higher layers go in last, so they overwrite the lower layers that they shadow
+        let entries: BTreeMap<_, _> = self.layers.iter().flat_map(MatrixMap::iter).collect();
+        entries.into_iter()
+    }
+
+    fn is_defined(&self, name: &MatrixName) -> bool {
+        self.layer_of(name).is_some()
+    }
+
+    fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::map::MatrixMap3;
+    use glam::DMat3;
+
+    #[test]
+    fn layered_matrix_map_fall_through() {
+        let [a, b, c, d] = ["A", "B", "C", "D"].map(MatrixName::new);
+        let mut lesson = MatrixMap3::new();
+        lesson.set(a.clone(), DMat3::IDENTITY).unwrap();
+        lesson.set(b.clone(), DMat3::IDENTITY * 2.).unwrap();
+
+        let mut map = LayeredMatrixMap::new();
+        map.set(c.clone(), DMat3::IDENTITY * 3.).unwrap();
+        map.insert_layer(0, lesson.clone());
+        map.set(a.clone(), DMat3::ZERO).unwrap();
+
+        assert_eq!(map.names(), vec![a.clone(), b.clone(), c.clone()]);
+        assert_eq!(map.len(), 3);
+        assert_eq!(
+            map.iter().next(),
+            Some((&a, &DMat3::ZERO)),
+            "The top layer should shadow the lower layers"
+        );
+        assert_eq!(map.layer_of(&a), Some(1));
+        assert_eq!(map.layer_of(&b), Some(0));
+        assert_eq!(map.layer_of(&d), None);
+        assert_eq!(map.get(&d), Err(MatrixMapError::NameNotDefined(d.clone())));
+        assert_eq!(map.get(&MatrixName::new("I")), Ok(DMat3::IDENTITY));
+
+        // Lower layers can't be changed through the layered map
+        assert_eq!(map.remove(&b), Err(MatrixMapError::ReadOnlyName(b.clone())));
+        assert_eq!(
+            map.rename(&b, d.clone(), false),
+            Err(MatrixMapError::ReadOnlyName(b.clone()))
+        );
+        assert_eq!(
+            map.rename(&c, b.clone(), false),
+            Err(MatrixMapError::NameAlreadyDefined(b.clone()))
+        );
+        map.rename(&c, b.clone(), true).unwrap();
+        assert_eq!(map.get(&b), Ok(DMat3::IDENTITY * 3.));
+        assert_eq!(map.layers()[0], lesson);
+
+        let generation = map.generation();
+        map.clear();
+        assert!(map.generation() > generation);
+        assert_eq!(map.names(), vec![a.clone(), b.clone()]);
+        assert_eq!(map.get(&a), Ok(DMat3::IDENTITY));
+
+        let generation = map.generation();
+        map.layer_mut(0).unwrap().remove(&b).unwrap();
+        assert!(map.generation() > generation);
+
+        // Failed changes don't change the generation, and the map never shares a generation
+        // with its top layer, even though their contents differ
+        let generation = map.generation();
+        assert!(map.remove(&d).is_err());
+        assert_eq!(map.generation(), generation);
+        map.set(c.clone(), DMat3::ZERO).unwrap();
+        assert_ne!(map.generation(), map.top().generation());
+        map.remove(&c).unwrap();
+        let generation = map.generation();
+        map.push_layer(MatrixMap3::new());
+        assert!(map.generation() > generation);
+
+        assert_eq!(map.layers().len(), 3);
+        assert!(map.remove_layer(3).is_none());
+        assert!(map.remove_layer(0).is_some());
+        assert!(map.remove_layer(1).is_some());
+        assert!(map.remove_layer(0).is_none());
+        assert!(map.top().is_empty());
+    }
+}
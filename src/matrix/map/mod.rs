@@ -0,0 +1,1399 @@
+//! This module handles and provides the [`MatrixMap`] trait and its primary implementors,
+//! [`MatrixMap2`], [`MatrixMap3`], and [`MatrixMapN`].
+//!
+//! Every [`MatrixMapHashMap`] has the read-only [built-in matrices](BUILTIN_MATRIX_NAMES) `I` and
+//! `Zero`, so expressions like `I * A` work without defining anything first.
+//!
+//! With the `persistence` feature, maps can be saved to and loaded from JSON or RON files with
+//! [`MatrixMapHashMap::save_to`] and [`MatrixMapHashMap::load_from`].
+//!
+//! The [`definitions`] submodule provides [`DefinedMatrixMap`], which can also hold matrices
+//! defined by expressions, like `C := A * B`, and the [`history`] submodule provides
+//! [`MatrixMapHistory`], which can undo and redo changes. The [`shared`] submodule provides
+//! [`SharedMatrixMap`], which can be shared between threads, and the [`layered`] submodule
+//! provides [`LayeredMatrixMap`], which stacks maps on top of each other. The [`scalar`]
+//! submodule provides
+//! [`ScalarMap`] for scalar variables like `x`, and [`Environment`], which evaluates expressions
+//! with both matrices and scalars.
+//!
+//! Maps can be compared with [`MatrixMap::diff`] and combined with [`MatrixMap::merge`], using the
+//! types in the [`diff`] submodule.
+
+use super::{MatrixName, MatrixValue, ScalarName};
+use crate::math::{RandomDistribution, RandomMatrix};
+use glam::{DMat2, DMat3};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use thiserror::Error;
+
+pub mod definitions;
+pub mod diff;
+pub mod history;
+pub mod layered;
+pub mod scalar;
+pub mod shared;
+
+pub use self::{
+    definitions::{DefinedMatrixMap, DefinitionError},
+    diff::{MatrixMapDiff, MergePolicy},
+    history::MatrixMapHistory,
+    layered::LayeredMatrixMap,
+    scalar::{Environment, ScalarMap, ScalarMapError},
+    shared::SharedMatrixMap,
+};
+
+#[cfg(feature = "persistence")]
+use std::path::{Path, PathBuf};
+
+/// All the stuff you want from this module.
+pub mod prelude {
+    pub use super::{
+        BuiltinMatrices, DefinedMatrixMap, Environment, LayeredMatrixMap, MatrixMap, MatrixMap2,
+        MatrixMap3, MatrixMapDiff, MatrixMapError, MatrixMapHistory, MatrixMapN, MatrixMapSnapshot,
+        MatrixMetadata, MergePolicy, ScalarMap,
+    };
+}
+
+/// An error which can be returned by a method of [`MatrixMap`].
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum MatrixMapError {
+    /// The matrix has an invalide name. See [`MatrixName`].
+    #[error("Invalid name for matrix: \"{0}\"")]
+    InvalidName(smol_str::SmolStr),
+
+    /// The matrix with this name is not defined in the map.
+    #[error("Matrix named \"{0}\" is not defined")]
+    NameNotDefined(MatrixName),
+
+    /// A matrix with this name is already defined in the map.
+    #[error("Matrix named \"{0}\" is already defined")]
+    NameAlreadyDefined(MatrixName),
+
+    /// The matrix with this name is built in, so it can't be changed. See
+    /// [`BUILTIN_MATRIX_NAMES`].
+    #[error("Matrix named \"{0}\" is built in and can't be changed")]
+    ReadOnlyName(MatrixName),
+
+    /// The matrix with this name is defined by an expression which can't currently be
+    /// evaluated. See [`DefinedMatrixMap::error`].
+    #[error("The definition of matrix \"{0}\" failed to evaluate")]
+    DefinitionFailed(MatrixName),
+
+    /// Defining or renaming a matrix would make a definition depend on itself. This holds the
+    /// names around the cycle, starting and ending with the same name.
+    #[error("Cyclic definition: {}", format_cycle(.0))]
+    CyclicDefinition(Vec<MatrixName>),
+
+    /// Merging two maps with [`MergePolicy::Fail`] found these names defined in both maps with
+    /// different values.
+    #[error("Merge conflict on matrices: {}", format_names(.0))]
+    MergeConflict(Vec<MatrixName>),
+}
+
+/// Format the names around a cycle like `A -> B -> A`.
+fn format_cycle(cycle: &[MatrixName]) -> String {
+    cycle
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// Format a list of names like `A, B, C`.
+fn format_names(names: &[MatrixName]) -> String {
+    names
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A map from names to defined matrices.
+pub trait MatrixMap {
+    /// The type of matrix that this map holds.
+    type MatrixType: Into<MatrixValue>;
+
+    /// Create a new, empty matrix map.
+    fn new() -> Self;
+
+    /// Set the value of the matrix with the given name.
+    ///
+    /// This method will blindly overwrite the old value if a matrix with this name already exists.
+    /// Use [`MatrixMap::get`] first to check.
+    fn set(&mut self, name: MatrixName, value: Self::MatrixType) -> Result<(), MatrixMapError>;
+
+    /// Get the named matrix from the map, if it exists.
+    fn get(&self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError>;
+
+    /// Get the value of the named scalar variable, if it exists.
+    ///
+    /// Plain matrix maps don't hold any scalars, so by default this always fails. Use an
+    /// [`Environment`] to evaluate expressions with scalar variables.
+    fn scalar(&self, name: &ScalarName) -> Result<f64, ScalarMapError> {
+        Err(ScalarMapError::NameNotDefined(name.clone()))
+    }
+
+    /// Get the named matrix from the map, or set it to the result of `default` first if it's not
+    /// defined, so that a matrix can be lazily created in a single call.
+    ///
+    /// ```
+    /// # use trinity::matrix::{map::prelude::*, MatrixName};
+    /// # use glam::DMat2;
+    /// let mut map = MatrixMap2::new();
+    /// let a = MatrixName::new("A");
+    /// assert_eq!(map.get_or_insert_with(a.clone(), || DMat2::IDENTITY), Ok(DMat2::IDENTITY));
+    /// assert_eq!(map.get_or_insert_with(a.clone(), || DMat2::ZERO), Ok(DMat2::IDENTITY));
+    /// assert_eq!(map.get(&a), Ok(DMat2::IDENTITY));
+    /// ```
+    fn get_or_insert_with(
+        &mut self,
+        name: MatrixName,
+        default: impl FnOnce() -> Self::MatrixType,
+    ) -> Result<Self::MatrixType, MatrixMapError> {
+        match self.get(&name) {
+            Err(MatrixMapError::NameNotDefined(_)) => {
+                self.set(name.clone(), default())?;
+                self.get(&name)
+            }
+            result => result,
+        }
+    }
+
+    /// Remove the named matrix from the map, returning its old value.
+    fn remove(&mut self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError>;
+
+    /// Remove every matrix from the map.
+    fn clear(&mut self);
+
+    /// Move the matrix named `from` to the name `to`.
+    ///
+    /// If a matrix named `to` already exists, then this fails unless `overwrite` is true. Either
+    /// way, the map is left unchanged if this returns an error. Renaming a matrix to its own name
+    /// does nothing.
+    fn rename(
+        &mut self,
+        from: &MatrixName,
+        to: MatrixName,
+        overwrite: bool,
+    ) -> Result<(), MatrixMapError>;
+
+    /// Iterate over the names and values of every matrix in the map, sorted by name.
+    fn iter(&self) -> impl Iterator<Item = (&MatrixName, &Self::MatrixType)>;
+
+    /// Is a matrix with this name in the map? Like [`iter`](Self::iter), this doesn't count the
+    /// builtin matrices.
+    ///
+    /// By default, this looks through [`iter`](Self::iter), but maps which can look up a name
+    /// directly should do that instead.
+    fn is_defined(&self, name: &MatrixName) -> bool {
+        self.iter().any(|(defined, _)| defined == name)
+    }
+
+    /// Get the names of every matrix in the map, sorted alphabetically.
+    fn names(&self) -> Vec<MatrixName> {
+        self.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Get the number of matrices in the map.
+    fn len(&self) -> usize;
+
+    /// Is the map empty?
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Compare this map to another one, finding the names which were added, removed, and changed
+    /// to get from this map to `other`.
+    ///
+    /// ```
+    /// # use trinity::matrix::{map::prelude::*, MatrixName};
+    /// # use glam::DMat2;
+    /// let [a, b, c] = ["A", "B", "C"].map(MatrixName::new);
+    /// let mut old = MatrixMap2::new();
+    /// old.set(a.clone(), DMat2::IDENTITY).unwrap();
+    /// old.set(b.clone(), DMat2::IDENTITY).unwrap();
+    ///
+    /// let mut new = old.clone();
+    /// new.set(b.clone(), DMat2::ZERO).unwrap();
+    /// new.rename(&a, c.clone(), false).unwrap();
+    ///
+    /// let diff = old.diff(&new);
+    /// assert_eq!(diff.added, [c].into());
+    /// assert_eq!(diff.removed, [a].into());
+    /// assert_eq!(diff.changed, [b].into());
+    /// ```
+    fn diff(&self, other: &impl MatrixMap<MatrixType = Self::MatrixType>) -> MatrixMapDiff
+    where
+        Self::MatrixType: PartialEq,
+    {
+        let mut diff = MatrixMapDiff::default();
+        for (name, value) in self.iter() {
+            match other.get(name) {
+                Ok(other_value) if other_value == *value => {}
+                Ok(_) => {
+                    diff.changed.insert(name.clone());
+                }
+                Err(_) => {
+                    diff.removed.insert(name.clone());
+                }
+            }
+        }
+        for (name, _) in other.iter() {
+            if self.get(name).is_err() {
+                diff.added.insert(name.clone());
+            }
+        }
+        diff
+    }
+
+    /// Copy every matrix from `other` into this map, using the policy to decide what to do with
+    /// names which both maps define with different values. Nothing is ever removed from this map.
+    ///
+    /// This returns the [diff](Self::diff) of the changes made to this map. With
+    /// [`MergePolicy::Fail`], any conflicts give [`MatrixMapError::MergeConflict`] and the map is
+    /// left unchanged.
+    fn merge(
+        &mut self,
+        other: &impl MatrixMap<MatrixType = Self::MatrixType>,
+        policy: MergePolicy,
+    ) -> Result<MatrixMapDiff, MatrixMapError>
+    where
+        Self::MatrixType: Clone + PartialEq,
+    {
+        let mut diff = self.diff(other);
+        diff.removed.clear();
+
+        match policy {
+            MergePolicy::KeepOurs => diff.changed.clear(),
+            MergePolicy::TakeTheirs => {}
+            MergePolicy::Fail if diff.changed.is_empty() => {}
+            MergePolicy::Fail => {
+                return Err(MatrixMapError::MergeConflict(
+                    diff.changed.into_iter().collect(),
+                ))
+            }
+        }
+
+        for (name, value) in other.iter() {
+            if diff.added.contains(name) || diff.changed.contains(name) {
+                self.set(name.clone(), value.clone())?;
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Fill the map with `count` random matrices from the distribution, named `R1`, `R2`, and so
+    /// on, skipping any names which are already defined. This returns the names of the new
+    /// matrices.
+    ///
+    /// The same seed always gives the same matrices, so demos and tests can be reproduced.
+    ///
+    /// ```
+    /// # use trinity::{math::RandomDistribution, matrix::{map::prelude::*, MatrixName}};
+    /// # use glam::DMat2;
+    /// let mut map = MatrixMap2::new();
+    /// map.set(MatrixName::new("R2"), DMat2::IDENTITY).unwrap();
+    ///
+    /// let names = map.populate_random(2, RandomDistribution::Integer, 42).unwrap();
+    /// assert_eq!(names, vec![MatrixName::new("R1"), MatrixName::new("R3")]);
+    /// assert_eq!(map.get(&MatrixName::new("R2")), Ok(DMat2::IDENTITY));
+    /// ```
+    fn populate_random(
+        &mut self,
+        count: usize,
+        distribution: RandomDistribution,
+        rng_seed: u64,
+    ) -> Result<Vec<MatrixName>, MatrixMapError>
+    where
+        Self::MatrixType: RandomMatrix,
+    {
+        let mut rng = StdRng::seed_from_u64(rng_seed);
+        let mut names = Vec::with_capacity(count);
+
+        for index in 1.. {
+            if names.len() == count {
+                break;
+            }
+
+            let name = MatrixName::new(&format!("R{index}"));
+            if let Err(MatrixMapError::NameNotDefined(_)) = self.get(&name) {
+                self.set(name.clone(), RandomMatrix::random(distribution, &mut rng))?;
+                names.push(name);
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// Get the generation of this map, which changes whenever the contents of the map change.
+    ///
+    /// Two maps with the same generation are guaranteed to have the same contents, so anything
+    /// computed from a map can be cached until its generation changes. Generations only ever
+    /// increase, so a map always has a larger generation after a change than before it. Use a
+    /// [`ChangeTracker`] to check for changes.
+    fn generation(&self) -> u64;
+}
+
+/// Tracks the [generation](MatrixMap::generation) of a map, to tell when it has changed since it
+/// was last checked, so that work like evaluating and rendering expressions can be redone only
+/// when it needs to be.
+///
+/// ```
+/// # use trinity::matrix::{map::{prelude::*, ChangeTracker}, MatrixName};
+/// # use glam::DMat2;
+/// let mut map = MatrixMap2::new();
+/// let mut tracker = ChangeTracker::new();
+/// assert!(tracker.has_changed(&map));
+/// assert!(!tracker.has_changed(&map));
+///
+/// map.set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+/// assert!(tracker.has_changed(&map));
+/// assert!(!tracker.has_changed(&map));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChangeTracker {
+    /// The generation of the map when it was last checked, or `None` if it's never been checked.
+    last_seen: Option<u64>,
+}
+
+impl ChangeTracker {
+    /// Create a new tracker, which will report a change the first time it checks a map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Has the map changed since the last time this tracker checked it?
+    pub fn has_changed(&mut self, map: &impl MatrixMap) -> bool {
+        let generation = map.generation();
+        self.last_seen.replace(generation) != Some(generation)
+    }
+}
+
+/// The next unused map generation. See [`MatrixMap::generation`].
+///
+/// This is shared between all maps, so that no two maps ever have the same generation unless
+/// one is a clone of the other.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Get a new, unique map generation.
+fn next_generation() -> u64 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The names of the read-only matrices built into every [`MatrixMapHashMap`]: `I` is the
+/// identity and `Zero` is the zero matrix.
+///
+/// These can't be set, removed, or renamed, and they aren't included in
+/// [`MatrixMap::iter`] or [`MatrixMap::len`].
+pub const BUILTIN_MATRIX_NAMES: [&str; 2] = ["I", "Zero"];
+
+/// Is this the name of a read-only built-in matrix? See [`BUILTIN_MATRIX_NAMES`].
+pub fn is_builtin(name: &MatrixName) -> bool {
+    BUILTIN_MATRIX_NAMES.contains(&name.name.as_str())
+}
+
+/// A type of matrix which can be stored in a [`MatrixMapHashMap`], with values for the
+/// [built-in matrices](BUILTIN_MATRIX_NAMES).
+pub trait BuiltinMatrices: Sized {
+    /// Get the value of the built-in matrix with this name, or `None` if it isn't built in or
+    /// this type has no value for it.
+    fn builtin(name: &MatrixName) -> Option<Self>;
+}
+
+impl BuiltinMatrices for DMat2 {
+    fn builtin(name: &MatrixName) -> Option<Self> {
+        match name.name.as_str() {
+            "I" => Some(Self::IDENTITY),
+            "Zero" => Some(Self::ZERO),
+            _ => None,
+        }
+    }
+}
+
+impl BuiltinMatrices for DMat3 {
+    fn builtin(name: &MatrixName) -> Option<Self> {
+        match name.name.as_str() {
+            "I" => Some(Self::IDENTITY),
+            "Zero" => Some(Self::ZERO),
+            _ => None,
+        }
+    }
+}
+
+/// A [`MatrixValue`] has no fixed dimension, so there's no single identity or zero matrix. The
+/// built-in names are still read-only in a [`MatrixMapN`], but they're never defined.
+impl BuiltinMatrices for MatrixValue {
+    fn builtin(_name: &MatrixName) -> Option<Self> {
+        None
+    }
+}
+
+/// Extra information about a matrix in a [`MatrixMapHashMap`], which doesn't affect its value
+/// but says how to show it. See [`MatrixMapHashMap::set_metadata`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct MatrixMetadata {
+    /// The sRGB colour to draw this matrix's visualisation in, or `None` to choose one
+    /// automatically.
+    pub colour: Option<[u8; 3]>,
+
+    /// A description of the matrix, like for a tooltip.
+    pub description: Option<String>,
+
+    /// Whether the matrix is pinned, like to the top of a list.
+    pub pinned: bool,
+}
+
+/// A [`MatrixMap`] for some generic type `T`.
+#[derive(Clone, Debug)]
+pub struct MatrixMapHashMap<T: Into<MatrixValue> + Clone> {
+    /// The [`HashMap`] backing this implementation.
+    map: HashMap<MatrixName, T>,
+
+    /// The metadata of the matrices that have any. Every name in here is also in `map`.
+    metadata: HashMap<MatrixName, MatrixMetadata>,
+
+    /// The generation of this map. See [`MatrixMap::generation`].
+    generation: u64,
+}
+
+impl<T: Into<MatrixValue> + Clone + PartialEq> PartialEq for MatrixMapHashMap<T> {
+    fn eq(&self, other: &Self) -> bool {
+        // Maps with the same contents are equal, even if they got there in different ways
+        self.map == other.map && self.metadata == other.metadata
+    }
+}
+
+/// A [`MatrixMap`] for 2D matrices.
+pub type MatrixMap2 = MatrixMapHashMap<DMat2>;
+
+/// A [`MatrixMap`] for 3D matrices.
+pub type MatrixMap3 = MatrixMapHashMap<DMat3>;
+
+/// A [`MatrixMap`] for matrices of any dimension, including ones larger than 3×3.
+///
+/// The matrices don't all have to have the same dimension, so this is also the map to use for a
+/// workspace with both 2D and 3D matrices. Mixing dimensions in an expression, like `A + B` where
+/// `A` is 2D and `B` is 3D, fails when it's evaluated with an error like
+/// [`CannotAddDifferentDimensions`](super::expression::ast::EvaluationError::CannotAddDifferentDimensions).
+pub type MatrixMapN = MatrixMapHashMap<MatrixValue>;
+
+impl<T: Into<MatrixValue> + Clone + BuiltinMatrices> MatrixMap for MatrixMapHashMap<T> {
+    type MatrixType = T;
+
+    fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            metadata: HashMap::new(),
+            generation: next_generation(),
+        }
+    }
+
+    fn set(&mut self, name: MatrixName, value: Self::MatrixType) -> Result<(), MatrixMapError> {
+        if is_builtin(&name) {
+            Err(MatrixMapError::ReadOnlyName(name))
+        } else if name.self_is_valid() {
+            self.map.insert(name, value);
+            self.generation = next_generation();
+            Ok(())
+        } else {
+            Err(MatrixMapError::InvalidName(name.name))
+        }
+    }
+
+    fn get(&self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        if MatrixName::is_valid(name.name.as_str()) {
+            match self.map.get(name) {
+                Some(matrix) => Ok(matrix.clone()),
+                None => {
+                    T::builtin(name).ok_or_else(|| MatrixMapError::NameNotDefined(name.to_owned()))
+                }
+            }
+        } else {
+            Err(MatrixMapError::InvalidName(name.name.clone()))
+        }
+    }
+
+    fn remove(&mut self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        if is_builtin(name) {
+            Err(MatrixMapError::ReadOnlyName(name.to_owned()))
+        } else if MatrixName::is_valid(name.name.as_str()) {
+            match self.map.remove(name) {
+                Some(matrix) => {
+                    self.metadata.remove(name);
+                    self.generation = next_generation();
+                    Ok(matrix)
+                }
+                None => Err(MatrixMapError::NameNotDefined(name.to_owned())),
+            }
+        } else {
+            Err(MatrixMapError::InvalidName(name.name.clone()))
+        }
+    }
+
+    fn rename(
+        &mut self,
+        from: &MatrixName,
+        to: MatrixName,
+        overwrite: bool,
+    ) -> Result<(), MatrixMapError> {
+        if !MatrixName::is_valid(from.name.as_str()) {
+            return Err(MatrixMapError::InvalidName(from.name.clone()));
+        }
+        if !to.self_is_valid() {
+            return Err(MatrixMapError::InvalidName(to.name));
+        }
+        if is_builtin(from) {
+            return Err(MatrixMapError::ReadOnlyName(from.to_owned()));
+        }
+        if is_builtin(&to) {
+            return Err(MatrixMapError::ReadOnlyName(to));
+        }
+        if !self.map.contains_key(from) {
+            return Err(MatrixMapError::NameNotDefined(from.to_owned()));
+        }
+        if *from == to {
+            return Ok(());
+        }
+        if !overwrite && self.map.contains_key(&to) {
+            return Err(MatrixMapError::NameAlreadyDefined(to));
+        }
+
+        if let Some(matrix) = self.map.remove(from) {
+            match self.metadata.remove(from) {
+                Some(metadata) => self.metadata.insert(to.clone(), metadata),
+                None => self.metadata.remove(&to),
+            };
+            self.map.insert(to, matrix);
+            self.generation = next_generation();
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        if !self.map.is_empty() {
+            self.map.clear();
+            self.metadata.clear();
+            self.generation = next_generation();
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&MatrixName, &Self::MatrixType)> {
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_unstable_by_key(|(name, _)| *name);
+        entries.into_iter()
+    }
+
+    fn is_defined(&self, name: &MatrixName) -> bool {
+        self.map.contains_key(name)
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl<T: Into<MatrixValue> + Clone + BuiltinMatrices> MatrixMapHashMap<T> {
+    /// Get the metadata of the named matrix, or `None` if it has none.
+    pub fn metadata(&self, name: &MatrixName) -> Option<&MatrixMetadata> {
+        self.metadata.get(name)
+    }
+
+    /// Set the metadata of the named matrix, which must already be defined.
+    ///
+    /// The metadata stays with the matrix when its value is set or it's renamed, and it's removed
+    /// along with the matrix.
+    pub fn set_metadata(
+        &mut self,
+        name: &MatrixName,
+        metadata: MatrixMetadata,
+    ) -> Result<(), MatrixMapError> {
+        if is_builtin(name) {
+            return Err(MatrixMapError::ReadOnlyName(name.to_owned()));
+        }
+        if !self.map.contains_key(name) {
+            return Err(MatrixMapError::NameNotDefined(name.to_owned()));
+        }
+
+        self.metadata.insert(name.to_owned(), metadata);
+        self.generation = next_generation();
+        Ok(())
+    }
+
+    /// Take a snapshot of every matrix in the map and its metadata, which can be put back later
+    /// with [`restore`](Self::restore).
+    ///
+    /// ```
+    /// # use trinity::matrix::{map::prelude::*, MatrixName};
+    /// # use glam::DMat2;
+    /// let mut map = MatrixMap2::new();
+    /// map.set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+    ///
+    /// let checkpoint = map.snapshot();
+    /// map.set(MatrixName::new("A"), DMat2::ZERO).unwrap();
+    /// map.set(MatrixName::new("B"), DMat2::ZERO).unwrap();
+    ///
+    /// map.restore(checkpoint);
+    /// assert_eq!(map.names(), [MatrixName::new("A")]);
+    /// assert_eq!(map.get(&MatrixName::new("A")), Ok(DMat2::IDENTITY));
+    /// ```
+    pub fn snapshot(&self) -> MatrixMapSnapshot<T> {
+        MatrixMapSnapshot {
+            map: self.map.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// Replace the contents of the map with a [snapshot](Self::snapshot).
+    ///
+    /// This counts as a change to the map, so its [generation](MatrixMap::generation) changes.
+    pub fn restore(&mut self, snapshot: MatrixMapSnapshot<T>) {
+        self.map = snapshot.map;
+        self.metadata = snapshot.metadata;
+        self.generation = next_generation();
+    }
+}
+
+/// The contents of a [`MatrixMapHashMap`] at some point, from [`MatrixMapHashMap::snapshot`].
+///
+/// Matrices are small, so this is just a copy. With the `serde` feature, snapshots are
+/// serialized in the same format as maps, so a lesson can keep named checkpoints in a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatrixMapSnapshot<T> {
+    /// The matrices.
+    map: HashMap<MatrixName, T>,
+
+    /// The metadata of the matrices that have any.
+    metadata: HashMap<MatrixName, MatrixMetadata>,
+}
+
+/// The version of the format that a [`MatrixMapHashMap`] is serialized in.
+///
+/// Every serialized map records this version alongside its matrices. Deserializing a map from a
+/// newer version fails, but unknown fields are ignored, so later versions can add fields (like
+/// metadata) that older versions will skip over.
+#[cfg(feature = "serde")]
+pub const MATRIX_MAP_FORMAT_VERSION: u32 = 1;
+
+/// The borrowed form of a serialized map or [snapshot](MatrixMapSnapshot).
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct VersionedRef<'a, T> {
+    /// The format version.
+    version: u32,
+    /// The matrices.
+    matrices: std::collections::BTreeMap<&'a MatrixName, &'a T>,
+    /// The metadata, which is left out if there isn't any.
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    metadata: std::collections::BTreeMap<&'a MatrixName, &'a MatrixMetadata>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T> VersionedRef<'a, T> {
+    /// Borrow these matrices and their metadata in the current format version.
+    fn new(
+        matrices: &'a HashMap<MatrixName, T>,
+        metadata: &'a HashMap<MatrixName, MatrixMetadata>,
+    ) -> Self {
+        Self {
+            version: MATRIX_MAP_FORMAT_VERSION,
+            matrices: matrices.iter().collect(),
+            metadata: metadata.iter().collect(),
+        }
+    }
+}
+
+/// The owned form of a serialized map or [snapshot](MatrixMapSnapshot).
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Versioned<T> {
+    /// The format version.
+    version: u32,
+    /// The matrices.
+    matrices: HashMap<MatrixName, T>,
+    /// The metadata, which is empty if it's missing.
+    #[serde(default)]
+    metadata: HashMap<MatrixName, MatrixMetadata>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> Versioned<T> {
+    /// Check that the version is supported and that no built-in matrices are defined, and return
+    /// the matrices and their metadata.
+    fn validate<E: serde::de::Error>(self) -> Result<MatrixMapSnapshot<T>, E> {
+        let Self {
+            version,
+            matrices,
+            mut metadata,
+        } = self;
+        if version > MATRIX_MAP_FORMAT_VERSION {
+            return Err(E::custom(format!(
+                "unsupported matrix map format version {version}, expected at most \
+                 {MATRIX_MAP_FORMAT_VERSION}"
+            )));
+        }
+        if let Some(name) = matrices.keys().find(|name| is_builtin(name)) {
+            return Err(E::custom(format!(
+                "matrix \"{name}\" is built in and can't be loaded"
+            )));
+        }
+
+        // Metadata for a matrix which doesn't exist is meaningless, so just drop it
+        metadata.retain(|name, _| matrices.contains_key(name));
+        Ok(MatrixMapSnapshot {
+            map: matrices,
+            metadata,
+        })
+    }
+}
+
+/// Maps are serialized as their [format version](MATRIX_MAP_FORMAT_VERSION), their matrices, and
+/// the metadata of the matrices that have any, sorted by name.
+#[cfg(feature = "serde")]
+impl<T: Into<MatrixValue> + Clone + serde::Serialize> serde::Serialize for MatrixMapHashMap<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        VersionedRef::new(&self.map, &self.metadata).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for MatrixMapHashMap<T>
+where
+    T: Into<MatrixValue> + Clone + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let MatrixMapSnapshot { map, metadata } =
+            Versioned::deserialize(deserializer)?.validate()?;
+        Ok(Self {
+            map,
+            metadata,
+            generation: next_generation(),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for MatrixMapSnapshot<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        VersionedRef::new(&self.map, &self.metadata).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for MatrixMapSnapshot<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Versioned::deserialize(deserializer)?.validate()
+    }
+}
+
+/// An error which can be returned by [`MatrixMapHashMap::save_to`] or
+/// [`MatrixMapHashMap::load_from`].
+#[cfg(feature = "persistence")]
+#[derive(Debug, Error)]
+pub enum PersistenceError {
+    /// The file couldn't be read or written.
+    #[error("Could not access the file: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The file extension wasn't `.json` or `.ron`.
+    #[error("Unknown file format for {0:?}, expected a .json or .ron file")]
+    UnknownFormat(PathBuf),
+
+    /// The map couldn't be converted to or from JSON.
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The map couldn't be converted to RON.
+    #[error("Could not write RON: {0}")]
+    RonWrite(#[from] ron::Error),
+
+    /// The map couldn't be converted from RON.
+    #[error("Invalid RON: {0}")]
+    RonRead(#[from] ron::error::SpannedError),
+}
+
+/// A file format that a map can be saved in, chosen by the file extension.
+#[cfg(feature = "persistence")]
+enum FileFormat {
+    /// JSON, in a `.json` file.
+    Json,
+
+    /// RON, in a `.ron` file.
+    Ron,
+}
+
+#[cfg(feature = "persistence")]
+impl FileFormat {
+    /// Get the format of the file at this path from its extension.
+    fn of(path: &Path) -> Result<Self, PersistenceError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(Self::Json),
+            Some("ron") => Ok(Self::Ron),
+            _ => Err(PersistenceError::UnknownFormat(path.to_owned())),
+        }
+    }
+}
+
+#[cfg(feature = "persistence")]
+impl<T> MatrixMapHashMap<T>
+where
+    T: Into<MatrixValue> + Clone + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Save this map to a file, as JSON or RON depending on whether the path ends in `.json` or
+    /// `.ron`. See [`MATRIX_MAP_FORMAT_VERSION`].
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), PersistenceError> {
+        let path = path.as_ref();
+        let contents = match FileFormat::of(path)? {
+            FileFormat::Json => serde_json::to_string_pretty(self)?,
+            FileFormat::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Load a map from a file saved with [`save_to`](Self::save_to).
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, PersistenceError> {
+        let path = path.as_ref();
+        let format = FileFormat::of(path)?;
+        let contents = std::fs::read_to_string(path)?;
+        Ok(match format {
+            FileFormat::Json => serde_json::from_str(&contents)?,
+            FileFormat::Ron => ron::from_str(&contents)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrix_map_set_get() {
+        let mut map2 = MatrixMap2::new();
+        let mut map3 = MatrixMap3::new();
+
+        let m1 = rand::random::<DMat2>();
+        let m2 = rand::random::<DMat2>();
+        let n1 = rand::random::<DMat3>();
+        let n2 = rand::random::<DMat3>();
+
+        let m1name = MatrixName::new("M_one");
+        let m2name = MatrixName::new("M_two");
+        let n1name = MatrixName::new("N_one");
+        let n2name = MatrixName::new("N_two");
+
+        assert_eq!(map2.set(m1name.clone(), m1), Ok(()));
+        assert_eq!(map2.set(m2name.clone(), m2), Ok(()));
+        assert_eq!(map3.set(n1name.clone(), n1), Ok(()));
+        assert_eq!(map3.set(n2name.clone(), n2), Ok(()));
+
+        assert_eq!(
+            map2.set(MatrixName { name: "m".into() }, m1),
+            Err(MatrixMapError::InvalidName("m".into()))
+        );
+        assert_eq!(
+            map3.set(MatrixName { name: "x".into() }, n1),
+            Err(MatrixMapError::InvalidName("x".into()))
+        );
+
+        assert_eq!(map2.get(&m1name), Ok(m1));
+        assert_eq!(map2.get(&m2name), Ok(m2));
+        assert_eq!(map3.get(&n1name), Ok(n1));
+        assert_eq!(map3.get(&n2name), Ok(n2));
+
+        assert_eq!(
+            map2.get(&MatrixName::new("X")),
+            Err(MatrixMapError::NameNotDefined(MatrixName::new("X")))
+        );
+        assert_eq!(
+            map2.get(&MatrixName { name: "y".into() }),
+            Err(MatrixMapError::InvalidName("y".into()))
+        );
+        assert_eq!(
+            map3.get(&MatrixName::new("X")),
+            Err(MatrixMapError::NameNotDefined(MatrixName::new("X")))
+        );
+        assert_eq!(
+            map3.get(&MatrixName { name: "y".into() }),
+            Err(MatrixMapError::InvalidName("y".into()))
+        );
+    }
+
+    #[test]
+    fn matrix_map_remove_clear() {
+        let mut map = MatrixMap3::new();
+        let a = rand::random::<DMat3>();
+        map.set(MatrixName::new("A"), a).unwrap();
+        map.set(MatrixName::new("B"), DMat3::IDENTITY).unwrap();
+
+        let generation = map.generation();
+        assert_eq!(map.remove(&MatrixName::new("A")), Ok(a));
+        assert_ne!(map.generation(), generation);
+        assert_eq!(
+            map.get(&MatrixName::new("A")),
+            Err(MatrixMapError::NameNotDefined(MatrixName::new("A")))
+        );
+        assert_eq!(map.get(&MatrixName::new("B")), Ok(DMat3::IDENTITY));
+
+        let generation = map.generation();
+        assert_eq!(
+            map.remove(&MatrixName::new("A")),
+            Err(MatrixMapError::NameNotDefined(MatrixName::new("A")))
+        );
+        assert_eq!(
+            map.remove(&MatrixName { name: "a".into() }),
+            Err(MatrixMapError::InvalidName("a".into()))
+        );
+        assert_eq!(map.generation(), generation);
+
+        map.clear();
+        assert_ne!(map.generation(), generation);
+        assert_eq!(map, MatrixMap3::new());
+        assert_eq!(
+            map.get(&MatrixName::new("B")),
+            Err(MatrixMapError::NameNotDefined(MatrixName::new("B")))
+        );
+
+        let generation = map.generation();
+        map.clear();
+        assert_eq!(map.generation(), generation);
+    }
+
+    #[test]
+    fn matrix_map_get_or_insert_with() {
+        let mut map = MatrixMap3::new();
+        let a = MatrixName::new("A");
+
+        let generation = map.generation();
+        assert_eq!(
+            map.get_or_insert_with(a.clone(), || DMat3::IDENTITY),
+            Ok(DMat3::IDENTITY)
+        );
+        assert_ne!(map.generation(), generation);
+
+        // Existing and built-in matrices are left alone, without calling the default
+        let generation = map.generation();
+        let unreachable = || -> DMat3 { panic!("The default shouldn't be needed") };
+        assert_eq!(
+            map.get_or_insert_with(a.clone(), unreachable),
+            Ok(DMat3::IDENTITY)
+        );
+        assert_eq!(
+            map.get_or_insert_with(MatrixName::new("Zero"), unreachable),
+            Ok(DMat3::ZERO)
+        );
+        assert_eq!(
+            map.get_or_insert_with(MatrixName { name: "a".into() }, unreachable),
+            Err(MatrixMapError::InvalidName("a".into()))
+        );
+        assert_eq!(map.generation(), generation);
+    }
+
+    #[test]
+    fn matrix_map_rename() {
+        let mut map = MatrixMap2::new();
+        let a = rand::random::<DMat2>();
+        map.set(MatrixName::new("A"), a).unwrap();
+        map.set(MatrixName::new("B"), DMat2::IDENTITY).unwrap();
+
+        let generation = map.generation();
+        assert_eq!(
+            map.rename(&MatrixName::new("A"), MatrixName::new("B"), false),
+            Err(MatrixMapError::NameAlreadyDefined(MatrixName::new("B")))
+        );
+        assert_eq!(
+            map.rename(&MatrixName::new("X"), MatrixName::new("Y"), true),
+            Err(MatrixMapError::NameNotDefined(MatrixName::new("X")))
+        );
+        assert_eq!(
+            map.rename(&MatrixName::new("A"), MatrixName { name: "a".into() }, true),
+            Err(MatrixMapError::InvalidName("a".into()))
+        );
+        assert_eq!(
+            map.rename(&MatrixName::new("A"), MatrixName::new("A"), false),
+            Ok(())
+        );
+        assert_eq!(map.generation(), generation);
+        assert_eq!(map.names(), [MatrixName::new("A"), MatrixName::new("B")]);
+
+        assert_eq!(
+            map.rename(&MatrixName::new("A"), MatrixName::new("C"), false),
+            Ok(())
+        );
+        assert_ne!(map.generation(), generation);
+        assert_eq!(map.names(), [MatrixName::new("B"), MatrixName::new("C")]);
+        assert_eq!(map.get(&MatrixName::new("C")), Ok(a));
+
+        assert_eq!(
+            map.rename(&MatrixName::new("C"), MatrixName::new("B"), true),
+            Ok(())
+        );
+        assert_eq!(map.names(), [MatrixName::new("B")]);
+        assert_eq!(map.get(&MatrixName::new("B")), Ok(a));
+    }
+
+    #[test]
+    fn matrix_map_iter_names_len() {
+        let mut map = MatrixMapN::new();
+        assert!(map.is_empty());
+        assert_eq!(map.iter().next(), None);
+
+        for name in ["C", "A", "B_two", "B"] {
+            map.set(MatrixName::new(name), MatrixValue::TwoD(DMat2::IDENTITY))
+                .unwrap();
+        }
+        map.set(MatrixName::new("A"), MatrixValue::ThreeD(DMat3::IDENTITY))
+            .unwrap();
+
+        assert_eq!(map.len(), 4);
+        assert!(!map.is_empty());
+        assert_eq!(
+            map.names(),
+            ["A", "B", "B_two", "C"].map(MatrixName::new).to_vec()
+        );
+        assert_eq!(
+            map.iter().next(),
+            Some((&MatrixName::new("A"), &MatrixValue::ThreeD(DMat3::IDENTITY)))
+        );
+        assert!(map
+            .iter()
+            .skip(1)
+            .all(|(_, value)| *value == MatrixValue::TwoD(DMat2::IDENTITY)));
+    }
+
+    #[test]
+    fn matrix_map_diff_merge() {
+        let [a, b, c, d] = ["A", "B", "C", "D"].map(MatrixName::new);
+        let mut ours = MatrixMap2::new();
+        ours.set(a.clone(), DMat2::IDENTITY).unwrap();
+        ours.set(b.clone(), DMat2::IDENTITY).unwrap();
+        ours.set(c.clone(), DMat2::IDENTITY).unwrap();
+
+        let mut theirs = MatrixMap2::new();
+        theirs.set(b.clone(), DMat2::IDENTITY).unwrap();
+        theirs.set(c.clone(), DMat2::ZERO).unwrap();
+        theirs.set(d.clone(), DMat2::ZERO).unwrap();
+
+        assert!(ours.diff(&ours.clone()).is_empty());
+        assert_eq!(
+            ours.diff(&theirs),
+            MatrixMapDiff {
+                added: [d.clone()].into(),
+                removed: [a.clone()].into(),
+                changed: [c.clone()].into(),
+            }
+        );
+        assert_eq!(theirs.diff(&ours).added, [a.clone()].into());
+
+        let generation = ours.generation();
+        assert_eq!(
+            ours.clone().merge(&theirs, MergePolicy::Fail),
+            Err(MatrixMapError::MergeConflict(vec![c.clone()]))
+        );
+        assert_eq!(ours.generation(), generation);
+
+        let mut merged = ours.clone();
+        let diff = merged.merge(&theirs, MergePolicy::KeepOurs).unwrap();
+        assert_eq!(diff.added, [d.clone()].into());
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+        assert_eq!(merged.get(&c), Ok(DMat2::IDENTITY));
+        assert_eq!(merged.get(&d), Ok(DMat2::ZERO));
+
+        let mut merged = ours.clone();
+        let diff = merged.merge(&theirs, MergePolicy::TakeTheirs).unwrap();
+        assert_eq!(diff.changed, [c.clone()].into());
+        assert_eq!(merged.names(), vec![a, b, c.clone(), d]);
+        assert_eq!(merged.get(&c), Ok(DMat2::ZERO));
+        assert!(merged.diff(&theirs).changed.is_empty());
+    }
+
+    #[test]
+    fn matrix_map_populate_random() {
+        let mut map = MatrixMap3::new();
+        let names = map
+            .populate_random(3, RandomDistribution::Orthogonal, 7)
+            .unwrap();
+        assert_eq!(names, ["R1", "R2", "R3"].map(MatrixName::new).to_vec());
+        for name in &names {
+            assert!(map.get(name).unwrap().determinant().abs() > 0.999);
+        }
+
+        let mut same = MatrixMap3::new();
+        same.populate_random(3, RandomDistribution::Orthogonal, 7)
+            .unwrap();
+        assert_eq!(same, map);
+
+        let names = map
+            .populate_random(2, RandomDistribution::Uniform, 7)
+            .unwrap();
+        assert_eq!(names, ["R4", "R5"].map(MatrixName::new).to_vec());
+        assert_eq!(map.len(), 5);
+        assert_eq!(
+            map.populate_random(0, RandomDistribution::Integer, 7),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn matrix_map_metadata() {
+        let [a, b] = ["A", "B"].map(MatrixName::new);
+        let metadata = MatrixMetadata {
+            colour: Some([255, 0, 128]),
+            description: Some("A shear".to_string()),
+            pinned: true,
+        };
+
+        let mut map = MatrixMap2::new();
+        assert_eq!(
+            map.set_metadata(&a, metadata.clone()),
+            Err(MatrixMapError::NameNotDefined(a.clone()))
+        );
+        map.set(a.clone(), DMat2::IDENTITY).unwrap();
+        let generation = map.generation();
+        map.set_metadata(&a, metadata.clone()).unwrap();
+        assert_ne!(map.generation(), generation);
+        assert_eq!(map.metadata(&a), Some(&metadata));
+
+        // Metadata follows the matrix around until it's removed
+        map.set(a.clone(), DMat2::ZERO).unwrap();
+        assert_eq!(map.metadata(&a), Some(&metadata));
+        map.rename(&a, b.clone(), false).unwrap();
+        assert_eq!(map.metadata(&a), None);
+        assert_eq!(map.metadata(&b), Some(&metadata));
+        map.set(a.clone(), DMat2::IDENTITY).unwrap();
+        map.rename(&a, b.clone(), true).unwrap();
+        assert_eq!(map.metadata(&b), None);
+
+        map.set_metadata(&b, metadata).unwrap();
+        map.remove(&b).unwrap();
+        map.set(b.clone(), DMat2::IDENTITY).unwrap();
+        assert_eq!(map.metadata(&b), None);
+    }
+
+    #[test]
+    fn matrix_map_snapshots() {
+        let [a, b] = ["A", "B"].map(MatrixName::new);
+        let mut map = MatrixMap3::new();
+        map.set(a.clone(), DMat3::IDENTITY).unwrap();
+        map.set_metadata(
+            &a,
+            MatrixMetadata {
+                pinned: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let snapshot = map.snapshot();
+        let original = map.clone();
+        map.remove(&a).unwrap();
+        map.set(b.clone(), DMat3::ZERO).unwrap();
+
+        let generation = map.generation();
+        map.restore(snapshot.clone());
+        assert_eq!(map, original);
+        assert!(map.metadata(&a).unwrap().pinned);
+        assert!(map.generation() > generation);
+
+        // Restoring the same snapshot twice works, since it's just a copy
+        map.clear();
+        map.restore(snapshot.clone());
+        assert_eq!(map, original);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&snapshot).unwrap();
+            assert_eq!(json, serde_json::to_string(&original).unwrap());
+            assert_eq!(
+                serde_json::from_str::<MatrixMapSnapshot<DMat3>>(&json).unwrap(),
+                snapshot
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn matrix_map_serde() {
+        let mut map = MatrixMap2::new();
+        map.set(
+            MatrixName::new("B"),
+            DMat2::from_cols_array(&[1., 2., 3., 4.]),
+        )
+        .unwrap();
+        map.set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(
+            json,
+            r#"{"version":1,"matrices":{"A":[1.0,0.0,0.0,1.0],"B":[1.0,2.0,3.0,4.0]}}"#
+        );
+        let loaded: MatrixMap2 = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, map);
+        assert_ne!(loaded.generation(), map.generation());
+
+        assert_eq!(
+            serde_json::from_str::<MatrixMap2>(
+                r#"{"version":1,"comment":"ignored","matrices":{"A":[1.0,0.0,0.0,1.0]}}"#
+            )
+            .unwrap()
+            .names(),
+            [MatrixName::new("A")]
+        );
+        assert!(serde_json::from_str::<MatrixMap2>(r#"{"version":2,"matrices":{}}"#).is_err());
+
+        map.set_metadata(
+            &MatrixName::new("B"),
+            MatrixMetadata {
+                pinned: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let json = serde_json::to_string(&map).unwrap();
+        assert!(
+            json.ends_with(r#""metadata":{"B":{"colour":null,"description":null,"pinned":true}}}"#)
+        );
+        assert_eq!(serde_json::from_str::<MatrixMap2>(&json).unwrap(), map);
+        assert_eq!(
+            serde_json::from_str::<MatrixMap2>(
+                r#"{"version":1,"matrices":{},"metadata":{"A":{"pinned":true}}}"#
+            )
+            .unwrap()
+            .metadata(&MatrixName::new("A")),
+            None
+        );
+        assert!(serde_json::from_str::<MatrixMap2>(
+            r#"{"version":1,"matrices":{"a":[1.0,0.0,0.0,1.0]}}"#
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn matrix_map_save_load() {
+        let mut map = MatrixMapN::new();
+        map.set(MatrixName::new("A"), MatrixValue::TwoD(DMat2::IDENTITY))
+            .unwrap();
+        // RON doesn't always round-trip floats exactly, so use values that it can
+        map.set(
+            MatrixName::new("B"),
+            MatrixValue::ThreeD(DMat3::from_cols_array(&[
+                0.5, -1.25, 3., 0.125, 2., -0.75, 0.0625, 6.5, -4.,
+            ])),
+        )
+        .unwrap();
+
+        let directory = std::env::temp_dir();
+        for extension in ["json", "ron"] {
+            let path = directory.join(format!(
+                "trinity_matrix_map_{}.{extension}",
+                std::process::id()
+            ));
+            map.save_to(&path).unwrap();
+            let loaded = MatrixMapN::load_from(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.unwrap(), map);
+        }
+
+        assert!(matches!(
+            map.save_to(directory.join("matrices.txt")),
+            Err(PersistenceError::UnknownFormat(_))
+        ));
+        assert!(matches!(
+            MatrixMapN::load_from(directory.join("trinity_matrix_map_missing.json")),
+            Err(PersistenceError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn matrix_map_builtins() {
+        use crate::matrix::expression::{ast::NumberOrMatrix, parse_expression_from_string};
+
+        let [a, i, zero] = ["A", "I", "Zero"].map(MatrixName::new);
+        let m = DMat2::from_cols_array(&[1., 2., 3., 4.]);
+
+        let mut map2 = MatrixMap2::new();
+        assert_eq!(map2.get(&i), Ok(DMat2::IDENTITY));
+        assert_eq!(map2.get(&zero), Ok(DMat2::ZERO));
+        assert_eq!(MatrixMap3::new().get(&i), Ok(DMat3::IDENTITY));
+        assert!(map2.is_empty());
+
+        map2.set(a.clone(), m).unwrap();
+        assert_eq!(
+            parse_expression_from_string("I * A + Zero")
+                .unwrap()
+                .evaluate(&map2),
+            Ok(NumberOrMatrix::Matrix(m.into()))
+        );
+
+        assert_eq!(
+            map2.set(i.clone(), m),
+            Err(MatrixMapError::ReadOnlyName(i.clone()))
+        );
+        assert_eq!(
+            map2.remove(&zero),
+            Err(MatrixMapError::ReadOnlyName(zero.clone()))
+        );
+        assert_eq!(
+            map2.rename(&a, i.clone(), true),
+            Err(MatrixMapError::ReadOnlyName(i.clone()))
+        );
+        assert_eq!(
+            map2.rename(&zero, a.clone(), true),
+            Err(MatrixMapError::ReadOnlyName(zero.clone()))
+        );
+        assert_eq!(map2.names(), vec![a]);
+        map2.clear();
+        assert_eq!(map2.get(&i), Ok(DMat2::IDENTITY));
+
+        // Maps of any dimension have no single identity, but the names are still reserved
+        let mut map_n = MatrixMapN::new();
+        assert_eq!(
+            map_n.get(&i),
+            Err(MatrixMapError::NameNotDefined(i.clone()))
+        );
+        assert_eq!(
+            map_n.set(i.clone(), MatrixValue::TwoD(m)),
+            Err(MatrixMapError::ReadOnlyName(i))
+        );
+    }
+
+    #[test]
+    fn matrix_map_generation() {
+        let mut map = MatrixMap2::new();
+        let other = MatrixMap2::new();
+        assert_ne!(map.generation(), other.generation());
+        assert_eq!(map, other);
+
+        let generation = map.generation();
+        map.set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+        assert_ne!(map.generation(), generation);
+
+        let generation = map.generation();
+        assert_eq!(
+            map.set(MatrixName { name: "a".into() }, DMat2::IDENTITY),
+            Err(MatrixMapError::InvalidName("a".into()))
+        );
+        assert_eq!(map.generation(), generation);
+        assert_eq!(map.clone().generation(), generation);
+
+        let mut tracker = ChangeTracker::new();
+        assert!(tracker.has_changed(&map));
+        map.remove(&MatrixName::new("A")).unwrap();
+        assert!(map.generation() > generation);
+        assert!(tracker.has_changed(&map));
+        map.clear();
+        assert!(!tracker.has_changed(&map));
+    }
+}
@@ -0,0 +1,381 @@
+//! This module provides [`ScalarMap`], which holds the values of scalar variables like `x`, and
+//! [`Environment`], which combines it with a [`MatrixMap`] to evaluate expressions with both.
+
+use super::{next_generation, MatrixMap, MatrixMapError};
+use crate::matrix::{MatrixName, ScalarName};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// An error which can be returned by a method of [`ScalarMap`].
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum ScalarMapError {
+    /// The scalar has an invalid name. See [`ScalarName`].
+    #[error("Invalid name for scalar: \"{0}\"")]
+    InvalidName(smol_str::SmolStr),
+
+    /// The scalar with this name is not defined in the map.
+    #[error("Scalar named \"{0}\" is not defined")]
+    NameNotDefined(ScalarName),
+}
+
+/// A map from names to the values of scalar variables.
+#[derive(Clone, Debug)]
+pub struct ScalarMap {
+    /// The [`HashMap`] backing this map.
+    map: HashMap<ScalarName, f64>,
+
+    /// The generation of this map. See [`MatrixMap::generation`].
+    generation: u64,
+}
+
+impl PartialEq for ScalarMap {
+    fn eq(&self, other: &Self) -> bool {
+        // Maps with the same contents are equal, even if they got there in different ways
+        self.map == other.map
+    }
+}
+
+impl Default for ScalarMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScalarMap {
+    /// Create a new, empty scalar map.
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            generation: next_generation(),
+        }
+    }
+
+    /// Set the value of the scalar with the given name, overwriting any old value.
+    pub fn set(&mut self, name: ScalarName, value: f64) -> Result<(), ScalarMapError> {
+        if name.self_is_valid() {
+            self.map.insert(name, value);
+            self.generation = next_generation();
+            Ok(())
+        } else {
+            Err(ScalarMapError::InvalidName(name.name))
+        }
+    }
+
+    /// Get the named scalar from the map, if it exists.
+    pub fn get(&self, name: &ScalarName) -> Result<f64, ScalarMapError> {
+        if name.self_is_valid() {
+            self.map
+                .get(name)
+                .copied()
+                .ok_or_else(|| ScalarMapError::NameNotDefined(name.to_owned()))
+        } else {
+            Err(ScalarMapError::InvalidName(name.name.clone()))
+        }
+    }
+
+    /// Remove the named scalar from the map, returning its old value.
+    pub fn remove(&mut self, name: &ScalarName) -> Result<f64, ScalarMapError> {
+        if !name.self_is_valid() {
+            return Err(ScalarMapError::InvalidName(name.name.clone()));
+        }
+
+        match self.map.remove(name) {
+            Some(value) => {
+                self.generation = next_generation();
+                Ok(value)
+            }
+            None => Err(ScalarMapError::NameNotDefined(name.to_owned())),
+        }
+    }
+
+    /// Iterate over the names and values of every scalar in the map, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&ScalarName, f64)> {
+        let mut entries: Vec<_> = self
+            .map
+            .iter()
+            .map(|(name, &value)| (name, value))
+            .collect();
+        entries.sort_unstable_by_key(|(name, _)| *name);
+        entries.into_iter()
+    }
+
+    /// Get the number of scalars in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Is the map empty?
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Get the generation of this map, which changes whenever its contents change. See
+    /// [`MatrixMap::generation`].
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+/// Everything that an expression can refer to: the named matrices in a [`MatrixMap`] `M`, and
+/// the scalar variables in a [`ScalarMap`].
+///
+/// This is a [`MatrixMap`] itself, so it can be passed to
+/// [`evaluate`](crate::matrix::expression::ast::AstNode::evaluate) like any other map.
+///
+/// ```
+/// # use trinity::matrix::{expression::{ast::NumberOrMatrix, parse_expression_from_string}, map::prelude::*, MatrixName, ScalarName};
+/// # use glam::DMat2;
+/// let mut environment = Environment::<MatrixMap2>::new();
+/// environment.matrices_mut().set(MatrixName::new("A"), DMat2::IDENTITY).unwrap();
+/// environment.scalars_mut().set(ScalarName::new("t"), 3.).unwrap();
+///
+/// let ast = parse_expression_from_string("2t A").unwrap();
+/// assert_eq!(
+///     ast.evaluate(&environment),
+///     Ok(NumberOrMatrix::Matrix((DMat2::IDENTITY * 6.).into()))
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct Environment<M> {
+    /// The named matrices.
+    matrices: M,
+
+    /// The scalar variables.
+    scalars: ScalarMap,
+
+    /// The generation of this environment, which changes when the matrices or the scalars
+    /// change. See [`MatrixMap::generation`].
+    ///
+    /// This is never the generation of the matrices or the scalars themselves, so the
+    /// environment is never mistaken for just its matrices.
+    generation: u64,
+}
+
+impl<M: PartialEq> PartialEq for Environment<M> {
+    fn eq(&self, other: &Self) -> bool {
+        // Environments with the same contents are equal, even if they got there in different ways
+        self.matrices == other.matrices && self.scalars == other.scalars
+    }
+}
+
+impl<M> Environment<M> {
+    /// Combine these matrices and scalars.
+    pub fn from_parts(matrices: M, scalars: ScalarMap) -> Self {
+        Self {
+            matrices,
+            scalars,
+            generation: next_generation(),
+        }
+    }
+
+    /// Split the environment back into its matrices and scalars.
+    pub fn into_parts(self) -> (M, ScalarMap) {
+        (self.matrices, self.scalars)
+    }
+
+    /// Get the named matrices.
+    pub fn matrices(&self) -> &M {
+        &self.matrices
+    }
+
+    /// Get the named matrices mutably.
+    ///
+    /// The matrices can't be watched once they're borrowed, so this always gives the environment
+    /// a new generation, even if the matrices aren't actually changed.
+    pub fn matrices_mut(&mut self) -> &mut M {
+        self.generation = next_generation();
+        &mut self.matrices
+    }
+
+    /// Get the scalar variables.
+    pub fn scalars(&self) -> &ScalarMap {
+        &self.scalars
+    }
+
+    /// Get the scalar variables mutably.
+    ///
+    /// Like [`matrices_mut`](Self::matrices_mut), this always gives the environment a new
+    /// generation.
+    pub fn scalars_mut(&mut self) -> &mut ScalarMap {
+        self.generation = next_generation();
+        &mut self.scalars
+    }
+}
+
+impl<M: MatrixMap> Environment<M> {
+    /// Apply `change` to the matrices, and give the environment a new generation if that changed
+    /// them.
+    fn change_matrices<T>(&mut self, change: impl FnOnce(&mut M) -> T) -> T {
+        let generation = self.matrices.generation();
+        let result = change(&mut self.matrices);
+        if self.matrices.generation() != generation {
+            self.generation = next_generation();
+        }
+        result
+    }
+}
+
+impl<M: MatrixMap> MatrixMap for Environment<M> {
+    type MatrixType = M::MatrixType;
+
+    fn new() -> Self {
+        Self::from_parts(M::new(), ScalarMap::new())
+    }
+
+    fn set(&mut self, name: MatrixName, value: Self::MatrixType) -> Result<(), MatrixMapError> {
+        self.change_matrices(|matrices| matrices.set(name, value))
+    }
+
+    fn get(&self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        self.matrices.get(name)
+    }
+
+    fn scalar(&self, name: &ScalarName) -> Result<f64, ScalarMapError> {
+        self.scalars.get(name)
+    }
+
+    fn remove(&mut self, name: &MatrixName) -> Result<Self::MatrixType, MatrixMapError> {
+        self.change_matrices(|matrices| matrices.remove(name))
+    }
+
+    fn clear(&mut self) {
+        self.change_matrices(M::clear);
+    }
+
+    fn rename(
+        &mut self,
+        from: &MatrixName,
+        to: MatrixName,
+        overwrite: bool,
+    ) -> Result<(), MatrixMapError> {
+        self.change_matrices(|matrices| matrices.rename(from, to, overwrite))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&MatrixName, &Self::MatrixType)> {
+        self.matrices.iter()
+    }
+
+    fn is_defined(&self, name: &MatrixName) -> bool {
+        self.matrices.is_defined(name)
+    }
+
+    fn len(&self) -> usize {
+        self.matrices.len()
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{
+        expression::{ast::NumberOrMatrix, parse_expression_from_string},
+        map::MatrixMap2,
+    };
+    use glam::DMat2;
+
+    #[test]
+    fn scalar_map_set_get_remove() {
+        let [x, y] = ["x", "y"].map(ScalarName::new);
+        let mut scalars = ScalarMap::new();
+        assert!(scalars.is_empty());
+
+        scalars.set(x.clone(), 2.).unwrap();
+        scalars.set(y.clone(), -1.5).unwrap();
+        assert_eq!(scalars.get(&x), Ok(2.));
+        assert_eq!(
+            scalars.iter().collect::<Vec<_>>(),
+            vec![(&x, 2.), (&y, -1.5)]
+        );
+
+        let generation = scalars.generation();
+        assert_eq!(scalars.remove(&y), Ok(-1.5));
+        assert_ne!(scalars.generation(), generation);
+        assert_eq!(
+            scalars.get(&y),
+            Err(ScalarMapError::NameNotDefined(y.clone()))
+        );
+        assert_eq!(scalars.remove(&y), Err(ScalarMapError::NameNotDefined(y)));
+        assert_eq!(
+            scalars.set(ScalarName { name: "X".into() }, 1.),
+            Err(ScalarMapError::InvalidName("X".into()))
+        );
+        assert_eq!(
+            scalars.get(&ScalarName { name: "i".into() }),
+            Err(ScalarMapError::InvalidName("i".into()))
+        );
+        assert_eq!(scalars.len(), 1);
+    }
+
+    #[test]
+    fn environment_evaluation() {
+        let [a, t] = [MatrixName::new("A"), MatrixName::new("T_")];
+        let mut environment = Environment::<MatrixMap2>::new();
+        environment
+            .matrices_mut()
+            .set(a.clone(), DMat2::from_cols_array(&[1., 2., 3., 4.]))
+            .unwrap();
+        environment
+            .scalars_mut()
+            .set(ScalarName::new("theta"), 90.)
+            .unwrap();
+        environment
+            .scalars_mut()
+            .set(ScalarName::new("k"), 2.)
+            .unwrap();
+
+        let evaluate = |expression: &str, environment: &Environment<MatrixMap2>| {
+            parse_expression_from_string(expression)
+                .unwrap()
+                .evaluate(environment)
+        };
+
+        assert_eq!(
+            evaluate("k^2 + k", &environment),
+            Ok(NumberOrMatrix::Number(6.))
+        );
+        assert_eq!(
+            evaluate("k A", &environment),
+            Ok(NumberOrMatrix::Matrix(
+                DMat2::from_cols_array(&[2., 4., 6., 8.]).into()
+            ))
+        );
+        assert_eq!(
+            evaluate("x A", &environment),
+            Err(ScalarMapError::NameNotDefined(ScalarName::new("x")).into())
+        );
+
+        // Plain matrix maps don't have any scalars
+        assert_eq!(
+            parse_expression_from_string("k")
+                .unwrap()
+                .evaluate(environment.matrices()),
+            Err(ScalarMapError::NameNotDefined(ScalarName::new("k")).into())
+        );
+
+        let generation = environment.generation();
+        environment
+            .scalars_mut()
+            .set(ScalarName::new("k"), 3.)
+            .unwrap();
+        assert!(environment.generation() > generation);
+        let generation = environment.generation();
+        environment.set(t, DMat2::ZERO).unwrap();
+        assert!(environment.generation() > generation);
+        assert_ne!(
+            environment.generation(),
+            environment.matrices().generation()
+        );
+        assert_ne!(environment.generation(), environment.scalars().generation());
+        let generation = environment.generation();
+        assert!(environment.remove(&MatrixName::new("B")).is_err());
+        assert_eq!(environment.generation(), generation);
+
+        let (matrices, scalars) = environment.into_parts();
+        assert_eq!(matrices.len(), 2);
+        assert_eq!(scalars.get(&ScalarName::new("k")), Ok(3.));
+    }
+}
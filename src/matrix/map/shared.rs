@@ -0,0 +1,204 @@
+//! This module provides [`SharedMatrixMap`], a handle to a map which can be read and changed from
+//! many threads at once.
+
+use super::{MatrixMap, MatrixMapError};
+use crate::matrix::MatrixName;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A cheap handle to a map which can be shared between threads, like the render and UI systems of
+/// an app. Cloning the handle shares the same map.
+///
+/// This can't implement [`MatrixMap`] itself, since [`MatrixMap::iter`] borrows from the map and
+/// the borrow would outlive the lock. Instead, [`read`](Self::read) and [`write`](Self::write)
+/// lock the map for as long as their guards live, and the common methods of [`MatrixMap`] are
+/// provided directly, each holding the lock for just that call.
+///
+/// With the `bevy` feature, this is a Bevy `Resource`.
+///
+/// ```
+/// # use trinity::matrix::{expression::{ast::NumberOrMatrix, parse_expression_from_string}, map::{prelude::*, SharedMatrixMap}, MatrixName};
+/// # use glam::DMat2;
+/// let map = SharedMatrixMap::<MatrixMap2>::default();
+/// let handle = map.clone();
+/// std::thread::spawn(move || handle.set(MatrixName::new("A"), DMat2::IDENTITY * 2.))
+///     .join()
+///     .unwrap()
+///     .unwrap();
+///
+/// // Expressions are evaluated against the locked map
+/// let ast = parse_expression_from_string("A * A").unwrap();
+/// assert_eq!(
+///     ast.evaluate(&*map.read()),
+///     Ok(NumberOrMatrix::Matrix((DMat2::IDENTITY * 4.).into()))
+/// );
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct SharedMatrixMap<M> {
+    /// The shared map.
+    map: Arc<RwLock<M>>,
+}
+
+impl<M> Clone for SharedMatrixMap<M> {
+    fn clone(&self) -> Self {
+        Self {
+            map: Arc::clone(&self.map),
+        }
+    }
+}
+
+impl<M: MatrixMap> Default for SharedMatrixMap<M> {
+    fn default() -> Self {
+        Self::new(M::new())
+    }
+}
+
+impl<M> SharedMatrixMap<M> {
+    /// Share this map.
+    pub fn new(map: M) -> Self {
+        Self {
+            map: Arc::new(RwLock::new(map)),
+        }
+    }
+
+    /// Lock the map for reading until the guard is dropped. Any number of threads can read at
+    /// once, but this blocks while another thread is writing.
+    ///
+    /// A thread which panicked while writing doesn't poison the map, since every method of
+    /// [`MatrixMap`] leaves it in a valid state.
+    pub fn read(&self) -> RwLockReadGuard<'_, M> {
+        self.map.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the map for writing until the guard is dropped. This blocks while any other thread
+    /// is reading or writing.
+    pub fn write(&self) -> RwLockWriteGuard<'_, M> {
+        self.map.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<M: MatrixMap> SharedMatrixMap<M> {
+    /// See [`MatrixMap::set`].
+    pub fn set(&self, name: MatrixName, value: M::MatrixType) -> Result<(), MatrixMapError> {
+        self.write().set(name, value)
+    }
+
+    /// See [`MatrixMap::get`].
+    pub fn get(&self, name: &MatrixName) -> Result<M::MatrixType, MatrixMapError> {
+        self.read().get(name)
+    }
+
+    /// See [`MatrixMap::get_or_insert_with`]. The map stays locked between checking for the
+    /// matrix and inserting it, so no other thread can set it in between.
+    pub fn get_or_insert_with(
+        &self,
+        name: MatrixName,
+        default: impl FnOnce() -> M::MatrixType,
+    ) -> Result<M::MatrixType, MatrixMapError> {
+        self.write().get_or_insert_with(name, default)
+    }
+
+    /// See [`MatrixMap::remove`].
+    pub fn remove(&self, name: &MatrixName) -> Result<M::MatrixType, MatrixMapError> {
+        self.write().remove(name)
+    }
+
+    /// See [`MatrixMap::clear`].
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    /// See [`MatrixMap::rename`].
+    pub fn rename(
+        &self,
+        from: &MatrixName,
+        to: MatrixName,
+        overwrite: bool,
+    ) -> Result<(), MatrixMapError> {
+        self.write().rename(from, to, overwrite)
+    }
+
+    /// See [`MatrixMap::names`].
+    pub fn names(&self) -> Vec<MatrixName> {
+        self.read().names()
+    }
+
+    /// See [`MatrixMap::len`].
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// See [`MatrixMap::is_empty`].
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// See [`MatrixMap::generation`].
+    pub fn generation(&self) -> u64 {
+        self.read().generation()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::map::MatrixMap3;
+    use glam::DMat3;
+
+    #[test]
+    fn shared_matrix_map_threads() {
+        let map = SharedMatrixMap::<MatrixMap3>::default();
+        let names = ["A", "B", "C", "D"].map(MatrixName::new);
+
+        let threads: Vec<_> = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let map = map.clone();
+                let name = name.clone();
+                std::thread::spawn(move || {
+                    map.set(name, DMat3::IDENTITY * index as f64).unwrap();
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(map.names(), names.to_vec());
+        assert_eq!(map.get(&names[3]), Ok(DMat3::IDENTITY * 3.));
+
+        let generation = map.generation();
+        map.rename(&names[0], MatrixName::new("E"), false).unwrap();
+        assert_eq!(map.remove(&names[1]), Ok(DMat3::IDENTITY));
+        assert!(map.generation() > generation);
+        assert_eq!(map.read().len(), 3);
+
+        map.write().clear();
+        assert!(map.is_empty());
+
+        // Exactly one thread gets to insert the matrix, and every thread sees its value
+        let f = MatrixName::new("F");
+        let values: Vec<_> = (0..8)
+            .map(|index| {
+                let map = map.clone();
+                let f = f.clone();
+                std::thread::spawn(move || {
+                    map.get_or_insert_with(f, || DMat3::IDENTITY * index as f64)
+                        .unwrap()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect();
+        assert!(values.iter().all(|&value| Ok(value) == map.get(&f)));
+
+        #[cfg(feature = "bevy")]
+        {
+            /// Check at compile time that this is a resource.
+            fn is_resource<R: bevy_ecs::system::Resource>() {}
+            is_resource::<SharedMatrixMap<MatrixMap3>>();
+        }
+    }
+}
@@ -1,17 +1,26 @@
 //! This module handles the internals of the matrices. Storing, handling, parsing, evaluating, etc.
 
+use crate::math::{MatrixPredicates, MatrixProperty};
 use core::fmt;
-use glam::f64::{DMat2, DMat3};
+use glam::f64::{DMat2, DMat3, DVec2, DVec3};
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::ops::Mul;
 
+mod complex;
+mod dynamic;
 pub mod expression;
 pub mod map;
+pub mod preset;
+mod square;
+
+pub use self::{complex::CMatN, dynamic::DMatN};
 
 /// The string used to build [`LEADING_MATRIX_NAME_REGEX`](struct@LEADING_MATRIX_NAME_REGEX) and
 /// [`FULL_MATRIX_NAME_REGEX`](struct@FULL_MATRIX_NAME_REGEX).
-const REGEX_STRING: &str = r"^[A-Z][a-z_]*";
+///
+/// The first character is an uppercase Latin or Greek letter.
+const REGEX_STRING: &str = r"^[A-ZΑ-Ω][a-z0-9_]*";
 
 lazy_static! {
     /// Matches a valid matrix name at the start of the string.
@@ -19,12 +28,21 @@ lazy_static! {
 
     /// Matches a valid matrix name which takes up the whole string.
     pub static ref FULL_MATRIX_NAME_REGEX: Regex = Regex::new(&format!("{REGEX_STRING}$")).unwrap();
+
+    /// Matches something shaped like a scalar name at the start of the string. See
+    /// [`ScalarName`].
+    pub static ref LEADING_SCALAR_NAME_REGEX: Regex = Regex::new(SCALAR_REGEX_STRING).unwrap();
 }
 
+/// The string used to build [`LEADING_SCALAR_NAME_REGEX`](struct@LEADING_SCALAR_NAME_REGEX).
+const SCALAR_REGEX_STRING: &str = r"^[a-z][a-z_]*";
+
 /// The name of a named matrix. Essentially a variable name.
 ///
-/// A matrix name must start with an uppercase letter, and can contain lowercase letters and
-/// underscores.
+/// A matrix name must start with an uppercase Latin or Greek letter, like `A` or `Σ`, and can
+/// then contain lowercase Latin letters, digits, and underscores. Since another uppercase letter
+/// always starts a new name, `AB` is the product of `A` and `B`, but `A2B` is the product of `A2`
+/// and `B`. Write `A 2` to multiply `A` by 2.
 ///
 /// ```
 /// # use trinity::matrix::MatrixName;
@@ -36,6 +54,11 @@ lazy_static! {
 ///     "Dave",
 ///     "N",
 ///     "T",
+///     "A1",
+///     "X_2",
+///     "M12b",
+///     "Σ",
+///     "Δt",
 ///     "Some_really_long_matrix_name_but_its_okay_because_it_fits_the_rules",
 /// ];
 /// for name in valid_names {
@@ -54,12 +77,16 @@ lazy_static! {
 ///     "WhatAboutPunctuation?",
 ///     "It's",
 ///     "X:C",
+///     "1A",
+///     "A1B",
+///     "σ",
+///     "Sσ",
 /// ];
 /// for name in invalid_names {
 ///     assert!(!MatrixName::is_valid(name), "'{name}' should be invalid");
 /// }
 /// ```
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct MatrixName {
     /// The name of the matrix. Should be pre-validated by [`MatrixName::new`].
     name: smol_str::SmolStr,
@@ -71,6 +98,28 @@ impl fmt::Display for MatrixName {
     }
 }
 
+/// Matrix names are serialized as plain strings, and validated when deserialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for MatrixName {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.name.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MatrixName {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = smol_str::SmolStr::deserialize(deserializer)?;
+        if Self::is_valid(&name) {
+            Ok(Self { name })
+        } else {
+            Err(serde::de::Error::custom(format!(
+                "invalid matrix name '{name}'"
+            )))
+        }
+    }
+}
+
 impl MatrixName {
     /// Create a new matrix name.
     ///
@@ -96,51 +145,222 @@ impl MatrixName {
     }
 }
 
-/// A 2D or 3D matrix.
+/// The name of a scalar variable, like `x` or `angle`. See
+/// [`ScalarMap`](map::scalar::ScalarMap).
+///
+/// A scalar name must start with a lowercase letter, and can contain lowercase letters and
+/// underscores. It also can't be read as anything else in an expression, so `i` and function
+/// names like `rot` or `exp` are invalid, but longer names like `rotation` are fine.
+///
+/// ```
+/// # use trinity::matrix::ScalarName;
+/// for name in ["x", "t", "angle", "scale_factor", "e", "rotation", "random", "logx", "inf"] {
+///     assert!(ScalarName::is_valid(name), "'{name}' should be valid");
+/// }
+///
+/// for name in ["", "X", "i", "rot", "x1", "my var", "norm", "exp", "logm", "log"] {
+///     assert!(!ScalarName::is_valid(name), "'{name}' should be invalid");
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScalarName {
+    /// The name of the scalar. Should be pre-validated by [`ScalarName::new`].
+    name: smol_str::SmolStr,
+}
+
+impl fmt::Display for ScalarName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Scalar names are serialized as plain strings, and validated when deserialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ScalarName {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.name.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ScalarName {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = smol_str::SmolStr::deserialize(deserializer)?;
+        if Self::is_valid(&name) {
+            Ok(Self { name })
+        } else {
+            Err(serde::de::Error::custom(format!(
+                "invalid scalar name '{name}'"
+            )))
+        }
+    }
+}
+
+impl ScalarName {
+    /// Create a new scalar name.
+    ///
+    /// In debug builds, this function will panic if the name is invalid (see [`Self::is_valid`]).
+    pub fn new(name: &str) -> Self {
+        debug_assert!(Self::is_valid(name), "ScalarName must be valid");
+        Self { name: name.into() }
+    }
+
+    /// Check if the scalar name is valid. See the [`ScalarName`] docs for valid names.
+    pub fn is_valid(name: &str) -> bool {
+        // The tokeniser decides what's a variable, so a name is valid exactly when it's
+        // tokenised as a single variable
+        matches!(
+            expression::tokenise::tokenise_expression(name).as_deref(),
+            Ok([expression::tokenise::Token::Variable(variable)]) if variable.name == name
+        )
+    }
+
+    /// Check if this scalar name is valid.
+    pub fn self_is_valid(&self) -> bool {
+        Self::is_valid(self.name.as_str())
+    }
+}
+
+/// A square matrix of any dimension.
+///
+/// 2D and 3D matrices are always stored as [`DMat2`] and [`DMat3`], since those are the only ones
+/// that can be visualised and most operations are only defined for them. Every other dimension
+/// is stored as a [`DMatN`], which only supports basic arithmetic. Use [`From<DMatN>`] to get
+/// the right variant.
 #[derive(Clone, Debug, PartialEq)]
-pub enum Matrix2dOr3d {
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum MatrixValue {
     /// A two dimensional matrix.
     TwoD(DMat2),
 
     /// A three dimensional matrix.
     ThreeD(DMat3),
+
+    /// A matrix of any other dimension, usually 4×4 or larger.
+    Dynamic(DMatN),
+}
+
+/// A 2D or 3D matrix which was serialized as a [`DMatN`] is deserialized as the right variant, like
+/// [`From<DMatN>`].
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MatrixValue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// The serialized form of a [`MatrixValue`], whose dynamic matrix might be any dimension.
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "MatrixValue")]
+        enum Serialized {
+            /// A two dimensional matrix.
+            TwoD(DMat2),
+
+            /// A three dimensional matrix.
+            ThreeD(DMat3),
+
+            /// A matrix of any dimension.
+            Dynamic(DMatN),
+        }
+
+        Ok(match Serialized::deserialize(deserializer)? {
+            Serialized::TwoD(matrix) => Self::TwoD(matrix),
+            Serialized::ThreeD(matrix) => Self::ThreeD(matrix),
+            Serialized::Dynamic(matrix) => matrix.into(),
+        })
+    }
 }
 
-impl From<DMat2> for Matrix2dOr3d {
+impl From<DMat2> for MatrixValue {
     fn from(value: DMat2) -> Self {
         Self::TwoD(value)
     }
 }
 
-impl From<DMat3> for Matrix2dOr3d {
+impl From<DMat3> for MatrixValue {
     fn from(value: DMat3) -> Self {
         Self::ThreeD(value)
     }
 }
 
-impl Mul<Matrix2dOr3d> for f64 {
-    type Output = Matrix2dOr3d;
+/// Get the matrix back out if it's 2D, or return the original value.
+impl TryFrom<MatrixValue> for DMat2 {
+    type Error = MatrixValue;
+
+    fn try_from(value: MatrixValue) -> Result<Self, Self::Error> {
+        match value {
+            MatrixValue::TwoD(matrix) => Ok(matrix),
+            value => Err(value),
+        }
+    }
+}
+
+/// Get the matrix back out if it's 3D, or return the original value.
+impl TryFrom<MatrixValue> for DMat3 {
+    type Error = MatrixValue;
+
+    fn try_from(value: MatrixValue) -> Result<Self, Self::Error> {
+        match value {
+            MatrixValue::ThreeD(matrix) => Ok(matrix),
+            value => Err(value),
+        }
+    }
+}
+
+impl From<DMatN> for MatrixValue {
+    fn from(value: DMatN) -> Self {
+        if let Some(matrix) = value.to_dmat2() {
+            Self::TwoD(matrix)
+        } else if let Some(matrix) = value.to_dmat3() {
+            Self::ThreeD(matrix)
+        } else {
+            Self::Dynamic(value)
+        }
+    }
+}
 
-    fn mul(self, rhs: Matrix2dOr3d) -> Self::Output {
+impl Mul<MatrixValue> for f64 {
+    type Output = MatrixValue;
+
+    fn mul(self, rhs: MatrixValue) -> Self::Output {
         match rhs {
-            Matrix2dOr3d::TwoD(matrix) => Matrix2dOr3d::TwoD(self * matrix),
-            Matrix2dOr3d::ThreeD(matrix) => Matrix2dOr3d::ThreeD(self * matrix),
+            MatrixValue::TwoD(matrix) => MatrixValue::TwoD(self * matrix),
+            MatrixValue::ThreeD(matrix) => MatrixValue::ThreeD(self * matrix),
+            MatrixValue::Dynamic(matrix) => MatrixValue::Dynamic(self * matrix),
         }
     }
 }
 
-impl Mul<f64> for Matrix2dOr3d {
-    type Output = Matrix2dOr3d;
+impl Mul<f64> for MatrixValue {
+    type Output = MatrixValue;
 
     fn mul(self, rhs: f64) -> Self::Output {
         match self {
-            Matrix2dOr3d::TwoD(matrix) => Matrix2dOr3d::TwoD(matrix * rhs),
-            Matrix2dOr3d::ThreeD(matrix) => Matrix2dOr3d::ThreeD(matrix * rhs),
+            MatrixValue::TwoD(matrix) => MatrixValue::TwoD(matrix * rhs),
+            MatrixValue::ThreeD(matrix) => MatrixValue::ThreeD(matrix * rhs),
+            MatrixValue::Dynamic(matrix) => MatrixValue::Dynamic(matrix * rhs),
         }
     }
 }
 
-impl Matrix2dOr3d {
+impl MatrixPredicates for MatrixValue {
+    fn has_property_within(
+        &self,
+        property: MatrixProperty,
+        epsilon: f64,
+        max_relative: f64,
+    ) -> bool {
+        match self {
+            MatrixValue::TwoD(matrix) => {
+                matrix.has_property_within(property, epsilon, max_relative)
+            }
+            MatrixValue::ThreeD(matrix) => {
+                matrix.has_property_within(property, epsilon, max_relative)
+            }
+            MatrixValue::Dynamic(matrix) => {
+                matrix.has_property_within(property, epsilon, max_relative)
+            }
+        }
+    }
+}
+
+impl MatrixValue {
     /// Try to multiply two matrices together.
     ///
     /// This method will fail if the two matrices are of different dimensions.
@@ -148,6 +368,7 @@ impl Matrix2dOr3d {
         match (left, right) {
             (Self::TwoD(a), Self::TwoD(b)) => Some(Self::TwoD(a * b)),
             (Self::ThreeD(a), Self::ThreeD(b)) => Some(Self::ThreeD(a * b)),
+            (Self::Dynamic(a), Self::Dynamic(b)) => DMatN::try_mul(&a, &b).map(Self::Dynamic),
             _ => None,
         }
     }
@@ -159,6 +380,158 @@ impl Matrix2dOr3d {
         match (left, right) {
             (Self::TwoD(a), Self::TwoD(b)) => Some(Self::TwoD(a + b)),
             (Self::ThreeD(a), Self::ThreeD(b)) => Some(Self::ThreeD(a + b)),
+            (Self::Dynamic(a), Self::Dynamic(b)) => DMatN::try_add(&a, &b).map(Self::Dynamic),
+            _ => None,
+        }
+    }
+
+    /// The number of rows and columns of this matrix.
+    pub fn dimension(&self) -> usize {
+        match self {
+            Self::TwoD(_) => 2,
+            Self::ThreeD(_) => 3,
+            Self::Dynamic(matrix) => matrix.dimension(),
+        }
+    }
+
+    /// The transpose of this matrix.
+    pub fn transpose(&self) -> Self {
+        match self {
+            Self::TwoD(matrix) => Self::TwoD(matrix.transpose()),
+            Self::ThreeD(matrix) => Self::ThreeD(matrix.transpose()),
+            Self::Dynamic(matrix) => Self::Dynamic(matrix.transpose()),
+        }
+    }
+
+    /// The determinant of this matrix.
+    pub fn determinant(&self) -> f64 {
+        match self {
+            Self::TwoD(matrix) => matrix.determinant(),
+            Self::ThreeD(matrix) => matrix.determinant(),
+            Self::Dynamic(matrix) => matrix.determinant(),
+        }
+    }
+
+    /// Get the entry in the given row and column of this matrix, if it exists. The indices are
+    /// 0-based.
+    pub fn get(&self, row: usize, column: usize) -> Option<f64> {
+        if row >= self.dimension() || column >= self.dimension() {
+            return None;
+        }
+
+        Some(match self {
+            Self::TwoD(matrix) => matrix.col(column)[row],
+            Self::ThreeD(matrix) => matrix.col(column)[row],
+            Self::Dynamic(matrix) => matrix.get(row, column)?,
+        })
+    }
+
+    /// Get the given row of this matrix as a vector, if it exists. The index is 0-based.
+    ///
+    /// Vectors are only 2D or 3D, so this always returns `None` for a [`Self::Dynamic`] matrix.
+    pub fn row(&self, index: usize) -> Option<Vector2dOr3d> {
+        if index >= self.dimension() {
+            return None;
+        }
+
+        match self {
+            Self::TwoD(matrix) => Some(Vector2dOr3d::TwoD(matrix.row(index))),
+            Self::ThreeD(matrix) => Some(Vector2dOr3d::ThreeD(matrix.row(index))),
+            Self::Dynamic(_) => None,
+        }
+    }
+
+    /// Get the given column of this matrix as a vector, if it exists. The index is 0-based.
+    ///
+    /// Vectors are only 2D or 3D, so this always returns `None` for a [`Self::Dynamic`] matrix.
+    pub fn column(&self, index: usize) -> Option<Vector2dOr3d> {
+        if index >= self.dimension() {
+            return None;
+        }
+
+        match self {
+            Self::TwoD(matrix) => Some(Vector2dOr3d::TwoD(matrix.col(index))),
+            Self::ThreeD(matrix) => Some(Vector2dOr3d::ThreeD(matrix.col(index))),
+            Self::Dynamic(_) => None,
+        }
+    }
+
+    /// Try to multiply a vector by this matrix, giving another vector.
+    ///
+    /// This method will fail if the matrix and vector are of different dimensions.
+    pub fn try_mul_vector(matrix: Self, vector: Vector2dOr3d) -> Option<Vector2dOr3d> {
+        match (matrix, vector) {
+            (Self::TwoD(m), Vector2dOr3d::TwoD(v)) => Some(Vector2dOr3d::TwoD(m * v)),
+            (Self::ThreeD(m), Vector2dOr3d::ThreeD(v)) => Some(Vector2dOr3d::ThreeD(m * v)),
+            _ => None,
+        }
+    }
+}
+
+/// A 2D or 3D column vector.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Vector2dOr3d {
+    /// A two dimensional vector.
+    TwoD(DVec2),
+
+    /// A three dimensional vector.
+    ThreeD(DVec3),
+}
+
+impl From<DVec2> for Vector2dOr3d {
+    fn from(value: DVec2) -> Self {
+        Self::TwoD(value)
+    }
+}
+
+impl From<DVec3> for Vector2dOr3d {
+    fn from(value: DVec3) -> Self {
+        Self::ThreeD(value)
+    }
+}
+
+impl Mul<Vector2dOr3d> for f64 {
+    type Output = Vector2dOr3d;
+
+    fn mul(self, rhs: Vector2dOr3d) -> Self::Output {
+        match rhs {
+            Vector2dOr3d::TwoD(vector) => Vector2dOr3d::TwoD(self * vector),
+            Vector2dOr3d::ThreeD(vector) => Vector2dOr3d::ThreeD(self * vector),
+        }
+    }
+}
+
+impl Mul<f64> for Vector2dOr3d {
+    type Output = Vector2dOr3d;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        match self {
+            Vector2dOr3d::TwoD(vector) => Vector2dOr3d::TwoD(vector * rhs),
+            Vector2dOr3d::ThreeD(vector) => Vector2dOr3d::ThreeD(vector * rhs),
+        }
+    }
+}
+
+impl Vector2dOr3d {
+    /// Try to add two vectors together.
+    ///
+    /// This method will fail if the two vectors are of different dimensions.
+    pub fn try_add(left: Self, right: Self) -> Option<Self> {
+        match (left, right) {
+            (Self::TwoD(a), Self::TwoD(b)) => Some(Self::TwoD(a + b)),
+            (Self::ThreeD(a), Self::ThreeD(b)) => Some(Self::ThreeD(a + b)),
+            _ => None,
+        }
+    }
+
+    /// Try to take the dot product of two vectors.
+    ///
+    /// This method will fail if the two vectors are of different dimensions.
+    pub fn try_dot(left: Self, right: Self) -> Option<f64> {
+        match (left, right) {
+            (Self::TwoD(a), Self::TwoD(b)) => Some(a.dot(b)),
+            (Self::ThreeD(a), Self::ThreeD(b)) => Some(a.dot(b)),
             _ => None,
         }
     }
@@ -169,7 +542,7 @@ mod tests {
     use super::*;
     use approx::{AbsDiffEq, RelativeEq};
 
-    impl AbsDiffEq for Matrix2dOr3d {
+    impl AbsDiffEq for MatrixValue {
         type Epsilon = <f64 as AbsDiffEq>::Epsilon;
 
         fn default_epsilon() -> Self::Epsilon {
@@ -180,12 +553,20 @@ mod tests {
             match (self, other) {
                 (Self::TwoD(a), Self::TwoD(b)) => a.abs_diff_eq(*b, epsilon),
                 (Self::ThreeD(a), Self::ThreeD(b)) => a.abs_diff_eq(*b, epsilon),
+                (Self::Dynamic(a), Self::Dynamic(b)) => {
+                    a.dimension() == b.dimension()
+                        && a.to_rows()
+                            .iter()
+                            .flatten()
+                            .zip(b.to_rows().iter().flatten())
+                            .all(|(a, b)| a.abs_diff_eq(b, epsilon))
+                }
                 _ => false,
             }
         }
     }
 
-    impl RelativeEq for Matrix2dOr3d {
+    impl RelativeEq for MatrixValue {
         fn default_max_relative() -> Self::Epsilon {
             <f64 as RelativeEq>::default_max_relative()
         }
@@ -199,11 +580,79 @@ mod tests {
             match (self, other) {
                 (Self::TwoD(a), Self::TwoD(b)) => a.relative_eq(b, epsilon, max_relative),
                 (Self::ThreeD(a), Self::ThreeD(b)) => a.relative_eq(b, epsilon, max_relative),
+                (Self::Dynamic(a), Self::Dynamic(b)) => {
+                    a.dimension() == b.dimension()
+                        && a.to_rows()
+                            .iter()
+                            .flatten()
+                            .zip(b.to_rows().iter().flatten())
+                            .all(|(a, b)| a.relative_eq(b, epsilon, max_relative))
+                }
+                _ => false,
+            }
+        }
+    }
+
+    impl AbsDiffEq for Vector2dOr3d {
+        type Epsilon = <f64 as AbsDiffEq>::Epsilon;
+
+        fn default_epsilon() -> Self::Epsilon {
+            <f64 as AbsDiffEq>::default_epsilon()
+        }
+
+        fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+            match (self, other) {
+                (Self::TwoD(a), Self::TwoD(b)) => a.abs_diff_eq(b, epsilon),
+                (Self::ThreeD(a), Self::ThreeD(b)) => a.abs_diff_eq(b, epsilon),
                 _ => false,
             }
         }
     }
 
+    impl RelativeEq for Vector2dOr3d {
+        fn default_max_relative() -> Self::Epsilon {
+            <f64 as RelativeEq>::default_max_relative()
+        }
+
+        fn relative_eq(
+            &self,
+            other: &Self,
+            epsilon: Self::Epsilon,
+            max_relative: Self::Epsilon,
+        ) -> bool {
+            match (self, other) {
+                (Self::TwoD(a), Self::TwoD(b)) => a.relative_eq(b, epsilon, max_relative),
+                (Self::ThreeD(a), Self::ThreeD(b)) => a.relative_eq(b, epsilon, max_relative),
+                _ => false,
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn matrix_value_serde() {
+        for value in [
+            MatrixValue::TwoD(DMat2::from_cols_array(&[1., 2., 3., 4.])),
+            MatrixValue::ThreeD(DMat3::IDENTITY),
+            MatrixValue::Dynamic(DMatN::identity(4)),
+        ] {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(serde_json::from_str::<MatrixValue>(&json).unwrap(), value);
+        }
+
+        // Small dynamic matrices are stored as the fixed size variants
+        assert_eq!(
+            serde_json::from_str::<MatrixValue>(r#"{"Dynamic":[[1,3],[2,4]]}"#).unwrap(),
+            MatrixValue::TwoD(DMat2::from_cols_array(&[1., 2., 3., 4.]))
+        );
+        assert_eq!(
+            serde_json::from_str::<MatrixValue>(r#"{"Dynamic":[[1,0,0],[0,1,0],[0,0,1]]}"#)
+                .unwrap(),
+            MatrixValue::ThreeD(DMat3::IDENTITY)
+        );
+        assert!(serde_json::from_str::<MatrixValue>(r#"{"Dynamic":[[1,2]]}"#).is_err());
+    }
+
     // Should panic iff we're in a debug build
     #[test]
     #[cfg_attr(debug_assertions, should_panic = "MatrixName must be valid")]
@@ -0,0 +1,110 @@
+//! This module provides [`Preset`]s, which are named expressions for classic transformations like
+//! a rotation by 90°. They're plain data, so lessons can add their own alongside the
+//! [defaults](Preset::defaults).
+
+use super::expression::{ast::AstNode, parse_expression_from_string, TokeniseOrParseError};
+
+/// A classic transformation with a name, like a shear, which is inserted as its expression text
+/// and then evaluated like anything else the user types.
+///
+/// With the `serde` feature, presets can be loaded from a lesson file to extend the defaults.
+///
+/// ```
+/// # use trinity::matrix::{map::prelude::*, preset::Preset, MatrixValue};
+/// # use trinity::matrix::expression::ast::NumberOrMatrix;
+/// # use glam::DMat2;
+/// let shear = Preset::new("Shear", "[1 1; 0 1]");
+/// assert_eq!(
+///     shear.parse().unwrap().evaluate(&MatrixMap2::new()),
+///     Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(DMat2::from_cols_array(&[1., 0., 1., 1.]))))
+/// );
+///
+/// assert!(Preset::defaults().iter().all(|preset| preset.parse().is_ok()));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Preset {
+    /// The name to show for this preset, like `Shear`.
+    pub name: String,
+
+    /// The expression for this preset, like `[1 1; 0 1]`.
+    pub expression: String,
+}
+
+impl Preset {
+    /// Create a new preset.
+    pub fn new(name: impl Into<String>, expression: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            expression: expression.into(),
+        }
+    }
+
+    /// Parse the expression of this preset. See [`parse_expression_from_string`].
+    pub fn parse(&self) -> Result<AstNode, TokeniseOrParseError<'_>> {
+        parse_expression_from_string(&self.expression)
+    }
+
+    /// Get the built-in presets, which are all 2D, in the order they should be shown.
+    pub fn defaults() -> Vec<Self> {
+        [
+            ("Rotate by 90°", "rot(90)"),
+            ("Reflect in y = x", "[0 1; 1 0]"),
+            ("Shear", "[1 1; 0 1]"),
+            ("Project onto the x axis", "proj_line(0)"),
+            ("Scale by ½", "I / 2"),
+            ("Collapse onto a line", "[1 2; 2 4]"),
+        ]
+        .into_iter()
+        .map(|(name, expression)| Self::new(name, expression))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{expression::ast::NumberOrMatrix, map::prelude::*, MatrixValue};
+    use approx::assert_relative_eq;
+    use glam::{DMat2, DVec2};
+
+    #[test]
+    fn default_presets() {
+        let map = MatrixMap2::new();
+        let matrices: Vec<DMat2> = Preset::defaults()
+            .iter()
+            .map(|preset| match preset.parse().unwrap().evaluate(&map) {
+                Ok(NumberOrMatrix::Matrix(MatrixValue::TwoD(matrix))) => matrix,
+                result => panic!("{preset:?} should be a 2D matrix, not {result:?}"),
+            })
+            .collect();
+
+        let [rotate, reflect, shear, project, scale, collapse] = matrices[..] else {
+            panic!("There should be 6 default presets");
+        };
+        assert_relative_eq!(rotate * DVec2::X, DVec2::Y, epsilon = 0.000000001);
+        assert_eq!(reflect * DVec2::new(1., 2.), DVec2::new(2., 1.));
+        assert_eq!(shear * DVec2::Y, DVec2::ONE);
+        assert_relative_eq!(
+            project * DVec2::new(3., 4.),
+            DVec2::new(3., 0.),
+            epsilon = 0.000000001
+        );
+        assert_eq!(scale, DMat2::IDENTITY * 0.5);
+        assert_eq!(collapse.determinant(), 0.);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn presets_from_lesson_file() {
+        let lesson = r#"[{"name": "Squash", "expression": "[1 0; 0 0.5]"}]"#;
+        let mut presets = Preset::defaults();
+        presets.extend(serde_json::from_str::<Vec<Preset>>(lesson).unwrap());
+
+        assert_eq!(presets.last(), Some(&Preset::new("Squash", "[1 0; 0 0.5]")));
+        assert_eq!(
+            serde_json::from_str::<Vec<Preset>>(&serde_json::to_string(&presets).unwrap()).unwrap(),
+            presets
+        );
+    }
+}
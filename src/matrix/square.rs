@@ -0,0 +1,236 @@
+//! This module provides the linear algebra shared by the square matrices of any dimension, like
+//! [`DMatN`](super::DMatN) and [`CMatN`](super::CMatN), which only differ in the type of their
+//! entries. See [`Entry`] and [`SquareMatrix`].
+
+use crate::math::{Complex, Invertible};
+use glam::{DMat2, DMat3};
+use std::{
+    cmp::Ordering,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+/// The entries of a [`SquareMatrix`], with just enough arithmetic for row reduction.
+pub(crate) trait Entry:
+    Clone
+    + PartialEq
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    /// The type of the magnitude of an entry.
+    type Magnitude: PartialOrd;
+
+    /// The additive identity.
+    const ZERO: Self;
+
+    /// The multiplicative identity.
+    const ONE: Self;
+
+    /// The magnitude of this entry, used to choose pivots and decide if they count as zero.
+    fn magnitude(&self) -> Self::Magnitude;
+
+    /// Scale a magnitude by a relative tolerance.
+    fn scale_magnitude(magnitude: Self::Magnitude, epsilon: f64) -> Self::Magnitude;
+}
+
+impl Entry for f64 {
+    type Magnitude = f64;
+
+    const ZERO: Self = 0.;
+    const ONE: Self = 1.;
+
+    fn magnitude(&self) -> f64 {
+        self.abs()
+    }
+
+    fn scale_magnitude(magnitude: f64, epsilon: f64) -> f64 {
+        magnitude * epsilon
+    }
+}
+
+impl Entry for Complex {
+    type Magnitude = f64;
+
+    const ZERO: Self = Complex::ZERO;
+    const ONE: Self = Complex::ONE;
+
+    fn magnitude(&self) -> f64 {
+        self.abs()
+    }
+
+    fn scale_magnitude(magnitude: f64, epsilon: f64) -> f64 {
+        magnitude * epsilon
+    }
+}
+
+/// A square matrix of [`Entry`]s with a non-zero dimension.
+pub(crate) trait SquareMatrix: Sized {
+    /// The type of the entries.
+    type Entry: Entry;
+
+    /// The identity matrix of the given dimension.
+    fn identity(dimension: usize) -> Self;
+
+    /// The number of rows and columns of this matrix.
+    fn dimension(&self) -> usize;
+
+    /// Multiply two matrices together, returning `None` if they have different dimensions.
+    fn try_mul(left: &Self, right: &Self) -> Option<Self>;
+
+    /// The rows of this matrix, from top to bottom.
+    fn to_rows(&self) -> Vec<Vec<Self::Entry>>;
+
+    /// Create a matrix from its rows. There must be as many rows as each row has entries.
+    fn from_square_rows(rows: Vec<Vec<Self::Entry>>) -> Self;
+}
+
+/// Impl [`SquareMatrix`] and [`Invertible`] for glam's fixed size matrices, so that they count as
+/// singular under the same pivot tolerance as matrices of every other dimension. The inverse
+/// itself comes from glam, which is more accurate than elimination for such small matrices.
+macro_rules! impl_square_matrix_glam {
+    ($($t:ty, $n:literal, $array:ty);*) => {
+        $(impl SquareMatrix for $t {
+            type Entry = f64;
+
+            fn identity(_dimension: usize) -> Self {
+                <$t>::IDENTITY
+            }
+
+            fn dimension(&self) -> usize {
+                $n
+            }
+
+            fn try_mul(left: &Self, right: &Self) -> Option<Self> {
+                Some(*left * *right)
+            }
+
+            fn to_rows(&self) -> Vec<Vec<f64>> {
+                self.transpose()
+                    .to_cols_array_2d()
+                    .iter()
+                    .map(|row| row.to_vec())
+                    .collect()
+            }
+
+            fn from_square_rows(rows: Vec<Vec<f64>>) -> Self {
+                let rows: $array = std::array::from_fn(|row| {
+                    std::array::from_fn(|column| rows[row][column])
+                });
+                <$t>::from_cols_array_2d(&rows).transpose()
+            }
+        }
+
+        impl Invertible for $t {
+            fn checked_inverse(&self, epsilon: f64) -> Option<Self> {
+                try_inverse(self, epsilon).map(|_| self.inverse())
+            }
+        })*
+    }
+}
+
+impl_square_matrix_glam!(DMat2, 2, [[f64; 2]; 2]; DMat3, 3, [[f64; 3]; 3]);
+
+/// Raise a matrix to a non-negative integer power, using the square and multiply algorithm like
+/// [`integer_power`](crate::math::integer_power).
+pub(crate) fn powu<M: SquareMatrix>(matrix: &M, power: u64) -> M {
+    let mut result = M::identity(matrix.dimension());
+    for bit_idx in (0..u64::BITS - power.leading_zeros()).rev() {
+        result = M::try_mul(&result, &result).expect("Dimensions should match");
+        if (1 << bit_idx) & power != 0 {
+            result = M::try_mul(&result, matrix).expect("Dimensions should match");
+        }
+    }
+    result
+}
+
+/// Find the row at or below `column` with the largest entry in that column, to use as the pivot.
+fn pivot_row<T: Entry>(rows: &[Vec<T>], column: usize) -> usize {
+    (column..rows.len())
+        .max_by(|&a, &b| {
+            rows[a][column]
+                .magnitude()
+                .partial_cmp(&rows[b][column].magnitude())
+                .unwrap_or(Ordering::Equal)
+        })
+        .expect("The range should be non-empty")
+}
+
+/// The determinant of a matrix, found by Gaussian elimination with partial pivoting.
+pub(crate) fn determinant<M: SquareMatrix>(matrix: &M) -> M::Entry {
+    let n = matrix.dimension();
+    let mut rows = matrix.to_rows();
+    let mut determinant = M::Entry::ONE;
+
+    for column in 0..n {
+        let pivot = pivot_row(&rows, column);
+        if rows[pivot][column] == M::Entry::ZERO {
+            return M::Entry::ZERO;
+        }
+        if pivot != column {
+            rows.swap(pivot, column);
+            determinant = -determinant;
+        }
+
+        let pivot_row = rows[column].clone();
+        determinant = determinant * pivot_row[column].clone();
+        for row in rows.iter_mut().skip(column + 1) {
+            let factor = row[column].clone() / pivot_row[column].clone();
+            for (entry, pivot_entry) in row.iter_mut().zip(&pivot_row).skip(column) {
+                *entry = entry.clone() - factor.clone() * pivot_entry.clone();
+            }
+        }
+    }
+
+    determinant
+}
+
+/// Try to invert a matrix with Gauss-Jordan elimination, returning `None` if it's singular.
+///
+/// A pivot counts as zero if its magnitude is within `epsilon` times the magnitude of the largest
+/// entry of the matrix, so that scaling a matrix never changes whether it's invertible.
+pub(crate) fn try_inverse<M: SquareMatrix>(matrix: &M, epsilon: f64) -> Option<M> {
+    let n = matrix.dimension();
+    let mut rows = matrix.to_rows();
+    let mut inverse = M::identity(n).to_rows();
+
+    let largest = rows.iter().flatten().map(Entry::magnitude).fold(
+        M::Entry::ZERO.magnitude(),
+        |max, magnitude| {
+            if magnitude > max {
+                magnitude
+            } else {
+                max
+            }
+        },
+    );
+    let tolerance = M::Entry::scale_magnitude(largest, epsilon);
+
+    for column in 0..n {
+        let pivot = pivot_row(&rows, column);
+        if rows[pivot][column].magnitude() <= tolerance {
+            return None;
+        }
+        rows.swap(pivot, column);
+        inverse.swap(pivot, column);
+
+        let pivot_value = rows[column][column].clone();
+        for entry in rows[column].iter_mut().chain(&mut inverse[column]) {
+            *entry = entry.clone() / pivot_value.clone();
+        }
+
+        let (pivot_row, pivot_inverse_row) = (rows[column].clone(), inverse[column].clone());
+        for row in (0..n).filter(|&row| row != column) {
+            let factor = rows[row][column].clone();
+            for (entry, pivot_entry) in rows[row].iter_mut().zip(&pivot_row) {
+                *entry = entry.clone() - factor.clone() * pivot_entry.clone();
+            }
+            for (entry, pivot_entry) in inverse[row].iter_mut().zip(&pivot_inverse_row) {
+                *entry = entry.clone() - factor.clone() * pivot_entry.clone();
+            }
+        }
+    }
+
+    Some(M::from_square_rows(inverse))
+}